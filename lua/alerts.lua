@@ -0,0 +1,35 @@
+-- Alert overlay for Proteus LuaCanvas
+-- Shows follower, donation and raid events posted from outside, e.g.:
+--   proteus ctl event follower {"user": "alice"}
+--   curl -d '{"user": "bob", "amount": 5}' http://localhost:47321/api/event/donation
+
+local M = {}
+M.__index = M
+
+local MESSAGES = {
+    follower = function(p) return (p.user or "Someone") .. " followed!" end,
+    donation = function(p) return (p.user or "Someone") .. " donated " .. tostring(p.amount or "") .. "!" end,
+    raid = function(p) return (p.user or "Someone") .. " is raiding with " .. tostring(p.viewers or 0) .. " viewers!" end,
+}
+
+function M.new()
+    return setmetatable({ layer = ui.layer() }, M)
+end
+
+function M:update(dt)
+    for _, event in ipairs(events.poll()) do
+        local message = MESSAGES[event.name]
+        if message then
+            local payload = type(event.payload) == "table" and event.payload or { user = event.payload }
+            self.layer:toast(message(payload), { duration = 5 })
+        end
+    end
+    self.layer:update(dt)
+end
+
+function M:draw()
+    canvas.clear(0, 0, 0, 0)
+    self.layer:draw()
+end
+
+return M
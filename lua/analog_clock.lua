@@ -30,8 +30,8 @@ function M:draw()
     local cy = self.h / 2
     local radius = math.min(self.w, self.h) * 0.35
     
-    -- Get current time
-    local time = os.date("*t")
+    -- Get current time (the wall clock, NTP-synced if configured)
+    local time = os.date("*t", math.floor(proteus.unix_time or os.time()))
     local hours = time.hour % 12
     local minutes = time.min
     local seconds = time.sec
@@ -34,8 +34,9 @@ function M:draw()
     -- Clear to dark background
     canvas.clear(15, 15, 25, 255)
     
-    -- Get current time
-    local time = os.date("*t")
+    -- Get current time (the wall clock, NTP-synced if configured)
+    local now = math.floor(proteus.unix_time or os.time())
+    local time = os.date("*t", now)
     local hours = string.format("%02d", time.hour)
     local minutes = string.format("%02d", time.min)
     local seconds = string.format("%02d", time.sec)
@@ -63,7 +64,7 @@ function M:draw()
     canvas.draw_text(cx, cy, time_str, clock_size, 100, 200, 255, 255)
     
     -- Draw date below
-    local date_str = os.date("%A, %B %d, %Y")
+    local date_str = os.date("%A, %B %d, %Y", now)
     local date_size = 32
     local dw, _ = canvas.measure_text(date_str, date_size)
     canvas.draw_text((self.w - dw) / 2, cy + th + 40, date_str, date_size, 150, 150, 180, 200)
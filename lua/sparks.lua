@@ -0,0 +1,41 @@
+-- Particle demo for Proteus LuaCanvas
+-- A fountain of sparks, with a burst on every beat
+
+local M = {}
+M.__index = M
+
+function M.new()
+    return setmetatable({ t = 0, last_beat = 0 }, M)
+end
+
+function M:init(w, h)
+    self.sparks = particles.new{
+        x = w / 2, y = h - 100, width = 40,
+        rate = 400, max = 4000,
+        lifetime = { 1.0, 2.0 },
+        speed = { 300, 600 },
+        angle = -math.pi / 2, spread = 0.6,
+        gravity = { 0, 500 },
+        drag = 0.3,
+        size = { 10, 2 },
+        colors = { { 255, 240, 180, 255 }, { 255, 120, 20, 200 }, { 120, 20, 0, 0 } },
+    }
+end
+
+function M:update(dt)
+    self.t = self.t + dt
+    -- Sway the fountain
+    self.sparks:set{ angle = -math.pi / 2 + math.sin(self.t) * 0.4 }
+    if proteus.beat and math.floor(proteus.beat) ~= self.last_beat then
+        self.last_beat = math.floor(proteus.beat)
+        self.sparks:emit(300)
+    end
+    self.sparks:update(dt)
+end
+
+function M:draw()
+    canvas.clear(0, 0, 0, 0)
+    self.sparks:draw()
+end
+
+return M
@@ -0,0 +1,50 @@
+-- Widget toolkit demo for Proteus LuaCanvas
+-- A "now playing" panel with a progress bar and a toast every few seconds
+
+local M = {}
+M.__index = M
+
+local TRACKS = { "Intro", "Main Theme", "Interlude", "Finale" }
+
+function M.new()
+    local self = setmetatable({ t = 0, track = 1, next_toast = 5 }, M)
+    self.layer = ui.layer()
+    self.list = ui.list{ x = 24, y = 72, w = 372, items = TRACKS, selected = 1, size = 26 }
+    self.progress = ui.progress{ x = 24, y = 236, w = 372, h = 10, value = 0 }
+    self.layer:add(ui.panel{
+        x = 40, y = 40, w = 420, h = 270,
+        border = { 255, 255, 255, 60 },
+        children = {
+            ui.label{ x = 24, y = 20, text = "Now playing", size = 32, color = { 255, 200, 90, 255 } },
+            self.list,
+            self.progress,
+        },
+    })
+    return self
+end
+
+function M:init(w, h)
+    self.layer:toast("Overlay ready")
+end
+
+function M:update(dt)
+    self.t = self.t + dt
+    -- Each track is 10 seconds long
+    local track = math.floor(self.t / 10) % #TRACKS + 1
+    self.list:set{ selected = track }
+    -- Whole percents, so the panel is only drawn again when the bar moves
+    self.progress:set{ value = math.floor(self.t % 10 * 10) / 100 }
+
+    if self.t >= self.next_toast then
+        self.next_toast = self.next_toast + 10
+        self.layer:toast("Up next: " .. TRACKS[track % #TRACKS + 1])
+    end
+    self.layer:update(dt)
+end
+
+function M:draw()
+    canvas.clear(0, 0, 0, 0)
+    self.layer:draw()
+end
+
+return M
@@ -0,0 +1,36 @@
+#version 450
+
+// Shader: beat_pulse.frag
+// Zooms and brightens the image on every beat, with a stronger kick on the
+// first beat of each bar. Tap 'T' in the preview window to set the tempo.
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_sampler;
+layout(set=0, binding=2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+    float seed;
+    float live;
+    float beat;
+    float bar;
+    float bpm;
+};
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+void main() {
+    // Sharp attack on the beat, exponential decay until the next one
+    float pulse = exp(-beat * 6.0);
+    if (bar < 0.25) {
+        pulse *= 1.5;
+    }
+
+    vec2 center = vec2(0.5);
+    vec2 uv = center + (v_tex_coords - center) / (1.0 + 0.04 * pulse);
+    vec4 color = texture(sampler2D(t_texture, s_sampler), uv);
+    color.rgb *= 1.0 + 0.25 * pulse;
+
+    f_color = color;
+}
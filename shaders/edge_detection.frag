@@ -1,14 +1,11 @@
 #version 450
 
-layout(location = 0) in vec2 tex_coords;
-layout(location = 0) out vec4 frag_color;
+#include <proteus>
 
-layout(set = 0, binding = 0) uniform texture2D t_texture;
-layout(set = 0, binding = 1) uniform sampler s_sampler;
+layout(location = 0) out vec4 frag_color;
 
 void main() {
-    vec2 tex_size = vec2(textureSize(sampler2D(t_texture, s_sampler), 0));
-    vec2 pixel = 1.0 / tex_size;
+    vec2 pixel = TEXEL_SIZE(t_texture);
     
     // Sobel kernels
     // Gx kernel: [-1 0 1; -2 0 2; -1 0 1]
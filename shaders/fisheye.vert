@@ -0,0 +1,29 @@
+#version 450
+
+// Fisheye lens warp, done on the vertices of a grid mesh instead of per pixel.
+// Use it as the `vertex` of a pass; the fragment shader then just samples
+// tex_coords (e.g. shaders/passthrough.frag):
+//
+//   shader:
+//     - path: shaders/passthrough.frag
+//       vertex: shaders/fisheye.vert
+//       mesh: [64, 36]
+//
+// Parameters:
+//   fisheye_strength  Bulge of the lens, 0 - 1 (animate it for a breathing lens)
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float fisheye_strength;
+};
+
+void main() {
+    // Push the grid points outwards, less towards the edges, so the center bulges
+    vec2 aspect = vec2(width / height, 1.0);
+    vec2 centered = (vertex_coords - 0.5) * aspect;
+    float radius = length(centered) / length(0.5 * aspect);
+    float bulge = 1.0 + clamp(fisheye_strength, 0.0, 1.0) * (1.0 - radius * radius);
+    vec2 screen = centered * bulge / aspect + 0.5;
+    place_vertex(screen, vertex_coords);
+}
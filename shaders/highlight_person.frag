@@ -0,0 +1,33 @@
+#version 450
+
+// Shader: highlight_person.frag
+// Keeps one person in full color and dims everyone else and the background.
+// People are numbered left to right (1, 2, ...); pick one with the `person`
+// parameter, e.g. `proteus ctl set-uniform person 2`.
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_sampler;
+layout(set=0, binding=3) uniform texture2D t_mask;
+layout(set=0, binding=8) uniform Params {
+    float person;
+};
+layout(set=0, binding=9) uniform texture2D t_instances;
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(sampler2D(t_texture, s_sampler), v_tex_coords);
+    float mask = smoothstep(0.4, 0.6, texture(sampler2D(t_mask, s_sampler), v_tex_coords).r);
+
+    // IDs must not be filtered: fetch the nearest texel
+    ivec2 size = textureSize(sampler2D(t_instances, s_sampler), 0);
+    ivec2 texel = min(ivec2(v_tex_coords * vec2(size)), size - 1);
+    float id = round(texelFetch(sampler2D(t_instances, s_sampler), texel, 0).r * 255.0);
+
+    float highlighted = mask * float(id == max(person, 1.0));
+    float gray = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+    vec3 dimmed = vec3(gray) * 0.35;
+
+    f_color = vec4(mix(dimmed, color.rgb, highlighted), color.a);
+}
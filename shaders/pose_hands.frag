@@ -0,0 +1,64 @@
+#version 450
+
+// Shader: pose_hands.frag
+// Glowing orbs follow the wrists and a faint skeleton is drawn over the arms.
+// Requires a pose model (`pose_model:` in the config file).
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_sampler;
+layout(set=0, binding=2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+};
+// x, y in texture coordinates, z = confidence (COCO order for the first 17)
+layout(set=0, binding=10) uniform Pose {
+    vec4 keypoints[33];
+};
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+const int LEFT_SHOULDER = 5;
+const int RIGHT_SHOULDER = 6;
+const int LEFT_ELBOW = 7;
+const int RIGHT_ELBOW = 8;
+const int LEFT_WRIST = 9;
+const int RIGHT_WRIST = 10;
+
+// Distance from p to the segment a-b, in pixels
+float segment_distance(vec2 p, vec2 a, vec2 b) {
+    vec2 scale = vec2(width, height);
+    vec2 pa = (p - a) * scale;
+    vec2 ba = (b - a) * scale;
+    float h = clamp(dot(pa, ba) / max(dot(ba, ba), 1e-4), 0.0, 1.0);
+    return length(pa - ba * h);
+}
+
+float bone(vec2 p, int a, int b) {
+    float confidence = min(keypoints[a].z, keypoints[b].z);
+    float d = segment_distance(p, keypoints[a].xy, keypoints[b].xy);
+    return smoothstep(0.3, 0.5, confidence) * (1.0 - smoothstep(1.5, 3.0, d));
+}
+
+vec3 orb(vec2 p, int index, vec3 tint) {
+    vec4 keypoint = keypoints[index];
+    float d = length((p - keypoint.xy) * vec2(width, height));
+    float pulse = 1.0 + 0.2 * sin(time * 6.0 + float(index));
+    return tint * smoothstep(0.3, 0.5, keypoint.z) * exp(-d / (18.0 * pulse));
+}
+
+void main() {
+    vec3 color = texture(sampler2D(t_texture, s_sampler), v_tex_coords).rgb;
+    vec2 p = v_tex_coords;
+
+    float skeleton = max(
+        max(bone(p, LEFT_SHOULDER, LEFT_ELBOW), bone(p, LEFT_ELBOW, LEFT_WRIST)),
+        max(bone(p, RIGHT_SHOULDER, RIGHT_ELBOW), bone(p, RIGHT_ELBOW, RIGHT_WRIST))
+    );
+    color = mix(color, vec3(1.0), skeleton * 0.6);
+    color += orb(p, LEFT_WRIST, vec3(0.2, 0.7, 1.0));
+    color += orb(p, RIGHT_WRIST, vec3(1.0, 0.3, 0.8));
+
+    f_color = vec4(color, 1.0);
+}
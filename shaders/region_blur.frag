@@ -0,0 +1,41 @@
+#version 450
+
+// Shader: region_blur.frag
+// Blurs only the areas covered by the mask regions (`regions` in the config
+// or `proteus.set_regions()` in Lua), e.g. a monitor showing private info.
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_sampler;
+layout(set=0, binding=2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+    float seed;
+};
+layout(set=0, binding=11) uniform texture2D t_regions;
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+const int RADIUS = 6;
+
+void main() {
+    vec4 original = texture(sampler2D(t_texture, s_sampler), v_tex_coords);
+    float region = texture(sampler2D(t_regions, s_sampler), v_tex_coords).r;
+    if (region <= 0.0) {
+        f_color = original;
+        return;
+    }
+
+    // Wide box blur with sparse taps: strong enough to make text unreadable
+    vec2 spread = 4.0 / vec2(width, height);
+    vec4 blurred = vec4(0.0);
+    for (int x = -RADIUS; x <= RADIUS; x++) {
+        for (int y = -RADIUS; y <= RADIUS; y++) {
+            blurred += texture(sampler2D(t_texture, s_sampler), v_tex_coords + vec2(x, y) * spread);
+        }
+    }
+    blurred /= float((2 * RADIUS + 1) * (2 * RADIUS + 1));
+
+    f_color = mix(original, blurred, region);
+}
@@ -0,0 +1,43 @@
+#version 450
+
+// Shader: tally.frag
+// Shows an on-air indicator (red border and dot) while the output is live,
+// and desaturates the image while in preview.
+// Toggle with 'L' in the preview window or `live:` in the config file.
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_sampler;
+layout(set=0, binding=2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+    float seed;
+    float live;
+};
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(sampler2D(t_texture, s_sampler), v_tex_coords);
+    vec2 pixel = v_tex_coords * vec2(width, height);
+
+    if (live > 0.5) {
+        // Red border
+        float border = 6.0;
+        if (pixel.x < border || pixel.y < border || pixel.x > width - border || pixel.y > height - border) {
+            color.rgb = vec3(0.9, 0.05, 0.05);
+        }
+
+        // Pulsing dot in the top-right corner
+        float dist = distance(pixel, vec2(width - 40.0, 40.0));
+        float pulse = 0.75 + 0.25 * sin(time * 4.0);
+        float dot_alpha = 1.0 - smoothstep(14.0, 16.0, dist);
+        color.rgb = mix(color.rgb, vec3(0.95, 0.1, 0.1) * pulse, dot_alpha);
+    } else {
+        float gray = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+        color.rgb = mix(color.rgb, vec3(gray), 0.6);
+    }
+
+    f_color = color;
+}
@@ -0,0 +1,48 @@
+#version 450
+
+// Shader: vu_meter.frag
+// Draws a stereo VU meter at the left edge: bars show the RMS level, a thin
+// line above each bar the peak. Needs an `audio_meter` in the config.
+
+layout(set=0, binding=0) uniform texture2D t_texture;
+layout(set=0, binding=1) uniform sampler s_sampler;
+layout(set=0, binding=2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+    float seed;
+    float live;
+    float beat;
+    float bar;
+    float bpm;
+    vec2 audio_peak;
+    vec2 audio_rms;
+};
+
+layout(location=0) in vec2 v_tex_coords;
+layout(location=0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(sampler2D(t_texture, s_sampler), v_tex_coords);
+
+    // Two bars, 12 pixels wide, from 5% above the bottom to 10% below the top
+    vec2 pixel = v_tex_coords * vec2(width, height);
+    int channel = int((pixel.x - 16.0) / 16.0);
+    bool in_bar = pixel.x >= 16.0 && mod(pixel.x - 16.0, 16.0) < 12.0 && channel < 2;
+    if (in_bar) {
+        float level = (0.95 - v_tex_coords.y) / 0.85;
+        float rms = channel == 0 ? audio_rms.x : audio_rms.y;
+        float peak = channel == 0 ? audio_peak.x : audio_peak.y;
+        // Green to red from bottom to top
+        vec3 meter = mix(vec3(0.1, 0.9, 0.2), vec3(1.0, 0.15, 0.1), smoothstep(0.6, 0.95, level));
+        if (level >= 0.0 && level <= rms) {
+            color.rgb = meter;
+        } else if (level >= 0.0 && abs(level - peak) * height * 0.85 < 1.5) {
+            color.rgb = vec3(1.0);
+        } else if (level >= 0.0 && level <= 1.0) {
+            color.rgb *= 0.4;
+        }
+    }
+
+    f_color = color;
+}
@@ -0,0 +1,183 @@
+//! Audio levels for VU meters.
+//!
+//! The `ffmpeg` command-line tool decodes the microphone or a video's
+//! soundtrack to 32-bit float stereo samples. Each block of samples updates
+//! the peak (which then falls back slowly) and the RMS (smoothed) of both
+//! channels; the pipeline passes them to shaders as uniforms and to Lua
+//! scripts as `proteus.audio`.
+
+use anyhow::{anyhow, Result};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{debug, info, warn};
+
+use crate::video::{resolve_stream, YOUTUBE_AUDIO_FORMAT};
+
+const SAMPLE_RATE: u32 = 48000;
+/// Stereo frames per level update (about 21 ms)
+const BLOCK_FRAMES: usize = 1024;
+/// Seconds for a peak to fall to 1/e of its value
+const PEAK_FALL_TIME: f32 = 0.5;
+/// Seconds the RMS is averaged over
+const RMS_WINDOW: f32 = 0.3;
+
+/// Current levels of the left and right channel (linear, 0.0 - 1.0).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioLevels {
+    pub peak: [f32; 2],
+    pub rms: [f32; 2],
+}
+
+/// What the meter listens to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioSource {
+    /// A microphone (the default input if no device is given)
+    Microphone(Option<String>),
+    /// The soundtrack of a video file or YouTube/Twitch URL, looped like video textures
+    Video(String),
+}
+
+/// Peak and RMS tracking over blocks of interleaved stereo samples.
+#[derive(Debug, Default)]
+struct LevelMeter {
+    levels: AudioLevels,
+    mean_square: [f32; 2],
+}
+
+impl LevelMeter {
+    fn process(&mut self, samples: &[f32]) {
+        let frames = samples.len() / 2;
+        if frames == 0 {
+            return;
+        }
+        let dt = frames as f32 / SAMPLE_RATE as f32;
+        let fall = (-dt / PEAK_FALL_TIME).exp();
+        let smoothing = 1.0 - (-dt / RMS_WINDOW).exp();
+        for channel in 0..2 {
+            let (peak, sum) = samples
+                .iter()
+                .skip(channel)
+                .step_by(2)
+                .fold((0.0f32, 0.0f32), |(peak, sum), &sample| (peak.max(sample.abs()), sum + sample * sample));
+            self.levels.peak[channel] = peak.min(1.0).max(self.levels.peak[channel] * fall);
+            self.mean_square[channel] += (sum / frames as f32 - self.mean_square[channel]) * smoothing;
+            self.levels.rms[channel] = self.mean_square[channel].sqrt().min(1.0);
+        }
+    }
+}
+
+/// ffmpeg input arguments of the default audio API on this platform.
+fn microphone_input(device: Option<&str>) -> Result<Vec<String>> {
+    let (format, input) = if cfg!(target_os = "macos") {
+        ("avfoundation", format!(":{}", device.unwrap_or("0")))
+    } else if cfg!(target_os = "windows") {
+        let device = device.ok_or_else(|| anyhow!("Set the microphone `device` (list them with `ffmpeg -list_devices true -f dshow -i dummy`)"))?;
+        ("dshow", format!("audio={}", device))
+    } else {
+        ("pulse", device.unwrap_or("default").to_string())
+    };
+    Ok(vec!["-f".to_string(), format.to_string(), "-i".to_string(), input])
+}
+
+/// Levels of a running `ffmpeg`. Dropping it stops the process.
+pub struct AudioMeter {
+    source: AudioSource,
+    child: Child,
+    levels: Arc<Mutex<AudioLevels>>,
+}
+
+impl AudioMeter {
+    pub fn start(source: AudioSource) -> Result<Self> {
+        let input = match &source {
+            AudioSource::Microphone(device) => microphone_input(device.as_deref())?,
+            AudioSource::Video(path) => {
+                let path = resolve_stream(path, YOUTUBE_AUDIO_FORMAT)?.map(|url| url.to_string_lossy().into_owned()).unwrap_or_else(|| path.clone());
+                ["-re", "-stream_loop", "-1", "-i"].iter().map(|arg| arg.to_string()).chain([path]).collect()
+            }
+        };
+        let mut child = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(&input)
+            .args(["-vn", "-ac", "2", "-ar", &SAMPLE_RATE.to_string(), "-f", "f32le", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start ffmpeg: {}", e))?;
+
+        let stderr = child.stderr.take().unwrap();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                debug!("ffmpeg (audio): {}", line);
+            }
+        });
+
+        let mut stdout = child.stdout.take().unwrap();
+        let levels = Arc::new(Mutex::new(AudioLevels::default()));
+        let shared = levels.clone();
+        thread::spawn(move || {
+            let mut meter = LevelMeter::default();
+            let mut buffer = vec![0u8; BLOCK_FRAMES * 2 * 4];
+            while stdout.read_exact(&mut buffer).is_ok() {
+                let samples: Vec<f32> = buffer.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect();
+                meter.process(&samples);
+                if let Ok(mut levels) = shared.lock() {
+                    *levels = meter.levels;
+                }
+            }
+            if let Ok(mut levels) = shared.lock() {
+                *levels = AudioLevels::default();
+            }
+            warn!("Audio meter stopped");
+        });
+
+        info!("Audio meter started ({:?})", source);
+        Ok(Self { source, child, levels })
+    }
+
+    pub fn source(&self) -> &AudioSource {
+        &self.source
+    }
+
+    /// The latest levels. Never blocks for long.
+    pub fn levels(&self) -> AudioLevels {
+        self.levels.lock().map(|levels| *levels).unwrap_or_default()
+    }
+}
+
+impl Drop for AudioMeter {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_meter() {
+        let mut meter = LevelMeter::default();
+        // Full-scale square wave on the left, silence on the right
+        let block: Vec<f32> = (0..BLOCK_FRAMES).flat_map(|i| [if i % 2 == 0 { 1.0 } else { -1.0 }, 0.0]).collect();
+        meter.process(&block);
+        assert_eq!(meter.levels.peak, [1.0, 0.0]);
+        assert!(meter.levels.rms[0] > 0.2 && meter.levels.rms[0] < 1.0);
+        for _ in 0..200 {
+            meter.process(&block);
+        }
+        assert!((meter.levels.rms[0] - 1.0).abs() < 1e-3);
+
+        let silence = vec![0.0; BLOCK_FRAMES * 2];
+        meter.process(&silence);
+        assert!(meter.levels.peak[0] > 0.9 && meter.levels.peak[0] < 1.0);
+        for _ in 0..200 {
+            meter.process(&silence);
+        }
+        assert!(meter.levels.peak[0] < 0.01);
+        assert!(meter.levels.rms[0] < 0.01);
+    }
+}
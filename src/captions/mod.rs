@@ -0,0 +1,269 @@
+//! Captions burned into the output.
+//!
+//! Cues come from an SRT file, timed by the shaders' `time` (so they follow
+//! `proteus ctl time seek` and stay in sync with video textures), or live from
+//! the control socket or local speech recognition (see [`speech`]). The text is
+//! laid out and rasterized on the CPU only when it changes; the shader module's
+//! caption pass blends the image onto the final frame.
+
+pub mod speech;
+
+use crate::lua_canvas::shaping::{self, ShapedRun};
+use crate::lua_canvas::FontDatabase;
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{anyhow, Result};
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use std::time::Instant;
+
+/// One subtitle, shown from `start` to `end` seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+}
+
+/// Parses an SRT timestamp (`00:01:02,500`, `.` is accepted as well) into seconds.
+fn parse_timestamp(s: &str) -> Result<f32> {
+    let invalid = || anyhow!("Invalid SRT timestamp '{}'", s);
+    let (clock, millis) = s.trim().split_once([',', '.']).ok_or_else(invalid)?;
+    let parts: Vec<f32> = clock.split(':').map(|part| part.parse::<f32>().map_err(|_| invalid())).collect::<Result<_>>()?;
+    let [hours, minutes, seconds] = parts[..] else { return Err(invalid()) };
+    let millis: f32 = millis.parse().map_err(|_| invalid())?;
+    Ok(hours * 3600.0 + minutes * 60.0 + seconds + millis / 1000.0)
+}
+
+/// Removes formatting tags (`<i>`, `<b>`, `<u>`, `<font color=...>`, `<c.class>`,
+/// `<v speaker>` and their closing tags) from cue text. Any other `<` is text,
+/// e.g. "I <3 you".
+fn strip_tags(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        result.push_str(&rest[..open]);
+        let tag = rest[open + 1..].find('>').map(|close| &rest[open + 1..open + 1 + close]);
+        let name = tag.map(|tag| tag.strip_prefix('/').unwrap_or(tag).split([' ', '\t', '.']).next().unwrap_or_default());
+        match (tag, name) {
+            (Some(tag), Some("i" | "b" | "u" | "font" | "c" | "v")) => rest = &rest[open + tag.len() + 2..],
+            _ => {
+                result.push('<');
+                rest = &rest[open + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Parses the cues of an SRT file, sorted by start time.
+pub fn parse_srt(content: &str) -> Result<Vec<Cue>> {
+    let mut cues = Vec::new();
+    let mut lines = content.trim_start_matches('\u{feff}').lines();
+    while let Some(line) = lines.next() {
+        // Cue numbers (optional in practice) and anything else outside a cue are skipped
+        let Some((start, end)) = line.split_once("-->") else { continue };
+        // Position hints may follow the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        // A blank line ends the cue
+        let text = lines.by_ref().take_while(|line| !line.trim().is_empty()).collect::<Vec<_>>().join("\n");
+        cues.push(Cue { start: parse_timestamp(start)?, end: parse_timestamp(end)?, text: strip_tags(&text) });
+    }
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(cues)
+}
+
+/// Reads the cues of an SRT file.
+pub fn load_srt(path: &Path) -> Result<Vec<Cue>> {
+    let content = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read captions {:?}: {}", path, e))?;
+    parse_srt(&content).map_err(|e| anyhow!("Failed to parse captions {:?}: {}", path, e))
+}
+
+/// The file cues and the latest live caption.
+#[derive(Debug, Clone, Default)]
+pub struct CaptionTrack {
+    cues: Vec<Cue>,
+    live: Option<(String, Instant)>,
+}
+
+impl CaptionTrack {
+    pub fn new(cues: Vec<Cue>) -> Self {
+        Self { cues, live: None }
+    }
+
+    /// Shows `text` live (replacing the file cues) from `now`, or removes the live caption.
+    pub fn set_live(&mut self, text: Option<String>, now: Instant) {
+        self.live = text.map(|text| (text, now));
+    }
+
+    /// Takes over the live caption of `other`.
+    pub fn continue_from(&mut self, other: &CaptionTrack) {
+        self.live = other.live.clone();
+    }
+
+    /// Text shown at shader time `time`: a live caption younger than
+    /// `live_duration` seconds (0 = until it is cleared), otherwise the file cue.
+    pub fn text(&self, time: f32, now: Instant, live_duration: f32) -> Option<&str> {
+        if let Some((text, since)) = &self.live {
+            if live_duration <= 0.0 || now.saturating_duration_since(*since).as_secs_f32() < live_duration {
+                return Some(text);
+            }
+        }
+        self.cues.iter().rev().find(|cue| cue.start <= time && time < cue.end).map(|cue| cue.text.as_str())
+    }
+}
+
+/// Splits `text` into lines no wider than `max_width` (measured by `measure`),
+/// breaking between words. Line breaks in the text are kept; a single word
+/// wider than `max_width` gets a line of its own.
+pub fn wrap_lines(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if !line.is_empty() && measure(&candidate) > max_width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Look of the rendered captions. Sizes are in output pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionStyle {
+    /// Font family (first system font if not found)
+    pub font: Option<String>,
+    pub size: f32,
+    /// sRGB RGBA text color
+    pub color: [u8; 4],
+    /// sRGB RGBA color of the box behind the text
+    pub background: [u8; 4],
+    /// Lines are wrapped at this width
+    pub max_width: f32,
+}
+
+/// Blends `color` with `coverage` (0.0 - 1.0) over the pixel at `x`, `y`.
+fn blend(image: &mut RgbaImage, x: i32, y: i32, color: [u8; 4], coverage: f32) {
+    if x < 0 || y < 0 || x >= image.width() as i32 || y >= image.height() as i32 {
+        return;
+    }
+    let dst = image.get_pixel_mut(x as u32, y as u32);
+    let src_a = color[3] as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    if out_a <= 0.0 {
+        return;
+    }
+    for i in 0..3 {
+        let value = (color[i] as f32 * src_a + dst[i] as f32 * dst_a * (1.0 - src_a)) / out_a;
+        dst[i] = value.round() as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+/// Renders `text` as centered lines on a padded background box.
+/// Returns `None` if the text is empty or no font is available.
+pub fn render_caption(fonts: &FontDatabase, text: &str, style: &CaptionStyle) -> Option<RgbaImage> {
+    let id = style.font.as_deref().and_then(|family| fonts.find_font(family)).or_else(|| fonts.default_font())?;
+    let data = fonts.get_font_data(id)?;
+    let font = FontRef::try_from_slice(&data).ok()?;
+    let scale = PxScale::from(style.size.max(1.0));
+    let font = font.as_scaled(scale);
+    // Shaped positions are in font units
+    let (h_scale, v_scale) = (font.h_scale_factor(), font.v_scale_factor());
+
+    let shape = |line: &str| shaping::shape(&data, line).unwrap_or_default();
+    let measure = |line: &str| shape(line).advance * h_scale;
+    let lines = wrap_lines(text, style.max_width, measure);
+    if lines.is_empty() {
+        return None;
+    }
+
+    let padding = (style.size * 0.35).ceil();
+    let line_height = font.height() + font.line_gap();
+    let runs: Vec<ShapedRun> = lines.iter().map(|line| shape(line)).collect();
+    let text_width = runs.iter().map(|run| run.advance * h_scale).fold(0.0, f32::max);
+    let width = (text_width + 2.0 * padding).ceil() as u32;
+    let height = (line_height * lines.len() as f32 + 2.0 * padding).ceil() as u32;
+    let mut image = RgbaImage::from_pixel(width, height, Rgba(style.background));
+
+    for (i, run) in runs.iter().enumerate() {
+        let x = padding + (text_width - run.advance * h_scale) / 2.0;
+        let baseline = padding + i as f32 * line_height + font.ascent();
+        for glyph in &run.glyphs {
+            let position = point(x + glyph.x * h_scale, baseline - glyph.y * v_scale);
+            if let Some(outlined) = font.outline_glyph(glyph.id.with_scale_and_position(scale, position)) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    blend(&mut image, bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32, style.color, coverage);
+                });
+            }
+        }
+    }
+    Some(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_srt() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello <i>world</i>\r\n\r\n2\r\n00:00:03.000 --> 00:00:04,000 X1:10\r\nTwo\r\nlines\r\n";
+        let cues = parse_srt(srt).unwrap();
+        assert_eq!(
+            cues,
+            vec![
+                Cue { start: 1.0, end: 2.5, text: "Hello world".to_string() },
+                Cue { start: 3.0, end: 4.0, text: "Two\nlines".to_string() },
+            ]
+        );
+        assert!((parse_timestamp("01:02:03,004").unwrap() - 3723.004).abs() < 1e-3);
+        assert!(parse_srt("1\n00:00:01 --> 00:00:02\nNo milliseconds").is_err());
+
+        // A blank line ends the cue, the text after it is not part of any cue
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nFirst\n \nstray\n\n2\n00:00:03,000 --> 00:00:04,000\nSecond\n";
+        let texts: Vec<_> = parse_srt(srt).unwrap().into_iter().map(|cue| cue.text).collect();
+        assert_eq!(texts, vec!["First", "Second"]);
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        assert_eq!(strip_tags("<b>Bold</b> <u>and</u> <font color=\"#ff0000\">red</font>"), "Bold and red");
+        assert_eq!(strip_tags("<v Alice><c.loud>Hi</c></v>"), "Hi");
+        assert_eq!(strip_tags("I <3 you, 1 < 2 > 0"), "I <3 you, 1 < 2 > 0");
+        assert_eq!(strip_tags("<script>x</script> <i"), "<script>x</script> <i");
+    }
+
+    #[test]
+    fn test_track() {
+        let now = Instant::now();
+        let mut track = CaptionTrack::new(vec![Cue { start: 1.0, end: 2.0, text: "cue".to_string() }]);
+        assert_eq!(track.text(0.5, now, 5.0), None);
+        assert_eq!(track.text(1.5, now, 5.0), Some("cue"));
+
+        track.set_live(Some("live".to_string()), now);
+        assert_eq!(track.text(0.5, now + Duration::from_secs(4), 5.0), Some("live"));
+        assert_eq!(track.text(1.5, now + Duration::from_secs(6), 5.0), Some("cue"));
+        assert_eq!(track.text(0.5, now + Duration::from_secs(60), 0.0), Some("live"));
+        track.set_live(None, now);
+        assert_eq!(track.text(0.5, now, 5.0), None);
+    }
+
+    #[test]
+    fn test_wrap_lines() {
+        let measure = |s: &str| s.chars().count() as f32;
+        assert_eq!(wrap_lines("the quick brown fox", 10.0, measure), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap_lines("a\nb  c", 10.0, measure), vec!["a", "b c"]);
+        assert_eq!(wrap_lines("extraordinarily long", 5.0, measure), vec!["extraordinarily", "long"]);
+        assert!(wrap_lines("  ", 10.0, measure).is_empty());
+    }
+}
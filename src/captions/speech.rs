@@ -0,0 +1,222 @@
+//! Live captions from local speech recognition.
+//!
+//! Runs whisper.cpp's `whisper-stream` tool, which captures the microphone and
+//! prints what it recognizes while the speaker talks. Each update of the
+//! current phrase becomes the live caption, so the text grows in place.
+//! Recognition lags behind the speech; `delay` holds the captions back further
+//! where the picture is delayed on its way to the viewers as well.
+
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Speech recognition settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechSettings {
+    /// whisper.cpp stream tool (`whisper-stream`, called `stream` in older releases)
+    pub command: String,
+    /// Whisper model file (ggml)
+    pub model: PathBuf,
+    /// Spoken language (`en`, `de`, ... or `auto`)
+    pub language: String,
+    /// Microphone (SDL capture device index; default device if `None`)
+    pub device: Option<u32>,
+    /// Seconds the captions are held back after they are recognized
+    pub delay: f32,
+    /// Further arguments, e.g. `["--step", "500", "--length", "5000", "-t", "8"]`
+    pub args: Vec<String>,
+}
+
+impl Default for SpeechSettings {
+    fn default() -> Self {
+        Self {
+            command: "whisper-stream".to_string(),
+            model: PathBuf::new(),
+            language: "en".to_string(),
+            device: None,
+            delay: 0.0,
+            args: Vec::new(),
+        }
+    }
+}
+
+/// Removes ANSI escape sequences (`whisper-stream` erases the line before each update).
+fn strip_escapes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI sequences end with a letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Caption text of a `whisper-stream` output line, without timestamps,
+/// non-speech annotations (`[BLANK_AUDIO]`, `(music)`, ...) and status lines.
+pub fn caption_text(line: &str) -> Option<String> {
+    let line = strip_escapes(line);
+    let line = line.trim();
+    if line.starts_with("###") || line.starts_with("init:") || line.starts_with("whisper_") || line.starts_with("main:") {
+        return None;
+    }
+    let mut text = String::with_capacity(line.len());
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Lines of the tool's own log kept to explain why it exited
+const LOG_LINES: usize = 20;
+
+/// Captions recognized by a running `whisper-stream`.
+pub struct SpeechToText {
+    settings: SpeechSettings,
+    child: Child,
+    /// Set when the tool is stopped on purpose, so its exit isn't reported
+    stopping: Arc<AtomicBool>,
+    text_rx: mpsc::Receiver<(String, Instant)>,
+    /// Recognized captions waiting for their delay
+    pending: VecDeque<(String, Instant)>,
+}
+
+impl SpeechToText {
+    /// Starts recognizing the microphone.
+    pub fn start(settings: SpeechSettings) -> Result<Self> {
+        let mut command = Command::new(&settings.command);
+        command.arg("-m").arg(&settings.model).args(["-l", &settings.language]);
+        if let Some(device) = settings.device {
+            command.args(["-c", &device.to_string()]);
+        }
+        let mut child = command
+            .args(&settings.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start {} (part of whisper.cpp): {}", settings.command, e))?;
+        let command_name = settings.command.clone();
+
+        // whisper.cpp logs model loading and timings to stderr; the last lines
+        // are shown if it exits on its own (e.g. a missing model or microphone)
+        let stderr = child.stderr.take().unwrap();
+        let stopping = Arc::new(AtomicBool::new(false));
+        let stderr_stopping = stopping.clone();
+        thread::spawn(move || {
+            let mut log = VecDeque::with_capacity(LOG_LINES);
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                if log.len() == LOG_LINES {
+                    log.pop_front();
+                }
+                log.push_back(line);
+            }
+            if !stderr_stopping.load(Ordering::Relaxed) {
+                warn!("{} exited:\n{}", command_name, log.into_iter().collect::<Vec<_>>().join("\n"));
+            }
+        });
+
+        // Updates of the current phrase start with a carriage return, phrases end
+        // with a newline. An update is complete when the tool flushes it, so
+        // whatever was read last is shown without waiting for the next one.
+        let mut stdout = child.stdout.take().unwrap();
+        let (text_tx, text_rx) = mpsc::channel();
+        let stdout_stopping = stopping.clone();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            let mut line = Vec::new();
+            let mut last = None;
+            while let Ok(n) = stdout.read(&mut buffer) {
+                if n == 0 {
+                    break;
+                }
+                let mut texts = Vec::new();
+                for &byte in &buffer[..n] {
+                    if byte == b'\n' || byte == b'\r' {
+                        texts.push(std::mem::take(&mut line));
+                    } else {
+                        line.push(byte);
+                    }
+                }
+                texts.push(line.clone());
+                for text in texts.iter().filter_map(|text| caption_text(&String::from_utf8_lossy(text))) {
+                    if last.as_ref() == Some(&text) {
+                        continue;
+                    }
+                    last = Some(text.clone());
+                    if text_tx.send((text, Instant::now())).is_err() {
+                        return;
+                    }
+                }
+            }
+            if !stdout_stopping.load(Ordering::Relaxed) {
+                warn!("Speech recognition stopped");
+            }
+        });
+
+        info!("Speech recognition started ({}, language {})", settings.model.display(), settings.language);
+        Ok(Self { settings, child, stopping, text_rx, pending: VecDeque::new() })
+    }
+
+    pub fn settings(&self) -> &SpeechSettings {
+        &self.settings
+    }
+
+    /// Returns the newest caption whose delay has passed at `now`, if any. Never blocks.
+    pub fn poll(&mut self, now: Instant) -> Option<String> {
+        self.pending.extend(self.text_rx.try_iter());
+        let delay = Duration::from_secs_f32(self.settings.delay.max(0.0));
+        let mut latest = None;
+        while let Some((_, recognized)) = self.pending.front() {
+            if *recognized + delay > now {
+                break;
+            }
+            latest = self.pending.pop_front().map(|(text, _)| text);
+        }
+        latest
+    }
+}
+
+impl Drop for SpeechToText {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caption_text() {
+        assert_eq!(caption_text("\u{1b}[2K hello  world"), Some("hello world".to_string()));
+        assert_eq!(caption_text("[00:00:00.000 --> 00:00:02.000]   Good morning."), Some("Good morning.".to_string()));
+        assert_eq!(caption_text(" [BLANK_AUDIO]"), None);
+        assert_eq!(caption_text("(upbeat music) Let's start"), Some("Let's start".to_string()));
+        assert_eq!(caption_text("### Transcription 3 START"), None);
+        assert_eq!(caption_text(""), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Camera connect / disconnect detection.
+//!
+//! Capture backends don't report hotplug events, so a background thread polls
+//! the device list and reports the cameras that appeared or disappeared since
+//! the last poll. The app logs the events, passes them to Lua canvases and
+//! switches to a preferred camera when it is connected.
+
+use super::{CameraInfo, CaptureBackend, NokhwaCapture};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::debug;
+
+/// Interval between device list polls.
+pub const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A camera was connected or disconnected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Connected(CameraInfo),
+    Disconnected(CameraInfo),
+}
+
+impl fmt::Display for DeviceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceEvent::Connected(device) => write!(f, "Camera connected: {}", device),
+            DeviceEvent::Disconnected(device) => write!(f, "Camera disconnected: {}", device),
+        }
+    }
+}
+
+/// Events that turn the device list `old` into `new`.
+pub fn diff_devices(old: &[CameraInfo], new: &[CameraInfo]) -> Vec<DeviceEvent> {
+    let removed = old.iter().filter(|device| !new.contains(device)).cloned().map(DeviceEvent::Disconnected);
+    let added = new.iter().filter(|device| !old.contains(device)).cloned().map(DeviceEvent::Connected);
+    removed.chain(added).collect()
+}
+
+/// The first of `candidates` (device indices or names) that is connected.
+pub fn best_input<'a>(candidates: impl IntoIterator<Item = &'a str>, devices: &[CameraInfo]) -> Option<&'a str> {
+    candidates.into_iter().find(|id| devices.iter().any(|device| device.matches(id)))
+}
+
+/// Polls the connected cameras in a background thread.
+pub struct DeviceMonitor {
+    event_rx: mpsc::Receiver<DeviceEvent>,
+    devices: Arc<Mutex<Vec<CameraInfo>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl DeviceMonitor {
+    /// Starts polling every `interval`. The cameras connected at startup are
+    /// listed without events.
+    pub fn start(interval: Duration) -> Self {
+        let (event_tx, event_rx) = mpsc::channel();
+        let devices = Arc::new(Mutex::new(Vec::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let (devices_clone, running_clone) = (devices.clone(), running.clone());
+
+        std::thread::spawn(move || {
+            let mut known: Option<Vec<CameraInfo>> = None;
+            while running_clone.load(Ordering::Relaxed) {
+                match NokhwaCapture::list_devices() {
+                    Ok(current) => {
+                        // The list is up to date when the events arrive
+                        *devices_clone.lock().unwrap() = current.clone();
+                        if let Some(known) = &known {
+                            for event in diff_devices(known, &current) {
+                                if event_tx.send(event).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        known = Some(current);
+                    }
+                    Err(e) => debug!("Failed to list cameras: {}", e),
+                }
+                std::thread::sleep(interval);
+            }
+        });
+
+        Self { event_rx, devices, running }
+    }
+
+    /// Returns the next camera event, if any. Never blocks.
+    pub fn try_recv(&self) -> Option<DeviceEvent> {
+        self.event_rx.try_recv().ok()
+    }
+
+    /// The cameras connected at the last poll.
+    pub fn devices(&self) -> Vec<CameraInfo> {
+        self.devices.lock().unwrap().clone()
+    }
+}
+
+impl Drop for DeviceMonitor {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_devices() {
+        let camera = |index, name: &str| CameraInfo { index, name: name.to_string() };
+        let old = [camera(0, "FaceTime HD Camera"), camera(1, "USB Camera")];
+        let new = [camera(0, "FaceTime HD Camera"), camera(1, "Logitech BRIO")];
+        assert_eq!(
+            diff_devices(&old, &new),
+            vec![DeviceEvent::Disconnected(camera(1, "USB Camera")), DeviceEvent::Connected(camera(1, "Logitech BRIO"))]
+        );
+        assert!(diff_devices(&new, &new).is_empty());
+
+        assert_eq!(best_input(["Logitech BRIO", "0"], &new), Some("Logitech BRIO"));
+        assert_eq!(best_input(["Logitech BRIO", "0"], &old), Some("0"));
+        assert_eq!(best_input(["2"], &old), None);
+    }
+}
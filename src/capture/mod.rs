@@ -1,15 +1,19 @@
 //! Webcam capture backends.
 
+pub mod hotplug;
+mod network_backend;
 mod nokhwa_backend;
 
+pub use network_backend::{is_network_input, NetworkCapture};
 pub use nokhwa_backend::NokhwaCapture;
 
-use crate::frame::VideoFrame;
+use crate::frame::{Rotation, VideoFrame, YuvMatrix, YuvRange};
 use anyhow::Result;
 use std::sync::mpsc;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tracing::{debug, info};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{debug, error, info};
 
 /// Trait for webcam capture backends.
 pub trait CaptureBackend {
@@ -18,6 +22,11 @@ pub trait CaptureBackend {
     where
         Self: Sized;
 
+    /// Returns the streaming modes a camera supports.
+    fn list_modes(device_id: &str) -> Result<Vec<CameraMode>>
+    where
+        Self: Sized;
+
     /// Opens the camera with the specified configuration.
     fn open(config: CaptureConfig) -> Result<Self>
     where
@@ -31,7 +40,7 @@ pub trait CaptureBackend {
 }
 
 /// Information about a camera device.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CameraInfo {
     /// Device index
     pub index: u32,
@@ -39,6 +48,50 @@ pub struct CameraInfo {
     pub name: String,
 }
 
+impl CameraInfo {
+    /// Whether a configured device ID (index or name) refers to this camera.
+    pub fn matches(&self, device_id: &str) -> bool {
+        match device_id.parse::<u32>() {
+            Ok(index) => index == self.index,
+            Err(_) => device_id == self.name,
+        }
+    }
+}
+
+impl std::fmt::Display for CameraInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.index, self.name)
+    }
+}
+
+/// Pixel format a camera streams in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraPixelFormat {
+    Mjpeg,
+    Yuyv,
+    Nv12,
+}
+
+/// A camera streaming mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CameraMode {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub format: CameraPixelFormat,
+}
+
+impl std::fmt::Display for CameraMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let format = match self.format {
+            CameraPixelFormat::Mjpeg => "mjpeg",
+            CameraPixelFormat::Yuyv => "yuyv",
+            CameraPixelFormat::Nv12 => "nv12",
+        };
+        write!(f, "{}x{} @ {} fps, {}", self.width, self.height, self.fps, format)
+    }
+}
+
 /// Configuration for camera capture.
 #[derive(Debug, Clone)]
 pub struct CaptureConfig {
@@ -54,6 +107,14 @@ pub struct CaptureConfig {
     pub max_input_height: u32,
     /// Desired frame rate
     pub fps: u32,
+    /// YUV matrix of uncompressed camera formats (None = reported by the driver, else BT.601 for SD, BT.709 for HD)
+    pub matrix: Option<YuvMatrix>,
+    /// YUV range of uncompressed camera formats (None = reported by the driver, else limited)
+    pub range: Option<YuvRange>,
+    /// Streaming mode to use instead of picking the best supported one
+    pub mode: Option<CameraMode>,
+    /// Orientation hint: the rotation that turns the camera's frames upright
+    pub rotation: Rotation,
 }
 
 impl Default for CaptureConfig {
@@ -65,10 +126,24 @@ impl Default for CaptureConfig {
             max_input_width: 1920,
             max_input_height: 1080,
             fps: 30,
+            matrix: None,
+            range: None,
+            mode: None,
+            rotation: Rotation::None,
         }
     }
 }
 
+/// Opens the backend for an input: a network listener for `tcp://` and `ws://`
+/// addresses, a camera otherwise.
+fn open_backend(config: CaptureConfig) -> Result<Box<dyn CaptureBackend>> {
+    if is_network_input(&config.device_id) {
+        Ok(Box::new(NetworkCapture::open(config)?))
+    } else {
+        Ok(Box::new(NokhwaCapture::open(config)?))
+    }
+}
+
 /// Async capture wrapper that runs camera capture in a background thread.
 /// This decouples frame acquisition from the render loop to improve FPS.
 /// 
@@ -80,6 +155,11 @@ pub struct AsyncCapture {
     width: u32,
     height: u32,
     running: Arc<AtomicBool>,
+    /// Frames received since the last `take_counts`
+    received: u64,
+    /// Frames dropped since the last `take_counts`, by the capture thread or
+    /// because a newer frame arrived before the render loop asked
+    dropped: Arc<AtomicU64>,
 }
 
 impl AsyncCapture {
@@ -94,10 +174,12 @@ impl AsyncCapture {
         
         let running = Arc::new(AtomicBool::new(true));
         let running_clone = running.clone();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let dropped_clone = dropped.clone();
         
         std::thread::spawn(move || {
             // Create camera inside the thread
-            let mut capture = match NokhwaCapture::open(config) {
+            let mut capture = match open_backend(config) {
                 Ok(c) => c,
                 Err(e) => {
                     let _ = setup_tx.send(Err(e));
@@ -111,17 +193,17 @@ impl AsyncCapture {
             }
             
             info!("Camera capture thread started");
+            let started = std::time::Instant::now();
             while running_clone.load(Ordering::Relaxed) {
-                let capture_start = std::time::Instant::now();
                 match capture.capture_frame() {
-                    Ok(frame) => {
-                        let capture_elapsed = capture_start.elapsed();
-                        debug!("[Perf] Camera capture_frame: {:?}", capture_elapsed);
+                    Ok(mut frame) => {
+                        // Arrival time, used to tell new frames apart and pace interpolation
+                        frame.timestamp_us.get_or_insert(started.elapsed().as_micros() as u64);
                         // Use try_send to drop frames if the receiver is slow
                         match frame_tx.try_send(frame) {
                             Ok(_) => {},
                             Err(mpsc::TrySendError::Full(_)) => {
-                                debug!("Render loop slow, dropping camera frame to maintain real-time");
+                                dropped_clone.fetch_add(1, Ordering::Relaxed);
                             },
                             Err(mpsc::TrySendError::Disconnected(_)) => {
                                 info!("Camera capture thread: receiver disconnected, exiting");
@@ -147,6 +229,8 @@ impl AsyncCapture {
             width,
             height,
             running,
+            received: 0,
+            dropped,
         })
     }
     
@@ -154,11 +238,22 @@ impl AsyncCapture {
     /// This never blocks - it returns immediately with whatever is available.
     pub fn get_latest_frame(&mut self) -> Option<&VideoFrame> {
         // Drain all available frames and keep the latest
+        let mut received = 0;
         while let Ok(frame) = self.frame_rx.try_recv() {
             self.latest_frame = Some(frame);
+            received += 1;
+        }
+        if received > 1 {
+            self.dropped.fetch_add(received - 1, Ordering::Relaxed);
         }
+        self.received += received.min(1);
         self.latest_frame.as_ref()
     }
+
+    /// Returns the frames received and dropped since the last call.
+    pub fn take_counts(&mut self) -> (u64, u64) {
+        (std::mem::take(&mut self.received), self.dropped.swap(0, Ordering::Relaxed))
+    }
     
     /// Returns the frame dimensions.
     pub fn frame_size(&self) -> (u32, u32) {
@@ -171,3 +266,63 @@ impl Drop for AsyncCapture {
         self.running.store(false, Ordering::Relaxed);
     }
 }
+
+/// Keeps trying to open a camera in a background thread until it succeeds.
+/// Used to pick up a camera that was unavailable at startup.
+pub struct CaptureRetry {
+    capture_rx: mpsc::Receiver<AsyncCapture>,
+    running: Arc<AtomicBool>,
+}
+
+impl CaptureRetry {
+    /// Starts retrying to open the camera every `interval`.
+    pub fn spawn(config: CaptureConfig, interval: Duration) -> Self {
+        Self::start(config, interval, interval)
+    }
+
+    /// Opens the camera in the background right away, retrying every `interval`
+    /// if it is unavailable.
+    pub fn open(config: CaptureConfig, interval: Duration) -> Self {
+        Self::start(config, Duration::ZERO, interval)
+    }
+
+    fn start(config: CaptureConfig, first_delay: Duration, interval: Duration) -> Self {
+        let (capture_tx, capture_rx) = mpsc::channel::<AsyncCapture>();
+        let running = Arc::new(AtomicBool::new(true));
+        let running_clone = running.clone();
+
+        std::thread::spawn(move || {
+            let mut delay = first_delay;
+            let mut first_attempt = true;
+            while running_clone.load(Ordering::Relaxed) {
+                std::thread::sleep(delay);
+                delay = interval;
+                match AsyncCapture::new(config.clone()) {
+                    Ok(capture) => {
+                        info!("Camera device {} is now available", config.device_id);
+                        let _ = capture_tx.send(capture);
+                        return;
+                    }
+                    Err(e) if first_attempt && first_delay.is_zero() => {
+                        error!("Failed to open camera device {}: {}, retrying in the background", config.device_id, e);
+                    }
+                    Err(e) => debug!("Camera device {} still unavailable: {}", config.device_id, e),
+                }
+                first_attempt = false;
+            }
+        });
+
+        Self { capture_rx, running }
+    }
+
+    /// Returns the camera once it has been opened. Never blocks.
+    pub fn try_take(&mut self) -> Option<AsyncCapture> {
+        self.capture_rx.try_recv().ok()
+    }
+}
+
+impl Drop for CaptureRetry {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,343 @@
+//! Frames sent over the network by another machine.
+//!
+//! A sender connects to `tcp://<address>:<port>` or `ws://<address>:<port>`
+//! (the configured input) and sends frames, each a 16-byte header followed by
+//! the pixel data. Over WebSocket, every binary message is one frame.
+//!
+//! ```text
+//! format  4 bytes  "RGBA", "NV12" or "JPEG"
+//! width   u32      little-endian
+//! height  u32      little-endian
+//! length  u32      little-endian, bytes of pixel data that follow
+//! ```
+//!
+//! One sender is read at a time; when it disconnects, the next one is accepted.
+//! Frames larger than the configured input size (`max_input_width` x
+//! `max_input_height`) are refused before their data is read.
+
+use super::{CameraInfo, CameraMode, CaptureBackend, CaptureConfig};
+use crate::frame::{PixelFormat, Rotation, VideoFrame, YuvColorSpace};
+use anyhow::{anyhow, Result};
+use std::io::{Cursor, Read};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::Message;
+use url::Url;
+
+pub const HEADER_SIZE: usize = 16;
+/// Largest accepted frame, in pixels per side
+const MAX_DIMENSION: u32 = 8192;
+/// How often the server thread checks for new senders and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long `capture_frame` waits before reporting that no frame arrived
+const FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Transport of a network input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Tcp,
+    WebSocket,
+}
+
+/// Parses a network input (`tcp://host:port` or `ws://host:port`). Returns `None` for cameras.
+fn parse_input(device_id: &str) -> Option<(Protocol, String)> {
+    let url = Url::parse(device_id).ok()?;
+    let protocol = match url.scheme() {
+        "tcp" => Protocol::Tcp,
+        "ws" => Protocol::WebSocket,
+        _ => return None,
+    };
+    Some((protocol, format!("{}:{}", url.host_str()?, url.port()?)))
+}
+
+/// Whether an input names a network listener rather than a camera.
+pub fn is_network_input(device_id: &str) -> bool {
+    parse_input(device_id).is_some()
+}
+
+/// Frame header: pixel format, width, height and length of the data that follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameHeader {
+    format: [u8; 4],
+    width: u32,
+    height: u32,
+    length: u32,
+}
+
+impl FrameHeader {
+    /// Parses a header of a frame of at most `max_width` x `max_height` pixels.
+    fn parse(bytes: &[u8], (max_width, max_height): (u32, u32)) -> Result<Self> {
+        let bytes: &[u8; HEADER_SIZE] = bytes.get(..HEADER_SIZE).and_then(|b| b.try_into().ok()).ok_or_else(|| anyhow!("Frame header too short"))?;
+        let word = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let header = Self { format: [bytes[0], bytes[1], bytes[2], bytes[3]], width: word(4), height: word(8), length: word(12) };
+        if header.width == 0 || header.height == 0 || header.width > MAX_DIMENSION || header.height > MAX_DIMENSION {
+            return Err(anyhow!("Invalid frame size {}x{}", header.width, header.height));
+        }
+        if header.width > max_width || header.height > max_height {
+            return Err(anyhow!("Frame of {}x{} is larger than the input size {}x{}", header.width, header.height, max_width, max_height));
+        }
+        // A JPEG is never larger than an uncompressed RGBA frame of the same size
+        if header.length as u64 > header.width as u64 * header.height as u64 * 4 {
+            return Err(anyhow!("Frame data too long ({} bytes for {}x{})", header.length, header.width, header.height));
+        }
+        Ok(header)
+    }
+
+    /// Decodes the frame's pixel data.
+    fn decode(&self, data: Vec<u8>, color: Option<YuvColorSpace>) -> Result<VideoFrame> {
+        let (width, height) = (self.width, self.height);
+        let pixels = width as usize * height as usize;
+        let frame = match &self.format {
+            b"RGBA" if data.len() == pixels * 4 => VideoFrame::from_data(width, height, PixelFormat::Rgba, data),
+            b"NV12" if width % 2 == 0 && height % 2 == 0 && data.len() == pixels + pixels / 2 => {
+                let color = color.unwrap_or_else(|| YuvColorSpace::for_resolution(width, height));
+                VideoFrame::from_data(width, height, PixelFormat::Nv12, data).with_color(color)
+            }
+            b"JPEG" => {
+                // The header's size bounds what the image may decode to
+                let mut limits = image::Limits::default();
+                limits.max_image_width = Some(width);
+                limits.max_image_height = Some(height);
+                limits.max_alloc = Some(pixels as u64 * 4 * 2);
+                let mut reader = image::ImageReader::with_format(Cursor::new(&data), image::ImageFormat::Jpeg);
+                reader.limits(limits);
+                let rgba = reader.decode()?.to_rgba8();
+                if rgba.dimensions() != (width, height) {
+                    return Err(anyhow!("JPEG of {}x{} doesn't match the header's {}x{}", rgba.width(), rgba.height(), width, height));
+                }
+                VideoFrame::from_data(width, height, PixelFormat::Rgba, rgba.into_raw())
+            }
+            b"RGBA" | b"NV12" => return Err(anyhow!("{} bytes don't match a {}x{} {} frame", data.len(), width, height, String::from_utf8_lossy(&self.format))),
+            format => return Err(anyhow!("Unsupported frame format {:?}", String::from_utf8_lossy(format))),
+        };
+        Ok(frame)
+    }
+}
+
+/// Frames received from a remote sender.
+pub struct NetworkCapture {
+    frame_rx: mpsc::Receiver<VideoFrame>,
+    width: u32,
+    height: u32,
+    rotation: Rotation,
+    stop: Arc<AtomicBool>,
+}
+
+impl CaptureBackend for NetworkCapture {
+    fn list_devices() -> Result<Vec<CameraInfo>> {
+        Ok(Vec::new())
+    }
+
+    fn list_modes(_device_id: &str) -> Result<Vec<CameraMode>> {
+        Err(anyhow!("Network inputs have no streaming modes; the sender picks the format"))
+    }
+
+    fn open(config: CaptureConfig) -> Result<Self> {
+        let (protocol, address) = parse_input(&config.device_id).ok_or_else(|| anyhow!("Not a network input: {}", config.device_id))?;
+        let listener = TcpListener::bind(&address).map_err(|e| anyhow!("Network input {} unavailable: {}", address, e))?;
+        listener.set_nonblocking(true)?;
+
+        // NV12 frames use the configured color space, or the usual one for their size
+        let color = match (config.matrix, config.range) {
+            (None, None) => None,
+            (matrix, range) => {
+                let auto = YuvColorSpace::for_resolution(config.width, config.height);
+                Some(YuvColorSpace { matrix: matrix.unwrap_or(auto.matrix), range: range.unwrap_or(auto.range) })
+            }
+        };
+
+        let max_size = (config.max_input_width.max(config.width), config.max_input_height.max(config.height));
+
+        // Waiting senders are held back until the render loop takes a frame
+        let (frame_tx, frame_rx) = mpsc::sync_channel(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        thread::spawn(move || {
+            while !server_stop.load(Ordering::Acquire) {
+                let stream = match listener.accept() {
+                    Ok((stream, address)) => {
+                        info!("Network input: sender connected from {}", address);
+                        stream
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Network input: connection failed: {}", e);
+                        continue;
+                    }
+                };
+                let result = match protocol {
+                    Protocol::Tcp => receive_tcp(stream, &frame_tx, color, max_size, &server_stop),
+                    Protocol::WebSocket => receive_websocket(stream, &frame_tx, color, max_size, &server_stop),
+                };
+                match result {
+                    Ok(()) => info!("Network input: sender disconnected"),
+                    Err(e) => warn!("Network input: sender disconnected: {}", e),
+                }
+            }
+        });
+
+        info!("Network input listening on {}", config.device_id);
+        Ok(Self { frame_rx, width: config.width, height: config.height, rotation: config.rotation, stop })
+    }
+
+    fn capture_frame(&mut self) -> Result<VideoFrame> {
+        let frame = self.frame_rx.recv_timeout(FRAME_TIMEOUT).map_err(|_| anyhow!("No frame from the network sender"))?;
+        (self.width, self.height) = (frame.width, frame.height);
+        Ok(frame.with_rotation(self.rotation))
+    }
+
+    fn frame_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+impl Drop for NetworkCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Reads exactly `buffer.len()` bytes, giving up when `stop` is set. Returns false at the end of the stream.
+fn read_full(stream: &mut TcpStream, buffer: &mut [u8], stop: &AtomicBool) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        if stop.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+        match stream.read(&mut buffer[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(anyhow!("Stream ended within a frame")),
+            Ok(n) => filled += n,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut | std::io::ErrorKind::Interrupted) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads `length` bytes of frame data, growing the buffer as the data
+/// arrives rather than trusting the length up front. Gives up when `stop` is set.
+fn read_data(stream: &mut TcpStream, length: usize, stop: &AtomicBool) -> Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    while data.len() < length {
+        if stop.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        // Bytes read before a timeout are kept in `data`
+        match (&mut *stream).take((length - data.len()) as u64).read_to_end(&mut data) {
+            Ok(0) => return Err(anyhow!("Stream ended within a frame")),
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut | std::io::ErrorKind::Interrupted) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(Some(data))
+}
+
+fn receive_tcp(mut stream: TcpStream, frame_tx: &mpsc::SyncSender<VideoFrame>, color: Option<YuvColorSpace>, max_size: (u32, u32), stop: &AtomicBool) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    // Reads time out, so a silent sender doesn't delay shutdown
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut header = [0u8; HEADER_SIZE];
+    loop {
+        if !read_full(&mut stream, &mut header, stop)? {
+            return Ok(());
+        }
+        let header = FrameHeader::parse(&header, max_size)?;
+        let Some(data) = read_data(&mut stream, header.length as usize, stop)? else {
+            return Ok(());
+        };
+        match header.decode(data, color) {
+            Ok(frame) => frame_tx.send(frame).map_err(|_| anyhow!("Capture closed"))?,
+            Err(e) => debug!("Network input: frame skipped: {}", e),
+        }
+    }
+}
+
+fn receive_websocket(stream: TcpStream, frame_tx: &mpsc::SyncSender<VideoFrame>, color: Option<YuvColorSpace>, max_size: (u32, u32), stop: &AtomicBool) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    // Messages are buffered whole, so they're limited to the largest frame
+    let max_message = HEADER_SIZE + max_size.0 as usize * max_size.1 as usize * 4;
+    let config = WebSocketConfig::default().max_message_size(Some(max_message)).max_frame_size(Some(max_message));
+    let mut socket = tungstenite::accept_with_config(stream, Some(config)).map_err(|e| anyhow!("Handshake failed: {}", e))?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    loop {
+        if stop.load(Ordering::Acquire) {
+            let _ = socket.close(None);
+            return Ok(());
+        }
+        let data = match socket.read() {
+            Ok(Message::Binary(data)) => data,
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let frame = FrameHeader::parse(&data, max_size).and_then(|header| {
+            let payload = &data[HEADER_SIZE..];
+            if payload.len() != header.length as usize {
+                return Err(anyhow!("Message has {} bytes of frame data, the header says {}", payload.len(), header.length));
+            }
+            header.decode(payload.to_vec(), color)
+        });
+        match frame {
+            Ok(frame) => frame_tx.send(frame).map_err(|_| anyhow!("Capture closed"))?,
+            Err(e) => debug!("Network input: frame skipped: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(format: &[u8; 4], width: u32, height: u32, length: u32) -> Vec<u8> {
+        [format.as_slice(), &width.to_le_bytes(), &height.to_le_bytes(), &length.to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(parse_input("tcp://0.0.0.0:9000"), Some((Protocol::Tcp, "0.0.0.0:9000".to_string())));
+        assert_eq!(parse_input("ws://127.0.0.1:9001"), Some((Protocol::WebSocket, "127.0.0.1:9001".to_string())));
+        assert_eq!(parse_input("tcp://0.0.0.0"), None);
+        assert_eq!(parse_input("0"), None);
+        assert_eq!(parse_input("FaceTime HD Camera"), None);
+    }
+
+    const MAX_SIZE: (u32, u32) = (1920, 1080);
+
+    #[test]
+    fn test_frames() {
+        let rgba = FrameHeader::parse(&header(b"RGBA", 2, 2, 16), MAX_SIZE).unwrap();
+        let frame = rgba.decode(vec![255; 16], None).unwrap();
+        assert_eq!((frame.width, frame.height, frame.format), (2, 2, PixelFormat::Rgba));
+        assert!(rgba.decode(vec![255; 12], None).is_err());
+
+        let nv12 = FrameHeader::parse(&header(b"NV12", 4, 2, 12), MAX_SIZE).unwrap();
+        assert_eq!(nv12.decode(vec![128; 12], None).unwrap().format, PixelFormat::Nv12);
+
+        assert!(FrameHeader::parse(&header(b"RGBA", 0, 2, 0), MAX_SIZE).is_err());
+        assert!(FrameHeader::parse(&header(b"RGBA", 2, 2, 17), MAX_SIZE).is_err());
+        assert!(FrameHeader::parse(&header(b"RGBA", 2, 2, 16)[..12], MAX_SIZE).is_err());
+        assert!(FrameHeader::parse(&header(b"RGBA", 3840, 2160, 16), MAX_SIZE).is_err());
+        let h264 = FrameHeader::parse(&header(b"H264", 2, 2, 4), MAX_SIZE).unwrap();
+        assert!(h264.decode(vec![0; 4], None).is_err());
+
+        // A JPEG must decode to the size in its header
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(64, 32).write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg).unwrap();
+        let matching = FrameHeader::parse(&header(b"JPEG", 64, 32, jpeg.len() as u32), MAX_SIZE).unwrap();
+        assert_eq!(matching.decode(jpeg.clone(), None).unwrap().width, 64);
+        let smaller = FrameHeader::parse(&header(b"JPEG", 32, 32, jpeg.len() as u32), MAX_SIZE).unwrap();
+        assert!(smaller.decode(jpeg, None).is_err());
+    }
+}
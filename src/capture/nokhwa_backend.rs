@@ -1,8 +1,8 @@
 //! Nokhwa-based webcam capture backend.
 
-use super::{CameraInfo, CaptureBackend, CaptureConfig};
-use crate::frame::{PixelFormat, VideoFrame};
-use anyhow::Result;
+use super::{CameraInfo, CameraMode, CameraPixelFormat, CaptureBackend, CaptureConfig};
+use crate::frame::{PixelFormat, Rotation, VideoFrame, YuvColorSpace, YuvMatrix, YuvRange};
+use anyhow::{anyhow, Result};
 use nokhwa::pixel_format::RgbFormat;
 use nokhwa::utils::{CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType, Resolution};
 use nokhwa::Camera;
@@ -12,6 +12,10 @@ pub struct NokhwaCapture {
     camera: Camera,
     width: u32,
     height: u32,
+    /// Color space of uncompressed YUV frames
+    color: YuvColorSpace,
+    /// Rotation stamped on every frame
+    rotation: Rotation,
 }
 
 impl CaptureBackend for NokhwaCapture {
@@ -26,7 +30,49 @@ impl CaptureBackend for NokhwaCapture {
             .collect())
     }
 
+    fn list_modes(device_id: &str) -> Result<Vec<CameraMode>> {
+        let index = Self::resolve_index(device_id)?;
+        let mut camera = Camera::new(index, RequestedFormat::new::<RgbFormat>(RequestedFormatType::None))?;
+        let mut modes: Vec<CameraMode> = camera
+            .compatible_camera_formats()?
+            .into_iter()
+            .filter_map(|fmt| {
+                let format = match fmt.format() {
+                    FrameFormat::MJPEG => CameraPixelFormat::Mjpeg,
+                    FrameFormat::YUYV => CameraPixelFormat::Yuyv,
+                    FrameFormat::NV12 => CameraPixelFormat::Nv12,
+                    _ => return None,
+                };
+                Some(CameraMode { width: fmt.width(), height: fmt.height(), fps: fmt.frame_rate(), format })
+            })
+            .collect();
+        modes.sort_by_key(|mode| std::cmp::Reverse((mode.width * mode.height, mode.fps)));
+        modes.dedup();
+        Ok(modes)
+    }
+
     fn open(config: CaptureConfig) -> Result<Self> {      
+        let target_index = Self::resolve_index(&config.device_id)?;
+
+        // A configured mode is used as is, without falling back to other formats
+        if let Some(mode) = config.mode {
+            let format = match mode.format {
+                CameraPixelFormat::Mjpeg => FrameFormat::MJPEG,
+                CameraPixelFormat::Yuyv => FrameFormat::YUYV,
+                CameraPixelFormat::Nv12 => FrameFormat::NV12,
+            };
+            let requested = RequestedFormat::new::<RgbFormat>(RequestedFormatType::Exact(
+                CameraFormat::new(Resolution::new(mode.width, mode.height), format, mode.fps),
+            ));
+            let mut camera = Camera::new(target_index, requested).map_err(|e| {
+                anyhow!("Camera '{}' can't stream {} ({}); see `proteus --list-devices --verbose`", config.device_id, mode, e)
+            })?;
+            camera.open_stream()?;
+            tracing::info!("Camera opened with configured mode: {}", mode);
+            return Ok(Self::from_camera(camera, &config));
+        }
+
+
         // 1. Initialize logic: Try multiple "seed" formats to establish connection
         // Some cameras are very picky and will reject "Closest" if the hint doesn't match roughly what they support.
         // 
@@ -74,18 +120,6 @@ impl CaptureBackend for NokhwaCapture {
 
         let mut camera = None;
         let mut active_format = None;
-        
-        // Resolve device index from config.device_id (index or name)
-        let target_index = if let Ok(idx) = config.device_id.parse::<u32>() {
-            CameraIndex::Index(idx)
-        } else {
-            let devices = nokhwa::query(nokhwa::utils::ApiBackend::Auto)?;
-            devices.into_iter()
-                .find(|d| d.human_name() == config.device_id)
-                .ok_or_else(|| anyhow::anyhow!("Camera not found: {}", config.device_id))?
-                .index()
-                .clone()
-        };
 
         // Filter seed formats by max dimensions
         let filtered_seeds: Vec<_> = seed_formats.into_iter()
@@ -187,18 +221,25 @@ impl CaptureBackend for NokhwaCapture {
             }
         }
 
-        let resolution = camera.resolution();
-        tracing::info!("Camera opened with resolution: {}", resolution);
-
-        Ok(Self {
-            camera,
-            width: resolution.width(),
-            height: resolution.height(),
-        })
+        Ok(Self::from_camera(camera, &config))
     }
 
     fn capture_frame(&mut self) -> Result<VideoFrame> {
         let frame = self.camera.frame()?;
+
+        // Pass uncompressed YUV through so it's converted with the camera's color space
+        let pixels = (self.width as usize) * (self.height as usize);
+        let raw_format = match frame.source_frame_format() {
+            FrameFormat::YUYV if frame.buffer().len() == pixels * 2 => Some(PixelFormat::Yuyv),
+            FrameFormat::NV12 if frame.buffer().len() == pixels + pixels / 2 => Some(PixelFormat::Nv12),
+            _ => None,
+        };
+        if let Some(format) = raw_format {
+            return Ok(VideoFrame::from_data(self.width, self.height, format, frame.buffer().to_vec())
+                .with_color(self.color)
+                .with_rotation(self.rotation));
+        }
+
         let decoded = frame.decode_image::<RgbFormat>()?;
         let rgb_data = decoded.into_raw();
 
@@ -207,10 +248,163 @@ impl CaptureBackend for NokhwaCapture {
             self.height,
             PixelFormat::Rgb,
             rgb_data,
-        ))
+        ).with_rotation(self.rotation))
     }
 
     fn frame_size(&self) -> (u32, u32) {
         (self.width, self.height)
     }
 }
+
+impl NokhwaCapture {
+    /// Resolves a device ID (index or name) to a camera index.
+    fn resolve_index(device_id: &str) -> Result<CameraIndex> {
+        if let Ok(idx) = device_id.parse::<u32>() {
+            return Ok(CameraIndex::Index(idx));
+        }
+        let devices = nokhwa::query(nokhwa::utils::ApiBackend::Auto)?;
+        Ok(devices.into_iter()
+            .find(|d| d.human_name() == device_id)
+            .ok_or_else(|| anyhow!("Camera not found: {}", device_id))?
+            .index()
+            .clone())
+    }
+
+    /// Wraps an opened camera.
+    fn from_camera(camera: Camera, config: &CaptureConfig) -> Self {
+        let resolution = camera.resolution();
+        tracing::info!("Camera opened with resolution: {}", resolution);
+
+        // Configured values first, then what the driver reports, then a guess from the resolution
+        let (matrix, range) = reported_color(camera.index());
+        let auto = YuvColorSpace::for_resolution(resolution.width(), resolution.height());
+        let color = YuvColorSpace {
+            matrix: config.matrix.or(matrix).unwrap_or(auto.matrix),
+            range: config.range.or(range).unwrap_or(auto.range),
+        };
+        tracing::info!("Camera YUV color space: {:?}", color);
+        if config.rotation != Rotation::None {
+            tracing::info!("Camera frames rotated: {:?}", config.rotation);
+        }
+
+        Self {
+            camera,
+            width: resolution.width(),
+            height: resolution.height(),
+            color,
+            rotation: config.rotation,
+        }
+    }
+}
+
+// `colorspace`, `ycbcr_enc` and `quantization` values of `struct v4l2_pix_format`
+#[cfg(any(target_os = "linux", test))]
+mod v4l2 {
+    pub const COLORSPACE_DEFAULT: u32 = 0;
+    pub const COLORSPACE_SMPTE240M: u32 = 2;
+    pub const COLORSPACE_REC709: u32 = 3;
+    pub const COLORSPACE_JPEG: u32 = 7;
+    pub const COLORSPACE_BT2020: u32 = 10;
+    pub const COLORSPACE_DCI_P3: u32 = 12;
+    pub const YCBCR_ENC_DEFAULT: u32 = 0;
+    pub const YCBCR_ENC_601: u32 = 1;
+    pub const YCBCR_ENC_709: u32 = 2;
+    pub const YCBCR_ENC_XV601: u32 = 3;
+    pub const YCBCR_ENC_XV709: u32 = 4;
+    pub const YCBCR_ENC_SYCC: u32 = 5;
+    pub const QUANTIZATION_DEFAULT: u32 = 0;
+    pub const QUANTIZATION_FULL_RANGE: u32 = 1;
+    pub const QUANTIZATION_LIM_RANGE: u32 = 2;
+}
+
+/// YUV matrix and range of a V4L2 format, resolving the defaults like the kernel's
+/// `V4L2_MAP_YCBCR_ENC_DEFAULT` and `V4L2_MAP_QUANTIZATION_DEFAULT`. None where the
+/// driver doesn't say (default colorspace) or the encoding isn't supported.
+#[cfg(any(target_os = "linux", test))]
+fn v4l2_color(colorspace: u32, ycbcr_enc: u32, quantization: u32) -> (Option<YuvMatrix>, Option<YuvRange>) {
+    if colorspace == v4l2::COLORSPACE_DEFAULT {
+        return (None, None);
+    }
+    let ycbcr_enc = match ycbcr_enc {
+        v4l2::YCBCR_ENC_DEFAULT => match colorspace {
+            v4l2::COLORSPACE_REC709 | v4l2::COLORSPACE_DCI_P3 => v4l2::YCBCR_ENC_709,
+            // Encodings other than BT.601 and BT.709 aren't supported
+            v4l2::COLORSPACE_BT2020 | v4l2::COLORSPACE_SMPTE240M => return (None, None),
+            _ => v4l2::YCBCR_ENC_601,
+        },
+        ycbcr_enc => ycbcr_enc,
+    };
+    let matrix = match ycbcr_enc {
+        v4l2::YCBCR_ENC_601 | v4l2::YCBCR_ENC_XV601 | v4l2::YCBCR_ENC_SYCC => Some(YuvMatrix::Bt601),
+        v4l2::YCBCR_ENC_709 | v4l2::YCBCR_ENC_XV709 => Some(YuvMatrix::Bt709),
+        _ => None,
+    };
+    let range = match quantization {
+        v4l2::QUANTIZATION_FULL_RANGE => YuvRange::Full,
+        v4l2::QUANTIZATION_LIM_RANGE => YuvRange::Limited,
+        v4l2::QUANTIZATION_DEFAULT if colorspace == v4l2::COLORSPACE_JPEG && !matches!(ycbcr_enc, v4l2::YCBCR_ENC_XV601 | v4l2::YCBCR_ENC_XV709) => YuvRange::Full,
+        _ => YuvRange::Limited,
+    };
+    (matrix, Some(range))
+}
+
+/// YUV matrix and range the V4L2 driver of a camera reports for its current format.
+#[cfg(target_os = "linux")]
+fn reported_color(index: &CameraIndex) -> (Option<YuvMatrix>, Option<YuvRange>) {
+    use std::os::unix::io::AsRawFd;
+
+    // `struct v4l2_format`: the type, then (8-byte aligned) the fields of `struct v4l2_pix_format`:
+    // width, height, pixelformat, field, bytesperline, sizeimage, colorspace, priv, flags, ycbcr_enc, quantization, ...
+    #[repr(C)]
+    struct v4l2_format {
+        type_: u32,
+        _pad: u32,
+        pix: [u32; 50],
+    }
+    const VIDIOC_G_FMT: u64 = 0xC0D05604; // _IOWR('V', 4, struct v4l2_format)
+    const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+    const V4L2_PIX_FMT_PRIV_MAGIC: u32 = 0xfeedcafe;
+
+    let Some(device) = index.as_index().ok().and_then(|index| std::fs::File::open(format!("/dev/video{}", index)).ok()) else {
+        return (None, None);
+    };
+    let mut format = v4l2_format { type_: V4L2_BUF_TYPE_VIDEO_CAPTURE, _pad: 0, pix: [0; 50] };
+    if unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_G_FMT, &mut format) } < 0 {
+        tracing::debug!("Camera colorimetry unavailable: {}", std::io::Error::last_os_error());
+        return (None, None);
+    }
+    let pix = format.pix;
+    // Drivers fill in the fields after `priv` only together with the magic value
+    let (ycbcr_enc, quantization) = if pix[7] == V4L2_PIX_FMT_PRIV_MAGIC { (pix[9], pix[10]) } else { (0, 0) };
+    tracing::debug!("Camera reports colorspace {}, ycbcr_enc {}, quantization {}", pix[6], ycbcr_enc, quantization);
+    v4l2_color(pix[6], ycbcr_enc, quantization)
+}
+
+/// Other platforms' capture APIs don't report the colorimetry.
+#[cfg(not(target_os = "linux"))]
+fn reported_color(_index: &CameraIndex) -> (Option<YuvMatrix>, Option<YuvRange>) {
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v4l2_color() {
+        // Driver doesn't say
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_DEFAULT, 0, 0), (None, None));
+        // UVC cameras without a color matching descriptor report sRGB: BT.601, limited
+        assert_eq!(v4l2_color(8, v4l2::YCBCR_ENC_DEFAULT, v4l2::QUANTIZATION_DEFAULT), (Some(YuvMatrix::Bt601), Some(YuvRange::Limited)));
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_REC709, v4l2::YCBCR_ENC_DEFAULT, v4l2::QUANTIZATION_DEFAULT), (Some(YuvMatrix::Bt709), Some(YuvRange::Limited)));
+        // JPEG colorspace defaults to full range, unless xvYCC
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_JPEG, v4l2::YCBCR_ENC_DEFAULT, v4l2::QUANTIZATION_DEFAULT), (Some(YuvMatrix::Bt601), Some(YuvRange::Full)));
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_JPEG, v4l2::YCBCR_ENC_XV709, v4l2::QUANTIZATION_DEFAULT), (Some(YuvMatrix::Bt709), Some(YuvRange::Limited)));
+        // Explicit values win over the colorspace defaults
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_REC709, v4l2::YCBCR_ENC_601, v4l2::QUANTIZATION_FULL_RANGE), (Some(YuvMatrix::Bt601), Some(YuvRange::Full)));
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_JPEG, v4l2::YCBCR_ENC_709, v4l2::QUANTIZATION_LIM_RANGE), (Some(YuvMatrix::Bt709), Some(YuvRange::Limited)));
+        // BT.2020 isn't supported: the matrix falls back to the guess
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_BT2020, v4l2::YCBCR_ENC_DEFAULT, v4l2::QUANTIZATION_DEFAULT), (None, None));
+        assert_eq!(v4l2_color(v4l2::COLORSPACE_REC709, 6, v4l2::QUANTIZATION_LIM_RANGE), (None, Some(YuvRange::Limited)));
+    }
+}
@@ -0,0 +1,881 @@
+//! Independent input → pipeline → virtual camera chains.
+//!
+//! A chain owns its camera capture, shader pipeline, and output device. Several
+//! chains can run in one process, sharing the GPU context and frame scheduler.
+
+use crate::{Config, InterpolationConfig, MixerConfig, MixerSourceConfig, ShaderEntry, TextureInput};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+pub use running::Chain;
+
+/// Configuration for an additional input → pipeline → output chain.
+/// Resolution and fps are shared with the top-level config.
+#[derive(Debug, Clone, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct ChainConfig {
+    /// Camera device ID (index or name)
+    pub input: String,
+    /// Shader passes for this chain
+    pub shaders: Vec<ShaderEntry>,
+    /// Ordered texture inputs for this chain
+    pub textures: Vec<TextureInput>,
+    /// Virtual camera device path (Linux v4l2loopback only)
+    pub device: Option<PathBuf>,
+    /// Input mixer; replaces `input` as this chain's source
+    pub mixer: Option<MixerConfig>,
+    /// Source shown while this chain's camera is unavailable
+    pub fallback: Option<MixerSourceConfig>,
+    /// Frame interpolation of this chain's camera input
+    pub interpolation: InterpolationConfig,
+}
+
+impl ChainConfig {
+    /// The primary chain described by the top-level config fields.
+    pub fn primary(config: &Config) -> Self {
+        Self {
+            input: config.input.clone(),
+            shaders: config.shaders.clone(),
+            textures: config.textures.clone(),
+            device: None,
+            mixer: config.mixer.clone(),
+            fallback: config.fallback.clone(),
+            interpolation: config.interpolation,
+        }
+    }
+
+    /// Returns all chains described by a config, primary chain first.
+    pub fn all(config: &Config) -> Vec<Self> {
+        std::iter::once(Self::primary(config))
+            .chain(config.chains.iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+mod running {
+    use super::ChainConfig;
+    use crate::config_utils::{
+        load_accumulation, load_bloom, load_particles, load_glyphs, load_sink_calibrations, load_sink_mirrors, load_compositor, load_decontamination, load_input_filter, load_fallback, load_interpolation, load_layout, load_mixer, load_modulators, load_monitor, load_output_format, load_output_rates, load_seed, load_text_blur, load_regions, load_data_buffers, load_redaction, load_qr, load_gestures, load_shaders, load_splash, load_cursor, load_zoom,
+        load_textures_with_size, load_texture_transforms, load_watermark, load_captions, load_clip, load_recording, load_idle, CAPTURE_RETRY_INTERVAL,
+    };
+    use crate::Config;
+    use anyhow::Result;
+    use proteus::capture::hotplug::DeviceEvent;
+    use proteus::capture::{AsyncCapture, CaptureRetry};
+    use proteus::audio::AudioLevels;
+    use proteus::clock::{ClockState, WallTime};
+    use proteus::frame::VideoFrame;
+    use proteus::interpolation::FrameInterpolator;
+    use proteus::mixer::Mixer;
+    use proteus::monitor::FrameMonitor;
+    use proteus::recorder::{RecordSettings, Recorder};
+    use proteus::recorder::clip::{ClipRecorder, ClipSettings};
+    use proteus::qr::QrSettings;
+    use proteus::gesture::{Gesture, GestureSettings};
+    use proteus::output::idle::{IdleDetector, IdleSettings, OutputState, CONSUMER_CHECK_INTERVAL};
+    use proteus::output::pacing::FramePacer;
+    use proteus::output::{OutputBackend, VirtualCameraConfig, VirtualCameraOutput};
+    use proteus::shader::gpu_context::GpuContext;
+    use proteus::shader::modulation::Modulator;
+    use proteus::shader::{Accumulation, Bloom, Calibration, Captions, Compositor, CursorOverlay, DataBuffer, Decontamination, Glyphs, InputFilter, Particles, OutputLayout, RedactionRule, Region, SeedMode, ShaderPipeline, Sink, TextBlur, Watermark, WgpuPipeline, Zoom};
+    use proteus::stats::{PipelineStats, Stage};
+    use std::borrow::Cow;
+    use std::collections::{BTreeSet, HashMap};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tracing::{debug, error, info, warn};
+
+    /// A running chain.
+    pub struct Chain {
+        name: String,
+        config: ChainConfig,
+        /// Camera device in use (differs from the configured one after a switch)
+        input: String,
+        capture: Option<AsyncCapture>,
+        /// Reopens the camera if it was unavailable at startup
+        capture_retry: Option<CaptureRetry>,
+        /// Source shown while the camera is unavailable
+        fallback: Option<Mixer>,
+        /// Source shown until the camera delivers its first frame
+        splash: Option<Mixer>,
+        mixer: Option<Mixer>,
+        monitor: Option<FrameMonitor>,
+        interpolator: Option<FrameInterpolator>,
+        pipeline: WgpuPipeline,
+        output: VirtualCameraOutput,
+        /// Schedules the virtual camera frames
+        output_pacer: FramePacer,
+        /// Frame interval of the virtual camera while it has consumers
+        output_interval: Duration,
+        /// Lowers the frame rate or suspends the chain without consumers
+        idle: IdleDetector,
+        width: u32,
+        height: u32,
+        /// Frame rate of recordings
+        recording_fps: u32,
+        live: bool,
+        /// Last frame written to the output, for snapshots
+        last_output: Option<VideoFrame>,
+        recorder: Option<Recorder>,
+        /// Write a metadata sidecar with recordings
+        record_metadata: bool,
+        /// Container and fragment interval of recordings
+        record_settings: RecordSettings,
+        clip: Option<ClipRecorder>,
+        clip_settings: ClipSettings,
+    }
+
+    impl Chain {
+        /// Opens the camera, builds the pipeline, and opens the output device for a chain.
+        pub fn new(
+            index: usize,
+            chain: ChainConfig,
+            config: &Config,
+            context: Arc<GpuContext>,
+        ) -> Result<Self> {
+            let name = format!("chain {}", index);
+
+            let mut capture = None;
+            let mut capture_retry = None;
+            let mut fallback = None;
+            let mut mixer = None;
+            let mut splash = None;
+
+            if let Some(mixer_config) = &chain.mixer {
+                mixer = Some(load_mixer(mixer_config, config));
+            } else if let Some(source) = load_splash(config) {
+                // Show the splash while the camera opens in the background
+                info!("[{}] Opening camera device {} in the background...", name, chain.input);
+                capture_retry = Some(CaptureRetry::open(config.capture_config(&chain.input), CAPTURE_RETRY_INTERVAL));
+                splash = Some(source);
+            } else {
+                info!("[{}] Opening camera device {}...", name, chain.input);
+                let capture_config = config.capture_config(&chain.input);
+                match AsyncCapture::new(capture_config.clone()) {
+                    Ok(c) => {
+                        info!("[{}] Camera opened successfully (async capture)", name);
+                        capture = Some(c);
+                    }
+                    // Without a fallback there is nothing presentable to output
+                    Err(e) if chain.fallback.is_none() => return Err(e),
+                    Err(e) => {
+                        warn!(
+                            "[{}] Failed to open camera: {}. Showing fallback until it appears.",
+                            name, e
+                        );
+                        capture_retry =
+                            Some(CaptureRetry::spawn(capture_config, CAPTURE_RETRY_INTERVAL));
+                        fallback = chain
+                            .fallback
+                            .as_ref()
+                            .and_then(|source| load_fallback(source, config));
+                    }
+                }
+            }
+
+            let monitor = config
+                .monitor
+                .as_ref()
+                .map(|monitor| load_monitor(monitor, mixer.as_ref()));
+
+            let interpolator = load_interpolation(chain.interpolation);
+
+            let mut pipeline = Self::build_pipeline(&chain, config.width, config.height, context)?;
+            pipeline.set_params(config.params.clone());
+            pipeline.set_modulators(load_modulators(config));
+            pipeline.set_layout(load_layout(config));
+            if let Err(e) = pipeline.set_watermark(load_watermark(config)) {
+                error!("[{}] Watermark disabled: {}", name, e);
+            }
+            if let Err(e) = pipeline.set_captions(load_captions(config)) {
+                error!("[{}] Captions disabled: {}", name, e);
+            }
+            pipeline.set_particles(load_particles(config));
+            pipeline.set_bloom(load_bloom(config));
+            pipeline.set_glyphs(load_glyphs(config));
+            pipeline.set_accumulation(load_accumulation(config));
+            pipeline.set_compositor(load_compositor(config));
+            pipeline.set_cursor_overlay(load_cursor(config));
+            pipeline.set_zoom(load_zoom(config));
+            pipeline.set_decontamination(load_decontamination(config));
+            pipeline.set_input_filter(load_input_filter(config));
+            pipeline.set_pose_model(config.pose_model.clone());
+            pipeline.set_text_blur(load_text_blur(config));
+            pipeline.set_regions(load_regions(config));
+            pipeline.set_data_buffers(load_data_buffers(config));
+            pipeline.set_redaction(load_redaction(config));
+            pipeline.set_qr(load_qr(config));
+            pipeline.set_gesture_recognition(load_gestures(config));
+            for (sink, mirror) in load_sink_mirrors(&config.sinks) {
+                pipeline.set_sink_mirror(sink, mirror);
+            }
+            for (sink, calibration) in load_sink_calibrations(&config.sinks) {
+                if let Err(e) = pipeline.set_sink_calibration(sink, calibration) {
+                    error!("[{}] {:?} calibration disabled: {}", name, sink, e);
+                }
+            }
+            pipeline.set_yuv_passthrough(config.input_passthrough);
+            pipeline.set_seed_mode(load_seed(config));
+            info!("[{}] Shader pipeline initialized", name);
+
+            let output = VirtualCameraOutput::new(Self::output_config(&chain, config))?;
+            info!("[{}] Virtual camera output initialized", name);
+            if let Some(format) = load_output_format(config).filter(|&format| format != output.pixel_format()) {
+                warn!(
+                    "[{}] Output format {:?} isn't supported by this platform's virtual camera, using {:?}",
+                    name, format, output.pixel_format()
+                );
+            }
+            // Pack the frames for the device on the GPU
+            pipeline.set_output_format(output.pixel_format());
+            let rates = load_output_rates(config);
+
+            Ok(Self {
+                name,
+                input: chain.input.clone(),
+                config: chain,
+                capture,
+                capture_retry,
+                fallback,
+                splash,
+                mixer,
+                monitor,
+                interpolator,
+                pipeline,
+                output,
+                output_pacer: FramePacer::new(rates.camera, Instant::now()),
+                output_interval: Duration::from_secs_f64(1.0 / rates.camera as f64),
+                idle: IdleDetector::new(load_idle(config), Instant::now()),
+                width: config.width,
+                height: config.height,
+                recording_fps: rates.recording,
+                live: config.live,
+                last_output: None,
+                recorder: None,
+                record_metadata: config.recording.metadata,
+                record_settings: load_recording(config),
+                clip: None,
+                clip_settings: load_clip(config),
+            })
+        }
+
+        #[cfg(target_os = "linux")]
+        fn output_config(chain: &ChainConfig, config: &Config) -> VirtualCameraConfig {
+            let defaults = VirtualCameraConfig::default();
+            VirtualCameraConfig {
+                device: chain.device.clone().unwrap_or(defaults.device),
+                width: config.width,
+                height: config.height,
+                fps: load_output_rates(config).camera,
+                format: load_output_format(config).unwrap_or(defaults.format),
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        fn output_config(_chain: &ChainConfig, config: &Config) -> VirtualCameraConfig {
+            VirtualCameraConfig {
+                width: config.width,
+                height: config.height,
+                fps: load_output_rates(config).camera,
+            }
+        }
+
+        fn build_pipeline(
+            chain: &ChainConfig,
+            width: u32,
+            height: u32,
+            context: Arc<GpuContext>,
+        ) -> Result<WgpuPipeline> {
+            let (shaders, pass_options) = load_shaders(&chain.shaders);
+            let texture_sources =
+                load_textures_with_size(&chain.textures, width, height, Some(context.clone()));
+            let mut pipeline = WgpuPipeline::new(
+                context,
+                width,
+                height,
+                shaders,
+                pass_options,
+                texture_sources,
+            )?;
+            pipeline.set_texture_transforms(&load_texture_transforms(&chain.textures));
+            Ok(pipeline)
+        }
+
+        /// Applies a changed chain config. Shader and texture changes are hot-reloaded;
+        /// input and device changes are reported as requiring a restart.
+        pub fn reload(&mut self, chain: ChainConfig, context: Arc<GpuContext>) {
+            if chain.input != self.config.input
+                || chain.device != self.config.device
+                || chain.mixer != self.config.mixer
+                || chain.fallback != self.config.fallback
+            {
+                warn!(
+                    "[{}] Changes to input, device, mixer, or fallback require a restart.",
+                    self.name
+                );
+            }
+
+            if chain.shaders != self.config.shaders || chain.textures != self.config.textures {
+                info!(
+                    "[{}] Reloading pipeline due to shader/texture changes...",
+                    self.name
+                );
+                match Self::build_pipeline(&chain, self.width, self.height, context) {
+                    Ok(mut pipeline) => {
+                        // Keep the state set at runtime
+                        pipeline.inherit_state(&self.pipeline);
+                        self.pipeline = pipeline;
+                        info!("[{}] Pipeline reloaded successfully", self.name);
+                    }
+                    Err(e) => error!("[{}] Failed to rebuild pipeline: {}", self.name, e),
+                }
+            }
+
+            if chain.interpolation != self.config.interpolation {
+                self.interpolator = load_interpolation(chain.interpolation);
+            }
+
+            self.config = chain;
+        }
+
+        /// The camera device in use (index or name).
+        pub fn input(&self) -> &str {
+            &self.input
+        }
+
+        /// Switches to another camera. The current one keeps streaming until the
+        /// new one delivers.
+        pub fn switch_input(&mut self, device_id: &str, config: &Config) -> Result<()> {
+            anyhow::ensure!(self.mixer.is_none(), "[{}] Inputs are set by the mixer", self.name);
+            info!("[{}] Switching to camera {}...", self.name, device_id);
+            self.capture_retry = Some(CaptureRetry::open(config.capture_config(device_id), CAPTURE_RETRY_INTERVAL));
+            self.input = device_id.to_string();
+            Ok(())
+        }
+
+        /// Passes a camera connect / disconnect to the chain's Lua canvases.
+        pub fn device_event(&mut self, event: &DeviceEvent) {
+            self.pipeline.device_event(event);
+        }
+
+        /// Sets the tally (live / on air) state for this chain.
+        pub fn set_live(&mut self, live: bool) {
+            self.live = live;
+        }
+
+        pub fn is_live(&self) -> bool {
+            self.live
+        }
+
+        pub fn is_recording(&self) -> bool {
+            self.recorder.is_some()
+        }
+
+        /// The last applied preset.
+        pub fn preset(&self) -> Option<&str> {
+            self.pipeline.preset()
+        }
+
+        pub fn redaction_rules(&self) -> &[RedactionRule] {
+            self.pipeline.redaction_rules()
+        }
+
+        /// Sets a named shader parameter for this chain.
+        pub fn set_param(&mut self, name: &str, value: f32) {
+            self.pipeline.set_param(name, value);
+        }
+
+        /// Replaces all of the chain's parameter values.
+        pub fn set_params(&mut self, params: HashMap<String, f32>) {
+            self.pipeline.set_params(params);
+        }
+
+        /// Returns the chain's current parameter values.
+        pub fn params(&self) -> &HashMap<String, f32> {
+            self.pipeline.params()
+        }
+
+        /// Names of the parameters the chain's shaders declare, sorted.
+        pub fn param_names(&self) -> Vec<String> {
+            self.pipeline.param_names()
+        }
+
+        /// The last frame the chain output, if any.
+        pub fn last_output(&self) -> Option<&VideoFrame> {
+            self.last_output.as_ref()
+        }
+
+        /// Sets the preset name reported in the chain's recording metadata.
+        pub fn set_preset(&mut self, name: &str) {
+            self.pipeline.set_preset(Some(name.to_string()));
+        }
+
+        /// Writes a metadata sidecar with the chain's next recordings (or stops doing so).
+        pub fn set_record_metadata(&mut self, enabled: bool) {
+            self.record_metadata = enabled;
+        }
+
+        /// Sets what the chain does without consumers.
+        pub fn set_idle(&mut self, settings: IdleSettings) {
+            if let Some(state) = self.idle.set_settings(settings, Instant::now()) {
+                self.set_output_state(state);
+            }
+        }
+
+        /// Adapts the virtual camera's frame rate to its idle state.
+        fn set_output_state(&mut self, state: OutputState) {
+            self.output_pacer.set_interval(match state {
+                OutputState::Active => self.output_interval,
+                OutputState::LowFps(fps) => Duration::from_secs_f64(1.0 / fps as f64),
+                // Only wakes up to look for consumers
+                OutputState::Suspended => CONSUMER_CHECK_INTERVAL,
+            });
+        }
+
+        /// Sets how the chain's next recordings are written.
+        pub fn set_record_settings(&mut self, settings: RecordSettings) {
+            self.record_settings = settings;
+        }
+
+        pub fn record_settings(&self) -> &RecordSettings {
+            &self.record_settings
+        }
+
+        /// Sets the caps of the chain's next clips.
+        pub fn set_clip_settings(&mut self, settings: ClipSettings) {
+            self.clip_settings = settings;
+        }
+
+        /// Replaces the chain's global modulators.
+        pub fn set_modulators(&mut self, modulators: Vec<Modulator>) {
+            self.pipeline.set_modulators(modulators);
+        }
+
+        /// Sets how the chain's output frame is composed.
+        pub fn set_layout(&mut self, layout: OutputLayout) {
+            self.pipeline.set_layout(layout);
+        }
+
+        /// Sets (or removes) the chain's watermark.
+        pub fn set_watermark(&mut self, watermark: Option<Watermark>) {
+            if let Err(e) = self.pipeline.set_watermark(watermark) {
+                error!("[{}] Watermark disabled: {}", self.name, e);
+            }
+        }
+
+        /// Sets (or removes) the chain's captions.
+        pub fn set_captions(&mut self, captions: Option<Captions>) {
+            if let Err(e) = self.pipeline.set_captions(captions) {
+                error!("[{}] Captions disabled: {}", self.name, e);
+            }
+        }
+
+        /// Shows a live caption on the chain's output, or removes it.
+        pub fn set_caption(&mut self, text: Option<String>) -> Result<()> {
+            self.pipeline.set_caption(text)
+        }
+
+        /// Sets (or removes) the chain's particle system.
+        pub fn set_particles(&mut self, particles: Option<Particles>) {
+            self.pipeline.set_particles(particles);
+        }
+
+        /// Sets (or removes) the chain's bloom.
+        pub fn set_bloom(&mut self, bloom: Option<Bloom>) {
+            self.pipeline.set_bloom(bloom);
+        }
+
+        /// Sets the characters of the chain's glyph atlas.
+        pub fn set_glyphs(&mut self, glyphs: Glyphs) {
+            self.pipeline.set_glyphs(glyphs);
+        }
+
+        /// Sets (or removes) the chain's long-exposure accumulation.
+        pub fn set_accumulation(&mut self, accumulation: Option<Accumulation>) {
+            self.pipeline.set_accumulation(accumulation);
+        }
+
+        /// Sets (or removes) the chain's compositor.
+        pub fn set_compositor(&mut self, compositor: Option<Compositor>) {
+            self.pipeline.set_compositor(compositor);
+        }
+
+        /// Sets (or removes) the chain's cursor highlighting.
+        pub fn set_cursor_overlay(&mut self, cursor: Option<CursorOverlay>) {
+            self.pipeline.set_cursor_overlay(cursor);
+        }
+
+        /// Moves the chain's cursor (`None` hides it).
+        pub fn set_cursor(&mut self, position: Option<[f32; 2]>) {
+            self.pipeline.set_cursor(position);
+        }
+
+        /// Shows a click ripple at the chain's cursor.
+        pub fn cursor_click(&mut self) {
+            self.pipeline.cursor_click();
+        }
+
+        /// Sets (or removes) the chain's zoom.
+        pub fn set_zoom(&mut self, zoom: Option<Zoom>) {
+            self.pipeline.set_zoom(zoom);
+        }
+
+        /// Zooms the chain in, out, or toggles (`None`). Returns whether it is zoomed in.
+        pub fn set_zoom_active(&mut self, active: Option<bool>) -> Result<bool> {
+            self.pipeline.set_zoom_active(active)
+        }
+
+        /// Moves the chain's zoom to a point in 0.0 - 1.0 frame coordinates.
+        pub fn set_zoom_target(&mut self, x: f32, y: f32) {
+            self.pipeline.set_zoom_target(x, y);
+        }
+
+        /// Hides the named compositor layers (and the watermark) of the chain.
+        pub fn set_hidden(&mut self, hidden: &BTreeSet<String>) {
+            self.pipeline.set_hidden(hidden);
+        }
+
+        /// Switches the chain's compositor to a named layout.
+        pub fn switch_compositor_layout(&mut self, name: &str) -> Result<()> {
+            self.pipeline.switch_compositor_layout(name)
+        }
+
+        /// Returns the chain's compositor settings.
+        pub fn compositor(&self) -> Option<&Compositor> {
+            self.pipeline.compositor()
+        }
+
+        /// Whether the chain's zoom is zoomed in (`None` without a zoom).
+        pub fn zoom_active(&self) -> Option<bool> {
+            self.pipeline.zoom_active()
+        }
+
+        /// Sets (or removes) the chain's edge decontamination.
+        pub fn set_decontamination(&mut self, decontamination: Option<Decontamination>) {
+            self.pipeline.set_decontamination(decontamination);
+        }
+
+        /// Sets how the chain samples and sharpens the camera input.
+        pub fn set_input_filter(&mut self, input_filter: InputFilter) {
+            self.pipeline.set_input_filter(input_filter);
+        }
+
+        /// Sets (or removes) the chain's pose estimation model.
+        pub fn set_pose_model(&mut self, path: Option<PathBuf>) {
+            self.pipeline.set_pose_model(path);
+        }
+
+        /// Sets (or removes) the blurring of on-screen text in the chain's input.
+        pub fn set_text_blur(&mut self, text_blur: Option<TextBlur>) {
+            self.pipeline.set_text_blur(text_blur);
+        }
+
+        /// Replaces the chain's static mask regions.
+        pub fn set_regions(&mut self, regions: Vec<Region>) {
+            self.pipeline.set_regions(regions);
+        }
+
+        /// Replaces the chain's data buffers.
+        pub fn set_data_buffers(&mut self, buffers: Vec<DataBuffer>) {
+            self.pipeline.set_data_buffers(buffers);
+        }
+
+        /// Sets the values of one of the chain's data buffers.
+        pub fn set_data_values(&mut self, name: &str, values: Vec<f32>) -> Result<()> {
+            self.pipeline.set_data_values(name, values)
+        }
+
+        /// Replaces the chain's redaction rules.
+        pub fn set_redaction(&mut self, rules: Vec<RedactionRule>) {
+            self.pipeline.set_redaction(rules);
+        }
+
+        /// Switches one of the chain's redaction rules on, off, or toggles it (`None`).
+        /// Returns whether it is on.
+        pub fn set_redaction_rule(&mut self, name: &str, enabled: Option<bool>) -> Result<bool> {
+            self.pipeline.set_redaction_rule(name, enabled)
+        }
+
+        /// Enables (or disables) the chain's QR code detection.
+        pub fn set_qr(&mut self, settings: Option<QrSettings>) {
+            self.pipeline.set_qr(settings);
+        }
+
+        /// Returns the QR codes the chain detected since the last call.
+        pub fn take_qr_codes(&mut self) -> Vec<String> {
+            self.pipeline.take_qr_codes()
+        }
+
+        /// Enables (or disables) the chain's gesture recognition.
+        pub fn set_gesture_recognition(&mut self, settings: Option<GestureSettings>) {
+            self.pipeline.set_gesture_recognition(settings);
+        }
+
+        /// Returns the gestures the chain recognized since the last call.
+        pub fn take_gestures(&mut self) -> Vec<Gesture> {
+            self.pipeline.take_gestures()
+        }
+
+        /// Mirrors the chain's virtual camera output and recordings horizontally.
+        pub fn set_sink_mirrors(&mut self, mirrors: [(Sink, bool); 2]) {
+            for (sink, mirror) in mirrors {
+                self.pipeline.set_sink_mirror(sink, mirror);
+            }
+        }
+
+        /// Sets (or removes) the color calibration of the chain's virtual camera output and recordings.
+        pub fn set_sink_calibrations(&mut self, calibrations: [(Sink, Option<Calibration>); 2]) {
+            for (sink, calibration) in calibrations {
+                if let Err(e) = self.pipeline.set_sink_calibration(sink, calibration) {
+                    error!("[{}] {:?} calibration disabled: {}", self.name, sink, e);
+                }
+            }
+        }
+
+        /// Converts the chain's uncompressed camera frames on the GPU.
+        pub fn set_yuv_passthrough(&mut self, enabled: bool) {
+            self.pipeline.set_yuv_passthrough(enabled);
+        }
+
+        /// Sets how the `seed` uniform changes from frame to frame.
+        pub fn set_seed_mode(&mut self, mode: SeedMode) {
+            self.pipeline.set_seed_mode(mode);
+        }
+
+        /// Sets the musical clock state for the next frame.
+        pub fn set_clock(&mut self, clock: ClockState) {
+            self.pipeline.set_clock(clock);
+        }
+
+        /// Sets the wall clock time for the next frame.
+        pub fn set_wall_time(&mut self, wall_time: WallTime) {
+            self.pipeline.set_wall_time(wall_time);
+        }
+
+        /// Sets the audio meter levels for the next frame.
+        pub fn set_audio_levels(&mut self, levels: AudioLevels) {
+            self.pipeline.set_audio_levels(levels);
+        }
+
+        /// The chain's input mixer, if configured.
+        pub fn mixer_mut(&mut self) -> Option<&mut Mixer> {
+            self.mixer.as_mut()
+        }
+
+        /// Saves the last output frame as an image, calibrated for recordings.
+        pub fn snapshot(&mut self, path: &Path) -> Result<()> {
+            if self.pipeline.recording_like_output() {
+                let frame = self
+                    .last_output
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("No frame has been rendered yet"))?;
+                frame.save(path)?;
+            } else {
+                self.pipeline.read_output(Sink::Recording)?.save(path)?;
+            }
+            info!("[{}] Saved snapshot to {:?}", self.name, path);
+            Ok(())
+        }
+
+        /// Starts recording the output to a video file.
+        pub fn start_recording(&mut self, path: &Path) -> Result<()> {
+            anyhow::ensure!(self.recorder.is_none(), "Already recording");
+            let mut recorder = Recorder::start(path, self.width, self.height, self.recording_fps, &self.record_settings)?;
+            if self.record_metadata {
+                recorder.enable_metadata()?;
+            }
+            self.recorder = Some(recorder);
+            Ok(())
+        }
+
+        /// Stops recording and returns the path of the finished file.
+        pub fn stop_recording(&mut self) -> Result<PathBuf> {
+            let recorder = self
+                .recorder
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("Not recording"))?;
+            recorder.stop()
+        }
+
+        /// Starts saving the next `seconds` of output as a GIF or APNG.
+        pub fn start_clip(&mut self, path: &Path, seconds: f32) -> Result<()> {
+            anyhow::ensure!(self.clip.is_none(), "Already recording a clip");
+            self.clip = Some(ClipRecorder::start(path, seconds, &self.clip_settings, Instant::now())?);
+            Ok(())
+        }
+
+        /// When the virtual camera or the recording needs its next frame.
+        pub fn next_frame(&self) -> Instant {
+            let recording = self.recorder.as_ref().map(Recorder::next_frame);
+            recording.map_or(self.output_pacer.next_frame(), |recording| recording.min(self.output_pacer.next_frame()))
+        }
+
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Frame statistics of the chain.
+        pub fn stats(&self) -> &PipelineStats {
+            self.pipeline.stats()
+        }
+
+        /// Counts the camera frames since the last tick and logs the stats once per second.
+        fn update_stats(&mut self) {
+            let stats = self.pipeline.stats_mut();
+            if let Some(capture) = &mut self.capture {
+                let (received, dropped) = capture.take_counts();
+                stats.frames(Stage::Capture, received);
+                stats.dropped(Stage::Capture, dropped);
+            }
+            if stats.update(Instant::now()) {
+                debug!("[Perf] [{}] {}", self.name, stats);
+            }
+        }
+
+        /// Writes the frames still in flight on the GPU to the output and recordings.
+        /// Call before dropping the chain.
+        pub fn finish(&mut self) {
+            loop {
+                match self.pipeline.finish_readback() {
+                    Ok(Some(processed)) => self.deliver(processed, Instant::now(), true),
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("[{}] Shader processing error: {}", self.name, e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        /// Writes a read back frame to the output (if `output_due`), the recording and the clip.
+        fn deliver(&mut self, processed: VideoFrame, now: Instant, output_due: bool) {
+            if output_due {
+                let write_start = Instant::now();
+                match self.output.write_frame(&processed) {
+                    Ok(()) => self.pipeline.stats_mut().frame(Stage::Output, write_start.elapsed()),
+                    Err(e) => {
+                        error!("[{}] Output error: {}", self.name, e);
+                        self.pipeline.stats_mut().dropped(Stage::Output, 1);
+                    }
+                }
+            }
+            // Recordings with a calibration of their own read the last rendered frame back
+            // separately (waiting for the GPU), along with its metadata
+            let recording = self.recorder.as_ref().is_some_and(|recorder| recorder.wants_frame(now)) || self.clip.is_some();
+            let recording_frame = if recording && !self.pipeline.recording_like_output() {
+                self.pipeline.read_output(Sink::Recording).map_err(|e| error!("[{}] Recording readback error: {}", self.name, e)).ok()
+            } else {
+                None
+            };
+            let recorded = recording_frame.as_ref().unwrap_or(&processed);
+            if let Some(recorder) = self.recorder.as_mut().filter(|recorder| recorder.wants_frame(now)) {
+                let metadata = match (recorder.wants_metadata(), &recording_frame) {
+                    (false, _) => None,
+                    (true, Some(_)) => Some(Cow::Owned(self.pipeline.frame_metadata())),
+                    (true, None) => Some(Cow::Borrowed(self.pipeline.read_back_metadata())),
+                };
+                recorder.write_frame(recorded, metadata.as_deref(), now);
+            }
+            if let Some(clip) = &mut self.clip {
+                let now = Instant::now();
+                clip.write_frame(recorded, now);
+                if clip.is_complete(now) {
+                    self.clip = None;
+                }
+            }
+            self.last_output = Some(processed);
+        }
+
+        /// Processes the latest camera frame, if any, and writes it to the output.
+        /// `time` drives the sources (mixer, fallback, splash), `shader_time` the shaders.
+        pub fn tick(&mut self, time: f32, shader_time: f32) {
+            self.pipeline.set_live(self.live);
+            self.update_stats();
+
+            if let Some(capture) = self.capture_retry.as_mut().and_then(|retry| retry.try_take()) {
+                info!("[{}] Camera connected", self.name);
+                self.capture = Some(capture);
+                self.capture_retry = None;
+                self.fallback = None;
+            }
+
+            // Rendered at the rate of whichever output needs a frame
+            let now = Instant::now();
+            if let Some(state) = self.idle.update(now, &mut self.output) {
+                self.set_output_state(state);
+            }
+            let output_due = self.output_pacer.tick(now) && self.idle.state() != OutputState::Suspended;
+            if !output_due && !self.recorder.as_ref().is_some_and(|recorder| recorder.wants_frame(now)) {
+                return;
+            }
+
+            if self.splash.is_some() {
+                if self.capture.as_mut().is_some_and(|capture| capture.get_latest_frame().is_some()) {
+                    info!("[{}] Camera ready, hiding splash", self.name);
+                    self.splash = None;
+                } else {
+                    // Shown as is, the shaders only apply to the camera
+                    if let Some(frame) = self.splash.as_mut().and_then(|splash| splash.next_frame(time)).filter(|_| output_due) {
+                        if let Err(e) = self.output.write_frame(&frame.resize(self.width, self.height)) {
+                            error!("[{}] Output error: {}", self.name, e);
+                        }
+                    }
+                    return;
+                }
+            }
+
+            if let Some(monitor) = &mut self.monitor {
+                let source = self.mixer.as_ref().or(self.fallback.as_ref().filter(|_| self.capture.is_none()));
+                monitor.set_static_source(source.is_some_and(Mixer::is_static));
+                self.pipeline.set_warning(monitor.warning());
+            }
+            let frame_option: Option<&VideoFrame> = if let Some(mixer) = &mut self.mixer {
+                mixer.next_frame(time)
+            } else if let Some(cap) = &mut self.capture {
+                cap.get_latest_frame()
+            } else if let Some(fallback) = &mut self.fallback {
+                fallback.next_frame(time)
+            } else {
+                None
+            };
+            let frame_option = match &mut self.monitor {
+                Some(monitor) => monitor.filter(frame_option, time),
+                None => frame_option,
+            };
+            let frame_option = match (&mut self.interpolator, frame_option) {
+                (Some(interpolator), Some(frame)) => Some(interpolator.next(frame, time)),
+                (_, frame) => frame,
+            };
+
+            let processed = match frame_option {
+                Some(frame) => self.pipeline.process_frame(frame, shader_time),
+                // Without a new frame, the frames still in flight are delivered one per tick
+                None => self.pipeline.finish_readback(),
+            };
+            let mut sampled = false;
+            match processed {
+                // The first frames are still in flight on the GPU
+                Ok(None) => {}
+                Ok(Some(processed)) => {
+                    self.deliver(processed, now, output_due);
+                    sampled = self.monitor.as_ref().is_some_and(|monitor| monitor.wants_output(time));
+                }
+                Err(e) => {
+                    error!("[{}] Shader processing error: {}", self.name, e);
+                }
+            }
+
+            if let Some(monitor) = &mut self.monitor {
+                monitor.observe(self.last_output.as_ref().filter(|_| sampled), time);
+            }
+            if let (Some(monitor), Some(mixer)) = (&mut self.monitor, &mut self.mixer) {
+                if let Some(index) = monitor.take_switch_request() {
+                    mixer.cut(index);
+                }
+            }
+        }
+    }
+}
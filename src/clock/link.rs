@@ -0,0 +1,61 @@
+//! Ableton Link session sync (requires the `link` feature).
+
+use anyhow::Result;
+
+/// Connection to an Ableton Link session on the local network.
+#[cfg(feature = "link")]
+pub struct LinkSync {
+    link: rusty_link::AblLink,
+    state: rusty_link::SessionState,
+    peers: u64,
+}
+
+#[cfg(feature = "link")]
+impl LinkSync {
+    /// Joins the Link session, proposing `bpm` if no other peers are present.
+    pub fn new(bpm: f32) -> Result<Self> {
+        let link = rusty_link::AblLink::new(bpm as f64);
+        link.enable(true);
+        tracing::info!("Ableton Link enabled");
+        Ok(Self { link, state: rusty_link::SessionState::new(), peers: 0 })
+    }
+
+    /// Returns the session's current tempo and beat position (aligned to `quantum` beats).
+    pub fn capture(&mut self, quantum: f64) -> (f32, f64) {
+        let peers = self.link.num_peers();
+        if peers != self.peers {
+            tracing::info!("Ableton Link: {} peer(s)", peers);
+            self.peers = peers;
+        }
+
+        self.link.capture_app_session_state(&mut self.state);
+        let time = self.link.clock_micros();
+        (self.state.tempo() as f32, self.state.beat_at_time(time, quantum))
+    }
+
+    /// Proposes a new tempo to the session.
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.link.capture_app_session_state(&mut self.state);
+        self.state.set_tempo(bpm as f64, self.link.clock_micros());
+        self.link.commit_app_session_state(&self.state);
+    }
+}
+
+/// Placeholder used when Proteus is built without the `link` feature.
+#[cfg(not(feature = "link"))]
+pub struct LinkSync {
+    _private: (),
+}
+
+#[cfg(not(feature = "link"))]
+impl LinkSync {
+    pub fn new(_bpm: f32) -> Result<Self> {
+        anyhow::bail!("Proteus was built without Ableton Link support (enable the `link` feature)")
+    }
+
+    pub fn capture(&mut self, _quantum: f64) -> (f32, f64) {
+        unreachable!("LinkSync can't be constructed without the `link` feature")
+    }
+
+    pub fn set_tempo(&mut self, _bpm: f32) {}
+}
@@ -0,0 +1,180 @@
+//! Musical clock (BPM and beat position).
+//!
+//! Tracks a tempo and a continuous beat position so effects can pulse in time with
+//! music. The tempo comes from the config, tap tempo, or an Ableton Link session.
+
+mod link;
+mod time;
+mod wall;
+
+pub use link::LinkSync;
+pub use time::{ShaderTime, TimeCommand};
+pub use wall::{WallClock, WallClockSettings, WallTime};
+
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// Tempo used unless configured otherwise.
+pub const DEFAULT_BPM: f32 = 120.0;
+
+/// Beats per bar (4/4 time).
+pub const BEATS_PER_BAR: f64 = 4.0;
+
+/// Accepted tempo range.
+const BPM_RANGE: (f64, f64) = (20.0, 999.0);
+
+/// A pause longer than this starts a new tap sequence.
+const TAP_RESET: Duration = Duration::from_secs(2);
+
+/// Number of recent taps averaged for tap tempo.
+const MAX_TAPS: usize = 8;
+
+/// Snapshot of the clock for one frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockState {
+    pub bpm: f32,
+    /// Beats elapsed since the clock started (fractional)
+    pub beat: f64,
+}
+
+impl Default for ClockState {
+    fn default() -> Self {
+        Self { bpm: DEFAULT_BPM, beat: 0.0 }
+    }
+}
+
+impl ClockState {
+    /// Position within the current beat (0.0 - 1.0).
+    pub fn beat_phase(&self) -> f32 {
+        self.beat.rem_euclid(1.0) as f32
+    }
+
+    /// Position within the current bar (0.0 - 1.0).
+    pub fn bar_phase(&self) -> f32 {
+        (self.beat.rem_euclid(BEATS_PER_BAR) / BEATS_PER_BAR) as f32
+    }
+}
+
+/// Tempo clock with tap tempo.
+pub struct BeatClock {
+    bpm: f64,
+    /// Beat position at `anchor`
+    anchor_beat: f64,
+    anchor: Instant,
+    taps: Vec<Instant>,
+    /// Link session the clock follows, if enabled
+    link: Option<LinkSync>,
+}
+
+impl BeatClock {
+    pub fn new(bpm: f32) -> Self {
+        Self {
+            bpm: (bpm as f64).clamp(BPM_RANGE.0, BPM_RANGE.1),
+            anchor_beat: 0.0,
+            anchor: Instant::now(),
+            taps: Vec::new(),
+            link: None,
+        }
+    }
+
+    /// Joins an Ableton Link session. The clock then follows the session's tempo and
+    /// beat, and local tempo changes are proposed to the other peers.
+    pub fn enable_link(&mut self) -> Result<()> {
+        self.link = Some(LinkSync::new(self.bpm as f32)?);
+        Ok(())
+    }
+
+    /// Follows the Link session, if enabled. Call once per frame before `state`.
+    pub fn update(&mut self, now: Instant) {
+        if let Some(link) = &mut self.link {
+            let (bpm, beat) = link.capture(BEATS_PER_BAR);
+            self.sync(beat, bpm, now);
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm as f32
+    }
+
+    /// Beat position at `now`.
+    pub fn beat_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.anchor).as_secs_f64();
+        self.anchor_beat + elapsed * self.bpm / 60.0
+    }
+
+    /// Clock snapshot at `now`.
+    pub fn state(&self, now: Instant) -> ClockState {
+        ClockState { bpm: self.bpm as f32, beat: self.beat_at(now) }
+    }
+
+    /// Changes the tempo without jumping the beat position.
+    pub fn set_bpm(&mut self, bpm: f32, now: Instant) {
+        self.sync(self.beat_at(now), bpm, now);
+        if let Some(link) = &mut self.link {
+            link.set_tempo(self.bpm as f32);
+        }
+    }
+
+    /// Sets both tempo and beat position, e.g. from an external sync source.
+    pub fn sync(&mut self, beat: f64, bpm: f32, now: Instant) {
+        self.anchor_beat = beat;
+        self.anchor = now;
+        self.bpm = (bpm as f64).clamp(BPM_RANGE.0, BPM_RANGE.1);
+    }
+
+    /// Registers a tap at `now`. Each tap lands on a beat; from the second tap in a
+    /// sequence on, the tempo follows the average tap interval. Returns the new tempo.
+    pub fn tap(&mut self, now: Instant) -> f32 {
+        if self.taps.last().is_some_and(|&last| now.saturating_duration_since(last) > TAP_RESET) {
+            self.taps.clear();
+        }
+        self.taps.push(now);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+
+        let mut bpm = self.bpm as f32;
+        if let (Some(first), Some(last)) = (self.taps.first(), self.taps.last()) {
+            let intervals = self.taps.len() - 1;
+            if intervals > 0 {
+                let average = last.duration_since(*first).as_secs_f64() / intervals as f64;
+                if average > 0.0 {
+                    bpm = (60.0 / average) as f32;
+                }
+            }
+        }
+
+        self.sync(self.beat_at(now).round(), bpm, now);
+        if let Some(link) = &mut self.link {
+            link.set_tempo(self.bpm as f32);
+        }
+        self.bpm as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_tempo() {
+        let start = Instant::now();
+        let mut clock = BeatClock::new(DEFAULT_BPM);
+        for i in 0..4 {
+            clock.tap(start + Duration::from_millis(500 * i));
+        }
+        assert!((clock.bpm() - 120.0).abs() < 0.01);
+
+        // The last tap (1.5s) lands on a beat
+        let state = clock.state(start + Duration::from_millis(1500));
+        assert!(state.beat_phase().abs() < 1e-6);
+        let state = clock.state(start + Duration::from_millis(1750));
+        assert!((state.beat_phase() - 0.5).abs() < 1e-4);
+
+        // Changing the tempo keeps the position
+        let now = start + Duration::from_millis(2000);
+        let beat = clock.beat_at(now);
+        clock.set_bpm(90.0, now);
+        assert!((clock.beat_at(now) - beat).abs() < 1e-9);
+    }
+}
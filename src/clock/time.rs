@@ -0,0 +1,154 @@
+//! Shader time with pause, scrubbing and a speed multiplier.
+//!
+//! The `time` uniform normally follows the wall clock. It can be frozen (e.g.
+//! for screenshots), moved to any position, or slowed down and sped up, without
+//! affecting anything else that runs on the wall clock (mixer fades, the beat
+//! clock, frame pacing).
+
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// A time control command (`proteus ctl time ...`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeCommand {
+    /// Freeze the time
+    Pause,
+    /// Continue from where it was paused
+    Resume,
+    /// Pause or resume
+    Toggle,
+    /// Jump to a time in seconds
+    Seek(f64),
+    /// Move the time by the given seconds (negative to go back)
+    Step(f64),
+    /// Set the speed multiplier (1.0 = real time)
+    Speed(f64),
+}
+
+impl FromStr for TimeCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let seconds = |value: &str| value.parse::<f64>().ok().filter(|s| s.is_finite()).ok_or_else(|| anyhow!("Invalid time '{}'", value));
+        let command = match args.as_slice() {
+            ["pause"] => TimeCommand::Pause,
+            ["resume"] => TimeCommand::Resume,
+            ["toggle"] => TimeCommand::Toggle,
+            // A leading sign makes the position relative
+            ["seek", value] if value.starts_with(['+', '-']) => TimeCommand::Step(seconds(value)?),
+            ["seek", value] => TimeCommand::Seek(seconds(value)?.max(0.0)),
+            ["speed", value] => TimeCommand::Speed(
+                value.parse().ok().filter(|s: &f64| s.is_finite() && *s >= 0.0).ok_or_else(|| anyhow!("Invalid speed '{}'", value))?,
+            ),
+            _ => bail!("Unknown time command '{}' (expected pause, resume, toggle, seek or speed)", line.trim()),
+        };
+        Ok(command)
+    }
+}
+
+impl fmt::Display for TimeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeCommand::Pause => write!(f, "pause"),
+            TimeCommand::Resume => write!(f, "resume"),
+            TimeCommand::Toggle => write!(f, "toggle"),
+            TimeCommand::Seek(seconds) => write!(f, "seek {}", seconds),
+            TimeCommand::Step(seconds) => write!(f, "seek {:+}", seconds),
+            TimeCommand::Speed(speed) => write!(f, "speed {}", speed),
+        }
+    }
+}
+
+/// The time passed to the shaders.
+pub struct ShaderTime {
+    /// Shader time at `anchor`
+    anchor_time: f64,
+    anchor: Instant,
+    speed: f64,
+    paused: bool,
+}
+
+impl ShaderTime {
+    /// Starts at 0 and runs in real time.
+    pub fn new(now: Instant) -> Self {
+        Self { anchor_time: 0.0, anchor: now, speed: 1.0, paused: false }
+    }
+
+    /// Shader time in seconds.
+    pub fn time(&self, now: Instant) -> f64 {
+        if self.paused {
+            return self.anchor_time;
+        }
+        self.anchor_time + now.saturating_duration_since(self.anchor).as_secs_f64() * self.speed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Runs a command. Time never goes below 0.
+    pub fn handle(&mut self, command: TimeCommand, now: Instant) {
+        // Later changes apply from now on
+        self.anchor_time = self.time(now);
+        self.anchor = now;
+        match command {
+            TimeCommand::Pause => self.paused = true,
+            TimeCommand::Resume => self.paused = false,
+            TimeCommand::Toggle => self.paused = !self.paused,
+            TimeCommand::Seek(seconds) => self.anchor_time = seconds.max(0.0),
+            TimeCommand::Step(seconds) => self.anchor_time = (self.anchor_time + seconds).max(0.0),
+            TimeCommand::Speed(speed) => self.speed = speed,
+        }
+    }
+}
+
+impl fmt::Display for ShaderTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} s", self.time(Instant::now()))?;
+        if self.paused {
+            write!(f, " (paused)")
+        } else {
+            write!(f, " ({}x)", self.speed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pause_seek_speed() {
+        let start = Instant::now();
+        let at = |secs: f64| start + Duration::from_secs_f64(secs);
+        let mut time = ShaderTime::new(start);
+        assert_eq!(time.time(at(2.0)), 2.0);
+
+        time.handle(TimeCommand::Pause, at(2.0));
+        assert_eq!(time.time(at(5.0)), 2.0);
+        time.handle(TimeCommand::Step(-0.5), at(5.0));
+        assert_eq!(time.time(at(6.0)), 1.5);
+
+        time.handle(TimeCommand::Speed(0.5), at(6.0));
+        time.handle(TimeCommand::Resume, at(6.0));
+        assert_eq!(time.time(at(8.0)), 2.5);
+
+        time.handle(TimeCommand::Seek(10.0), at(8.0));
+        assert_eq!(time.time(at(8.0)), 10.0);
+        time.handle(TimeCommand::Step(-20.0), at(8.0));
+        assert_eq!(time.time(at(8.0)), 0.0);
+
+        for line in ["pause", "resume", "toggle", "seek 12.5", "seek -1.5", "seek +2", "speed 0.25"] {
+            assert_eq!(line.parse::<TimeCommand>().unwrap().to_string(), line);
+        }
+        assert!("speed -1".parse::<TimeCommand>().is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Wall clock time for overlays (the `clock` uniform and `proteus.clock`).
+//!
+//! The system clock is corrected by the offset measured against an NTP server
+//! (SNTP, RFC 4330), if one is configured, so machines showing the same time
+//! agree to a few milliseconds even when their own clocks drift.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{TimeZone, Timelike};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// Seconds from the NTP epoch (1900) to the Unix epoch (1970).
+const NTP_EPOCH_OFFSET: f64 = 2_208_988_800.0;
+/// How long to wait for an NTP response.
+const NTP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Queries per sync; the one with the shortest round trip is used.
+const NTP_SAMPLES: usize = 4;
+/// Wait before trying a failed sync again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where the wall clock comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallClockSettings {
+    /// NTP server (`host` or `host:port`); the system clock as is without one
+    pub ntp_server: Option<String>,
+    /// Time between NTP syncs
+    pub ntp_interval: Duration,
+    /// Frame rate of the timecode
+    pub timecode_fps: f32,
+}
+
+impl Default for WallClockSettings {
+    fn default() -> Self {
+        Self { ntp_server: None, ntp_interval: Duration::from_secs(300), timecode_fps: 30.0 }
+    }
+}
+
+/// Wall clock time of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WallTime {
+    /// Seconds since the Unix epoch (UTC)
+    pub unix: f64,
+    /// Seconds since local midnight
+    pub seconds_of_day: f64,
+    /// Whether the time is corrected by NTP
+    pub synced: bool,
+    /// Frame rate of `timecode()`
+    pub timecode_fps: f32,
+}
+
+impl WallTime {
+    /// Local time of day as `HH:MM:SS:FF`.
+    pub fn timecode(&self) -> String {
+        let seconds = self.seconds_of_day.max(0.0);
+        let whole = seconds.floor() as u64;
+        let fps = self.timecode_fps.max(1.0) as f64;
+        let frame = ((seconds - whole as f64) * fps).floor().min(fps.ceil() - 1.0) as u64;
+        format!("{:02}:{:02}:{:02}:{:02}", whole / 3600 % 24, whole / 60 % 60, whole % 60, frame)
+    }
+}
+
+/// Seconds since midnight in `timezone` at `unix` seconds.
+fn seconds_of_day<Tz: TimeZone>(unix: f64, timezone: &Tz) -> f64 {
+    let Some(time) = chrono::DateTime::from_timestamp(unix.floor() as i64, 0) else { return 0.0 };
+    time.with_timezone(timezone).num_seconds_from_midnight() as f64 + unix.rem_euclid(1.0)
+}
+
+/// The system clock, corrected by NTP in a background thread if configured.
+pub struct WallClock {
+    settings: WallClockSettings,
+    /// Seconds to add to the system clock, once synced
+    offset: Arc<Mutex<Option<f64>>>,
+    /// Stops the sync thread when dropped
+    _stop: Option<Sender<()>>,
+}
+
+impl WallClock {
+    pub fn new(settings: WallClockSettings) -> Self {
+        let offset = Arc::new(Mutex::new(None));
+        let stop = settings.ntp_server.clone().map(|server| {
+            let (stop_tx, stop_rx) = mpsc::channel::<()>();
+            let offset = offset.clone();
+            let interval = settings.ntp_interval;
+            thread::spawn(move || {
+                let mut failing = false;
+                loop {
+                    match sync(&server) {
+                        Ok((measured, delay)) => {
+                            let first = offset.lock().unwrap().replace(measured).is_none();
+                            if first || failing {
+                                info!("Wall clock synced with {} (offset {:+.3} s, round trip {:.0} ms)", server, measured, delay * 1000.0);
+                            } else {
+                                debug!("Wall clock offset {:+.4} s (round trip {:.0} ms)", measured, delay * 1000.0);
+                            }
+                            failing = false;
+                        }
+                        Err(e) => {
+                            if !failing {
+                                warn!("NTP sync with {} failed, retrying every {:?}: {}", server, RETRY_INTERVAL, e);
+                            }
+                            failing = true;
+                        }
+                    }
+                    let wait = if failing { RETRY_INTERVAL.min(interval) } else { interval };
+                    if stop_rx.recv_timeout(wait) != Err(RecvTimeoutError::Timeout) {
+                        break;
+                    }
+                }
+            });
+            stop_tx
+        });
+        Self { settings, offset, _stop: stop }
+    }
+
+    pub fn settings(&self) -> &WallClockSettings {
+        &self.settings
+    }
+
+    /// The corrected time now.
+    pub fn now(&self) -> WallTime {
+        let offset = *self.offset.lock().unwrap();
+        let system = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        let unix = system + offset.unwrap_or(0.0);
+        WallTime {
+            unix,
+            seconds_of_day: seconds_of_day(unix, &chrono::Local),
+            synced: offset.is_some(),
+            timecode_fps: self.settings.timecode_fps,
+        }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new(WallClockSettings::default())
+    }
+}
+
+/// Queries the server a few times and returns the offset and round trip of the
+/// fastest response, the least affected by network delays.
+fn sync(server: &str) -> Result<(f64, f64)> {
+    let address = resolve(server)?;
+    let socket = UdpSocket::bind(if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+    socket.set_read_timeout(Some(NTP_TIMEOUT))?;
+    socket.connect(address)?;
+
+    let mut best: Option<(f64, f64)> = None;
+    let mut error = None;
+    for _ in 0..NTP_SAMPLES {
+        match query(&socket) {
+            Ok(sample) if best.is_none_or(|(_, delay)| sample.1 < delay) => best = Some(sample),
+            Ok(_) => {}
+            Err(e) => error = Some(e),
+        }
+    }
+    best.ok_or_else(|| error.unwrap_or_else(|| anyhow!("No response")))
+}
+
+fn resolve(server: &str) -> Result<SocketAddr> {
+    let addresses = match server.to_socket_addrs() {
+        Ok(addresses) => addresses,
+        Err(_) => (server, 123).to_socket_addrs()?,
+    };
+    addresses.into_iter().next().ok_or_else(|| anyhow!("{} has no address", server))
+}
+
+fn query(socket: &UdpSocket) -> Result<(f64, f64)> {
+    let mut request = [0u8; 48];
+    // No leap second warning, version 4, client mode
+    request[0] = 0x23;
+    let sent = unix_now();
+    let transmit = to_ntp(sent);
+    request[40..48].copy_from_slice(&transmit.to_be_bytes());
+    socket.send(&request)?;
+
+    let started = Instant::now();
+    let mut response = [0u8; 48];
+    loop {
+        let length = socket.recv(&mut response)?;
+        // The round trip by the monotonic clock, in case the system clock steps meanwhile
+        let received = sent + started.elapsed().as_secs_f64();
+        // Late answers to an earlier query are skipped
+        if length >= 48 && response[24..32] == transmit.to_be_bytes() {
+            return parse_response(&response, sent, received);
+        }
+    }
+}
+
+/// Offset of the local clock from the server's and the round trip delay (in
+/// seconds) from a response to a request sent at `sent` and received at
+/// `received` (Unix seconds).
+fn parse_response(response: &[u8; 48], sent: f64, received: f64) -> Result<(f64, f64)> {
+    if response[0] & 0x07 != 4 {
+        bail!("Not a server response");
+    }
+    if response[0] >> 6 == 3 || response[1] == 0 {
+        bail!("Server is not synchronized");
+    }
+    let server_received = from_ntp(u64::from_be_bytes(response[32..40].try_into().unwrap()));
+    let server_sent = from_ntp(u64::from_be_bytes(response[40..48].try_into().unwrap()));
+    let offset = ((server_received - sent) + (server_sent - received)) / 2.0;
+    let delay = (received - sent) - (server_sent - server_received);
+    Ok((offset, delay.max(0.0)))
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+/// NTP timestamp (32.32 fixed point seconds since 1900) of Unix seconds.
+fn to_ntp(unix: f64) -> u64 {
+    let seconds = unix + NTP_EPOCH_OFFSET;
+    ((seconds.floor() as u64) << 32) | ((seconds.fract() * 4_294_967_296.0) as u64)
+}
+
+/// Unix seconds of an NTP timestamp.
+fn from_ntp(timestamp: u64) -> f64 {
+    (timestamp >> 32) as f64 + (timestamp & 0xffff_ffff) as f64 / 4_294_967_296.0 - NTP_EPOCH_OFFSET
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        assert!((from_ntp(to_ntp(1_760_620_800.25)) - 1_760_620_800.25).abs() < 1e-6);
+
+        // Server 1.5 s ahead, 40 ms each way, 10 ms to answer
+        let sent = 1_760_620_800.0;
+        let mut response = [0u8; 48];
+        response[0] = 0x24;
+        response[1] = 2;
+        response[32..40].copy_from_slice(&to_ntp(sent + 1.5 + 0.04).to_be_bytes());
+        response[40..48].copy_from_slice(&to_ntp(sent + 1.5 + 0.05).to_be_bytes());
+        let (offset, delay) = parse_response(&response, sent, sent + 0.09).unwrap();
+        assert!((offset - 1.5).abs() < 1e-4 && (delay - 0.08).abs() < 1e-4);
+
+        // Kiss-o'-death (stratum 0)
+        response[1] = 0;
+        assert!(parse_response(&response, sent, sent + 0.09).is_err());
+    }
+
+    #[test]
+    fn test_timecode() {
+        // 2025-10-16 13:45:07.5 UTC
+        let seconds = seconds_of_day(1_760_622_307.5, &chrono::Utc);
+        assert_eq!(seconds, 13.0 * 3600.0 + 45.0 * 60.0 + 7.5);
+        let time = WallTime { unix: 0.0, seconds_of_day: seconds, synced: true, timecode_fps: 25.0 };
+        assert_eq!(time.timecode(), "13:45:07:12");
+    }
+}
@@ -0,0 +1,220 @@
+//! A/B comparison of parameter snapshots.
+//!
+//! Two snapshots of the shader parameters (A and B) can be stored and then
+//! toggled, crossfaded, or mixed at any position, to evaluate grading choices
+//! quickly. Mixing blends the two parameter sets value by value.
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Crossfade duration used by `ab fade` without a duration.
+const DEFAULT_FADE: f32 = 1.0;
+
+/// One of the two snapshot slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbSlot {
+    A,
+    B,
+}
+
+impl AbSlot {
+    /// Mix position at which only this slot is shown.
+    fn position(self) -> f32 {
+        match self {
+            AbSlot::A => 0.0,
+            AbSlot::B => 1.0,
+        }
+    }
+}
+
+impl FromStr for AbSlot {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "a" | "A" => Ok(AbSlot::A),
+            "b" | "B" => Ok(AbSlot::B),
+            _ => bail!("Invalid snapshot '{}' (expected a or b)", name),
+        }
+    }
+}
+
+impl fmt::Display for AbSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AbSlot::A => "a",
+            AbSlot::B => "b",
+        })
+    }
+}
+
+/// An A/B compare command (`proteus ctl ab ...`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbCommand {
+    /// Store the current parameters as a snapshot
+    Store(AbSlot),
+    /// Cut to a snapshot
+    Show(AbSlot),
+    /// Cut to the other snapshot
+    Toggle,
+    /// Show a blend of both snapshots (0.0 = A, 1.0 = B)
+    Mix(f32),
+    /// Crossfade to the other snapshot over the given seconds
+    Fade(f32),
+}
+
+impl FromStr for AbCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let command = match args.as_slice() {
+            ["store", slot] => AbCommand::Store(slot.parse()?),
+            ["show", slot] => AbCommand::Show(slot.parse()?),
+            ["toggle"] => AbCommand::Toggle,
+            ["mix", position] => AbCommand::Mix(
+                position.parse().ok().filter(|p: &f32| (0.0..=1.0).contains(p)).ok_or_else(|| anyhow!("Invalid mix '{}' (expected 0.0 - 1.0)", position))?,
+            ),
+            ["fade"] => AbCommand::Fade(DEFAULT_FADE),
+            ["fade", seconds] => AbCommand::Fade(
+                seconds.parse().ok().filter(|s: &f32| *s >= 0.0).ok_or_else(|| anyhow!("Invalid duration '{}'", seconds))?,
+            ),
+            _ => bail!("Unknown A/B command '{}' (expected store, show, toggle, mix or fade)", line.trim()),
+        };
+        Ok(command)
+    }
+}
+
+impl fmt::Display for AbCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbCommand::Store(slot) => write!(f, "store {}", slot),
+            AbCommand::Show(slot) => write!(f, "show {}", slot),
+            AbCommand::Toggle => write!(f, "toggle"),
+            AbCommand::Mix(position) => write!(f, "mix {}", position),
+            AbCommand::Fade(seconds) => write!(f, "fade {}", seconds),
+        }
+    }
+}
+
+struct Fade {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Two parameter snapshots and the current mix between them.
+#[derive(Default)]
+pub struct AbCompare {
+    a: Option<HashMap<String, f32>>,
+    b: Option<HashMap<String, f32>>,
+    /// Mix position (0.0 = A, 1.0 = B)
+    position: f32,
+    fade: Option<Fade>,
+}
+
+impl AbCompare {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs a command against the current parameter values. Returns the
+    /// parameters to apply, if they change right away.
+    pub fn handle(&mut self, command: AbCommand, params: &HashMap<String, f32>, now: Instant) -> Result<Option<HashMap<String, f32>>> {
+        self.fade = None;
+        let target = match command {
+            AbCommand::Store(slot) => {
+                let snapshot = Some(params.clone());
+                match slot {
+                    AbSlot::A => self.a = snapshot,
+                    AbSlot::B => self.b = snapshot,
+                }
+                self.position = slot.position();
+                return Ok(None);
+            }
+            AbCommand::Show(slot) => slot.position(),
+            AbCommand::Toggle => self.other().position(),
+            AbCommand::Mix(position) => position,
+            AbCommand::Fade(seconds) => {
+                let to = self.other().position();
+                self.blend(self.position)?;
+                self.fade = Some(Fade { from: self.position, to, start: now, duration: Duration::from_secs_f32(seconds) });
+                return Ok(self.update(now));
+            }
+        };
+        let blended = self.blend(target)?;
+        self.position = target;
+        Ok(Some(blended))
+    }
+
+    /// Advances a running crossfade. Returns the blended parameters while fading.
+    pub fn update(&mut self, now: Instant) -> Option<HashMap<String, f32>> {
+        let fade = self.fade.as_ref()?;
+        let progress = if fade.duration.is_zero() {
+            1.0
+        } else {
+            (now.duration_since(fade.start).as_secs_f32() / fade.duration.as_secs_f32()).min(1.0)
+        };
+        self.position = fade.from + (fade.to - fade.from) * progress;
+        if progress >= 1.0 {
+            self.fade = None;
+        }
+        self.blend(self.position).ok()
+    }
+
+    /// The snapshot further away from the current mix position.
+    fn other(&self) -> AbSlot {
+        if self.position < 0.5 {
+            AbSlot::B
+        } else {
+            AbSlot::A
+        }
+    }
+
+    /// Blends the snapshots value by value. Parameters only in one snapshot keep their value.
+    fn blend(&self, position: f32) -> Result<HashMap<String, f32>> {
+        let (Some(a), Some(b)) = (&self.a, &self.b) else {
+            bail!("Store both snapshots first (ab store a, ab store b)");
+        };
+        let mut blended = a.clone();
+        for (name, &value_b) in b {
+            let value = match a.get(name) {
+                Some(&value_a) => value_a + (value_b - value_a) * position,
+                None => value_b,
+            };
+            blended.insert(name.clone(), value);
+        }
+        Ok(blended)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_and_fade() {
+        let mut compare = AbCompare::new();
+        let start = Instant::now();
+        let params = |v: f32| HashMap::from([("intensity".to_string(), v)]);
+
+        compare.handle(AbCommand::Store(AbSlot::A), &params(0.0), start).unwrap();
+        assert!(compare.handle(AbCommand::Toggle, &params(0.0), start).is_err());
+        compare.handle(AbCommand::Store(AbSlot::B), &params(1.0), start).unwrap();
+
+        // Stored B last, so toggling shows A
+        assert_eq!(compare.handle(AbCommand::Toggle, &params(1.0), start).unwrap(), Some(params(0.0)));
+        assert_eq!(compare.handle(AbCommand::Mix(0.25), &params(0.0), start).unwrap(), Some(params(0.25)));
+
+        // Fades from the current mix to B
+        compare.handle(AbCommand::Fade(1.0), &params(0.25), start).unwrap();
+        let halfway = compare.update(start + Duration::from_millis(500)).unwrap()["intensity"];
+        assert!((halfway - 0.625).abs() < 1e-6);
+        assert_eq!(compare.update(start + Duration::from_secs(2)), Some(params(1.0)));
+        assert_eq!(compare.update(start + Duration::from_secs(3)), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Versions of the config file format.
+//!
+//! A config file states its format with `version:`; files without one are
+//! version 1. Older files are upgraded on load, one version at a time, with a
+//! warning for every key that was renamed or moved, so existing configs keep
+//! working as the format changes. Migrations work on the parsed YAML before it
+//! becomes a `Config`.
+
+use anyhow::{anyhow, bail, Result};
+use serde_yaml::{Mapping, Value};
+
+/// Version of the config format of this release.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Upgrades of each version to the next, starting at version 1. A release that
+/// renames or moves keys bumps `CONFIG_VERSION` and adds its upgrade here.
+const MIGRATIONS: [fn(&mut Mapping, &mut Vec<String>); 1] = [migrate_v1];
+
+/// Version 2 renamed `shader` to `shaders`, as it lists the passes.
+fn migrate_v1(config: &mut Mapping, changes: &mut Vec<String>) {
+    rename(config, "shader", "shaders", changes);
+}
+
+/// Renames the key `from` to `to`. If both are set, `from` is dropped.
+fn rename(config: &mut Mapping, from: &str, to: &str, changes: &mut Vec<String>) {
+    let Some(value) = config.remove(from) else { return };
+    if config.contains_key(to) {
+        changes.push(format!("`{}` was renamed to `{}`; ignored, as `{}` is set", from, to, to));
+    } else {
+        config.insert(to.into(), value);
+        changes.push(format!("`{}` was renamed to `{}`", from, to));
+    }
+}
+
+/// Upgrades `config` to `CONFIG_VERSION`. Returns a description of each change.
+pub fn migrate(config: &mut Value) -> Result<Vec<String>> {
+    // An empty file
+    let Some(config) = config.as_mapping_mut() else { return Ok(Vec::new()) };
+    let version = match config.get("version") {
+        None => 1,
+        Some(value) => value
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| anyhow!("Invalid config version {:?}", value))?,
+    };
+    if version > CONFIG_VERSION {
+        bail!("Config version {} is newer than this release of Proteus supports ({})", version, CONFIG_VERSION);
+    }
+
+    let mut changes = Vec::new();
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(config, &mut changes);
+    }
+    config.insert("version".into(), CONFIG_VERSION.into());
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut config = yaml("recording:\n  metadata: true\n");
+        assert!(migrate(&mut config).unwrap().is_empty());
+        assert_eq!(config, yaml("version: 2\nrecording:\n  metadata: true\n"));
+        assert!(migrate(&mut yaml("version: 2\n")).unwrap().is_empty());
+
+        assert!(migrate(&mut yaml("version: 3\n")).is_err());
+        assert!(migrate(&mut yaml("version: 0\n")).is_err());
+        assert!(migrate(&mut yaml("version: zero\n")).is_err());
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        // Files without a version are version 1
+        let mut config = yaml("shader:\n  - shaders/crt.frag\nwidth: 1280\n");
+        assert_eq!(migrate(&mut config).unwrap(), vec!["`shader` was renamed to `shaders`"]);
+        assert_eq!(config, yaml("version: 2\nshaders:\n  - shaders/crt.frag\nwidth: 1280\n"));
+
+        // The new key wins if both are set
+        let mut config = yaml("version: 1\nshader: [a.frag]\nshaders: [b.frag]\n");
+        assert_eq!(migrate(&mut config).unwrap(), vec!["`shader` was renamed to `shaders`; ignored, as `shaders` is set"]);
+        assert_eq!(config, yaml("version: 2\nshaders: [b.frag]\n"));
+
+        // Version 2 files are left alone
+        let mut config = yaml("version: 2\nshader: [a.frag]\n");
+        assert!(migrate(&mut config).unwrap().is_empty());
+    }
+}
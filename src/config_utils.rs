@@ -1,25 +1,127 @@
-use crate::Config;
+use crate::paths;
+use crate::{SyncConfig, AccumulationModeConfig, AudioMeterConfig, CanvasResolutionConfig, CaptionPositionConfig, TransferConfig, CompositorLayerConfig, CompositorSourceConfig, CompositorStageConfig, Config, DataSourceConfig, ParticleEmitterConfig, ExecutionProviderConfig, GamepadMappingConfig, GuideConfig, InputSamplingConfig, PresentModeConfig, IdleModeConfig, RecordContainerConfig, VideoEncoderConfig, SourceTransformConfig, TextureInput, InterpolationConfig, LayoutMode, MixerConfig, MixerSourceConfig, ModulatorConfig, MonitorActionConfig, MonitorConfig, OutputFormatConfig, RedactionEffectConfig, SeedConfig, SinkConfig, SinksConfig, SeedModeConfig, ShaderEntry, TransitionStyleConfig, WatermarkPositionConfig};
+use proteus::audio::{AudioMeter, AudioSource};
 use proteus::capture::{AsyncCapture, CaptureConfig};
-use proteus::shader::{ShaderSource, TextureSlot};
+use proteus::output::window_output::{Guides, PresentMode};
+use proteus::output::idle::{IdleMode, IdleSettings};
+use proteus::output::pacing::OutputRates;
+use proteus::shader::{Accumulation, AccumulationMode, Bloom, Glyphs, Calibration, CaptionPosition, Captions, Transfer, Compositor, CompositorLayer, CompositorSource, CompositorStage, CompositorTransition, CursorOverlay, DataBuffer, DataSource, Decontamination, InputFilter, InputSampling, OutputLayout, ParticleEmitter, Particles, PassOptions, RedactionEffect, RedactionRule, RedactionSource, Region, RegionShape, SeedMode, ShaderCache, Sink, ShaderSource, SourceTransform, TextBlur, TextureSlot, TransitionStyle, Watermark, WatermarkPosition, Zoom, DATA_SLOTS, MAX_DATA_ITEMS, MAX_PARTICLES, MAX_RAMP_COLORS};
+use proteus::shader::modulation::Modulator;
 use proteus::video::VideoPlayer;
-use proteus::lua_canvas::LuaCanvas;
-use notify::{RecommendedWatcher, RecursiveMode, Watcher, Event};
-use std::path::PathBuf;
+use proteus::frame::{PixelFormat, VideoFrame};
+use proteus::gamepad::{GamepadControl, GamepadController, GamepadMapping, GamepadTarget};
+use proteus::interpolation::{FrameInterpolator, InterpolationMode};
+use proteus::lua_canvas::{CanvasOptions, LuaCanvas, MAX_SUPERSAMPLE};
+use proteus::ml::{ExecutionProvider, ExecutionProviders, ObjectModel, MAX_KEYPOINTS};
+use proteus::control::ControlCommand;
+use proteus::qr::QrSettings;
+use proteus::captions::speech::{SpeechSettings, SpeechToText};
+use proteus::obs::{ObsClient, ObsSettings};
+use proteus::mqtt::{MqttClient, MqttSettings};
+use proteus::streamdeck::{StreamDeckServer, StreamDeckSettings};
+use proteus::web::{WebServer, WebSettings};
+use proteus::sync::{SyncNode, SyncSettings};
+use proteus::clock::{WallClock, WallClockSettings};
+use proteus::recorder::clip::ClipSettings;
+use proteus::recorder::encoder::{RateControl, VideoEncoder};
+use proteus::recorder::{RecordContainer, RecordSettings};
+use proteus::gesture::{Gesture, GestureSettings};
+use proteus::mixer::{Mixer, MixerSource};
+use proteus::monitor::{FrameMonitor, MonitorAction, MonitorSettings};
+use proteus::schedule::{ScheduleRule, ScheduleWindow, Scheduler};
+use proteus::reload::{self, ReloadKind, Watch};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
 use std::fs;
-use tracing::{info, error};
+use tracing::{debug, info, error, warn};
 
 /// Result of comparing two Config instances.
 /// Used to determine what actions to take on config reload.
 #[derive(Debug)]
 pub struct ConfigDiff {
-    /// Changes that require a full restart (output, input, dimensions, fps)
+    /// Changes that require a full restart (output, input, dimensions, fps, priority, power)
     pub requires_restart: bool,
     /// Shader list changed (hot-reloadable)
     pub shader_changed: bool,
     /// Texture list changed (hot-reloadable)
     pub textures_changed: bool,
+    /// Tally state changed (applied live)
+    pub live_changed: bool,
+    /// Initial shader parameter values changed (applied live)
+    pub params_changed: bool,
+    /// Global modulators changed (applied live)
+    pub modulation_changed: bool,
+    /// Configured tempo changed (applied live)
+    pub bpm_changed: bool,
+    /// Wall clock settings changed (applied live)
+    pub wall_clock_changed: bool,
+    /// Output layout changed (applied live)
+    pub layout_changed: bool,
+    /// Watermark changed (applied live)
+    pub watermark_changed: bool,
+    /// Captions changed (applied live)
+    pub captions_changed: bool,
+    /// Speech recognition changed (restarted live)
+    pub speech_changed: bool,
+    /// Audio meter changed (restarted live)
+    pub audio_meter_changed: bool,
+    /// OBS connection or scene commands changed (reconnected live)
+    pub obs_changed: bool,
+    /// MQTT connection changed, or the presets offered to Home Assistant (reconnected live)
+    pub mqtt_changed: bool,
+    pub stream_deck_changed: bool,
+    /// Web UI changed (restarted live)
+    pub web_changed: bool,
+    /// Sync role or address changed (reconnected live)
+    pub sync_changed: bool,
+    /// Bloom changed (applied live)
+    pub particles_changed: bool,
+    pub bloom_changed: bool,
+    /// Glyph atlas characters or font changed (applied live)
+    pub glyphs_changed: bool,
+    /// Long-exposure accumulation changed (applied live)
+    pub accumulation_changed: bool,
+    /// Compositor layers changed (applied live)
+    pub compositor_changed: bool,
+    /// Cursor highlighting changed (applied live)
+    pub cursor_changed: bool,
+    /// Zoom changed (applied live)
+    pub zoom_changed: bool,
+    /// Visibility schedule changed (applied live)
+    pub schedule_changed: bool,
+    /// Edge decontamination changed (applied live)
+    pub decontamination_changed: bool,
+    /// Camera input sampling or sharpening changed (applied live)
+    pub input_filter_changed: bool,
+    /// Pose estimation model changed (applied live)
+    pub pose_changed: bool,
+    /// Text blur changed (applied live)
+    pub text_blur_changed: bool,
+    /// Mask regions changed (applied live)
+    pub regions_changed: bool,
+    pub buffers_changed: bool,
+    /// Redaction rules changed (applied live)
+    pub redaction_changed: bool,
+    /// QR code detection changed (applied live)
+    pub qr_changed: bool,
+    /// Gesture recognition changed (applied live)
+    pub gestures_changed: bool,
+    /// Preview guides changed (applied live)
+    pub guides_changed: bool,
+    /// Virtual camera idle mode changed (applied live)
+    pub idle_changed: bool,
+    /// Preview present mode or frame latency changed (applied live)
+    pub present_changed: bool,
+    /// Mirroring or color calibration of a sink changed (applied live)
+    pub sinks_changed: bool,
+    /// GPU YUV conversion toggled (applied live)
+    pub passthrough_changed: bool,
+    /// Frame interpolation changed (applied live)
+    pub interpolation_changed: bool,
+    /// Seed mode changed (applied live)
+    pub seed_changed: bool,
 }
 
 impl ConfigDiff {
@@ -27,17 +129,78 @@ impl ConfigDiff {
     pub fn compare(old: &crate::Config, new: &crate::Config) -> Self {
         let requires_restart = 
             old.output != new.output ||
+            old.output_format != new.output_format ||
             old.input != new.input ||
             old.width != new.width ||
             old.height != new.height ||
             old.max_input_width != new.max_input_width ||
             old.max_input_height != new.max_input_height ||
-            old.fps != new.fps;
+            old.fps != new.fps ||
+            old.output_fps != new.output_fps ||
+            old.program_window != new.program_window ||
+            old.priority != new.priority ||
+            old.gpu_power != new.gpu_power ||
+            old.shader_cache != new.shader_cache ||
+            old.background != new.background ||
+            old.chains.len() != new.chains.len() ||
+            old.mixer != new.mixer ||
+            old.monitor != new.monitor ||
+            old.fallback != new.fallback ||
+            old.splash != new.splash ||
+            old.persist_params != new.persist_params ||
+            old.link != new.link ||
+            old.gamepad != new.gamepad ||
+            old.input_color != new.input_color ||
+            old.input_mode != new.input_mode ||
+            old.input_rotation != new.input_rotation ||
+            old.execution_providers != new.execution_providers ||
+            old.openvino_device != new.openvino_device;
+        // The MQTT select and the Stream Deck keys list the preset names
+        let presets_renamed = old.presets.len() != new.presets.len() || !new.presets.keys().all(|name| old.presets.contains_key(name));
             
         Self {
             requires_restart,
-            shader_changed: old.shader != new.shader,
+            shader_changed: old.shaders != new.shaders,
             textures_changed: old.textures != new.textures,
+            live_changed: old.live != new.live,
+            params_changed: old.params != new.params,
+            modulation_changed: old.modulators != new.modulators,
+            bpm_changed: old.bpm != new.bpm,
+            wall_clock_changed: old.wall_clock != new.wall_clock,
+            layout_changed: old.layout != new.layout || old.grid != new.grid,
+            watermark_changed: old.watermark != new.watermark,
+            captions_changed: old.captions != new.captions,
+            speech_changed: old.speech != new.speech,
+            audio_meter_changed: old.audio_meter != new.audio_meter || (new.audio_meter.is_some() && old.textures != new.textures),
+            obs_changed: old.obs != new.obs,
+            mqtt_changed: old.mqtt != new.mqtt || (new.mqtt.is_some() && presets_renamed),
+            stream_deck_changed: old.stream_deck != new.stream_deck || (new.stream_deck.is_some() && presets_renamed),
+            web_changed: old.web != new.web,
+            sync_changed: old.sync != new.sync,
+            particles_changed: old.particles != new.particles,
+            bloom_changed: old.bloom != new.bloom,
+            glyphs_changed: old.glyphs != new.glyphs,
+            accumulation_changed: old.accumulation != new.accumulation,
+            compositor_changed: old.compositor != new.compositor,
+            cursor_changed: old.cursor != new.cursor,
+            zoom_changed: old.zoom != new.zoom,
+            schedule_changed: old.schedule != new.schedule,
+            decontamination_changed: old.decontamination != new.decontamination,
+            input_filter_changed: old.input_filter != new.input_filter,
+            pose_changed: old.pose_model != new.pose_model,
+            text_blur_changed: old.text_blur != new.text_blur,
+            regions_changed: old.regions != new.regions,
+            buffers_changed: old.buffers != new.buffers,
+            redaction_changed: old.redaction != new.redaction,
+            qr_changed: old.qr != new.qr,
+            gestures_changed: old.gestures != new.gestures,
+            guides_changed: old.guides != new.guides,
+            idle_changed: old.idle != new.idle,
+            present_changed: old.present_mode != new.present_mode || old.max_frame_latency != new.max_frame_latency,
+            sinks_changed: old.sinks != new.sinks,
+            passthrough_changed: old.input_passthrough != new.input_passthrough,
+            interpolation_changed: old.interpolation != new.interpolation,
+            seed_changed: old.seed != new.seed,
         }
     }
     
@@ -50,8 +213,7 @@ impl ConfigDiff {
 /// Manages configuration file watching and reloading.
 pub struct ConfigWatcher {
     path: PathBuf,
-    _watcher: RecommendedWatcher,
-    rx: Receiver<std::result::Result<Event, notify::Error>>,
+    watch: Watch,
     current_config: Option<Config>,
 }
 
@@ -59,86 +221,874 @@ impl ConfigWatcher {
     /// Create a new config watcher if a path is provided.
     pub fn new(path: Option<PathBuf>) -> Option<Self> {
         let path = path?;
-        let (tx, rx) = channel();
-        
-        match RecommendedWatcher::new(tx, notify::Config::default()) {
-            Ok(mut watcher) => {
-                if let Err(e) = watcher.watch(&path, RecursiveMode::NonRecursive) {
-                    tracing::warn!("Failed to watch config file {:?}: {}", path, e);
-                    return None;
-                }
-                info!("Watching config file {:?} for changes", path);
-                
-                // Load initial config
-                let current_config = match fs::read_to_string(&path) {
-                    Ok(content) => serde_yaml::from_str::<Config>(&content).ok(),
-                    Err(_) => None,
-                };
-
-                Some(Self {
-                    path,
-                    _watcher: watcher,
-                    rx,
-                    current_config,
-                })
+        let watch = reload::watch(ReloadKind::Config, [path.clone()]);
+        // Load initial config
+        let current_config = Self::load(&path).ok();
+        Some(Self { path, watch, current_config })
+    }
+
+    fn load(path: &Path) -> anyhow::Result<Config> {
+        let content = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Failed to read config file: {}", e))?;
+        let mut config = Config::parse(&content, path).map_err(|e| anyhow::anyhow!("Failed to parse config: {}", e))?;
+        config.expand_studio().map_err(|e| anyhow::anyhow!("Invalid studio: {}", e))?;
+        config.resolve_paths(Some(path));
+        Ok(config)
+    }
+
+    /// Check for changes and return (old_config, new_config) if changed.
+    pub fn check_for_changes(&mut self) -> Option<(Option<Config>, Config)> {
+        self.watch.poll()?;
+        info!("Config file changed, checking for updates...");
+        match Self::load(&self.path) {
+            Ok(new_config) => {
+                self.watch.report(&self.path, Ok(()));
+                let old = self.current_config.replace(new_config.clone());
+                Some((old, new_config))
             }
             Err(e) => {
-                tracing::warn!("Failed to create config watcher: {}", e);
+                self.watch.report(&self.path, Err(e));
                 None
             }
         }
     }
+}
 
-    /// Check for changes and return (old_config, new_config) if changed.
-    pub fn check_for_changes(&mut self) -> Option<(Option<Config>, Config)> {
-        let mut needs_reload = false;
-        while let Ok(res) = self.rx.try_recv() {
-            if let Ok(event) = res {
-                if matches!(event.kind, notify::EventKind::Modify(_) | notify::EventKind::Create(_)) {
-                    needs_reload = true;
+/// Interval between checks for parameter changes to save.
+const PARAM_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Saves shader parameters changed at runtime (control socket, gamepad, presets, ...)
+/// to a sidecar file, so they survive restarts. Only values that differ from the
+/// config's `params` are stored.
+pub struct ParamStore {
+    path: PathBuf,
+    saved: BTreeMap<String, f32>,
+    last_check: Instant,
+}
+
+impl ParamStore {
+    /// Opens the sidecar file, loading the values saved by the last run.
+    fn open(path: PathBuf) -> Self {
+        let saved = match fs::read_to_string(&path) {
+            Ok(content) => serde_yaml::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring invalid parameter file {:?}: {}", path, e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        if !saved.is_empty() {
+            info!("Restoring {} parameter value(s) from {:?}", saved.len(), path);
+        }
+        Self { path, saved, last_check: Instant::now() }
+    }
+
+    /// The values saved by the last run.
+    pub fn saved(&self) -> &BTreeMap<String, f32> {
+        &self.saved
+    }
+
+    /// Saves the parameters that differ from `defaults`, checking at most once per second.
+    pub fn update(&mut self, params: &HashMap<String, f32>, defaults: &HashMap<String, f32>) {
+        if self.last_check.elapsed() >= PARAM_SAVE_INTERVAL {
+            self.last_check = Instant::now();
+            self.save(params, defaults);
+        }
+    }
+
+    /// Saves the parameters that differ from `defaults`, if they changed since the last save.
+    pub fn save(&mut self, params: &HashMap<String, f32>, defaults: &HashMap<String, f32>) {
+        let changed: BTreeMap<String, f32> = params
+            .iter()
+            .filter(|(name, value)| defaults.get(*name) != Some(*value))
+            .map(|(name, value)| (name.clone(), *value))
+            .collect();
+        if changed == self.saved {
+            return;
+        }
+        match serde_yaml::to_string(&changed) {
+            Ok(yaml) => match fs::write(&self.path, yaml) {
+                Ok(()) => debug!("Saved {} parameter value(s) to {:?}", changed.len(), self.path),
+                Err(e) => warn!("Failed to save parameters to {:?}: {}", self.path, e),
+            },
+            Err(e) => warn!("Failed to serialize parameters: {}", e),
+        }
+        // Not retried on failure, to avoid a warning every second
+        self.saved = changed;
+    }
+}
+
+/// Helper to open the parameter sidecar file (`<config>.params.yaml`), if enabled.
+pub fn load_param_store(config: &Config) -> Option<ParamStore> {
+    if !config.persist_params {
+        return None;
+    }
+    let Some(config_path) = &config.config_path else {
+        warn!("persist_params requires a config file");
+        return None;
+    };
+    Some(ParamStore::open(config_path.with_extension("params.yaml")))
+}
+
+/// Helper to load shaders from config entries.
+/// Returns the loaded sources along with their pass options (index-aligned).
+pub fn load_shaders(entries: &[ShaderEntry]) -> (Vec<ShaderSource>, Vec<PassOptions>) {
+    if entries.is_empty() {
+        info!("Using passthrough shader");
+        return (Vec::new(), Vec::new());
+    }
+    
+    let mut shaders = Vec::new();
+    let mut options = Vec::new();
+    for entry in entries {
+        info!("Loading shader {}", entry.describe());
+        match entry.source().and_then(|source| Ok((source, entry.options()?))) {
+            Ok((source, pass_options)) => {
+                shaders.push(source);
+                options.push(pass_options);
+            }
+            Err(e) => error!("{}", e),
+        }
+    }
+    (shaders, options)
+}
+
+/// Converts the global modulator configs.
+pub fn load_modulators(config: &Config) -> Vec<Modulator> {
+    config.modulators.iter().map(ModulatorConfig::to_modulator).collect()
+}
+
+/// Converts the configured output layout.
+pub fn load_layout(config: &Config) -> OutputLayout {
+    match config.layout {
+        LayoutMode::Single => OutputLayout::Single,
+        LayoutMode::SideBySide => OutputLayout::SideBySide,
+        LayoutMode::Mirrored => OutputLayout::Mirrored,
+        LayoutMode::Grid => OutputLayout::Grid(config.grid),
+    }
+}
+
+/// Converts the configured preview guides.
+pub fn load_guides(config: &Config) -> Guides {
+    let mut guides = Guides::default();
+    for guide in &config.guides {
+        match guide {
+            GuideConfig::Thirds => guides.thirds = true,
+            GuideConfig::Center => guides.center = true,
+            GuideConfig::SafeArea => guides.safe_area = true,
+        }
+    }
+    guides
+}
+
+/// Converts the configured present mode of the preview window.
+pub fn load_present_mode(config: &Config) -> PresentMode {
+    match config.present_mode {
+        PresentModeConfig::AutoVsync => PresentMode::AutoVsync,
+        PresentModeConfig::Mailbox => PresentMode::Mailbox,
+        PresentModeConfig::Immediate => PresentMode::Immediate,
+    }
+}
+
+/// Converts the configured particle system.
+pub fn load_particles(config: &Config) -> Option<Particles> {
+    config.particles.as_ref().map(|particles| {
+        if particles.count > MAX_PARTICLES {
+            warn!("Limiting particles to {}", MAX_PARTICLES);
+        }
+        if particles.colors.len() > MAX_RAMP_COLORS {
+            warn!("Only the first {} particle colors are used", MAX_RAMP_COLORS);
+        }
+        Particles {
+            emitter: match particles.emitter {
+                ParticleEmitterConfig::MaskEdge => ParticleEmitter::MaskEdge,
+                ParticleEmitterConfig::Motion => ParticleEmitter::Motion,
+            },
+            count: particles.count.clamp(1, MAX_PARTICLES),
+            lifetime: particles.lifetime.max(0.01),
+            speed: particles.speed,
+            gravity: particles.gravity,
+            size: particles.size.max(0.0),
+            threshold: particles.threshold.clamp(0.0, 1.0),
+            colors: particles.colors.iter().take(MAX_RAMP_COLORS).copied().collect(),
+        }
+    })
+}
+
+/// Converts the configured bloom.
+pub fn load_bloom(config: &Config) -> Option<Bloom> {
+    config.bloom.as_ref().map(|bloom| Bloom {
+        threshold: bloom.threshold,
+        softness: bloom.softness,
+        intensity: bloom.intensity,
+        radius: bloom.radius,
+    })
+}
+
+/// Converts the configured glyph atlas characters.
+pub fn load_glyphs(config: &Config) -> Glyphs {
+    config.glyphs.as_ref().map_or_else(Glyphs::default, |glyphs| Glyphs { font: glyphs.font.clone(), characters: glyphs.characters.clone() })
+}
+
+/// Converts the configured long-exposure accumulation.
+pub fn load_accumulation(config: &Config) -> Option<Accumulation> {
+    config.accumulation.as_ref().map(|accumulation| Accumulation {
+        mode: match accumulation.mode {
+            AccumulationModeConfig::Average => AccumulationMode::Average,
+            AccumulationModeConfig::Max => AccumulationMode::Max,
+        },
+        frames: accumulation.frames,
+        decay: accumulation.decay,
+    })
+}
+
+/// Converts the configured cursor highlighting.
+pub fn load_cursor(config: &Config) -> Option<CursorOverlay> {
+    config.cursor.as_ref().map(|cursor| CursorOverlay {
+        dot_radius: cursor.dot_radius.max(0.0),
+        dot_color: cursor.dot_color,
+        ring_radius: cursor.ring_radius.max(0.0),
+        ring_width: cursor.ring_width.max(0.0),
+        ring_color: cursor.ring_color,
+        click_radius: cursor.click_radius.max(0.0),
+        click_duration: cursor.click_duration.max(0.0),
+        click_color: cursor.click_color,
+    })
+}
+
+/// Converts the configured zoom.
+pub fn load_zoom(config: &Config) -> Option<Zoom> {
+    config.zoom.as_ref().map(|zoom| Zoom {
+        level: zoom.level.max(1.0),
+        smoothing: zoom.smoothing.max(0.0),
+        active: zoom.active,
+        follow_cursor: zoom.follow_cursor,
+    })
+}
+
+/// Converts the configured compositor. Layers with an invalid texture slot are skipped.
+pub fn load_compositor(config: &Config) -> Option<Compositor> {
+    let compositor = config.compositor.as_ref()?;
+    Some(Compositor {
+        stage: match compositor.stage {
+            CompositorStageConfig::Before => CompositorStage::Before,
+            CompositorStageConfig::After => CompositorStage::After,
+        },
+        layers: load_compositor_layers(&compositor.layers),
+        layouts: compositor
+            .layouts
+            .iter()
+            .map(|(name, layers)| (name.clone(), load_compositor_layers(layers)))
+            .collect(),
+        transition: CompositorTransition {
+            style: match compositor.transition.style {
+                TransitionStyleConfig::Fade => TransitionStyle::Fade,
+                TransitionStyleConfig::Slide => TransitionStyle::Slide,
+                TransitionStyleConfig::Scale => TransitionStyle::Scale,
+            },
+            duration: compositor.transition.duration.max(0.0),
+        },
+        layout: None,
+    })
+}
+
+/// Creates the scheduler from the configured rules. Invalid rules are skipped with a warning.
+pub fn load_schedule(config: &Config) -> Scheduler {
+    let rules = config
+        .schedule
+        .iter()
+        .filter_map(|rule| {
+            let window = match (&rule.between, rule.every, rule.duration) {
+                (Some(range), None, _) => ScheduleWindow::between(range),
+                (None, Some(period), Some(duration)) => Ok(ScheduleWindow::Every { period, at: rule.at, duration }),
+                (None, Some(_), None) => Err(anyhow::anyhow!("'every' requires 'for'")),
+                _ => Err(anyhow::anyhow!("expected either 'between' or 'every'")),
+            };
+            match window {
+                Ok(window) => Some(ScheduleRule { target: rule.show.clone(), window }),
+                Err(e) => {
+                    warn!("Skipping schedule rule for '{}': {}", rule.show, e);
+                    None
                 }
             }
+        })
+        .collect();
+    Scheduler::new(rules)
+}
+
+fn load_compositor_layers(layers: &[CompositorLayerConfig]) -> Vec<CompositorLayer> {
+    layers
+        .iter()
+        .filter_map(|layer| {
+            let source = match layer.source {
+                CompositorSourceConfig::Camera => CompositorSource::Camera,
+                CompositorSourceConfig::Output => CompositorSource::Output,
+                CompositorSourceConfig::Texture if layer.slot < 4 => CompositorSource::Texture(layer.slot),
+                CompositorSourceConfig::Texture => {
+                    warn!("Skipping compositor layer: texture slot {} doesn't exist (0 - 3)", layer.slot);
+                    return None;
+                }
+            };
+            Some(CompositorLayer {
+                name: layer.name.clone(),
+                source,
+                rect: layer.rect,
+                crop: layer.crop,
+                z: layer.z,
+                opacity: layer.opacity,
+                radius: layer.radius,
+            })
+        })
+        .collect()
+}
+
+/// Converts the configured edge decontamination.
+pub fn load_decontamination(config: &Config) -> Option<Decontamination> {
+    config.decontamination.as_ref().map(|decontamination| Decontamination {
+        radius: decontamination.radius,
+        erode: decontamination.erode,
+    })
+}
+
+/// Converts the configured camera input filtering (linear sampling without sharpening by default).
+pub fn load_input_filter(config: &Config) -> InputFilter {
+    let Some(input_filter) = &config.input_filter else { return InputFilter::default() };
+    InputFilter {
+        sampling: match input_filter.sampling {
+            InputSamplingConfig::Linear => InputSampling::Linear,
+            InputSamplingConfig::Nearest => InputSampling::Nearest,
+        },
+        sharpen: input_filter.sharpen.max(0.0),
+    }
+}
+
+/// Converts the configured text blur.
+pub fn load_text_blur(config: &Config) -> Option<TextBlur> {
+    config.text_blur.as_ref().map(|text_blur| TextBlur {
+        model: text_blur.model.clone(),
+        threshold: text_blur.threshold.clamp(0.0, 1.0),
+        padding: text_blur.padding,
+        radius: text_blur.radius,
+    })
+}
+
+/// Converts the configured mask regions, skipping invalid ones.
+pub fn load_regions(config: &Config) -> Vec<Region> {
+    config
+        .regions
+        .iter()
+        .filter_map(|region| {
+            let shape = match (region.rect, &region.polygon) {
+                (Some([x, y, width, height]), None) => RegionShape::Rect { x, y, width, height },
+                (None, Some(points)) if points.len() >= 3 => RegionShape::Polygon(points.clone()),
+                (None, Some(_)) => {
+                    warn!("Ignoring region: a polygon needs at least 3 points");
+                    return None;
+                }
+                _ => {
+                    warn!("Ignoring region: expected either `rect` or `polygon`");
+                    return None;
+                }
+            };
+            Some(Region { shape, feather: region.feather.max(0.0) })
+        })
+        .collect()
+}
+
+/// Converts the configured data buffers, skipping those beyond the slots.
+pub fn load_data_buffers(config: &Config) -> Vec<DataBuffer> {
+    if config.buffers.len() > DATA_SLOTS {
+        warn!("Only the first {} data buffers are used", DATA_SLOTS);
+    }
+    let mut buffers: Vec<DataBuffer> = Vec::new();
+    for buffer in config.buffers.iter().take(DATA_SLOTS) {
+        if buffers.iter().any(|other| other.name == buffer.name) {
+            warn!("Data buffer '{}' is defined twice; only the first can be set by name", buffer.name);
         }
+        let (source, items) = match buffer.source {
+            DataSourceConfig::Values => (DataSource::Values, buffer.size.clamp(1, MAX_DATA_ITEMS)),
+            DataSourceConfig::Pose => {
+                if config.pose_model.is_none() {
+                    warn!("Data buffer '{}' reads the pose, but no `pose_model` is configured", buffer.name);
+                }
+                (DataSource::Pose, MAX_KEYPOINTS as u32)
+            }
+        };
+        buffers.push(DataBuffer { name: buffer.name.clone(), source, items, values: buffer.values.clone() });
+    }
+    buffers
+}
 
-        if needs_reload {
-            info!("Config file changed, checking for updates...");
-            match fs::read_to_string(&self.path) {
-                Ok(content) => match serde_yaml::from_str::<Config>(&content) {
-                    Ok(new_config) => {
-                        let old = self.current_config.clone();
-                        self.current_config = Some(new_config.clone());
-                        return Some((old, new_config));
-                    }
-                    Err(e) => error!("Failed to parse new config: {}", e),
+/// Converts the configured redaction rules, skipping invalid ones.
+pub fn load_redaction(config: &Config) -> Vec<RedactionRule> {
+    config
+        .redaction
+        .iter()
+        .filter_map(|rule| {
+            let source = match (rule.rect, rule.people, &rule.model) {
+                (Some([x, y, width, height]), false, None) => RedactionSource::Rect { x, y, width, height },
+                (None, true, None) => RedactionSource::People,
+                (None, false, Some(path)) => RedactionSource::Model(ObjectModel {
+                    path: path.clone(),
+                    threshold: rule.threshold.clamp(0.0, 1.0),
+                    class_count: rule.class_count,
+                }),
+                _ => {
+                    warn!("Ignoring redaction rule '{}': expected exactly one of `rect`, `people` or `model`", rule.name);
+                    return None;
+                }
+            };
+            Some(RedactionRule {
+                name: rule.name.clone(),
+                enabled: rule.enabled,
+                source,
+                effect: match rule.effect {
+                    RedactionEffectConfig::Pixelate => RedactionEffect::Pixelate,
+                    RedactionEffectConfig::Blur => RedactionEffect::Blur,
                 },
-                Err(e) => error!("Failed to read config file: {}", e),
+                strength: rule.strength.max(1.0),
+                padding: rule.padding.max(0.0),
+                classes: rule.classes.clone(),
+                except_largest: rule.except_largest,
+            })
+        })
+        .collect()
+}
+
+/// Converts the configured QR code detection settings.
+pub fn load_qr(config: &Config) -> Option<QrSettings> {
+    config.qr.as_ref().map(|qr| QrSettings {
+        interval: Duration::from_secs_f32(qr.interval.max(0.0)),
+        cooldown: Duration::from_secs_f32(qr.cooldown.max(0.0)),
+    })
+}
+
+/// Parses the control commands mapped to QR code payloads, skipping invalid ones.
+pub fn load_qr_actions(config: &Config) -> HashMap<String, ControlCommand> {
+    let Some(qr) = &config.qr else { return HashMap::new() };
+    qr.actions
+        .iter()
+        .filter_map(|(payload, command)| match command.parse() {
+            Ok(command) => Some((payload.clone(), command)),
+            Err(e) => {
+                warn!("Ignoring QR action for '{}': {}", payload, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Converts the configured gesture recognition settings.
+pub fn load_gestures(config: &Config) -> Option<GestureSettings> {
+    let gestures = config.gestures.as_ref()?;
+    if config.pose_model.is_none() {
+        warn!("Gesture recognition needs a pose model (`pose_model`)");
+    }
+    Some(GestureSettings {
+        min_score: gestures.min_score.clamp(0.0, 1.0),
+        hold: Duration::from_secs_f32(gestures.hold.max(0.0)),
+        cooldown: Duration::from_secs_f32(gestures.cooldown.max(0.0)),
+    })
+}
+
+/// Parses the control commands mapped to gestures, skipping invalid ones.
+pub fn load_gesture_actions(config: &Config) -> HashMap<Gesture, ControlCommand> {
+    let Some(gestures) = &config.gestures else { return HashMap::new() };
+    gestures
+        .actions
+        .iter()
+        .filter_map(|(gesture, command)| match (gesture.parse(), command.parse()) {
+            (Ok(gesture), Ok(command)) => Some((gesture, command)),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("Ignoring gesture action for '{}': {}", gesture, e);
+                None
             }
+        })
+        .collect()
+}
+
+/// Converts the configured execution provider order (platform default if empty).
+pub fn load_execution_providers(config: &Config) -> ExecutionProviders {
+    let mut providers = ExecutionProviders {
+        openvino_device: config.openvino_device.clone(),
+        tensorrt_cache: paths::cache_dir().map(|dir| dir.join("tensorrt")),
+        ..Default::default()
+    };
+    if !config.execution_providers.is_empty() {
+        providers.order = config.execution_providers.iter().map(|provider| match provider {
+            ExecutionProviderConfig::Tensorrt => ExecutionProvider::TensorRt,
+            ExecutionProviderConfig::Cuda => ExecutionProvider::Cuda,
+            ExecutionProviderConfig::Rocm => ExecutionProvider::Rocm,
+            ExecutionProviderConfig::Openvino => ExecutionProvider::OpenVino,
+            ExecutionProviderConfig::Directml => ExecutionProvider::DirectMl,
+            ExecutionProviderConfig::Coreml => ExecutionProvider::CoreMl,
+            ExecutionProviderConfig::Cpu => ExecutionProvider::Cpu,
+        }).collect();
+    }
+    providers
+}
+
+/// Creates the frame interpolator, if enabled.
+pub fn load_interpolation(mode: InterpolationConfig) -> Option<FrameInterpolator> {
+    match mode {
+        InterpolationConfig::Off => None,
+        InterpolationConfig::Blend => Some(FrameInterpolator::new(InterpolationMode::Blend)),
+        InterpolationConfig::Motion => Some(FrameInterpolator::new(InterpolationMode::Motion)),
+    }
+}
+
+/// Converts the configured watermark.
+pub fn load_watermark(config: &Config) -> Option<Watermark> {
+    config.watermark.as_ref().map(|watermark| Watermark {
+        path: watermark.path.clone(),
+        position: match watermark.position {
+            WatermarkPositionConfig::TopLeft => WatermarkPosition::TopLeft,
+            WatermarkPositionConfig::TopRight => WatermarkPosition::TopRight,
+            WatermarkPositionConfig::BottomLeft => WatermarkPosition::BottomLeft,
+            WatermarkPositionConfig::BottomRight => WatermarkPosition::BottomRight,
+            WatermarkPositionConfig::Center => WatermarkPosition::Center,
+        },
+        margin: watermark.margin,
+        scale: watermark.scale,
+        opacity: watermark.opacity,
+    })
+}
+
+/// Converts the configured captions.
+pub fn load_captions(config: &Config) -> Option<Captions> {
+    config.captions.as_ref().map(|captions| Captions {
+        srt: captions.srt.clone(),
+        font: captions.font.clone(),
+        size: captions.size.max(0.0),
+        color: captions.color,
+        background: captions.background,
+        position: match captions.position {
+            CaptionPositionConfig::Top => CaptionPosition::Top,
+            CaptionPositionConfig::Bottom => CaptionPosition::Bottom,
+        },
+        margin: captions.margin,
+        max_width: captions.max_width.clamp(0.0, 1.0),
+        live_duration: captions.live_duration.max(0.0),
+    })
+}
+
+/// Starts the configured OBS connection.
+pub fn load_obs(config: &Config) -> Option<ObsClient> {
+    let obs = config.obs.as_ref()?;
+    Some(ObsClient::start(ObsSettings { url: obs.url.clone(), password: obs.password.clone() }))
+}
+
+/// Connects to the configured MQTT broker. Errors are logged.
+pub fn load_mqtt(config: &Config) -> Option<MqttClient> {
+    let mqtt = config.mqtt.as_ref()?;
+    if let Some(rule) = &mqtt.privacy_rule {
+        if !config.redaction.iter().any(|redaction| &redaction.name == rule) {
+            warn!("MQTT privacy rule '{}' is not a redaction rule", rule);
+        }
+    }
+    let mut presets: Vec<String> = config.presets.keys().cloned().collect();
+    presets.sort();
+    let settings = MqttSettings {
+        host: mqtt.host.clone(),
+        port: mqtt.port,
+        username: mqtt.username.clone(),
+        password: mqtt.password.clone(),
+        topic: mqtt.topic.clone(),
+        discovery: mqtt.discovery,
+        privacy_rule: mqtt.privacy_rule.clone(),
+        presets,
+    };
+    match MqttClient::start(settings) {
+        Ok(mqtt) => Some(mqtt),
+        Err(e) => {
+            error!("MQTT disabled: {}", e);
+            None
         }
-        None
     }
 }
 
-/// Helper to load shaders from paths.
-pub fn load_shaders(paths: &[PathBuf]) -> Vec<ShaderSource> {
-    if paths.is_empty() {
-        info!("Using passthrough shader");
-        return Vec::new();
+/// The shader cache in the user cache directory, if enabled.
+pub fn load_shader_cache(config: &Config) -> Option<ShaderCache> {
+    if !config.shader_cache {
+        return None;
     }
-    
-    let mut shaders = Vec::new();
-    for path in paths {
-        info!("Loading shader from {:?}", path);
-        match fs::read_to_string(path) {
-            Ok(source) => shaders.push(ShaderSource::Glsl { code: source, path: Some(path.clone()) }),
-            Err(e) => error!("Failed to read shader {:?}: {}", path, e),
+    paths::cache_dir().map(|dir| ShaderCache::new(dir.join("shaders")))
+}
+
+/// Starts the Stream Deck server. Errors are logged.
+pub fn load_stream_deck(config: &Config) -> Option<StreamDeckServer> {
+    let stream_deck = config.stream_deck.as_ref()?;
+    for preset in stream_deck.icons.keys().filter(|preset| !config.presets.contains_key(*preset)) {
+        warn!("Stream Deck icon for unknown preset '{}'", preset);
+    }
+    let mut presets: Vec<String> = config.presets.keys().cloned().collect();
+    presets.sort();
+    let settings = StreamDeckSettings { port: stream_deck.port, presets, icons: stream_deck.icons.clone() };
+    match StreamDeckServer::start(settings) {
+        Ok(server) => Some(server),
+        Err(e) => {
+            error!("Stream Deck server disabled: {}", e);
+            None
+        }
+    }
+}
+
+/// Starts the web UI server. Errors are logged.
+pub fn load_web(config: &Config) -> Option<WebServer> {
+    let web = config.web.as_ref()?;
+    let settings = WebSettings {
+        address: web.address.clone(),
+        port: web.port,
+        token: web.token.clone().filter(|token| !token.is_empty()),
+        hosts: web.hosts.clone(),
+        preview_fps: web.preview_fps,
+        ranges: web.ranges.iter().map(|(name, [min, max])| (name.clone(), (*min, *max))).collect(),
+    };
+    match WebServer::start(settings) {
+        Ok(server) => Some(server),
+        Err(e) => {
+            error!("Web UI disabled: {}", e);
+            None
+        }
+    }
+}
+
+/// Starts the sync leader or follower. Errors are logged.
+pub fn load_sync(config: &Config) -> Option<SyncNode> {
+    let settings = match config.sync.as_ref()? {
+        SyncConfig::Leader { address, port } => SyncSettings::Leader { address: address.clone(), port: *port },
+        SyncConfig::Follower { leader } => SyncSettings::Follower { leader: leader.clone() },
+    };
+    match SyncNode::start(&settings) {
+        Ok(sync) => Some(sync),
+        Err(e) => {
+            error!("Sync disabled: {}", e);
+            None
+        }
+    }
+}
+
+/// Shortest time between NTP syncs, to go easy on public servers.
+const MIN_NTP_INTERVAL: f32 = 16.0;
+/// Longest time between NTP syncs.
+const MAX_NTP_INTERVAL: f32 = 86400.0;
+
+/// Starts the wall clock, synced with the NTP server if one is set.
+pub fn load_wall_clock(config: &Config) -> WallClock {
+    let wall_clock = &config.wall_clock;
+    let defaults = WallClockSettings::default();
+    let mut settings = WallClockSettings {
+        ntp_server: wall_clock.ntp.clone().filter(|server| !server.trim().is_empty()),
+        ntp_interval: defaults.ntp_interval,
+        timecode_fps: wall_clock.timecode_fps,
+    };
+    if (MIN_NTP_INTERVAL..=MAX_NTP_INTERVAL).contains(&wall_clock.ntp_interval) {
+        settings.ntp_interval = Duration::from_secs_f32(wall_clock.ntp_interval);
+    } else {
+        warn!("wall_clock.ntp_interval must be {} - {} s, syncing every {:?}", MIN_NTP_INTERVAL, MAX_NTP_INTERVAL, defaults.ntp_interval);
+    }
+    if wall_clock.timecode_fps.is_nan() || wall_clock.timecode_fps < 1.0 {
+        warn!("Invalid wall_clock.timecode_fps {}, using {}", wall_clock.timecode_fps, defaults.timecode_fps);
+        settings.timecode_fps = defaults.timecode_fps;
+    }
+    WallClock::new(settings)
+}
+
+/// Parses the control commands mapped to OBS scenes, skipping invalid ones.
+pub fn load_obs_scenes(config: &Config) -> HashMap<String, ControlCommand> {
+    let Some(obs) = &config.obs else { return HashMap::new() };
+    obs.scenes
+        .iter()
+        .filter_map(|(scene, command)| match command.parse() {
+            Ok(command) => Some((scene.clone(), command)),
+            Err(e) => {
+                warn!("Ignoring OBS scene command for '{}': {}", scene, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Frame rates of the outputs; unset ones use `fps`.
+pub fn load_output_rates(config: &Config) -> OutputRates {
+    let rate = |fps: Option<u32>| fps.unwrap_or(config.fps).max(1);
+    OutputRates {
+        preview: rate(config.output_fps.preview),
+        camera: rate(config.output_fps.camera),
+        recording: rate(config.output_fps.recording),
+    }
+}
+
+/// Converts the idle mode of the virtual camera.
+pub fn load_idle(config: &Config) -> IdleSettings {
+    IdleSettings {
+        mode: match config.idle.mode {
+            IdleModeConfig::Off => IdleMode::Off,
+            IdleModeConfig::LowFps => IdleMode::LowFps(config.idle.fps.max(1)),
+            IdleModeConfig::Suspend => IdleMode::Suspend,
+        },
+        delay: Duration::from_secs_f32(config.idle.delay.max(0.0)),
+    }
+}
+
+/// Converts how recordings are written.
+pub fn load_recording(config: &Config) -> RecordSettings {
+    RecordSettings {
+        container: match config.recording.container {
+            RecordContainerConfig::Mp4 => RecordContainer::Mp4,
+            RecordContainerConfig::Mkv => RecordContainer::Mkv,
+        },
+        fragment_interval: config.recording.fragment_interval.max(0.1),
+        encoder: match config.recording.encoder {
+            VideoEncoderConfig::Auto => VideoEncoder::Auto,
+            VideoEncoderConfig::X264 => VideoEncoder::X264,
+            VideoEncoderConfig::Nvenc => VideoEncoder::Nvenc,
+            VideoEncoderConfig::Qsv => VideoEncoder::Qsv,
+            VideoEncoderConfig::VideoToolbox => VideoEncoder::VideoToolbox,
+            VideoEncoderConfig::Amf => VideoEncoder::Amf,
+        },
+        rate_control: match (config.recording.quality, config.recording.bitrate) {
+            (Some(_), Some(bitrate)) => {
+                warn!("Recording quality and bitrate are both set, using the bitrate");
+                RateControl::Bitrate(bitrate)
+            }
+            (None, Some(bitrate)) => RateControl::Bitrate(bitrate),
+            (Some(quality), None) => RateControl::Quality(quality.min(51)),
+            (None, None) => RateControl::Default,
+        },
+    }
+}
+
+/// Helper to get the caps of `proteus ctl clip` clips.
+pub fn load_clip(config: &Config) -> ClipSettings {
+    ClipSettings {
+        fps: config.clip.fps.clamp(1, 50),
+        max_width: config.clip.max_width.max(16),
+        max_duration: config.clip.max_duration.max(0.1),
+    }
+}
+
+/// Starts the configured speech recognition. Errors are logged.
+pub fn load_speech(config: &Config) -> Option<SpeechToText> {
+    let speech = config.speech.as_ref()?;
+    if config.captions.is_none() {
+        warn!("Speech recognition needs `captions` to show its text");
+    }
+    let settings = SpeechSettings {
+        command: speech.command.clone(),
+        model: speech.model.clone(),
+        language: speech.language.clone(),
+        device: speech.device,
+        delay: speech.delay.max(0.0),
+        args: speech.args.clone(),
+    };
+    match SpeechToText::start(settings) {
+        Ok(speech) => Some(speech),
+        Err(e) => {
+            error!("Speech recognition disabled: {}", e);
+            None
+        }
+    }
+}
+
+pub fn load_audio_meter(config: &Config) -> Option<AudioMeter> {
+    let source = match config.audio_meter.as_ref()? {
+        AudioMeterConfig::Microphone { device } => AudioSource::Microphone(device.clone()),
+        AudioMeterConfig::Texture { slot } => match config.textures.get(*slot) {
+            Some(TextureInput::Video { path, .. }) => AudioSource::Video(path.to_string_lossy().into_owned()),
+            _ => {
+                error!("Audio meter disabled: texture {} is not a video", slot);
+                return None;
+            }
+        },
+    };
+    match AudioMeter::start(source) {
+        Ok(meter) => Some(meter),
+        Err(e) => {
+            error!("Audio meter disabled: {}", e);
+            None
+        }
+    }
+}
+
+/// Converts the calibration and transfer settings of a sink (`None` if it is left as is).
+pub fn load_calibration(sink: &SinkConfig) -> Option<Calibration> {
+    let transfer = match sink.transfer {
+        TransferConfig::Srgb => Transfer::Srgb,
+        TransferConfig::Linear => Transfer::Linear,
+    };
+    match &sink.calibration {
+        Some(calibration) => Some(Calibration {
+            gamma: calibration.gamma,
+            brightness: calibration.brightness,
+            contrast: calibration.contrast,
+            saturation: calibration.saturation,
+            lut: calibration.lut.clone(),
+            transfer,
+        }),
+        None if transfer != Transfer::Srgb => Some(Calibration { transfer, ..Calibration::default() }),
+        None => None,
+    }
+}
+
+/// Converts the calibration settings of the sinks read back from the pipeline.
+pub fn load_sink_calibrations(sinks: &SinksConfig) -> [(Sink, Option<Calibration>); 2] {
+    [
+        (Sink::VirtualCamera, load_calibration(&sinks.virtual_camera)),
+        (Sink::Recording, load_calibration(sinks.recording())),
+    ]
+}
+
+/// Reads the mirror flags of the sinks read back from the pipeline.
+pub fn load_sink_mirrors(sinks: &SinksConfig) -> [(Sink, bool); 2] {
+    [(Sink::VirtualCamera, sinks.virtual_camera.mirror), (Sink::Recording, sinks.recording().mirror)]
+}
+
+/// Sets up gamepad input from the mapping table. Invalid mappings are skipped with a warning.
+pub fn load_gamepad(mappings: &[GamepadMappingConfig]) -> Option<GamepadController> {
+    if mappings.is_empty() {
+        return None;
+    }
+
+    let mappings = mappings
+        .iter()
+        .filter_map(|mapping| {
+            let control = match mapping.input.parse::<GamepadControl>() {
+                Ok(control) => control,
+                Err(e) => {
+                    warn!("Skipping gamepad mapping: {}", e);
+                    return None;
+                }
+            };
+            let target = match (&mapping.param, &mapping.preset, &mapping.command) {
+                (Some(name), None, None) => GamepadTarget::Param { name: name.clone(), min: mapping.min, max: mapping.max },
+                (None, Some(name), None) => GamepadTarget::Preset(name.clone()),
+                (None, None, Some(command)) => match command.parse() {
+                    Ok(command) => GamepadTarget::Command(command),
+                    Err(e) => {
+                        warn!("Skipping gamepad mapping for '{}': {}", mapping.input, e);
+                        return None;
+                    }
+                },
+                _ => {
+                    warn!("Skipping gamepad mapping for '{}': set one of param, preset or command", mapping.input);
+                    return None;
+                }
+            };
+            Some(GamepadMapping { control, target })
+        })
+        .collect();
+
+    match GamepadController::new(mappings) {
+        Ok(gamepad) => Some(gamepad),
+        Err(e) => {
+            warn!("{}", e);
+            None
         }
     }
-    shaders
 }
 
 /// Helper to load texture sources from TextureInput list with specified canvas size.
 pub fn load_textures_with_size(
-    inputs: &[crate::TextureInput],
+    inputs: &[TextureInput],
     width: u32,
     height: u32,
     context: Option<Arc<proteus::shader::gpu_context::GpuContext>>,
@@ -149,7 +1099,7 @@ pub fn load_textures_with_size(
     for input in inputs {
         if texture_sources.len() >= 4 { break; }
         match input {
-            crate::TextureInput::Video { path } => {
+            TextureInput::Video { path, .. } => {
                 match VideoPlayer::new(path) {
                     Ok(player) => texture_sources.push(TextureSlot::Video(player)),
                     Err(e) => {
@@ -158,11 +1108,11 @@ pub fn load_textures_with_size(
                     }
                 }
             },
-            crate::TextureInput::Image { path } => {
+            TextureInput::Image { path, .. } => {
                 texture_sources.push(TextureSlot::Image(path.clone()));
             },
-            crate::TextureInput::Lua { path } => {
-                match LuaCanvas::new(path, width, height, device_queue.clone()) {
+            TextureInput::Lua { path, resolution, .. } => {
+                match LuaCanvas::new(path, width, height, load_canvas_options(*resolution), device_queue.clone()) {
                     Ok(canvas) => texture_sources.push(TextureSlot::LuaCanvas(canvas)),
                     Err(e) => {
                         error!("Failed to create Lua canvas {:?}: {}", path, e);
@@ -175,6 +1125,36 @@ pub fn load_textures_with_size(
     texture_sources
 }
 
+fn load_canvas_options(resolution: CanvasResolutionConfig) -> CanvasOptions {
+    let supersample = resolution.supersample.clamp(1, MAX_SUPERSAMPLE);
+    if supersample != resolution.supersample {
+        warn!("Lua canvas supersample {} is out of range (1 - {}), using {}", resolution.supersample, MAX_SUPERSAMPLE, supersample);
+    }
+    let logical_size = resolution.logical_size.and_then(|[width, height]| {
+        if width == 0 || height == 0 {
+            warn!("Ignoring empty Lua canvas logical size {}x{}", width, height);
+            return None;
+        }
+        Some((width, height))
+    });
+    CanvasOptions { supersample, logical_size }
+}
+
+fn load_source_transform(transform: SourceTransformConfig) -> SourceTransform {
+    SourceTransform {
+        crop: transform.crop,
+        scale: transform.scale,
+        offset: transform.offset,
+        rotation: transform.rotation,
+        opacity: transform.opacity,
+    }
+}
+
+/// Helper to get the crop and transform of each texture slot.
+pub fn load_texture_transforms(inputs: &[TextureInput]) -> Vec<SourceTransform> {
+    inputs.iter().map(|input| load_source_transform(input.transform())).collect()
+}
+
 /// Helper to initialize camera.
 pub fn init_capture(config: CaptureConfig) -> Option<AsyncCapture> {
     match AsyncCapture::new(config) {
@@ -185,3 +1165,114 @@ pub fn init_capture(config: CaptureConfig) -> Option<AsyncCapture> {
         }
     }
 }
+
+/// Interval between attempts to open a camera that was unavailable.
+pub const CAPTURE_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Helper to open a named mixer source with its transform. Returns None (and logs) if it fails to open.
+fn load_mixer_source(source: &MixerSourceConfig, config: &Config) -> Option<(String, MixerSource, SourceTransform)> {
+    let (name, mixer_source) = match source {
+        MixerSourceConfig::Camera { input, .. } => {
+            info!("Opening mixer camera {}...", input);
+            let capture = init_capture(config.capture_config(input))?;
+            Some((format!("camera {}", input), MixerSource::Camera(capture)))
+        }
+        MixerSourceConfig::Video { path, .. } => match VideoPlayer::new(path) {
+            Ok(player) => Some((path.display().to_string(), MixerSource::Video(player))),
+            Err(e) => {
+                error!("Failed to open mixer video {:?}: {}", path, e);
+                None
+            }
+        },
+        MixerSourceConfig::Image { path, .. } => match image::open(path) {
+            Ok(img) => {
+                let rgba = img.to_rgba8();
+                let frame = VideoFrame::from_data(rgba.width(), rgba.height(), PixelFormat::Rgba, rgba.into_raw());
+                Some((path.display().to_string(), MixerSource::Image(frame)))
+            }
+            Err(e) => {
+                error!("Failed to open mixer image {:?}: {}", path, e);
+                None
+            }
+        },
+        MixerSourceConfig::Lua { path, resolution, .. } => match LuaCanvas::new(path, config.width, config.height, load_canvas_options(*resolution), None) {
+            Ok(canvas) => Some((path.display().to_string(), MixerSource::Lua(Box::new(canvas)))),
+            Err(e) => {
+                error!("Failed to load mixer Lua scene {:?}: {}", path, e);
+                None
+            }
+        },
+        MixerSourceConfig::TestPattern => Some(("test pattern".to_string(), MixerSource::TestPattern)),
+    }?;
+    Some((name, mixer_source, load_source_transform(source.transform())))
+}
+
+/// Helper to build the input mixer. Sources that fail to open are skipped.
+pub fn load_mixer(mixer: &MixerConfig, config: &Config) -> Mixer {
+    let sources: Vec<_> = mixer
+        .sources
+        .iter()
+        .filter_map(|source| load_mixer_source(source, config))
+        .collect();
+    info!("Mixer initialized with {} source(s)", sources.len());
+    Mixer::new(sources, config.width, config.height, mixer.transition)
+}
+
+/// Helper to build the fallback shown while the camera is unavailable, as a single-source mixer.
+pub fn load_fallback(fallback: &MixerSourceConfig, config: &Config) -> Option<Mixer> {
+    let source = load_mixer_source(fallback, config)?;
+    info!("Showing fallback source: {}", source.0);
+    Some(Mixer::new(vec![source], config.width, config.height, 0.0))
+}
+
+/// Helper to convert the `seed` config.
+pub fn load_seed(config: &Config) -> SeedMode {
+    match config.seed {
+        SeedConfig::Mode(SeedModeConfig::Random) => SeedMode::Random,
+        SeedConfig::Mode(SeedModeConfig::Frame) => SeedMode::Frame,
+        SeedConfig::Fixed(value) => SeedMode::Fixed(value),
+    }
+}
+
+/// Helper to get the configured virtual camera pixel format.
+pub fn load_output_format(config: &Config) -> Option<PixelFormat> {
+    config.output_format.map(|format| match format {
+        OutputFormatConfig::Yuyv => PixelFormat::Yuyv,
+        OutputFormatConfig::Uyvy => PixelFormat::Uyvy,
+        OutputFormatConfig::Nv12 => PixelFormat::Nv12,
+        OutputFormatConfig::Rgba => PixelFormat::Rgba,
+    })
+}
+
+/// Helper to build the startup splash, shown until the camera delivers its first frame.
+pub fn load_splash(config: &Config) -> Option<Mixer> {
+    let source = load_mixer_source(config.splash.as_ref()?, config)?;
+    info!("Showing splash: {}", source.0);
+    Some(Mixer::new(vec![source], config.width, config.height, 0.0))
+}
+
+/// Helper to build the black / frozen frame monitor. The `fallback` action cuts to the
+/// named mixer source when one is available, and shows color bars otherwise.
+pub fn load_monitor(monitor: &MonitorConfig, mixer: Option<&Mixer>) -> FrameMonitor {
+    let action = match monitor.action {
+        MonitorActionConfig::Log => MonitorAction::Log,
+        MonitorActionConfig::Overlay => MonitorAction::Overlay,
+        MonitorActionConfig::Fallback => {
+            let index = mixer.zip(monitor.fallback.as_deref()).and_then(|(mixer, name)| {
+                let index = (0..mixer.len()).find(|&i| mixer.source_name(i) == Some(name));
+                if index.is_none() {
+                    warn!("Monitor fallback source '{}' not found in mixer, using color bars", name);
+                }
+                index
+            });
+            index.map_or(MonitorAction::TestPattern, MonitorAction::SwitchSource)
+        }
+    };
+    let settings = MonitorSettings {
+        duration: monitor.duration,
+        black_threshold: monitor.black_threshold,
+        tolerance: monitor.tolerance,
+    };
+    info!("Monitor enabled ({:?} after {:.1}s)", action, settings.duration);
+    FrameMonitor::new(settings, action)
+}
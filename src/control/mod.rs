@@ -0,0 +1,520 @@
+//! Local control socket for a running instance.
+//!
+//! A running Proteus listens on a per-user Unix socket (a loopback TCP port on
+//! Windows) for one-line text commands, as sent by `proteus ctl <command>`. Each
+//! command is answered with `ok [message]` or `error: <message>`. A line that
+//! isn't a command closes the connection, so that e.g. a web page posting to
+//! the loopback port can't smuggle commands in after its HTTP headers.
+
+use crate::clock::TimeCommand;
+use crate::compare::AbCommand;
+use crate::events;
+use crate::obs::ObsCommand;
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How long a client waits for the render loop to handle a command.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest accepted command line
+const MAX_LINE: u64 = 64 * 1024;
+/// Wait after a failed accept before trying again
+const ACCEPT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Loopback port used for control on platforms without Unix sockets.
+#[cfg(not(unix))]
+pub const CONTROL_PORT: u16 = 47319;
+
+/// A command accepted on the control socket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// Set a named shader parameter
+    SetUniform { name: String, value: f32 },
+    /// Set the values of a data buffer (4 per item; none to clear it)
+    SetBuffer { name: String, values: Vec<f32> },
+    /// Apply a parameter preset from the config
+    SwitchPreset(String),
+    /// Revert the last parameter or preset change
+    Undo,
+    /// Reapply the last undone parameter change
+    Redo,
+    /// Store, toggle, or blend the A/B parameter snapshots
+    Ab(AbCommand),
+    /// Save the current output as an image (default: timestamped PNG)
+    Snapshot(Option<PathBuf>),
+    /// Start recording the output (default: timestamped MP4)
+    RecordStart(Option<PathBuf>),
+    /// Stop recording
+    RecordStop,
+    /// Start or stop recording
+    RecordToggle,
+    /// Save the next seconds of output as a GIF or APNG (default: 3 s, timestamped GIF)
+    Clip { seconds: Option<f32>, path: Option<PathBuf> },
+    /// Set the tally state
+    SetLive(bool),
+    /// Set the clock tempo
+    SetBpm(f32),
+    /// Tap tempo
+    Tap,
+    /// Pause, scrub, or change the speed of the shader time
+    Time(TimeCommand),
+    /// Crossfade the mixer to a source (1-based, as with the number keys)
+    SwitchSource(usize),
+    /// Switch the primary camera to a device (index or name)
+    SwitchInput(String),
+    /// List the connected cameras
+    Devices,
+    /// Switch the compositor to a named layout
+    SwitchLayout(String),
+    /// Zoom in (`Some(true)`), out, or toggle (`None`)
+    Zoom(Option<bool>),
+    /// Move the zoom to a point in 0.0 - 1.0 frame coordinates (e.g. from a cursor tracker)
+    ZoomAt { x: f32, y: f32 },
+    /// Move the highlighted cursor (and a zoom following it) to a point in
+    /// 0.0 - 1.0 frame coordinates, or hide it (`None`)
+    Cursor(Option<[f32; 2]>),
+    /// Show a click ripple at the cursor
+    Click,
+    /// Show a live caption (e.g. from speech-to-text), or remove it (`None`)
+    Caption(Option<String>),
+    /// Switch a redaction rule on (`Some(true)`), off, or toggle it (`None`)
+    Redact { rule: String, enabled: Option<bool> },
+    /// Switch the scene or start/stop recording or streaming in OBS
+    Obs(ObsCommand),
+    /// Post a named event with a JSON payload to the Lua scripts
+    Event { name: String, payload: Value },
+    /// Report the frame rates, times and drops of the pipeline stages
+    Stats,
+    /// Reload the config file, shaders, Lua scripts and image textures
+    Reload,
+    /// Report the last reload of each file and whether it worked
+    ReloadStatus,
+    /// Save the runtime state to a session file (default: timestamped YAML)
+    SaveSession(Option<PathBuf>),
+    /// Restore the runtime state from a session file
+    LoadSession(PathBuf),
+    /// Shut down the instance
+    Quit,
+}
+
+impl FromStr for ControlCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let command = match args.as_slice() {
+            ["set-uniform", name, value] => ControlCommand::SetUniform {
+                name: name.to_string(),
+                value: value.parse().map_err(|_| anyhow!("Invalid value '{}'", value))?,
+            },
+            ["set-buffer", name, values @ ..] => ControlCommand::SetBuffer {
+                name: name.to_string(),
+                values: values.iter().map(|value| value.parse().map_err(|_| anyhow!("Invalid value '{}'", value))).collect::<Result<_>>()?,
+            },
+            ["switch-preset", name] => ControlCommand::SwitchPreset(name.to_string()),
+            ["undo"] => ControlCommand::Undo,
+            ["redo"] => ControlCommand::Redo,
+            ["ab", rest @ ..] => ControlCommand::Ab(rest.join(" ").parse()?),
+            ["snapshot"] => ControlCommand::Snapshot(None),
+            ["snapshot", path] => ControlCommand::Snapshot(Some(PathBuf::from(path))),
+            ["record", "start"] => ControlCommand::RecordStart(None),
+            ["record", "start", path] => ControlCommand::RecordStart(Some(PathBuf::from(path))),
+            ["record", "stop"] => ControlCommand::RecordStop,
+            ["record", "toggle"] => ControlCommand::RecordToggle,
+            ["clip"] => ControlCommand::Clip { seconds: None, path: None },
+            ["clip", arg] => match arg.parse::<f32>() {
+                Ok(seconds) => ControlCommand::Clip { seconds: Some(clip_seconds(seconds, arg)?), path: None },
+                Err(_) => ControlCommand::Clip { seconds: None, path: Some(PathBuf::from(arg)) },
+            },
+            ["clip", seconds, path] => ControlCommand::Clip {
+                seconds: Some(clip_seconds(seconds.parse().map_err(|_| anyhow!("Invalid clip length '{}'", seconds))?, seconds)?),
+                path: Some(PathBuf::from(path)),
+            },
+            ["set-live", state] => ControlCommand::SetLive(match *state {
+                "on" | "true" | "1" => true,
+                "off" | "false" | "0" => false,
+                _ => bail!("Invalid live state '{}' (expected on or off)", state),
+            }),
+            ["set-bpm", bpm] => ControlCommand::SetBpm(
+                bpm.parse().ok().filter(|b: &f32| *b > 0.0).ok_or_else(|| anyhow!("Invalid tempo '{}'", bpm))?,
+            ),
+            ["tap"] => ControlCommand::Tap,
+            ["time", rest @ ..] => ControlCommand::Time(rest.join(" ").parse()?),
+            ["switch-source", index] => ControlCommand::SwitchSource(
+                index.parse().ok().filter(|&i| i > 0).ok_or_else(|| anyhow!("Invalid source '{}'", index))?,
+            ),
+            ["switch-input", name @ ..] if !name.is_empty() => ControlCommand::SwitchInput(name.join(" ")),
+            ["devices"] => ControlCommand::Devices,
+            ["switch-layout", name] => ControlCommand::SwitchLayout(name.to_string()),
+            ["zoom", state] => ControlCommand::Zoom(match *state {
+                "on" | "in" => Some(true),
+                "off" | "out" => Some(false),
+                "toggle" => None,
+                _ => bail!("Invalid zoom state '{}' (expected on, off or toggle)", state),
+            }),
+            ["zoom-at", x, y] => ControlCommand::ZoomAt { x: coordinate(x)?, y: coordinate(y)? },
+            ["cursor", "hide"] => ControlCommand::Cursor(None),
+            ["cursor", x, y] => ControlCommand::Cursor(Some([coordinate(x)?, coordinate(y)?])),
+            ["click"] => ControlCommand::Click,
+            ["caption", "clear"] => ControlCommand::Caption(None),
+            ["caption", text @ ..] if !text.is_empty() => ControlCommand::Caption(Some(text.join(" "))),
+            ["redact", rule, state] => ControlCommand::Redact {
+                rule: rule.to_string(),
+                enabled: match *state {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    "toggle" => None,
+                    _ => bail!("Invalid redaction state '{}' (expected on, off or toggle)", state),
+                },
+            },
+            ["obs", rest @ ..] => ControlCommand::Obs(rest.join(" ").parse()?),
+            // The payload is taken from the line as is, since JSON strings may contain spaces
+            ["event", name, ..] => ControlCommand::Event {
+                name: name.to_string(),
+                payload: events::parse_payload(line.trim_start()["event".len()..].trim_start()[name.len()..].trim()),
+            },
+            ["stats"] => ControlCommand::Stats,
+            ["reload"] => ControlCommand::Reload,
+            ["reload", "status"] => ControlCommand::ReloadStatus,
+            ["session", "save"] => ControlCommand::SaveSession(None),
+            ["session", "save", path] => ControlCommand::SaveSession(Some(PathBuf::from(path))),
+            ["session", "load", path] => ControlCommand::LoadSession(PathBuf::from(path)),
+            ["quit"] => ControlCommand::Quit,
+            [] => bail!("Empty command"),
+            _ => bail!("Unknown command '{}'", line.trim()),
+        };
+        Ok(command)
+    }
+}
+
+impl fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlCommand::SetUniform { name, value } => write!(f, "set-uniform {} {}", name, value),
+            ControlCommand::SetBuffer { name, values } => {
+                write!(f, "set-buffer {}", name)?;
+                for value in values {
+                    write!(f, " {}", value)?;
+                }
+                Ok(())
+            }
+            ControlCommand::SwitchPreset(name) => write!(f, "switch-preset {}", name),
+            ControlCommand::Undo => write!(f, "undo"),
+            ControlCommand::Redo => write!(f, "redo"),
+            ControlCommand::Ab(command) => write!(f, "ab {}", command),
+            ControlCommand::Snapshot(None) => write!(f, "snapshot"),
+            ControlCommand::Snapshot(Some(path)) => write!(f, "snapshot {}", path.display()),
+            ControlCommand::RecordStart(None) => write!(f, "record start"),
+            ControlCommand::RecordStart(Some(path)) => write!(f, "record start {}", path.display()),
+            ControlCommand::RecordStop => write!(f, "record stop"),
+            ControlCommand::RecordToggle => write!(f, "record toggle"),
+            ControlCommand::Clip { seconds, path } => {
+                write!(f, "clip")?;
+                if let Some(seconds) = seconds {
+                    write!(f, " {}", seconds)?;
+                }
+                if let Some(path) = path {
+                    write!(f, " {}", path.display())?;
+                }
+                Ok(())
+            }
+            ControlCommand::SetLive(live) => write!(f, "set-live {}", if *live { "on" } else { "off" }),
+            ControlCommand::SetBpm(bpm) => write!(f, "set-bpm {}", bpm),
+            ControlCommand::Tap => write!(f, "tap"),
+            ControlCommand::Time(command) => write!(f, "time {}", command),
+            ControlCommand::SwitchSource(index) => write!(f, "switch-source {}", index),
+            ControlCommand::SwitchInput(name) => write!(f, "switch-input {}", name),
+            ControlCommand::Devices => write!(f, "devices"),
+            ControlCommand::SwitchLayout(name) => write!(f, "switch-layout {}", name),
+            ControlCommand::Zoom(state) => write!(f, "zoom {}", match state {
+                Some(true) => "on",
+                Some(false) => "off",
+                None => "toggle",
+            }),
+            ControlCommand::ZoomAt { x, y } => write!(f, "zoom-at {} {}", x, y),
+            ControlCommand::Cursor(None) => write!(f, "cursor hide"),
+            ControlCommand::Cursor(Some([x, y])) => write!(f, "cursor {} {}", x, y),
+            ControlCommand::Click => write!(f, "click"),
+            ControlCommand::Caption(None) => write!(f, "caption clear"),
+            ControlCommand::Caption(Some(text)) => write!(f, "caption {}", text),
+            ControlCommand::Redact { rule, enabled } => write!(f, "redact {} {}", rule, match enabled {
+                Some(true) => "on",
+                Some(false) => "off",
+                None => "toggle",
+            }),
+            ControlCommand::Obs(command) => write!(f, "obs {}", command),
+            ControlCommand::Event { name, payload: Value::Null } => write!(f, "event {}", name),
+            ControlCommand::Event { name, payload } => write!(f, "event {} {}", name, payload),
+            ControlCommand::Stats => write!(f, "stats"),
+            ControlCommand::Reload => write!(f, "reload"),
+            ControlCommand::ReloadStatus => write!(f, "reload status"),
+            ControlCommand::SaveSession(None) => write!(f, "session save"),
+            ControlCommand::SaveSession(Some(path)) => write!(f, "session save {}", path.display()),
+            ControlCommand::LoadSession(path) => write!(f, "session load {}", path.display()),
+            ControlCommand::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl ControlCommand {
+    /// The file the command reads or writes, if it names one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ControlCommand::Snapshot(path) | ControlCommand::RecordStart(path) | ControlCommand::Clip { path, .. } | ControlCommand::SaveSession(path) => path.as_deref(),
+            ControlCommand::LoadSession(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a coordinate in 0.0 - 1.0 frame coordinates.
+fn coordinate(value: &str) -> Result<f32> {
+    value.parse().ok().filter(|v| (0.0..=1.0).contains(v)).ok_or_else(|| anyhow!("Invalid coordinate '{}' (expected 0 - 1)", value))
+}
+
+/// Checks a clip length in seconds.
+fn clip_seconds(seconds: f32, value: &str) -> Result<f32> {
+    if seconds > 0.0 && seconds.is_finite() { Ok(seconds) } else { bail!("Invalid clip length '{}'", value) }
+}
+
+/// A command received from a client, to be answered by the render loop.
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    reply_tx: Sender<String>,
+}
+
+impl ControlRequest {
+    /// Creates a request for another front end (e.g. the Stream Deck server); the
+    /// reply line (`ok [message]` or `error: <message>`) arrives on the receiver.
+    pub fn new(command: ControlCommand) -> (Self, Receiver<String>) {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        (Self { command, reply_tx }, reply_rx)
+    }
+
+    /// Sends the result back to the client.
+    pub fn reply(self, result: Result<String>) {
+        let line = match result {
+            Ok(message) if message.is_empty() => "ok".to_string(),
+            Ok(message) => format!("ok {}", message),
+            Err(e) => format!("error: {}", e),
+        };
+        let _ = self.reply_tx.send(line);
+    }
+}
+
+/// Listens for control commands in a background thread.
+pub struct ControlServer {
+    request_rx: Receiver<ControlRequest>,
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Starts listening. Fails if another instance already owns the control socket.
+    #[cfg(unix)]
+    pub fn start() -> Result<Self> {
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let path = socket_path();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                bail!("Another Proteus instance is already listening on {:?}", path);
+            }
+            // Stale socket from an instance that didn't shut down cleanly
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        info!("Control socket listening on {:?}", path);
+
+        let request_rx = Self::accept_loop(move || listener.accept().map(|(stream, _)| stream));
+        Ok(Self { request_rx, path })
+    }
+
+    /// Starts listening. Fails if another instance already owns the control port.
+    #[cfg(not(unix))]
+    pub fn start() -> Result<Self> {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", CONTROL_PORT))
+            .map_err(|e| anyhow!("Control port {} unavailable (another instance running?): {}", CONTROL_PORT, e))?;
+        info!("Control server listening on 127.0.0.1:{}", CONTROL_PORT);
+
+        let request_rx = Self::accept_loop(move || listener.accept().map(|(stream, _)| stream));
+        Ok(Self { request_rx })
+    }
+
+    fn accept_loop<S, F>(mut accept: F) -> Receiver<ControlRequest>
+    where
+        S: Read + Write + Send + 'static,
+        F: FnMut() -> std::io::Result<S> + Send + 'static,
+    {
+        let (request_tx, request_rx) = mpsc::channel();
+        thread::spawn(move || {
+            loop {
+                match accept() {
+                    Ok(stream) => {
+                        let request_tx = request_tx.clone();
+                        thread::spawn(move || handle_client(stream, request_tx));
+                    }
+                    Err(e) => {
+                        warn!("Control connection failed: {}", e);
+                        thread::sleep(ACCEPT_RETRY_INTERVAL);
+                    }
+                }
+            }
+        });
+        request_rx
+    }
+
+    /// Returns the next pending command, if any. Never blocks.
+    pub fn try_recv(&self) -> Option<ControlRequest> {
+        self.request_rx.try_recv().ok()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether a line is the request line of an HTTP request (e.g. a browser posting to the port).
+fn is_http_request(line: &str) -> bool {
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    method.chars().all(|c| c.is_ascii_uppercase()) && parts.nth(1).is_some_and(|version| version.starts_with("HTTP/"))
+}
+
+/// Reads commands line by line and forwards them to the render loop. The
+/// first line that isn't a command is answered with an error and ends the
+/// connection.
+fn handle_client<S: Read + Write>(stream: S, request_tx: Sender<ControlRequest>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while matches!((&mut reader).take(MAX_LINE).read_line(&mut line), Ok(n) if n > 0) {
+        if is_http_request(&line) {
+            warn!("Refused an HTTP request on the control socket");
+            let _ = writeln!(reader.get_mut(), "error: not an HTTP server");
+            return;
+        }
+        debug!("Control command: {}", line.trim());
+        let command = match line.parse::<ControlCommand>() {
+            Ok(command) => command,
+            Err(e) => {
+                let _ = writeln!(reader.get_mut(), "error: {}", e);
+                return;
+            }
+        };
+        let (request, reply_rx) = ControlRequest::new(command);
+        if request_tx.send(request).is_err() {
+            return;
+        }
+        let response = reply_rx.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(|_| "error: timed out waiting for a response".to_string());
+        if writeln!(reader.get_mut(), "{}", response).is_err() {
+            return;
+        }
+        line.clear();
+    }
+}
+
+/// Path of the per-user control socket.
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("proteus.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_else(|_| "default".to_string());
+            std::env::temp_dir().join(format!("proteus-{}.sock", user))
+        }
+    }
+}
+
+/// Sends a command to the running instance and returns its response message.
+pub fn send(command: &ControlCommand) -> Result<String> {
+    #[cfg(unix)]
+    let stream = std::os::unix::net::UnixStream::connect(socket_path());
+    #[cfg(not(unix))]
+    let stream = std::net::TcpStream::connect(("127.0.0.1", CONTROL_PORT));
+    let mut stream = stream.map_err(|e| anyhow!("No running Proteus instance found: {}", e))?;
+
+    writeln!(stream, "{}", command)?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+
+    let response = response.trim();
+    if let Some(message) = response.strip_prefix("error: ") {
+        bail!("{}", message);
+    }
+    Ok(response.strip_prefix("ok").unwrap_or(response).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trip() {
+        for line in ["set-uniform intensity 0.5", "set-buffer points 0.25 0.5 0.1 0", "set-buffer points", "switch-preset calm", "undo", "redo", "ab store a", "ab toggle", "ab mix 0.5", "ab fade 2", "snapshot out.png", "record start", "record stop", "record toggle", "clip", "clip 3", "clip 2.5 out.gif", "clip loop.png", "set-live on", "set-bpm 128", "tap", "time pause", "time seek -2", "switch-source 2", "switch-input Logitech BRIO", "devices", "switch-layout interview", "zoom toggle", "zoom-at 0.25 0.75", "cursor 0.5 0.5", "cursor hide", "click", "caption Hello world", "caption clear", "redact bystanders off", "redact logos toggle", "obs scene Be Right Back", "obs record toggle", "event raid", r#"event follower {"user":"alice"}"#, r#"event donation {"amount":5,"message":"Great  stream"}"#, "stats", "reload", "reload status", "session save", "session save show.yaml", "session load show.yaml", "quit"] {
+            let command: ControlCommand = line.parse().unwrap();
+            assert_eq!(command.to_string(), line);
+        }
+        assert!("set-uniform intensity loud".parse::<ControlCommand>().is_err());
+        assert!("set-buffer points 0.5 many".parse::<ControlCommand>().is_err());
+        assert!("switch-source 0".parse::<ControlCommand>().is_err());
+        assert!("zoom-at 1.5 0".parse::<ControlCommand>().is_err());
+        assert!("clip 0".parse::<ControlCommand>().is_err());
+    }
+
+    #[test]
+    fn test_handle_client() {
+        // Commands are forwarded until the first line that isn't one
+        let (request_tx, request_rx) = mpsc::channel::<ControlRequest>();
+        let responder = thread::spawn(move || {
+            request_rx
+                .iter()
+                .map(|request| {
+                    let command = request.command.to_string();
+                    request.reply(Ok(String::new()));
+                    command
+                })
+                .collect::<Vec<_>>()
+        });
+        let mut client = Duplex { input: std::io::Cursor::new(b"set-live on\nnot a command\nquit\n".to_vec()), output: Vec::new() };
+        handle_client(&mut client, request_tx.clone());
+
+        // A browser posting to the port gets nothing run
+        let mut browser = Duplex { input: std::io::Cursor::new(b"POST / HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\nquit\n".to_vec()), output: Vec::new() };
+        handle_client(&mut browser, request_tx);
+        assert_eq!(responder.join().unwrap(), ["set-live on"]);
+        assert!(String::from_utf8_lossy(&client.output).starts_with("ok\nerror: "));
+        assert!(is_http_request("GET /api/state HTTP/1.1") && !is_http_request("set-live on"));
+    }
+
+    /// A stream reading from `input` and writing to `output`.
+    struct Duplex {
+        input: std::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for &mut Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for &mut Duplex {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Named events from outside (e.g. follower or donation alerts) for Lua scripts.
+//!
+//! Events are posted with `proteus ctl event <name> [payload]`, the web UI's
+//! `POST /api/event/<name>` or the MQTT topic `<topic>/event/<name>`, and every
+//! script receives each of them from `events.poll()`. Payloads are JSON; text
+//! that isn't JSON is passed on as a string.
+
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+use tracing::{debug, warn};
+
+/// Most events kept for a script that doesn't poll; older ones are dropped.
+pub const MAX_QUEUED: usize = 256;
+
+/// A named event and its payload (`Value::Null` without one).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: String,
+    pub payload: Value,
+}
+
+type Queue = Mutex<VecDeque<Event>>;
+
+static SUBSCRIBERS: Mutex<Vec<Weak<Queue>>> = Mutex::new(Vec::new());
+
+/// Parses a payload: JSON if it is, a string otherwise, `Null` if empty.
+pub fn parse_payload(text: &str) -> Value {
+    let text = text.trim();
+    if text.is_empty() {
+        return Value::Null;
+    }
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+/// Queues an event for every subscriber. Returns the number of subscribers.
+pub fn post(event: Event) -> usize {
+    debug!("Event {}: {}", event.name, event.payload);
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.retain(|queue| queue.strong_count() > 0);
+    for queue in subscribers.iter().filter_map(Weak::upgrade) {
+        let mut queue = queue.lock().unwrap();
+        if queue.len() >= MAX_QUEUED {
+            warn!("Dropping event {:?}, {} events are waiting to be polled", queue[0].name, MAX_QUEUED);
+            queue.pop_front();
+        }
+        queue.push_back(event.clone());
+    }
+    subscribers.len()
+}
+
+/// The events posted since subscribing. Unsubscribes when the last clone is dropped.
+#[derive(Clone)]
+pub struct Subscription {
+    queue: Arc<Queue>,
+}
+
+impl Subscription {
+    /// Takes the events posted since the last call, oldest first.
+    pub fn poll(&self) -> Vec<Event> {
+        self.queue.lock().unwrap().drain(..).collect()
+    }
+}
+
+/// Starts receiving events.
+pub fn subscribe() -> Subscription {
+    let queue = Arc::new(Mutex::new(VecDeque::new()));
+    SUBSCRIBERS.lock().unwrap().push(Arc::downgrade(&queue));
+    Subscription { queue }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_post_and_poll() {
+        let first = subscribe();
+        let second = subscribe();
+        post(Event { name: "follower".to_string(), payload: parse_payload(r#"{ "user": "alice" }"#) });
+        assert_eq!(first.poll(), vec![Event { name: "follower".to_string(), payload: json!({ "user": "alice" }) }]);
+        assert!(first.poll().is_empty());
+        assert_eq!(second.poll().len(), 1);
+
+        // Dropped subscriptions no longer receive events, full queues drop the oldest
+        drop(second);
+        for i in 0..MAX_QUEUED + 2 {
+            post(Event { name: "tick".to_string(), payload: json!(i) });
+        }
+        let events = first.poll();
+        assert_eq!((events.len(), &events[0].payload), (MAX_QUEUED, &json!(2)));
+        assert_eq!(parse_payload(" thanks! "), json!("thanks!"));
+        assert_eq!(parse_payload(""), Value::Null);
+    }
+}
@@ -31,6 +31,94 @@ impl PixelFormat {
     }
 }
 
+/// YUV to RGB conversion matrix.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// SD video and JPEG
+    Bt601,
+    /// HD video
+    #[default]
+    Bt709,
+}
+
+/// Value range of YUV samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Y 16-235, UV 16-240 (standard for video)
+    #[default]
+    Limited,
+    /// 0-255
+    Full,
+}
+
+/// Color space of YUV frame data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct YuvColorSpace {
+    pub matrix: YuvMatrix,
+    pub range: YuvRange,
+}
+
+impl YuvColorSpace {
+    /// The usual color space for a video of the given size: BT.601 for SD, BT.709 for HD,
+    /// limited range for both.
+    pub fn for_resolution(_width: u32, height: u32) -> Self {
+        let matrix = if height < 720 { YuvMatrix::Bt601 } else { YuvMatrix::Bt709 };
+        Self { matrix, range: YuvRange::Limited }
+    }
+
+    fn color_info(&self) -> ezk_image::ColorInfo {
+        let (primaries, space) = match self.matrix {
+            YuvMatrix::Bt601 => (ezk_image::ColorPrimaries::BT601, ezk_image::ColorSpace::BT601),
+            YuvMatrix::Bt709 => (ezk_image::ColorPrimaries::BT709, ezk_image::ColorSpace::BT709),
+        };
+        ezk_image::ColorInfo::YUV(ezk_image::YuvColorInfo {
+            transfer: ezk_image::ColorTransfer::Linear,
+            primaries,
+            space,
+            full_range: self.range == YuvRange::Full,
+        })
+    }
+}
+
+/// Clockwise rotation that turns a frame upright, for cameras that deliver
+/// sideways frames (e.g. phones used as webcams).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    /// Parses a clockwise angle in degrees; only multiples of 90 are supported.
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees % 360 {
+            0 => Some(Rotation::None),
+            90 => Some(Rotation::Cw90),
+            180 => Some(Rotation::Cw180),
+            270 => Some(Rotation::Cw270),
+            _ => None,
+        }
+    }
+
+    /// Number of clockwise quarter turns.
+    pub fn quarter_turns(self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Cw90 => 1,
+            Rotation::Cw180 => 2,
+            Rotation::Cw270 => 3,
+        }
+    }
+
+    /// Whether the rotation swaps width and height.
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(self, Rotation::Cw90 | Rotation::Cw270)
+    }
+}
+
 /// A video frame containing image data.
 #[derive(Debug, Clone)]
 pub struct VideoFrame {
@@ -44,6 +132,10 @@ pub struct VideoFrame {
     pub timestamp_us: Option<u64>,
     /// Raw pixel data
     pub data: Vec<u8>,
+    /// Color space of YUV data (ignored for RGB formats)
+    pub color: YuvColorSpace,
+    /// Rotation that turns the frame upright, applied when it's uploaded to the GPU
+    pub rotation: Rotation,
 }
 
 impl VideoFrame {
@@ -56,6 +148,8 @@ impl VideoFrame {
             format,
             timestamp_us: None,
             data: vec![0; size],
+            color: YuvColorSpace::default(),
+            rotation: Rotation::None,
         }
     }
 
@@ -67,6 +161,60 @@ impl VideoFrame {
             format,
             timestamp_us: None,
             data,
+            color: YuvColorSpace::default(),
+            rotation: Rotation::None,
+        }
+    }
+
+    /// Sets the color space the YUV data is encoded in.
+    pub fn with_color(mut self, color: YuvColorSpace) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the rotation that turns the frame upright.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Size of the frame once `rotation` is applied.
+    pub fn upright_size(&self) -> (u32, u32) {
+        if self.rotation.swaps_dimensions() {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    /// Applies `rotation` on the CPU. Converts to RGBA if the frame is rotated.
+    pub fn upright(self) -> VideoFrame {
+        if self.rotation == Rotation::None {
+            return self;
+        }
+        let rgba = if self.format == PixelFormat::Rgba { self } else { self.to_rgba() };
+        let (w, h) = (rgba.width as usize, rgba.height as usize);
+        let (width, height) = rgba.upright_size();
+        let mut data = vec![0u8; w * h * 4];
+        for (i, out) in data.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (i % width as usize, i / width as usize);
+            let (sx, sy) = match rgba.rotation {
+                Rotation::None => (x, y),
+                Rotation::Cw90 => (y, h - 1 - x),
+                Rotation::Cw180 => (w - 1 - x, h - 1 - y),
+                Rotation::Cw270 => (w - 1 - y, x),
+            };
+            let src = (sy * w + sx) * 4;
+            out.copy_from_slice(&rgba.data[src..src + 4]);
+        }
+        VideoFrame {
+            width,
+            height,
+            format: PixelFormat::Rgba,
+            timestamp_us: rgba.timestamp_us,
+            data,
+            color: YuvColorSpace::default(),
+            rotation: Rotation::None,
         }
     }
 
@@ -118,10 +266,45 @@ impl VideoFrame {
             height: new_height,
             format: PixelFormat::Rgba,
             timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
             data: dst_image.into_vec(),
+            color: YuvColorSpace::default(),
         }
     }
 
+    /// Resizes this frame to exactly `width` x `height`, ignoring the aspect ratio.
+    /// Always converts to RGBA format.
+    pub fn resize(&self, width: u32, height: u32) -> VideoFrame {
+        let rgba = self.to_rgba();
+        if rgba.width == width && rgba.height == height {
+            return rgba;
+        }
+
+        use fast_image_resize::{images::Image, Resizer, PixelType};
+
+        let src_image = Image::from_vec_u8(rgba.width, rgba.height, rgba.data, PixelType::U8x4)
+            .expect("Failed to create source image");
+        let mut dst_image = Image::new(width.max(1), height.max(1), PixelType::U8x4);
+        Resizer::new().resize(&src_image, &mut dst_image, None).expect("Resize failed");
+
+        VideoFrame {
+            width: width.max(1),
+            height: height.max(1),
+            format: PixelFormat::Rgba,
+            timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
+            data: dst_image.into_vec(),
+            color: YuvColorSpace::default(),
+        }
+    }
+
+    /// Saves this frame as an image file; the format is chosen from the file extension.
+    pub fn save(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let rgba = self.to_rgba();
+        image::save_buffer(path, &rgba.data, rgba.width, rgba.height, image::ColorType::Rgba8)?;
+        Ok(())
+    }
+
     /// Converts this frame to RGBA format.
     pub fn to_rgba(&self) -> VideoFrame {
         if self.format == PixelFormat::Rgba {
@@ -147,7 +330,9 @@ impl VideoFrame {
                 height: self.height,
                 format: PixelFormat::Rgba,
                 timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
                 data: rgba_data,
+                color: YuvColorSpace::default(),
             };
         }
 
@@ -166,12 +351,7 @@ impl VideoFrame {
                 dst_color,
             ).expect("Failed to wrap RGBA dst buffer");
 
-            let src_color_yuv = ezk_image::ColorInfo::YUV(ezk_image::YuvColorInfo {
-                transfer: ezk_image::ColorTransfer::Linear,
-                primaries: ezk_image::ColorPrimaries::BT709,
-                space: ezk_image::ColorSpace::BT709,
-                full_range: false,
-            });
+            let src_color_yuv = self.color.color_info();
 
             match self.format {
                 PixelFormat::Yuyv => {
@@ -225,7 +405,9 @@ impl VideoFrame {
             height: self.height,
             format: PixelFormat::Rgba,
             timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
             data: rgba_data,
+            color: YuvColorSpace::default(),
         }
     }
 
@@ -269,12 +451,7 @@ impl VideoFrame {
                 transfer: ezk_image::ColorTransfer::Linear,
                 primaries: ezk_image::ColorPrimaries::BT709,
             });
-            let src_color_yuv = ezk_image::ColorInfo::YUV(ezk_image::YuvColorInfo {
-                transfer: ezk_image::ColorTransfer::Linear,
-                primaries: ezk_image::ColorPrimaries::BT709,
-                space: ezk_image::ColorSpace::BT709,
-                full_range: false,
-            });
+            let src_color_yuv = self.color.color_info();
 
             match self.format {
                 PixelFormat::Rgba => {
@@ -340,7 +517,9 @@ impl VideoFrame {
             height: self.height,
             format: PixelFormat::Nv12,
             timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
             data: nv12_data,
+            color: YuvColorSpace::default(),
         }
     }
 
@@ -386,12 +565,7 @@ impl VideoFrame {
                 transfer: ezk_image::ColorTransfer::Linear,
                 primaries: ezk_image::ColorPrimaries::BT709,
             });
-             let src_color_yuv = ezk_image::ColorInfo::YUV(ezk_image::YuvColorInfo {
-                transfer: ezk_image::ColorTransfer::Linear,
-                primaries: ezk_image::ColorPrimaries::BT709,
-                space: ezk_image::ColorSpace::BT709,
-                full_range: false,
-            });
+             let src_color_yuv = self.color.color_info();
 
             match self.format {
                 PixelFormat::Rgba => {
@@ -437,7 +611,10 @@ impl VideoFrame {
             height: self.height,
             format: PixelFormat::Yuyv,
             timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
             data: yuyv_data,
+            // The UYVY fast path only reorders bytes
+            color: if self.format == PixelFormat::Uyvy { self.color } else { YuvColorSpace::default() },
         }
     }
 
@@ -476,12 +653,7 @@ impl VideoFrame {
                 transfer: ezk_image::ColorTransfer::Linear,
                 primaries: ezk_image::ColorPrimaries::BT709,
             });
-             let src_color_yuv = ezk_image::ColorInfo::YUV(ezk_image::YuvColorInfo {
-                transfer: ezk_image::ColorTransfer::Linear,
-                primaries: ezk_image::ColorPrimaries::BT709,
-                space: ezk_image::ColorSpace::BT709,
-                full_range: false,
-            });
+             let src_color_yuv = self.color.color_info();
 
             match self.format {
                 PixelFormat::Rgba => {
@@ -539,7 +711,10 @@ impl VideoFrame {
             height: self.height,
             format: PixelFormat::Uyvy,
             timestamp_us: self.timestamp_us,
+            rotation: self.rotation,
             data: uyvy_data,
+            // YUYV input only has its bytes reordered
+            color: if self.format == PixelFormat::Yuyv { self.color } else { YuvColorSpace::default() },
         }
     }
 }
@@ -561,9 +736,40 @@ impl QuadVertex {
         QuadVertex { position: [-1.0, 1.0], tex_coords: [0.0, 0.0] },
     ];
 
+    /// Vertices for a full-screen quad showing the texture horizontally mirrored.
+    pub const MIRRORED_VERTICES: &'static [QuadVertex] = &[
+        QuadVertex { position: [-1.0, -1.0], tex_coords: [1.0, 1.0] },
+        QuadVertex { position: [1.0, -1.0], tex_coords: [0.0, 1.0] },
+        QuadVertex { position: [1.0, 1.0], tex_coords: [0.0, 0.0] },
+        QuadVertex { position: [-1.0, 1.0], tex_coords: [1.0, 0.0] },
+    ];
+
     /// Indices for the quad (two triangles).
     pub const INDICES: &'static [u16] = &[0, 1, 2, 2, 3, 0];
 
+    /// Vertices and indices of a full-screen grid of `columns` x `rows` cells,
+    /// for passes that move vertices in their vertex shader.
+    pub fn grid(columns: u32, rows: u32) -> (Vec<QuadVertex>, Vec<u32>) {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let v = row as f32 / rows as f32;
+                vertices.push(QuadVertex { position: [u * 2.0 - 1.0, 1.0 - v * 2.0], tex_coords: [u, v] });
+            }
+        }
+        let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let top_left = row * (columns + 1) + column;
+                let bottom_left = top_left + columns + 1;
+                indices.extend_from_slice(&[bottom_left, bottom_left + 1, top_left + 1, top_left + 1, top_left, bottom_left]);
+            }
+        }
+        (vertices, indices)
+    }
+
     /// Returns the vertex buffer layout.
     pub fn layout() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -589,6 +795,22 @@ impl QuadVertex {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_grid() {
+        let (vertices, indices) = QuadVertex::grid(2, 1);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices.len(), 12);
+        assert_eq!(vertices[0].position, [-1.0, 1.0]);
+        assert_eq!(vertices[0].tex_coords, [0.0, 0.0]);
+        assert_eq!(vertices[5].position, [1.0, -1.0]);
+        assert_eq!(vertices[5].tex_coords, [1.0, 1.0]);
+        assert!(indices.iter().all(|&index| (index as usize) < vertices.len()));
+
+        // One cell is the quad
+        let (vertices, _) = QuadVertex::grid(1, 1);
+        assert!(QuadVertex::VERTICES.iter().all(|quad| vertices.iter().any(|v| v.position == quad.position && v.tex_coords == quad.tex_coords)));
+    }
+
     #[test]
     fn test_rgb_to_rgba_conversion() {
         let rgb_data = vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
@@ -602,4 +824,21 @@ mod tests {
         // Check second pixel (green)
         assert_eq!(&rgba_frame.data[4..8], &[0, 255, 0, 255]);
     }
+
+    #[test]
+    fn test_upright() {
+        // A B
+        // C D
+        let frame = VideoFrame::from_data(2, 2, PixelFormat::Rgba, [1, 2, 3, 4].iter().flat_map(|&v| [v; 4]).collect());
+        let pixels = |rotation| -> Vec<u8> { frame.clone().with_rotation(rotation).upright().data.chunks(4).map(|p| p[0]).collect() };
+        assert_eq!(pixels(Rotation::None), [1, 2, 3, 4]);
+        assert_eq!(pixels(Rotation::Cw90), [3, 1, 4, 2]);
+        assert_eq!(pixels(Rotation::Cw180), [4, 3, 2, 1]);
+        assert_eq!(pixels(Rotation::Cw270), [2, 4, 1, 3]);
+
+        let wide = VideoFrame::from_data(3, 1, PixelFormat::Rgba, vec![0; 12]).with_rotation(Rotation::Cw90);
+        assert_eq!(wide.upright_size(), (1, 3));
+        let upright = wide.upright();
+        assert_eq!((upright.width, upright.height, upright.rotation), (1, 3, Rotation::None));
+    }
 }
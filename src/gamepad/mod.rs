@@ -0,0 +1,299 @@
+//! Gamepad input for live effect control.
+//!
+//! Maps sticks, triggers, and buttons to named shader parameters, preset
+//! switches and control commands. Gamepad events are translated into control commands, so they behave
+//! exactly like the equivalent `proteus ctl` commands. Reading gamepads requires the `gamepad` feature.
+
+use crate::control::ControlCommand;
+use anyhow::{bail, Result};
+use std::str::FromStr;
+#[cfg(any(feature = "gamepad", test))]
+use tracing::debug;
+
+/// A gamepad button that can be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// A gamepad stick axis that can be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+/// A gamepad element that can be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadControl {
+    Button(Button),
+    Axis(Axis),
+}
+
+impl FromStr for GamepadControl {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        let control = match name {
+            "south" => GamepadControl::Button(Button::South),
+            "east" => GamepadControl::Button(Button::East),
+            "north" => GamepadControl::Button(Button::North),
+            "west" => GamepadControl::Button(Button::West),
+            "left-bumper" => GamepadControl::Button(Button::LeftBumper),
+            "right-bumper" => GamepadControl::Button(Button::RightBumper),
+            "left-trigger" => GamepadControl::Button(Button::LeftTrigger),
+            "right-trigger" => GamepadControl::Button(Button::RightTrigger),
+            "select" => GamepadControl::Button(Button::Select),
+            "start" => GamepadControl::Button(Button::Start),
+            "mode" => GamepadControl::Button(Button::Mode),
+            "left-thumb" => GamepadControl::Button(Button::LeftThumb),
+            "right-thumb" => GamepadControl::Button(Button::RightThumb),
+            "dpad-up" => GamepadControl::Button(Button::DPadUp),
+            "dpad-down" => GamepadControl::Button(Button::DPadDown),
+            "dpad-left" => GamepadControl::Button(Button::DPadLeft),
+            "dpad-right" => GamepadControl::Button(Button::DPadRight),
+            "left-stick-x" => GamepadControl::Axis(Axis::LeftStickX),
+            "left-stick-y" => GamepadControl::Axis(Axis::LeftStickY),
+            "right-stick-x" => GamepadControl::Axis(Axis::RightStickX),
+            "right-stick-y" => GamepadControl::Axis(Axis::RightStickY),
+            _ => bail!("Unknown gamepad input '{}'", name),
+        };
+        Ok(control)
+    }
+}
+
+/// What a mapped gamepad element controls.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadTarget {
+    /// Drive a named parameter across `min..max` (sticks and analog triggers are continuous,
+    /// buttons switch between `min` and `max`)
+    Param { name: String, min: f32, max: f32 },
+    /// Apply a preset when the button is pressed
+    Preset(String),
+    /// Run a control command when the button is pressed
+    Command(ControlCommand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadMapping {
+    pub control: GamepadControl,
+    pub target: GamepadTarget,
+}
+
+/// Input from a gamepad element.
+#[cfg(any(feature = "gamepad", test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GamepadInput {
+    /// A stick, analog trigger or button moved to 0.0 - 1.0
+    Changed(GamepadControl, f32),
+    /// A button was pressed
+    Pressed(GamepadControl),
+}
+
+/// Translates gamepad input into control commands. A parameter only gets the last
+/// value it was driven to, so a stick that moved many times since the last frame
+/// sets it once.
+#[cfg(any(feature = "gamepad", test))]
+fn translate(mappings: &[GamepadMapping], inputs: impl IntoIterator<Item = GamepadInput>) -> Vec<ControlCommand> {
+    let mut commands: Vec<ControlCommand> = Vec::new();
+    for input in inputs {
+        let control = match input {
+            GamepadInput::Changed(control, _) | GamepadInput::Pressed(control) => control,
+        };
+        for mapping in mappings.iter().filter(|m| m.control == control) {
+            match (&mapping.target, input) {
+                (GamepadTarget::Param { name, min, max }, GamepadInput::Changed(_, value)) => {
+                    let value = min + (max - min) * value.clamp(0.0, 1.0);
+                    // Moved to the end, so it still follows preset switches in between
+                    commands.retain(|command| !matches!(command, ControlCommand::SetUniform { name: set, .. } if set == name));
+                    commands.push(ControlCommand::SetUniform { name: name.clone(), value });
+                }
+                (GamepadTarget::Preset(name), GamepadInput::Pressed(_)) => {
+                    debug!("Gamepad {:?} -> preset '{}'", control, name);
+                    commands.push(ControlCommand::SwitchPreset(name.clone()));
+                }
+                (GamepadTarget::Command(command), GamepadInput::Pressed(_)) => {
+                    debug!("Gamepad {:?} -> '{}'", control, command);
+                    commands.push(command.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+    commands
+}
+
+#[cfg(feature = "gamepad")]
+mod backend {
+    use super::*;
+    use anyhow::anyhow;
+    use gilrs::{EventType, Gilrs};
+    use tracing::info;
+
+    impl Button {
+        fn from_gilrs(button: gilrs::Button) -> Option<Self> {
+            Some(match button {
+                gilrs::Button::South => Button::South,
+                gilrs::Button::East => Button::East,
+                gilrs::Button::North => Button::North,
+                gilrs::Button::West => Button::West,
+                gilrs::Button::LeftTrigger => Button::LeftBumper,
+                gilrs::Button::RightTrigger => Button::RightBumper,
+                gilrs::Button::LeftTrigger2 => Button::LeftTrigger,
+                gilrs::Button::RightTrigger2 => Button::RightTrigger,
+                gilrs::Button::Select => Button::Select,
+                gilrs::Button::Start => Button::Start,
+                gilrs::Button::Mode => Button::Mode,
+                gilrs::Button::LeftThumb => Button::LeftThumb,
+                gilrs::Button::RightThumb => Button::RightThumb,
+                gilrs::Button::DPadUp => Button::DPadUp,
+                gilrs::Button::DPadDown => Button::DPadDown,
+                gilrs::Button::DPadLeft => Button::DPadLeft,
+                gilrs::Button::DPadRight => Button::DPadRight,
+                _ => return None,
+            })
+        }
+    }
+
+    impl Axis {
+        fn from_gilrs(axis: gilrs::Axis) -> Option<Self> {
+            Some(match axis {
+                gilrs::Axis::LeftStickX => Axis::LeftStickX,
+                gilrs::Axis::LeftStickY => Axis::LeftStickY,
+                gilrs::Axis::RightStickX => Axis::RightStickX,
+                gilrs::Axis::RightStickY => Axis::RightStickY,
+                _ => return None,
+            })
+        }
+    }
+
+    /// Polls connected gamepads and translates their input into control commands.
+    pub struct GamepadController {
+        gilrs: Gilrs,
+        mappings: Vec<GamepadMapping>,
+    }
+
+    impl GamepadController {
+        pub fn new(mappings: Vec<GamepadMapping>) -> Result<Self> {
+            let gilrs = Gilrs::new().map_err(|e| anyhow!("Gamepad input unavailable: {}", e))?;
+            for (_, gamepad) in gilrs.gamepads() {
+                info!("Gamepad connected: {}", gamepad.name());
+            }
+            Ok(Self { gilrs, mappings })
+        }
+
+        /// Drains pending gamepad events. Never blocks.
+        pub fn poll(&mut self) -> Vec<ControlCommand> {
+            let mut inputs = Vec::new();
+            while let Some(event) = self.gilrs.next_event() {
+                let input = match event.event {
+                    EventType::ButtonChanged(button, value, _) => Button::from_gilrs(button).map(|button| GamepadInput::Changed(GamepadControl::Button(button), value)),
+                    EventType::ButtonPressed(button, _) => Button::from_gilrs(button).map(|button| GamepadInput::Pressed(GamepadControl::Button(button))),
+                    // Sticks report -1.0 - 1.0
+                    EventType::AxisChanged(axis, value, _) => Axis::from_gilrs(axis).map(|axis| GamepadInput::Changed(GamepadControl::Axis(axis), (value + 1.0) / 2.0)),
+                    EventType::Connected => {
+                        info!("Gamepad connected: {}", self.gilrs.gamepad(event.id).name());
+                        None
+                    }
+                    EventType::Disconnected => {
+                        info!("Gamepad disconnected");
+                        None
+                    }
+                    _ => None,
+                };
+                inputs.extend(input);
+            }
+            translate(&self.mappings, inputs)
+        }
+    }
+}
+
+#[cfg(feature = "gamepad")]
+pub use backend::GamepadController;
+
+/// Placeholder used when Proteus is built without the `gamepad` feature.
+#[cfg(not(feature = "gamepad"))]
+pub struct GamepadController {
+    _private: (),
+}
+
+#[cfg(not(feature = "gamepad"))]
+impl GamepadController {
+    pub fn new(_mappings: Vec<GamepadMapping>) -> Result<Self> {
+        bail!("Proteus was built without gamepad support (enable the `gamepad` feature)")
+    }
+
+    pub fn poll(&mut self) -> Vec<ControlCommand> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(input: &str, name: &str, min: f32, max: f32) -> GamepadMapping {
+        GamepadMapping { control: input.parse().unwrap(), target: GamepadTarget::Param { name: name.to_string(), min, max } }
+    }
+
+    fn set(name: &str, value: f32) -> ControlCommand {
+        ControlCommand::SetUniform { name: name.to_string(), value }
+    }
+
+    #[test]
+    fn test_parse_control() {
+        assert_eq!("left-bumper".parse::<GamepadControl>().unwrap(), GamepadControl::Button(Button::LeftBumper));
+        assert_eq!("right-trigger".parse::<GamepadControl>().unwrap(), GamepadControl::Button(Button::RightTrigger));
+        assert_eq!("right-stick-y".parse::<GamepadControl>().unwrap(), GamepadControl::Axis(Axis::RightStickY));
+        assert!("left-stick".parse::<GamepadControl>().is_err());
+    }
+
+    #[test]
+    fn test_translate() {
+        let stick = GamepadControl::Axis(Axis::LeftStickX);
+        let south = GamepadControl::Button(Button::South);
+        let start = GamepadControl::Button(Button::Start);
+        let mappings = vec![
+            param("left-stick-x", "hue", 0.0, 2.0),
+            param("south", "strobe", 0.0, 1.0),
+            GamepadMapping { control: south, target: GamepadTarget::Preset("calm".to_string()) },
+            GamepadMapping { control: start, target: GamepadTarget::Command("snapshot".parse().unwrap()) },
+        ];
+
+        // Values are scaled to min - max and clamped
+        assert_eq!(translate(&mappings, [GamepadInput::Changed(stick, 0.25)]), vec![set("hue", 0.5)]);
+        assert_eq!(translate(&mappings, [GamepadInput::Changed(stick, 1.5)]), vec![set("hue", 2.0)]);
+        // Presets and commands only trigger on a press, parameters on a change
+        assert_eq!(translate(&mappings, [GamepadInput::Pressed(south)]), vec![ControlCommand::SwitchPreset("calm".to_string())]);
+        assert_eq!(translate(&mappings, [GamepadInput::Changed(start, 1.0)]), vec![]);
+        assert_eq!(translate(&mappings, [GamepadInput::Pressed(start)]), vec!["snapshot".parse().unwrap()]);
+
+        // One value per parameter, after the preset switch it followed
+        let inputs = [
+            GamepadInput::Changed(stick, 0.1),
+            GamepadInput::Changed(south, 1.0),
+            GamepadInput::Changed(stick, 0.2),
+            GamepadInput::Pressed(south),
+            GamepadInput::Changed(stick, 0.5),
+        ];
+        assert_eq!(translate(&mappings, inputs), vec![set("strobe", 1.0), ControlCommand::SwitchPreset("calm".to_string()), set("hue", 1.0)]);
+    }
+}
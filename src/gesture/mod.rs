@@ -0,0 +1,297 @@
+//! Hand gesture recognition from body pose keypoints.
+//!
+//! Recognizes a few deliberate gestures from the keypoints of the pose model
+//! (see [`crate::ml::PoseEngine`]). Waving works with any pose model; the open
+//! palm and thumbs-up need the hand landmarks of a BlazePose model.
+
+use crate::ml::{Keypoint, MAX_KEYPOINTS};
+use anyhow::{bail, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Seconds of wrist movement considered when looking for a wave
+const WAVE_WINDOW: Duration = Duration::from_millis(1500);
+/// Number of direction changes that make a wave
+const WAVE_REVERSALS: usize = 3;
+/// Minimum swing of a wave, relative to the forearm length
+const WAVE_AMPLITUDE: f32 = 0.3;
+/// How far the wrist may drift while a static gesture is held, relative to the forearm length
+const HOLD_DRIFT: f32 = 0.3;
+
+/// Keypoints of one arm (COCO order, then the BlazePose hand landmarks).
+struct Arm {
+    shoulder: usize,
+    elbow: usize,
+    wrist: usize,
+    pinky: usize,
+    index: usize,
+    thumb: usize,
+}
+
+const ARMS: [Arm; 2] = [
+    Arm { shoulder: 5, elbow: 7, wrist: 9, pinky: 23, index: 25, thumb: 27 },
+    Arm { shoulder: 6, elbow: 8, wrist: 10, pinky: 24, index: 26, thumb: 28 },
+];
+
+/// A recognized gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gesture {
+    /// Flat hand raised with the fingers pointing up
+    OpenPalm,
+    /// Fist with the thumb pointing up
+    ThumbsUp,
+    /// Raised hand moving side to side
+    Wave,
+}
+
+impl Gesture {
+    pub const ALL: [Gesture; 3] = [Gesture::OpenPalm, Gesture::ThumbsUp, Gesture::Wave];
+}
+
+impl FromStr for Gesture {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        let gesture = match name {
+            "open-palm" => Gesture::OpenPalm,
+            "thumbs-up" => Gesture::ThumbsUp,
+            "wave" => Gesture::Wave,
+            _ => bail!("Unknown gesture '{}'", name),
+        };
+        Ok(gesture)
+    }
+}
+
+impl fmt::Display for Gesture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Gesture::OpenPalm => "open-palm",
+            Gesture::ThumbsUp => "thumbs-up",
+            Gesture::Wave => "wave",
+        })
+    }
+}
+
+/// Recognition thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureSettings {
+    /// Minimum keypoint confidence (0.0 - 1.0) for a hand to be considered
+    pub min_score: f32,
+    /// How long a static gesture (open palm, thumbs-up) must be held before it triggers
+    pub hold: Duration,
+    /// Minimum time between two triggers of the same gesture
+    pub cooldown: Duration,
+}
+
+impl Default for GestureSettings {
+    fn default() -> Self {
+        Self {
+            min_score: 0.5,
+            hold: Duration::from_millis(500),
+            cooldown: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Default)]
+struct GestureState {
+    /// When the gesture was first seen, and the wrist position at that time
+    since: Option<(Instant, Keypoint)>,
+    /// Already triggered since the gesture appeared
+    triggered: bool,
+    last_trigger: Option<Instant>,
+}
+
+/// Turns a stream of pose keypoints into debounced gesture triggers.
+pub struct GestureRecognizer {
+    settings: GestureSettings,
+    states: [GestureState; 3],
+    /// Recent horizontal wrist positions of each raised hand
+    wrist_history: [VecDeque<(Instant, f32)>; 2],
+}
+
+impl GestureRecognizer {
+    pub fn new(settings: GestureSettings) -> Self {
+        Self {
+            settings,
+            states: Default::default(),
+            wrist_history: Default::default(),
+        }
+    }
+
+    pub fn settings(&self) -> GestureSettings {
+        self.settings
+    }
+
+    /// Processes the keypoints of one pose result and returns the gestures
+    /// that triggered.
+    pub fn update(&mut self, keypoints: &[Keypoint; MAX_KEYPOINTS], now: Instant) -> Vec<Gesture> {
+        let mut triggered = Vec::new();
+        for gesture in Gesture::ALL {
+            let detected = ARMS
+                .iter()
+                .enumerate()
+                .find_map(|(side, arm)| self.detect(gesture, side, arm, keypoints, now));
+            let state = &mut self.states[gesture as usize];
+            let Some((wrist, forearm)) = detected else {
+                state.since = None;
+                state.triggered = false;
+                continue;
+            };
+
+            // Static gestures must be held in place; a moving hand restarts the hold
+            let since = match state.since {
+                Some((since, anchor)) if distance(&anchor, &wrist) <= HOLD_DRIFT * forearm => since,
+                _ => {
+                    state.since = Some((now, wrist));
+                    state.triggered = false;
+                    now
+                }
+            };
+            let held = gesture == Gesture::Wave || now.duration_since(since) >= self.settings.hold;
+            let cooled_down = state.last_trigger.is_none_or(|last| now.duration_since(last) >= self.settings.cooldown);
+            if held && cooled_down && !state.triggered {
+                state.triggered = true;
+                state.last_trigger = Some(now);
+                triggered.push(gesture);
+            }
+        }
+        triggered
+    }
+
+    /// Returns the wrist and the forearm length if `gesture` is shown with the given arm.
+    fn detect(
+        &mut self,
+        gesture: Gesture,
+        side: usize,
+        arm: &Arm,
+        keypoints: &[Keypoint; MAX_KEYPOINTS],
+        now: Instant,
+    ) -> Option<(Keypoint, f32)> {
+        let min_score = self.settings.min_score;
+        let point = |index: usize| Some(keypoints[index]).filter(|keypoint| keypoint.score >= min_score);
+        let (Some(shoulder), Some(elbow), Some(wrist)) = (point(arm.shoulder), point(arm.elbow), point(arm.wrist)) else {
+            self.wrist_history[side].clear();
+            return None;
+        };
+        let forearm = distance(&elbow, &wrist).max(f32::EPSILON);
+        // Texture coordinates: smaller y is higher up
+        let forearm_up = wrist.y < elbow.y;
+
+        match gesture {
+            Gesture::OpenPalm => {
+                let (index, pinky) = (point(arm.index)?, point(arm.pinky)?);
+                let fingers_up = index.y < wrist.y - 0.25 * forearm && pinky.y < wrist.y - 0.2 * forearm;
+                (forearm_up && wrist.y < shoulder.y && fingers_up).then_some((wrist, forearm))
+            }
+            Gesture::ThumbsUp => {
+                let (index, pinky, thumb) = (point(arm.index)?, point(arm.pinky)?, point(arm.thumb)?);
+                let fist = index.y > wrist.y - 0.2 * forearm && pinky.y > wrist.y - 0.2 * forearm;
+                let thumb_up = thumb.y < index.y.min(pinky.y) - 0.15 * forearm;
+                (forearm_up && fist && thumb_up).then_some((wrist, forearm))
+            }
+            Gesture::Wave => {
+                let history = &mut self.wrist_history[side];
+                if !(forearm_up && wrist.y < shoulder.y) {
+                    history.clear();
+                    return None;
+                }
+                history.push_back((now, wrist.x));
+                while history.front().is_some_and(|&(time, _)| now.duration_since(time) > WAVE_WINDOW) {
+                    history.pop_front();
+                }
+                if count_reversals(history.iter().map(|&(_, x)| x), WAVE_AMPLITUDE * forearm) < WAVE_REVERSALS {
+                    return None;
+                }
+                history.clear();
+                Some((wrist, forearm))
+            }
+        }
+    }
+}
+
+fn distance(a: &Keypoint, b: &Keypoint) -> f32 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// Counts the direction changes of a movement, ignoring swings smaller than `amplitude`.
+fn count_reversals(positions: impl Iterator<Item = f32>, amplitude: f32) -> usize {
+    let mut reversals = 0;
+    // Extreme position of the current swing and its direction (0 until the first swing)
+    let mut extreme: Option<f32> = None;
+    let mut direction = 0.0f32;
+    for x in positions {
+        let Some(last) = extreme else {
+            extreme = Some(x);
+            continue;
+        };
+        let delta = x - last;
+        if direction == 0.0 {
+            if delta.abs() >= amplitude {
+                direction = delta.signum();
+                extreme = Some(x);
+            }
+        } else if delta * direction > 0.0 {
+            // Still moving the same way
+            extreme = Some(x);
+        } else if delta.abs() >= amplitude {
+            reversals += 1;
+            direction = -direction;
+            extreme = Some(x);
+        }
+    }
+    reversals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypoint(x: f32, y: f32) -> Keypoint {
+        Keypoint { x, y, score: 1.0, _padding: 0.0 }
+    }
+
+    /// A left arm with the forearm raised and the given hand landmarks (offsets from the wrist).
+    fn pose(wrist_x: f32, index: (f32, f32), pinky: (f32, f32), thumb: (f32, f32)) -> [Keypoint; MAX_KEYPOINTS] {
+        let mut keypoints = [Keypoint::default(); MAX_KEYPOINTS];
+        let arm = &ARMS[0];
+        keypoints[arm.shoulder] = keypoint(0.5, 0.5);
+        keypoints[arm.elbow] = keypoint(wrist_x, 0.5);
+        keypoints[arm.wrist] = keypoint(wrist_x, 0.3);
+        keypoints[arm.index] = keypoint(wrist_x + index.0, 0.3 + index.1);
+        keypoints[arm.pinky] = keypoint(wrist_x + pinky.0, 0.3 + pinky.1);
+        keypoints[arm.thumb] = keypoint(wrist_x + thumb.0, 0.3 + thumb.1);
+        keypoints
+    }
+
+    #[test]
+    fn test_thumbs_up_is_debounced() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let start = Instant::now();
+        let thumbs_up = pose(0.4, (0.02, 0.0), (0.02, 0.02), (0.0, -0.08));
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        assert!(recognizer.update(&thumbs_up, at(0)).is_empty());
+        assert_eq!(recognizer.update(&thumbs_up, at(600)), vec![Gesture::ThumbsUp]);
+        // Held: triggers only once
+        assert!(recognizer.update(&thumbs_up, at(3000)).is_empty());
+        // Released and shown again: triggers after the hold time
+        assert!(recognizer.update(&[Keypoint::default(); MAX_KEYPOINTS], at(3100)).is_empty());
+        assert!(recognizer.update(&thumbs_up, at(3200)).is_empty());
+        assert_eq!(recognizer.update(&thumbs_up, at(3800)), vec![Gesture::ThumbsUp]);
+    }
+
+    #[test]
+    fn test_wave() {
+        let mut recognizer = GestureRecognizer::new(GestureSettings::default());
+        let start = Instant::now();
+        let mut triggered = Vec::new();
+        for (i, x) in [0.4, 0.5, 0.4, 0.5, 0.4].into_iter().enumerate() {
+            let hand = pose(x, (0.0, -0.1), (0.0, -0.1), (0.0, -0.05));
+            triggered.extend(recognizer.update(&hand, start + Duration::from_millis(200 * i as u64)));
+        }
+        assert_eq!(triggered, vec![Gesture::Wave]);
+    }
+}
@@ -0,0 +1,99 @@
+//! Undo/redo history of runtime parameter changes.
+//!
+//! Parameter changes made at runtime (control socket, presets, gamepad, ...) are
+//! recorded as snapshots of all parameter values, so an accidental change during
+//! a live show can be reverted instantly. Rapid changes to the same parameter,
+//! like a knob being turned, are merged into a single step.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Number of undo steps kept.
+const MAX_STEPS: usize = 50;
+
+/// Changes to the same target less than this apart are merged into one step.
+const MERGE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Bounded undo/redo history of parameter values.
+#[derive(Default)]
+pub struct ParamHistory {
+    undo: VecDeque<HashMap<String, f32>>,
+    redo: Vec<HashMap<String, f32>>,
+    /// Target and time of the last recorded change, for merging
+    last_change: Option<(String, Instant)>,
+}
+
+impl ParamHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the values from before a change to `target` (a parameter or preset name).
+    pub fn record(&mut self, before: &HashMap<String, f32>, target: &str, now: Instant) {
+        let merge = self
+            .last_change
+            .as_ref()
+            .is_some_and(|(last, time)| last == target && now.duration_since(*time) < MERGE_WINDOW);
+        self.last_change = Some((target.to_string(), now));
+        self.redo.clear();
+        if !merge {
+            self.push_undo(before.clone());
+        }
+    }
+
+    /// Returns the values that undo the last change, given the current values.
+    pub fn undo(&mut self, current: &HashMap<String, f32>) -> Option<HashMap<String, f32>> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push(current.clone());
+        self.last_change = None;
+        Some(previous)
+    }
+
+    /// Returns the values that redo the last undone change, given the current values.
+    pub fn redo(&mut self, current: &HashMap<String, f32>) -> Option<HashMap<String, f32>> {
+        let next = self.redo.pop()?;
+        self.push_undo(current.clone());
+        self.last_change = None;
+        Some(next)
+    }
+
+    fn push_undo(&mut self, values: HashMap<String, f32>) {
+        if self.undo.len() == MAX_STEPS {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(intensity: f32) -> HashMap<String, f32> {
+        HashMap::from([("intensity".to_string(), intensity)])
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = ParamHistory::new();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // A knob turned from 0.0 to 0.3 in small steps is one step
+        history.record(&values(0.0), "intensity", at(0));
+        history.record(&values(0.1), "intensity", at(300));
+        history.record(&values(0.2), "intensity", at(600));
+        // A preset applied later is another
+        history.record(&values(0.3), "calm", at(5000));
+
+        assert_eq!(history.undo(&values(0.8)), Some(values(0.3)));
+        assert_eq!(history.undo(&values(0.3)), Some(values(0.0)));
+        assert_eq!(history.undo(&values(0.0)), None);
+        assert_eq!(history.redo(&values(0.0)), Some(values(0.3)));
+
+        // A new change drops the redo steps
+        history.record(&values(0.3), "intensity", at(6000));
+        assert_eq!(history.redo(&values(0.5)), None);
+        assert_eq!(history.undo(&values(0.5)), Some(values(0.3)));
+    }
+}
@@ -0,0 +1,290 @@
+//! Frame interpolation.
+//!
+//! Synthesizes in-between frames so a camera slower than the output rate
+//! (e.g. 30 fps into a 60 fps virtual camera) plays back smoothly instead of
+//! repeating frames. Output lags the camera by one frame interval, since each
+//! new frame is blended in from the previous one over that interval.
+
+use crate::frame::{PixelFormat, VideoFrame};
+
+/// Size of a motion estimation block in luma pixels.
+const BLOCK: usize = 8;
+/// Luma plane downscale factor for motion estimation.
+const LUMA_SCALE: usize = 4;
+/// Search range in luma pixels (× `LUMA_SCALE` in frame pixels).
+const SEARCH: i32 = 6;
+
+/// How in-between frames are synthesized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// Crossfade between consecutive frames
+    Blend,
+    /// Warp both frames along estimated block motion before blending
+    Motion,
+}
+
+/// Turns a stream of timestamped camera frames into smoothly changing output.
+pub struct FrameInterpolator {
+    mode: InterpolationMode,
+    previous: Option<VideoFrame>,
+    current: Option<VideoFrame>,
+    current_timestamp: Option<u64>,
+    /// Render time the current frame arrived
+    arrived: f32,
+    /// Smoothed camera frame interval in seconds
+    interval: f32,
+    /// Motion per block from the previous to the current frame, in frame pixels
+    motion: Vec<[f32; 2]>,
+    output: VideoFrame,
+}
+
+impl FrameInterpolator {
+    pub fn new(mode: InterpolationMode) -> Self {
+        Self {
+            mode,
+            previous: None,
+            current: None,
+            current_timestamp: None,
+            arrived: 0.0,
+            interval: 1.0 / 30.0,
+            motion: Vec::new(),
+            output: VideoFrame::new(0, 0, PixelFormat::Rgba),
+        }
+    }
+
+    pub fn mode(&self) -> InterpolationMode {
+        self.mode
+    }
+
+    /// Returns the frame to show at `time`. Frames without a timestamp (mixer
+    /// and fallback sources) are passed through unchanged.
+    pub fn next<'a>(&'a mut self, frame: &'a VideoFrame, time: f32) -> &'a VideoFrame {
+        let Some(timestamp) = frame.timestamp_us else {
+            return frame;
+        };
+
+        if self.current_timestamp != Some(timestamp) {
+            self.push(frame.to_rgba(), timestamp, time);
+        }
+
+        let (Some(previous), Some(current)) = (&self.previous, &self.current) else {
+            return self.current.as_ref().unwrap_or(frame);
+        };
+        let alpha = ((time - self.arrived) / self.interval).clamp(0.0, 1.0);
+        if alpha >= 1.0 {
+            return current;
+        }
+
+        match self.mode {
+            InterpolationMode::Blend => blend(previous, current, alpha, &mut self.output),
+            InterpolationMode::Motion => warp(previous, current, &self.motion, alpha, &mut self.output),
+        }
+        &self.output
+    }
+
+    fn push(&mut self, frame: VideoFrame, timestamp: u64, time: f32) {
+        let same_size = self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.width == frame.width && current.height == frame.height);
+
+        if let (true, Some(last)) = (same_size, self.current_timestamp) {
+            let delta = (timestamp.saturating_sub(last) as f32 / 1_000_000.0).clamp(0.001, 0.5);
+            self.interval = self.interval * 0.8 + delta * 0.2;
+            self.previous = self.current.take();
+        } else {
+            self.previous = None;
+        }
+
+        if let (InterpolationMode::Motion, Some(previous)) = (self.mode, &self.previous) {
+            self.motion = estimate_motion(previous, &frame);
+        }
+        self.current = Some(frame);
+        self.current_timestamp = Some(timestamp);
+        self.arrived = time;
+    }
+}
+
+fn prepare_output(like: &VideoFrame, output: &mut VideoFrame) {
+    if output.width != like.width || output.height != like.height {
+        *output = VideoFrame::new(like.width, like.height, PixelFormat::Rgba);
+    }
+    output.rotation = like.rotation;
+}
+
+/// Crossfades two RGBA frames of the same size.
+fn blend(a: &VideoFrame, b: &VideoFrame, alpha: f32, output: &mut VideoFrame) {
+    prepare_output(b, output);
+    let weight = (alpha * 256.0) as u32;
+    for ((out, &a), &b) in output.data.iter_mut().zip(&a.data).zip(&b.data) {
+        *out = ((a as u32 * (256 - weight) + b as u32 * weight) >> 8) as u8;
+    }
+}
+
+/// Number of motion blocks per axis.
+fn block_grid(width: u32, height: u32) -> (usize, usize) {
+    let luma_w = width as usize / LUMA_SCALE;
+    let luma_h = height as usize / LUMA_SCALE;
+    (luma_w.div_ceil(BLOCK).max(1), luma_h.div_ceil(BLOCK).max(1))
+}
+
+/// Downscaled luma plane used for block matching.
+fn luma(frame: &VideoFrame) -> (Vec<u8>, usize, usize) {
+    let width = frame.width as usize / LUMA_SCALE;
+    let height = frame.height as usize / LUMA_SCALE;
+    let stride = frame.width as usize * 4;
+    let mut plane = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &frame.data[(y * LUMA_SCALE + LUMA_SCALE / 2) * stride..];
+        for x in 0..width {
+            let px = &row[(x * LUMA_SCALE + LUMA_SCALE / 2) * 4..];
+            plane.push(((px[0] as u32 + 2 * px[1] as u32 + px[2] as u32) / 4) as u8);
+        }
+    }
+    (plane, width, height)
+}
+
+/// Finds, per block, the displacement from `a` to `b` with the lowest sum of
+/// absolute differences. Larger displacements are penalized so flat or noisy
+/// areas stay still.
+fn estimate_motion(a: &VideoFrame, b: &VideoFrame) -> Vec<[f32; 2]> {
+    let (luma_a, width, height) = luma(a);
+    let (luma_b, _, _) = luma(b);
+    let (blocks_x, blocks_y) = block_grid(a.width, a.height);
+    let mut motion = vec![[0.0; 2]; blocks_x * blocks_y];
+
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let x0 = bx * BLOCK;
+            let y0 = by * BLOCK;
+            let x1 = (x0 + BLOCK).min(width);
+            let y1 = (y0 + BLOCK).min(height);
+            let pixels = ((x1.saturating_sub(x0)) * (y1.saturating_sub(y0))) as u32;
+            if pixels == 0 {
+                continue;
+            }
+
+            let mut best = (u32::MAX, 0, 0);
+            for dy in -SEARCH..=SEARCH {
+                for dx in -SEARCH..=SEARCH {
+                    let in_bounds = x0 as i32 + dx >= 0
+                        && y0 as i32 + dy >= 0
+                        && x1 as i32 + dx <= width as i32
+                        && y1 as i32 + dy <= height as i32;
+                    if !in_bounds {
+                        continue;
+                    }
+                    let mut cost = (dx.unsigned_abs() + dy.unsigned_abs()) * pixels;
+                    for y in y0..y1 {
+                        let row_a = &luma_a[y * width..];
+                        let row_b = &luma_b[(y as i32 + dy) as usize * width..];
+                        for x in x0..x1 {
+                            cost += row_a[x].abs_diff(row_b[(x as i32 + dx) as usize]) as u32;
+                        }
+                        if cost >= best.0 {
+                            break;
+                        }
+                    }
+                    if cost < best.0 {
+                        best = (cost, dx, dy);
+                    }
+                }
+            }
+            motion[by * blocks_x + bx] = [
+                (best.1 * LUMA_SCALE as i32) as f32,
+                (best.2 * LUMA_SCALE as i32) as f32,
+            ];
+        }
+    }
+    motion
+}
+
+/// Motion at a frame pixel, bilinearly interpolated between block centers.
+fn sample_motion(motion: &[[f32; 2]], blocks: (usize, usize), x: f32, y: f32) -> [f32; 2] {
+    let size = (BLOCK * LUMA_SCALE) as f32;
+    let gx = (x / size - 0.5).clamp(0.0, (blocks.0 - 1) as f32);
+    let gy = (y / size - 0.5).clamp(0.0, (blocks.1 - 1) as f32);
+    let (x0, y0) = (gx as usize, gy as usize);
+    let (x1, y1) = ((x0 + 1).min(blocks.0 - 1), (y0 + 1).min(blocks.1 - 1));
+    let (fx, fy) = (gx.fract(), gy.fract());
+
+    let at = |bx: usize, by: usize| motion[by * blocks.0 + bx];
+    let mut result = [0.0; 2];
+    for (axis, value) in result.iter_mut().enumerate() {
+        let top = at(x0, y0)[axis] * (1.0 - fx) + at(x1, y0)[axis] * fx;
+        let bottom = at(x0, y1)[axis] * (1.0 - fx) + at(x1, y1)[axis] * fx;
+        *value = top * (1.0 - fy) + bottom * fy;
+    }
+    result
+}
+
+/// Blends `a` moved forward and `b` moved back along the motion field.
+fn warp(a: &VideoFrame, b: &VideoFrame, motion: &[[f32; 2]], alpha: f32, output: &mut VideoFrame) {
+    prepare_output(b, output);
+    let width = b.width as usize;
+    let height = b.height as usize;
+    let blocks = block_grid(b.width, b.height);
+    let weight = (alpha * 256.0) as u32;
+
+    let offset = |x: f32, y: f32| -> usize {
+        let x = (x.round() as i64).clamp(0, width as i64 - 1) as usize;
+        let y = (y.round() as i64).clamp(0, height as i64 - 1) as usize;
+        (y * width + x) * 4
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let [vx, vy] = sample_motion(motion, blocks, x as f32, y as f32);
+            let src_a = offset(x as f32 - vx * alpha, y as f32 - vy * alpha);
+            let src_b = offset(x as f32 + vx * (1.0 - alpha), y as f32 + vy * (1.0 - alpha));
+            let dst = (y * width + x) * 4;
+            for c in 0..4 {
+                let value = a.data[src_a + c] as u32 * (256 - weight) + b.data[src_b + c] as u32 * weight;
+                output.data[dst + c] = (value >> 8) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise, so every block has a unique match.
+    fn noise(width: u32, height: u32, shift: u32) -> VideoFrame {
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let sx = (x + width - shift) % width;
+                let v = ((sx / 4).wrapping_mul(2_654_435_761) ^ (y / 4).wrapping_mul(40_503)) >> 7;
+                data.extend_from_slice(&[v as u8, v as u8, v as u8, 255]);
+            }
+        }
+        VideoFrame::from_data(width, height, PixelFormat::Rgba, data)
+    }
+
+    #[test]
+    fn test_blend_halfway() {
+        let mut interpolator = FrameInterpolator::new(InterpolationMode::Blend);
+        let mut black = VideoFrame::from_data(2, 2, PixelFormat::Rgba, vec![0; 16]);
+        let mut white = VideoFrame::from_data(2, 2, PixelFormat::Rgba, vec![255; 16]);
+        black.timestamp_us = Some(0);
+        white.timestamp_us = Some(100_000);
+
+        interpolator.next(&black, 0.0);
+        interpolator.interval = 0.1;
+        // The new frame fades in over one interval
+        assert_eq!(interpolator.next(&white, 1.0).data[0], 0);
+        assert!((120..=135).contains(&interpolator.next(&white, 1.05).data[0]));
+        assert_eq!(interpolator.next(&white, 1.2).data[0], 255);
+    }
+
+    #[test]
+    fn test_motion_estimation_finds_shift() {
+        let a = noise(128, 128, 0);
+        let b = noise(128, 128, 8);
+        let motion = estimate_motion(&a, &b);
+        let (blocks_x, _) = block_grid(128, 128);
+        assert_eq!(motion[blocks_x + 1], [8.0, 0.0]);
+    }
+}
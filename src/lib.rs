@@ -9,3 +9,26 @@ pub mod shader;
 pub mod ml;
 pub mod video;
 pub mod lua_canvas;
+pub mod mixer;
+pub mod monitor;
+pub mod clock;
+pub mod control;
+pub mod gamepad;
+pub mod recorder;
+pub mod interpolation;
+pub mod qr;
+pub mod gesture;
+pub mod history;
+pub mod compare;
+pub mod stats;
+pub mod schedule;
+pub mod captions;
+pub mod obs;
+pub mod mqtt;
+pub mod streamdeck;
+pub mod audio;
+pub mod web;
+pub mod session;
+pub mod reload;
+pub mod events;
+pub mod sync;
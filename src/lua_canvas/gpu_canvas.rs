@@ -3,7 +3,12 @@
 //! Provides primitives for 2D rendering: rectangles, circles, lines.
 //! Uses SDF-based fragment shaders for anti-aliased rendering.
 //! All draw calls are batched and submitted in a single command buffer.
+//!
+//! Draw commands are in logical coordinates, which are stretched over the
+//! render target. With supersampling the render target is a multiple of the
+//! output texture's size and is box-filtered down into it after each frame.
 
+use super::CanvasOptions;
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 
@@ -14,8 +19,15 @@ pub const MAX_PRIMITIVES: usize = 16384;
 pub struct GpuCanvas {
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
+    // Size of the coordinate space of draw commands
     pub width: u32,
     pub height: u32,
+    // Size of the render target
+    render_width: u32,
+    render_height: u32,
+    // Size of the output texture
+    output_width: u32,
+    output_height: u32,
     // Render target texture
     texture: wgpu::Texture,
     // Cached views
@@ -34,6 +46,12 @@ pub struct GpuCanvas {
     line_pipeline_clipped: wgpu::RenderPipeline,
     glyph_pipeline: wgpu::RenderPipeline,
     glyph_pipeline_clipped: wgpu::RenderPipeline,
+    feedback_pipeline: wgpu::RenderPipeline,
+    feedback_pipeline_clipped: wgpu::RenderPipeline,
+    camera_pipeline: wgpu::RenderPipeline,
+    camera_pipeline_clipped: wgpu::RenderPipeline,
+    sprite_pipeline: wgpu::RenderPipeline,
+    sprite_pipeline_clipped: wgpu::RenderPipeline,
     stencil_write_pipeline: wgpu::RenderPipeline,
     // Vertex buffer for full-screen quad
     quad_vertex_buffer: wgpu::Buffer,
@@ -42,16 +60,43 @@ pub struct GpuCanvas {
     // Glyph atlas resources
     glyph_atlas_texture: wgpu::Texture,
     glyph_bind_group: wgpu::BindGroup,
+    // Copy of the previous frame for canvas.feedback()
+    feedback_texture: wgpu::Texture,
+    feedback_bind_group: wgpu::BindGroup,
+    // Set once the script uses feedback, to skip the copy otherwise
+    feedback_used: bool,
+    // Camera texture for canvas.draw_camera(), set every frame by the pipeline
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    camera_sampler: wgpu::Sampler,
+    camera_bind_group: Option<wgpu::BindGroup>,
+    // Sprite sheets of canvas.load_spritesheet() and videos of
+    // canvas.open_video(), by index
+    sprite_sampler: wgpu::Sampler,
+    sprite_textures: Vec<SpriteTexture>,
     // Staging buffer for CPU readback
     staging_buffer: wgpu::Buffer,
-    // Current clip state
-    clip_active: bool,
     // Batched draw commands
     pending_commands: Vec<DrawCommand>,
     // Pending clear color (if any)
     pending_clear: Option<wgpu::Color>,
     // Pre-allocated uniform buffer for batching
     uniform_buffer: wgpu::Buffer,
+    // Output texture when supersampling
+    downsample: Option<Downsample>,
+}
+
+/// Output texture of a supersampled canvas and the pass that fills it.
+struct Downsample {
+    texture: wgpu::Texture,
+    srgb_view: wgpu::TextureView,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A texture drawn with `DrawCommandType::Sprite`.
+struct SpriteTexture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
 }
 
 /// Types of draw commands
@@ -62,11 +107,16 @@ pub enum DrawCommandType {
     StrokeCircle,
     Line,
     Glyph,
+    Feedback,
+    Camera,
+    /// A frame of the sprite sheet (or video) with this index
+    Sprite(u32),
     PushClip,
     PopClip,
 }
 
 /// A batched draw command
+#[derive(Clone)]
 pub struct DrawCommand {
     pub cmd_type: DrawCommandType,
     pub uniforms: [f32; 16], // 4x vec4 = 16 floats
@@ -98,8 +148,8 @@ const QUAD_VERTICES: &[[f32; 2]; 6] = &[
 ];
 
 impl GpuCanvas {
-    /// Create a new GPU canvas with the given dimensions.
-    pub fn new(width: u32, height: u32) -> Self {
+    /// Create a new GPU canvas with an output texture of the given dimensions.
+    pub fn new(width: u32, height: u32, options: CanvasOptions) -> Self {
         // Create wgpu instance and adapter
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
@@ -128,7 +178,7 @@ impl GpuCanvas {
         let device = Arc::new(device);
         let queue = Arc::new(queue);
 
-        Self::with_device_queue(device, queue, width, height)
+        Self::with_device_queue(device, queue, width, height, options)
     }
 
     /// Create a GPU canvas using an existing device and queue.
@@ -137,7 +187,13 @@ impl GpuCanvas {
         queue: Arc<wgpu::Queue>,
         width: u32,
         height: u32,
+        options: CanvasOptions,
     ) -> Self {
+        let (output_width, output_height) = (width, height);
+        let supersample = options.supersample.max(1);
+        let (width, height) = (output_width * supersample, output_height * supersample);
+        let (logical_width, logical_height) = options.logical_size.unwrap_or((output_width, output_height));
+
         // Create render target texture
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Canvas Texture"),
@@ -174,10 +230,10 @@ impl GpuCanvas {
 
         // Create staging buffer for CPU readback
         // Align to 256 bytes for COPY_BYTES_PER_ROW_ALIGNMENT
-        let aligned_bytes_per_row = (width * 4 + 255) & !255;
+        let aligned_bytes_per_row = (output_width * 4 + 255) & !255;
         let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Staging Buffer"),
-            size: (aligned_bytes_per_row * height) as u64,
+            size: (aligned_bytes_per_row * output_height) as u64,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         });
@@ -283,6 +339,58 @@ impl GpuCanvas {
             ],
         });
 
+        // The previous frame, sampled like the glyph atlas
+        let feedback_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Canvas Feedback Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let feedback_view = feedback_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let feedback_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Feedback Bind Group"),
+            layout: &glyph_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&feedback_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&glyph_atlas_sampler),
+                },
+            ],
+        });
+
+        // Trilinear, so small camera sprites use the input's mip levels
+        let camera_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Camera Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Sprite frames are clamped to their own texels, without mip levels
+        let sprite_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sprite Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
         // Create shaders and pipelines
         let rect_fill_pipeline = Self::create_rect_fill_pipeline(&device, &uniform_bind_group_layout, false);
         let rect_fill_clipped_pipeline = Self::create_rect_fill_pipeline(&device, &uniform_bind_group_layout, true);
@@ -294,13 +402,26 @@ impl GpuCanvas {
         let line_pipeline_clipped = Self::create_line_pipeline(&device, &uniform_bind_group_layout, true);
         let glyph_pipeline = Self::create_glyph_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, false);
         let glyph_pipeline_clipped = Self::create_glyph_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, true);
+        let feedback_pipeline = Self::create_feedback_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, false);
+        let feedback_pipeline_clipped = Self::create_feedback_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, true);
+        let camera_pipeline = Self::create_camera_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, false);
+        let camera_pipeline_clipped = Self::create_camera_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, true);
+        let sprite_pipeline = Self::create_sprite_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, false);
+        let sprite_pipeline_clipped = Self::create_sprite_pipeline(&device, &uniform_bind_group_layout, &glyph_bind_group_layout, true);
         let stencil_write_pipeline = Self::create_stencil_write_pipeline(&device, &uniform_bind_group_layout);
 
+        let downsample = (supersample > 1)
+            .then(|| Self::create_downsample(&device, &srgb_view, output_width, output_height, supersample));
+
         Self {
             device,
             queue,
-            width,
-            height,
+            width: logical_width,
+            height: logical_height,
+            render_width: width,
+            render_height: height,
+            output_width,
+            output_height,
             texture,
             texture_view,
             srgb_view,
@@ -315,19 +436,142 @@ impl GpuCanvas {
             line_pipeline_clipped,
             glyph_pipeline,
             glyph_pipeline_clipped,
+            feedback_pipeline,
+            feedback_pipeline_clipped,
+            camera_pipeline,
+            camera_pipeline_clipped,
+            sprite_pipeline,
+            sprite_pipeline_clipped,
             stencil_write_pipeline,
             quad_vertex_buffer,
             uniform_bind_group_layout,
             glyph_atlas_texture,
             glyph_bind_group,
+            feedback_texture,
+            feedback_bind_group,
+            feedback_used: false,
+            texture_bind_group_layout: glyph_bind_group_layout,
+            camera_sampler,
+            camera_bind_group: None,
+            sprite_sampler,
+            sprite_textures: Vec::new(),
             staging_buffer,
-            clip_active: false,
             pending_commands: Vec::with_capacity(1024),
             pending_clear: None,
             uniform_buffer,
+            downsample,
         }
     }
 
+    /// Output texture of `width`x`height` and the pass that box-filters the
+    /// render target (`source`, `factor` times larger) into it.
+    fn create_downsample(
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        factor: u32,
+    ) -> Downsample {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Canvas Output Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
+        });
+        // Averaged in linear light: read and written through sRGB views
+        let srgb_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Downsample Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Downsample Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Downsample Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/downsample.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Downsample Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+        let constants = [("FACTOR", factor as f64)];
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Downsample Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 8,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &[wgpu::VertexAttribute {
+                        offset: 0,
+                        shader_location: 0,
+                        format: wgpu::VertexFormat::Float32x2,
+                    }],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Downsample { texture, srgb_view, pipeline, bind_group }
+    }
+
+    /// Render target pixels per logical unit, horizontally and vertically.
+    pub fn pixel_scale(&self) -> (f32, f32) {
+        (self.render_width as f32 / self.width as f32, self.render_height as f32 / self.height as f32)
+    }
+
     fn create_rect_fill_pipeline(
         device: &wgpu::Device,
         bind_group_layout: &wgpu::BindGroupLayout,
@@ -391,9 +635,63 @@ impl GpuCanvas {
             source: wgpu::ShaderSource::Wgsl(include_str!("shaders/glyph.wgsl").into()),
         });
 
+        Self::create_textured_pipeline(device, uniform_layout, glyph_layout, &shader, "Glyph Pipeline", stencil_test)
+    }
+
+    fn create_feedback_pipeline(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+        stencil_test: bool,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Feedback Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/feedback.wgsl").into()),
+        });
+
+        Self::create_textured_pipeline(device, uniform_layout, texture_layout, &shader, "Feedback Pipeline", stencil_test)
+    }
+
+    fn create_camera_pipeline(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+        stencil_test: bool,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Camera Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/camera.wgsl").into()),
+        });
+
+        Self::create_textured_pipeline(device, uniform_layout, texture_layout, &shader, "Camera Pipeline", stencil_test)
+    }
+
+    fn create_sprite_pipeline(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+        stencil_test: bool,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sprite.wgsl").into()),
+        });
+
+        Self::create_textured_pipeline(device, uniform_layout, texture_layout, &shader, "Sprite Pipeline", stencil_test)
+    }
+
+    /// Pipeline for a shader that samples a texture bound at group 1.
+    fn create_textured_pipeline(
+        device: &wgpu::Device,
+        uniform_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+        shader: &wgpu::ShaderModule,
+        label: &str,
+        stencil_test: bool,
+    ) -> wgpu::RenderPipeline {
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Glyph Pipeline Layout"),
-            bind_group_layouts: &[uniform_layout, glyph_layout],
+            label: Some(&format!("{} Layout", label)),
+            bind_group_layouts: &[uniform_layout, texture_layout],
             immediate_size: 0,
         });
 
@@ -409,10 +707,10 @@ impl GpuCanvas {
         };
 
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Glyph Pipeline"),
+            label: Some(label),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("vs_main"),
                 buffers: &[wgpu::VertexBufferLayout {
                     array_stride: 8,
@@ -426,7 +724,7 @@ impl GpuCanvas {
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: wgpu::TextureFormat::Rgba8Unorm,
@@ -564,7 +862,6 @@ impl GpuCanvas {
             b: b as f64 / 255.0,
             a: a as f64 / 255.0,
         });
-        self.clip_active = false;
     }
 
 
@@ -591,17 +888,13 @@ impl GpuCanvas {
         );
     }
 
-    /// Queue a glyph draw command without an immediate flush.
-    pub fn queue_glyph(&mut self, 
-        target_x: f32, target_y: f32, target_w: f32, target_h: f32,
-        atlas_x: f32, atlas_y: f32, atlas_w: f32, atlas_h: f32,
-        r: u8, g: u8, b: u8, a: u8,
-    ) {
-        if self.pending_commands.len() >= MAX_PRIMITIVES {
-            self.flush();
-        }
-
-        self.pending_commands.push(DrawCommand {
+    /// Build a glyph draw command, to be batched like the other commands.
+    /// `target` and `atlas` are x, y, w, h; `color` is RGBA.
+    pub fn glyph_command(&self, target: [f32; 4], atlas: [f32; 4], color: [u8; 4], clip_active: bool) -> DrawCommand {
+        let [target_x, target_y, target_w, target_h] = target;
+        let [atlas_x, atlas_y, atlas_w, atlas_h] = atlas;
+        let [r, g, b, a] = color;
+        DrawCommand {
             cmd_type: DrawCommandType::Glyph,
             uniforms: [
                 target_x, target_y, target_w, target_h,
@@ -609,19 +902,21 @@ impl GpuCanvas {
                 r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0,
                 2048.0, 2048.0, self.width as f32, self.height as f32,
             ],
-            clip_active: self.clip_active,
-        });
+            clip_active,
+        }
     }
 
 
 
 
-    /// Draw an image directly to the canvas texture (used for sprites).
+    /// Draw an image directly to the canvas texture (used for sprites), at
+    /// render target pixels.
     /// Note: Call flush() first if you want this to appear on top of previous draws.
     pub fn draw_image(&self, x: i32, y: i32, width: u32, height: u32, data: &[u8]) {
-        if x < 0 || y < 0 || x + width as i32 > self.width as i32 || y + height as i32 > self.height as i32 {
+        let (render_width, render_height) = (self.render_width as i32, self.render_height as i32);
+        if x < 0 || y < 0 || x + width as i32 > render_width || y + height as i32 > render_height {
             // Partial clipping or skip? For now, skip if out of bounds to keep it simple.
-            if x >= self.width as i32 || y >= self.height as i32 || x + width as i32 <= 0 || y + height as i32 <= 0 {
+            if x >= render_width || y >= render_height || x + width as i32 <= 0 || y + height as i32 <= 0 {
                 return;
             }
         }
@@ -698,10 +993,6 @@ impl GpuCanvas {
         // Handle clear
         let has_clear = self.pending_clear.is_some();
         let clear_color = self.pending_clear.take().unwrap_or(wgpu::Color::TRANSPARENT);
-        
-        if has_clear {
-            self.clip_active = false; // Clear resets clip state for a new frame
-        }
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -761,6 +1052,21 @@ impl GpuCanvas {
                         if cmd.clip_active { &self.glyph_pipeline_clipped } else { &self.glyph_pipeline },
                         if cmd.clip_active { Some(1) } else { None }
                     ),
+                    DrawCommandType::Feedback => (
+                        if cmd.clip_active { &self.feedback_pipeline_clipped } else { &self.feedback_pipeline },
+                        if cmd.clip_active { Some(1) } else { None }
+                    ),
+                    // Nothing to draw without a camera texture
+                    DrawCommandType::Camera if self.camera_bind_group.is_none() => continue,
+                    DrawCommandType::Camera => (
+                        if cmd.clip_active { &self.camera_pipeline_clipped } else { &self.camera_pipeline },
+                        if cmd.clip_active { Some(1) } else { None }
+                    ),
+                    DrawCommandType::Sprite(sheet) if sheet as usize >= self.sprite_textures.len() => continue,
+                    DrawCommandType::Sprite(_) => (
+                        if cmd.clip_active { &self.sprite_pipeline_clipped } else { &self.sprite_pipeline },
+                        if cmd.clip_active { Some(1) } else { None }
+                    ),
                     DrawCommandType::PushClip => (
                         &self.stencil_write_pipeline,
                         Some(1)
@@ -779,15 +1085,15 @@ impl GpuCanvas {
                     // Re-bind uniforms
                     render_pass.set_bind_group(0, &bind_group, &[dynamic_offset]);
                     
-                    // For glyphs, also bind the atlas
-                    if matches!(cmd.cmd_type, DrawCommandType::Glyph) {
-                        render_pass.set_bind_group(1, &self.glyph_bind_group, &[]);
+                    // For glyphs and feedback, also bind the texture
+                    if let Some(texture_bind_group) = self.texture_bind_group(cmd.cmd_type) {
+                        render_pass.set_bind_group(1, texture_bind_group, &[]);
                     }
                 } else {
                     render_pass.set_bind_group(0, &bind_group, &[dynamic_offset]);
-                    // For glyphs, also bind the atlas
-                    if matches!(cmd.cmd_type, DrawCommandType::Glyph) {
-                        render_pass.set_bind_group(1, &self.glyph_bind_group, &[]);
+                    // For glyphs and feedback, also bind the texture
+                    if let Some(texture_bind_group) = self.texture_bind_group(cmd.cmd_type) {
+                        render_pass.set_bind_group(1, texture_bind_group, &[]);
                     }
                 }
 
@@ -809,12 +1115,163 @@ impl GpuCanvas {
         self.pending_commands.clear();
     }
 
+    /// Texture bound at group 1 for a command type.
+    fn texture_bind_group(&self, cmd_type: DrawCommandType) -> Option<&wgpu::BindGroup> {
+        match cmd_type {
+            DrawCommandType::Glyph => Some(&self.glyph_bind_group),
+            DrawCommandType::Feedback => Some(&self.feedback_bind_group),
+            DrawCommandType::Camera => self.camera_bind_group.as_ref(),
+            DrawCommandType::Sprite(sheet) => self.sprite_textures.get(sheet as usize).map(|sprite| &sprite.bind_group),
+            _ => None,
+        }
+    }
+
+    /// Sets the camera texture drawn by canvas.draw_camera(). `view` must not
+    /// be sRGB, since the canvas stores encoded values.
+    pub fn set_camera(&mut self, view: Option<&wgpu::TextureView>) {
+        self.camera_bind_group = view.map(|view| {
+            self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Camera Bind Group"),
+                layout: &self.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.camera_sampler),
+                    },
+                ],
+            })
+        });
+    }
+
+    /// Uploads a sprite sheet, returning its index for `DrawCommandType::Sprite`.
+    pub fn add_sprite_sheet(&mut self, image: &image::RgbaImage) -> u32 {
+        let index = self.add_sprite_texture(image.width(), image.height());
+        self.write_sprite_texture(index, image.width(), image.height(), image.as_raw());
+        index
+    }
+
+    /// Adds an empty sprite texture (e.g. for video frames), returning its
+    /// index for `DrawCommandType::Sprite`.
+    pub fn add_sprite_texture(&mut self, width: u32, height: u32) -> u32 {
+        let sprite = self.create_sprite_texture(width, height);
+        self.sprite_textures.push(sprite);
+        self.sprite_textures.len() as u32 - 1
+    }
+
+    /// Replaces the pixels (RGBA) of a sprite texture, resizing it if needed.
+    pub fn write_sprite_texture(&mut self, index: u32, width: u32, height: u32, data: &[u8]) {
+        let Some(sprite) = self.sprite_textures.get(index as usize) else {
+            return;
+        };
+        if sprite.texture.width() != width || sprite.texture.height() != height {
+            self.sprite_textures[index as usize] = self.create_sprite_texture(width, height);
+        }
+        let texture = &self.sprite_textures[index as usize].texture;
+        self.queue.write_texture(
+            texture.as_image_copy(),
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            texture.size(),
+        );
+    }
+
+    /// Like the camera, sprite textures aren't sRGB, since the canvas stores
+    /// encoded values.
+    fn create_sprite_texture(&self, width: u32, height: u32) -> SpriteTexture {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sprite Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Bind Group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sprite_sampler),
+                },
+            ],
+        });
+        SpriteTexture { texture, bind_group }
+    }
+
+    /// Records the copy of the finished frame into the feedback texture, for
+    /// canvas.feedback() in the next frame. Skipped until a script uses it.
+    fn store_feedback(&self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.feedback_used {
+            return;
+        }
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            self.feedback_texture.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.render_width,
+                height: self.render_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Records the downsampling of the render target into the output texture,
+    /// if supersampling.
+    fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(downsample) = &self.downsample else { return };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Downsample Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &downsample.srgb_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            ..Default::default()
+        });
+        render_pass.set_pipeline(&downsample.pipeline);
+        render_pass.set_bind_group(0, &downsample.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
+        render_pass.draw(0..6, 0..1);
+    }
+
+    /// The texture the canvas is read from.
+    fn output_texture(&self) -> &wgpu::Texture {
+        self.downsample.as_ref().map_or(&self.texture, |downsample| &downsample.texture)
+    }
+
     /// Add multiple pre-batched commands.
     pub fn add_commands(&mut self, commands: Vec<DrawCommand>) {
         if commands.is_empty() {
             return;
         }
         for cmd in commands {
+            if matches!(cmd.cmd_type, DrawCommandType::Feedback) {
+                self.feedback_used = true;
+            }
             if self.pending_commands.len() >= MAX_PRIMITIVES {
                 self.flush();
             }
@@ -827,15 +1284,16 @@ impl GpuCanvas {
         // Flush all pending draws first
         self.flush();
 
-        let aligned_bytes_per_row = (self.width * 4 + 255) & !255;
+        let aligned_bytes_per_row = (self.output_width * 4 + 255) & !255;
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Copy Encoder"),
         });
 
+        self.resolve(&mut encoder);
         encoder.copy_texture_to_buffer(
             wgpu::TexelCopyTextureInfo {
-                texture: &self.texture,
+                texture: self.output_texture(),
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
@@ -845,15 +1303,16 @@ impl GpuCanvas {
                 layout: wgpu::TexelCopyBufferLayout {
                     offset: 0,
                     bytes_per_row: Some(aligned_bytes_per_row),
-                    rows_per_image: Some(self.height),
+                    rows_per_image: Some(self.output_height),
                 },
             },
             wgpu::Extent3d {
-                width: self.width,
-                height: self.height,
+                width: self.output_width,
+                height: self.output_height,
                 depth_or_array_layers: 1,
             },
         );
+        self.store_feedback(&mut encoder);
 
         let submission_index = self.queue.submit(std::iter::once(encoder.finish()));
 
@@ -869,10 +1328,10 @@ impl GpuCanvas {
         let data = buffer_slice.get_mapped_range();
         
         // Remove padding from rows
-        let mut result = Vec::with_capacity((self.width * self.height * 4) as usize);
-        for y in 0..self.height {
+        let mut result = Vec::with_capacity((self.output_width * self.output_height * 4) as usize);
+        for y in 0..self.output_height {
             let start = (y * aligned_bytes_per_row) as usize;
-            let end = start + (self.width * 4) as usize;
+            let end = start + (self.output_width * 4) as usize;
             result.extend_from_slice(&data[start..end]);
         }
 
@@ -886,13 +1345,21 @@ impl GpuCanvas {
     /// Returns a reference to the texture that can be used directly for sampling.
     pub fn prepare_texture(&mut self) -> &wgpu::Texture {
         self.flush();
-        &self.texture
+        if self.feedback_used || self.downsample.is_some() {
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Feedback Encoder"),
+            });
+            self.resolve(&mut encoder);
+            self.store_feedback(&mut encoder);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.output_texture()
     }
 
     /// Get the cached sRGB texture view for the canvas texture.
     /// Note: Call prepare_texture() first to ensure all draws are flushed.
     /// Returns an sRGB view so samplers convert sRGB->linear automatically.
     pub fn texture_view(&self) -> &wgpu::TextureView {
-        &self.srgb_view
+        self.downsample.as_ref().map_or(&self.srgb_view, |downsample| &downsample.srgb_view)
     }
 }
@@ -5,23 +5,35 @@
 //! each frame to generate RGBA pixel data.
 
 mod gpu_canvas;
+mod particles;
+pub mod shaping;
+mod sprites;
 
 use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
 use anyhow::{anyhow, Result};
 use fontdb::{Database, ID};
 use gpu_canvas::GpuCanvas;
-use mlua::{Function, Lua, Table};
-use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use shaping::ShapeCache;
+use mlua::{Function, Lua, Table, Variadic};
+use crate::reload::{self, ReloadKind, Watch};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, Receiver};
 use std::sync::{Arc, Mutex};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info, warn};
+use crate::audio::AudioLevels;
+use crate::clock::WallTime;
+use crate::events;
+use crate::ml::{Keypoint, MAX_KEYPOINTS};
+use crate::shader::{Region, RegionShape};
+use crate::video::VideoPlayer;
 
 /// A Lua-driven canvas that renders to an RGBA buffer each frame.
 pub struct LuaCanvas {
     path: PathBuf,
     pub width: u32,
     pub height: u32,
+    // Size of the coordinate space of the script
+    logical_width: u32,
+    logical_height: u32,
     gpu_canvas: Arc<Mutex<GpuCanvas>>,
     lua: Lua,
     instance: Option<mlua::RegistryKey>,
@@ -30,18 +42,65 @@ pub struct LuaCanvas {
     view_dirty: bool,
     // API state for the high-performance batcher
     api_state: Arc<Mutex<GpuCanvasBatcherState>>,
+    // Mask regions set with proteus.set_regions(), until picked up by the pipeline
+    pending_regions: Arc<Mutex<Option<Vec<Region>>>>,
+    // Data buffer values set with proteus.set_buffer(), in call order
+    pending_buffers: PendingBuffers,
+    // Events for events.poll(), kept across script reloads
+    events: events::Subscription,
     // File watching
-    _watcher: Option<RecommendedWatcher>,
-    reload_rx: Option<Receiver<std::result::Result<Event, notify::Error>>>,
+    watch: Watch,
 }
 
+/// Largest supersampling factor of a canvas.
+pub const MAX_SUPERSAMPLE: u32 = 4;
+
+/// How a canvas renders relative to the texture it's bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanvasOptions {
+    /// Renders at this multiple of the texture size and downsamples
+    pub supersample: u32,
+    /// Width and height scripts draw in, stretched over the texture; the
+    /// texture size without one
+    pub logical_size: Option<(u32, u32)>,
+}
+
+impl Default for CanvasOptions {
+    fn default() -> Self {
+        Self { supersample: 1, logical_size: None }
+    }
+}
+
+/// Runtime state exposed to Lua scripts through the global `proteus` table.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScriptGlobals {
+    /// Tally state: true when the output is live / on air
+    pub live: bool,
+    /// Tempo of the musical clock
+    pub bpm: f32,
+    /// Beats elapsed since start (fractional)
+    pub beat: f64,
+    /// Position within the current beat (0.0 - 1.0)
+    pub beat_phase: f32,
+    /// Position within the current bar (0.0 - 1.0)
+    pub bar_phase: f32,
+    /// Wall clock time
+    pub wall_time: WallTime,
+    /// Levels of the audio meter (zero without one)
+    pub audio: AudioLevels,
+    /// Body pose keypoints, if a pose model is configured
+    pub pose: Option<[Keypoint; MAX_KEYPOINTS]>,
+}
+
+/// Data buffer names and values set by a script.
+type PendingBuffers = Arc<Mutex<Vec<(String, Vec<f32>)>>>;
+
 /// Cached glyph entry in the atlas
 struct GlyphCacheEntry {
     atlas_x: u32,
     atlas_y: u32,
     width: u32,
     height: u32,
-    advance: f32,
     offset_x: f32,
     offset_y: f32,
 }
@@ -110,6 +169,195 @@ struct GpuCanvasBatcherState {
     // Glyph caching: key is (font_id, glyph_id, size_in_tenths)
     glyph_cache: std::collections::HashMap<(ID, u16, u32), GlyphCacheEntry>,
     atlas_allocator: AtlasAllocator,
+    // Incremented when the atlas is reset, which invalidates recorded glyphs
+    atlas_generation: u64,
+    // Depth of canvas.record() calls in progress
+    recording: usize,
+    // Shaped strings by font
+    shape_cache: ShapeCache,
+    // Sprite sheets by path and frame size, kept across script reloads
+    sprite_sheets: std::collections::HashMap<(String, u32, u32), Arc<SpriteSheet>>,
+    // Sprite textures of closed videos, for the next canvas.open_video()
+    released_videos: Arc<Mutex<Vec<u32>>>,
+    // Time of the current frame, for videos
+    time: f32,
+}
+
+/// A sprite sheet uploaded to the canvas.
+struct SpriteSheet {
+    index: u32,
+    layout: sprites::SheetLayout,
+}
+
+/// A sprite sheet as a Lua value.
+struct SpriteSheetHandle(Arc<SpriteSheet>);
+
+impl mlua::UserData for SpriteSheetHandle {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        // sheet:frame_size() -> w, h
+        methods.add_method("frame_size", |_, sheet, ()| Ok((sheet.0.layout.frame_width, sheet.0.layout.frame_height)));
+    }
+}
+
+/// A video from canvas.open_video(), whose frames are uploaded to a sprite
+/// texture as canvas.draw_video() shows them.
+struct VideoHandle {
+    path: String,
+    player: VideoPlayer,
+    index: u32,
+    looping: bool,
+    // Canvas time of the first draw
+    start: Option<f32>,
+    // Set when a video that doesn't loop is drawn after its end
+    ended: bool,
+    // Timestamp and size of the frame in the texture
+    uploaded: Option<(f32, u32, u32)>,
+    released: Arc<Mutex<Vec<u32>>>,
+}
+
+impl Drop for VideoHandle {
+    fn drop(&mut self) {
+        self.released.lock().unwrap().push(self.index);
+    }
+}
+
+impl mlua::UserData for VideoHandle {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        // video:size() -> w, h
+        methods.add_method("size", |_, video, ()| Ok((video.player.width, video.player.height)));
+        // video:duration() -> seconds (0 for live streams)
+        methods.add_method("duration", |_, video, ()| Ok(video.player.duration));
+        // video:playing(), false once a video that doesn't loop has ended
+        methods.add_method("playing", |_, video, ()| Ok(!video.ended));
+        // video:restart()
+        methods.add_method_mut("restart", |_, video, ()| {
+            video.player = VideoPlayer::new(&video.path).map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+            video.start = None;
+            video.ended = false;
+            video.uploaded = None;
+            Ok(())
+        });
+    }
+}
+
+/// Draw commands recorded with canvas.record(), drawn again with `list:draw()`
+/// without running the Lua code that made them.
+struct DrawList {
+    commands: Vec<gpu_canvas::DrawCommand>,
+    // Clip state after the commands
+    clip_active: bool,
+    atlas_generation: u64,
+    state: Arc<Mutex<GpuCanvasBatcherState>>,
+}
+
+impl mlua::UserData for DrawList {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        // list:draw()
+        methods.add_method("draw", |_, list, ()| {
+            let mut s = list.state.lock().unwrap();
+            s.commands.extend(list.commands.iter().cloned());
+            s.clip_active = list.clip_active;
+            Ok(())
+        });
+        // list:valid(), false once the glyphs it draws were evicted from the atlas
+        methods.add_method("valid", |_, list, ()| {
+            Ok(list.atlas_generation == list.state.lock().unwrap().atlas_generation)
+        });
+    }
+}
+
+/// A particle emitter as a Lua value, from particles.new{...}.
+struct ParticleEmitter {
+    emitter: particles::Emitter,
+    state: Arc<Mutex<GpuCanvasBatcherState>>,
+}
+
+impl mlua::UserData for ParticleEmitter {
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        // emitter:set{...}
+        methods.add_method_mut("set", |_, p, settings: Table| set_emitter_settings(&mut p.emitter.settings, &settings));
+        // emitter:emit(count, [x, y])
+        methods.add_method_mut("emit", |_, p, (count, x, y): (usize, Option<f32>, Option<f32>)| {
+            p.emitter.emit(count, x.zip(y));
+            Ok(())
+        });
+        // emitter:update(dt)
+        methods.add_method_mut("update", |_, p, dt: f32| {
+            p.emitter.update(dt);
+            Ok(())
+        });
+        // emitter:draw()
+        methods.add_method("draw", |_, p, ()| {
+            let mut s = p.state.lock().unwrap();
+            let (w, h) = (s.width as f32, s.height as f32);
+            let clip = s.clip_active;
+            let commands = p.emitter.sprites().map(|particle| {
+                let [r, g, b, a] = particle.color.map(|c| c.clamp(0.0, 255.0) / 255.0);
+                gpu_canvas::DrawCommand {
+                    cmd_type: gpu_canvas::DrawCommandType::FillCircle,
+                    uniforms: [particle.x, particle.y, particle.size / 2.0, 0.0, r, g, b, a, 0.0, w, h, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    clip_active: clip,
+                }
+            });
+            s.commands.extend(commands);
+            Ok(())
+        });
+        // emitter:count()
+        methods.add_method("count", |_, p, ()| Ok(p.emitter.count()));
+        // emitter:clear()
+        methods.add_method_mut("clear", |_, p, ()| {
+            p.emitter.clear();
+            Ok(())
+        });
+    }
+}
+
+/// Applies the settings in `table` (as in particles.new{...}) to `settings`.
+fn set_emitter_settings(settings: &mut particles::EmitterSettings, table: &Table) -> mlua::Result<()> {
+    // A number, or a pair of numbers as a table
+    let pair = |key: &str| -> mlua::Result<Option<[f32; 2]>> {
+        Ok(match table.get::<mlua::Value>(key)? {
+            mlua::Value::Nil => None,
+            mlua::Value::Table(pair) => Some([pair.get(1)?, pair.get(2)?]),
+            mlua::Value::Number(value) => Some([value as f32; 2]),
+            mlua::Value::Integer(value) => Some([value as f32; 2]),
+            _ => return Err(mlua::Error::external(format!("particles: `{}` must be a number or a pair of numbers", key))),
+        })
+    };
+    for (key, field) in [
+        ("x", &mut settings.x),
+        ("y", &mut settings.y),
+        ("width", &mut settings.width),
+        ("height", &mut settings.height),
+        ("rate", &mut settings.rate),
+        ("angle", &mut settings.angle),
+        ("spread", &mut settings.spread),
+        ("drag", &mut settings.drag),
+    ] {
+        if let Some(value) = table.get::<Option<f32>>(key)? {
+            *field = value;
+        }
+    }
+    if let Some(max) = table.get::<Option<usize>>("max")? {
+        settings.max = max.min(particles::MAX_PARTICLES);
+    }
+    if let Some(lifetime) = pair("lifetime")? {
+        settings.lifetime = lifetime;
+    }
+    if let Some(speed) = pair("speed")? {
+        settings.speed = speed;
+    }
+    if let Some(size) = pair("size")? {
+        settings.size = size;
+    }
+    if let Some(gravity) = table.get::<Option<[f32; 2]>>("gravity")? {
+        settings.gravity = gravity;
+    }
+    if let Some(colors) = table.get::<Option<Vec<Vec<f32>>>>("colors")? {
+        // Opaque without an alpha
+        settings.colors = colors.iter().map(|c| [0, 1, 2, 3].map(|i| c.get(i).copied().unwrap_or(255.0))).collect();
+    }
+    Ok(())
 }
 
 /// Wrapper for Lua to call canvas methods efficiently
@@ -158,6 +406,11 @@ impl FontDatabase {
         self.db.faces().next().map(|f| f.id)
     }
 
+    /// Get the first monospaced font ID, if any.
+    pub fn monospace_font(&self) -> Option<ID> {
+        self.db.faces().find(|f| f.monospaced).map(|f| f.id)
+    }
+
     /// Get cached font data for a font ID.
     pub fn get_font_data(&self, id: ID) -> Option<Arc<Vec<u8>>> {
         // Check cache first
@@ -206,21 +459,27 @@ pub struct LuaFrame {
 }
 
 impl LuaCanvas {
-    /// Create a new LuaCanvas from a Lua script path.
+    /// Create a new LuaCanvas from a Lua script path, rendering to a texture
+    /// of `width`x`height`.
     pub fn new(
         path: impl AsRef<Path>,
         width: u32,
         height: u32,
+        options: CanvasOptions,
         device_queue: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
     ) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
         info!("Creating LuaCanvas from {:?} ({}x{})", path, width, height);
 
         let gpu_canvas = if let Some((device, queue)) = device_queue {
-            GpuCanvas::with_device_queue(device, queue, width, height)
+            GpuCanvas::with_device_queue(device, queue, width, height, options)
         } else {
-            GpuCanvas::new(width, height)
+            GpuCanvas::new(width, height, options)
         };
+        let (logical_width, logical_height) = (gpu_canvas.width, gpu_canvas.height);
+        if options != CanvasOptions::default() {
+            info!("Lua canvas draws at {}x{}, supersampled {}x", logical_width, logical_height, options.supersample.max(1));
+        }
         let gpu_canvas = Arc::new(Mutex::new(gpu_canvas));
 
         // Initialize font database with system fonts
@@ -228,25 +487,7 @@ impl LuaCanvas {
 
         let lua = Lua::new();
         
-        // Setup file watcher
-        let (watcher, reload_rx) = {
-            let (tx, rx) = channel();
-            match RecommendedWatcher::new(tx, notify::Config::default()) {
-                Ok(mut w) => {
-                    if let Err(e) = w.watch(&path, RecursiveMode::NonRecursive) {
-                        warn!("Failed to watch Lua script {:?}: {}", path, e);
-                        (None, None)
-                    } else {
-                        info!("Watching Lua script {:?} for changes", path);
-                        (Some(w), Some(rx))
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to create file watcher: {}", e);
-                    (None, None)
-                }
-            }
-        };
+        let watch = reload::watch(ReloadKind::Lua, [path.clone()]);
         
         let image_cache = Arc::new(Mutex::new(std::collections::HashMap::new()));
 
@@ -254,6 +495,8 @@ impl LuaCanvas {
             path,
             width,
             height,
+            logical_width,
+            logical_height,
             gpu_canvas: gpu_canvas.clone(),
             lua,
             instance: None,
@@ -261,8 +504,8 @@ impl LuaCanvas {
             initialized: false,
             view_dirty: true,
             api_state: Arc::new(Mutex::new(GpuCanvasBatcherState {
-                width,
-                height,
+                width: logical_width,
+                height: logical_height,
                 commands: Vec::with_capacity(1024),
                 clip_active: false,
                 gpu_canvas,
@@ -270,9 +513,17 @@ impl LuaCanvas {
                 image_cache,
                 glyph_cache: std::collections::HashMap::new(),
                 atlas_allocator: AtlasAllocator::new(2048),
+                atlas_generation: 0,
+                recording: 0,
+                shape_cache: ShapeCache::default(),
+                sprite_sheets: std::collections::HashMap::new(),
+                released_videos: Arc::new(Mutex::new(Vec::new())),
+                time: 0.0,
             })),
-            _watcher: watcher,
-            reload_rx,
+            pending_regions: Arc::new(Mutex::new(None)),
+            pending_buffers: Arc::new(Mutex::new(Vec::new())),
+            events: events::subscribe(),
+            watch,
         };
 
         canvas.load_script()?;
@@ -286,6 +537,8 @@ impl LuaCanvas {
 
         // Register canvas drawing functions
         self.register_canvas_api()?;
+        self.register_proteus_api()?;
+        self.register_events_api()?;
         
         // Expose script directory as a global
         let script_dir = self.path.parent()
@@ -334,6 +587,124 @@ impl LuaCanvas {
         Ok(())
     }
 
+    /// Update the global `proteus` table read by scripts (e.g. `proteus.live`).
+    pub fn set_globals(&mut self, globals: &ScriptGlobals) -> Result<()> {
+        let lua_globals = self.lua.globals();
+        let table: Table = match lua_globals.get("proteus")? {
+            mlua::Value::Table(table) => table,
+            _ => {
+                let table = self.lua.create_table()?;
+                lua_globals.set("proteus", table.clone())?;
+                table
+            }
+        };
+        table.set("live", globals.live)?;
+        table.set("bpm", globals.bpm)?;
+        table.set("beat", globals.beat)?;
+        table.set("beat_phase", globals.beat_phase)?;
+        table.set("bar_phase", globals.bar_phase)?;
+        table.set("clock", globals.wall_time.seconds_of_day)?;
+        table.set("unix_time", globals.wall_time.unix)?;
+        table.set("timecode", globals.wall_time.timecode())?;
+        table.set("clock_synced", globals.wall_time.synced)?;
+        let audio = self.lua.create_table()?;
+        audio.set("peak", self.lua.create_sequence_from(globals.audio.peak)?)?;
+        audio.set("rms", self.lua.create_sequence_from(globals.audio.rms)?)?;
+        table.set("audio", audio)?;
+        match &globals.pose {
+            Some(pose) => {
+                let keypoints = self.lua.create_table()?;
+                for (i, keypoint) in pose.iter().enumerate() {
+                    let entry = self.lua.create_table()?;
+                    entry.set("x", keypoint.x)?;
+                    entry.set("y", keypoint.y)?;
+                    entry.set("score", keypoint.score)?;
+                    keypoints.set(i + 1, entry)?;
+                }
+                table.set("pose", keypoints)?;
+            }
+            None => table.set("pose", mlua::Value::Nil)?,
+        }
+        Ok(())
+    }
+
+    /// Returns the mask regions the script set since the last call.
+    pub fn take_regions(&self) -> Option<Vec<Region>> {
+        self.pending_regions.lock().unwrap().take()
+    }
+
+    /// Returns the data buffer values the script set since the last call.
+    pub fn take_buffers(&self) -> Vec<(String, Vec<f32>)> {
+        std::mem::take(&mut *self.pending_buffers.lock().unwrap())
+    }
+
+    /// Register the functions of the global `proteus` table.
+    fn register_proteus_api(&mut self) -> Result<()> {
+        let lua_globals = self.lua.globals();
+        let table: Table = match lua_globals.get("proteus")? {
+            mlua::Value::Table(table) => table,
+            _ => {
+                let table = self.lua.create_table()?;
+                lua_globals.set("proteus", table.clone())?;
+                table
+            }
+        };
+
+        // proteus.set_regions({ {x=, y=, w=, h=, feather=}, {points={{x, y}, ...}, feather=}, ... })
+        let pending_regions = self.pending_regions.clone();
+        let set_regions_fn = self.lua.create_function(move |_, regions: Vec<Table>| {
+            let regions = regions
+                .iter()
+                .map(|region| {
+                    let shape = match region.get::<Option<Vec<[f32; 2]>>>("points")? {
+                        Some(points) => RegionShape::Polygon(points),
+                        None => RegionShape::Rect {
+                            x: region.get("x")?,
+                            y: region.get("y")?,
+                            width: region.get("w")?,
+                            height: region.get("h")?,
+                        },
+                    };
+                    Ok(Region { shape, feather: region.get::<Option<f32>>("feather")?.unwrap_or(0.0) })
+                })
+                .collect::<mlua::Result<Vec<_>>>()?;
+            *pending_regions.lock().unwrap() = Some(regions);
+            Ok(())
+        })?;
+        table.set("set_regions", set_regions_fn)?;
+
+        // proteus.set_buffer(name, { x, y, z, w, ... })
+        let pending_buffers = self.pending_buffers.clone();
+        let set_buffer_fn = self.lua.create_function(move |_, (name, values): (String, Vec<f32>)| {
+            pending_buffers.lock().unwrap().push((name, values));
+            Ok(())
+        })?;
+        table.set("set_buffer", set_buffer_fn)?;
+        Ok(())
+    }
+
+    /// Register the global `events` table.
+    fn register_events_api(&mut self) -> Result<()> {
+        let table = self.lua.create_table()?;
+
+        // events.poll() -> { {name=, payload=}, ... }, oldest first
+        let subscription = self.events.clone();
+        let poll_fn = self.lua.create_function(move |lua, ()| {
+            let list = lua.create_table()?;
+            for (i, event) in subscription.poll().into_iter().enumerate() {
+                let entry = lua.create_table()?;
+                entry.set("name", event.name)?;
+                entry.set("payload", json_to_lua(lua, event.payload)?)?;
+                list.set(i + 1, entry)?;
+            }
+            Ok(list)
+        })?;
+        table.set("poll", poll_fn)?;
+
+        self.lua.globals().set("events", table)?;
+        Ok(())
+    }
+
     /// Register the canvas drawing API in Lua globals.
     fn register_canvas_api(&mut self) -> Result<()> {
         let state = self.api_state.clone();
@@ -347,6 +718,9 @@ impl LuaCanvas {
             let state = state.clone();
             let clear_fn = lua.create_function(move |_, (r, g, b, a): (u8, u8, u8, u8)| {
                 let mut s = state.lock().unwrap();
+                if s.recording > 0 {
+                    return Err(mlua::Error::external("canvas.clear() can't be recorded"));
+                }
                 let (w, h) = (s.width as f32, s.height as f32);
                 s.commands.clear();
                 s.commands.push(gpu_canvas::DrawCommand {
@@ -512,11 +886,52 @@ impl LuaCanvas {
             canvas_table.set("draw_line", draw_line_fn)?;
         }
 
+        // canvas.feedback([alpha], [scale], [dx], [dy], [angle])
+        {
+            type FeedbackArgs = (Option<f32>, Option<f32>, Option<f32>, Option<f32>, Option<f32>);
+            let state = state.clone();
+            let feedback_fn = lua.create_function(move |_, (alpha, scale, dx, dy, angle): FeedbackArgs| {
+                let mut s = state.lock().unwrap();
+                let (w, h) = (s.width as f32, s.height as f32);
+                let clip = s.clip_active;
+                // A zero scale would divide by zero in the shader
+                let scale = scale.unwrap_or(1.0).max(1e-3);
+                s.commands.push(gpu_canvas::DrawCommand {
+                    cmd_type: gpu_canvas::DrawCommandType::Feedback,
+                    uniforms: [alpha.unwrap_or(1.0), scale, dx.unwrap_or(0.0), dy.unwrap_or(0.0), angle.unwrap_or(0.0), 0.0, 0.0, 0.0, 0.0, w, h, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    clip_active: clip,
+                });
+                Ok(())
+            })?;
+            canvas_table.set("feedback", feedback_fn)?;
+        }
+
+        // canvas.draw_camera(x, y, w, h)
+        {
+            let state = state.clone();
+            let draw_camera_fn = lua.create_function(move |_, (x, y, wr, hr): (f32, f32, f32, f32)| {
+                let mut s = state.lock().unwrap();
+                let (w, h) = (s.width as f32, s.height as f32);
+                let clip = s.clip_active;
+                s.commands.push(gpu_canvas::DrawCommand {
+                    cmd_type: gpu_canvas::DrawCommandType::Camera,
+                    uniforms: [x, y, wr, hr, 0.0, 0.0, 0.0, 0.0, 0.0, w, h, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    clip_active: clip,
+                });
+                Ok(())
+            })?;
+            canvas_table.set("draw_camera", draw_camera_fn)?;
+        }
+
         // canvas.draw_image(path, x, y, [w, h])
         {
             let state = state.clone();
             let draw_image_fn = lua.create_function(move |_, (path, x, y, img_w, img_h): (String, f32, f32, Option<f32>, Option<f32>)| {
                 let mut s = state.lock().unwrap();
+                // Drawn right away rather than batched
+                if s.recording > 0 {
+                    return Err(mlua::Error::external("canvas.draw_image() can't be recorded"));
+                }
                 // Ensure all batched commands are pushed to GPU before immediate draw
                 if !s.commands.is_empty() {
                     let commands = std::mem::take(&mut s.commands);
@@ -530,6 +945,112 @@ impl LuaCanvas {
             canvas_table.set("draw_image", draw_image_fn)?;
         }
 
+        // canvas.load_spritesheet(path, frame_w, frame_h) -> sheet
+        {
+            let state = state.clone();
+            let load_spritesheet_fn = lua.create_function(move |_, (path, frame_w, frame_h): (String, u32, u32)| {
+                let mut s = state.lock().unwrap();
+                let key = (path.clone(), frame_w, frame_h);
+                if let Some(sheet) = s.sprite_sheets.get(&key) {
+                    return Ok(SpriteSheetHandle(sheet.clone()));
+                }
+                let (image, layout) = sprites::load(Path::new(&path), frame_w, frame_h).map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+                let index = s.gpu_canvas.lock().unwrap().add_sprite_sheet(&image);
+                info!("Loaded sprite sheet {:?} ({} frames, animations: {:?})", path, layout.frame_count, layout.animations.keys().collect::<Vec<_>>());
+                let sheet = Arc::new(SpriteSheet { index, layout });
+                s.sprite_sheets.insert(key, sheet.clone());
+                Ok(SpriteSheetHandle(sheet))
+            })?;
+            canvas_table.set("load_spritesheet", load_spritesheet_fn)?;
+        }
+
+        // canvas.draw_anim(sheet, anim_name, x, y, t, [w, h])
+        {
+            let state = state.clone();
+            let draw_anim_fn = lua.create_function(move |_, (sheet, name, x, y, t, size): (mlua::AnyUserData, String, f32, f32, f32, Variadic<f32>)| {
+                let (wr, hr) = (size.first().copied(), size.get(1).copied());
+                let sheet = sheet.borrow::<SpriteSheetHandle>()?.0.clone();
+                let Some(animation) = sheet.layout.animations.get(&name) else {
+                    return Err(mlua::Error::external(format!("Sprite sheet has no animation {:?}", name)));
+                };
+                let [fx, fy, fw, fh] = sheet.layout.frame_rect(animation.frame_at(t)).map(|v| v as f32);
+                let mut s = state.lock().unwrap();
+                let (w, h) = (s.width as f32, s.height as f32);
+                let clip = s.clip_active;
+                s.commands.push(gpu_canvas::DrawCommand {
+                    cmd_type: gpu_canvas::DrawCommandType::Sprite(sheet.index),
+                    uniforms: [x, y, wr.unwrap_or(fw), hr.unwrap_or(fh), fx, fy, fw, fh, 1.0, w, h, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    clip_active: clip,
+                });
+                Ok(())
+            })?;
+            canvas_table.set("draw_anim", draw_anim_fn)?;
+        }
+
+        // canvas.open_video(path, [{loop = true}]) -> video
+        {
+            let state = state.clone();
+            let open_video_fn = lua.create_function(move |_, (path, options): (String, Option<Table>)| {
+                let looping = match &options {
+                    Some(options) => options.get::<Option<bool>>("loop")?.unwrap_or(true),
+                    None => true,
+                };
+                let player = VideoPlayer::new(&path).map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+                let s = state.lock().unwrap();
+                let released = s.released_videos.clone();
+                let reused = released.lock().unwrap().pop();
+                let index = match reused {
+                    Some(index) => index,
+                    None => s.gpu_canvas.lock().unwrap().add_sprite_texture(player.width, player.height),
+                };
+                Ok(VideoHandle { path, player, index, looping, start: None, ended: false, uploaded: None, released })
+            })?;
+            canvas_table.set("open_video", open_video_fn)?;
+        }
+
+        // canvas.draw_video(video, x, y, [w, h])
+        {
+            let state = state.clone();
+            let draw_video_fn = lua.create_function(move |_, (video, x, y, size): (mlua::AnyUserData, f32, f32, Variadic<f32>)| {
+                let mut video = video.borrow_mut::<VideoHandle>()?;
+                let video = &mut *video;
+                let mut s = state.lock().unwrap();
+                // Shows the frame of the time it's drawn at
+                if s.recording > 0 {
+                    return Err(mlua::Error::external("canvas.draw_video() can't be recorded"));
+                }
+                let time = s.time;
+                let start = *video.start.get_or_insert(time);
+                if !video.looping && video.player.duration > 0.0 && time - start >= video.player.duration {
+                    video.ended = true;
+                }
+                if video.ended {
+                    return Ok(());
+                }
+                if let Some(frame) = video.player.get_frame(time) {
+                    if video.uploaded.map(|(timestamp, _, _)| timestamp) != Some(frame.timestamp) {
+                        s.gpu_canvas.lock().unwrap().write_sprite_texture(video.index, frame.width, frame.height, &frame.data);
+                        video.uploaded = Some((frame.timestamp, frame.width, frame.height));
+                    }
+                }
+                // Nothing to show before the first frame is decoded
+                let Some((_, fw, fh)) = video.uploaded else {
+                    return Ok(());
+                };
+                let (fw, fh) = (fw as f32, fh as f32);
+                let (wr, hr) = (size.first().copied(), size.get(1).copied());
+                let (w, h) = (s.width as f32, s.height as f32);
+                let clip = s.clip_active;
+                s.commands.push(gpu_canvas::DrawCommand {
+                    cmd_type: gpu_canvas::DrawCommandType::Sprite(video.index),
+                    uniforms: [x, y, wr.unwrap_or(fw), hr.unwrap_or(fh), 0.0, 0.0, fw, fh, 1.0, w, h, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    clip_active: clip,
+                });
+                Ok(())
+            })?;
+            canvas_table.set("draw_video", draw_video_fn)?;
+        }
+
         // canvas.draw_text_font(x, y, text, font, size, r, g, b, a)
         {
             let state = state.clone();
@@ -545,8 +1066,8 @@ impl LuaCanvas {
         {
             let state = state.clone();
             let measure_text_fn = lua.create_function(move |_, (text, size): (String, f32)| {
-                let s = state.lock().unwrap();
-                let (w, h) = measure_text_impl(&s.font_db, None, &text, size);
+                let mut s = state.lock().unwrap();
+                let (w, h) = measure_text_impl(&mut s, None, &text, size);
                 Ok((w, h))
             })?;
             canvas_table.set("measure_text", measure_text_fn)?;
@@ -556,8 +1077,8 @@ impl LuaCanvas {
         {
             let state = state.clone();
             let measure_text_font_fn = lua.create_function(move |_, (text, font, size): (String, String, f32)| {
-                let s = state.lock().unwrap();
-                let (w, h) = measure_text_impl(&s.font_db, Some(&font), &text, size);
+                let mut s = state.lock().unwrap();
+                let (w, h) = measure_text_impl(&mut s, Some(&font), &text, size);
                 Ok((w, h))
             })?;
             canvas_table.set("measure_text_font", measure_text_font_fn)?;
@@ -573,43 +1094,67 @@ impl LuaCanvas {
             canvas_table.set("list_fonts", list_fonts_fn)?;
         }
 
+        // particles.new{...} -> emitter
+        {
+            let state = state.clone();
+            let particles_table = lua.create_table()?;
+            let new_fn = lua.create_function(move |_, settings: Option<Table>| {
+                let mut emitter_settings = particles::EmitterSettings::default();
+                if let Some(settings) = settings {
+                    set_emitter_settings(&mut emitter_settings, &settings)?;
+                }
+                Ok(ParticleEmitter { emitter: particles::Emitter::new(emitter_settings), state: state.clone() })
+            })?;
+            particles_table.set("new", new_fn)?;
+            lua.globals().set("particles", particles_table)?;
+        }
+
+        // canvas.record(fn) -> draw list of the draws fn makes
+        {
+            let state = state.clone();
+            let record_fn = lua.create_function(move |_, function: Function| {
+                let (outer, clip_active) = {
+                    let mut s = state.lock().unwrap();
+                    s.recording += 1;
+                    (std::mem::take(&mut s.commands), s.clip_active)
+                };
+                let result = function.call::<()>(());
+                let mut s = state.lock().unwrap();
+                s.recording -= 1;
+                let commands = std::mem::replace(&mut s.commands, outer);
+                // Recording draws nothing yet
+                let list = DrawList { commands, clip_active: s.clip_active, atlas_generation: s.atlas_generation, state: state.clone() };
+                s.clip_active = clip_active;
+                result.map(|()| list)
+            })?;
+            canvas_table.set("record", record_fn)?;
+        }
+
         // width, height
-        canvas_table.set("width", self.width)?;
-        canvas_table.set("height", self.height)?;
+        canvas_table.set("width", self.logical_width)?;
+        canvas_table.set("height", self.logical_height)?;
 
         self.lua.globals().set("canvas", canvas_table)?;
+
+        // Widget toolkit, built on canvas.record()
+        let ui: Table = self.lua.load(include_str!("ui.lua")).set_name("ui.lua").eval()?;
+        self.lua.globals().set("ui", ui)?;
         Ok(())
     }
 
-    /// Check for file changes using notify watcher and reload if necessary.
+    /// Check for file changes and reload if necessary.
     fn check_reload(&mut self) {
-        let Some(rx) = &self.reload_rx else { return; };
-        
-        let mut needs_reload = false;
-        // Drain channel to clear backlog and debounce
-        while let Ok(res) = rx.try_recv() {
-            match res {
-                Ok(event) => {
-                    if matches!(event.kind, notify::EventKind::Modify(_) | notify::EventKind::Create(_)) {
-                        needs_reload = true;
-                        info!("Lua script modified: {:?}", event.paths);
-                    }
-                }
-                Err(e) => warn!("Watch error: {}", e),
-            }
+        if self.watch.poll().is_none() {
+            return;
         }
+        info!("Reloading Lua script...");
 
-        if needs_reload {
-            info!("Reloading Lua script...");
-            
-            let saved_state = self.try_save_state();
-            
-            if let Err(e) = self.load_script() {
-                error!("Failed to reload Lua script: {}", e);
-            } else if let Some(state) = saved_state {
-                self.try_load_state(state);
-            }
+        let saved_state = self.try_save_state();
+        let result = self.load_script();
+        if let (Ok(()), Some(state)) = (&result, saved_state) {
+            self.try_load_state(state);
         }
+        self.watch.report(&self.path, result);
     }
 
     /// Try to call save_state() on the current instance if it exists.
@@ -668,6 +1213,33 @@ impl LuaCanvas {
         }
     }
 
+    /// Call on_qr(payload) on the instance if it exists.
+    pub fn on_qr(&mut self, payload: &str) {
+        self.call_event("on_qr", &[payload]);
+    }
+
+    /// Call on_gesture(name) on the instance if it exists.
+    pub fn on_gesture(&mut self, gesture: &str) {
+        self.call_event("on_gesture", &[gesture]);
+    }
+
+    /// Call on_device(event, name) on the instance if it exists, with `event`
+    /// `"connected"` or `"disconnected"`.
+    pub fn on_device(&mut self, event: &str, name: &str) {
+        self.call_event("on_device", &[event, name]);
+    }
+
+    /// Call an optional event method with string arguments.
+    fn call_event(&mut self, method: &str, args: &[&str]) {
+        let Some(instance_key) = &self.instance else { return; };
+        let Ok(instance) = self.lua.registry_value::<Table>(instance_key) else { return; };
+        let Ok(event_fn) = instance.get::<Function>(method) else { return; };
+
+        if let Err(e) = event_fn.call::<()>((&instance, Variadic::from_iter(args.iter().copied()))) {
+            warn!("Lua {}() error: {}", method, e);
+        }
+    }
+
     /// Get the current frame for the given time.
     /// Returns RGBA pixel data.
     pub fn get_frame(&mut self, time: f32) -> Option<LuaFrame> {
@@ -684,7 +1256,7 @@ impl LuaCanvas {
         // Call init once
         if !self.initialized {
             if let Ok(init_fn) = instance.get::<Function>("init") {
-                if let Err(e) = init_fn.call::<()>((&instance, self.width, self.height)) {
+                if let Err(e) = init_fn.call::<()>((&instance, self.logical_width, self.logical_height)) {
                     warn!("Lua init() error: {}", e);
                 }
             }
@@ -695,6 +1267,7 @@ impl LuaCanvas {
         // Calculate delta time
         let dt = time - self.last_time;
         self.last_time = time;
+        self.api_state.lock().unwrap().time = time;
 
         // Call update(dt)
         let update_start = Instant::now();
@@ -740,8 +1313,9 @@ impl LuaCanvas {
     }
 
     /// Prepare the canvas texture for direct GPU access (no CPU readback).
-    /// Runs update/draw Lua methods and returns a texture view.
-    pub fn prepare_texture(&mut self, time: f32) -> Option<wgpu::TextureView> {
+    /// Runs update/draw Lua methods and returns a texture view. `camera` is the
+    /// current camera frame for canvas.draw_camera() (a non-sRGB view).
+    pub fn prepare_texture(&mut self, time: f32, camera: Option<&wgpu::TextureView>) -> Option<wgpu::TextureView> {
         use std::time::Instant;
         
         let frame_start = Instant::now();
@@ -755,7 +1329,7 @@ impl LuaCanvas {
         // Call init once
         if !self.initialized {
             if let Ok(init_fn) = instance.get::<Function>("init") {
-                if let Err(e) = init_fn.call::<()>((&instance, self.width, self.height)) {
+                if let Err(e) = init_fn.call::<()>((&instance, self.logical_width, self.logical_height)) {
                     warn!("Lua init() error: {}", e);
                 }
             }
@@ -766,6 +1340,12 @@ impl LuaCanvas {
         // Calculate delta time
         let dt = time - self.last_time;
         self.last_time = time;
+        self.api_state.lock().unwrap().time = time;
+
+        // Before draw(), which may flush early (draw_image)
+        if let Ok(mut canvas) = self.gpu_canvas.lock() {
+            canvas.set_camera(camera);
+        }
 
         // Call update(dt)
         let update_start = Instant::now();
@@ -822,6 +1402,34 @@ impl LuaCanvas {
     }
 }
 
+/// Converts a JSON value for Lua: objects and arrays become tables, `null` nil.
+fn json_to_lua(lua: &Lua, value: serde_json::Value) -> mlua::Result<mlua::Value> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => mlua::Value::Nil,
+        Value::Bool(value) => mlua::Value::Boolean(value),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => mlua::Value::Integer(value),
+            None => mlua::Value::Number(number.as_f64().unwrap_or(0.0)),
+        },
+        Value::String(value) => mlua::Value::String(lua.create_string(&value)?),
+        Value::Array(items) => {
+            let table = lua.create_table()?;
+            for (i, item) in items.into_iter().enumerate() {
+                table.set(i + 1, json_to_lua(lua, item)?)?;
+            }
+            mlua::Value::Table(table)
+        }
+        Value::Object(fields) => {
+            let table = lua.create_table()?;
+            for (key, item) in fields {
+                table.set(key, json_to_lua(lua, item)?)?;
+            }
+            mlua::Value::Table(table)
+        }
+    })
+}
+
 /// Helper function to render text onto the GPU canvas with glyph caching.
 fn draw_text_impl(
     state: &mut GpuCanvasBatcherState,
@@ -855,29 +1463,33 @@ fn draw_text_impl(
         return;
     };
 
-    let scale = PxScale::from(size);
-    let scaled_font = font.as_scaled(scale);
-    let size_key = (size * 10.0) as u32; // Tenths of a pixel for stable caching
+    let scaled_font = font.as_scaled(PxScale::from(size));
+
+    let Some(run) = state.shape_cache.get(font_id, &font_data, text) else {
+        warn!("Failed to shape text");
+        return;
+    };
+    // Shaped positions are in font units
+    let (h_scale, v_scale) = (scaled_font.h_scale_factor(), scaled_font.v_scale_factor());
 
     // Calculate baseline position
     let ascent = scaled_font.ascent();
     let baseline_y = y + ascent;
-    let mut cursor_x = x;
 
-    // We need to keep the canvas lock during the entire loop to batch commands correctly
-    let Ok(mut canvas) = state.gpu_canvas.lock() else {
+    // The canvas uploads new glyphs to the atlas
+    let Ok(canvas) = state.gpu_canvas.lock() else {
         return;
     };
 
-    // If we have existing non-glyph commands, flush them first to maintain order
-    // (though usually text is drawn on top or separately)
-    if !state.commands.is_empty() {
-        let commands = std::mem::take(&mut state.commands);
-        canvas.add_commands(commands);
-    }
+    // Glyphs are rasterized at the resolution of the render target
+    let (scale_x, scale_y) = canvas.pixel_scale();
+    let scale = PxScale { x: size * scale_x, y: size * scale_y };
+    let raster_font = font.as_scaled(scale);
+    let size_key = (scale.y * 10.0) as u32; // Tenths of a pixel for stable caching
 
-    for c in text.chars() {
-        let glyph_id = scaled_font.glyph_id(c);
+    for glyph in &run.glyphs {
+        let glyph_id = glyph.id;
+        let (pen_x, pen_y) = (x + glyph.x * h_scale, baseline_y - glyph.y * v_scale);
         let key = (font_id, glyph_id.0, size_key);
 
         let entry = if let Some(entry) = state.glyph_cache.get(&key) {
@@ -885,7 +1497,7 @@ fn draw_text_impl(
         } else {
             // Not in cache, rasterize and upload
             let glyph = glyph_id.with_scale_and_position(scale, ab_glyph::point(0.0, 0.0));
-            if let Some(outlined) = scaled_font.outline_glyph(glyph) {
+            if let Some(outlined) = raster_font.outline_glyph(glyph) {
                 let bounds = outlined.px_bounds();
                 let width = bounds.width() as u32;
                 let height = bounds.height() as u32;
@@ -908,7 +1520,6 @@ fn draw_text_impl(
                             atlas_y: ay,
                             width,
                             height,
-                            advance: scaled_font.h_advance(glyph_id),
                             offset_x: bounds.min.x,
                             offset_y: bounds.min.y,
                         };
@@ -918,6 +1529,7 @@ fn draw_text_impl(
                         // Atlas full - reset and try again (simple strategy)
                         state.atlas_allocator.reset();
                         state.glyph_cache.clear();
+                        state.atlas_generation += 1;
                         // Recursive retry once
                         if let Some((ax, ay)) = state.atlas_allocator.allocate(width, height) {
                             canvas.upload_glyph_to_atlas(ax, ay, width, height, &pixels);
@@ -926,7 +1538,6 @@ fn draw_text_impl(
                                 atlas_y: ay,
                                 width,
                                 height,
-                                advance: scaled_font.h_advance(glyph_id),
                                 offset_x: bounds.min.x,
                                 offset_y: bounds.min.y,
                             };
@@ -937,35 +1548,33 @@ fn draw_text_impl(
                         }
                     }
                 } else {
-                    // Empty glyph (like space), just advance
-                    cursor_x += scaled_font.h_advance(glyph_id);
+                    // Empty glyph (like space)
                     continue;
                 }
             } else {
-                cursor_x += scaled_font.h_advance(glyph_id);
                 continue;
             }
         };
 
-        // Add draw command to canvas (batched)
-        canvas.queue_glyph(
-            cursor_x + entry.offset_x,
-            baseline_y + entry.offset_y,
-            entry.width as f32,
-            entry.height as f32,
-            entry.atlas_x as f32,
-            entry.atlas_y as f32,
-            entry.width as f32,
-            entry.height as f32,
-            r, g, b, a
+        // Batched with the other commands
+        let command = canvas.glyph_command(
+            [
+                pen_x + entry.offset_x / scale_x,
+                pen_y + entry.offset_y / scale_y,
+                entry.width as f32 / scale_x,
+                entry.height as f32 / scale_y,
+            ],
+            [entry.atlas_x as f32, entry.atlas_y as f32, entry.width as f32, entry.height as f32],
+            [r, g, b, a],
+            state.clip_active,
         );
-
-        cursor_x += entry.advance;
+        state.commands.push(command);
     }
 }
 
 /// Helper function to measure text dimensions.
-fn measure_text_impl(font_db: &Arc<FontDatabase>, font_family: Option<&str>, text: &str, size: f32) -> (f32, f32) {
+fn measure_text_impl(state: &mut GpuCanvasBatcherState, font_family: Option<&str>, text: &str, size: f32) -> (f32, f32) {
+    let font_db = &state.font_db;
     // Find font
     let font_id = font_family
         .and_then(|family| font_db.find_font(family))
@@ -986,11 +1595,7 @@ fn measure_text_impl(font_db: &Arc<FontDatabase>, font_family: Option<&str>, tex
     let scale = PxScale::from(size);
     let scaled_font = font.as_scaled(scale);
 
-    let mut width = 0.0f32;
-    for c in text.chars() {
-        let glyph_id = scaled_font.glyph_id(c);
-        width += scaled_font.h_advance(glyph_id);
-    }
+    let width = state.shape_cache.get(font_id, &font_data, text).map_or(0.0, |run| run.advance * scaled_font.h_scale_factor());
 
     let height = scaled_font.ascent() - scaled_font.descent();
 
@@ -1035,10 +1640,28 @@ fn draw_image_impl(
         // Important: Flush batch before writing image pixels
         canvas.flush();
         
-        let target_w = w.unwrap_or(img_data.width as f32) as u32;
-        let target_h = h.unwrap_or(img_data.height as f32) as u32;
-        
-        // Fast path for 1:1 image writes. Resize is not yet supported in this direct path.
-        canvas.draw_image(x as i32, y as i32, target_w, target_h, &img_data.data);
+        // Written at render target pixels, so the image is scaled by the
+        // supersampling and logical size as well
+        let (scale_x, scale_y) = canvas.pixel_scale();
+        let target_w = (w.unwrap_or(img_data.width as f32) * scale_x).round() as u32;
+        let target_h = (h.unwrap_or(img_data.height as f32) * scale_y).round() as u32;
+        if target_w == 0 || target_h == 0 {
+            return;
+        }
+        let (x, y) = ((x * scale_x) as i32, (y * scale_y) as i32);
+
+        if (target_w, target_h) == (img_data.width, img_data.height) {
+            canvas.draw_image(x, y, target_w, target_h, &img_data.data);
+        } else {
+            // Resized copies are cached along with the images
+            let key = format!("{}@{}x{}", path, target_w, target_h);
+            let Ok(mut cache) = image_cache.lock() else { return; };
+            if !cache.contains_key(&key) {
+                let Some(image) = image::RgbaImage::from_raw(img_data.width, img_data.height, img_data.data.clone()) else { return; };
+                let resized = image::imageops::resize(&image, target_w, target_h, image::imageops::FilterType::Triangle);
+                cache.insert(key.clone(), Arc::new(ImageData { width: target_w, height: target_h, data: resized.into_raw() }));
+            }
+            canvas.draw_image(x, y, target_w, target_h, &cache[&key].data);
+        }
     }
 }
@@ -0,0 +1,218 @@
+//! 2D particle emitters for Lua canvases (`particles.new{...}`).
+//!
+//! Moving a few hundred particles in Lua already takes a good part of a frame,
+//! so emitters spawn, move and draw their particles natively; scripts only
+//! place them and change their settings.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Most particles alive per emitter.
+pub const MAX_PARTICLES: usize = 16384;
+
+/// Settings of an emitter. Positions and sizes are in canvas pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitterSettings {
+    /// Center of the area particles spawn in
+    pub x: f32,
+    pub y: f32,
+    /// Size of the area particles spawn in (0 for a point)
+    pub width: f32,
+    pub height: f32,
+    /// Particles spawned per second
+    pub rate: f32,
+    /// Most particles alive at once
+    pub max: usize,
+    /// Shortest and longest lifetime in seconds
+    pub lifetime: [f32; 2],
+    /// Lowest and highest initial speed in pixels per second
+    pub speed: [f32; 2],
+    /// Direction of the initial velocity in radians (0 is right, y points down)
+    pub angle: f32,
+    /// Width of the cone of directions in radians
+    pub spread: f32,
+    /// Acceleration in pixels per second²
+    pub gravity: [f32; 2],
+    /// Share of the velocity lost per second
+    pub drag: f32,
+    /// Diameter at spawn and at the end of the lifetime
+    pub size: [f32; 2],
+    /// RGBA over the lifetime, evenly spaced
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            rate: 0.0,
+            max: 1000,
+            lifetime: [1.0, 1.0],
+            speed: [50.0, 150.0],
+            angle: 0.0,
+            spread: std::f32::consts::TAU,
+            gravity: [0.0, 0.0],
+            drag: 0.0,
+            size: [8.0, 8.0],
+            colors: vec![[255.0, 255.0, 255.0, 255.0], [255.0, 255.0, 255.0, 0.0]],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+/// A drawn particle: center, diameter and RGBA (0 - 255).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleSprite {
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+/// An emitter and its particles.
+pub struct Emitter {
+    pub settings: EmitterSettings,
+    particles: Vec<Particle>,
+    /// Fraction of a particle owed by `rate`
+    pending: f32,
+    rng: StdRng,
+}
+
+impl Emitter {
+    pub fn new(settings: EmitterSettings) -> Self {
+        Self::with_rng(settings, StdRng::from_os_rng())
+    }
+
+    fn with_rng(settings: EmitterSettings, rng: StdRng) -> Self {
+        Self { settings, particles: Vec::new(), pending: 0.0, rng }
+    }
+
+    pub fn count(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Removes all particles.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.pending = 0.0;
+    }
+
+    /// Spawns `count` particles at once, at the emitter or at `x`, `y`.
+    pub fn emit(&mut self, count: usize, at: Option<(f32, f32)>) {
+        let max = self.settings.max.min(MAX_PARTICLES);
+        let count = count.min(max.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let particle = self.spawn(at);
+            self.particles.push(particle);
+        }
+    }
+
+    fn spawn(&mut self, at: Option<(f32, f32)>) -> Particle {
+        let s = &self.settings;
+        let (x, y) = at.unwrap_or((s.x, s.y));
+        let x = x + (self.rng.random::<f32>() - 0.5) * s.width;
+        let y = y + (self.rng.random::<f32>() - 0.5) * s.height;
+        let angle = s.angle + (self.rng.random::<f32>() - 0.5) * s.spread;
+        let speed = between(&mut self.rng, s.speed);
+        let lifetime = between(&mut self.rng, s.lifetime).max(1e-3);
+        Particle { x, y, vx: angle.cos() * speed, vy: angle.sin() * speed, age: 0.0, lifetime }
+    }
+
+    /// Ages, moves and spawns particles.
+    pub fn update(&mut self, dt: f32) {
+        let dt = dt.max(0.0);
+        let s = &self.settings;
+        let damping = (-s.drag * dt).exp();
+        let [gx, gy] = s.gravity;
+        self.particles.retain_mut(|p| {
+            p.age += dt;
+            p.vx = (p.vx + gx * dt) * damping;
+            p.vy = (p.vy + gy * dt) * damping;
+            p.x += p.vx * dt;
+            p.y += p.vy * dt;
+            p.age < p.lifetime
+        });
+
+        self.pending += self.settings.rate.max(0.0) * dt;
+        let count = self.pending.floor();
+        self.pending -= count;
+        self.emit(count as usize, None);
+    }
+
+    /// The particles as they are drawn, oldest first.
+    pub fn sprites(&self) -> impl Iterator<Item = ParticleSprite> + '_ {
+        let s = &self.settings;
+        self.particles.iter().map(move |p| {
+            let t = (p.age / p.lifetime).clamp(0.0, 1.0);
+            ParticleSprite { x: p.x, y: p.y, size: s.size[0] + (s.size[1] - s.size[0]) * t, color: gradient(&s.colors, t) }
+        })
+    }
+}
+
+fn between(rng: &mut StdRng, [low, high]: [f32; 2]) -> f32 {
+    let (low, high) = (low.min(high), low.max(high));
+    rng.random_range(low..=high)
+}
+
+/// Color at `t` (0 - 1) of evenly spaced `colors`.
+fn gradient(colors: &[[f32; 4]], t: f32) -> [f32; 4] {
+    match colors {
+        [] => [255.0; 4],
+        [color] => *color,
+        _ => {
+            let position = t * (colors.len() - 1) as f32;
+            let index = (position.floor() as usize).min(colors.len() - 2);
+            let f = position - index as f32;
+            let (a, b) = (colors[index], colors[index + 1]);
+            std::array::from_fn(|i| a[i] + (b[i] - a[i]) * f)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emitter() {
+        let settings = EmitterSettings { rate: 100.0, max: 30, lifetime: [0.5, 0.5], speed: [100.0, 100.0], spread: 0.0, ..Default::default() };
+        let mut emitter = Emitter::with_rng(settings, StdRng::seed_from_u64(1));
+
+        // Spawned at the rate, up to the maximum
+        emitter.update(0.105);
+        assert_eq!(emitter.count(), 10);
+        emitter.update(0.2);
+        assert_eq!(emitter.count(), 30);
+
+        // Moved right at the speed, faded over the lifetime
+        let oldest = emitter.sprites().next().unwrap();
+        assert!((oldest.x - 20.0).abs() < 1e-3 && oldest.y == 0.0);
+        assert!((oldest.color[3] - 255.0 * 0.6).abs() < 1e-3);
+
+        // All gone after their lifetime
+        emitter.settings.rate = 0.0;
+        emitter.update(0.5);
+        assert_eq!(emitter.count(), 0);
+        emitter.emit(5, Some((10.0, 10.0)));
+        assert_eq!(emitter.sprites().next().map(|p| (p.x, p.y)), Some((10.0, 10.0)));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let colors = [[0.0, 0.0, 0.0, 0.0], [100.0, 0.0, 0.0, 255.0], [200.0, 0.0, 0.0, 255.0]];
+        assert_eq!(gradient(&colors, 0.25)[0], 50.0);
+        assert_eq!(gradient(&colors, 1.0)[0], 200.0);
+    }
+}
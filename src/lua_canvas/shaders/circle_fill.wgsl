@@ -39,8 +39,9 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     
     let dist = circle_sdf(pixel_pos, center, radius);
     
-    // Anti-aliased edge
-    let alpha = 1.0 - smoothstep(-1.0, 1.0, dist);
+    // Anti-aliased edge, a pixel of the render target wide on either side
+    let aa = fwidth(pixel_pos.x);
+    let alpha = 1.0 - smoothstep(-aa, aa, dist);
     
     if alpha <= 0.0 {
         discard;
@@ -0,0 +1,29 @@
+// Averages each FACTOR x FACTOR block of the supersampled canvas into one pixel
+override FACTOR: u32 = 2u;
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@vertex
+fn vs_main(@location(0) pos: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let origin = vec2<u32>(position.xy) * FACTOR;
+
+    // Weighted by alpha, so transparent pixels don't darken the edges
+    var sum = vec4<f32>(0.0);
+    for (var y = 0u; y < FACTOR; y++) {
+        for (var x = 0u; x < FACTOR; x++) {
+            let texel = textureLoad(source, origin + vec2<u32>(x, y), 0);
+            sum += vec4<f32>(texel.rgb * texel.a, texel.a);
+        }
+    }
+    if sum.a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+
+    return vec4<f32>(sum.rgb / sum.a, sum.a / f32(FACTOR * FACTOR));
+}
@@ -0,0 +1,54 @@
+// Draws the previous frame of the canvas, faded, zoomed, moved and rotated
+struct Uniforms {
+    transform: vec4<f32>,  // alpha, scale, dx, dy (pixels)
+    rotation: vec4<f32>,   // angle (radians), 0, 0, 0
+    extra: vec4<f32>,      // 0, canvas_w, canvas_h, 0
+    extra2: vec4<f32>,     // unused
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0)
+var feedback_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var feedback_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) pixel: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) pos: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Covers the whole canvas
+    let canvas_size = vec2<f32>(uniforms.extra.y, uniforms.extra.z);
+    out.position = vec4<f32>(pos, 0.0, 1.0);
+    out.pixel = vec2<f32>(pos.x + 1.0, 1.0 - pos.y) * 0.5 * canvas_size;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let canvas_size = vec2<f32>(uniforms.extra.y, uniforms.extra.z);
+    let center = canvas_size * 0.5;
+
+    // Where this pixel was in the previous frame (inverse of the transform)
+    let p = in.pixel - center - uniforms.transform.zw;
+    let c = cos(-uniforms.rotation.x);
+    let s = sin(-uniforms.rotation.x);
+    let source = vec2<f32>(p.x * c - p.y * s, p.x * s + p.y * c) / uniforms.transform.y + center;
+    let uv = source / canvas_size;
+
+    let color = textureSample(feedback_texture, feedback_sampler, uv);
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    let final_alpha = select(0.0, color.a * uniforms.transform.x, inside);
+    if final_alpha <= 0.0 {
+        discard;
+    }
+
+    return vec4<f32>(color.rgb, final_alpha);
+}
@@ -0,0 +1,57 @@
+// Draws a frame of a sprite sheet
+struct Uniforms {
+    bounds: vec4<f32>,  // x, y, w, h (pixels)
+    frame: vec4<f32>,   // x, y, w, h of the frame in the sheet (texels)
+    extra: vec4<f32>,   // alpha, canvas_w, canvas_h, 0
+    extra2: vec4<f32>,  // unused
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0)
+var sheet_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var sheet_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) pos: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+
+    let canvas_size = vec2<f32>(uniforms.extra.y, uniforms.extra.z);
+
+    // Map unit pos (-1 to 1) to (0 to 1)
+    let unit_pos = (pos + 1.0) * 0.5;
+    let pixel_pos = uniforms.bounds.xy + unit_pos * uniforms.bounds.zw;
+
+    // Convert to NDC (-1 to 1)
+    let ndc_x = (pixel_pos.x / canvas_size.x) * 2.0 - 1.0;
+    let ndc_y = 1.0 - (pixel_pos.y / canvas_size.y) * 2.0;
+
+    out.position = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+    out.uv = unit_pos;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Half a texel in from the frame's edges, so neighbouring frames don't bleed in
+    let sheet_size = vec2<f32>(textureDimensions(sheet_texture));
+    let texel = uniforms.frame.xy + in.uv * uniforms.frame.zw;
+    let clamped = clamp(texel, uniforms.frame.xy + 0.5, uniforms.frame.xy + uniforms.frame.zw - 0.5);
+
+    // Non-sRGB texture, so values stay encoded like the canvas
+    let color = textureSample(sheet_texture, sheet_sampler, clamped / sheet_size);
+    let alpha = color.a * uniforms.extra.x;
+    if alpha <= 0.0 {
+        discard;
+    }
+
+    return vec4<f32>(color.rgb, alpha);
+}
@@ -0,0 +1,170 @@
+//! Text shaping for the canvas and captions.
+//!
+//! One glyph per character at its own advance only works for simple scripts:
+//! Arabic joins its letters, Devanagari and Thai combine and reorder marks, and
+//! many fonts have ligatures. rustybuzz (a HarfBuzz port) turns a string into
+//! the glyphs to draw and their positions. Mixed right-to-left and
+//! left-to-right text (a Hebrew chat message with English words) is first split
+//! into runs of one direction with the Unicode Bidirectional Algorithm, which
+//! are shaped separately and laid out in visual order. Overlays draw the same
+//! strings every frame, so shaped runs are cached.
+
+use ab_glyph::GlyphId;
+use fontdb::ID;
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use unicode_bidi::BidiInfo;
+
+/// Shaped strings kept per font before the cache starts over.
+const MAX_CACHED_RUNS: usize = 1024;
+
+/// A glyph of a shaped run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapedGlyph {
+    pub id: GlyphId,
+    /// Position relative to the start of the run, in font units (y up)
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The glyphs of a string, left to right.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapedRun {
+    pub glyphs: Vec<ShapedGlyph>,
+    /// Width of the run, in font units
+    pub advance: f32,
+}
+
+/// Part of a string in one direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BidiRun {
+    /// Byte range in the string
+    pub range: Range<usize>,
+    pub direction: Direction,
+}
+
+/// Splits `text` into runs of one direction with the Unicode Bidirectional
+/// Algorithm, in visual order (left to right).
+pub fn bidi_runs(text: &str) -> Vec<BidiRun> {
+    let bidi = BidiInfo::new(text, None);
+    let mut runs = Vec::new();
+    for paragraph in &bidi.paragraphs {
+        let (levels, ranges) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        for range in ranges {
+            let direction = if levels[range.start].is_rtl() { Direction::RightToLeft } else { Direction::LeftToRight };
+            runs.push(BidiRun { range, direction });
+        }
+    }
+    runs
+}
+
+/// Lays the runs of `text` out left to right, each shaped by `shape_run` into
+/// glyphs in visual order. The line starts at x = 0 whatever its direction.
+fn layout(text: &str, mut shape_run: impl FnMut(&str, Direction) -> ShapedRun) -> ShapedRun {
+    let mut line = ShapedRun::default();
+    for run in bidi_runs(text) {
+        let shaped = shape_run(&text[run.range], run.direction);
+        line.glyphs.extend(shaped.glyphs.into_iter().map(|glyph| ShapedGlyph { x: line.advance + glyph.x, ..glyph }));
+        line.advance += shaped.advance;
+    }
+    line
+}
+
+/// Shapes `text` as a single line with the font in `font_data`. None if the
+/// font can't be read.
+pub fn shape(font_data: &[u8], text: &str) -> Option<ShapedRun> {
+    let face = Face::from_slice(font_data, 0)?;
+    Some(layout(text, |text, direction| shape_run(&face, text, direction)))
+}
+
+/// Shapes text of one direction. Right-to-left text comes out of the shaper in
+/// visual order as well.
+fn shape_run(face: &Face, text: &str, direction: Direction) -> ShapedRun {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.set_direction(direction);
+    // Script and language from the text
+    buffer.guess_segment_properties();
+    let glyphs = rustybuzz::shape(face, &[], buffer);
+
+    let mut run = ShapedRun::default();
+    for (info, position) in glyphs.glyph_infos().iter().zip(glyphs.glyph_positions()) {
+        run.glyphs.push(ShapedGlyph {
+            id: GlyphId(info.glyph_id as u16),
+            x: run.advance + position.x_offset as f32,
+            y: position.y_offset as f32,
+        });
+        run.advance += position.x_advance as f32;
+    }
+    run
+}
+
+/// Shaped runs by font and string; sizes share a run, as it is in font units.
+#[derive(Default)]
+pub struct ShapeCache {
+    runs: HashMap<ID, HashMap<String, Arc<ShapedRun>>>,
+}
+
+impl ShapeCache {
+    /// The shaped run of `text` in the font `font_id` (with the data `font_data`).
+    pub fn get(&mut self, font_id: ID, font_data: &[u8], text: &str) -> Option<Arc<ShapedRun>> {
+        let runs = self.runs.entry(font_id).or_default();
+        if let Some(run) = runs.get(text) {
+            return Some(run.clone());
+        }
+        // Text that changes every frame (clocks, counters) would grow the cache forever
+        if runs.len() >= MAX_CACHED_RUNS {
+            runs.clear();
+        }
+        let run = Arc::new(shape(font_data, text)?);
+        runs.insert(text.to_string(), run.clone());
+        Some(run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(range: Range<usize>, direction: Direction) -> BidiRun {
+        BidiRun { range, direction }
+    }
+
+    /// One glyph per character, 10 units wide, with the character as its id;
+    /// right-to-left runs come out reversed like from the shaper.
+    fn fake_shape(text: &str, direction: Direction) -> ShapedRun {
+        let mut chars: Vec<char> = text.chars().collect();
+        if direction == Direction::RightToLeft {
+            chars.reverse();
+        }
+        let glyphs = chars.iter().enumerate().map(|(i, c)| ShapedGlyph { id: GlyphId(*c as u16), x: i as f32 * 10.0, y: 0.0 }).collect();
+        ShapedRun { glyphs, advance: chars.len() as f32 * 10.0 }
+    }
+
+    fn visual(line: &ShapedRun) -> String {
+        line.glyphs.iter().map(|glyph| char::from_u32(glyph.id.0 as u32).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_bidi_runs() {
+        assert_eq!(bidi_runs("hello"), vec![run(0..5, Direction::LeftToRight)]);
+        // English with a Hebrew word: the word is a right-to-left run in place
+        assert_eq!(bidi_runs("say שלום now"), vec![run(0..4, Direction::LeftToRight), run(4..12, Direction::RightToLeft), run(12..16, Direction::LeftToRight)]);
+        // Hebrew with an English word: the line reads from the right, so the end comes first
+        assert_eq!(bidi_runs("שלום world!"), vec![run(14..15, Direction::RightToLeft), run(9..14, Direction::LeftToRight), run(0..9, Direction::RightToLeft)]);
+    }
+
+    #[test]
+    fn test_layout() {
+        let line = layout("say שלום now", fake_shape);
+        assert_eq!(visual(&line), "say םולש now");
+
+        // A right-to-left line also starts at x = 0 and grows to the right
+        let line = layout("שלום world!", fake_shape);
+        assert_eq!(visual(&line), "!world םולש");
+        assert_eq!(line.glyphs.iter().map(|glyph| glyph.x).collect::<Vec<_>>(), (0..11).map(|i| i as f32 * 10.0).collect::<Vec<_>>());
+        assert_eq!(line.advance, 110.0);
+    }
+}
@@ -0,0 +1,172 @@
+//! Sprite sheets for canvas.load_spritesheet() and canvas.draw_anim().
+//!
+//! A sheet is an image of equally sized frames, numbered row by row from the
+//! top left. Its animations are listed in a JSON file next to the image (the
+//! image's path with a `.json` extension):
+//!
+//! ```json
+//! {
+//!   "animations": {
+//!     "walk": { "frames": [0, 1, 2, 3], "fps": 12 },
+//!     "jump": { "frames": [8, 9, 10], "durations": [80, 120, 300], "loop": false }
+//!   }
+//! }
+//! ```
+//!
+//! `durations` are per frame in milliseconds and take precedence over `fps`.
+//! Every sheet also has the animation `all`, of all its frames at the default
+//! rate.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Frame rate of animations without `fps` or `durations`.
+const DEFAULT_FPS: f32 = 10.0;
+
+/// An animation as written in the JSON file.
+#[derive(Debug, Deserialize)]
+struct AnimationConfig {
+    frames: Vec<u32>,
+    #[serde(default)]
+    fps: Option<f32>,
+    #[serde(default)]
+    durations: Option<Vec<f32>>,
+    #[serde(default = "default_loop", rename = "loop")]
+    looping: bool,
+}
+
+fn default_loop() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct SheetConfig {
+    #[serde(default)]
+    animations: HashMap<String, AnimationConfig>,
+}
+
+/// A sequence of frames of a sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    frames: Vec<u32>,
+    /// Seconds per frame
+    durations: Vec<f32>,
+    looping: bool,
+}
+
+impl Animation {
+    /// The frame shown `t` seconds after the animation started. Looping
+    /// animations start over, others stay at their last frame.
+    pub fn frame_at(&self, t: f32) -> u32 {
+        let total: f32 = self.durations.iter().sum();
+        let mut t = t.max(0.0);
+        if self.looping && total > 0.0 {
+            t %= total;
+        }
+        for (frame, duration) in self.frames.iter().zip(&self.durations) {
+            if t < *duration {
+                return *frame;
+            }
+            t -= duration;
+        }
+        self.frames.last().copied().unwrap_or(0)
+    }
+}
+
+/// Frame layout and animations of a sprite sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetLayout {
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub columns: u32,
+    pub frame_count: u32,
+    pub animations: HashMap<String, Animation>,
+}
+
+impl SheetLayout {
+    /// Layout of a `width`x`height` image of `frame_width`x`frame_height`
+    /// frames, with the animations of `json` (if any).
+    pub fn new(width: u32, height: u32, frame_width: u32, frame_height: u32, json: Option<&str>) -> Result<Self> {
+        if frame_width == 0 || frame_height == 0 || frame_width > width || frame_height > height {
+            bail!("Frames of {}x{} don't fit a {}x{} sprite sheet", frame_width, frame_height, width, height);
+        }
+        let columns = width / frame_width;
+        let frame_count = columns * (height / frame_height);
+
+        let config: SheetConfig = match json {
+            Some(json) => serde_json::from_str(json)?,
+            None => SheetConfig { animations: HashMap::new() },
+        };
+        let mut animations = HashMap::new();
+        for (name, animation) in config.animations {
+            if animation.frames.is_empty() {
+                bail!("Animation {:?} has no frames", name);
+            }
+            if let Some(frame) = animation.frames.iter().find(|frame| **frame >= frame_count) {
+                bail!("Animation {:?} uses frame {}, the sheet has {}", name, frame, frame_count);
+            }
+            let durations = match animation.durations {
+                Some(durations) if durations.len() == animation.frames.len() => durations.iter().map(|ms| ms.max(0.0) / 1000.0).collect(),
+                Some(_) => bail!("Animation {:?} needs a duration for each of its {} frames", name, animation.frames.len()),
+                None => vec![1.0 / animation.fps.unwrap_or(DEFAULT_FPS).max(0.001); animation.frames.len()],
+            };
+            animations.insert(name, Animation { frames: animation.frames, durations, looping: animation.looping });
+        }
+        animations.entry("all".to_string()).or_insert_with(|| Animation {
+            frames: (0..frame_count).collect(),
+            durations: vec![1.0 / DEFAULT_FPS; frame_count as usize],
+            looping: true,
+        });
+
+        Ok(Self { frame_width, frame_height, columns, frame_count, animations })
+    }
+
+    /// Pixel rectangle (x, y, w, h) of a frame in the image.
+    pub fn frame_rect(&self, frame: u32) -> [u32; 4] {
+        let frame = frame.min(self.frame_count - 1);
+        [(frame % self.columns) * self.frame_width, (frame / self.columns) * self.frame_height, self.frame_width, self.frame_height]
+    }
+}
+
+/// Loads the image at `path` as RGBA and its sheet layout.
+pub fn load(path: &Path, frame_width: u32, frame_height: u32) -> Result<(image::RgbaImage, SheetLayout)> {
+    let image = image::open(path).with_context(|| format!("Failed to load sprite sheet {:?}", path))?.to_rgba8();
+    let json_path = path.with_extension("json");
+    let json = match std::fs::read_to_string(&json_path) {
+        Ok(json) => Some(json),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(anyhow!("Failed to read {:?}: {}", json_path, e)),
+    };
+    let layout = SheetLayout::new(image.width(), image.height(), frame_width, frame_height, json.as_deref())
+        .with_context(|| format!("Invalid sprite sheet {:?}", path))?;
+    Ok((image, layout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sheet_layout() {
+        let json = r#"{ "animations": {
+            "walk": { "frames": [0, 1, 2, 3], "fps": 4 },
+            "jump": { "frames": [4, 5], "durations": [100, 300], "loop": false }
+        } }"#;
+        let layout = SheetLayout::new(128, 64, 32, 32, Some(json)).unwrap();
+        assert_eq!((layout.columns, layout.frame_count), (4, 8));
+        assert_eq!(layout.frame_rect(5), [32, 32, 32, 32]);
+
+        // Looping at 4 fps, one shot with its own timing
+        let walk = &layout.animations["walk"];
+        assert_eq!([0.0, 0.3, 0.9, 1.1].map(|t| walk.frame_at(t)), [0, 1, 3, 0]);
+        let jump = &layout.animations["jump"];
+        assert_eq!([0.05, 0.2, 5.0].map(|t| jump.frame_at(t)), [4, 5, 5]);
+        assert_eq!(layout.animations["all"].frame_at(0.75), 7);
+
+        assert!(SheetLayout::new(128, 64, 32, 32, Some(r#"{ "animations": { "x": { "frames": [8] } } }"#)).is_err());
+        assert!(SheetLayout::new(128, 64, 32, 32, Some(r#"{ "animations": { "x": { "frames": [0, 1], "durations": [10] } } }"#)).is_err());
+        assert!(SheetLayout::new(16, 16, 32, 32, None).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+-- Retained widgets for Lua canvas overlays: panels, labels, progress bars,
+-- lists and message toasts.
+--
+-- Widgets are added to a layer once and changed with widget:set{...}. A layer
+-- records the draws of each top-level widget with canvas.record() and replays
+-- them every frame, so widgets only run their drawing code again after a
+-- change.
+
+local ui = {}
+
+local Widget = {}
+Widget.__index = Widget
+
+local Layer = {}
+Layer.__index = Layer
+
+local WHITE = { 255, 255, 255, 255 }
+
+local DEFAULTS = {
+    panel = { x = 0, y = 0, w = 100, h = 100, color = { 20, 20, 30, 220 }, border_width = 2 },
+    label = { x = 0, y = 0, text = "", size = 24, color = WHITE, align = "left" },
+    progress = { x = 0, y = 0, w = 200, h = 12, value = 0, color = { 80, 200, 120, 255 }, background = { 255, 255, 255, 60 } },
+    list = { x = 0, y = 0, items = {}, size = 24, spacing = 8, color = WHITE, highlight = { 255, 255, 255, 40 } },
+}
+
+local TOAST_SIZE = 28
+local TOAST_PADDING = 16
+local TOAST_MARGIN = 24
+local TOAST_FADE = 0.25
+
+local function same(a, b)
+    if a == b then
+        return true
+    end
+    if type(a) ~= "table" or type(b) ~= "table" or #a ~= #b then
+        return false
+    end
+    for i = 1, #a do
+        if a[i] ~= b[i] then
+            return false
+        end
+    end
+    return true
+end
+
+local function widget(kind, props)
+    local w = setmetatable({ kind = kind, children = {}, dirty = true }, Widget)
+    for key, value in pairs(DEFAULTS[kind]) do
+        w[key] = value
+    end
+    for key, value in pairs(props or {}) do
+        if key ~= "children" then
+            w[key] = value
+        end
+    end
+    for _, child in ipairs(props and props.children or {}) do
+        w:add(child)
+    end
+    return w
+end
+
+--- A filled rectangle with an optional `border` color; `children` are placed
+--- relative to it.
+function ui.panel(props) return widget("panel", props) end
+
+--- Text, aligned `left`, `center` or `right` of `x` (or within `w`, if set).
+function ui.label(props) return widget("label", props) end
+
+--- A bar filled to `value` (0.0 - 1.0).
+function ui.progress(props) return widget("progress", props) end
+
+--- A column of `items`; the `selected` one (an index) is highlighted across `w`.
+function ui.list(props) return widget("list", props) end
+
+--- Marks the widget's top-level widget for drawing again.
+function Widget:invalidate()
+    local w = self
+    while w.parent do
+        w = w.parent
+    end
+    w.dirty = true
+end
+
+--- Changes properties; the widget is only drawn again if one actually changed.
+function Widget:set(props)
+    local changed = false
+    for key, value in pairs(props) do
+        if not same(self[key], value) then
+            self[key] = value
+            changed = true
+        end
+    end
+    if changed then
+        self:invalidate()
+    end
+    return self
+end
+
+--- Adds a child widget, placed relative to this one.
+function Widget:add(child)
+    child.parent = self
+    table.insert(self.children, child)
+    self:invalidate()
+    return child
+end
+
+--- Removes a child widget.
+function Widget:remove(child)
+    for i, c in ipairs(self.children) do
+        if c == child then
+            table.remove(self.children, i)
+            child.parent = nil
+            self:invalidate()
+            return
+        end
+    end
+end
+
+local function text(x, y, str, size, color, font)
+    local r, g, b, a = color[1], color[2], color[3], color[4] or 255
+    if font then
+        canvas.draw_text_font(x, y, str, font, size, r, g, b, a)
+    else
+        canvas.draw_text(x, y, str, size, r, g, b, a)
+    end
+end
+
+local function measure(str, size, font)
+    if font then
+        return canvas.measure_text_font(str, font, size)
+    end
+    return canvas.measure_text(str, size)
+end
+
+local function fill(x, y, w, h, color)
+    canvas.fill_rect(x, y, w, h, color[1], color[2], color[3], color[4] or 255)
+end
+
+local draw_widget
+
+local DRAW = {
+    panel = function(w, x, y)
+        fill(x, y, w.w, w.h, w.color)
+        if w.border then
+            local c = w.border
+            canvas.stroke_rect(x, y, w.w, w.h, c[1], c[2], c[3], c[4] or 255, w.border_width)
+        end
+    end,
+    label = function(w, x, y)
+        local str = tostring(w.text)
+        if w.align ~= "left" then
+            local width = measure(str, w.size, w.font)
+            local space = w.w or 0
+            x = x + (w.align == "center" and (space - width) / 2 or space - width)
+        end
+        text(x, y, str, w.size, w.color, w.font)
+    end,
+    progress = function(w, x, y)
+        fill(x, y, w.w, w.h, w.background)
+        local value = math.max(0, math.min(1, w.value))
+        if value > 0 then
+            fill(x, y, w.w * value, w.h, w.color)
+        end
+    end,
+    list = function(w, x, y)
+        local line = w.size + w.spacing
+        for i, item in ipairs(w.items) do
+            local top = y + (i - 1) * line
+            if i == w.selected and w.w then
+                fill(x - w.spacing / 2, top - w.spacing / 2, w.w, line, w.highlight)
+            end
+            text(x, top, tostring(item), w.size, w.color, w.font)
+        end
+    end,
+}
+
+draw_widget = function(w, x, y)
+    if w.visible == false then
+        return
+    end
+    x, y = x + w.x, y + w.y
+    DRAW[w.kind](w, x, y)
+    for _, child in ipairs(w.children) do
+        draw_widget(child, x, y)
+    end
+end
+
+--- A set of widgets and toasts drawn together.
+function ui.layer()
+    return setmetatable({ widgets = {}, toasts = {}, toasts_dirty = false }, Layer)
+end
+
+--- Adds a top-level widget.
+function Layer:add(w)
+    w.parent = nil
+    w.dirty = true
+    table.insert(self.widgets, w)
+    return w
+end
+
+--- Removes a top-level widget.
+function Layer:remove(w)
+    for i, c in ipairs(self.widgets) do
+        if c == w then
+            table.remove(self.widgets, i)
+            return
+        end
+    end
+end
+
+--- Shows a message in the bottom right corner for `options.duration` seconds
+--- (default 3). Newer messages stack above older ones.
+function Layer:toast(message, options)
+    options = options or {}
+    table.insert(self.toasts, {
+        text = tostring(message),
+        duration = options.duration or 3,
+        color = options.color or WHITE,
+        background = options.background or { 20, 20, 30, 230 },
+        time = 0,
+        alpha = -1,
+    })
+    self.toasts_dirty = true
+end
+
+--- Advances the toasts; call from update(dt).
+function Layer:update(dt)
+    for i = #self.toasts, 1, -1 do
+        local toast = self.toasts[i]
+        toast.time = toast.time + dt
+        if toast.time >= toast.duration then
+            table.remove(self.toasts, i)
+            self.toasts_dirty = true
+        else
+            local fade = math.min(toast.time, toast.duration - toast.time) / TOAST_FADE
+            -- Whole steps of the 8-bit alpha, so toasts are only recorded again while fading
+            local alpha = math.floor(math.min(1, fade) * 255)
+            if alpha ~= toast.alpha then
+                toast.alpha = alpha
+                self.toasts_dirty = true
+            end
+        end
+    end
+end
+
+local function faded(color, alpha)
+    return { color[1], color[2], color[3], math.floor((color[4] or 255) * alpha / 255) }
+end
+
+function Layer:draw_toasts()
+    local y = canvas.height - TOAST_MARGIN
+    for i = #self.toasts, 1, -1 do
+        local toast = self.toasts[i]
+        local width, height = measure(toast.text, TOAST_SIZE)
+        local box_w, box_h = width + 2 * TOAST_PADDING, height + 2 * TOAST_PADDING
+        y = y - box_h
+        local x = canvas.width - TOAST_MARGIN - box_w
+        fill(x, y, box_w, box_h, faded(toast.background, toast.alpha))
+        text(x + TOAST_PADDING, y + TOAST_PADDING, toast.text, TOAST_SIZE, faded(toast.color, toast.alpha))
+        y = y - TOAST_PADDING / 2
+    end
+end
+
+--- Draws the layer, replaying the recorded draws of unchanged widgets;
+--- call from draw().
+function Layer:draw()
+    for _, w in ipairs(self.widgets) do
+        if w.dirty or not w.list or not w.list:valid() then
+            w.list = canvas.record(function() draw_widget(w, 0, 0) end)
+            w.dirty = false
+        end
+        w.list:draw()
+    end
+    if #self.toasts > 0 then
+        if self.toasts_dirty or not self.toast_list or not self.toast_list:valid() then
+            self.toast_list = canvas.record(function() self:draw_toasts() end)
+            self.toasts_dirty = false
+        end
+        self.toast_list:draw()
+    end
+end
+
+return ui
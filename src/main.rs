@@ -1,29 +1,68 @@
 //! Proteus: Cross-platform shader webcam transformer CLI.
 
+mod chain;
+mod config_migration;
 mod config_utils;
+mod paths;
+mod service;
 mod utils;
-use config_utils::{ConfigDiff, ConfigWatcher, load_shaders, load_textures_with_size, init_capture};
-use utils::FpsCounter;
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+use chain::Chain;
+use chain::ChainConfig;
+use config_utils::{ConfigDiff, ConfigWatcher, ParamStore, load_accumulation, load_bloom, load_particles, load_glyphs, load_calibration, load_sink_calibrations, load_sink_mirrors, load_compositor, load_decontamination, load_input_filter, load_execution_providers, load_fallback, load_splash, load_gamepad, load_layout, load_mixer, load_modulators, load_monitor, load_param_store, load_text_blur, load_regions, load_data_buffers, load_redaction, load_qr, load_qr_actions, load_schedule, load_gestures, load_gesture_actions, load_shaders, load_guides, load_present_mode, load_interpolation, load_seed, load_textures_with_size, load_texture_transforms, load_watermark, load_captions, load_speech, load_audio_meter, load_obs, load_obs_scenes, load_wall_clock, load_mqtt, load_stream_deck, load_web, load_sync, load_shader_cache, load_clip, load_recording, load_idle, load_output_rates, load_cursor, load_zoom, init_capture, CAPTURE_RETRY_INTERVAL};
+use utils::{timestamped_path, FpsCounter};
 
 use anyhow::Result;
-use clap::{CommandFactory, Parser, ValueEnum};
-use proteus::capture::{AsyncCapture, CaptureBackend, CaptureConfig, NokhwaCapture};
-use proteus::output::window_output::WindowRenderer;
-#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
-use proteus::output::{OutputBackend, VirtualCameraConfig, VirtualCameraOutput};
-use proteus::shader::{WgpuPipeline, ShaderPipeline};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use proteus::control::{ControlCommand, ControlServer};
+use proteus::gamepad::GamepadController;
+use proteus::history::ParamHistory;
+use proteus::compare::AbCompare;
+use proteus::capture::hotplug::{best_input, DeviceEvent, DeviceMonitor, DEVICE_POLL_INTERVAL};
+use proteus::capture::{AsyncCapture, CameraInfo, CameraMode, CameraPixelFormat, CaptureBackend, CaptureConfig, CaptureRetry, NokhwaCapture};
+use proteus::frame::{Rotation, YuvMatrix, YuvRange};
+use proteus::output::idle::IdleSettings;
+use proteus::output::pacing::{FramePacer, OutputRates};
+use proteus::output::window_output::{Guides, WindowRenderer};
+use proteus::mixer::Mixer;
+use proteus::interpolation::FrameInterpolator;
+use proteus::monitor::{FrameMonitor, MonitorSettings};
+use proteus::recorder::{RecordSettings, Recorder};
+use proteus::recorder::clip::{ClipRecorder, ClipSettings};
+use proteus::captions::speech::{SpeechSettings, SpeechToText};
+use proteus::audio::AudioMeter;
+use proteus::obs::{ObsClient, ObsSettings};
+use proteus::mqtt::{MqttClient, MqttSettings, MqttState};
+use proteus::streamdeck::{StreamDeckServer, StreamDeckState};
+use proteus::web::{WebServer, WebSettings, WebState};
+use proteus::sync::SyncNode;
+use proteus::session::{Session, SessionTime};
+use proteus::schedule::Scheduler;
+use proteus::stats::Stage;
+use proteus::reload;
+use proteus::events::{self, Event};
+use proteus::qr::QrSettings;
+use proteus::gesture::{Gesture, GestureSettings};
+use proteus::shader::{BuiltinShader, Captions, CursorOverlay, Glyphs, Particles, PassOptions, ShaderSource, Sink, WgpuPipeline, DEFAULT_LAYOUT};
+use proteus::shader::color_audit::Verdict;
+use proteus::clock::{BeatClock, ShaderTime, WallClock, WallClockSettings, DEFAULT_BPM};
+use proteus::shader::modulation::{Modulator, Rate, Waveform};
 use proteus::shader::gpu_context::GpuContext;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::prelude::*;
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
-use winit::event::WindowEvent;
+use winit::event::{ElementState, WindowEvent};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::window::{Window, WindowAttributes, WindowId};
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowAttributes, WindowId};
 
 /// Output mode for processed video.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
@@ -39,320 +78,3243 @@ pub enum OutputMode {
     VirtualCamera,
 }
 
+/// Process scheduling priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProcessPriority {
+    /// Leave the OS default priority untouched
+    #[default]
+    Normal,
+    /// Request elevated priority (may require privileges on Linux/macOS)
+    High,
+}
+
+/// GPU adapter power preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpuPower {
+    /// Prefer the discrete GPU (default)
+    #[default]
+    HighPerformance,
+    /// Prefer the integrated GPU to save power
+    LowPower,
+}
+
+impl From<GpuPower> for wgpu::PowerPreference {
+    fn from(power: GpuPower) -> Self {
+        match power {
+            GpuPower::HighPerformance => wgpu::PowerPreference::HighPerformance,
+            GpuPower::LowPower => wgpu::PowerPreference::LowPower,
+        }
+    }
+}
+
+/// Frame rate used in background mode while the preview window is hidden.
+const BACKGROUND_FPS: u32 = 15;
+
+/// Length of `proteus ctl clip` clips when none is given.
+const DEFAULT_CLIP_SECONDS: f32 = 3.0;
+
+/// Crop and transform of a texture or mixer source.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SourceTransformConfig {
+    /// x, y, width, height of the part of the source shown
+    pub crop: [f32; 4],
+    /// Size relative to the frame
+    pub scale: f32,
+    /// x, y movement in frame widths and heights
+    pub offset: [f32; 2],
+    /// Clockwise rotation in degrees
+    pub rotation: f32,
+    /// 0.0 (invisible) - 1.0 (opaque)
+    pub opacity: f32,
+}
+
+impl Default for SourceTransformConfig {
+    fn default() -> Self {
+        Self { crop: [0.0, 0.0, 1.0, 1.0], scale: 1.0, offset: [0.0, 0.0], rotation: 0.0, opacity: 1.0 }
+    }
+}
+
 /// A texture input for shaders (image or video).
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum TextureInput {
-    Image { path: PathBuf },
-    Video { path: PathBuf },
-    Lua { path: PathBuf },
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+    },
+    Video {
+        path: PathBuf,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+    },
+    Lua {
+        path: PathBuf,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+        #[serde(flatten)]
+        resolution: CanvasResolutionConfig,
+    },
 }
 
-/// Configuration file structure.
-#[derive(Debug, Clone, Deserialize, PartialEq)]
-#[serde(default)]
-pub struct Config {
-    /// Path to the config file (if loaded from file)
-    #[serde(skip)]
-    pub config_path: Option<PathBuf>,
-    /// Camera device ID (index or name)
-    pub input: String,
-    /// Path to GLSL fragment shader file(s)
-    pub shader: Vec<PathBuf>,
-    /// Frame width
-    pub width: u32,
-    /// Frame height
-    pub height: u32,
-    /// Maximum frame width (for camera format selection)
-    pub max_input_width: Option<u32>,
-    /// Maximum frame height (for camera format selection)
-    pub max_input_height: Option<u32>,
-    /// Target frames per second
-    pub fps: u32,
-    /// Output mode: window or virtual-camera
-    pub output: OutputMode,
-    /// Ordered texture inputs (images and videos)
-    pub textures: Vec<TextureInput>,
+/// Resolution of a Lua canvas relative to its texture.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+pub struct CanvasResolutionConfig {
+    /// Renders at this multiple of the texture size and downsamples
+    #[serde(default = "default_supersample")]
+    pub supersample: u32,
+    /// Width and height the script draws in, scaled to the texture
+    #[serde(default)]
+    pub logical_size: Option<[u32; 2]>,
 }
 
-impl Default for Config {
+impl Default for CanvasResolutionConfig {
     fn default() -> Self {
-        Self {
-            config_path: None,
-            input: "0".to_string(),
-            shader: Vec::new(),
-            width: 1920,
-            height: 1080,
-            max_input_width: None,
-            max_input_height: None,
-            fps: 30,
-            output: OutputMode::Window,
-            textures: Vec::new(),
-        }
+        Self { supersample: default_supersample(), logical_size: None }
     }
 }
 
-impl Config {
-    /// Create a Config from CLI arguments.
-    /// This handles the texture ordering from interleaved --image and --video flags.
-    fn from_cli_args(args: Args) -> Self {
-        // Build ordered texture inputs from CLI image/video args
-        let matches = Args::command().get_matches();
-        let mut ordered_inputs: Vec<(usize, TextureInput)> = Vec::new();
+fn default_supersample() -> u32 {
+    1
+}
 
-        if let Some(indices) = matches.indices_of("video") {
-            let paths: Vec<&PathBuf> = args.video.iter().collect();
-            for (i, idx) in indices.enumerate() {
-                if i < paths.len() {
-                    ordered_inputs.push((idx, TextureInput::Video { path: paths[i].clone() }));
-                }
-            }
+impl TextureInput {
+    fn path_mut(&mut self) -> &mut PathBuf {
+        match self {
+            TextureInput::Image { path, .. } | TextureInput::Video { path, .. } | TextureInput::Lua { path, .. } => path,
         }
+    }
 
-        if let Some(indices) = matches.indices_of("image") {
-            let paths: Vec<&PathBuf> = args.image.iter().collect();
-            for (i, idx) in indices.enumerate() {
-                if i < paths.len() {
-                    ordered_inputs.push((idx, TextureInput::Image { path: paths[i].clone() }));
-                }
-            }
+    fn transform(&self) -> SourceTransformConfig {
+        match self {
+            TextureInput::Image { transform, .. } | TextureInput::Video { transform, .. } | TextureInput::Lua { transform, .. } => *transform,
         }
+    }
+}
 
-        if let Some(indices) = matches.indices_of("lua") {
-            let paths: Vec<&PathBuf> = args.lua.iter().collect();
-            for (i, idx) in indices.enumerate() {
-                if i < paths.len() {
-                    ordered_inputs.push((idx, TextureInput::Lua { path: paths[i].clone() }));
-                }
-            }
-        }
+/// A shader pass entry: a bare path, a path with per-pass options, or a builtin pass.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ShaderEntry {
+    Path(PathBuf),
+    Pass {
+        path: PathBuf,
+        /// Fraction of the output resolution this pass renders at
+        #[serde(default = "default_pass_scale")]
+        scale: f32,
+        /// Modulators that only affect this pass
+        #[serde(default)]
+        modulators: Vec<ModulatorConfig>,
+        /// Vertex shader for geometric warps (`.wgsl` files are WGSL, others GLSL)
+        #[serde(default)]
+        vertex: Option<PathBuf>,
+        /// Columns and rows of the grid the pass is drawn with (default: a single
+        /// quad, or `DEFAULT_VERTEX_MESH` with a vertex shader)
+        #[serde(default)]
+        mesh: Option<[u32; 2]>,
+    },
+    Builtin {
+        builtin: BuiltinShaderConfig,
+        #[serde(default = "default_pass_scale")]
+        scale: f32,
+        #[serde(default)]
+        modulators: Vec<ModulatorConfig>,
+    },
+}
 
-        ordered_inputs.sort_by_key(|k| k.0);
-        let textures = ordered_inputs.into_iter().map(|(_, t)| t).collect();
+/// Shader passes that ship with Proteus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuiltinShaderConfig {
+    Kaleidoscope,
+    Mirror,
+    Tunnel,
+    Ascii,
+    Halftone,
+    Retro,
+    Studio,
+    StudioChroma,
+}
 
-        Self {
-            config_path: None,
-            input: args.input,
-            shader: args.shader,
-            width: args.width,
-            height: args.height,
-            max_input_width: args.max_input_width,
-            max_input_height: args.max_input_height,
-            fps: args.fps,
-            output: args.output,
-            textures,
+impl BuiltinShaderConfig {
+    fn to_builtin(self) -> BuiltinShader {
+        match self {
+            BuiltinShaderConfig::Kaleidoscope => BuiltinShader::Kaleidoscope,
+            BuiltinShaderConfig::Mirror => BuiltinShader::Mirror,
+            BuiltinShaderConfig::Tunnel => BuiltinShader::Tunnel,
+            BuiltinShaderConfig::Ascii => BuiltinShader::Ascii,
+            BuiltinShaderConfig::Halftone => BuiltinShader::Halftone,
+            BuiltinShaderConfig::Retro => BuiltinShader::Retro,
+            BuiltinShaderConfig::Studio => BuiltinShader::Studio,
+            BuiltinShaderConfig::StudioChroma => BuiltinShader::StudioChroma,
         }
     }
-    
-    /// Load configuration from a YAML file.
-    pub fn from_file(path: &PathBuf) -> Result<Self> {
-        let content = fs::read_to_string(path)
-            .map_err(|e| anyhow::anyhow!("Failed to read config file {:?}: {}", path, e))?;
-        
-        let mut config: Config = serde_yaml::from_str(&content)
-            .map_err(|e| anyhow::anyhow!("Failed to parse config file {:?}: {}", path, e))?;
-        
-        config.config_path = Some(path.clone());
-        info!("Loaded configuration from {:?}", path);
-        
-        Ok(config)
-    }
 }
 
-/// Cross-platform shader webcam transformer.
-#[derive(Parser, Debug)]
-#[command(name = "proteus")]
-#[command(about = "Apply GPU shaders to webcam video in real-time")]
-#[command(group = clap::ArgGroup::new("config_or_options")
-    .required(false)
-    .args(["config"])
-    .conflicts_with_all(["input", "shader", "width", "height", "max_input_width", "max_input_height", "fps", "output", "image", "video"])
-)]
-struct Args {
-    /// Path to YAML configuration file (mutually exclusive with other options)
-    #[arg(short, long)]
-    config: Option<PathBuf>,
-
-    /// Camera device ID (index or name)
-    #[arg(short, long, default_value = "0")]
-    input: String,
-
-    /// Path to GLSL fragment shader file(s)
-    #[arg(short, long, num_args = 1..)]
-    shader: Vec<PathBuf>,
-
-    /// Frame width
-    #[arg(long, default_value = "1920")]
-    width: u32,
-
-    /// Frame height
-    #[arg(long, default_value = "1080")]
-    height: u32,
-
-    /// Maximum frame width (defaults to width if not specified)
-    #[arg(long)]
-    max_input_width: Option<u32>,
+fn default_pass_scale() -> f32 {
+    1.0
+}
 
-    /// Maximum frame height (defaults to height if not specified)
-    #[arg(long)]
-    max_input_height: Option<u32>,
+/// Grid of passes with a vertex shader but no `mesh`.
+const DEFAULT_VERTEX_MESH: [u32; 2] = [64, 36];
 
-    /// Target frames per second
-    #[arg(long, default_value = "30")]
-    fps: u32,
+/// Largest `mesh` in each direction.
+const MAX_MESH_CELLS: u32 = 1024;
 
-    /// List available cameras and exit
-    #[arg(long)]
-    list_devices: bool,
+/// How the studio separates the person from the camera background.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StudioKey {
+    /// The segmentation mask
+    #[default]
+    Mask,
+    /// A key color, e.g. a green screen
+    Chroma,
+}
 
-    /// Output mode: window or virtual-camera
-    #[arg(long, value_enum, default_value = "window")]
-    output: OutputMode,
+/// A virtual studio: the keyed camera over a background, with overlays and a
+/// Lua HUD on top. Expanded into the first shader pass and the texture slots.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct StudioConfig {
+    #[serde(default)]
+    pub key: StudioKey,
+    /// Key color (sRGB, 0 - 1) for the chroma key
+    #[serde(default = "default_studio_color")]
+    pub color: [f32; 3],
+    /// Chroma distance from the key color that is fully removed
+    #[serde(default = "default_studio_tolerance")]
+    pub tolerance: f32,
+    /// Width of the transition from removed to kept
+    #[serde(default = "default_studio_softness")]
+    pub softness: f32,
+    /// Removal of key color reflected onto the person (0 - 1)
+    #[serde(default = "default_studio_spill")]
+    pub spill: f32,
+    /// Image or video behind the person
+    pub background: PathBuf,
+    /// Images or videos (with alpha) drawn over the person, in order
+    #[serde(default)]
+    pub overlays: Vec<PathBuf>,
+    /// Lua script drawn on top of everything
+    pub hud: Option<PathBuf>,
+}
 
-    /// Path to image file(s) for shader use (up to 4 total with videos/lua, black if not provided)
-    #[arg(long, num_args = 0..=4)]
-    image: Vec<PathBuf>,
+fn default_studio_color() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
 
-    /// Path to video file(s) for shader use (up to 4 total with images/lua)
-    #[arg(long, num_args = 0..=4)]
-    video: Vec<PathBuf>,
+fn default_studio_tolerance() -> f32 {
+    0.15
+}
 
-    /// Path to Lua script(s) for dynamic texture generation (up to 4 total with images/videos)
-    #[arg(long, num_args = 0..=4)]
-    lua: Vec<PathBuf>,
+fn default_studio_softness() -> f32 {
+    0.1
 }
 
-/// Application state for the event loop.
-struct ProteusApp {
-    config: Config,
-    window: Option<Arc<Window>>,
-    renderer: Option<WindowRenderer>,
-    capture: Option<AsyncCapture>,
-    context: Option<Arc<GpuContext>>,
-    pipeline: Option<WgpuPipeline>,
-    last_frame_time: Instant,
-    frame_duration: Duration,
-    start_time: Instant,
-    fps_counter: FpsCounter,
-    // Config hot-reloading
-    config_watcher: Option<ConfigWatcher>,
+fn default_studio_spill() -> f32 {
+    0.5
 }
 
-impl ProteusApp {
-    fn new(config: Config) -> Self {
-        let frame_duration = Duration::from_secs_f64(1.0 / config.fps as f64);
-        
-        let config_watcher = ConfigWatcher::new(config.config_path.clone());
+/// Texture slots shared by the studio overlays and the HUD.
+const STUDIO_OVERLAY_SLOTS: usize = 3;
 
-        Self {
-            config,
-            window: None,
-            renderer: None,
-            capture: None,
-            context: None,
-            pipeline: None,
-            last_frame_time: Instant::now(),
-            frame_duration,
-            start_time: Instant::now(),
-            fps_counter: FpsCounter::new(),
-            config_watcher,
+impl StudioConfig {
+    /// Image or video input for a file: anything that isn't an image format is played as a video.
+    fn texture(path: &Path) -> TextureInput {
+        let path = path.to_path_buf();
+        match image::ImageFormat::from_path(&path) {
+            Ok(_) => TextureInput::Image { path, transform: SourceTransformConfig::default() },
+            Err(_) => TextureInput::Video { path, transform: SourceTransformConfig::default() },
         }
     }
 
-    fn initialize(&mut self) -> Result<()> {
-        // Initialize camera capture
-        let capture_config = CaptureConfig {
-            device_id: self.config.input.clone(),
-            width: self.config.width,
-            height: self.config.height,
-            max_input_width: self.config.max_input_width.unwrap_or(self.config.width),
-            max_input_height: self.config.max_input_height.unwrap_or(self.config.height),
-            fps: self.config.fps,
+    /// The studio pass.
+    fn pass(&self) -> ShaderEntry {
+        let builtin = match self.key {
+            StudioKey::Mask => BuiltinShaderConfig::Studio,
+            StudioKey::Chroma => BuiltinShaderConfig::StudioChroma,
         };
+        ShaderEntry::Builtin { builtin, scale: default_pass_scale(), modulators: Vec::new() }
+    }
 
-        info!("Opening camera device {}...", self.config.input);
-        
-        if let Some(capture) = init_capture(capture_config) {
-             let (cam_w, cam_h) = capture.frame_size();
-             info!("Camera opened successfully at {}x{} (async capture)", cam_w, cam_h);
-             self.capture = Some(capture);
-        } else {
-             error!("Failed to initialize camera capture");
-             // Don't error out, just continue without capture (recoverable via config reload)
+    /// The texture slots the pass reads: background, overlays, HUD.
+    fn textures(&self) -> Result<Vec<TextureInput>> {
+        let layers = self.overlays.len() + usize::from(self.hud.is_some());
+        if layers > STUDIO_OVERLAY_SLOTS {
+            anyhow::bail!("The studio has room for {} overlays and the HUD together, {} given", STUDIO_OVERLAY_SLOTS, layers);
         }
-
-        // Load shaders if provided
-        let shaders = load_shaders(&self.config.shader);
-
-        let context = self.context.clone().ok_or_else(|| anyhow::anyhow!("GPU context not initialized"))?;
-
-        // Initialize shader pipeline with textures from config
-        let texture_sources = load_textures_with_size(&self.config.textures, self.config.width, self.config.height, Some(context.clone()));
-        
-        self.pipeline = Some(WgpuPipeline::new(context, self.config.width, self.config.height, shaders, texture_sources)?);
-        info!("Shader pipeline initialized");
-
-        Ok(())
+        let mut textures = vec![Self::texture(&self.background)];
+        textures.extend(self.overlays.iter().map(|path| Self::texture(path)));
+        textures.extend(self.hud.iter().map(|path| TextureInput::Lua { path: path.clone(), transform: SourceTransformConfig::default(), resolution: CanvasResolutionConfig::default() }));
+        Ok(textures)
     }
 
-    fn process_frame(&mut self) {
-        // Check for config reload first
-        self.check_config_reload();
+    /// Initial values of the key parameters.
+    fn params(&self) -> [(&'static str, f32); 6] {
+        [
+            ("studio_key_r", self.color[0]),
+            ("studio_key_g", self.color[1]),
+            ("studio_key_b", self.color[2]),
+            ("studio_tolerance", self.tolerance),
+            ("studio_softness", self.softness),
+            ("studio_spill", self.spill),
+        ]
+    }
+}
 
-        let Some(capture) = &mut self.capture else {
-            return;
-        };
-        let Some(pipeline) = &mut self.pipeline else {
-            return;
-        };
-        let Some(renderer) = &mut self.renderer else {
-            return;
-        };
+impl ShaderEntry {
+    /// Path to the shader source file (`None` for builtin passes).
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            ShaderEntry::Path(path) | ShaderEntry::Pass { path, .. } => Some(path),
+            ShaderEntry::Builtin { .. } => None,
+        }
+    }
 
-        if let Some(fps) = self.fps_counter.update() {
-            debug!("[Perf] Rendering at {:.2} FPS (Resolution: {}x{})", fps, self.config.width, self.config.height);
+    /// The shader and vertex shader files.
+    fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        match self {
+            ShaderEntry::Path(path) => vec![path],
+            ShaderEntry::Pass { path, vertex, .. } => std::iter::once(path).chain(vertex.as_mut()).collect(),
+            ShaderEntry::Builtin { .. } => Vec::new(),
         }
+    }
 
-        // Get latest frame (non-blocking)
-        if let Some(frame) = capture.get_latest_frame() {
-            // Calculate time
-            let time = self.start_time.elapsed().as_secs_f32();
-            
-            // Optimized path: Render directly on GPU without CPU readback
-            if let Err(e) = pipeline.process_frame_gpu(&frame, time) {
-                error!("Shader processing error: {}", e);
-                return;
-            }
+    /// The shader file, or `builtin <name>`, for messages.
+    pub fn describe(&self) -> String {
+        match self {
+            ShaderEntry::Path(path) | ShaderEntry::Pass { path, .. } => path.display().to_string(),
+            ShaderEntry::Builtin { builtin, .. } => format!("builtin {}", builtin.to_builtin().name()),
+        }
+    }
 
-            // Display in window by sharing texture
-            if let Some(texture) = pipeline.output_texture() {
-                 let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-                 if let Err(e) = renderer.render_texture(&view) {
-                      error!("Render error: {}", e);
-                 }
+    /// Reads the shader source file (or takes the builtin source).
+    pub fn source(&self) -> Result<ShaderSource> {
+        match self {
+            ShaderEntry::Path(path) | ShaderEntry::Pass { path, .. } => {
+                let code = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Failed to read shader {:?}: {}", path, e))?;
+                Ok(ShaderSource::Glsl { code, path: Some(path.clone()) })
             }
+            ShaderEntry::Builtin { builtin, .. } => Ok(ShaderSource::Glsl { code: builtin.to_builtin().code().to_string(), path: None }),
         }
     }
 
-    /// Check for config file updates and reload if necessary.
-    fn check_config_reload(&mut self) {
-        if let Some(watcher) = &mut self.config_watcher {
-            if let Some((old_config, new_config)) = watcher.check_for_changes() {
-                self.handle_config_change(old_config, new_config);
+    /// Rendering options for this pass. Reads the vertex shader file.
+    pub fn options(&self) -> Result<PassOptions> {
+        match self {
+            ShaderEntry::Path(_) => Ok(PassOptions::default()),
+            ShaderEntry::Pass { scale, modulators, vertex, mesh, .. } => {
+                let vertex = match vertex {
+                    Some(path) => {
+                        let code = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Failed to read vertex shader {:?}: {}", path, e))?;
+                        let path = Some(path.clone());
+                        let is_wgsl = path.as_ref().and_then(|path| path.extension()).is_some_and(|ext| ext.eq_ignore_ascii_case("wgsl"));
+                        Some(if is_wgsl { ShaderSource::Wgsl { code, path } } else { ShaderSource::Glsl { code, path } })
+                    }
+                    None => None,
+                };
+                let mesh = mesh.or(vertex.is_some().then_some(DEFAULT_VERTEX_MESH));
+                Ok(PassOptions {
+                    scale: *scale,
+                    modulators: modulators.iter().map(ModulatorConfig::to_modulator).collect(),
+                    vertex,
+                    mesh: mesh.map(|[columns, rows]| (columns.clamp(1, MAX_MESH_CELLS), rows.clamp(1, MAX_MESH_CELLS))),
+                })
             }
+            ShaderEntry::Builtin { scale, modulators, .. } => Ok(PassOptions {
+                scale: *scale,
+                modulators: modulators.iter().map(ModulatorConfig::to_modulator).collect(),
+                ..PassOptions::default()
+            }),
         }
     }
+}
 
-    fn handle_config_change(&mut self, old_config_opt: Option<Config>, new_config: Config) {
-        if let Some(old_config) = old_config_opt {
-            let diff = ConfigDiff::compare(&old_config, &new_config);
-            
-            if diff.requires_restart {
-                tracing::warn!("Changes to output, input, width, height, max_input_width, max_input_height, or fps require a restart.");
-            }
+/// Waveform of a parameter modulator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WaveformConfig {
+    #[default]
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    Noise,
+}
 
-            if diff.needs_pipeline_reload() {
-                info!("Reloading pipeline due to shader/texture changes...");
+/// An LFO driving a named shader parameter.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ModulatorConfig {
+    /// Name of the parameter to drive
+    pub param: String,
+    #[serde(default)]
+    pub shape: WaveformConfig,
+    /// Cycles per second
+    #[serde(default = "default_modulator_rate")]
+    pub rate: f32,
+    /// Cycle length in beats; syncs the modulator to the tempo and overrides `rate`
+    #[serde(default)]
+    pub beats: Option<f32>,
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "default_range_max")]
+    pub max: f32,
+    /// Phase offset in cycles (0.0 - 1.0)
+    #[serde(default)]
+    pub phase: f32,
+}
+
+fn default_modulator_rate() -> f32 {
+    1.0
+}
+
+fn default_range_max() -> f32 {
+    1.0
+}
+
+impl ModulatorConfig {
+    pub fn to_modulator(&self) -> Modulator {
+        Modulator {
+            param: self.param.clone(),
+            waveform: match self.shape {
+                WaveformConfig::Sine => Waveform::Sine,
+                WaveformConfig::Triangle => Waveform::Triangle,
+                WaveformConfig::Saw => Waveform::Saw,
+                WaveformConfig::Square => Waveform::Square,
+                WaveformConfig::Noise => Waveform::Noise,
+            },
+            rate: match self.beats {
+                Some(beats) => Rate::Beats(beats),
+                None => Rate::Hz(self.rate),
+            },
+            min: self.min,
+            max: self.max,
+            phase: self.phase,
+        }
+    }
+}
+
+/// A source that can be selected by the input mixer.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MixerSourceConfig {
+    Camera {
+        input: String,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+    },
+    Video {
+        path: PathBuf,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+    },
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+    },
+    Lua {
+        path: PathBuf,
+        #[serde(default)]
+        transform: SourceTransformConfig,
+        #[serde(flatten)]
+        resolution: CanvasResolutionConfig,
+    },
+    TestPattern,
+}
+
+impl MixerSourceConfig {
+    /// The source's file, if it has one.
+    fn path_mut(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            MixerSourceConfig::Video { path, .. } | MixerSourceConfig::Image { path, .. } | MixerSourceConfig::Lua { path, .. } => Some(path),
+            MixerSourceConfig::Camera { .. } | MixerSourceConfig::TestPattern => None,
+        }
+    }
+
+    fn transform(&self) -> SourceTransformConfig {
+        match self {
+            MixerSourceConfig::Camera { transform, .. }
+            | MixerSourceConfig::Video { transform, .. }
+            | MixerSourceConfig::Image { transform, .. }
+            | MixerSourceConfig::Lua { transform, .. } => *transform,
+            MixerSourceConfig::TestPattern => SourceTransformConfig::default(),
+        }
+    }
+}
+
+/// Input mixer configuration.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MixerConfig {
+    /// Selectable sources (keys 1-9 in the preview window)
+    pub sources: Vec<MixerSourceConfig>,
+    /// Crossfade duration in seconds (0 = hard cut)
+    pub transition: f32,
+}
+
+impl Default for MixerConfig {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            transition: 0.5,
+        }
+    }
+}
+
+/// Action taken when the monitor detects black or frozen input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MonitorActionConfig {
+    /// Log a warning
+    #[default]
+    Log,
+    /// Cut to the mixer's `fallback` source, or show color bars until the input recovers
+    Fallback,
+    /// Flash a warning border over the output
+    Overlay,
+}
+
+/// Maps a gamepad input to a shader parameter, a preset or a control command.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct GamepadMappingConfig {
+    /// Gamepad element, e.g. `left-stick-x`, `right-trigger`, or `south`
+    pub input: String,
+    /// Parameter driven across `min..max`
+    #[serde(default)]
+    pub param: Option<String>,
+    /// Preset applied when the button is pressed
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Control command run when the button is pressed, e.g. `obs scene BRB`
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "default_range_max")]
+    pub max: f32,
+}
+
+/// How the output frame is composed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutMode {
+    /// The final shader output
+    #[default]
+    Single,
+    /// The output twice, side by side
+    SideBySide,
+    /// The output and a mirrored copy, side by side
+    Mirrored,
+    /// A 2x2 grid of the stages listed in `grid`
+    Grid,
+}
+
+/// How in-between frames are synthesized when the output runs faster than the camera.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterpolationConfig {
+    /// Repeat the last camera frame
+    #[default]
+    Off,
+    /// Crossfade between camera frames
+    Blend,
+    /// Motion-compensated blend (more CPU)
+    Motion,
+}
+
+/// Value of the `seed` shader uniform: `random`, `frame`, or a fixed number.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum SeedConfig {
+    Mode(SeedModeConfig),
+    Fixed(f32),
+}
+
+impl Default for SeedConfig {
+    fn default() -> Self {
+        SeedConfig::Mode(SeedModeConfig::Random)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeedModeConfig {
+    /// A new random value every frame
+    Random,
+    /// Derived from the frame number (reproducible)
+    Frame,
+}
+
+/// ONNX Runtime execution provider for the segmentation model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExecutionProviderConfig {
+    Tensorrt,
+    Cuda,
+    Rocm,
+    Openvino,
+    Directml,
+    Coreml,
+    Cpu,
+}
+
+/// YUV matrix of a camera's uncompressed formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMatrixConfig {
+    /// Reported by the driver, else BT.601 for SD resolutions and BT.709 for HD
+    #[default]
+    Auto,
+    Bt601,
+    Bt709,
+}
+
+/// YUV value range of a camera's uncompressed formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorRangeConfig {
+    /// Reported by the driver, else limited range
+    #[default]
+    Auto,
+    Limited,
+    Full,
+}
+
+/// Color space override for a camera.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct InputColorConfig {
+    pub matrix: ColorMatrixConfig,
+    pub range: ColorRangeConfig,
+}
+
+impl InputColorConfig {
+    fn matrix(&self) -> Option<YuvMatrix> {
+        match self.matrix {
+            ColorMatrixConfig::Auto => None,
+            ColorMatrixConfig::Bt601 => Some(YuvMatrix::Bt601),
+            ColorMatrixConfig::Bt709 => Some(YuvMatrix::Bt709),
+        }
+    }
+
+    fn range(&self) -> Option<YuvRange> {
+        match self.range {
+            ColorRangeConfig::Auto => None,
+            ColorRangeConfig::Limited => Some(YuvRange::Limited),
+            ColorRangeConfig::Full => Some(YuvRange::Full),
+        }
+    }
+}
+
+/// Camera pixel format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CameraPixelFormatConfig {
+    Mjpeg,
+    Yuyv,
+    Nv12,
+}
+
+/// Pixel format delivered by the virtual camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormatConfig {
+    Yuyv,
+    Uyvy,
+    Nv12,
+    Rgba,
+}
+
+/// Camera streaming mode, as listed by `--list-devices --verbose`.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+pub struct InputModeConfig {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub format: CameraPixelFormatConfig,
+}
+
+impl InputModeConfig {
+    fn to_mode(self) -> CameraMode {
+        CameraMode {
+            width: self.width,
+            height: self.height,
+            fps: self.fps,
+            format: match self.format {
+                CameraPixelFormatConfig::Mjpeg => CameraPixelFormat::Mjpeg,
+                CameraPixelFormatConfig::Yuyv => CameraPixelFormat::Yuyv,
+                CameraPixelFormatConfig::Nv12 => CameraPixelFormat::Nv12,
+            },
+        }
+    }
+}
+
+/// Composition guide drawn over the preview window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuideConfig {
+    /// Rule-of-thirds grid
+    Thirds,
+    /// Center cross
+    Center,
+    /// Action safe and title safe areas
+    SafeArea,
+}
+
+/// Borderless clean feed window on another monitor.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ProgramWindowConfig {
+    /// Monitor index or part of its name
+    pub monitor: String,
+}
+
+impl Default for ProgramWindowConfig {
+    fn default() -> Self {
+        Self { monitor: "1".to_string() }
+    }
+}
+
+/// How the preview window presents frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresentModeConfig {
+    /// V-sync; no tearing
+    #[default]
+    AutoVsync,
+    /// Newest frame at the next v-sync; no tearing, less latency
+    Mailbox,
+    /// No v-sync; lowest latency, may tear
+    Immediate,
+}
+
+/// Corner (or center) the watermark is placed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatermarkPositionConfig {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+/// Logo overlay composited after all shaders.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct WatermarkConfig {
+    /// Image file (PNG with alpha recommended)
+    pub path: PathBuf,
+    #[serde(default)]
+    pub position: WatermarkPositionConfig,
+    /// Distance from the frame edges in output pixels
+    #[serde(default = "default_watermark_margin")]
+    pub margin: f32,
+    /// Width as a fraction of the frame width
+    #[serde(default = "default_watermark_scale")]
+    pub scale: f32,
+    /// 0.0 (invisible) - 1.0 (opaque)
+    #[serde(default = "default_watermark_opacity")]
+    pub opacity: f32,
+}
+
+fn default_watermark_margin() -> f32 {
+    24.0
+}
+
+fn default_watermark_scale() -> f32 {
+    0.15
+}
+
+fn default_watermark_opacity() -> f32 {
+    1.0
+}
+
+/// Frame edge the captions are placed at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionPositionConfig {
+    Top,
+    #[default]
+    Bottom,
+}
+
+/// Captions burned into the output, from an SRT file or `proteus ctl caption`.
+/// Colors are sRGB RGBA (0 - 1).
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CaptionsConfig {
+    /// Subtitle file, timed by the shaders' `time`
+    pub srt: Option<PathBuf>,
+    /// Font family (first system font if not found)
+    pub font: Option<String>,
+    /// Text height as a fraction of the frame height
+    pub size: f32,
+    pub color: [f32; 4],
+    /// Box behind the text
+    pub background: [f32; 4],
+    pub position: CaptionPositionConfig,
+    /// Distance from the top or bottom edge as a fraction of the frame height
+    pub margin: f32,
+    /// Widest line as a fraction of the frame width
+    pub max_width: f32,
+    /// Seconds a live caption is shown (0 = until `caption clear`)
+    pub live_duration: f32,
+}
+
+/// Live captions from whisper.cpp's `whisper-stream`, which listens to the microphone.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct SpeechConfig {
+    /// Whisper model file (ggml)
+    pub model: PathBuf,
+    /// Spoken language (`en`, `de`, ... or `auto`)
+    #[serde(default = "default_speech_language")]
+    pub language: String,
+    /// Microphone (capture device index; default device if unset)
+    #[serde(default)]
+    pub device: Option<u32>,
+    /// Seconds the captions are held back after they are recognized
+    #[serde(default)]
+    pub delay: f32,
+    /// whisper.cpp stream tool
+    #[serde(default = "default_speech_command")]
+    pub command: String,
+    /// Further arguments passed to the tool
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+fn default_speech_language() -> String {
+    SpeechSettings::default().language
+}
+
+fn default_speech_command() -> String {
+    SpeechSettings::default().command
+}
+
+/// What the audio meter listens to; its levels drive VU meters in shaders and Lua.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "source", rename_all = "kebab-case")]
+pub enum AudioMeterConfig {
+    Microphone {
+        /// Input device (the default input if unset; required on Windows)
+        #[serde(default)]
+        device: Option<String>,
+    },
+    /// The soundtrack of a video texture
+    Texture {
+        /// Index into `textures`
+        slot: usize,
+    },
+}
+
+impl Default for CaptionsConfig {
+    fn default() -> Self {
+        let defaults = Captions::default();
+        Self {
+            srt: defaults.srt,
+            font: defaults.font,
+            size: defaults.size,
+            color: defaults.color,
+            background: defaults.background,
+            position: CaptionPositionConfig::default(),
+            margin: defaults.margin,
+            max_width: defaults.max_width,
+            live_duration: defaults.live_duration,
+        }
+    }
+}
+
+/// Connection to OBS Studio's WebSocket server (Tools → WebSocket Server Settings).
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ObsConfig {
+    /// Server address
+    #[serde(default = "default_obs_url")]
+    pub url: String,
+    /// Server password, if authentication is enabled
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Control commands run when OBS switches to the named program scene
+    #[serde(default)]
+    pub scenes: HashMap<String, String>,
+}
+
+fn default_obs_url() -> String {
+    ObsSettings::default().url
+}
+
+/// Wall clock time for overlays, corrected by an NTP server if set.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WallClockConfig {
+    /// NTP server (`host` or `host:port`); the system clock without one
+    pub ntp: Option<String>,
+    /// Seconds between NTP syncs
+    pub ntp_interval: f32,
+    /// Frame rate of `proteus.timecode`
+    pub timecode_fps: f32,
+}
+
+impl Default for WallClockConfig {
+    fn default() -> Self {
+        let defaults = WallClockSettings::default();
+        Self {
+            ntp: defaults.ntp_server,
+            ntp_interval: defaults.ntp_interval.as_secs_f32(),
+            timecode_fps: defaults.timecode_fps,
+        }
+    }
+}
+
+/// MQTT broker for Home Assistant and other smart-home systems (requires the `mqtt` feature).
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Base topic of the state and command topics
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    /// Publish Home Assistant discovery messages
+    #[serde(default = "default_mqtt_discovery")]
+    pub discovery: bool,
+    /// Redaction rule switched by the privacy topic
+    #[serde(default)]
+    pub privacy_rule: Option<String>,
+}
+
+fn default_mqtt_port() -> u16 {
+    MqttSettings::default().port
+}
+
+fn default_mqtt_topic() -> String {
+    MqttSettings::default().topic
+}
+
+fn default_mqtt_discovery() -> bool {
+    MqttSettings::default().discovery
+}
+
+/// WebSocket server for a Stream Deck plugin.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct StreamDeckConfig {
+    #[serde(default = "default_stream_deck_port")]
+    pub port: u16,
+    /// Key images per preset (PNG, JPEG or SVG)
+    #[serde(default)]
+    pub icons: HashMap<String, PathBuf>,
+}
+
+fn default_stream_deck_port() -> u16 {
+    proteus::streamdeck::DEFAULT_PORT
+}
+
+/// Browser UI with a preview, parameter sliders, presets and the log.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WebConfig {
+    /// Address to listen on (`0.0.0.0` for the whole network, which requires a token)
+    pub address: String,
+    pub port: u16,
+    /// Required as `?token=` in the page URL, if set
+    pub token: Option<String>,
+    /// Host names the page is opened by besides IP addresses and `localhost` (e.g. `studio.local`)
+    pub hosts: Vec<String>,
+    /// Preview frames per second
+    pub preview_fps: f32,
+    /// Slider ranges per parameter, `[min, max]` (default `[0, 1]`)
+    pub ranges: HashMap<String, [f32; 2]>,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        let defaults = WebSettings::default();
+        Self {
+            address: defaults.address,
+            port: defaults.port,
+            token: defaults.token,
+            hosts: defaults.hosts,
+            preview_fps: defaults.preview_fps,
+            ranges: HashMap::new(),
+        }
+    }
+}
+
+/// Role of the instance in a multi-machine sync.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum SyncConfig {
+    /// Send the runtime state to the followers
+    Leader {
+        /// Address to listen on (`0.0.0.0` for other machines on the network)
+        #[serde(default = "default_sync_address")]
+        address: String,
+        #[serde(default = "default_sync_port")]
+        port: u16,
+    },
+    /// Mirror the leader's runtime state
+    Follower {
+        /// The leader's `host` or `host:port`
+        leader: String,
+    },
+}
+
+fn default_sync_address() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_sync_port() -> u16 {
+    proteus::sync::DEFAULT_PORT
+}
+
+/// Container of recordings started without a path.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordContainerConfig {
+    /// Fragmented MP4
+    #[default]
+    Mp4,
+    /// Matroska
+    Mkv,
+}
+
+/// H.264 encoder of recordings.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoEncoderConfig {
+    /// The first available hardware encoder, x264 without one
+    #[default]
+    Auto,
+    X264,
+    Nvenc,
+    Qsv,
+    VideoToolbox,
+    Amf,
+}
+
+/// How recordings are written.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RecordingConfig {
+    pub container: RecordContainerConfig,
+    /// Seconds between fragments; at most this much is lost in a crash
+    pub fragment_interval: f32,
+    pub encoder: VideoEncoderConfig,
+    /// Constant quality, 0 (lossless) - 51 (worst) like x264's CRF
+    pub quality: Option<u32>,
+    /// Bitrate in kbit/s (instead of `quality`)
+    pub bitrate: Option<u32>,
+    /// Write the preset, parameters and detections of each recorded frame to a
+    /// JSON lines file next to the recording
+    pub metadata: bool,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            container: RecordContainerConfig::default(),
+            fragment_interval: RecordSettings::default().fragment_interval,
+            encoder: VideoEncoderConfig::default(),
+            quality: None,
+            bitrate: None,
+            metadata: false,
+        }
+    }
+}
+
+/// What the virtual camera does while no application reads it.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdleModeConfig {
+    /// Keep running at the full frame rate
+    #[default]
+    Off,
+    /// Keep delivering frames at `fps`
+    LowFps,
+    /// Render nothing until an application connects
+    Suspend,
+}
+
+/// Idle mode of the virtual camera without consumers.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct IdleConfig {
+    pub mode: IdleModeConfig,
+    /// Frames per second with `low-fps`
+    pub fps: u32,
+    /// Seconds without consumers before going idle
+    pub delay: f32,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self { mode: IdleModeConfig::default(), fps: 1, delay: IdleSettings::default().delay.as_secs_f32() }
+    }
+}
+
+/// Frame rates of the individual outputs; each defaults to `fps`.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct OutputFpsConfig {
+    /// Preview window
+    pub preview: Option<u32>,
+    /// Virtual camera
+    pub camera: Option<u32>,
+    /// Recordings
+    pub recording: Option<u32>,
+}
+
+/// Caps of the clips saved with `proteus ctl clip`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ClipConfig {
+    /// Frames per second (at most 50)
+    pub fps: u32,
+    /// Frames are scaled down to this width
+    pub max_width: u32,
+    /// Longest clip in seconds
+    pub max_duration: f32,
+}
+
+impl Default for ClipConfig {
+    fn default() -> Self {
+        let defaults = ClipSettings::default();
+        Self { fps: defaults.fps, max_width: defaults.max_width, max_duration: defaults.max_duration }
+    }
+}
+
+/// Final blit settings of each place frames are shown or written to.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SinksConfig {
+    /// The preview window
+    pub preview: SinkConfig,
+    /// The program window (like `virtual_camera` if not set)
+    pub program: Option<SinkConfig>,
+    /// The virtual camera output, the web UI preview and the frame monitor
+    pub virtual_camera: SinkConfig,
+    /// Recordings, clips and snapshots (like `virtual_camera` if not set)
+    pub recording: Option<SinkConfig>,
+}
+
+impl SinksConfig {
+    pub fn program(&self) -> &SinkConfig {
+        self.program.as_ref().unwrap_or(&self.virtual_camera)
+    }
+
+    pub fn recording(&self) -> &SinkConfig {
+        self.recording.as_ref().unwrap_or(&self.virtual_camera)
+    }
+}
+
+/// Final blit settings of one sink.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SinkConfig {
+    /// Mirror the sink horizontally
+    pub mirror: bool,
+    /// Color calibration
+    pub calibration: Option<CalibrationConfig>,
+    /// Signal written to the sink
+    pub transfer: TransferConfig,
+}
+
+/// Color calibration of a sink, applied in its final blit.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct CalibrationConfig {
+    /// Values above 1.0 brighten the midtones
+    #[serde(default = "default_calibration_one")]
+    pub gamma: f32,
+    /// Added to all channels (-1.0 - 1.0)
+    #[serde(default)]
+    pub brightness: f32,
+    #[serde(default = "default_calibration_one")]
+    pub contrast: f32,
+    /// 0.0 (greyscale) - 1.0 (unchanged)
+    #[serde(default = "default_calibration_one")]
+    pub saturation: f32,
+    /// 1D or 3D LUT in the `.cube` format, applied after the adjustments
+    pub lut: Option<PathBuf>,
+}
+
+/// Transfer characteristics forced on a sink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferConfig {
+    /// sRGB-encoded (what screens and video apps expect)
+    #[default]
+    Srgb,
+    /// Linear light
+    Linear,
+}
+
+fn default_calibration_one() -> f32 {
+    1.0
+}
+
+/// Where the compositor runs relative to the shaders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompositorStageConfig {
+    /// The shaders see the composed frame
+    Before,
+    /// The composed frame includes the shader output
+    #[default]
+    After,
+}
+
+/// Image shown in a compositor layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompositorSourceConfig {
+    Camera,
+    Output,
+    /// The texture slot given by `slot`
+    Texture,
+}
+
+/// A source placed in the composed frame.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct CompositorLayerConfig {
+    /// Name for `schedule` rules
+    pub name: Option<String>,
+    pub source: CompositorSourceConfig,
+    /// Texture slot (0 - 3) for `source: texture`
+    #[serde(default)]
+    pub slot: usize,
+    /// x, y, width, height in 0.0 - 1.0 frame coordinates
+    pub rect: [f32; 4],
+    /// x, y, width, height of the part of the source shown
+    #[serde(default = "default_compositor_crop")]
+    pub crop: [f32; 4],
+    /// Layers with a higher `z` are drawn on top
+    #[serde(default)]
+    pub z: i32,
+    /// 0.0 (invisible) - 1.0 (opaque)
+    #[serde(default = "default_compositor_opacity")]
+    pub opacity: f32,
+    /// Corner radius in output pixels
+    #[serde(default)]
+    pub radius: f32,
+}
+
+fn default_compositor_crop() -> [f32; 4] {
+    [0.0, 0.0, 1.0, 1.0]
+}
+
+fn default_compositor_opacity() -> f32 {
+    1.0
+}
+
+/// How layers enter and leave when the compositor layout changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransitionStyleConfig {
+    #[default]
+    Fade,
+    Slide,
+    Scale,
+}
+
+/// Animation of compositor layout switches.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct TransitionConfig {
+    #[serde(default)]
+    pub style: TransitionStyleConfig,
+    /// Seconds (0 = cut)
+    #[serde(default = "default_transition_duration")]
+    pub duration: f32,
+}
+
+impl Default for TransitionConfig {
+    fn default() -> Self {
+        Self { style: TransitionStyleConfig::Fade, duration: default_transition_duration() }
+    }
+}
+
+fn default_transition_duration() -> f32 {
+    0.5
+}
+
+/// Layout of several sources in one frame.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct CompositorConfig {
+    #[serde(default)]
+    pub stage: CompositorStageConfig,
+    /// The `default` layout
+    #[serde(default)]
+    pub layers: Vec<CompositorLayerConfig>,
+    /// Further layouts, switched with `proteus ctl switch-layout` or F1 - F12
+    #[serde(default)]
+    pub layouts: BTreeMap<String, Vec<CompositorLayerConfig>>,
+    #[serde(default)]
+    pub transition: TransitionConfig,
+}
+
+/// Shows a compositor layer (or the watermark) only at certain times of day.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ScheduleRuleConfig {
+    /// Compositor layer name, or `watermark`
+    pub show: String,
+    /// Daily time range, `HH:MM-HH:MM`
+    pub between: Option<String>,
+    /// Period in minutes of a repeating window
+    pub every: Option<u32>,
+    /// Minute the repeating window starts at, counted from midnight
+    #[serde(default)]
+    pub at: u32,
+    /// Length of the repeating window in minutes
+    #[serde(rename = "for")]
+    pub duration: Option<u32>,
+}
+
+/// Edge decontamination of segmentation composites.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct DecontaminationConfig {
+    /// Distance in pixels searched for clean foreground color
+    #[serde(default = "default_decontamination_radius")]
+    pub radius: f32,
+    /// Distance in pixels the mask is shrunk by
+    #[serde(default = "default_decontamination_erode")]
+    pub erode: f32,
+}
+
+fn default_decontamination_radius() -> f32 {
+    6.0
+}
+
+fn default_decontamination_erode() -> f32 {
+    1.0
+}
+
+/// How the first pass samples the camera input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputSamplingConfig {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+/// Sampling and sharpening of the camera input.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct InputFilterConfig {
+    #[serde(default)]
+    pub sampling: InputSamplingConfig,
+    /// Unsharp mask amount after scaling to the render size (0 = off)
+    #[serde(default)]
+    pub sharpen: f32,
+}
+
+/// How frames are blended into the long exposure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccumulationModeConfig {
+    /// Running average (motion blur)
+    #[default]
+    Average,
+    /// Brightest value with fading trails (light painting)
+    Max,
+}
+
+/// Long-exposure accumulation of the final shader output.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct AccumulationConfig {
+    #[serde(default)]
+    pub mode: AccumulationModeConfig,
+    /// Number of frames averaged (`average` mode)
+    #[serde(default = "default_accumulation_frames")]
+    pub frames: u32,
+    /// Fraction of the trails that fades per frame (`max` mode)
+    #[serde(default = "default_accumulation_decay")]
+    pub decay: f32,
+}
+
+fn default_accumulation_frames() -> u32 {
+    8
+}
+
+fn default_accumulation_decay() -> f32 {
+    0.05
+}
+
+/// Glow around bright areas of the final shader output.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct BloomConfig {
+    /// Brightness (max of R, G, B) from which pixels glow
+    #[serde(default = "default_bloom_threshold")]
+    pub threshold: f32,
+    /// Width of the soft transition below the threshold, as a fraction of it
+    #[serde(default = "default_bloom_softness")]
+    pub softness: f32,
+    /// Strength of the glow
+    #[serde(default = "default_bloom_intensity")]
+    pub intensity: f32,
+    /// Spread of the glow, 0.0 (tight) - 1.0 (wide)
+    #[serde(default = "default_bloom_radius")]
+    pub radius: f32,
+}
+
+fn default_bloom_threshold() -> f32 {
+    0.8
+}
+
+fn default_bloom_softness() -> f32 {
+    0.5
+}
+
+fn default_bloom_intensity() -> f32 {
+    0.6
+}
+
+fn default_bloom_radius() -> f32 {
+    0.6
+}
+
+/// Where particles are spawned.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParticleEmitterConfig {
+    /// Edge of the segmentation mask
+    #[default]
+    MaskEdge,
+    /// Where the camera image changed since the last frame
+    Motion,
+}
+
+/// GPU particles over the final shader output.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ParticlesConfig {
+    pub emitter: ParticleEmitterConfig,
+    /// Number of particles
+    pub count: u32,
+    /// Longest lifetime in seconds
+    pub lifetime: f32,
+    /// Initial speed in frame heights per second
+    pub speed: f32,
+    /// Downward acceleration in frame heights per second² (negative rises)
+    pub gravity: f32,
+    /// Diameter in output pixels
+    pub size: f32,
+    /// Emitter strength (0 - 1) below which nothing spawns
+    pub threshold: f32,
+    /// Colors over the lifetime (RGBA, 0 - 1)
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl Default for ParticlesConfig {
+    fn default() -> Self {
+        let particles = Particles::default();
+        Self {
+            emitter: ParticleEmitterConfig::default(),
+            count: particles.count,
+            lifetime: particles.lifetime,
+            speed: particles.speed,
+            gravity: particles.gravity,
+            size: particles.size,
+            threshold: particles.threshold,
+            colors: particles.colors,
+        }
+    }
+}
+
+/// Characters of the glyph atlas (`t_glyphs`) used by the `ascii` builtin.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GlyphsConfig {
+    /// Font family (default: the first monospaced system font)
+    pub font: Option<String>,
+    /// Characters to draw with, in any order
+    pub characters: String,
+}
+
+impl Default for GlyphsConfig {
+    fn default() -> Self {
+        let glyphs = Glyphs::default();
+        Self { font: glyphs.font, characters: glyphs.characters }
+    }
+}
+
+/// Cursor and click highlighting. Sizes are in output pixels, colors sRGB RGBA (0 - 1).
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CursorConfig {
+    /// Radius of the dot drawn at the cursor (0 = none)
+    pub dot_radius: f32,
+    pub dot_color: [f32; 4],
+    /// Radius of the highlight ring (0 = none)
+    pub ring_radius: f32,
+    /// Line width of the ring (0 = filled)
+    pub ring_width: f32,
+    pub ring_color: [f32; 4],
+    /// Radius click ripples grow to
+    pub click_radius: f32,
+    /// Seconds a click ripple lasts
+    pub click_duration: f32,
+    pub click_color: [f32; 4],
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        let defaults = CursorOverlay::default();
+        Self {
+            dot_radius: defaults.dot_radius,
+            dot_color: defaults.dot_color,
+            ring_radius: defaults.ring_radius,
+            ring_width: defaults.ring_width,
+            ring_color: defaults.ring_color,
+            click_radius: defaults.click_radius,
+            click_duration: defaults.click_duration,
+            click_color: defaults.click_color,
+        }
+    }
+}
+
+/// Zoom into the final frame that follows the mouse cursor.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ZoomConfig {
+    /// Magnification while zoomed in
+    #[serde(default = "default_zoom_level")]
+    pub level: f32,
+    /// Seconds the view takes to catch up with the cursor and zoom level (0 = instantly)
+    #[serde(default = "default_zoom_smoothing")]
+    pub smoothing: f32,
+    /// Start zoomed in
+    #[serde(default)]
+    pub active: bool,
+    /// Follow the mouse over the preview window (and `proteus ctl cursor`)
+    #[serde(default = "default_zoom_follow_cursor")]
+    pub follow_cursor: bool,
+}
+
+fn default_zoom_level() -> f32 {
+    2.0
+}
+
+fn default_zoom_smoothing() -> f32 {
+    0.3
+}
+
+fn default_zoom_follow_cursor() -> bool {
+    true
+}
+
+/// Black / frozen frame detection.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// Seconds the output must be black or unchanged before the alarm triggers
+    pub duration: f32,
+    /// Mean brightness (0.0 - 1.0) at or below which a frame counts as black
+    pub black_threshold: f32,
+    /// Mean brightness difference (0.0 - 1.0) up to which a frame counts as unchanged
+    pub tolerance: f32,
+    /// What to do when the alarm triggers
+    pub action: MonitorActionConfig,
+    /// Mixer source name to cut to with the `fallback` action
+    pub fallback: Option<String>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        let settings = MonitorSettings::default();
+        Self {
+            duration: settings.duration,
+            black_threshold: settings.black_threshold,
+            tolerance: settings.tolerance,
+            action: MonitorActionConfig::Log,
+            fallback: None,
+        }
+    }
+}
+
+/// Blurring of on-screen text detected in the camera input.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct TextBlurConfig {
+    /// ONNX text detection model (e.g. a PaddleOCR detection export)
+    pub model: PathBuf,
+    /// Probability (0.0 - 1.0) from which a pixel counts as text
+    #[serde(default = "default_text_blur_threshold")]
+    pub threshold: f32,
+    /// Extra margin around each text region in pixels
+    #[serde(default = "default_text_blur_padding")]
+    pub padding: f32,
+    /// Blur radius in pixels
+    #[serde(default = "default_text_blur_radius")]
+    pub radius: f32,
+}
+
+fn default_text_blur_threshold() -> f32 {
+    0.3
+}
+
+fn default_text_blur_padding() -> f32 {
+    8.0
+}
+
+fn default_text_blur_radius() -> f32 {
+    16.0
+}
+
+/// A static mask region (`t_regions`), given either as `rect` or as `polygon`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct RegionConfig {
+    /// x, y, width, height (0.0 - 1.0)
+    pub rect: Option<[f32; 4]>,
+    /// Corner points (0.0 - 1.0)
+    pub polygon: Option<Vec<[f32; 2]>>,
+    /// Width in pixels over which the region fades out beyond its edge
+    #[serde(default)]
+    pub feather: f32,
+}
+
+/// Where the contents of a data buffer come from.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataSourceConfig {
+    /// `values`, replaced by Lua (`proteus.set_buffer`) or `proteus ctl set-buffer`
+    #[default]
+    Values,
+    /// The body pose keypoints (requires `pose_model`)
+    Pose,
+}
+
+/// A storage buffer shaders read as `vec4` items (`Data0` - `Data3`, in order).
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct DataBufferConfig {
+    /// Name used to set the values
+    pub name: String,
+    #[serde(default)]
+    pub source: DataSourceConfig,
+    /// Capacity in items (4 floats each)
+    #[serde(default = "default_buffer_size")]
+    pub size: u32,
+    /// Initial values, 4 per item
+    #[serde(default)]
+    pub values: Vec<f32>,
+}
+
+fn default_buffer_size() -> u32 {
+    256
+}
+
+/// How a redaction rule hides its regions.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionEffectConfig {
+    #[default]
+    Pixelate,
+    Blur,
+}
+
+/// A redaction rule, redacting either a `rect`, the `people` or the objects found by a detection `model`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct RedactionRuleConfig {
+    /// Name used by `proteus ctl redact`
+    pub name: String,
+    #[serde(default = "default_redaction_enabled")]
+    pub enabled: bool,
+    /// x, y, width, height (0.0 - 1.0)
+    pub rect: Option<[f32; 4]>,
+    /// The people told apart by the segmentation
+    #[serde(default)]
+    pub people: bool,
+    /// ONNX detection model (YOLOv8-style export)
+    pub model: Option<PathBuf>,
+    /// Score (0.0 - 1.0) from which a detection counts
+    #[serde(default = "default_redaction_threshold")]
+    pub threshold: f32,
+    /// Classes of the model to redact (all if empty)
+    #[serde(default)]
+    pub classes: Vec<usize>,
+    /// Number of class scores in the model output (if further values follow, e.g. keypoints)
+    pub class_count: Option<usize>,
+    /// Leave the largest detection alone (the main subject)
+    #[serde(default)]
+    pub except_largest: bool,
+    #[serde(default)]
+    pub effect: RedactionEffectConfig,
+    /// Pixel block size or blur radius in pixels
+    #[serde(default = "default_redaction_strength")]
+    pub strength: f32,
+    /// Width in pixels over which the effect fades out beyond the region
+    #[serde(default)]
+    pub padding: f32,
+}
+
+fn default_redaction_enabled() -> bool {
+    true
+}
+
+fn default_redaction_threshold() -> f32 {
+    0.5
+}
+
+fn default_redaction_strength() -> f32 {
+    24.0
+}
+
+/// QR code detection.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct QrConfig {
+    /// Seconds between scans
+    pub interval: f32,
+    /// Seconds a code must be out of view before it triggers again
+    pub cooldown: f32,
+    /// Control commands run when a code with the given payload comes into view
+    pub actions: HashMap<String, String>,
+}
+
+impl Default for QrConfig {
+    fn default() -> Self {
+        let settings = QrSettings::default();
+        Self {
+            interval: settings.interval.as_secs_f32(),
+            cooldown: settings.cooldown.as_secs_f32(),
+            actions: HashMap::new(),
+        }
+    }
+}
+
+/// Gesture recognition.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GestureConfig {
+    /// Minimum keypoint confidence (0.0 - 1.0)
+    pub min_score: f32,
+    /// Seconds a static gesture must be held before it triggers
+    pub hold: f32,
+    /// Minimum seconds between two triggers of the same gesture
+    pub cooldown: f32,
+    /// Control commands run when a gesture (`open-palm`, `thumbs-up`, `wave`) triggers
+    pub actions: HashMap<String, String>,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        let settings = GestureSettings::default();
+        Self {
+            min_score: settings.min_score,
+            hold: settings.hold.as_secs_f32(),
+            cooldown: settings.cooldown.as_secs_f32(),
+            actions: HashMap::new(),
+        }
+    }
+}
+
+/// Configuration file structure.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Path to the config file (if loaded from file)
+    #[serde(skip)]
+    pub config_path: Option<PathBuf>,
+    /// Camera device ID (index or name)
+    pub input: String,
+    /// Cameras switched to automatically when they are connected, most preferred
+    /// first (index or name); `input` is used while none of them is connected
+    pub preferred_inputs: Vec<String>,
+    /// GLSL fragment shader passes, optionally with per-pass options
+    pub shaders: Vec<ShaderEntry>,
+    /// Virtual studio set up in front of `shaders` (uses the texture slots)
+    pub studio: Option<StudioConfig>,
+    /// Frame width
+    pub width: u32,
+    /// Frame height
+    pub height: u32,
+    /// Maximum frame width (for camera format selection)
+    pub max_input_width: Option<u32>,
+    /// Maximum frame height (for camera format selection)
+    pub max_input_height: Option<u32>,
+    /// Target frames per second
+    pub fps: u32,
+    /// Frame rates of the preview, virtual camera and recordings; the pipeline renders at the highest
+    pub output_fps: OutputFpsConfig,
+    /// Lower the frame rate or suspend the virtual camera while no application reads it
+    pub idle: IdleConfig,
+    /// Output mode: window or virtual-camera
+    pub output: OutputMode,
+    /// Pixel format delivered by the virtual camera (Linux only; Windows and macOS use the OBS format)
+    pub output_format: Option<OutputFormatConfig>,
+    /// Ordered texture inputs (images and videos)
+    pub textures: Vec<TextureInput>,
+    /// Process scheduling priority
+    pub priority: ProcessPriority,
+    /// GPU adapter power preference
+    pub gpu_power: GpuPower,
+    /// Keep converted shaders and compiled pipelines in the cache directory for faster starts
+    pub shader_cache: bool,
+    /// Low-impact mode: throttle to BACKGROUND_FPS while the preview window is minimized
+    pub background: bool,
+    /// Initial tally state (toggle with `L` in the preview window)
+    pub live: bool,
+    /// Additional independent chains (virtual camera mode only)
+    pub chains: Vec<ChainConfig>,
+    /// Input mixer; replaces `input` as the source of the (primary) chain
+    pub mixer: Option<MixerConfig>,
+    /// Black / frozen frame detection
+    pub monitor: Option<MonitorConfig>,
+    /// Source shown while the camera is unavailable
+    pub fallback: Option<MixerSourceConfig>,
+    /// Source shown at startup until the camera delivers its first frame
+    pub splash: Option<MixerSourceConfig>,
+    /// Initial values of named shader parameters (`Params` block at binding 8)
+    pub params: HashMap<String, f32>,
+    /// Save parameters changed at runtime next to the config file and restore them at startup
+    pub persist_params: bool,
+    /// Named sets of parameter values, applied with `proteus ctl switch-preset`
+    pub presets: HashMap<String, HashMap<String, f32>>,
+    /// LFOs driving named shader parameters in all passes
+    pub modulators: Vec<ModulatorConfig>,
+    /// Initial tempo of the musical clock (tap `T` in the preview window to change it)
+    pub bpm: f32,
+    /// Follow an Ableton Link session's tempo and beat (requires the `link` feature)
+    pub link: bool,
+    /// Wall clock of the `clock` uniform and `proteus.clock`, optionally synced over NTP
+    pub wall_clock: WallClockConfig,
+    /// Follow OBS Studio's program scene and control OBS with `proteus ctl obs`
+    pub obs: Option<ObsConfig>,
+    /// Publish the state to an MQTT broker and accept commands from it (requires the `mqtt` feature)
+    pub mqtt: Option<MqttConfig>,
+    /// Show the state on Stream Deck keys and accept key presses from a plugin
+    pub stream_deck: Option<StreamDeckConfig>,
+    /// Web UI for operating the instance from a browser
+    pub web: Option<WebConfig>,
+    /// Mirror the runtime state from a leader instance to followers, e.g. one per projector
+    pub sync: Option<SyncConfig>,
+    /// Gamepad inputs mapped to parameters, presets and control commands
+    pub gamepad: Vec<GamepadMappingConfig>,
+    /// Output layout, composed after all shaders
+    pub layout: LayoutMode,
+    /// Stages shown in the `grid` layout (0 = camera input, n = output of pass n)
+    pub grid: [usize; 4],
+    /// Logo overlay composited after all shaders and the layout
+    pub watermark: Option<WatermarkConfig>,
+    /// Captions from an SRT file or the control socket, drawn after the layout
+    pub captions: Option<CaptionsConfig>,
+    /// Live captions from local speech recognition (requires `captions`)
+    pub speech: Option<SpeechConfig>,
+    /// Audio levels exposed to shaders (`audio_peak`, `audio_rms`) and Lua (`proteus.audio`)
+    pub audio_meter: Option<AudioMeterConfig>,
+    /// Particles over the final shader output, before the bloom
+    pub particles: Option<ParticlesConfig>,
+    /// Glow around bright areas of the final shader output
+    pub bloom: Option<BloomConfig>,
+    /// Characters of the glyph atlas shaders read from `t_glyphs`
+    pub glyphs: Option<GlyphsConfig>,
+    /// Long-exposure accumulation of the final shader output
+    pub accumulation: Option<AccumulationConfig>,
+    /// Camera, shader output and texture slots placed in one frame
+    pub compositor: Option<CompositorConfig>,
+    /// Times of day at which compositor layers and the watermark are shown
+    pub schedule: Vec<ScheduleRuleConfig>,
+    /// Cursor and click highlighting, after the compositor
+    pub cursor: Option<CursorConfig>,
+    /// Zoom following the mouse, after the compositor and before the layout
+    pub zoom: Option<ZoomConfig>,
+    /// Removes background color from the person's edges when segmentation is active
+    pub decontamination: Option<DecontaminationConfig>,
+    /// Nearest or linear sampling of the camera input, and sharpening after scaling it
+    pub input_filter: Option<InputFilterConfig>,
+    /// Execution providers tried for the segmentation model, in order (platform default if empty)
+    pub execution_providers: Vec<ExecutionProviderConfig>,
+    /// OpenVINO device (`GPU`, `CPU`, `NPU`, ...)
+    pub openvino_device: Option<String>,
+    /// ONNX pose estimation model (MoveNet or BlazePose) whose keypoints are passed to shaders and Lua
+    pub pose_model: Option<PathBuf>,
+    /// Blurs on-screen text (emails, keys, ...) in the camera input
+    pub text_blur: Option<TextBlurConfig>,
+    /// Static mask regions shaders can limit effects to (binding 11)
+    pub regions: Vec<RegionConfig>,
+    /// Storage buffers shaders read as `vec4` items (bindings 13 - 16)
+    pub buffers: Vec<DataBufferConfig>,
+    /// Rules that pixelate or blur regions of the camera input
+    pub redaction: Vec<RedactionRuleConfig>,
+    /// QR code detection, passed to Lua (`on_qr`) and mapped to control commands
+    pub qr: Option<QrConfig>,
+    /// Gesture recognition on the pose keypoints (requires `pose_model`)
+    pub gestures: Option<GestureConfig>,
+    /// Composition guides shown in the preview window (toggle with `G`)
+    pub guides: Vec<GuideConfig>,
+    /// V-sync of the preview window
+    pub present_mode: PresentModeConfig,
+    /// Frames queued for the preview window (1 - 3); fewer lower the latency
+    pub max_frame_latency: u32,
+    /// Mirroring and color calibration of the preview window, program window, virtual camera and recordings
+    pub sinks: SinksConfig,
+    /// Clean feed of the output on another monitor, next to the preview window (window mode only)
+    pub program_window: Option<ProgramWindowConfig>,
+    /// YUV color space overrides per camera (device index or name)
+    pub input_color: HashMap<String, InputColorConfig>,
+    /// Streaming modes forced per camera (device index or name)
+    pub input_mode: HashMap<String, InputModeConfig>,
+    /// Clockwise rotation in degrees that turns a camera's frames upright (device index or name)
+    pub input_rotation: HashMap<String, u32>,
+    /// Convert uncompressed camera frames (YUYV, NV12) on the GPU instead of the CPU
+    pub input_passthrough: bool,
+    /// Frame interpolation of the camera input (primary chain and preview window)
+    pub interpolation: InterpolationConfig,
+    /// How the `seed` uniform changes per frame (random, frame counter, or fixed)
+    pub seed: SeedConfig,
+    /// Container, encoder and metadata of recordings
+    pub recording: RecordingConfig,
+    /// Caps of the GIF/APNG clips saved with `proteus ctl clip`
+    pub clip: ClipConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_path: None,
+            input: "0".to_string(),
+            preferred_inputs: Vec::new(),
+            shaders: Vec::new(),
+            width: 1920,
+            height: 1080,
+            max_input_width: None,
+            max_input_height: None,
+            fps: 30,
+            output: OutputMode::Window,
+            output_format: None,
+            textures: Vec::new(),
+            priority: ProcessPriority::Normal,
+            gpu_power: GpuPower::HighPerformance,
+            shader_cache: true,
+            background: false,
+            live: false,
+            chains: Vec::new(),
+            mixer: None,
+            monitor: None,
+            fallback: None,
+            splash: None,
+            params: HashMap::new(),
+            persist_params: false,
+            presets: HashMap::new(),
+            modulators: Vec::new(),
+            bpm: DEFAULT_BPM,
+            link: false,
+            wall_clock: WallClockConfig::default(),
+            obs: None,
+            mqtt: None,
+            stream_deck: None,
+            web: None,
+            sync: None,
+            gamepad: Vec::new(),
+            layout: LayoutMode::Single,
+            grid: [0, 1, 2, 3],
+            watermark: None,
+            captions: None,
+            speech: None,
+            audio_meter: None,
+            particles: None,
+            bloom: None,
+            glyphs: None,
+            studio: None,
+            accumulation: None,
+            compositor: None,
+            schedule: Vec::new(),
+            cursor: None,
+            zoom: None,
+            decontamination: None,
+            input_filter: None,
+            execution_providers: Vec::new(),
+            openvino_device: None,
+            pose_model: None,
+            text_blur: None,
+            regions: Vec::new(),
+            buffers: Vec::new(),
+            redaction: Vec::new(),
+            qr: None,
+            gestures: None,
+            guides: Vec::new(),
+            present_mode: PresentModeConfig::default(),
+            max_frame_latency: 2,
+            sinks: SinksConfig::default(),
+            program_window: None,
+            input_color: HashMap::new(),
+            input_mode: HashMap::new(),
+            input_rotation: HashMap::new(),
+            input_passthrough: false,
+            interpolation: InterpolationConfig::Off,
+            seed: SeedConfig::default(),
+            recording: RecordingConfig::default(),
+            clip: ClipConfig::default(),
+            output_fps: OutputFpsConfig::default(),
+            idle: IdleConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Create a Config from CLI arguments.
+    /// This handles the texture ordering from interleaved --image and --video flags.
+    fn from_cli_args(args: Args) -> Self {
+        // Build ordered texture inputs from CLI image/video args
+        let matches = Args::command().get_matches();
+        let mut ordered_inputs: Vec<(usize, TextureInput)> = Vec::new();
+
+        if let Some(indices) = matches.indices_of("video") {
+            let paths: Vec<&PathBuf> = args.video.iter().collect();
+            for (i, idx) in indices.enumerate() {
+                if i < paths.len() {
+                    ordered_inputs.push((idx, TextureInput::Video { path: paths[i].clone(), transform: SourceTransformConfig::default() }));
+                }
+            }
+        }
+
+        if let Some(indices) = matches.indices_of("image") {
+            let paths: Vec<&PathBuf> = args.image.iter().collect();
+            for (i, idx) in indices.enumerate() {
+                if i < paths.len() {
+                    ordered_inputs.push((idx, TextureInput::Image { path: paths[i].clone(), transform: SourceTransformConfig::default() }));
+                }
+            }
+        }
+
+        if let Some(indices) = matches.indices_of("lua") {
+            let paths: Vec<&PathBuf> = args.lua.iter().collect();
+            for (i, idx) in indices.enumerate() {
+                if i < paths.len() {
+                    ordered_inputs.push((idx, TextureInput::Lua { path: paths[i].clone(), transform: SourceTransformConfig::default(), resolution: CanvasResolutionConfig::default() }));
+                }
+            }
+        }
+
+        ordered_inputs.sort_by_key(|k| k.0);
+        let textures = ordered_inputs.into_iter().map(|(_, t)| t).collect();
+
+        Self {
+            config_path: None,
+            input: args.input,
+            preferred_inputs: Vec::new(),
+            shaders: args.shader.into_iter().map(ShaderEntry::Path).collect(),
+            width: args.width,
+            height: args.height,
+            max_input_width: args.max_input_width,
+            max_input_height: args.max_input_height,
+            fps: args.fps,
+            output: args.output,
+            output_format: None,
+            textures,
+            priority: args.priority,
+            gpu_power: args.gpu_power,
+            shader_cache: true,
+            background: args.background,
+            live: false,
+            chains: Vec::new(),
+            mixer: None,
+            monitor: None,
+            fallback: None,
+            splash: None,
+            params: HashMap::new(),
+            persist_params: false,
+            presets: HashMap::new(),
+            modulators: Vec::new(),
+            bpm: DEFAULT_BPM,
+            link: false,
+            wall_clock: WallClockConfig::default(),
+            obs: None,
+            mqtt: None,
+            stream_deck: None,
+            web: None,
+            sync: None,
+            gamepad: Vec::new(),
+            layout: LayoutMode::Single,
+            grid: [0, 1, 2, 3],
+            watermark: None,
+            captions: None,
+            speech: None,
+            audio_meter: None,
+            particles: None,
+            bloom: None,
+            glyphs: None,
+            studio: None,
+            accumulation: None,
+            compositor: None,
+            schedule: Vec::new(),
+            cursor: None,
+            zoom: None,
+            decontamination: None,
+            input_filter: None,
+            execution_providers: Vec::new(),
+            openvino_device: None,
+            pose_model: None,
+            text_blur: None,
+            regions: Vec::new(),
+            buffers: Vec::new(),
+            redaction: Vec::new(),
+            qr: None,
+            gestures: None,
+            guides: Vec::new(),
+            present_mode: PresentModeConfig::default(),
+            max_frame_latency: 2,
+            sinks: SinksConfig::default(),
+            program_window: None,
+            input_color: HashMap::new(),
+            input_mode: HashMap::new(),
+            input_rotation: HashMap::new(),
+            input_passthrough: false,
+            interpolation: InterpolationConfig::Off,
+            seed: SeedConfig::default(),
+            recording: RecordingConfig::default(),
+            clip: ClipConfig::default(),
+            output_fps: OutputFpsConfig::default(),
+            idle: IdleConfig::default(),
+        }
+    }
+    
+    /// Camera capture settings for the given device using this config's resolution and fps.
+    pub fn capture_config(&self, device_id: &str) -> CaptureConfig {
+        let color = self.input_color.get(device_id).cloned().unwrap_or_default();
+        let rotation = self.input_rotation.get(device_id).map_or(Some(Rotation::None), |&degrees| Rotation::from_degrees(degrees));
+        if rotation.is_none() {
+            warn!("Ignoring input_rotation of camera {}: only 0, 90, 180 and 270 degrees are supported", device_id);
+        }
+        CaptureConfig {
+            device_id: device_id.to_string(),
+            width: self.width,
+            height: self.height,
+            max_input_width: self.max_input_width.unwrap_or(self.width),
+            max_input_height: self.max_input_height.unwrap_or(self.height),
+            fps: self.fps,
+            matrix: color.matrix(),
+            range: color.range(),
+            mode: self.input_mode.get(device_id).map(|mode| mode.to_mode()),
+            rotation: rotation.unwrap_or_default(),
+        }
+    }
+
+    /// Resolves relative file paths (shaders, textures, sources, models) that don't
+    /// exist in the working directory, see [`paths::resolve`].
+    pub fn resolve_paths(&mut self, config_file: Option<&Path>) {
+        let primary = (&mut self.shaders, &mut self.textures, &mut self.mixer, &mut self.fallback);
+        let chains = self.chains.iter_mut().map(|chain| (&mut chain.shaders, &mut chain.textures, &mut chain.mixer, &mut chain.fallback));
+        let mut files: Vec<&mut PathBuf> = Vec::new();
+        for (shaders, textures, mixer, fallback) in std::iter::once(primary).chain(chains) {
+            files.extend(shaders.iter_mut().flat_map(ShaderEntry::paths_mut));
+            files.extend(textures.iter_mut().map(TextureInput::path_mut));
+            let sources = mixer.iter_mut().flat_map(|mixer| mixer.sources.iter_mut()).chain(fallback.as_mut());
+            files.extend(sources.filter_map(MixerSourceConfig::path_mut));
+        }
+        files.extend(self.splash.as_mut().and_then(MixerSourceConfig::path_mut));
+        files.extend(self.watermark.as_mut().map(|watermark| &mut watermark.path));
+        files.extend(self.captions.as_mut().and_then(|captions| captions.srt.as_mut()));
+        files.extend(self.speech.as_mut().map(|speech| &mut speech.model));
+        let sinks = [Some(&mut self.sinks.preview), self.sinks.program.as_mut(), Some(&mut self.sinks.virtual_camera), self.sinks.recording.as_mut()];
+        files.extend(sinks.into_iter().flatten().filter_map(|sink| sink.calibration.as_mut()?.lut.as_mut()));
+        files.extend(self.pose_model.as_mut());
+        files.extend(self.text_blur.as_mut().map(|text_blur| &mut text_blur.model));
+        files.extend(self.stream_deck.iter_mut().flat_map(|stream_deck| stream_deck.icons.values_mut()));
+        files.extend(self.redaction.iter_mut().filter_map(|rule| rule.model.as_mut()));
+        for file in files {
+            *file = paths::resolve(file, config_file);
+        }
+    }
+
+    /// Sets up the studio: its pass runs before the configured shaders, its
+    /// background, overlays and HUD take the texture slots in that order, and
+    /// its key settings become initial parameter values.
+    pub fn expand_studio(&mut self) -> Result<()> {
+        let Some(studio) = &self.studio else { return Ok(()) };
+        if !self.textures.is_empty() {
+            anyhow::bail!("`studio` uses the texture slots and can't be combined with `textures`");
+        }
+        self.textures = studio.textures()?;
+        self.shaders.insert(0, studio.pass());
+        for (name, value) in studio.params() {
+            self.params.entry(name.to_string()).or_insert(value);
+        }
+        Ok(())
+    }
+
+    /// Parses a config file, migrating older versions of the format (with warnings).
+    pub fn parse(content: &str, path: &Path) -> Result<Self> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content)?;
+        let changes = config_migration::migrate(&mut value)?;
+        if changes.is_empty() {
+            // Parsed from the text for line numbers in the errors
+            return Ok(serde_yaml::from_str(content)?);
+        }
+        for change in &changes {
+            tracing::warn!("{:?}: {}", path, change);
+        }
+        tracing::warn!("{:?}: Update these settings and add `version: {}` to the config file", path, config_migration::CONFIG_VERSION);
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    /// Load configuration from a YAML file.
+    pub fn from_file(path: &PathBuf) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config file {:?}: {}", path, e))?;
+        
+        let mut config = Config::parse(&content, path)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file {:?}: {}", path, e))?;
+        
+        config.expand_studio().map_err(|e| anyhow::anyhow!("Invalid studio in {:?}: {}", path, e))?;
+        config.resolve_paths(Some(path));
+        config.config_path = Some(path.clone());
+        info!("Loaded configuration from {:?}", path);
+        
+        Ok(config)
+    }
+}
+
+/// Cross-platform shader webcam transformer.
+#[derive(Parser, Debug)]
+#[command(name = "proteus")]
+#[command(about = "Apply GPU shaders to webcam video in real-time")]
+#[command(group = clap::ArgGroup::new("config_or_options")
+    .required(false)
+    .args(["config"])
+    .conflicts_with_all(["input", "shader", "width", "height", "max_input_width", "max_input_height", "fps", "output", "image", "video", "priority", "gpu_power", "background"])
+)]
+struct Args {
+    /// Path to YAML configuration file (mutually exclusive with other options)
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Camera device ID (index or name)
+    #[arg(short, long, default_value = "0")]
+    input: String,
+
+    /// Path to GLSL fragment shader file(s)
+    #[arg(short, long, num_args = 1..)]
+    shader: Vec<PathBuf>,
+
+    /// Frame width
+    #[arg(long, default_value = "1920")]
+    width: u32,
+
+    /// Frame height
+    #[arg(long, default_value = "1080")]
+    height: u32,
+
+    /// Maximum frame width (defaults to width if not specified)
+    #[arg(long)]
+    max_input_width: Option<u32>,
+
+    /// Maximum frame height (defaults to height if not specified)
+    #[arg(long)]
+    max_input_height: Option<u32>,
+
+    /// Target frames per second
+    #[arg(long, default_value = "30")]
+    fps: u32,
+
+    /// List available cameras and exit
+    #[arg(long)]
+    list_devices: bool,
+
+    /// With --list-devices, also list each camera's supported modes
+    #[arg(long, requires = "list_devices")]
+    verbose: bool,
+
+    /// Output mode: window or virtual-camera
+    #[arg(long, value_enum, default_value = "window")]
+    output: OutputMode,
+
+    /// Path to image file(s) for shader use (up to 4 total with videos/lua, black if not provided)
+    #[arg(long, num_args = 0..=4)]
+    image: Vec<PathBuf>,
+
+    /// Path to video file(s) for shader use (up to 4 total with images/lua)
+    #[arg(long, num_args = 0..=4)]
+    video: Vec<PathBuf>,
+
+    /// Path to Lua script(s) for dynamic texture generation (up to 4 total with images/videos)
+    #[arg(long, num_args = 0..=4)]
+    lua: Vec<PathBuf>,
+
+    /// Process priority: normal or high
+    #[arg(long, value_enum, default_value = "normal")]
+    priority: ProcessPriority,
+
+    /// GPU power preference: high-performance or low-power
+    #[arg(long, value_enum, default_value = "high-performance")]
+    gpu_power: GpuPower,
+
+    /// Low-impact mode: drop to 15 FPS while the preview window is minimized
+    #[arg(long)]
+    background: bool,
+
+    /// Run in the background as a virtual camera, without a console window
+    #[arg(long)]
+    daemon: bool,
+
+    /// Log file once detached in daemon mode (default: proteus.log in the cache directory)
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage starting Proteus at login
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    /// Control the running instance, e.g. `ctl set-uniform intensity 0.5`
+    ///
+    /// Commands: set-uniform <name> <value>, switch-preset <name>, snapshot [path],
+    /// record start [path], record stop, set-live <on|off>, time <pause|resume|seek <s>|speed <x>>,
+    /// switch-source <n>, stats, quit
+    Ctl {
+        #[arg(required = true, num_args = 1.., allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Check a config file and compile its shaders, without opening cameras or the GPU
+    Check {
+        /// Path to YAML configuration file
+        config: PathBuf,
+    },
+    /// Show the config and cache directories used to look up files
+    Paths,
+    /// Render color test patterns through the pipeline and report sRGB conversion errors
+    ColorAudit {
+        /// Config file whose GPU and sink transfer settings are used
+        config: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceAction {
+    /// Start Proteus in daemon mode at login with the given config file
+    Install {
+        /// Path to YAML configuration file
+        #[arg(short, long)]
+        config: PathBuf,
+    },
+    /// Stop starting Proteus at login
+    Uninstall,
+}
+
+/// Application state for the event loop.
+struct ProteusApp {
+    config: Config,
+    window: Option<Arc<Window>>,
+    renderer: Option<WindowRenderer>,
+    /// Clean feed on another monitor: no guides, mirrored and calibrated like the virtual camera
+    program: Option<WindowRenderer>,
+    // Camera device in use (differs from `config.input` after a switch)
+    input: String,
+    capture: Option<AsyncCapture>,
+    // Reopens the camera if it was unavailable at startup, or opens the one switched to
+    capture_retry: Option<CaptureRetry>,
+    // Reports cameras being connected and disconnected
+    devices: Option<DeviceMonitor>,
+    // Source shown while the camera is unavailable
+    fallback: Option<Mixer>,
+    // Source shown until the camera delivers its first frame
+    splash: Option<Mixer>,
+    mixer: Option<Mixer>,
+    monitor: Option<FrameMonitor>,
+    interpolator: Option<FrameInterpolator>,
+    context: Option<Arc<GpuContext>>,
+    pipeline: Option<WgpuPipeline>,
+    /// Frame rates of the preview and recordings
+    rates: OutputRates,
+    /// Schedules the preview frames; the pipeline also renders when a recording needs a frame
+    preview_pacer: FramePacer,
+    /// Schedules the program window frames at the preview rate, without the background throttle
+    program_pacer: FramePacer,
+    start_time: Instant,
+    // Config hot-reloading
+    config_watcher: Option<ConfigWatcher>,
+    // Whether the window is minimized or fully occluded
+    hidden: bool,
+    modifiers: ModifiersState,
+    // Tally state (live / on air)
+    live: bool,
+    // Musical clock (BPM / tap tempo)
+    clock: BeatClock,
+    // Wall clock for overlays, optionally synced over NTP
+    wall_clock: WallClock,
+    /// Time passed to the shaders (can be paused and scrubbed)
+    shader_time: ShaderTime,
+    // Control socket for `proteus ctl`
+    control: Option<ControlServer>,
+    gamepad: Option<GamepadController>,
+    speech: Option<SpeechToText>,
+    audio_meter: Option<AudioMeter>,
+    obs: Option<ObsClient>,
+    mqtt: Option<MqttClient>,
+    stream_deck: Option<StreamDeckServer>,
+    web: Option<WebServer>,
+    sync: Option<SyncNode>,
+    recorder: Option<Recorder>,
+    clip: Option<ClipRecorder>,
+    // Sidecar file for parameters changed at runtime
+    param_store: Option<ParamStore>,
+    // Undo/redo of parameter changes
+    history: ParamHistory,
+    // A/B parameter snapshots
+    ab: AbCompare,
+    // Shows and hides compositor layers and the watermark by time of day
+    scheduler: Scheduler,
+}
+
+impl ProteusApp {
+    fn new(config: Config) -> Self {
+        let rates = load_output_rates(&config);
+
+        let config_watcher = ConfigWatcher::new(config.config_path.clone());
+        let clock = new_clock(&config);
+        let wall_clock = load_wall_clock(&config);
+        let param_store = load_param_store(&config);
+        let scheduler = load_schedule(&config);
+
+        Self {
+            input: config.input.clone(),
+            config,
+            window: None,
+            renderer: None,
+            program: None,
+            capture: None,
+            capture_retry: None,
+            devices: None,
+            fallback: None,
+            splash: None,
+            mixer: None,
+            monitor: None,
+            interpolator: None,
+            context: None,
+            pipeline: None,
+            rates,
+            preview_pacer: FramePacer::new(rates.preview, Instant::now()),
+            program_pacer: FramePacer::new(rates.preview, Instant::now()),
+            start_time: Instant::now(),
+            config_watcher,
+            hidden: false,
+            modifiers: ModifiersState::empty(),
+            live: false,
+            clock,
+            wall_clock,
+            shader_time: ShaderTime::new(Instant::now()),
+            control: None,
+            gamepad: None,
+            speech: None,
+            audio_meter: None,
+            obs: None,
+            mqtt: None,
+            stream_deck: None,
+            web: None,
+            sync: None,
+            recorder: None,
+            clip: None,
+            param_store,
+            history: ParamHistory::new(),
+            ab: AbCompare::new(),
+            scheduler,
+        }
+    }
+
+    /// Undoes (or with `redo`, redoes) the last parameter change.
+    fn undo_params(&mut self, redo: bool) -> Result<()> {
+        let pipeline = self.pipeline.as_mut().ok_or_else(|| anyhow::anyhow!("Pipeline not initialized"))?;
+        let params = if redo { self.history.redo(pipeline.params()) } else { self.history.undo(pipeline.params()) };
+        let params = params.ok_or_else(|| anyhow::anyhow!("Nothing to {}", if redo { "redo" } else { "undo" }))?;
+        pipeline.set_params(params);
+        info!("{} parameter change", if redo { "Redid" } else { "Undid" });
+        Ok(())
+    }
+
+    /// The runtime state, as saved in sessions and sent to sync followers.
+    fn session(&self) -> Result<Session> {
+        let pipeline = self.pipeline.as_ref().ok_or_else(|| anyhow::anyhow!("Pipeline not initialized"))?;
+        Ok(Session {
+            preset: pipeline.preset().map(str::to_string),
+            params: pipeline.params().iter().map(|(name, value)| (name.clone(), *value)).collect(),
+            layout: pipeline.compositor().map(|compositor| compositor.layout.clone().unwrap_or_else(|| DEFAULT_LAYOUT.to_string())),
+            redaction: pipeline.redaction_rules().iter().map(|rule| (rule.name.clone(), rule.enabled)).collect(),
+            zoom: pipeline.zoom_active(),
+            source: self.mixer.as_ref().map(|mixer| mixer.active() + 1),
+            live: self.live,
+            bpm: Some(self.clock.bpm()),
+            time: Some(SessionTime::of(&self.shader_time, Instant::now())),
+        })
+    }
+
+    /// Saves the runtime state to a session file.
+    fn save_session(&self, path: &Path) -> Result<()> {
+        self.session()?.save(path)?;
+        info!("Saved session to {:?}", path);
+        Ok(())
+    }
+
+    /// Restores the runtime state from a session file. Parts that no longer fit
+    /// the config (e.g. a removed layout) are skipped and reported.
+    fn load_session(&mut self, path: &Path) -> Result<String> {
+        let session = Session::load(path)?;
+        if let (false, Some(pipeline)) = (session.params.is_empty(), &self.pipeline) {
+            self.history.record(pipeline.params(), "session", Instant::now());
+        }
+        let skipped = self.restore_session(session, false)?;
+        for reason in &skipped {
+            warn!("Session {:?}: {}", path, reason);
+        }
+        info!("Restored session from {:?}", path);
+        Ok(skipped.join("; "))
+    }
+
+    /// Applies the parts of a session that are set and returns the reasons
+    /// for the parts that were skipped. A loaded session replaces the parameters
+    /// and preset; with `merge` (a sync update, which only holds what changed)
+    /// parameters not in it keep their values.
+    fn restore_session(&mut self, session: Session, merge: bool) -> Result<Vec<String>> {
+        let pipeline = self.pipeline.as_mut().ok_or_else(|| anyhow::anyhow!("Pipeline not initialized"))?;
+        let now = Instant::now();
+        let mut skipped = Vec::new();
+        if merge {
+            let mut params = pipeline.params().clone();
+            params.extend(session.params);
+            pipeline.set_params(params);
+        } else if !session.params.is_empty() {
+            pipeline.set_params(session.params.into_iter().collect());
+        }
+        if !merge || session.preset.is_some() {
+            pipeline.set_preset(session.preset);
+        }
+        if let Some(layout) = &session.layout {
+            if let Err(e) = pipeline.switch_compositor_layout(layout) {
+                skipped.push(e.to_string());
+            }
+        }
+        for (rule, enabled) in &session.redaction {
+            if let Err(e) = pipeline.set_redaction_rule(rule, Some(*enabled)) {
+                skipped.push(e.to_string());
+            }
+        }
+        if let Some(zoom) = session.zoom {
+            if let Err(e) = pipeline.set_zoom_active(Some(zoom)) {
+                skipped.push(e.to_string());
+            }
+        }
+        if let (Some(source), Some(mixer)) = (session.source, &mut self.mixer) {
+            mixer.cut(source.saturating_sub(1));
+        }
+        self.live = session.live;
+        if let Some(bpm) = session.bpm.filter(|bpm| *bpm > 0.0) {
+            self.clock.set_bpm(bpm, now);
+        }
+        if let Some(time) = session.time {
+            time.restore(&mut self.shader_time, now);
+        }
+        Ok(skipped)
+    }
+
+    /// Returns the preview frame interval to use, throttled in background mode while hidden.
+    fn effective_frame_duration(&self) -> Duration {
+        let frame_duration = Duration::from_secs_f64(1.0 / self.rates.preview as f64);
+        if self.config.background && self.hidden {
+            frame_duration.max(Duration::from_secs_f64(1.0 / BACKGROUND_FPS as f64))
+        } else {
+            frame_duration
+        }
+    }
+
+    /// Handle a key press in the preview window.
+    fn handle_key(&mut self, code: KeyCode) {
+        // Ctrl+Z undoes, Ctrl+Shift+Z / Ctrl+Y redoes (Cmd on macOS)
+        let command_key = self.modifiers.control_key() || self.modifiers.super_key();
+        if command_key && matches!(code, KeyCode::KeyZ | KeyCode::KeyY) {
+            let redo = code == KeyCode::KeyY || self.modifiers.shift_key();
+            if let Err(e) = self.undo_params(redo) {
+                info!("{}", e);
+            }
+            return;
+        }
+
+        if code == KeyCode::KeyL {
+            self.live = !self.live;
+            info!("Tally: {}", if self.live { "LIVE" } else { "PREVIEW" });
+            return;
+        }
+
+        if code == KeyCode::KeyG {
+            if let Some(renderer) = &mut self.renderer {
+                // Show the configured guides, or all of them if none are configured
+                let configured = load_guides(&self.config);
+                let guides = if !renderer.guides().is_empty() {
+                    Guides::default()
+                } else if configured.is_empty() {
+                    Guides::ALL
+                } else {
+                    configured
+                };
+                renderer.set_guides(guides);
+            }
+            return;
+        }
+
+        if code == KeyCode::KeyZ {
+            if let Some(pipeline) = &mut self.pipeline {
+                match pipeline.set_zoom_active(None) {
+                    Ok(active) => info!("Zoom: {}", if active { "on" } else { "off" }),
+                    Err(e) => info!("{}", e),
+                }
+            }
+            return;
+        }
+
+        if code == KeyCode::KeyR {
+            reload::reload_all();
+            return;
+        }
+
+        if code == KeyCode::KeyT {
+            let bpm = self.clock.tap(Instant::now());
+            info!("Tap tempo: {:.1} BPM", bpm);
+            return;
+        }
+
+        // F1 - F12 switch the compositor layouts, `default` first
+        let function_keys = [
+            KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+            KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+        ];
+        if let Some(index) = function_keys.iter().position(|&key| key == code) {
+            if let Some(pipeline) = &mut self.pipeline {
+                let name = pipeline.compositor().and_then(|compositor| compositor.layout_names().get(index).map(|name| name.to_string()));
+                if let Some(name) = name {
+                    match pipeline.switch_compositor_layout(&name) {
+                        Ok(()) => info!("Switched to layout '{}'", name),
+                        Err(e) => info!("{}", e),
+                    }
+                }
+            }
+            return;
+        }
+
+        let digit = match code {
+            KeyCode::Digit1 => 1,
+            KeyCode::Digit2 => 2,
+            KeyCode::Digit3 => 3,
+            KeyCode::Digit4 => 4,
+            KeyCode::Digit5 => 5,
+            KeyCode::Digit6 => 6,
+            KeyCode::Digit7 => 7,
+            KeyCode::Digit8 => 8,
+            KeyCode::Digit9 => 9,
+            _ => return,
+        };
+
+        // 1-9 crossfade to a mixer source, Shift+1-9 cuts
+        if let Some(mixer) = &mut self.mixer {
+            if self.modifiers.shift_key() {
+                mixer.cut(digit - 1);
+            } else {
+                mixer.switch_to(digit - 1);
+            }
+        }
+    }
+
+    /// Opens the program window borderless on the configured monitor. Errors are logged.
+    fn open_program_window(&mut self, event_loop: &ActiveEventLoop, context: Arc<GpuContext>) {
+        let Some(program) = &self.config.program_window else {
+            return;
+        };
+        let Some(monitor) = find_monitor(event_loop, &program.monitor) else {
+            let monitors: Vec<String> = event_loop
+                .available_monitors()
+                .enumerate()
+                .map(|(index, monitor)| format!("{}: {}", index, monitor.name().unwrap_or_default()))
+                .collect();
+            warn!("Monitor '{}' not found, no program window (available: {})", program.monitor, monitors.join(", "));
+            return;
+        };
+
+        let window_attrs = WindowAttributes::default()
+            .with_title("Proteus - Program")
+            .with_decorations(false)
+            .with_fullscreen(Some(Fullscreen::Borderless(Some(monitor.clone()))));
+        let window = match event_loop.create_window(window_attrs) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                error!("Failed to create program window: {}", e);
+                return;
+            }
+        };
+        window.set_cursor_visible(false);
+
+        match WindowRenderer::new(window, context) {
+            Ok(mut renderer) => {
+                renderer.set_mirror(self.config.sinks.program().mirror);
+                renderer.set_present_mode(load_present_mode(&self.config), self.config.max_frame_latency);
+                if let Err(e) = renderer.set_calibration(load_calibration(self.config.sinks.program())) {
+                    error!("Program window calibration disabled: {}", e);
+                }
+                info!("Program window on monitor {}", monitor.name().unwrap_or_else(|| program.monitor.clone()));
+                self.program = Some(renderer);
+            }
+            Err(e) => error!("Failed to create program window renderer: {}", e),
+        }
+    }
+
+    fn set_hidden(&mut self, hidden: bool) {
+        if self.hidden != hidden {
+            self.hidden = hidden;
+            self.preview_pacer.set_interval(self.effective_frame_duration());
+            if self.config.background {
+                info!("Preview window {}, showing {:.0} FPS", if hidden { "hidden" } else { "visible" }, 1.0 / self.effective_frame_duration().as_secs_f64());
+            }
+        }
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        if let Some(mixer_config) = &self.config.mixer {
+            // The mixer owns its own camera captures
+            self.mixer = Some(load_mixer(mixer_config, &self.config));
+        } else if self.splash.is_some() {
+            // Keep showing the splash while the camera opens in the background
+            info!("Opening camera device {} in the background...", self.input);
+            let capture_config = self.config.capture_config(&self.input);
+            self.capture_retry = Some(CaptureRetry::open(capture_config, CAPTURE_RETRY_INTERVAL));
+        } else {
+            // Initialize camera capture
+            let capture_config = self.config.capture_config(&self.input);
+
+            info!("Opening camera device {}...", self.input);
+            
+            if let Some(capture) = init_capture(capture_config.clone()) {
+                 let (cam_w, cam_h) = capture.frame_size();
+                 info!("Camera opened successfully at {}x{} (async capture)", cam_w, cam_h);
+                 self.capture = Some(capture);
+            } else {
+                 error!("Failed to initialize camera capture, retrying in the background");
+                 // Don't error out, show the fallback (if any) until the camera appears
+                 self.capture_retry = Some(CaptureRetry::spawn(capture_config, CAPTURE_RETRY_INTERVAL));
+                 if let Some(fallback) = &self.config.fallback {
+                     self.fallback = load_fallback(fallback, &self.config);
+                 }
+            }
+        }
+
+        self.devices = Some(DeviceMonitor::start(DEVICE_POLL_INTERVAL));
+
+        // Load shaders if provided
+        let (shaders, pass_options) = load_shaders(&self.config.shaders);
+
+        let context = self.context.clone().ok_or_else(|| anyhow::anyhow!("GPU context not initialized"))?;
+        self.live = self.config.live;
+
+        // Initialize shader pipeline with textures from config
+        let texture_sources = load_textures_with_size(&self.config.textures, self.config.width, self.config.height, Some(context.clone()));
+        
+        let mut pipeline = WgpuPipeline::new(context, self.config.width, self.config.height, shaders, pass_options, texture_sources)?;
+        pipeline.set_texture_transforms(&load_texture_transforms(&self.config.textures));
+        pipeline.set_params(self.config.params.clone());
+        if let Some(store) = &self.param_store {
+            for (name, value) in store.saved() {
+                pipeline.set_param(name, *value);
+            }
+        }
+        pipeline.set_modulators(load_modulators(&self.config));
+        pipeline.set_layout(load_layout(&self.config));
+        if let Err(e) = pipeline.set_watermark(load_watermark(&self.config)) {
+            error!("Watermark disabled: {}", e);
+        }
+        if let Err(e) = pipeline.set_captions(load_captions(&self.config)) {
+            error!("Captions disabled: {}", e);
+        }
+        pipeline.set_particles(load_particles(&self.config));
+        pipeline.set_bloom(load_bloom(&self.config));
+        pipeline.set_glyphs(load_glyphs(&self.config));
+        pipeline.set_accumulation(load_accumulation(&self.config));
+        pipeline.set_compositor(load_compositor(&self.config));
+        pipeline.set_cursor_overlay(load_cursor(&self.config));
+        pipeline.set_zoom(load_zoom(&self.config));
+        pipeline.set_decontamination(load_decontamination(&self.config));
+        pipeline.set_input_filter(load_input_filter(&self.config));
+        pipeline.set_pose_model(self.config.pose_model.clone());
+        pipeline.set_text_blur(load_text_blur(&self.config));
+        pipeline.set_regions(load_regions(&self.config));
+        pipeline.set_data_buffers(load_data_buffers(&self.config));
+        pipeline.set_redaction(load_redaction(&self.config));
+        pipeline.set_qr(load_qr(&self.config));
+        pipeline.set_gesture_recognition(load_gestures(&self.config));
+        for (sink, mirror) in load_sink_mirrors(&self.config.sinks) {
+            pipeline.set_sink_mirror(sink, mirror);
+        }
+        for (sink, calibration) in load_sink_calibrations(&self.config.sinks) {
+            if let Err(e) = pipeline.set_sink_calibration(sink, calibration) {
+                error!("{:?} calibration disabled: {}", sink, e);
+            }
+        }
+        pipeline.set_yuv_passthrough(self.config.input_passthrough);
+        pipeline.set_seed_mode(load_seed(&self.config));
+        self.pipeline = Some(pipeline);
+        info!("Shader pipeline initialized");
+
+        match ControlServer::start() {
+            Ok(control) => self.control = Some(control),
+            Err(e) => tracing::warn!("Control socket unavailable: {}", e),
+        }
+        self.gamepad = load_gamepad(&self.config.gamepad);
+        self.speech = load_speech(&self.config);
+        self.audio_meter = load_audio_meter(&self.config);
+        self.obs = load_obs(&self.config);
+        self.mqtt = load_mqtt(&self.config);
+        self.stream_deck = load_stream_deck(&self.config);
+        self.web = load_web(&self.config);
+        self.sync = load_sync(&self.config);
+
+        if let Some(monitor_config) = &self.config.monitor {
+            self.monitor = Some(load_monitor(monitor_config, self.mixer.as_ref()));
+        }
+        self.interpolator = load_interpolation(self.config.interpolation);
+
+        Ok(())
+    }
+
+    /// Shows the next splash frame in the window. The shaders only apply to the camera.
+    fn show_splash(&mut self) {
+        let time = self.start_time.elapsed().as_secs_f32();
+        let (Some(splash), Some(renderer)) = (&mut self.splash, &mut self.renderer) else {
+            return;
+        };
+        if let Some(frame) = splash.next_frame(time) {
+            renderer.set_frame(frame.clone());
+            if let Err(e) = renderer.render() {
+                error!("Render error: {}", e);
+            }
+        }
+    }
+
+    /// Renders a frame; it is shown in the preview window if `preview` is set and in the
+    /// program window if `program` is.
+    fn process_frame(&mut self, preview: bool, program: bool) {
+        // Check for config reload first
+        self.check_config_reload();
+
+        // Keep the splash up until the pipeline is ready and the camera delivers a frame
+        if self.splash.is_some() {
+            if let Some(capture) = self.capture_retry.as_mut().and_then(|retry| retry.try_take()) {
+                self.capture = Some(capture);
+                self.capture_retry = None;
+            }
+            let camera_ready = self.capture.as_mut().is_some_and(|capture| capture.get_latest_frame().is_some());
+            if self.pipeline.is_some() && camera_ready {
+                info!("Camera ready, hiding splash");
+                self.splash = None;
+            } else {
+                self.show_splash();
+                return;
+            }
+        }
+
+        let Some(pipeline) = &mut self.pipeline else {
+            return;
+        };
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+
+        let stats = pipeline.stats_mut();
+        if let Some(capture) = &mut self.capture {
+            let (received, dropped) = capture.take_counts();
+            stats.frames(Stage::Capture, received);
+            stats.dropped(Stage::Capture, dropped);
+        }
+        if stats.update(Instant::now()) {
+            debug!("[Perf] {}", stats);
+        }
+
+        // Calculate time
+        let time = self.start_time.elapsed().as_secs_f32();
+        pipeline.set_live(self.live);
+        let now = Instant::now();
+        let shader_time = self.shader_time.time(now) as f32;
+        self.clock.update(now);
+        pipeline.set_clock(self.clock.state(now));
+        pipeline.set_wall_time(self.wall_clock.now());
+        pipeline.set_audio_levels(self.audio_meter.as_ref().map(AudioMeter::levels).unwrap_or_default());
+        pipeline.set_hidden(self.scheduler.update(now));
+
+        if let Some(capture) = self.capture_retry.as_mut().and_then(|retry| retry.try_take()) {
+            info!("Camera connected, leaving fallback");
+            self.capture = Some(capture);
+            self.capture_retry = None;
+            self.fallback = None;
+        }
+
+        if let Some(monitor) = &mut self.monitor {
+            let source = self.mixer.as_ref().or(self.fallback.as_ref().filter(|_| self.capture.is_none()));
+            monitor.set_static_source(source.is_some_and(Mixer::is_static));
+            pipeline.set_warning(monitor.warning());
+        }
+
+        // Get latest frame (non-blocking), from the mixer if configured
+        let frame = if let Some(mixer) = &mut self.mixer {
+            mixer.next_frame(time)
+        } else if let Some(capture) = &mut self.capture {
+            capture.get_latest_frame()
+        } else if let Some(fallback) = &mut self.fallback {
+            fallback.next_frame(time)
+        } else {
+            None
+        };
+        let frame = match &mut self.monitor {
+            Some(monitor) => monitor.filter(frame, time),
+            None => frame,
+        };
+        let frame = match (&mut self.interpolator, frame) {
+            (Some(interpolator), Some(frame)) => Some(interpolator.next(frame, time)),
+            (_, frame) => frame,
+        };
+
+        let rendered = frame.is_some();
+        if let Some(frame) = frame {
+            
+            // Optimized path: Render directly on GPU without CPU readback
+            if let Err(e) = pipeline.process_frame_gpu(frame, shader_time) {
+                error!("Shader processing error: {}", e);
+                return;
+            }
+
+            // Display in window by sharing texture
+            if preview {
+                let output_start = Instant::now();
+                match pipeline.write_output(renderer) {
+                    Ok(()) => pipeline.stats_mut().frame(Stage::Output, output_start.elapsed()),
+                    Err(e) => {
+                        error!("Render error: {}", e);
+                        pipeline.stats_mut().dropped(Stage::Output, 1);
+                    }
+                }
+            }
+            if let Some(program_renderer) = self.program.as_mut().filter(|_| program) {
+                if let Err(e) = pipeline.write_output(program_renderer) {
+                    error!("Program window error: {}", e);
+                }
+            }
+
+            // Recording needs the frame on the CPU
+            let now = Instant::now();
+            if let Some(recorder) = self.recorder.as_mut().filter(|recorder| recorder.wants_frame(now)) {
+                match pipeline.read_output(Sink::Recording) {
+                    Ok(output) => {
+                        let metadata = recorder.wants_metadata().then(|| pipeline.frame_metadata());
+                        recorder.write_frame(&output, metadata.as_ref(), now);
+                    }
+                    Err(e) => error!("Recording readback error: {}", e),
+                }
+            }
+            if let Some(clip) = self.clip.as_mut().filter(|clip| clip.wants_frame(now)) {
+                match pipeline.read_output(Sink::Recording) {
+                    Ok(output) => clip.write_frame(&output, now),
+                    Err(e) => error!("Clip readback error: {}", e),
+                }
+            }
+            if self.clip.as_ref().is_some_and(|clip| clip.is_complete(now)) {
+                self.clip = None;
+            }
+            if let Some(web) = &mut self.web {
+                if web.wants_preview(now) {
+                    match pipeline.read_output(Sink::VirtualCamera) {
+                        Ok(output) => web.publish_preview(&output),
+                        Err(e) => error!("Web UI preview readback error: {}", e),
+                    }
+                }
+            }
+        }
+
+        // The monitor samples the output a few times a second
+        if let Some(monitor) = &mut self.monitor {
+            let output = if rendered && monitor.wants_output(time) {
+                pipeline.read_output(Sink::VirtualCamera).map_err(|e| error!("Monitor readback error: {}", e)).ok()
+            } else {
+                None
+            };
+            monitor.observe(output.as_ref(), time);
+        }
+        if let (Some(monitor), Some(mixer)) = (&mut self.monitor, &mut self.mixer) {
+            if let Some(index) = monitor.take_switch_request() {
+                mixer.cut(index);
+            }
+        }
+    }
+
+    /// Check for config file updates and reload if necessary.
+    fn check_config_reload(&mut self) {
+        if let Some(watcher) = &mut self.config_watcher {
+            if let Some((old_config, new_config)) = watcher.check_for_changes() {
+                self.handle_config_change(old_config, new_config);
+            }
+        }
+    }
+
+    fn handle_config_change(&mut self, old_config_opt: Option<Config>, new_config: Config) {
+        if let Some(old_config) = old_config_opt {
+            let diff = ConfigDiff::compare(&old_config, &new_config);
+            
+            if diff.requires_restart {
+                tracing::warn!("Changed settings other than shaders and textures require a restart.");
+            }
+
+            if diff.live_changed {
+                self.live = new_config.live;
+                info!("Tally: {}", if self.live { "LIVE" } else { "PREVIEW" });
+            }
+
+            if diff.params_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    for (name, value) in &new_config.params {
+                        pipeline.set_param(name, *value);
+                    }
+                }
+                self.config.params = new_config.params.clone();
+            }
+            if diff.modulation_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_modulators(load_modulators(&new_config));
+                }
+            }
+
+            if diff.layout_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_layout(load_layout(&new_config));
+                }
+            }
+
+            if diff.particles_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_particles(load_particles(&new_config));
+                }
+            }
+
+            if diff.bloom_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_bloom(load_bloom(&new_config));
+                }
+            }
+
+            if diff.glyphs_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_glyphs(load_glyphs(&new_config));
+                }
+            }
+
+            if diff.accumulation_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_accumulation(load_accumulation(&new_config));
+                }
+            }
+
+            if diff.compositor_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_compositor(load_compositor(&new_config));
+                }
+            }
+
+            if diff.cursor_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_cursor_overlay(load_cursor(&new_config));
+                }
+            }
+
+            if diff.zoom_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_zoom(load_zoom(&new_config));
+                }
+            }
+
+            if diff.decontamination_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_decontamination(load_decontamination(&new_config));
+                }
+            }
+
+            if diff.input_filter_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_input_filter(load_input_filter(&new_config));
+                }
+            }
+
+            if diff.pose_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_pose_model(new_config.pose_model.clone());
+                }
+            }
+
+            if diff.text_blur_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_text_blur(load_text_blur(&new_config));
+                }
+            }
+
+            if diff.regions_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_regions(load_regions(&new_config));
+                }
+            }
+
+            if diff.buffers_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_data_buffers(load_data_buffers(&new_config));
+                }
+            }
+
+            if diff.redaction_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_redaction(load_redaction(&new_config));
+                }
+            }
+
+            if diff.qr_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_qr(load_qr(&new_config));
+                }
+                self.config.qr = new_config.qr.clone();
+            }
+
+            if diff.gestures_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_gesture_recognition(load_gestures(&new_config));
+                }
+                self.config.gestures = new_config.gestures.clone();
+            }
+
+            if diff.watermark_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    if let Err(e) = pipeline.set_watermark(load_watermark(&new_config)) {
+                        error!("Watermark disabled: {}", e);
+                    }
+                }
+            }
+
+            if diff.captions_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    if let Err(e) = pipeline.set_captions(load_captions(&new_config)) {
+                        error!("Captions disabled: {}", e);
+                    }
+                }
+            }
+
+            if diff.present_changed {
+                for renderer in self.renderer.iter_mut().chain(&mut self.program) {
+                    renderer.set_present_mode(load_present_mode(&new_config), new_config.max_frame_latency);
+                }
+            }
+
+            if diff.sinks_changed {
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.set_mirror(new_config.sinks.preview.mirror);
+                    if let Err(e) = renderer.set_calibration(load_calibration(&new_config.sinks.preview)) {
+                        error!("Preview calibration disabled: {}", e);
+                    }
+                }
+                if let Some(pipeline) = &mut self.pipeline {
+                    for (sink, mirror) in load_sink_mirrors(&new_config.sinks) {
+                        pipeline.set_sink_mirror(sink, mirror);
+                    }
+                    for (sink, calibration) in load_sink_calibrations(&new_config.sinks) {
+                        if let Err(e) = pipeline.set_sink_calibration(sink, calibration) {
+                            error!("{:?} calibration disabled: {}", sink, e);
+                        }
+                    }
+                }
+                if let Some(program) = &mut self.program {
+                    program.set_mirror(new_config.sinks.program().mirror);
+                    if let Err(e) = program.set_calibration(load_calibration(new_config.sinks.program())) {
+                        error!("Program window calibration disabled: {}", e);
+                    }
+                }
+            }
+
+            if diff.passthrough_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_yuv_passthrough(new_config.input_passthrough);
+                }
+            }
+
+            if diff.seed_changed {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_seed_mode(load_seed(&new_config));
+                }
+            }
+
+            if diff.interpolation_changed {
+                self.interpolator = load_interpolation(new_config.interpolation);
+            }
+
+            if diff.guides_changed {
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.set_guides(load_guides(&new_config));
+                }
+                self.config.guides = new_config.guides.clone();
+            }
+
+            if diff.bpm_changed {
+                self.clock.set_bpm(new_config.bpm, Instant::now());
+                info!("Tempo: {:.1} BPM", self.clock.bpm());
+            }
+
+            if diff.wall_clock_changed {
+                self.wall_clock = load_wall_clock(&new_config);
+            }
+
+            if diff.schedule_changed {
+                self.scheduler = load_schedule(&new_config);
+            }
+
+            if diff.speech_changed {
+                // Release the microphone before the new recognizer opens it
+                self.speech = None;
+                self.speech = load_speech(&new_config);
+            }
+
+            if diff.audio_meter_changed {
+                self.audio_meter = None;
+                self.audio_meter = load_audio_meter(&new_config);
+            }
+
+            if diff.obs_changed {
+                self.obs = load_obs(&new_config);
+                self.config.obs = new_config.obs.clone();
+            }
+
+            if diff.mqtt_changed {
+                // Disconnect before the new client connects with the same client ID
+                self.mqtt = None;
+                self.mqtt = load_mqtt(&new_config);
+            }
+
+            if diff.stream_deck_changed {
+                // Free the port before the new server binds it
+                self.stream_deck = None;
+                self.stream_deck = load_stream_deck(&new_config);
+            }
+
+            if diff.web_changed {
+                self.web = None;
+                self.web = load_web(&new_config);
+            }
+
+            if diff.sync_changed {
+                // Free the port before the new leader binds it
+                self.sync = None;
+                self.sync = load_sync(&new_config);
+            }
+            self.config.presets = new_config.presets.clone();
+            self.config.preferred_inputs = new_config.preferred_inputs.clone();
+            self.config.recording = new_config.recording;
+            self.config.clip = new_config.clip.clone();
+
+            if diff.needs_pipeline_reload() {
+                info!("Reloading pipeline due to shader/texture changes...");
                 if let Err(e) = self.rebuild_pipeline(&new_config) {
                      error!("Failed to rebuild pipeline: {}", e);
                 } else {
@@ -360,16 +3322,294 @@ impl ProteusApp {
                 }
             }
         }
-    }
-
-    fn rebuild_pipeline(&mut self, config: &Config) -> Result<()> {
-       let shaders = load_shaders(&config.shader);
-       let context = self.context.clone().ok_or_else(|| anyhow::anyhow!("No GPU context"))?;
-       let texture_sources = load_textures_with_size(&config.textures, self.config.width, self.config.height, Some(context.clone()));
-       
-       let pipeline = WgpuPipeline::new(context, self.config.width, self.config.height, shaders, texture_sources)?;
-       self.pipeline = Some(pipeline);
-       Ok(())
+    }
+
+    fn rebuild_pipeline(&mut self, config: &Config) -> Result<()> {
+       let (shaders, pass_options) = load_shaders(&config.shaders);
+       let context = self.context.clone().ok_or_else(|| anyhow::anyhow!("No GPU context"))?;
+       let texture_sources = load_textures_with_size(&config.textures, self.config.width, self.config.height, Some(context.clone()));
+       
+       let mut pipeline = WgpuPipeline::new(context, self.config.width, self.config.height, shaders, pass_options, texture_sources)?;
+       pipeline.set_texture_transforms(&load_texture_transforms(&config.textures));
+       // Keep the state set at runtime
+       if let Some(old) = &self.pipeline {
+           pipeline.inherit_state(old);
+       }
+       self.pipeline = Some(pipeline);
+       Ok(())
+    }
+
+    /// Handle pending commands from gamepads, QR codes, gestures, OBS scenes, MQTT, and the control socket.
+    fn handle_control(&mut self, event_loop: &ActiveEventLoop) {
+        let gamepad_commands = self.gamepad.as_mut().map(|gamepad| gamepad.poll()).unwrap_or_default();
+        for command in gamepad_commands {
+            if let Err(e) = self.run_command(command.clone(), event_loop) {
+                tracing::warn!("Gamepad command '{}' failed: {}", command, e);
+            }
+        }
+
+        if let Some(text) = self.speech.as_mut().and_then(|speech| speech.poll(Instant::now())) {
+            if let Err(e) = self.run_command(ControlCommand::Caption(Some(text)), event_loop) {
+                debug!("Speech caption not shown: {}", e);
+            }
+        }
+
+        let mqtt_commands = self.mqtt.as_mut().map(|mqtt| mqtt.poll()).unwrap_or_default();
+        for command in mqtt_commands {
+            if let Err(e) = self.run_command(command.clone(), event_loop) {
+                tracing::warn!("MQTT command '{}' failed: {}", command, e);
+            }
+        }
+
+        let sync_updates = self.sync.as_ref().map(|sync| sync.poll()).unwrap_or_default();
+        for update in sync_updates {
+            match self.restore_session(update, true) {
+                Ok(skipped) => skipped.iter().for_each(|reason| warn!("Sync: {}", reason)),
+                Err(e) => debug!("Sync update not applied: {}", e),
+            }
+        }
+
+        if let Some(scene) = self.obs.as_ref().and_then(|obs| obs.poll_scene()) {
+            if let Some(command) = load_obs_scenes(&self.config).remove(&scene) {
+                if let Err(e) = self.run_command(command.clone(), event_loop) {
+                    tracing::warn!("OBS scene command '{}' failed: {}", command, e);
+                }
+            }
+        }
+
+        let qr_codes = self.pipeline.as_mut().map(|pipeline| pipeline.take_qr_codes()).unwrap_or_default();
+        if !qr_codes.is_empty() {
+            let actions = load_qr_actions(&self.config);
+            for command in qr_codes.iter().filter_map(|payload| actions.get(payload)) {
+                if let Err(e) = self.run_command(command.clone(), event_loop) {
+                    tracing::warn!("QR command '{}' failed: {}", command, e);
+                }
+            }
+        }
+
+        let gestures = self.pipeline.as_mut().map(|pipeline| pipeline.take_gestures()).unwrap_or_default();
+        if !gestures.is_empty() {
+            let actions = load_gesture_actions(&self.config);
+            for command in gestures.iter().filter_map(|gesture| actions.get(gesture)) {
+                if let Err(e) = self.run_command(command.clone(), event_loop) {
+                    tracing::warn!("Gesture command '{}' failed: {}", command, e);
+                }
+            }
+        }
+
+        let events: Vec<DeviceEvent> = self.devices.as_ref().map(|devices| std::iter::from_fn(|| devices.try_recv()).collect()).unwrap_or_default();
+        for event in &events {
+            info!("{}", event);
+            if let Some(pipeline) = &mut self.pipeline {
+                pipeline.device_event(event);
+            }
+        }
+        if !events.is_empty() {
+            self.switch_to_preferred_input();
+        }
+
+        if let (Some(mqtt), Some(pipeline)) = (&mut self.mqtt, &self.pipeline) {
+            let privacy_rule = mqtt.settings().privacy_rule.as_deref();
+            mqtt.publish(&MqttState {
+                live: self.live,
+                fps: pipeline.stats().stage(Stage::Output).fps,
+                recording: self.recorder.is_some(),
+                preset: pipeline.preset().map(String::from),
+                privacy: privacy_rule.and_then(|rule| pipeline.redaction_rules().iter().find(|r| r.name == rule)).map(|rule| rule.enabled),
+            });
+        }
+
+        let sync_state = self.sync.as_ref().filter(|sync| !sync.is_follower()).and_then(|_| self.session().ok());
+        if let (Some(sync), Some(state)) = (&mut self.sync, sync_state) {
+            sync.publish(&state);
+        }
+
+        if let (Some(stream_deck), Some(pipeline)) = (&mut self.stream_deck, &self.pipeline) {
+            stream_deck.publish(&StreamDeckState {
+                live: self.live,
+                recording: self.recorder.is_some(),
+                preset: pipeline.preset().map(String::from),
+                redaction: pipeline.redaction_rules().iter().map(|rule| (rule.name.clone(), rule.enabled)).collect(),
+            });
+        }
+
+        if let (Some(web), Some(pipeline)) = (&mut self.web, &self.pipeline) {
+            web.publish(&web_state(self.live, self.recorder.is_some(), pipeline.preset(), &self.config.presets, pipeline.param_names(), pipeline.params()));
+        }
+
+        // Key presses and web UI commands are answered like `proteus ctl`
+        let control = self.control.iter().flat_map(|control| std::iter::from_fn(move || control.try_recv()));
+        let stream_deck = self.stream_deck.iter().flat_map(|stream_deck| std::iter::from_fn(move || stream_deck.try_recv()));
+        let web = self.web.iter().flat_map(|web| std::iter::from_fn(move || web.try_recv()));
+        let requests: Vec<_> = control.chain(stream_deck).chain(web).collect();
+        for request in requests {
+            let result = self.run_command(request.command.clone(), event_loop);
+            if let Err(e) = &result {
+                tracing::warn!("Control command '{}' failed: {}", request.command, e);
+            }
+            request.reply(result);
+        }
+    }
+
+    /// Switches to the most preferred connected camera, if it isn't in use yet.
+    fn switch_to_preferred_input(&mut self) {
+        let (Some(devices), false) = (&self.devices, self.config.preferred_inputs.is_empty() || self.mixer.is_some()) else { return };
+        let candidates = self.config.preferred_inputs.iter().chain(std::iter::once(&self.config.input)).map(String::as_str);
+        if let Some(best) = best_input(candidates, &devices.devices()).filter(|best| *best != self.input) {
+            let best = best.to_string();
+            if let Err(e) = self.switch_input(&best) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    /// Switches to another camera. The current one keeps showing until the new one delivers.
+    fn switch_input(&mut self, device_id: &str) -> Result<()> {
+        anyhow::ensure!(self.mixer.is_none(), "Inputs are set by the mixer");
+        info!("Switching to camera {}...", device_id);
+        self.capture_retry = Some(CaptureRetry::open(self.config.capture_config(device_id), CAPTURE_RETRY_INTERVAL));
+        self.input = device_id.to_string();
+        Ok(())
+    }
+
+    fn run_command(&mut self, command: ControlCommand, event_loop: &ActiveEventLoop) -> Result<String> {
+        let pipeline = self.pipeline.as_mut().ok_or_else(|| anyhow::anyhow!("Pipeline not initialized"))?;
+        match command {
+            ControlCommand::SetUniform { name, value } => {
+                self.history.record(pipeline.params(), &name, Instant::now());
+                pipeline.set_param(&name, value);
+            }
+            ControlCommand::SetBuffer { name, values } => {
+                pipeline.set_data_values(&name, values)?;
+            }
+            ControlCommand::SwitchPreset(name) => {
+                let preset = self.config.presets.get(&name).ok_or_else(|| anyhow::anyhow!("Unknown preset '{}'", name))?;
+                self.history.record(pipeline.params(), &name, Instant::now());
+                for (param, value) in preset {
+                    pipeline.set_param(param, *value);
+                }
+                info!("Switched to preset '{}'", name);
+                pipeline.set_preset(Some(name));
+            }
+            ControlCommand::Undo => self.undo_params(false)?,
+            ControlCommand::Redo => self.undo_params(true)?,
+            ControlCommand::Ab(command) => {
+                let now = Instant::now();
+                if let Some(params) = self.ab.handle(command, pipeline.params(), now)? {
+                    self.history.record(pipeline.params(), "ab", now);
+                    pipeline.set_params(params);
+                }
+            }
+            ControlCommand::Snapshot(path) => {
+                let path = path.unwrap_or_else(|| timestamped_path("snapshot", "png"));
+                pipeline.read_output(Sink::Recording)?.save(&path)?;
+                info!("Saved snapshot to {:?}", path);
+                return Ok(path.display().to_string());
+            }
+            ControlCommand::RecordStart(path) => {
+                anyhow::ensure!(self.recorder.is_none(), "Already recording");
+                let settings = load_recording(&self.config);
+                let path = path.unwrap_or_else(|| timestamped_path("recording", settings.container.extension()));
+                let mut recorder = Recorder::start(&path, self.config.width, self.config.height, self.rates.recording, &settings)?;
+                if self.config.recording.metadata {
+                    recorder.enable_metadata()?;
+                }
+                self.recorder = Some(recorder);
+                return Ok(path.display().to_string());
+            }
+            ControlCommand::RecordStop => {
+                let recorder = self.recorder.take().ok_or_else(|| anyhow::anyhow!("Not recording"))?;
+                return Ok(recorder.stop()?.display().to_string());
+            }
+            ControlCommand::RecordToggle if self.recorder.is_some() => return self.run_command(ControlCommand::RecordStop, event_loop),
+            ControlCommand::RecordToggle => return self.run_command(ControlCommand::RecordStart(None), event_loop),
+            ControlCommand::Clip { seconds, path } => {
+                anyhow::ensure!(self.clip.is_none(), "Already recording a clip");
+                let path = path.unwrap_or_else(|| timestamped_path("clip", "gif"));
+                let clip = ClipRecorder::start(&path, seconds.unwrap_or(DEFAULT_CLIP_SECONDS), &load_clip(&self.config), Instant::now())?;
+                self.clip = Some(clip);
+                return Ok(path.display().to_string());
+            }
+            ControlCommand::SetLive(live) => {
+                self.live = live;
+                info!("Tally: {}", if self.live { "LIVE" } else { "PREVIEW" });
+            }
+            ControlCommand::SetBpm(bpm) => {
+                self.clock.set_bpm(bpm, Instant::now());
+                return Ok(format!("{:.1} bpm", self.clock.bpm()));
+            }
+            ControlCommand::Tap => {
+                return Ok(format!("{:.1} bpm", self.clock.tap(Instant::now())));
+            }
+            ControlCommand::Time(command) => {
+                self.shader_time.handle(command, Instant::now());
+                return Ok(self.shader_time.to_string());
+            }
+            ControlCommand::SwitchSource(index) => {
+                let mixer = self.mixer.as_mut().ok_or_else(|| anyhow::anyhow!("No mixer configured"))?;
+                anyhow::ensure!(index <= mixer.len(), "No source {} (mixer has {})", index, mixer.len());
+                mixer.switch_to(index - 1);
+            }
+            ControlCommand::SwitchInput(name) => {
+                self.switch_input(&name)?;
+            }
+            ControlCommand::Devices => {
+                let devices = self.devices.as_ref().map(|devices| devices.devices()).unwrap_or_default();
+                return Ok(describe_devices(&devices, &self.input));
+            }
+            ControlCommand::SwitchLayout(name) => {
+                pipeline.switch_compositor_layout(&name)?;
+                info!("Switched to layout '{}'", name);
+            }
+            ControlCommand::Zoom(state) => {
+                let active = pipeline.set_zoom_active(state)?;
+                return Ok(if active { "on" } else { "off" }.to_string());
+            }
+            ControlCommand::ZoomAt { x, y } => {
+                pipeline.set_zoom_target(x, y);
+            }
+            ControlCommand::Cursor(position) => {
+                pipeline.set_cursor(position);
+            }
+            ControlCommand::Click => {
+                pipeline.cursor_click();
+            }
+            ControlCommand::Caption(text) => {
+                pipeline.set_caption(text)?;
+            }
+            ControlCommand::Redact { rule, enabled } => {
+                let enabled = pipeline.set_redaction_rule(&rule, enabled)?;
+                return Ok(if enabled { "on" } else { "off" }.to_string());
+            }
+            ControlCommand::Obs(command) => {
+                self.obs.as_ref().ok_or_else(|| anyhow::anyhow!("OBS is not configured (`obs`)"))?.send(command)?;
+            }
+            ControlCommand::Event { name, payload } => {
+                events::post(Event { name, payload });
+            }
+            ControlCommand::Stats => {
+                return Ok(pipeline.stats().to_string());
+            }
+            ControlCommand::Reload => {
+                reload::reload_all();
+            }
+            ControlCommand::ReloadStatus => {
+                return Ok(describe_reload_status());
+            }
+            ControlCommand::SaveSession(path) => {
+                let path = path.unwrap_or_else(|| timestamped_path("session", "yaml"));
+                self.save_session(&path)?;
+                return Ok(path.display().to_string());
+            }
+            ControlCommand::LoadSession(path) => {
+                return self.load_session(&path);
+            }
+            ControlCommand::Quit => {
+                info!("Quit requested via control socket");
+                event_loop.exit();
+            }
+        }
+        Ok(String::new())
     }
 }
 
@@ -390,16 +3630,29 @@ impl ApplicationHandler for ProteusApp {
                 self.window = Some(window.clone());
 
                 // Create GPU context shared between pipeline and renderer
-                match GpuContext::new(Some(&window)) {
+                match GpuContext::new(Some(&window), self.config.gpu_power.into(), load_shader_cache(&self.config)) {
                     Ok(context) => {
                         let context = Arc::new(context);
                         self.context = Some(context.clone());
 
                         // Create renderer
-                        match WindowRenderer::new(window, context) {
-                            Ok(renderer) => {
+                        match WindowRenderer::new(window, context.clone()) {
+                            Ok(mut renderer) => {
+                                renderer.set_guides(load_guides(&self.config));
+                                renderer.set_mirror(self.config.sinks.preview.mirror);
+                                renderer.set_present_mode(load_present_mode(&self.config), self.config.max_frame_latency);
+                                if let Err(e) = renderer.set_calibration(load_calibration(&self.config.sinks.preview)) {
+                                    error!("Preview calibration disabled: {}", e);
+                                }
                                 self.renderer = Some(renderer);
                                 info!("Window created successfully");
+                                self.open_program_window(event_loop, context.clone());
+
+                                // Show the splash right away, the pipeline takes a moment to build
+                                if self.config.mixer.is_none() {
+                                    self.splash = load_splash(&self.config);
+                                    self.show_splash();
+                                }
 
                                 // Initialize capture and pipeline
                                 if let Err(e) = self.initialize() {
@@ -429,24 +3682,77 @@ impl ApplicationHandler for ProteusApp {
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
+        // The program window only shows the output; keys and redraws are handled like the preview's
+        if let Some(program) = self.program.as_mut().filter(|program| program.window().id() == window_id) {
+            match &event {
+                WindowEvent::CloseRequested => {
+                    info!("Program window closed");
+                    self.program = None;
+                    return;
+                }
+                WindowEvent::Resized(size) => {
+                    program.resize(*size);
+                    return;
+                }
+                WindowEvent::Occluded(_) | WindowEvent::CursorMoved { .. } | WindowEvent::CursorLeft { .. } | WindowEvent::MouseInput { .. } => return,
+                _ => {}
+            }
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 info!("Window closed");
                 event_loop.exit();
             }
             WindowEvent::Resized(size) => {
+                // Some platforms report minimization as a zero-sized resize
+                self.set_hidden(size.width == 0 || size.height == 0);
                 if let Some(renderer) = &mut self.renderer {
                     renderer.resize(size);
                 }
             }
+            WindowEvent::Occluded(occluded) => {
+                self.set_hidden(occluded);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // The preview shows the whole frame stretched to the window
+                if let (Some(pipeline), Some(window)) = (&mut self.pipeline, &self.window) {
+                    let size = window.inner_size();
+                    if size.width > 0 && size.height > 0 {
+                        let x = position.x as f32 / size.width as f32;
+                        let x = if self.config.sinks.preview.mirror { 1.0 - x } else { x };
+                        pipeline.set_cursor(Some([x, position.y as f32 / size.height as f32]));
+                    }
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.set_cursor(None);
+                }
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, .. } => {
+                if let Some(pipeline) = &mut self.pipeline {
+                    pipeline.cursor_click();
+                }
+            }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed && !event.repeat => {
+                if let PhysicalKey::Code(code) = event.physical_key {
+                    self.handle_key(code);
+                }
+            }
             WindowEvent::RedrawRequested => {
+                // Rendered at the rate of whichever output needs a frame
                 let now = Instant::now();
-                if now.duration_since(self.last_frame_time) >= self.frame_duration {
-                    self.process_frame();
-                    self.last_frame_time = now;
+                let preview = self.preview_pacer.tick(now);
+                let program = self.program.is_some() && self.program_pacer.tick(now);
+                if preview || program || self.recorder.as_ref().is_some_and(|recorder| recorder.wants_frame(now)) {
+                    self.process_frame(preview, program);
                 }
 
                 // Request next frame
@@ -458,53 +3764,101 @@ impl ApplicationHandler for ProteusApp {
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.handle_control(event_loop);
+        if let (Some(params), Some(pipeline)) = (self.ab.update(Instant::now()), &mut self.pipeline) {
+            pipeline.set_params(params);
+        }
+        if let (Some(store), Some(pipeline)) = (&mut self.param_store, &self.pipeline) {
+            store.update(pipeline.params(), &self.config.params);
+        }
         if let Some(window) = &self.window {
             window.request_redraw();
         }
+        // Keeps the program window running while the preview is minimized
+        if let Some(program) = &self.program {
+            program.window().request_redraw();
+        }
     }
 }
 
 fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
+    let cli_args = Args::parse();
 
-    // Initialize ONNX Runtime
-    // We ignore errors here because if ML is not used/model missing, we might survive?
-    // But if we want auto-download or proper setup, we should check it.
-    // However, if the user doesn't use segmentation, we don't want to crash?
-    // But the error happens when loading the dylib.
-    if let Err(e) = proteus::ml::SegmentationEngine::init() {
-        tracing::warn!("Failed to initialize ONNX Runtime: {}. Segmentation will be unavailable.", e);
-    }
+    // Initialize logging; recent lines are kept for the web UI. In daemon mode
+    // the log ends up in a file, so it isn't colored.
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_ansi(!cli_args.daemon).with_writer(|| service::LogOutput))
+        .with(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(|| proteus::web::LogWriter))
+        .with(tracing_subscriber::filter::LevelFilter::INFO)
+        .init();
 
-    let cli_args = Args::parse();
+    match &cli_args.command {
+        Some(Command::Service { action }) => {
+            return match action {
+                ServiceAction::Install { config } => service::install(&paths::resolve(config, None)),
+                ServiceAction::Uninstall => service::uninstall(),
+            };
+        }
+        Some(Command::Ctl { command }) => {
+            let command: ControlCommand = command.join(" ").parse()?;
+            let message = proteus::control::send(&command)?;
+            if !message.is_empty() {
+                println!("{}", message);
+            }
+            return Ok(());
+        }
+        Some(Command::Check { config }) => return check_config(&paths::resolve(config, None)),
+        Some(Command::Paths) => {
+            paths::print();
+            return Ok(());
+        }
+        Some(Command::ColorAudit { config }) => return color_audit(config.as_ref().map(|config| paths::resolve(config, None)).as_ref()),
+        None => {}
+    }
 
     // List devices mode (allowed with or without config)
     if cli_args.list_devices {
-        println!("Available cameras:");
-        match NokhwaCapture::list_devices() {
-            Ok(devices) => {
-                for device in devices {
-                    println!("  [{}] {}", device.index, device.name);
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to list devices: {}", e);
-            }
-        }
+        list_devices(cli_args.verbose);
         return Ok(());
     }
 
+    // Detach before any threads are spawned
+    let daemon = cli_args.daemon;
+    if daemon {
+        let log_file = cli_args.log_file.clone().or_else(service::default_log_file);
+        service::detach(log_file.as_deref())?;
+    }
+
     // Load config from file or build from CLI args
-    let config = if let Some(config_path) = &cli_args.config {
-        Config::from_file(config_path)?
+    // Relative config paths are also looked up in the user config directory
+    let mut config = if let Some(config_path) = &cli_args.config {
+        Config::from_file(&paths::resolve(config_path, None))?
     } else {
-        Config::from_cli_args(cli_args)
+        let mut config = Config::from_cli_args(cli_args);
+        config.resolve_paths(None);
+        config
     };
 
+    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+    if daemon && config.output != OutputMode::VirtualCamera {
+        info!("Daemon mode: using virtual camera output");
+        config.output = OutputMode::VirtualCamera;
+    }
+
     info!("Starting Proteus...");
 
+    // Only records the execution providers; ONNX Runtime is loaded with the first model
+    if let Err(e) = proteus::ml::SegmentationEngine::init(&load_execution_providers(&config)) {
+        tracing::warn!("Failed to configure ONNX Runtime: {}", e);
+    }
+
+    if config.priority == ProcessPriority::High {
+        if let Err(e) = utils::raise_process_priority() {
+            tracing::warn!("Failed to raise process priority: {}", e);
+        }
+    }
+
     // Dispatch based on output mode
     match config.output {
         OutputMode::Window => run_window_mode(config)?,
@@ -515,8 +3869,107 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Prints the available cameras (and with `verbose`, their modes).
+/// One-line list of the connected cameras for `proteus ctl devices`, marking the one in use.
+fn describe_devices(devices: &[CameraInfo], input: &str) -> String {
+    if devices.is_empty() {
+        return "no cameras found".to_string();
+    }
+    devices
+        .iter()
+        .map(|device| if device.matches(input) { format!("{} (in use)", device) } else { device.to_string() })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_reload_status() -> String {
+    let status = reload::status();
+    if status.is_empty() {
+        return "no reloads yet".to_string();
+    }
+    status.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+fn list_devices(verbose: bool) {
+    println!("Available cameras:");
+    match NokhwaCapture::list_devices() {
+        Ok(devices) => {
+            for device in devices {
+                println!("  {}", device);
+                if verbose {
+                    match NokhwaCapture::list_modes(&device.index.to_string()) {
+                        Ok(modes) => {
+                            for mode in modes {
+                                println!("      {}", mode);
+                            }
+                        }
+                        Err(e) => println!("      Failed to list modes: {}", e),
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to list devices: {}", e);
+        }
+    }
+}
+
+/// Parses a config file and compiles the shaders of all its chains.
+fn check_config(path: &PathBuf) -> Result<()> {
+    let config = Config::from_file(path)?;
+    let mut failed = 0;
+    for entry in ChainConfig::all(&config).iter().flat_map(|chain| chain.shaders.iter()) {
+        let result = entry.source().and_then(|source| WgpuPipeline::check_shader(&source)).and_then(|()| match entry.options()?.vertex {
+            Some(vertex) => WgpuPipeline::check_vertex_shader(&vertex),
+            None => Ok(()),
+        });
+        match result {
+            Ok(()) => println!("ok     {}", entry.describe()),
+            Err(e) => {
+                println!("error  {}: {}", entry.describe(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} shader(s) failed to compile", failed);
+    }
+    println!("Config {} is valid", path.display());
+    Ok(())
+}
+
+/// Runs the color test patterns through a headless pipeline and prints which
+/// paths lose or duplicate the sRGB encoding.
+fn color_audit(config_path: Option<&PathBuf>) -> Result<()> {
+    let config = match config_path {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    let context = Arc::new(GpuContext::new(None, config.gpu_power.into(), load_shader_cache(&config))?);
+    let mut reports = Vec::new();
+    for (sink, calibration) in load_sink_calibrations(&config.sinks) {
+        let transfer = calibration.map(|calibration| calibration.transfer).unwrap_or_default();
+        println!("{:?} sink, {:?} transfer:", sink, transfer);
+        let sink_reports = proteus::shader::color_audit::run(context.clone(), sink, transfer)?;
+        for report in &sink_reports {
+            println!("  {}", report);
+        }
+        reports.extend(sink_reports);
+    }
+    println!("The preview window is not measured: its surface format is logged at startup (encoded in the shader if not sRGB).");
+    let failed = reports.iter().filter(|report| report.verdict != Verdict::Ok).count();
+    if failed > 0 {
+        anyhow::bail!("{} color path(s) failed", failed);
+    }
+    Ok(())
+}
+
 /// Run in window output mode (default).
 fn run_window_mode(config: Config) -> Result<()> {
+    if !config.chains.is_empty() {
+        tracing::warn!("Additional chains are only used in virtual-camera mode and will be ignored");
+    }
+
     let mut app = ProteusApp::new(config);
 
     // Create event loop
@@ -526,6 +3979,10 @@ fn run_window_mode(config: Config) -> Result<()> {
     // Run app
     event_loop.run_app(&mut app)?;
 
+    if let (Some(store), Some(pipeline)) = (&mut app.param_store, &app.pipeline) {
+        store.save(pipeline.params(), &app.config.params);
+    }
+
     Ok(())
 }
 
@@ -535,6 +3992,11 @@ fn run_virtual_camera_mode(config: Config) -> Result<()> {
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::thread;
 
+    #[cfg(not(target_os = "linux"))]
+    if !config.chains.is_empty() {
+        anyhow::bail!("Multiple chains require one virtual camera device per chain, which is only supported on Linux (v4l2loopback)");
+    }
+
     // Set up signal handler for graceful shutdown
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
@@ -543,49 +4005,59 @@ fn run_virtual_camera_mode(config: Config) -> Result<()> {
         r.store(false, Ordering::SeqCst);
     })?;
 
-    // Initialize camera capture (async for better performance)
-    let capture_config = CaptureConfig {
-        device_id: config.input.clone(),
-        width: config.width,
-        height: config.height,
-        max_input_width: config.max_input_width.unwrap_or(config.width),
-        max_input_height: config.max_input_height.unwrap_or(config.height),
-        fps: config.fps,
-    };
-
-    info!("Opening camera device {}...", config.input);
-    let mut capture = Some(AsyncCapture::new(capture_config)?);
-    info!("Camera opened successfully (async capture)");
+    // Initialize GPU Context (headless/no-window), shared by all chains
+    let context = Arc::new(GpuContext::new(None, config.gpu_power.into(), load_shader_cache(&config))?);
 
-    // Load shaders if provided
-    let shaders = load_shaders(&config.shader);
-
-    // Initialize GPU Context (headless/no-window)
-    let context = Arc::new(GpuContext::new(None)?);
+    // Open camera, pipeline, and virtual camera output for each chain
+    let mut chains = Vec::new();
+    for (index, chain_config) in ChainConfig::all(&config).into_iter().enumerate() {
+        chains.push(Chain::new(index, chain_config, &config, context.clone())?);
+    }
 
-    // Build texture sources from config textures
-    let texture_sources = load_textures_with_size(&config.textures, config.width, config.height, Some(context.clone()));
-    
-    let mut pipeline = WgpuPipeline::new(context.clone(), config.width, config.height, shaders, texture_sources)?;
-    info!("Shader pipeline initialized");
+    // Restore the parameters changed in the last run
+    let mut param_store = load_param_store(&config);
+    let mut default_params = config.params.clone();
+    if let Some(store) = &param_store {
+        for (name, value) in store.saved() {
+            chains.iter_mut().for_each(|chain| chain.set_param(name, *value));
+        }
+    }
 
     // Initialize config watcher if config file is used
     let mut config_watcher = ConfigWatcher::new(config.config_path.clone());
+    let mut presets = config.presets.clone();
+    let mut qr_actions = load_qr_actions(&config);
+    let mut gesture_actions = load_gesture_actions(&config);
+    let mut scheduler = load_schedule(&config);
+    let mut preferred_inputs = config.preferred_inputs.clone();
+    let devices = DeviceMonitor::start(DEVICE_POLL_INTERVAL);
 
-    // Initialize virtual camera output
-    let vc_config = VirtualCameraConfig {
-        width: config.width,
-        height: config.height,
-        fps: config.fps,
-        ..Default::default()
+    let control = match ControlServer::start() {
+        Ok(control) => Some(control),
+        Err(e) => {
+            tracing::warn!("Control socket unavailable: {}", e);
+            None
+        }
     };
-    let mut output = VirtualCameraOutput::new(vc_config)?;
-    info!("Virtual camera output initialized");
+    let mut gamepad = load_gamepad(&config.gamepad);
+    let mut speech = load_speech(&config);
+    let mut audio_meter = load_audio_meter(&config);
+    let mut obs = load_obs(&config);
+    let mut obs_scenes = load_obs_scenes(&config);
+    let mut mqtt = load_mqtt(&config);
+    let mut stream_deck = load_stream_deck(&config);
+    let mut web = load_web(&config);
+    let mut sync = load_sync(&config);
 
-    let frame_duration = Duration::from_secs_f64(1.0 / config.fps as f64);
+    let rates = load_output_rates(&config);
     let start_time = Instant::now();
+    let mut clock = new_clock(&config);
+    let mut wall_clock = load_wall_clock(&config);
+    let mut shader_time = ShaderTime::new(start_time);
+    let mut history = ParamHistory::new();
+    let mut ab = AbCompare::new();
     let mut fps_counter = FpsCounter::new();
-    info!("Starting virtual camera stream at {} fps", config.fps);
+    info!("Starting virtual camera stream at {} fps ({} chain(s))", rates.camera, chains.len());
 
     // Main loop
     while running.load(Ordering::SeqCst) {
@@ -596,68 +4068,636 @@ fn run_virtual_camera_mode(config: Config) -> Result<()> {
                      let diff = ConfigDiff::compare(&old_config, &new_config);
                      
                      if diff.requires_restart {
-                         tracing::warn!("Changes to output, input, width, height, max_input_width, max_input_height, or fps require a restart.");
+                         tracing::warn!("Changed settings other than shaders and textures require a restart.");
                      }
 
-                    if diff.needs_pipeline_reload() {
-                        info!("Reloading pipeline due to shader/texture changes...");
-                        let new_shaders = load_shaders(&new_config.shader);
-                        let new_texture_sources = load_textures_with_size(&new_config.textures, config.width, config.height, Some(context.clone()));
-                       
-                        match WgpuPipeline::new(context.clone(), config.width, config.height, new_shaders, new_texture_sources) {
-                           Ok(new_pipeline) => {
-                               pipeline = new_pipeline;
-                               info!("Pipeline reloaded successfully");
-                           }
-                           Err(e) => error!("Failed to rebuild pipeline: {}", e),
-                        }
-                    }
+                     for (chain, chain_config) in chains.iter_mut().zip(ChainConfig::all(&new_config)) {
+                         chain.reload(chain_config, context.clone());
+                         if diff.live_changed {
+                             chain.set_live(new_config.live);
+                         }
+                         if diff.params_changed {
+                             for (name, value) in &new_config.params {
+                                 chain.set_param(name, *value);
+                             }
+                         }
+                         if diff.modulation_changed {
+                             chain.set_modulators(load_modulators(&new_config));
+                         }
+                         if diff.layout_changed {
+                             chain.set_layout(load_layout(&new_config));
+                         }
+                         if diff.watermark_changed {
+                             chain.set_watermark(load_watermark(&new_config));
+                         }
+                         if diff.captions_changed {
+                             chain.set_captions(load_captions(&new_config));
+                         }
+                         if diff.particles_changed {
+                             chain.set_particles(load_particles(&new_config));
+                         }
+                         if diff.bloom_changed {
+                             chain.set_bloom(load_bloom(&new_config));
+                         }
+                         if diff.glyphs_changed {
+                             chain.set_glyphs(load_glyphs(&new_config));
+                         }
+                         if diff.accumulation_changed {
+                             chain.set_accumulation(load_accumulation(&new_config));
+                         }
+                         if diff.compositor_changed {
+                             chain.set_compositor(load_compositor(&new_config));
+                         }
+                         if diff.cursor_changed {
+                             chain.set_cursor_overlay(load_cursor(&new_config));
+                         }
+                         if diff.zoom_changed {
+                             chain.set_zoom(load_zoom(&new_config));
+                         }
+                         if diff.decontamination_changed {
+                             chain.set_decontamination(load_decontamination(&new_config));
+                         }
+                         if diff.input_filter_changed {
+                             chain.set_input_filter(load_input_filter(&new_config));
+                         }
+                         if diff.pose_changed {
+                             chain.set_pose_model(new_config.pose_model.clone());
+                         }
+                         if diff.text_blur_changed {
+                             chain.set_text_blur(load_text_blur(&new_config));
+                         }
+                         if diff.regions_changed {
+                             chain.set_regions(load_regions(&new_config));
+                         }
+                         if diff.buffers_changed {
+                             chain.set_data_buffers(load_data_buffers(&new_config));
+                         }
+                         if diff.redaction_changed {
+                             chain.set_redaction(load_redaction(&new_config));
+                         }
+                         if diff.qr_changed {
+                             chain.set_qr(load_qr(&new_config));
+                         }
+                         if diff.gestures_changed {
+                             chain.set_gesture_recognition(load_gestures(&new_config));
+                         }
+                         if diff.sinks_changed {
+                             chain.set_sink_mirrors(load_sink_mirrors(&new_config.sinks));
+                             chain.set_sink_calibrations(load_sink_calibrations(&new_config.sinks));
+                         }
+                         if diff.passthrough_changed {
+                             chain.set_yuv_passthrough(new_config.input_passthrough);
+                         }
+                         if diff.seed_changed {
+                             chain.set_seed_mode(load_seed(&new_config));
+                         }
+                     }
+                     if diff.bpm_changed {
+                         clock.set_bpm(new_config.bpm, Instant::now());
+                         info!("Tempo: {:.1} BPM", clock.bpm());
+                     }
+                     if diff.wall_clock_changed {
+                         wall_clock = load_wall_clock(&new_config);
+                     }
+                     if diff.schedule_changed {
+                         scheduler = load_schedule(&new_config);
+                     }
+                     if diff.speech_changed {
+                         // Release the microphone before the new recognizer opens it
+                         drop(speech.take());
+                         speech = load_speech(&new_config);
+                     }
+                     if diff.audio_meter_changed {
+                         drop(audio_meter.take());
+                         audio_meter = load_audio_meter(&new_config);
+                     }
+                     if diff.obs_changed {
+                         obs = load_obs(&new_config);
+                         obs_scenes = load_obs_scenes(&new_config);
+                     }
+                     if diff.mqtt_changed {
+                         // Disconnect before the new client connects with the same client ID
+                         mqtt.take();
+                         mqtt = load_mqtt(&new_config);
+                     }
+                     if diff.stream_deck_changed {
+                         // Free the port before the new server binds it
+                         stream_deck.take();
+                         stream_deck = load_stream_deck(&new_config);
+                     }
+                     if diff.web_changed {
+                         web.take();
+                         web = load_web(&new_config);
+                     }
+                     if diff.sync_changed {
+                         sync.take();
+                         sync = load_sync(&new_config);
+                     }
+                     presets = new_config.presets.clone();
+                     default_params = new_config.params.clone();
+                     qr_actions = load_qr_actions(&new_config);
+                     gesture_actions = load_gesture_actions(&new_config);
+                     preferred_inputs = new_config.preferred_inputs.clone();
+                     chains.iter_mut().for_each(|chain| chain.set_record_metadata(new_config.recording.metadata));
+                     chains.iter_mut().for_each(|chain| chain.set_record_settings(load_recording(&new_config)));
+                     if diff.idle_changed {
+                         chains.iter_mut().for_each(|chain| chain.set_idle(load_idle(&new_config)));
+                     }
+                     chains.iter_mut().for_each(|chain| chain.set_clip_settings(load_clip(&new_config)));
                  }
             }
         }
 
-        let frame_start = Instant::now();
+        for command in gamepad.as_mut().map(|gamepad| gamepad.poll()).unwrap_or_default() {
+            if let Err(e) = run_chain_command(command.clone(), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config) {
+                tracing::warn!("Gamepad command '{}' failed: {}", command, e);
+            }
+        }
 
-        // FPS counter
-        if let Some(fps) = fps_counter.update() {
-            info!("Virtual camera: {:.2} FPS", fps);
+        if let Some(text) = speech.as_mut().and_then(|speech| speech.poll(Instant::now())) {
+            if let Err(e) = run_chain_command(ControlCommand::Caption(Some(text)), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config) {
+                debug!("Speech caption not shown: {}", e);
+            }
         }
 
-        // Get latest frame (non-blocking)
-        let frame_option = if let Some(cap) = &mut capture {
-            cap.get_latest_frame()
-        } else {
-            None
-        };
+        for command in mqtt.as_mut().map(|mqtt| mqtt.poll()).unwrap_or_default() {
+            if let Err(e) = run_chain_command(command.clone(), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config) {
+                tracing::warn!("MQTT command '{}' failed: {}", command, e);
+            }
+        }
 
-        if let Some(frame) = frame_option {
-            // Process through shader
-            let time = start_time.elapsed().as_secs_f32();
-            let shader_start = Instant::now();
-            match pipeline.process_frame(frame, time) {
-                Ok(processed) => {
-                    let shader_elapsed = shader_start.elapsed();
-                    // Write to virtual camera
-                    let write_start = Instant::now();
-                    if let Err(e) = output.write_frame(&processed) {
-                        error!("Output error: {}", e);
-                    }
-                    let write_elapsed = write_start.elapsed();
-                    debug!("[Perf] Virtual Camera - Shader: {:?}, Write: {:?}", shader_elapsed, write_elapsed);
-                }
-                Err(e) => {
-                    error!("Shader processing error: {}", e);
+        for update in sync.as_ref().map(|sync| sync.poll()).unwrap_or_default() {
+            for reason in restore_chain_session(&mut chains, &mut clock, &mut shader_time, update, true) {
+                warn!("Sync: {}", reason);
+            }
+        }
+
+        if let Some(command) = obs.as_ref().and_then(|obs| obs.poll_scene()).and_then(|scene| obs_scenes.get(&scene)) {
+            if let Err(e) = run_chain_command(command.clone(), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config) {
+                tracing::warn!("OBS scene command '{}' failed: {}", command, e);
+            }
+        }
+
+        let qr_codes: Vec<String> = chains.iter_mut().flat_map(|chain| chain.take_qr_codes()).collect();
+        for command in qr_codes.iter().filter_map(|payload| qr_actions.get(payload)) {
+            if let Err(e) = run_chain_command(command.clone(), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config) {
+                tracing::warn!("QR command '{}' failed: {}", command, e);
+            }
+        }
+
+        let gestures: Vec<Gesture> = chains.iter_mut().flat_map(|chain| chain.take_gestures()).collect();
+        for command in gestures.iter().filter_map(|gesture| gesture_actions.get(gesture)) {
+            if let Err(e) = run_chain_command(command.clone(), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config) {
+                tracing::warn!("Gesture command '{}' failed: {}", command, e);
+            }
+        }
+
+        let events: Vec<DeviceEvent> = std::iter::from_fn(|| devices.try_recv()).collect();
+        for event in &events {
+            info!("{}", event);
+            chains.iter_mut().for_each(|chain| chain.device_event(event));
+        }
+        // Only the primary chain follows the preferred cameras
+        if !events.is_empty() && !preferred_inputs.is_empty() {
+            let candidates = preferred_inputs.iter().chain(std::iter::once(&config.input)).map(String::as_str);
+            if let Some(best) = best_input(candidates, &devices.devices()).filter(|best| *best != chains[0].input()) {
+                let best = best.to_string();
+                if let Err(e) = chains[0].switch_input(&best, &config) {
+                    warn!("{}", e);
                 }
             }
         }
 
-        // Frame rate limiting
-        let elapsed = frame_start.elapsed();
-        if elapsed < frame_duration {
-            thread::sleep(frame_duration - elapsed);
+        // Key presses and web UI commands are answered like `proteus ctl`
+        let requests = control.iter().flat_map(|control| std::iter::from_fn(move || control.try_recv()));
+        let deck_requests = stream_deck.iter().flat_map(|stream_deck| std::iter::from_fn(move || stream_deck.try_recv()));
+        let web_requests = web.iter().flat_map(|web| std::iter::from_fn(move || web.try_recv()));
+        for request in requests.chain(deck_requests).chain(web_requests) {
+            let result = run_chain_command(request.command.clone(), &mut chains, &mut clock, &mut shader_time, &mut history, &mut ab, &presets, &running, &devices, obs.as_ref(), &config);
+            if let Err(e) = &result {
+                tracing::warn!("Control command '{}' failed: {}", request.command, e);
+            }
+            request.reply(result);
+        }
+
+        if let (Some(mqtt), Some(primary)) = (&mut mqtt, chains.first()) {
+            let privacy_rule = mqtt.settings().privacy_rule.as_deref();
+            mqtt.publish(&MqttState {
+                live: primary.is_live(),
+                fps: primary.stats().stage(Stage::Output).fps,
+                recording: primary.is_recording(),
+                preset: primary.preset().map(String::from),
+                privacy: privacy_rule.and_then(|rule| primary.redaction_rules().iter().find(|r| r.name == rule)).map(|rule| rule.enabled),
+            });
+        }
+        if let Some(sync) = sync.as_mut().filter(|sync| !sync.is_follower()) {
+            sync.publish(&chain_session(&mut chains, &clock, &shader_time));
+        }
+        if let (Some(stream_deck), Some(primary)) = (&mut stream_deck, chains.first()) {
+            stream_deck.publish(&StreamDeckState {
+                live: primary.is_live(),
+                recording: primary.is_recording(),
+                preset: primary.preset().map(String::from),
+                redaction: primary.redaction_rules().iter().map(|rule| (rule.name.clone(), rule.enabled)).collect(),
+            });
+        }
+        if let (Some(web), Some(primary)) = (&mut web, chains.first()) {
+            web.publish(&web_state(primary.is_live(), primary.is_recording(), primary.preset(), &presets, primary.param_names(), primary.params()));
+            if let Some(frame) = primary.last_output().filter(|_| web.wants_preview(Instant::now())) {
+                web.publish_preview(frame);
+            }
+        }
+
+        // FPS counter
+        if let Some(fps) = fps_counter.update() {
+            info!("Render loop: {:.2} FPS", fps);
+        }
+
+        // Process each chain with the latest camera frame
+        let time = start_time.elapsed().as_secs_f32();
+        let now = Instant::now();
+        clock.update(now);
+        let clock_state = clock.state(now);
+        let wall_time = wall_clock.now();
+        let shader_seconds = shader_time.time(now) as f32;
+        let hidden = scheduler.update(now);
+        let audio_levels = audio_meter.as_ref().map(AudioMeter::levels).unwrap_or_default();
+        for chain in &mut chains {
+            chain.set_clock(clock_state);
+            chain.set_wall_time(wall_time);
+            chain.set_audio_levels(audio_levels);
+            chain.set_hidden(hidden);
+            chain.tick(time, shader_seconds);
+        }
+
+        if let Some(params) = ab.update(Instant::now()) {
+            chains.iter_mut().for_each(|chain| chain.set_params(params.clone()));
+        }
+
+        // Parameters are the same in all chains
+        if let Some(store) = &mut param_store {
+            store.update(chains[0].params(), &default_params);
         }
+
+        // Sleep until an output needs the next frame; recording without any camera frame
+        // never takes one, so wait at least a millisecond, and idle chains wait long, so
+        // wake up often enough to answer commands
+        let next_frame = chains.iter().map(Chain::next_frame).min().unwrap_or_else(Instant::now);
+        thread::sleep(next_frame.saturating_duration_since(Instant::now()).clamp(Duration::from_millis(1), Duration::from_millis(100)));
     }
 
+    for chain in &mut chains {
+        chain.finish();
+    }
+    if let Some(store) = &mut param_store {
+        store.save(chains[0].params(), &default_params);
+    }
     info!("Virtual camera stream stopped");
     Ok(())
 }
+
+/// Finds a monitor by index or (case-insensitive) part of its name.
+fn find_monitor(event_loop: &ActiveEventLoop, id: &str) -> Option<MonitorHandle> {
+    let mut monitors = event_loop.available_monitors();
+    match id.parse::<usize>() {
+        Ok(index) => monitors.nth(index),
+        Err(_) => {
+            let id = id.to_lowercase();
+            monitors.find(|monitor| monitor.name().is_some_and(|name| name.to_lowercase().contains(&id)))
+        }
+    }
+}
+
+/// Creates the beat clock, joining an Ableton Link session if configured.
+fn new_clock(config: &Config) -> BeatClock {
+    let mut clock = BeatClock::new(config.bpm);
+    if config.link {
+        if let Err(e) = clock.enable_link() {
+            tracing::warn!("Ableton Link unavailable: {}", e);
+        }
+    }
+    clock
+}
+
+/// What the web UI shows: the tally, presets and the declared shader parameters.
+fn web_state(live: bool, recording: bool, preset: Option<&str>, presets: &HashMap<String, HashMap<String, f32>>, param_names: Vec<String>, params: &HashMap<String, f32>) -> WebState {
+    let mut preset_names: Vec<String> = presets.keys().cloned().collect();
+    preset_names.sort();
+    WebState {
+        live,
+        recording,
+        preset: preset.map(String::from),
+        presets: preset_names,
+        params: param_names.into_iter().map(|name| {
+            let value = params.get(&name).copied().unwrap_or(0.0);
+            (name, value)
+        }).collect(),
+    }
+}
+
+/// The runtime state of the primary chain, as saved in sessions and sent to sync followers.
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+fn chain_session(chains: &mut [Chain], clock: &BeatClock, shader_time: &ShaderTime) -> Session {
+    let Some(primary) = chains.first_mut() else { return Session::default() };
+    Session {
+        preset: primary.preset().map(str::to_string),
+        params: primary.params().iter().map(|(name, value)| (name.clone(), *value)).collect(),
+        layout: primary.compositor().map(|compositor| compositor.layout.clone().unwrap_or_else(|| DEFAULT_LAYOUT.to_string())),
+        redaction: primary.redaction_rules().iter().map(|rule| (rule.name.clone(), rule.enabled)).collect(),
+        zoom: primary.zoom_active(),
+        source: primary.mixer_mut().map(|mixer| mixer.active() + 1),
+        live: primary.is_live(),
+        bpm: Some(clock.bpm()),
+        time: Some(SessionTime::of(shader_time, Instant::now())),
+    }
+}
+
+/// Applies the parts of a session that are set to the chains and returns the
+/// reasons for the parts that were skipped. A loaded session replaces the
+/// parameters; with `merge` (a sync update) parameters not in it keep their values.
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+fn restore_chain_session(chains: &mut [Chain], clock: &mut BeatClock, shader_time: &mut ShaderTime, session: Session, merge: bool) -> Vec<String> {
+    let now = Instant::now();
+    if merge {
+        for chain in chains.iter_mut() {
+            let mut params = chain.params().clone();
+            params.extend(session.params.iter().map(|(name, value)| (name.clone(), *value)));
+            chain.set_params(params);
+        }
+    } else if !session.params.is_empty() {
+        let params: HashMap<String, f32> = session.params.iter().map(|(name, value)| (name.clone(), *value)).collect();
+        chains.iter_mut().for_each(|chain| chain.set_params(params.clone()));
+    }
+    // Chains without the layout, rule or zoom are left alone
+    let mut skipped = Vec::new();
+    let mut apply = |restore: &dyn Fn(&mut Chain) -> Result<()>| {
+        let errors: Vec<_> = chains.iter_mut().map(restore).filter_map(Result::err).collect();
+        if errors.len() == chains.len() {
+            skipped.extend(errors.first().map(|e| e.to_string()));
+        }
+    };
+    if let Some(layout) = &session.layout {
+        apply(&|chain| chain.switch_compositor_layout(layout));
+    }
+    for (rule, enabled) in &session.redaction {
+        apply(&|chain| chain.set_redaction_rule(rule, Some(*enabled)).map(|_| ()));
+    }
+    if let Some(zoom) = session.zoom {
+        apply(&|chain| chain.set_zoom_active(Some(zoom)).map(|_| ()));
+    }
+    for chain in chains.iter_mut() {
+        if let Some(preset) = &session.preset {
+            chain.set_preset(preset);
+        }
+        chain.set_live(session.live);
+    }
+    if let (Some(source), Some(mixer)) = (session.source, chains.first_mut().and_then(|chain| chain.mixer_mut())) {
+        mixer.cut(source.saturating_sub(1));
+    }
+    if let Some(bpm) = session.bpm.filter(|bpm| *bpm > 0.0) {
+        clock.set_bpm(bpm, now);
+    }
+    if let Some(time) = session.time {
+        time.restore(shader_time, now);
+    }
+    skipped
+}
+
+/// Runs a control command against the virtual camera chains. Parameters and tally
+/// apply to all chains; snapshots, recording, and source switching to the primary chain.
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+#[allow(clippy::too_many_arguments)]
+fn run_chain_command(
+    command: ControlCommand,
+    chains: &mut [Chain],
+    clock: &mut BeatClock,
+    shader_time: &mut ShaderTime,
+    history: &mut ParamHistory,
+    ab: &mut AbCompare,
+    presets: &HashMap<String, HashMap<String, f32>>,
+    running: &std::sync::atomic::AtomicBool,
+    devices: &DeviceMonitor,
+    obs: Option<&ObsClient>,
+    config: &Config,
+) -> Result<String> {
+    let primary = chains.first_mut().ok_or_else(|| anyhow::anyhow!("No chains running"))?;
+    match command {
+        ControlCommand::SetUniform { name, value } => {
+            history.record(primary.params(), &name, Instant::now());
+            chains.iter_mut().for_each(|chain| chain.set_param(&name, value));
+        }
+        ControlCommand::SetBuffer { name, values } => {
+            // Chains without the buffer are left alone
+            let mut set = false;
+            let mut error = None;
+            for chain in chains.iter_mut() {
+                match chain.set_data_values(&name, values.clone()) {
+                    Ok(()) => set = true,
+                    Err(e) => error = Some(e),
+                }
+            }
+            if let (false, Some(e)) = (set, error) {
+                return Err(e);
+            }
+        }
+        ControlCommand::SwitchPreset(name) => {
+            let preset = presets.get(&name).ok_or_else(|| anyhow::anyhow!("Unknown preset '{}'", name))?;
+            history.record(primary.params(), &name, Instant::now());
+            for chain in chains.iter_mut() {
+                for (param, value) in preset {
+                    chain.set_param(param, *value);
+                }
+                chain.set_preset(&name);
+            }
+            info!("Switched to preset '{}'", name);
+        }
+        ControlCommand::Undo => {
+            let params = history.undo(primary.params()).ok_or_else(|| anyhow::anyhow!("Nothing to undo"))?;
+            chains.iter_mut().for_each(|chain| chain.set_params(params.clone()));
+            info!("Undid parameter change");
+        }
+        ControlCommand::Redo => {
+            let params = history.redo(primary.params()).ok_or_else(|| anyhow::anyhow!("Nothing to redo"))?;
+            chains.iter_mut().for_each(|chain| chain.set_params(params.clone()));
+            info!("Redid parameter change");
+        }
+        ControlCommand::Ab(command) => {
+            let now = Instant::now();
+            if let Some(params) = ab.handle(command, primary.params(), now)? {
+                history.record(primary.params(), "ab", now);
+                chains.iter_mut().for_each(|chain| chain.set_params(params.clone()));
+            }
+        }
+        ControlCommand::Snapshot(path) => {
+            let path = path.unwrap_or_else(|| timestamped_path("snapshot", "png"));
+            primary.snapshot(&path)?;
+            return Ok(path.display().to_string());
+        }
+        ControlCommand::RecordStart(path) => {
+            let path = path.unwrap_or_else(|| timestamped_path("recording", primary.record_settings().container.extension()));
+            primary.start_recording(&path)?;
+            return Ok(path.display().to_string());
+        }
+        ControlCommand::RecordStop => {
+            return Ok(primary.stop_recording()?.display().to_string());
+        }
+        ControlCommand::RecordToggle if primary.is_recording() => {
+            return Ok(primary.stop_recording()?.display().to_string());
+        }
+        ControlCommand::RecordToggle => {
+            let path = timestamped_path("recording", primary.record_settings().container.extension());
+            primary.start_recording(&path)?;
+            return Ok(path.display().to_string());
+        }
+        ControlCommand::Clip { seconds, path } => {
+            let path = path.unwrap_or_else(|| timestamped_path("clip", "gif"));
+            primary.start_clip(&path, seconds.unwrap_or(DEFAULT_CLIP_SECONDS))?;
+            return Ok(path.display().to_string());
+        }
+        ControlCommand::SetLive(live) => {
+            chains.iter_mut().for_each(|chain| chain.set_live(live));
+            info!("Tally: {}", if live { "LIVE" } else { "PREVIEW" });
+        }
+        ControlCommand::SetBpm(bpm) => {
+            clock.set_bpm(bpm, Instant::now());
+            return Ok(format!("{:.1} bpm", clock.bpm()));
+        }
+        ControlCommand::Tap => {
+            return Ok(format!("{:.1} bpm", clock.tap(Instant::now())));
+        }
+        ControlCommand::Time(command) => {
+            shader_time.handle(command, Instant::now());
+            return Ok(shader_time.to_string());
+        }
+        ControlCommand::SwitchSource(index) => {
+            let mixer = primary.mixer_mut().ok_or_else(|| anyhow::anyhow!("No mixer configured"))?;
+            anyhow::ensure!(index <= mixer.len(), "No source {} (mixer has {})", index, mixer.len());
+            mixer.switch_to(index - 1);
+        }
+        ControlCommand::SwitchInput(name) => {
+            primary.switch_input(&name, config)?;
+        }
+        ControlCommand::Devices => {
+            return Ok(describe_devices(&devices.devices(), primary.input()));
+        }
+        ControlCommand::SwitchLayout(name) => {
+            // Chains without a compositor (or without the layout) keep theirs
+            let mut switched = false;
+            let mut error = None;
+            for chain in chains.iter_mut() {
+                match chain.switch_compositor_layout(&name) {
+                    Ok(()) => switched = true,
+                    Err(e) => error = Some(e),
+                }
+            }
+            if let (false, Some(e)) = (switched, error) {
+                return Err(e);
+            }
+            info!("Switched to layout '{}'", name);
+        }
+        ControlCommand::Zoom(state) => {
+            // Chains without a zoom are left alone
+            let mut active = None;
+            let mut error = None;
+            for chain in chains.iter_mut() {
+                match chain.set_zoom_active(state) {
+                    Ok(now) => active = Some(now),
+                    Err(e) => error = Some(e),
+                }
+            }
+            match (active, error) {
+                (Some(active), _) => return Ok(if active { "on" } else { "off" }.to_string()),
+                (None, Some(e)) => return Err(e),
+                (None, None) => {}
+            }
+        }
+        ControlCommand::ZoomAt { x, y } => {
+            for chain in chains.iter_mut() {
+                chain.set_zoom_target(x, y);
+            }
+        }
+        ControlCommand::Cursor(position) => {
+            for chain in chains.iter_mut() {
+                chain.set_cursor(position);
+            }
+        }
+        ControlCommand::Click => {
+            for chain in chains.iter_mut() {
+                chain.cursor_click();
+            }
+        }
+        ControlCommand::Caption(text) => {
+            // Chains without captions are left alone
+            let mut shown = false;
+            let mut error = None;
+            for chain in chains.iter_mut() {
+                match chain.set_caption(text.clone()) {
+                    Ok(()) => shown = true,
+                    Err(e) => error = Some(e),
+                }
+            }
+            if let (false, Some(e)) = (shown, error) {
+                return Err(e);
+            }
+        }
+        ControlCommand::Redact { rule, enabled } => {
+            // Chains without the rule are left alone
+            let mut state = None;
+            let mut error = None;
+            for chain in chains.iter_mut() {
+                match chain.set_redaction_rule(&rule, enabled) {
+                    Ok(now) => state = Some(now),
+                    Err(e) => error = Some(e),
+                }
+            }
+            match (state, error) {
+                (Some(enabled), _) => return Ok(if enabled { "on" } else { "off" }.to_string()),
+                (None, Some(e)) => return Err(e),
+                (None, None) => {}
+            }
+        }
+        ControlCommand::Obs(command) => {
+            obs.ok_or_else(|| anyhow::anyhow!("OBS is not configured (`obs`)"))?.send(command)?;
+        }
+        ControlCommand::Event { name, payload } => {
+            // Scripts of all chains share the event bus
+            events::post(Event { name, payload });
+        }
+        ControlCommand::Stats => {
+            if let [chain] = chains {
+                return Ok(chain.stats().to_string());
+            }
+            let stats: Vec<String> = chains.iter().map(|chain| format!("[{}] {}", chain.name(), chain.stats())).collect();
+            return Ok(stats.join("; "));
+        }
+        ControlCommand::Reload => {
+            reload::reload_all();
+        }
+        ControlCommand::ReloadStatus => {
+            return Ok(describe_reload_status());
+        }
+        ControlCommand::SaveSession(path) => {
+            let path = path.unwrap_or_else(|| timestamped_path("session", "yaml"));
+            chain_session(chains, clock, shader_time).save(&path)?;
+            info!("Saved session to {:?}", path);
+            return Ok(path.display().to_string());
+        }
+        ControlCommand::LoadSession(path) => {
+            let session = Session::load(&path)?;
+            if !session.params.is_empty() {
+                history.record(primary.params(), "session", Instant::now());
+            }
+            let skipped = restore_chain_session(chains, clock, shader_time, session, false);
+            for reason in &skipped {
+                warn!("Session {:?}: {}", path, reason);
+            }
+            info!("Restored session from {:?}", path);
+            return Ok(skipped.join("; "));
+        }
+        ControlCommand::Quit => {
+            info!("Quit requested via control socket");
+            running.store(false, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+    Ok(String::new())
+}
@@ -0,0 +1,307 @@
+//! Crossbar mixer for switching and blending between input sources.
+//!
+//! The mixer sits in front of the shader chain and selects which source
+//! (camera, video file, image, Lua scene, or test pattern) feeds the pipeline. Switching can
+//! be an instant cut or a timed crossfade.
+
+use crate::capture::AsyncCapture;
+use crate::frame::{PixelFormat, Rotation, VideoFrame};
+use crate::lua_canvas::LuaCanvas;
+use crate::shader::SourceTransform;
+use crate::video::VideoPlayer;
+use std::borrow::Cow;
+use tracing::info;
+
+/// An input source that can be selected by the mixer.
+pub enum MixerSource {
+    /// Live camera capture
+    Camera(AsyncCapture),
+    /// Looping video file or stream
+    Video(VideoPlayer),
+    /// Still image
+    Image(VideoFrame),
+    /// Lua canvas script rendered on the CPU
+    Lua(Box<LuaCanvas>),
+    /// Generated color bars
+    TestPattern,
+}
+
+/// Timestamp and size of a source frame, which tell new frames apart.
+type FrameKey = (Option<u64>, u32, u32);
+
+/// A source together with its most recent converted frame.
+struct SourceState {
+    name: String,
+    source: MixerSource,
+    /// Cached frame for sources that don't own a `VideoFrame` (video, Lua, test pattern)
+    cache: Option<VideoFrame>,
+    /// Timestamp of the cached video frame, to avoid re-copying unchanged frames
+    cache_timestamp: f32,
+    /// Crop and transform applied to the source's frames
+    transform: SourceTransform,
+    /// The last transformed frame, with the timestamp and size of the frame it came from
+    transformed: Option<(FrameKey, VideoFrame)>,
+}
+
+impl SourceState {
+    fn frame(&mut self, time: f32, width: u32, height: u32) -> Option<&VideoFrame> {
+        if self.transform.is_identity() {
+            return self.source_frame(time, width, height);
+        }
+        let transform = self.transform;
+        let mut transformed = self.transformed.take();
+        let frame = self.source_frame(time, width, height)?;
+        // Only transform new frames
+        let key = (frame.timestamp_us, frame.width, frame.height);
+        if transformed.as_ref().is_none_or(|(transformed_key, _)| *transformed_key != key) {
+            transformed = Some((key, transform.apply(frame)));
+        }
+        self.transformed = transformed;
+        self.transformed.as_ref().map(|(_, frame)| frame)
+    }
+
+    fn source_frame(&mut self, time: f32, width: u32, height: u32) -> Option<&VideoFrame> {
+        match &mut self.source {
+            MixerSource::Camera(capture) => capture.get_latest_frame(),
+            MixerSource::Video(player) => {
+                if let Some(decoded) = player.get_frame(time) {
+                    if self.cache.is_none() || decoded.timestamp != self.cache_timestamp {
+                        let mut frame = VideoFrame::from_data(
+                            decoded.width,
+                            decoded.height,
+                            PixelFormat::Rgba,
+                            decoded.data.clone(),
+                        );
+                        frame.timestamp_us = Some((decoded.timestamp * 1_000_000.0) as u64);
+                        self.cache = Some(frame);
+                        self.cache_timestamp = decoded.timestamp;
+                    }
+                }
+                self.cache.as_ref()
+            }
+            MixerSource::Image(frame) => Some(frame),
+            MixerSource::Lua(canvas) => {
+                if let Some(rendered) = canvas.get_frame(time) {
+                    let mut frame = VideoFrame::from_data(rendered.width, rendered.height, PixelFormat::Rgba, rendered.data);
+                    frame.timestamp_us = Some((time * 1_000_000.0) as u64);
+                    self.cache = Some(frame);
+                }
+                self.cache.as_ref()
+            }
+            MixerSource::TestPattern => {
+                Some(self.cache.get_or_insert_with(|| color_bars(width, height)))
+            }
+        }
+    }
+}
+
+/// An in-progress crossfade.
+struct Transition {
+    from: usize,
+    start: Option<f32>,
+}
+
+/// Selects between multiple sources, with optional crossfade transitions.
+pub struct Mixer {
+    sources: Vec<SourceState>,
+    active: usize,
+    transition: Option<Transition>,
+    /// Crossfade duration in seconds (0 = cut)
+    transition_duration: f32,
+    width: u32,
+    height: u32,
+    /// Output buffer used while blending
+    blend: VideoFrame,
+}
+
+impl Mixer {
+    /// Creates a mixer over named sources with their crop and transform. `width`/`height`
+    /// size the test pattern and blend output.
+    pub fn new(sources: Vec<(String, MixerSource, SourceTransform)>, width: u32, height: u32, transition_duration: f32) -> Self {
+        let sources = sources
+            .into_iter()
+            .map(|(name, source, transform)| SourceState {
+                name,
+                source,
+                cache: None,
+                cache_timestamp: 0.0,
+                transform,
+                transformed: None,
+            })
+            .collect();
+
+        Self {
+            sources,
+            active: 0,
+            transition: None,
+            transition_duration: transition_duration.max(0.0),
+            width,
+            height,
+            blend: VideoFrame::new(width, height, PixelFormat::Rgba),
+        }
+    }
+
+    /// Number of configured sources.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns true if no sources are configured.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Index of the currently selected source.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Whether the picture is a still image or test pattern (and no crossfade is running).
+    pub fn is_static(&self) -> bool {
+        self.transition.is_none()
+            && self.sources.get(self.active).is_some_and(|state| matches!(state.source, MixerSource::Image(_) | MixerSource::TestPattern))
+    }
+
+    /// Name of the source at `index`.
+    pub fn source_name(&self, index: usize) -> Option<&str> {
+        self.sources.get(index).map(|s| s.name.as_str())
+    }
+
+    /// Switches to a source immediately.
+    pub fn cut(&mut self, index: usize) {
+        if index >= self.sources.len() {
+            return;
+        }
+        info!("Mixer: cut to source {} ({})", index, self.sources[index].name);
+        self.active = index;
+        self.transition = None;
+    }
+
+    /// Switches to a source using the configured crossfade duration.
+    pub fn switch_to(&mut self, index: usize) {
+        if index >= self.sources.len() || index == self.active {
+            return;
+        }
+        if self.transition_duration <= 0.0 {
+            self.cut(index);
+            return;
+        }
+        info!("Mixer: crossfade to source {} ({})", index, self.sources[index].name);
+        self.transition = Some(Transition { from: self.active, start: None });
+        self.active = index;
+    }
+
+    /// Returns the mixed frame for the given time, or None if the active source has no frame yet.
+    pub fn next_frame(&mut self, time: f32) -> Option<&VideoFrame> {
+        let (width, height) = (self.width, self.height);
+
+        if let Some(transition) = &mut self.transition {
+            let start = *transition.start.get_or_insert(time);
+            let t = ((time - start) / self.transition_duration).clamp(0.0, 1.0);
+            if t >= 1.0 {
+                self.transition = None;
+            } else {
+                let from = transition.from;
+                let to = self.active;
+                // Borrow both sources mutably at once
+                let (a, b) = if from < to {
+                    let (lo, hi) = self.sources.split_at_mut(to);
+                    (&mut lo[from], &mut hi[0])
+                } else {
+                    let (lo, hi) = self.sources.split_at_mut(from);
+                    (&mut hi[0], &mut lo[to])
+                };
+                // If one side has no frame yet, fall through to the active source
+                if let (Some(fa), Some(fb)) = (a.frame(time, width, height), b.frame(time, width, height)) {
+                    crossfade(&upright_rgba(fa), &upright_rgba(fb), t, &mut self.blend);
+                    return Some(&self.blend);
+                }
+            }
+        }
+
+        self.sources.get_mut(self.active)?.frame(time, width, height)
+    }
+}
+
+/// An upright RGBA view of a frame, copied only if it needs converting or rotating.
+fn upright_rgba(frame: &VideoFrame) -> Cow<'_, VideoFrame> {
+    if frame.format == PixelFormat::Rgba && frame.rotation == Rotation::None {
+        Cow::Borrowed(frame)
+    } else {
+        Cow::Owned(frame.to_rgba().upright())
+    }
+}
+
+/// Blends two RGBA frames into `out` (which defines the output size), sampling nearest-neighbor.
+fn crossfade(a: &VideoFrame, b: &VideoFrame, t: f32, out: &mut VideoFrame) {
+    let weight_b = (t * 256.0) as u32;
+    let weight_a = 256 - weight_b;
+    let (w, h) = (out.width as usize, out.height as usize);
+
+    // Byte offsets of the sampled columns, so the inner loop doesn't divide
+    let columns = |frame: &VideoFrame| -> Vec<usize> { (0..w).map(|x| x * frame.width as usize / w * 4).collect() };
+    let (columns_a, columns_b) = (columns(a), columns(b));
+    let row_start = |frame: &VideoFrame, y: usize| -> usize { y * frame.height as usize / h * frame.width as usize * 4 };
+
+    for (y, out_row) in out.data.chunks_exact_mut(w * 4).enumerate().take(h) {
+        let (row_a, row_b) = (&a.data[row_start(a, y)..], &b.data[row_start(b, y)..]);
+        for ((pixel, &ia), &ib) in out_row.chunks_exact_mut(4).zip(&columns_a).zip(&columns_b) {
+            for c in 0..4 {
+                let va = row_a[ia + c] as u32;
+                let vb = row_b[ib + c] as u32;
+                pixel[c] = ((va * weight_a + vb * weight_b) >> 8) as u8;
+            }
+        }
+    }
+}
+
+/// Generates a simple SMPTE-style color bar test pattern.
+pub fn color_bars(width: u32, height: u32) -> VideoFrame {
+    const BARS: [[u8; 3]; 7] = [
+        [192, 192, 192],
+        [192, 192, 0],
+        [0, 192, 192],
+        [0, 192, 0],
+        [192, 0, 192],
+        [192, 0, 0],
+        [0, 0, 192],
+    ];
+
+    let mut frame = VideoFrame::new(width, height, PixelFormat::Rgba);
+    let bar_height = height as usize * 3 / 4;
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let color = if y < bar_height {
+                BARS[x * BARS.len() / width as usize]
+            } else {
+                // Bottom strip: black to white ramp
+                let v = (x * 255 / width.max(1) as usize) as u8;
+                [v, v, v]
+            };
+            let i = (y * width as usize + x) * 4;
+            frame.data[i..i + 3].copy_from_slice(&color);
+            frame.data[i + 3] = 255;
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossfade_midpoint() {
+        let a = VideoFrame::from_data(1, 1, PixelFormat::Rgba, vec![0, 0, 0, 255]);
+        let b = VideoFrame::from_data(2, 2, PixelFormat::Rgba, vec![200; 16]);
+        let mut out = VideoFrame::new(1, 1, PixelFormat::Rgba);
+        crossfade(&a, &b, 0.5, &mut out);
+        assert_eq!(out.data, vec![100, 100, 100, 227]);
+
+        // Scaled up nearest-neighbor
+        let a = VideoFrame::from_data(2, 1, PixelFormat::Rgba, vec![0, 0, 0, 0, 255, 255, 255, 255]);
+        let mut out = VideoFrame::new(4, 2, PixelFormat::Rgba);
+        crossfade(&a, &a, 0.0, &mut out);
+        assert_eq!(out.data.chunks(4).map(|pixel| pixel[0]).collect::<Vec<_>>(), [0, 0, 255, 255, 0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Per-person instance IDs derived from the segmentation mask.
+//!
+//! The selfie model only separates people from the background, so people are
+//! told apart by the connected regions of its mask. People who touch or
+//! overlap in the frame share one instance.
+
+use super::Detection;
+
+/// Maximum number of labeled people; smaller extra regions are dropped.
+pub const MAX_INSTANCES: usize = 8;
+
+/// Mask value from which a pixel counts as part of a person
+const THRESHOLD: u8 = 128;
+/// Regions smaller than this fraction of the mask are treated as noise
+const MIN_AREA: f32 = 0.005;
+/// Soft mask edges (above this value) receive the ID of the adjacent person
+const EDGE_THRESHOLD: u8 = 8;
+/// Number of pixels the IDs are grown into the soft edges
+const EDGE_GROWTH: usize = 3;
+
+/// Labels the people in `mask` (one byte per pixel) with IDs 1 to
+/// `MAX_INSTANCES`, numbered left to right; the background is 0.
+pub fn label_instances(mask: &[u8], width: usize, height: usize) -> Vec<u8> {
+    // Flood-fill the confident foreground into 4-connected regions
+    let mut regions = vec![u32::MAX; width * height];
+    // (area, sum of x coordinates) per region
+    let mut stats: Vec<(usize, usize)> = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..mask.len() {
+        if mask[start] < THRESHOLD || regions[start] != u32::MAX {
+            continue;
+        }
+        let region = stats.len() as u32;
+        let (mut area, mut sum_x) = (0, 0);
+        regions[start] = region;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            area += 1;
+            sum_x += i % width;
+            for n in neighbors(i, width, height) {
+                if mask[n] >= THRESHOLD && regions[n] == u32::MAX {
+                    regions[n] = region;
+                    stack.push(n);
+                }
+            }
+        }
+        stats.push((area, sum_x));
+    }
+
+    // Keep the largest regions and number them by their horizontal center
+    let min_area = (MIN_AREA * (width * height) as f32) as usize;
+    let mut kept: Vec<usize> = (0..stats.len()).filter(|&r| stats[r].0 >= min_area.max(1)).collect();
+    kept.sort_by_key(|&r| std::cmp::Reverse(stats[r].0));
+    kept.truncate(MAX_INSTANCES);
+    kept.sort_by(|&a, &b| {
+        let center = |r: usize| stats[r].1 as f32 / stats[r].0 as f32;
+        center(a).total_cmp(&center(b))
+    });
+    let mut ids = vec![0u8; stats.len()];
+    for (index, &region) in kept.iter().enumerate() {
+        ids[region] = index as u8 + 1;
+    }
+
+    let mut labels: Vec<u8> = regions.iter().map(|&r| if r == u32::MAX { 0 } else { ids[r as usize] }).collect();
+
+    // Grow the IDs into the soft edges, so they cover the whole visible person
+    for _ in 0..EDGE_GROWTH {
+        let previous = labels.clone();
+        for i in 0..labels.len() {
+            if previous[i] != 0 || mask[i] < EDGE_THRESHOLD {
+                continue;
+            }
+            if let Some(id) = neighbors(i, width, height).map(|n| previous[n]).find(|&id| id != 0) {
+                labels[i] = id;
+            }
+        }
+    }
+    labels
+}
+
+/// Bounding boxes of the labeled people (see [`label_instances`]), in order of
+/// their IDs, as detections of class 0 with full confidence.
+pub fn person_boxes(labels: &[u8], width: usize, height: usize) -> Vec<Detection> {
+    // (min x, min y, max x, max y) per ID
+    let mut bounds = [(usize::MAX, usize::MAX, 0, 0); MAX_INSTANCES];
+    for (i, &id) in labels.iter().enumerate() {
+        if id == 0 || id as usize > MAX_INSTANCES {
+            continue;
+        }
+        let (x, y) = (i % width, i / width);
+        let b = &mut bounds[id as usize - 1];
+        *b = (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y));
+    }
+    bounds
+        .iter()
+        .filter(|b| b.0 != usize::MAX)
+        .map(|&(x0, y0, x1, y1)| Detection {
+            class: 0,
+            score: 1.0,
+            x: x0 as f32 / width as f32,
+            y: y0 as f32 / height as f32,
+            width: (x1 + 1 - x0) as f32 / width as f32,
+            height: (y1 + 1 - y0) as f32 / height as f32,
+        })
+        .collect()
+}
+
+/// Indices of the 4-connected neighbors of pixel `i`.
+fn neighbors(i: usize, width: usize, height: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (i % width, i / width);
+    [
+        (x > 0).then(|| i - 1),
+        (x + 1 < width).then(|| i + 1),
+        (y > 0).then(|| i - width),
+        (y + 1 < height).then(|| i + width),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_instances() {
+        // Two people (right one larger), a noise pixel, and a soft edge
+        let (width, height) = (40, 20);
+        let mut mask = vec![0u8; width * height];
+        for y in 2..8 {
+            for x in 2..5 {
+                mask[y * width + x] = 255;
+            }
+            for x in 10..16 {
+                mask[y * width + x] = 255;
+            }
+        }
+        mask[15 * width + 30] = 255;
+        mask[5 * width + 5] = 64;
+
+        let labels = label_instances(&mask, width, height);
+        assert_eq!(labels[3 * width + 3], 1);
+        assert_eq!(labels[3 * width + 12], 2);
+        assert_eq!(labels[5 * width + 5], 1);
+        assert_eq!(labels[15 * width + 30], 0);
+        assert_eq!(labels[0], 0);
+
+        let boxes = person_boxes(&labels, width, height);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!((boxes[1].x, boxes[1].y), (10.0 / 40.0, 2.0 / 20.0));
+        assert_eq!((boxes[1].width, boxes[1].height), (6.0 / 40.0, 6.0 / 20.0));
+    }
+}
@@ -1,13 +1,27 @@
 use anyhow::{anyhow, Result};
 use image::{imageops::FilterType, GrayImage, ImageBuffer, Rgba, RgbImage, Rgb};
+use ort::ep::ExecutionProviderDispatch;
 use ort::session::{builder::GraphOptimizationLevel, Session};
 use ort::value::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Once, OnceLock};
 use std::thread;
-use tracing::{info, warn, debug};
+use tracing::{debug, error, info, warn};
 
 use crate::frame::VideoFrame;
 
+mod instances;
+mod objects;
+mod pose;
+mod text;
+
+pub use instances::{person_boxes, MAX_INSTANCES};
+pub use objects::{AsyncObjectDetector, Detection, ObjectDetector, ObjectModel};
+pub use pose::{AsyncPoseEngine, Keypoint, PoseEngine, MAX_KEYPOINTS};
+pub use text::{AsyncTextDetector, TextBox, TextDetector};
+
 // Landscape input resolution (256x144) - optimized for 16:9 webcam feeds
 // Note: Width x Height in image terms, model uses NCHW format [1, 3, 144, 256]
 const MODEL_WIDTH: u32 = 256;
@@ -16,82 +30,173 @@ const MODEL_HEIGHT: u32 = 144;
 // Embed the ONNX model directly into the binary
 const SELFIE_MODEL_BYTES: &[u8] = include_bytes!("../../models/mediapipe_selfie.onnx");
 
+/// ONNX Runtime execution providers the segmentation model can run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    /// NVIDIA TensorRT (`tensorrt` feature)
+    TensorRt,
+    /// NVIDIA CUDA (`cuda` feature, Linux)
+    Cuda,
+    /// AMD ROCm (`rocm` feature, Linux)
+    Rocm,
+    /// Intel OpenVINO for iGPUs, NPUs and CPUs (`openvino` feature)
+    OpenVino,
+    /// DirectML (Windows)
+    DirectMl,
+    /// CoreML (macOS)
+    CoreMl,
+    /// The default CPU provider
+    Cpu,
+}
+
+/// Execution provider selection, in order of preference.
+///
+/// Providers that aren't compiled into this build are skipped, and providers
+/// that fail to register (missing drivers or libraries) fall through to the
+/// next one. The CPU is always the last resort.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionProviders {
+    pub order: Vec<ExecutionProvider>,
+    /// OpenVINO device (`GPU`, `CPU`, `NPU`, `GPU.1`, ...), OpenVINO's default if unset
+    pub openvino_device: Option<String>,
+    /// Directory for cached TensorRT engines, a temporary directory if unset
+    pub tensorrt_cache: Option<PathBuf>,
+}
+
+impl Default for ExecutionProviders {
+    fn default() -> Self {
+        let order = if cfg!(target_os = "macos") {
+            vec![ExecutionProvider::CoreMl]
+        } else if cfg!(target_os = "windows") {
+            vec![ExecutionProvider::TensorRt, ExecutionProvider::OpenVino, ExecutionProvider::DirectMl]
+        } else {
+            vec![
+                ExecutionProvider::TensorRt,
+                ExecutionProvider::Cuda,
+                ExecutionProvider::Rocm,
+                ExecutionProvider::OpenVino,
+            ]
+        };
+        Self { order, openvino_device: None, tensorrt_cache: None }
+    }
+}
+
+impl ExecutionProviders {
+    /// The providers of `order` that are available in this build.
+    fn dispatch(&self) -> Vec<ExecutionProviderDispatch> {
+        let mut providers = Vec::new();
+        for provider in &self.order {
+            match provider {
+                #[cfg(feature = "tensorrt")]
+                ExecutionProvider::TensorRt => {
+                    // Building TensorRT engines takes a while, so cache them between runs
+                    let cache = self.tensorrt_cache.clone().unwrap_or_else(|| std::env::temp_dir().join("proteus-tensorrt"));
+                    providers.push(
+                        ort::ep::TensorRT::default()
+                            .with_fp16(true)
+                            .with_engine_cache(true)
+                            .with_engine_cache_path(cache.to_string_lossy())
+                            .build(),
+                    );
+                }
+                #[cfg(all(target_os = "linux", feature = "cuda"))]
+                ExecutionProvider::Cuda => providers.push(ort::ep::CUDA::default().build()),
+                #[cfg(all(target_os = "linux", feature = "rocm"))]
+                ExecutionProvider::Rocm => providers.push(ort::ep::ROCm::default().build()),
+                #[cfg(feature = "openvino")]
+                ExecutionProvider::OpenVino => {
+                    let mut openvino = ort::ep::OpenVINO::default();
+                    if let Some(device) = &self.openvino_device {
+                        openvino = openvino.with_device_type(device);
+                    }
+                    providers.push(openvino.build());
+                }
+                #[cfg(target_os = "windows")]
+                ExecutionProvider::DirectMl => providers.push(ort::ep::DirectML::default().build()),
+                #[cfg(target_os = "macos")]
+                ExecutionProvider::CoreMl => providers.push(ort::ep::CoreML::default().build()),
+                ExecutionProvider::Cpu => providers.push(ort::ep::CPU::default().build()),
+                #[allow(unreachable_patterns)]
+                other => {
+                    debug!("{:?} Execution Provider is not available in this build, skipping", other);
+                    continue;
+                }
+            }
+            info!("{:?} Execution Provider registered", provider);
+        }
+        providers
+    }
+}
+
+/// Result of one segmentation inference, at the frame's resolution.
+pub struct Segmentation {
+    /// Combined person mask (0 = background, 255 = person)
+    pub mask: Vec<u8>,
+    /// Person IDs (0 = background, 1 to `MAX_INSTANCES` left to right), if requested
+    pub instances: Option<Vec<u8>>,
+    pub width: u32,
+    pub height: u32,
+}
+
 pub struct SegmentationEngine {
     session: Session,
+    instances: bool,
 }
 
-impl SegmentationEngine {
-    /// Initialize the ONNX Runtime environment.
-    /// This should be called once at startup.
-    pub fn init() -> Result<()> {
+/// Execution providers for the ONNX Runtime environment, set by [`SegmentationEngine::init`].
+static EXECUTION_PROVIDERS: OnceLock<ExecutionProviders> = OnceLock::new();
+static RUNTIME_INIT: Once = Once::new();
+
+/// Creates the ONNX Runtime environment on first use, so that runs without
+/// any model never load the runtime or touch the execution providers.
+fn ensure_runtime() {
+    RUNTIME_INIT.call_once(|| {
+        let providers = EXECUTION_PROVIDERS.get_or_init(ExecutionProviders::default);
+        info!("Initializing ONNX Runtime");
         ort::init()
             .with_name("proteus")
+            .with_execution_providers(providers.dispatch())
             .commit();
-        Ok(())
+    });
+}
+
+impl SegmentationEngine {
+    /// Set the execution providers used by all sessions. The ONNX Runtime
+    /// environment itself is created when the first model is loaded.
+    /// This should be called once at startup.
+    pub fn init(providers: &ExecutionProviders) -> Result<()> {
+        EXECUTION_PROVIDERS
+            .set(providers.clone())
+            .map_err(|_| anyhow!("ONNX Runtime execution providers are already set"))
     }
 
     /// Load the embedded segmentation model.
-    pub fn new() -> Result<Option<Self>> {
+    /// With `instances`, each result also labels the individual people.
+    pub fn new(instances: bool) -> Result<Option<Self>> {
         info!("Loading embedded segmentation model");
-        
+        ensure_runtime();
+
         let mut session_builder = Session::builder()?;
         session_builder = session_builder.with_optimization_level(GraphOptimizationLevel::Level3)?;
         session_builder = session_builder.with_intra_threads(4)?;
-        
-        // --- Mac Optimization: CoreML ---
-        #[cfg(target_os = "macos")]
-        {
-            use ort::ep::CoreMLExecutionProvider;
-            session_builder = session_builder.with_execution_providers([
-                CoreMLExecutionProvider::default().build()
-            ])?;
-            info!("CoreML Execution Provider enabled");
-        }
-
-        // --- Windows Optimization: DirectML (GPU) ---
-        #[cfg(target_os = "windows")]
-        {
-            use ort::ep::DirectMLExecutionProvider;
-            session_builder = session_builder.with_execution_providers([
-                DirectMLExecutionProvider::default().build()
-            ])?;
-            info!("DirectML Execution Provider enabled (GPU acceleration)");
-        }
 
-        // --- Linux Optimization: CUDA / ROCm (GPU) ---
-        #[cfg(target_os = "linux")]
-        {
-            #[allow(unused_mut)]
-            let mut providers = Vec::new();
-
-            #[cfg(feature = "cuda")]
-            {
-                use ort::ep::CUDAExecutionProvider;
-                let p = CUDAExecutionProvider::default().build();
-                providers.push(p);
-                info!("CUDA Execution Provider registered");
-            }
-
-            #[cfg(feature = "rocm")]
-            {
-                use ort::ep::ROCmExecutionProvider;
-                let p = ROCmExecutionProvider::default().build();
-                providers.push(p);
-                info!("ROCm Execution Provider registered");
-            }
-            
-            if !providers.is_empty() {
-                 session_builder = session_builder.with_execution_providers(providers)?;
-            }
-        }
-    
+        // Execution providers come from the environment (see `init`); ones that
+        // fail to register are skipped in order, down to the CPU.
         let session = session_builder.commit_from_memory(SELFIE_MODEL_BYTES)?;
 
-        Ok(Some(Self { session }))
+        Ok(Some(Self { session, instances }))
+    }
+
+    /// Runs one inference on a blank frame, so that lazy initialization in the
+    /// runtime and execution provider doesn't delay the first real frame.
+    pub fn warm_up(&mut self) -> Result<()> {
+        let blank = VideoFrame::new(MODEL_WIDTH, MODEL_HEIGHT, crate::frame::PixelFormat::Rgba);
+        self.predict(&blank)?;
+        Ok(())
     }
 
     /// Run inference on a video frame and return the alpha mask at original resolution
-    pub fn predict(&mut self, frame: &VideoFrame) -> Result<(Vec<u8>, u32, u32)> {
+    pub fn predict(&mut self, frame: &VideoFrame) -> Result<Segmentation> {
         let orig_w = frame.width;
         let orig_h = frame.height;
         
@@ -158,6 +263,10 @@ impl SegmentationEngine {
             .map(|&v| (v.clamp(0.0, 1.0) * 255.0) as u8)  // Already person mask
             .collect();
         
+        // Label the people at model resolution (IDs must not be interpolated)
+        let instance_bytes = self.instances
+            .then(|| instances::label_instances(&mask_bytes, MODEL_WIDTH as usize, MODEL_HEIGHT as usize));
+
         let mask_img = GrayImage::from_raw(MODEL_WIDTH, MODEL_HEIGHT, mask_bytes)
             .ok_or_else(|| anyhow!("Failed to create mask image"))?;
         
@@ -172,29 +281,90 @@ impl SegmentationEngine {
         
         // Resize back to original frame resolution (use Gaussian for smooth alpha mask)
         let final_mask = image::imageops::resize(&cropped, orig_w, orig_h, FilterType::Gaussian);
+
+        let final_instances = match instance_bytes {
+            Some(bytes) => {
+                let instance_img = GrayImage::from_raw(MODEL_WIDTH, MODEL_HEIGHT, bytes)
+                    .ok_or_else(|| anyhow!("Failed to create instance image"))?;
+                let cropped = image::imageops::crop_imm(&instance_img, offset_x, offset_y, scaled_w, scaled_h).to_image();
+                Some(image::imageops::resize(&cropped, orig_w, orig_h, FilterType::Nearest).into_raw())
+            }
+            None => None,
+        };
         
-        Ok((final_mask.into_raw(), orig_w, orig_h))
+        Ok(Segmentation { mask: final_mask.into_raw(), instances: final_instances, width: orig_w, height: orig_h })
+    }
+}
+
+/// Load state of a model on a worker thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelState {
+    Loading,
+    Ready,
+    /// The model could not be loaded; the worker has exited
+    Failed,
+}
+
+/// A [`ModelState`] shared between a worker thread and its wrapper.
+#[derive(Debug, Clone, Default)]
+pub struct SharedModelState(Arc<AtomicU8>);
+
+impl SharedModelState {
+    pub fn get(&self) -> ModelState {
+        match self.0.load(Ordering::Acquire) {
+            0 => ModelState::Loading,
+            1 => ModelState::Ready,
+            _ => ModelState::Failed,
+        }
+    }
+
+    pub fn set(&self, state: ModelState) {
+        self.0.store(state as u8, Ordering::Release);
+    }
+}
+
+/// Loads a model on its worker thread. A failure is logged and recorded in
+/// `state`, so the pipeline can tell it from a model that is still loading.
+fn load_on_worker<T>(state: &SharedModelState, name: &str, load: impl FnOnce() -> Result<T>) -> Option<T> {
+    match load() {
+        Ok(model) => Some(model),
+        Err(e) => {
+            error!("Failed to load {}: {}", name, e);
+            state.set(ModelState::Failed);
+            None
+        }
     }
 }
 
 /// A background-threaded wrapper for the segmentation engine.
+/// The session is created and warmed up on the worker thread, so startup and
+/// pipeline reloads don't stall; frames are only accepted once it is ready.
 pub struct AsyncSegmentationEngine {
     frame_tx: mpsc::SyncSender<VideoFrame>,
-    mask_rx: Receiver<(Vec<u8>, u32, u32)>,
+    mask_rx: Receiver<Segmentation>,
+    state: SharedModelState,
+    instances: bool,
 }
 
 impl AsyncSegmentationEngine {
-    pub fn new() -> Result<Option<Self>> {
-        let mut engine_opt = SegmentationEngine::new()?;
-        let Some(mut engine) = engine_opt.take() else {
-            return Ok(None);
-        };
-
+    /// With `instances`, results also label the individual people.
+    pub fn new(instances: bool) -> Result<Option<Self>> {
         // Use a bounded channel of size 1 to implement "drop-if-busy"
         let (frame_tx, frame_rx) = mpsc::sync_channel::<VideoFrame>(1);
-        let (mask_tx, mask_rx) = mpsc::channel::<(Vec<u8>, u32, u32)>();
+        let (mask_tx, mask_rx) = mpsc::channel::<Segmentation>();
+        let state = SharedModelState::default();
+        let worker_state = state.clone();
 
         thread::spawn(move || {
+            let start = std::time::Instant::now();
+            let load = || SegmentationEngine::new(instances)?.ok_or_else(|| anyhow!("No model available"));
+            let Some(mut engine) = load_on_worker(&worker_state, "segmentation model", load) else { return };
+            if let Err(e) = engine.warm_up() {
+                warn!("Segmentation warm-up failed: {}", e);
+            }
+            worker_state.set(ModelState::Ready);
+            info!("Segmentation model ready after {:?}", start.elapsed());
+
             info!("ML Worker Thread started (Zero-Backpressure mode)");
             while let Ok(frame) = frame_rx.recv() {
                 let start = std::time::Instant::now();
@@ -214,24 +384,35 @@ impl AsyncSegmentationEngine {
             info!("ML Worker Thread exiting");
         });
 
-        Ok(Some(Self { frame_tx, mask_rx }))
+        Ok(Some(Self { frame_tx, mask_rx, state, instances }))
+    }
+
+    /// Whether results label the individual people.
+    pub fn instances(&self) -> bool {
+        self.instances
+    }
+
+    /// Whether the model is still loading, ready or failed to load.
+    pub fn state(&self) -> ModelState {
+        self.state.get()
     }
 
-    /// Try to send a frame for processing. Returns true if sent, false if busy.
+    /// Returns true once the model is loaded and warmed up.
+    pub fn is_ready(&self) -> bool {
+        self.state() == ModelState::Ready
+    }
+
+    /// Try to send a frame for processing. Returns true if sent, false if busy
+    /// or not ready yet.
     pub fn try_predict(&self, frame: VideoFrame) -> bool {
-        match self.frame_tx.try_send(frame) {
-            Ok(_) => true,
-            Err(e) => {
-                if let mpsc::TrySendError::Full(_) = e {
-                    debug!("ML Worker busy, dropping frame to maintain real-time sync");
-                }
-                false
-            }
+        if !self.is_ready() {
+            return false;
         }
+        self.frame_tx.try_send(frame).is_ok()
     }
 
     /// Get the latest available result from the background thread.
-    pub fn poll_result(&self) -> Option<(Vec<u8>, u32, u32)> {
+    pub fn poll_result(&self) -> Option<Segmentation> {
         let mut latest = None;
         // Drain the channel to get the MOST RECENT result
         while let Ok(result) = self.mask_rx.try_recv() {
@@ -240,3 +421,22 @@ impl AsyncSegmentationEngine {
         latest
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_on_worker() {
+        let state = SharedModelState::default();
+        assert_eq!(state.get(), ModelState::Loading);
+        // The worker marks the model ready itself, after warming it up
+        assert_eq!(load_on_worker(&state, "model", || Ok(1)), Some(1));
+        assert_eq!(state.get(), ModelState::Loading);
+
+        assert_eq!(load_on_worker::<u32>(&state, "model", || Err(anyhow!("No such file"))), None);
+        assert_eq!(state.get(), ModelState::Failed);
+        state.set(ModelState::Ready);
+        assert_eq!(state.get(), ModelState::Ready);
+    }
+}
@@ -0,0 +1,258 @@
+//! Object detection with a user-supplied ONNX model.
+//!
+//! Supports YOLOv8/YOLO11-style detection exports, which output one
+//! `[cx, cy, w, h, class scores...]` column per candidate box (`[1, 4 + C, N]`,
+//! or transposed `[1, N, 4 + C]`) in input pixels. Models trained on logos or
+//! faces (e.g. the YOLOv8-face exports, whose keypoints follow the class
+//! score and are skipped with `class_count: 1`) work the same way.
+
+use anyhow::{anyhow, bail, Result};
+use image::{imageops::FilterType, ImageBuffer, Rgba};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Value;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tracing::{debug, info, warn};
+
+use super::{load_on_worker, ModelState, SharedModelState};
+use crate::frame::VideoFrame;
+
+/// Input size used when the model's input size is dynamic
+const DEFAULT_INPUT_SIZE: u32 = 640;
+/// Letterbox padding value the YOLO exports are trained with
+const PADDING: f32 = 114.0 / 255.0;
+/// Overlapping boxes of the same class above this IoU are merged
+const NMS_IOU: f32 = 0.45;
+
+/// A detected object in texture coordinates of the frame
+/// (0.0 - 1.0, top-left origin).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub class: usize,
+    /// Confidence (0.0 - 1.0)
+    pub score: f32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Detection {
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    /// Intersection over union of two boxes.
+    fn iou(&self, other: &Detection) -> f32 {
+        let width = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let height = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        let intersection = width.max(0.0) * height.max(0.0);
+        let union = self.area() + other.area() - intersection;
+        if union <= 0.0 { 0.0 } else { intersection / union }
+    }
+}
+
+/// Detection model settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectModel {
+    pub path: PathBuf,
+    /// Score (0.0 - 1.0) from which a box counts as detected
+    pub threshold: f32,
+    /// Number of class scores per box (all values after the box if `None`)
+    pub class_count: Option<usize>,
+}
+
+pub struct ObjectDetector {
+    session: Session,
+    input_name: String,
+    input_width: u32,
+    input_height: u32,
+    threshold: f32,
+    class_count: Option<usize>,
+}
+
+impl ObjectDetector {
+    /// Load a detection model from an ONNX file.
+    pub fn new(model: &ObjectModel) -> Result<Self> {
+        info!("Loading detection model {:?}", model.path);
+        super::ensure_runtime();
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(2)?
+            .commit_from_file(&model.path)?;
+
+        let input = session.inputs().first().ok_or_else(|| anyhow!("Detection model has no inputs"))?;
+        let shape = input.dtype().tensor_shape().ok_or_else(|| anyhow!("Detection model input is not a tensor"))?;
+        if shape.len() != 4 {
+            bail!("Expected a 4D image input, got shape {:?}", &shape[..]);
+        }
+        let size = |dim: i64| if dim > 0 { dim as u32 } else { DEFAULT_INPUT_SIZE };
+        let (input_width, input_height) = (size(shape[3]), size(shape[2]));
+        let input_name = input.name().to_string();
+
+        info!("Detection model: {}x{} input", input_width, input_height);
+        Ok(Self {
+            session,
+            input_name,
+            input_width,
+            input_height,
+            threshold: model.threshold,
+            class_count: model.class_count,
+        })
+    }
+
+    /// Run detection on a video frame.
+    pub fn predict(&mut self, frame: &VideoFrame) -> Result<Vec<Detection>> {
+        let rgba_img = ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(frame.width, frame.height, &frame.data)
+            .ok_or_else(|| anyhow!("Failed to create image buffer"))?;
+
+        // Letterbox into the model input, keeping the aspect ratio
+        let (in_w, in_h) = (self.input_width, self.input_height);
+        let scale = (in_w as f32 / frame.width as f32).min(in_h as f32 / frame.height as f32);
+        let scaled_w = ((frame.width as f32 * scale).round() as u32).clamp(1, in_w);
+        let scaled_h = ((frame.height as f32 * scale).round() as u32).clamp(1, in_h);
+        let offset_x = (in_w - scaled_w) / 2;
+        let offset_y = (in_h - scaled_h) / 2;
+        let resized = image::imageops::resize(&rgba_img, scaled_w, scaled_h, FilterType::Triangle);
+
+        let plane_size = (in_w * in_h) as usize;
+        let mut input_data = vec![PADDING; 3 * plane_size];
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            let i = ((y + offset_y) * in_w + x + offset_x) as usize;
+            for c in 0..3 {
+                input_data[c * plane_size + i] = pixel[c] as f32 / 255.0;
+            }
+        }
+
+        let input_value = Value::from_array(([1, 3, in_h as i64, in_w as i64], input_data))?;
+        let outputs = self.session.run(ort::inputs![self.input_name.as_str() => input_value])?;
+        let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
+        let (rows, columns) = match shape[..] {
+            [_, rows, columns] if (rows * columns) as usize == data.len() => (rows as usize, columns as usize),
+            _ => return Err(anyhow!("Unexpected detection output shape {:?}", &shape[..])),
+        };
+        // There are far more candidates than values per candidate
+        let (values, candidates, transposed) = if rows <= columns { (rows, columns, false) } else { (columns, rows, true) };
+        if values < 5 {
+            bail!("Detection output has no class scores (shape {:?})", &shape[..]);
+        }
+        let value = |candidate: usize, index: usize| if transposed { data[candidate * values + index] } else { data[index * candidates + candidate] };
+
+        // Back from the letterboxed input to the frame
+        let to_frame = |x: f32, y: f32| {
+            (((x - offset_x as f32) / scale) / frame.width as f32, ((y - offset_y as f32) / scale) / frame.height as f32)
+        };
+        let class_count = self.class_count.unwrap_or(values - 4).clamp(1, values - 4);
+        let mut detections = Vec::new();
+        for candidate in 0..candidates {
+            let (class, score) = (0..class_count)
+                .map(|class| (class, value(candidate, 4 + class)))
+                .fold((0, f32::MIN), |best, entry| if entry.1 > best.1 { entry } else { best });
+            if score < self.threshold {
+                continue;
+            }
+            let (cx, cy, w, h) = (value(candidate, 0), value(candidate, 1), value(candidate, 2), value(candidate, 3));
+            let (x0, y0) = to_frame(cx - w / 2.0, cy - h / 2.0);
+            let (x1, y1) = to_frame(cx + w / 2.0, cy + h / 2.0);
+            detections.push(Detection { class, score, x: x0, y: y0, width: x1 - x0, height: y1 - y0 });
+        }
+        Ok(non_max_suppression(detections))
+    }
+}
+
+/// Keeps the best of each group of overlapping boxes of the same class.
+fn non_max_suppression(mut detections: Vec<Detection>) -> Vec<Detection> {
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut kept: Vec<Detection> = Vec::new();
+    for detection in detections {
+        if !kept.iter().any(|other| other.class == detection.class && other.iou(&detection) > NMS_IOU) {
+            kept.push(detection);
+        }
+    }
+    kept
+}
+
+/// A background-threaded wrapper for the object detector, like
+/// [`AsyncTextDetector`](super::AsyncTextDetector).
+pub struct AsyncObjectDetector {
+    model: ObjectModel,
+    frame_tx: mpsc::SyncSender<VideoFrame>,
+    detections_rx: Receiver<Vec<Detection>>,
+    state: SharedModelState,
+}
+
+impl AsyncObjectDetector {
+    pub fn new(model: ObjectModel) -> Self {
+        let (frame_tx, frame_rx) = mpsc::sync_channel::<VideoFrame>(1);
+        let (detections_tx, detections_rx) = mpsc::channel();
+        let state = SharedModelState::default();
+        let worker_state = state.clone();
+        let settings = model.clone();
+
+        thread::spawn(move || {
+            let name = format!("detection model {:?}", settings.path);
+            let Some(mut detector) = load_on_worker(&worker_state, &name, || ObjectDetector::new(&settings)) else { return };
+            worker_state.set(ModelState::Ready);
+            info!("Detection model {:?} ready", settings.path);
+
+            while let Ok(frame) = frame_rx.recv() {
+                let start = std::time::Instant::now();
+                match detector.predict(&frame) {
+                    Ok(detections) => {
+                        debug!("Detection Worker Inference: {} object(s) in {:?}", detections.len(), start.elapsed());
+                        if detections_tx.send(detections).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Detection Worker error: {}", e),
+                }
+            }
+            info!("Detection Worker Thread exiting");
+        });
+
+        Self { model, frame_tx, detections_rx, state }
+    }
+
+    /// The settings this detector was created with.
+    pub fn model(&self) -> &ObjectModel {
+        &self.model
+    }
+
+    /// Whether the model is still loading, ready or failed to load.
+    pub fn state(&self) -> ModelState {
+        self.state.get()
+    }
+
+    /// Returns true once the model is loaded.
+    pub fn is_ready(&self) -> bool {
+        self.state() == ModelState::Ready
+    }
+
+    /// Try to send a frame for processing. Returns false if busy or not ready yet.
+    pub fn try_predict(&self, frame: VideoFrame) -> bool {
+        self.is_ready() && self.frame_tx.try_send(frame).is_ok()
+    }
+
+    /// Get the latest available detections from the background thread.
+    pub fn poll_result(&self) -> Option<Vec<Detection>> {
+        self.detections_rx.try_iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_max_suppression() {
+        let detection = |class, score, x| Detection { class, score, x, y: 0.0, width: 0.2, height: 0.2 };
+        let kept = non_max_suppression(vec![
+            detection(0, 0.6, 0.01),
+            detection(0, 0.9, 0.0),
+            detection(1, 0.7, 0.0),
+            detection(0, 0.5, 0.5),
+        ]);
+        assert_eq!(kept, vec![detection(0, 0.9, 0.0), detection(1, 0.7, 0.0), detection(0, 0.5, 0.5)]);
+    }
+}
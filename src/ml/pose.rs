@@ -0,0 +1,236 @@
+//! Body pose estimation with a user-supplied ONNX model.
+//!
+//! Supports single-person MoveNet exports (17 keypoints, `[1, 1, 17, 3]` as
+//! y, x, score) and BlazePose landmark exports (33 keypoints, 5 values each
+//! as x, y, z, visibility, presence in input pixels). The format is detected
+//! from the model's output size. Either way the first 17 keypoints follow the
+//! COCO order, so effects work with both.
+
+use anyhow::{anyhow, bail, Result};
+use image::{imageops::FilterType, ImageBuffer, Rgba};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::tensor::TensorElementType;
+use ort::value::Value;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tracing::{debug, info, warn};
+
+use super::{load_on_worker, ModelState, SharedModelState};
+use crate::frame::VideoFrame;
+
+/// Number of keypoints passed to shaders and scripts (BlazePose has the most).
+pub const MAX_KEYPOINTS: usize = 33;
+
+/// Input size used when the model's input size is dynamic
+const DEFAULT_INPUT_SIZE: u32 = 192;
+
+/// BlazePose landmark of each output keypoint: the 17 COCO keypoints, then
+/// the remaining BlazePose landmarks in their own order
+const BLAZEPOSE_ORDER: [usize; MAX_KEYPOINTS] = [
+    0, 2, 5, 7, 8, 11, 12, 13, 14, 15, 16, 23, 24, 25, 26, 27, 28,
+    1, 3, 4, 6, 9, 10, 17, 18, 19, 20, 21, 22, 29, 30, 31, 32,
+];
+
+/// A body keypoint. `x` and `y` are in texture coordinates of the frame
+/// (0.0 - 1.0, top-left origin); `score` is the confidence (0.0 - 1.0).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    pub score: f32,
+    pub _padding: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PoseFormat {
+    MoveNet,
+    BlazePose,
+}
+
+pub struct PoseEngine {
+    session: Session,
+    format: PoseFormat,
+    input_name: String,
+    /// Channels-last input (`[1, H, W, 3]`)
+    nhwc: bool,
+    /// Integer pixel input (MoveNet's TF Hub export)
+    int_input: bool,
+    input_width: u32,
+    input_height: u32,
+}
+
+impl PoseEngine {
+    /// Load a pose model from an ONNX file.
+    pub fn new(path: &Path) -> Result<Self> {
+        info!("Loading pose model {:?}", path);
+        super::ensure_runtime();
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(2)?
+            .commit_from_file(path)?;
+
+        let input = session.inputs().first().ok_or_else(|| anyhow!("Pose model has no inputs"))?;
+        let shape = input.dtype().tensor_shape().ok_or_else(|| anyhow!("Pose model input is not a tensor"))?;
+        if shape.len() != 4 {
+            bail!("Expected a 4D image input, got shape {:?}", &shape[..]);
+        }
+        let nhwc = shape[3] == 3;
+        let (height, width) = if nhwc { (shape[1], shape[2]) } else { (shape[2], shape[3]) };
+        let size = |dim: i64| if dim > 0 { dim as u32 } else { DEFAULT_INPUT_SIZE };
+        let int_input = matches!(input.dtype().tensor_type(), Some(TensorElementType::Int32));
+        let input_name = input.name().to_string();
+
+        let output = session.outputs().first().ok_or_else(|| anyhow!("Pose model has no outputs"))?;
+        let output_len: i64 = output.dtype().tensor_shape().map_or(0, |shape| shape.iter().map(|&d| d.max(1)).product());
+        let format = if output_len >= (MAX_KEYPOINTS * 5) as i64 { PoseFormat::BlazePose } else { PoseFormat::MoveNet };
+
+        info!("Pose model: {:?} format, {}x{} input", format, size(width), size(height));
+        Ok(Self {
+            session,
+            format,
+            input_name,
+            nhwc,
+            int_input,
+            input_width: size(width),
+            input_height: size(height),
+        })
+    }
+
+    /// Run inference on a video frame and return the keypoints (unused entries have a score of 0).
+    pub fn predict(&mut self, frame: &VideoFrame) -> Result<[Keypoint; MAX_KEYPOINTS]> {
+        let rgba_img = ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(frame.width, frame.height, &frame.data)
+            .ok_or_else(|| anyhow!("Failed to create image buffer"))?;
+
+        // Letterbox into the model input, keeping the aspect ratio
+        let (in_w, in_h) = (self.input_width, self.input_height);
+        let scale = (in_w as f32 / frame.width as f32).min(in_h as f32 / frame.height as f32);
+        let scaled_w = ((frame.width as f32 * scale).round() as u32).clamp(1, in_w);
+        let scaled_h = ((frame.height as f32 * scale).round() as u32).clamp(1, in_h);
+        let offset_x = (in_w - scaled_w) / 2;
+        let offset_y = (in_h - scaled_h) / 2;
+        let resized = image::imageops::resize(&rgba_img, scaled_w, scaled_h, FilterType::Triangle);
+
+        // MoveNet expects 0-255 pixel values, BlazePose 0.0 - 1.0
+        let normalize = if self.format == PoseFormat::BlazePose { 1.0 / 255.0 } else { 1.0 };
+        let plane_size = (in_w * in_h) as usize;
+        let mut input_data = vec![0.0f32; 3 * plane_size];
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            let i = ((y + offset_y) * in_w + x + offset_x) as usize;
+            for c in 0..3 {
+                let index = if self.nhwc { i * 3 + c } else { c * plane_size + i };
+                input_data[index] = pixel[c] as f32 * normalize;
+            }
+        }
+
+        let shape = if self.nhwc {
+            [1, in_h as i64, in_w as i64, 3]
+        } else {
+            [1, 3, in_h as i64, in_w as i64]
+        };
+        let input_value = if self.int_input {
+            let data: Vec<i32> = input_data.iter().map(|&v| v as i32).collect();
+            Value::from_array((shape, data))?.into_dyn()
+        } else {
+            Value::from_array((shape, input_data))?.into_dyn()
+        };
+        let outputs = self.session.run(ort::inputs![self.input_name.as_str() => &input_value])?;
+        let (_, data) = outputs[0].try_extract_tensor::<f32>()?;
+
+        // Model input coordinates (0.0 - 1.0) back to frame texture coordinates
+        let to_frame = |x: f32, y: f32| {
+            (
+                (x * in_w as f32 - offset_x as f32) / scaled_w as f32,
+                (y * in_h as f32 - offset_y as f32) / scaled_h as f32,
+            )
+        };
+
+        let mut keypoints = [Keypoint::default(); MAX_KEYPOINTS];
+        match self.format {
+            PoseFormat::MoveNet => {
+                for (keypoint, values) in keypoints.iter_mut().zip(data.chunks_exact(3).take(17)) {
+                    let (x, y) = to_frame(values[1], values[0]);
+                    *keypoint = Keypoint { x, y, score: values[2].clamp(0.0, 1.0), _padding: 0.0 };
+                }
+            }
+            PoseFormat::BlazePose => {
+                let sigmoid = |v: f32| 1.0 / (1.0 + (-v).exp());
+                let landmarks: Vec<&[f32]> = data.chunks_exact(5).collect();
+                for (keypoint, &landmark) in keypoints.iter_mut().zip(&BLAZEPOSE_ORDER) {
+                    let Some(values) = landmarks.get(landmark) else { continue };
+                    let (x, y) = to_frame(values[0] / in_w as f32, values[1] / in_h as f32);
+                    *keypoint = Keypoint { x, y, score: sigmoid(values[3]), _padding: 0.0 };
+                }
+            }
+        }
+        Ok(keypoints)
+    }
+}
+
+/// A background-threaded wrapper for the pose engine, like
+/// [`AsyncSegmentationEngine`](super::AsyncSegmentationEngine).
+pub struct AsyncPoseEngine {
+    path: PathBuf,
+    frame_tx: mpsc::SyncSender<VideoFrame>,
+    pose_rx: Receiver<[Keypoint; MAX_KEYPOINTS]>,
+    state: SharedModelState,
+}
+
+impl AsyncPoseEngine {
+    pub fn new(path: PathBuf) -> Self {
+        let (frame_tx, frame_rx) = mpsc::sync_channel::<VideoFrame>(1);
+        let (pose_tx, pose_rx) = mpsc::channel();
+        let state = SharedModelState::default();
+        let worker_state = state.clone();
+        let model_path = path.clone();
+
+        thread::spawn(move || {
+            let name = format!("pose model {:?}", model_path);
+            let Some(mut engine) = load_on_worker(&worker_state, &name, || PoseEngine::new(&model_path)) else { return };
+            worker_state.set(ModelState::Ready);
+            info!("Pose model ready");
+
+            while let Ok(frame) = frame_rx.recv() {
+                let start = std::time::Instant::now();
+                match engine.predict(&frame) {
+                    Ok(keypoints) => {
+                        debug!("Pose Worker Inference: {:?}", start.elapsed());
+                        if pose_tx.send(keypoints).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Pose Worker error: {}", e),
+                }
+            }
+            info!("Pose Worker Thread exiting");
+        });
+
+        Self { path, frame_tx, pose_rx, state }
+    }
+
+    /// The model file this engine was created from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the model is still loading, ready or failed to load.
+    pub fn state(&self) -> ModelState {
+        self.state.get()
+    }
+
+    /// Returns true once the model is loaded.
+    pub fn is_ready(&self) -> bool {
+        self.state() == ModelState::Ready
+    }
+
+    /// Try to send a frame for processing. Returns false if busy or not ready yet.
+    pub fn try_predict(&self, frame: VideoFrame) -> bool {
+        self.is_ready() && self.frame_tx.try_send(frame).is_ok()
+    }
+
+    /// Get the latest available keypoints from the background thread.
+    pub fn poll_result(&self) -> Option<[Keypoint; MAX_KEYPOINTS]> {
+        self.pose_rx.try_iter().last()
+    }
+}
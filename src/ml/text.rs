@@ -0,0 +1,232 @@
+//! On-screen text detection with a user-supplied ONNX model.
+//!
+//! Supports DB-style detection models (e.g. the PaddleOCR detection exports)
+//! that output a per-pixel text probability map (`[1, 1, H, W]`). Only text
+//! regions are located; nothing is recognized or read.
+
+use anyhow::{anyhow, Result};
+use image::{imageops::FilterType, ImageBuffer, Rgba};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Value;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use tracing::{debug, info, warn};
+
+use super::{load_on_worker, ModelState, SharedModelState};
+use crate::frame::VideoFrame;
+
+/// Longer side of the detection input (rounded to a multiple of 32)
+const INPUT_SIZE: u32 = 960;
+/// ImageNet normalization used by the PaddleOCR exports
+const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const STD: [f32; 3] = [0.229, 0.224, 0.225];
+/// Regions with fewer probability map pixels are ignored
+const MIN_AREA: usize = 12;
+/// DB models predict shrunk text kernels; boxes are grown by this fraction of their height
+const UNCLIP: f32 = 0.5;
+
+/// A detected text region in texture coordinates of the frame
+/// (0.0 - 1.0, top-left origin).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+pub struct TextDetector {
+    session: Session,
+    input_name: String,
+    /// Probability from which a pixel counts as text
+    threshold: f32,
+}
+
+impl TextDetector {
+    /// Load a text detection model from an ONNX file.
+    pub fn new(path: &Path, threshold: f32) -> Result<Self> {
+        info!("Loading text detection model {:?}", path);
+        super::ensure_runtime();
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(2)?
+            .commit_from_file(path)?;
+        let input_name = session
+            .inputs()
+            .first()
+            .ok_or_else(|| anyhow!("Text detection model has no inputs"))?
+            .name()
+            .to_string();
+        Ok(Self { session, input_name, threshold })
+    }
+
+    /// Run detection on a video frame.
+    pub fn predict(&mut self, frame: &VideoFrame) -> Result<Vec<TextBox>> {
+        let rgba_img = ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(frame.width, frame.height, &frame.data)
+            .ok_or_else(|| anyhow!("Failed to create image buffer"))?;
+
+        // Keep the aspect ratio; DB models need sizes divisible by 32
+        let scale = INPUT_SIZE as f32 / frame.width.max(frame.height) as f32;
+        let round = |v: u32| (((v as f32 * scale / 32.0).round() as u32).max(1)) * 32;
+        let (in_w, in_h) = (round(frame.width), round(frame.height));
+        let resized = image::imageops::resize(&rgba_img, in_w, in_h, FilterType::Triangle);
+
+        let plane_size = (in_w * in_h) as usize;
+        let mut input_data = vec![0.0f32; 3 * plane_size];
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            let i = (y * in_w + x) as usize;
+            for c in 0..3 {
+                input_data[c * plane_size + i] = (pixel[c] as f32 / 255.0 - MEAN[c]) / STD[c];
+            }
+        }
+
+        let input_value = Value::from_array(([1, 3, in_h as i64, in_w as i64], input_data))?;
+        let outputs = self.session.run(ort::inputs![self.input_name.as_str() => input_value])?;
+        let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
+        let (map_w, map_h) = match shape[..] {
+            [.., h, w] if (h * w) as usize == data.len() => (w as usize, h as usize),
+            _ => return Err(anyhow!("Unexpected text detection output shape {:?}", &shape[..])),
+        };
+
+        let text: Vec<bool> = data.iter().map(|&p| p > self.threshold).collect();
+        Ok(text_boxes(&text, map_w, map_h))
+    }
+}
+
+/// Bounding boxes of the 8-connected text regions of a probability map,
+/// grown back to the full text size.
+fn text_boxes(text: &[bool], width: usize, height: usize) -> Vec<TextBox> {
+    let mut visited = vec![false; text.len()];
+    let mut stack = Vec::new();
+    let mut boxes = Vec::new();
+    for start in 0..text.len() {
+        if !text[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+        let mut area = 0;
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % width, i / width);
+            (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+            area += 1;
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let n = ny * width + nx;
+                    if text[n] && !visited[n] {
+                        visited[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        if area < MIN_AREA {
+            continue;
+        }
+
+        let grow = (max_y - min_y + 1) as f32 * UNCLIP;
+        let (x0, y0) = (min_x as f32 - grow, min_y as f32 - grow);
+        let (x1, y1) = ((max_x + 1) as f32 + grow, (max_y + 1) as f32 + grow);
+        boxes.push(TextBox {
+            x: x0 / width as f32,
+            y: y0 / height as f32,
+            width: (x1 - x0) / width as f32,
+            height: (y1 - y0) / height as f32,
+        });
+    }
+    boxes
+}
+
+/// A background-threaded wrapper for the text detector, like
+/// [`AsyncPoseEngine`](super::AsyncPoseEngine).
+pub struct AsyncTextDetector {
+    path: PathBuf,
+    frame_tx: mpsc::SyncSender<VideoFrame>,
+    boxes_rx: Receiver<Vec<TextBox>>,
+    state: SharedModelState,
+}
+
+impl AsyncTextDetector {
+    pub fn new(path: PathBuf, threshold: f32) -> Self {
+        let (frame_tx, frame_rx) = mpsc::sync_channel::<VideoFrame>(1);
+        let (boxes_tx, boxes_rx) = mpsc::channel();
+        let state = SharedModelState::default();
+        let worker_state = state.clone();
+        let model_path = path.clone();
+
+        thread::spawn(move || {
+            let name = format!("text detection model {:?}", model_path);
+            let Some(mut detector) = load_on_worker(&worker_state, &name, || TextDetector::new(&model_path, threshold)) else { return };
+            worker_state.set(ModelState::Ready);
+            info!("Text detection model ready");
+
+            while let Ok(frame) = frame_rx.recv() {
+                let start = std::time::Instant::now();
+                match detector.predict(&frame) {
+                    Ok(boxes) => {
+                        debug!("Text Worker Inference: {} region(s) in {:?}", boxes.len(), start.elapsed());
+                        if boxes_tx.send(boxes).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Text Worker error: {}", e),
+                }
+            }
+            info!("Text Worker Thread exiting");
+        });
+
+        Self { path, frame_tx, boxes_rx, state }
+    }
+
+    /// The model file this detector was created from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the model is still loading, ready or failed to load.
+    pub fn state(&self) -> ModelState {
+        self.state.get()
+    }
+
+    /// Returns true once the model is loaded.
+    pub fn is_ready(&self) -> bool {
+        self.state() == ModelState::Ready
+    }
+
+    /// Try to send a frame for processing. Returns false if busy or not ready yet.
+    pub fn try_predict(&self, frame: VideoFrame) -> bool {
+        self.is_ready() && self.frame_tx.try_send(frame).is_ok()
+    }
+
+    /// Get the latest available text regions from the background thread.
+    pub fn poll_result(&self) -> Option<Vec<TextBox>> {
+        self.boxes_rx.try_iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_boxes() {
+        // One 8x2 line of text and a speck of noise
+        let (width, height) = (20, 10);
+        let mut text = vec![false; width * height];
+        for y in 4..6 {
+            for x in 2..10 {
+                text[y * width + x] = true;
+            }
+        }
+        text[9 * width + 19] = true;
+
+        let boxes = text_boxes(&text, width, height);
+        assert_eq!(boxes.len(), 1);
+        let b = boxes[0];
+        // Grown by half the kernel height (1 pixel) on every side
+        assert!((b.x - 0.05).abs() < 1e-6 && (b.y - 0.3).abs() < 1e-6);
+        assert!((b.width - 0.5).abs() < 1e-6 && (b.height - 0.4).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,316 @@
+//! Output health monitoring.
+//!
+//! Detects when the output becomes all-black or stops changing for a while
+//! (camera died, capture or pipeline stalled) and reacts with a configurable
+//! action. The output is checked rather than the input, so a stalled pipeline
+//! counts too; still image sources are exempt from the frozen check.
+
+use crate::frame::{PixelFormat, VideoFrame};
+use crate::mixer::color_bars;
+use tracing::{info, warn};
+
+/// Number of sample points per axis used to fingerprint a frame.
+const SAMPLE_GRID: (usize, usize) = (32, 18);
+/// Seconds between output samples
+const SAMPLE_INTERVAL: f32 = 0.25;
+
+/// The condition that raised an alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alarm {
+    /// The frame has been (nearly) black
+    Black,
+    /// The frame has not changed, or no frame has arrived
+    Frozen,
+}
+
+/// What to do while an alarm is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorAction {
+    /// Only log the alarm
+    Log,
+    /// Replace the input with color bars until the source recovers
+    TestPattern,
+    /// Cut the mixer to the source at this index
+    SwitchSource(usize),
+    /// Flash a warning border over the output
+    Overlay,
+}
+
+/// Detection thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorSettings {
+    /// Seconds a condition must persist before the alarm is raised
+    pub duration: f32,
+    /// Mean luma (0.0 - 1.0) at or below which a frame counts as black
+    pub black_threshold: f32,
+    /// Mean luma difference (0.0 - 1.0) up to which a frame counts as unchanged,
+    /// so noise or film grain on a frozen picture doesn't hide it
+    pub tolerance: f32,
+}
+
+impl Default for MonitorSettings {
+    fn default() -> Self {
+        Self {
+            duration: 3.0,
+            black_threshold: 0.02,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Watches the output frames for black or frozen output. Output frames are
+/// sampled a few times a second (see `wants_output`).
+pub struct FrameMonitor {
+    settings: MonitorSettings,
+    action: MonitorAction,
+    alarm: Option<Alarm>,
+    /// Luma samples of the frame at the last change
+    reference: Option<Vec<u8>>,
+    /// Time the frame content last changed
+    last_change: Option<f32>,
+    /// Time the frame first turned black
+    black_since: Option<f32>,
+    /// Time of the last output sample
+    last_sample: Option<f32>,
+    /// Whether the source is a still image, which can't freeze
+    static_source: bool,
+    /// Luma samples of the input when the test pattern replaced it
+    input_reference: Option<Vec<u8>>,
+    /// Source index to switch to, taken by the caller
+    pending_switch: Option<usize>,
+    /// Frame shown instead of the input while the alarm is active
+    substitute: Option<VideoFrame>,
+}
+
+impl FrameMonitor {
+    pub fn new(settings: MonitorSettings, action: MonitorAction) -> Self {
+        Self {
+            settings,
+            action,
+            alarm: None,
+            reference: None,
+            last_change: None,
+            black_since: None,
+            last_sample: None,
+            static_source: false,
+            input_reference: None,
+            pending_switch: None,
+            substitute: None,
+        }
+    }
+
+    /// The currently active alarm, if any.
+    pub fn alarm(&self) -> Option<Alarm> {
+        self.alarm
+    }
+
+    /// Whether the warning border should be drawn over the output.
+    pub fn warning(&self) -> bool {
+        self.alarm.is_some() && self.action == MonitorAction::Overlay
+    }
+
+    /// Returns the mixer source to switch to, if the alarm requested one.
+    pub fn take_switch_request(&mut self) -> Option<usize> {
+        self.pending_switch.take()
+    }
+
+    /// Sets whether the current source is a still image (or test pattern),
+    /// whose output isn't expected to change.
+    pub fn set_static_source(&mut self, static_source: bool) {
+        self.static_source = static_source;
+    }
+
+    /// Whether the caller should read back the output for `observe`.
+    pub fn wants_output(&self, time: f32) -> bool {
+        !self.substituting() && self.last_sample.is_none_or(|last| time - last >= SAMPLE_INTERVAL)
+    }
+
+    /// Whether the test pattern replaces the input, hiding the output's health.
+    fn substituting(&self) -> bool {
+        self.alarm.is_some() && self.action == MonitorAction::TestPattern
+    }
+
+    /// Returns the input frame to process: the input itself, or color bars
+    /// while the test pattern action is active. As the output then shows the
+    /// bars, the input tells when the source is back.
+    pub fn filter<'a>(&'a mut self, frame: Option<&'a VideoFrame>, time: f32) -> Option<&'a VideoFrame> {
+        if !self.substituting() {
+            return frame;
+        }
+        if let Some(frame) = frame {
+            let samples = sample_luma(frame);
+            let reference = self.input_reference.get_or_insert_with(|| samples.clone());
+            let changed = difference(reference, &samples) > self.settings.tolerance;
+            if mean(&samples) > self.settings.black_threshold && (self.alarm == Some(Alarm::Black) || changed) {
+                // Start over on the output
+                self.input_reference = None;
+                self.reference = None;
+                self.last_change = Some(time);
+                self.black_since = None;
+                self.set_alarm(None);
+                return Some(frame);
+            }
+        }
+        let (width, height) = frame.map(|f| (f.width, f.height)).unwrap_or((1280, 720));
+        Some(self.substitute.get_or_insert_with(|| color_bars(width, height)))
+    }
+
+    /// Observes the output: a read back frame when `wants_output` asked for
+    /// one, otherwise `None` to only advance the timers.
+    pub fn observe(&mut self, output: Option<&VideoFrame>, time: f32) {
+        if self.substituting() {
+            return;
+        }
+        if self.last_change.is_none() || self.static_source {
+            self.last_change = Some(time);
+        }
+
+        if let Some(output) = output {
+            self.last_sample = Some(time);
+            let samples = sample_luma(output);
+            if self.reference.as_ref().is_none_or(|reference| difference(reference, &samples) > self.settings.tolerance) {
+                self.last_change = Some(time);
+            }
+            if mean(&samples) <= self.settings.black_threshold {
+                self.black_since.get_or_insert(time);
+            } else {
+                self.black_since = None;
+            }
+            if self.last_change == Some(time) {
+                self.reference = Some(samples);
+            }
+        }
+
+        let condition = if self.black_since.is_some_and(|since| time - since >= self.settings.duration) {
+            Some(Alarm::Black)
+        } else if time - self.last_change.unwrap_or(time) >= self.settings.duration {
+            Some(Alarm::Frozen)
+        } else {
+            None
+        };
+        self.set_alarm(condition);
+    }
+
+    fn set_alarm(&mut self, condition: Option<Alarm>) {
+        if condition == self.alarm {
+            return;
+        }
+
+        match condition {
+            Some(alarm) => {
+                warn!("Monitor: {:?} output detected for {:.1}s", alarm, self.settings.duration);
+                if self.alarm.is_none() {
+                    if let MonitorAction::SwitchSource(index) = self.action {
+                        self.pending_switch = Some(index);
+                    }
+                }
+            }
+            None => {
+                info!("Monitor: output recovered");
+                self.substitute = None;
+            }
+        }
+        self.alarm = condition;
+    }
+}
+
+/// Luma of a sparse sample grid. The grid keeps clear of the frame edges,
+/// where the warning border is drawn.
+fn sample_luma(frame: &VideoFrame) -> Vec<u8> {
+    let (w, h) = (frame.width as usize, frame.height as usize);
+    if w == 0 || h == 0 {
+        return Vec::new();
+    }
+
+    let luma_at = |x: usize, y: usize| -> u8 {
+        let i = y * w + x;
+        let data = &frame.data;
+        let value = match frame.format {
+            PixelFormat::Rgb | PixelFormat::Rgba => {
+                let p = i * frame.format.bytes_per_pixel();
+                data.get(p..p + 3)
+                    .map(|c| ((c[0] as u32 * 77 + c[1] as u32 * 150 + c[2] as u32 * 29) >> 8) as u8)
+            }
+            PixelFormat::Yuyv => data.get(i * 2).copied(),
+            PixelFormat::Uyvy => data.get(i * 2 + 1).copied(),
+            PixelFormat::Nv12 => data.get(i).copied(),
+        };
+        value.unwrap_or(0)
+    };
+
+    let (gx, gy) = SAMPLE_GRID;
+    let (inset_x, inset_y) = (w / 20, h / 20);
+    let (inner_w, inner_h) = (w - 2 * inset_x, h - 2 * inset_y);
+    (0..gy)
+        .flat_map(|sy| (0..gx).map(move |sx| (sx, sy)))
+        .map(|(sx, sy)| luma_at(inset_x + (sx * 2 + 1) * inner_w / (gx * 2), inset_y + (sy * 2 + 1) * inner_h / (gy * 2)))
+        .collect()
+}
+
+/// Mean luma (0.0 - 1.0) of samples.
+fn mean(samples: &[u8]) -> f32 {
+    samples.iter().map(|&value| value as f32).sum::<f32>() / samples.len().max(1) as f32 / 255.0
+}
+
+/// Mean absolute difference (0.0 - 1.0) between two sets of samples.
+fn difference(a: &[u8], b: &[u8]) -> f32 {
+    if a.len() != b.len() {
+        return 1.0;
+    }
+    a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b) as f32).sum::<f32>() / a.len().max(1) as f32 / 255.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(value: u8) -> VideoFrame {
+        VideoFrame::from_data(4, 4, PixelFormat::Rgba, vec![value; 64])
+    }
+
+    #[test]
+    fn test_black_and_frozen_detection() {
+        let settings = MonitorSettings { duration: 1.0, black_threshold: 0.05, tolerance: 0.01 };
+        let mut monitor = FrameMonitor::new(settings, MonitorAction::Log);
+
+        let black = solid(0);
+        monitor.observe(Some(&black), 0.0);
+        monitor.observe(Some(&black), 0.5);
+        assert_eq!(monitor.alarm(), None);
+        monitor.observe(Some(&black), 1.5);
+        assert_eq!(monitor.alarm(), Some(Alarm::Black));
+
+        // A bright frame recovers, but the same frame repeated counts as frozen,
+        // even with a little noise
+        monitor.observe(Some(&solid(200)), 2.0);
+        assert_eq!(monitor.alarm(), None);
+        monitor.observe(Some(&solid(201)), 2.5);
+        monitor.observe(None, 3.5);
+        assert_eq!(monitor.alarm(), Some(Alarm::Frozen));
+        monitor.observe(Some(&solid(100)), 3.75);
+        assert_eq!(monitor.alarm(), None);
+
+        // Still images don't freeze
+        monitor.set_static_source(true);
+        monitor.observe(Some(&solid(100)), 6.0);
+        assert_eq!(monitor.alarm(), None);
+    }
+
+    #[test]
+    fn test_test_pattern_recovery() {
+        let settings = MonitorSettings { duration: 1.0, black_threshold: 0.05, tolerance: 0.01 };
+        let mut monitor = FrameMonitor::new(settings, MonitorAction::TestPattern);
+        let frozen = solid(100);
+        monitor.observe(Some(&frozen), 0.0);
+        monitor.observe(None, 1.0);
+        assert_eq!(monitor.alarm(), Some(Alarm::Frozen));
+        assert!(!monitor.wants_output(2.0));
+
+        // The bars replace the input until the input changes again
+        assert!(!std::ptr::eq(monitor.filter(Some(&frozen), 1.1).unwrap(), &frozen));
+        assert_eq!(monitor.alarm(), Some(Alarm::Frozen));
+        let live = solid(150);
+        assert!(std::ptr::eq(monitor.filter(Some(&live), 1.2).unwrap(), &live));
+        assert_eq!(monitor.alarm(), None);
+    }
+}
@@ -0,0 +1,435 @@
+//! MQTT integration for Home Assistant and other smart-home systems
+//! (the connection requires the `mqtt` feature).
+//!
+//! Proteus publishes its state (tally, frame rate, recording, preset, privacy)
+//! as retained messages below a base topic and listens on command topics, so
+//! buttons and automations can switch presets or hide the picture. With
+//! discovery enabled, Home Assistant picks the entities up automatically.
+//!
+//! | Topic                   | Direction | Payload                           |
+//! |-------------------------|-----------|-----------------------------------|
+//! | `<topic>/status`        | out       | `online` / `offline`              |
+//! | `<topic>/live`          | out       | `ON` / `OFF`                      |
+//! | `<topic>/fps`           | out       | output frame rate                 |
+//! | `<topic>/recording`     | out       | `ON` / `OFF`                      |
+//! | `<topic>/preset`        | out       | last applied preset               |
+//! | `<topic>/privacy`       | out       | `ON` / `OFF`                      |
+//! | `<topic>/live/set`      | in        | `ON` / `OFF`                      |
+//! | `<topic>/preset/set`    | in        | preset name                       |
+//! | `<topic>/privacy/set`   | in        | `ON` / `OFF`                      |
+//! | `<topic>/command`       | in        | any `proteus ctl` command         |
+//! | `<topic>/event/<name>`  | in        | event payload for Lua scripts     |
+
+// The topic helpers are only used by the client
+#![cfg_attr(not(feature = "mqtt"), allow(dead_code))]
+
+use crate::control::ControlCommand;
+use crate::events;
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two frame rate updates.
+const FPS_INTERVAL: Duration = Duration::from_secs(5);
+/// Frame rate changes below this aren't published.
+const FPS_THRESHOLD: f32 = 0.5;
+
+/// Broker connection and topics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Base topic; also the client ID and the Home Assistant device ID
+    pub topic: String,
+    /// Publish Home Assistant discovery messages
+    pub discovery: bool,
+    /// Redaction rule switched by the privacy topic
+    pub privacy_rule: Option<String>,
+    /// Preset names offered by the Home Assistant preset select
+    pub presets: Vec<String>,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            topic: "proteus".to_string(),
+            discovery: true,
+            privacy_rule: None,
+            presets: Vec::new(),
+        }
+    }
+}
+
+/// State published to the broker.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MqttState {
+    pub live: bool,
+    /// Output frame rate
+    pub fps: f32,
+    pub recording: bool,
+    pub preset: Option<String>,
+    /// Whether the privacy redaction rule is enabled (`None` without a privacy rule)
+    pub privacy: Option<bool>,
+}
+
+fn switch_payload(on: bool) -> &'static str {
+    if on { "ON" } else { "OFF" }
+}
+
+fn parse_switch(payload: &str) -> Result<bool> {
+    match payload.trim().to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => bail!("Invalid switch payload '{}' (expected ON or OFF)", payload.trim()),
+    }
+}
+
+/// Translates a message on a command topic (relative to the base topic) into a control command.
+pub fn parse_command(subtopic: &str, payload: &str, privacy_rule: Option<&str>) -> Result<ControlCommand> {
+    if let Some(name) = subtopic.strip_prefix("event/").filter(|name| !name.is_empty()) {
+        return Ok(ControlCommand::Event { name: name.to_string(), payload: events::parse_payload(payload) });
+    }
+    match subtopic {
+        "live/set" => Ok(ControlCommand::SetLive(parse_switch(payload)?)),
+        "preset/set" => Ok(ControlCommand::SwitchPreset(payload.trim().to_string())),
+        "privacy/set" => Ok(ControlCommand::Redact {
+            rule: privacy_rule.ok_or_else(|| anyhow!("No privacy rule configured (`mqtt.privacy_rule`)"))?.to_string(),
+            enabled: Some(parse_switch(payload)?),
+        }),
+        "command" => {
+            let command: ControlCommand = payload.parse()?;
+            // Files are only named locally (`proteus ctl`)
+            if command.path().is_some() {
+                bail!("Commands with a file path aren't accepted over MQTT");
+            }
+            Ok(command)
+        }
+        _ => bail!("Unknown command topic '{}'", subtopic),
+    }
+}
+
+/// The state messages (topic, payload) that differ from `previous`. The frame
+/// rate is only included with `fps`.
+fn state_messages(topic: &str, state: &MqttState, previous: Option<&MqttState>, fps: bool) -> Vec<(String, String)> {
+    let mut messages = Vec::new();
+    let changed = |field: fn(&MqttState) -> String| previous.is_none_or(|previous| field(previous) != field(state));
+    let mut add = |name: &str, field: fn(&MqttState) -> String| {
+        if changed(field) {
+            messages.push((format!("{}/{}", topic, name), field(state)));
+        }
+    };
+    add("live", |state| switch_payload(state.live).to_string());
+    add("recording", |state| switch_payload(state.recording).to_string());
+    add("preset", |state| state.preset.clone().unwrap_or_default());
+    if state.privacy.is_some() {
+        add("privacy", |state| switch_payload(state.privacy.unwrap_or_default()).to_string());
+    }
+    if fps {
+        messages.push((format!("{}/fps", topic), format!("{:.1}", state.fps)));
+    }
+    messages
+}
+
+/// Retained Home Assistant discovery messages (topic, payload) for the entities.
+fn discovery_messages(settings: &MqttSettings) -> Vec<(String, String)> {
+    let topic = &settings.topic;
+    let id = topic.replace('/', "_");
+    let device = json!({
+        "identifiers": [id],
+        "name": "Proteus",
+        "model": "Proteus",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let mut entities: Vec<(&str, &str, Value)> = vec![
+        ("switch", "live", json!({ "name": "Live", "icon": "mdi:broadcast", "command_topic": format!("{}/live/set", topic) })),
+        ("sensor", "fps", json!({ "name": "Frame rate", "unit_of_measurement": "fps", "state_class": "measurement" })),
+        ("binary_sensor", "recording", json!({ "name": "Recording", "icon": "mdi:record-rec" })),
+    ];
+    if settings.privacy_rule.is_some() {
+        entities.push(("switch", "privacy", json!({ "name": "Privacy", "icon": "mdi:incognito", "command_topic": format!("{}/privacy/set", topic) })));
+    }
+    if !settings.presets.is_empty() {
+        entities.push(("select", "preset", json!({ "name": "Preset", "icon": "mdi:palette", "options": settings.presets, "command_topic": format!("{}/preset/set", topic) })));
+    } else {
+        entities.push(("sensor", "preset", json!({ "name": "Preset", "icon": "mdi:palette" })));
+    }
+
+    entities
+        .into_iter()
+        .map(|(component, name, mut config)| {
+            config["unique_id"] = json!(format!("{}_{}", id, name));
+            config["state_topic"] = json!(format!("{}/{}", topic, name));
+            config["availability_topic"] = json!(format!("{}/status", topic));
+            config["device"] = device.clone();
+            (format!("homeassistant/{}/{}/{}/config", component, id, name), config.to_string())
+        })
+        .collect()
+}
+
+/// Decides which state changes are published.
+#[derive(Debug, Default)]
+struct StateTracker {
+    published: Option<MqttState>,
+    fps_published: Option<Instant>,
+}
+
+impl StateTracker {
+    /// Returns the messages for `state`, remembering it as published.
+    fn update(&mut self, topic: &str, state: &MqttState, now: Instant) -> Vec<(String, String)> {
+        let fps = match (&self.published, self.fps_published) {
+            (Some(published), Some(at)) => {
+                now.duration_since(at) >= FPS_INTERVAL && (published.fps - state.fps).abs() >= FPS_THRESHOLD
+            }
+            _ => true,
+        };
+        let messages = state_messages(topic, state, self.published.as_ref(), fps);
+        let fps_state = if fps { state.fps } else { self.published.as_ref().map_or(state.fps, |published| published.fps) };
+        if fps {
+            self.fps_published = Some(now);
+        }
+        self.published = Some(MqttState { fps: fps_state, ..state.clone() });
+        messages
+    }
+
+    /// Publishes everything again (after reconnecting).
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(feature = "mqtt")]
+mod client {
+    use super::*;
+    use rumqttc::{Client, ConnectionError, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver};
+    use std::sync::Arc;
+    use std::thread;
+    use tracing::{debug, info, warn};
+
+    /// How long the worker waits before reconnecting to the broker.
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Connection to an MQTT broker.
+    pub struct MqttClient {
+        settings: MqttSettings,
+        client: Client,
+        message_rx: Receiver<(String, String)>,
+        /// Set by the worker after each (re)connect, so the state is published again
+        reconnected: Arc<AtomicBool>,
+        stopped: Arc<AtomicBool>,
+        tracker: StateTracker,
+    }
+
+    impl MqttClient {
+        /// Connects to the broker in the background.
+        pub fn start(settings: MqttSettings) -> Result<Self> {
+            let topic = settings.topic.trim_end_matches('/').to_string();
+            if topic.is_empty() || topic.contains(['+', '#']) {
+                bail!("Invalid MQTT topic '{}'", settings.topic);
+            }
+            let settings = MqttSettings { topic, ..settings };
+            let status_topic = format!("{}/status", settings.topic);
+
+            let mut options = MqttOptions::new(settings.topic.replace('/', "_"), &settings.host, settings.port);
+            options.set_keep_alive(Duration::from_secs(30));
+            options.set_last_will(LastWill::new(&status_topic, "offline", QoS::AtLeastOnce, true));
+            if let Some(username) = &settings.username {
+                options.set_credentials(username, settings.password.as_deref().unwrap_or_default());
+            }
+            let (client, mut connection) = Client::new(options, 64);
+
+            let (message_tx, message_rx) = mpsc::channel();
+            let reconnected = Arc::new(AtomicBool::new(false));
+            let stopped = Arc::new(AtomicBool::new(false));
+            let worker_client = client.clone();
+            let worker_settings = settings.clone();
+            let worker_reconnected = reconnected.clone();
+            let worker_stopped = stopped.clone();
+
+            thread::spawn(move || {
+                let topic = &worker_settings.topic;
+                let mut connected = false;
+                for event in connection.iter() {
+                    let stopping = worker_stopped.load(Ordering::Acquire);
+                    match event {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            info!("Connected to MQTT broker {}:{}", worker_settings.host, worker_settings.port);
+                            connected = true;
+                            // Clean sessions forget subscriptions, so they're renewed on every connect
+                            let result = worker_client
+                                .try_subscribe(format!("{}/+/set", topic), QoS::AtLeastOnce)
+                                .and_then(|_| worker_client.try_subscribe(format!("{}/command", topic), QoS::AtLeastOnce))
+                                .and_then(|_| worker_client.try_subscribe(format!("{}/event/+", topic), QoS::AtLeastOnce))
+                                .and_then(|_| worker_client.try_publish(format!("{}/status", topic), QoS::AtLeastOnce, true, "online"));
+                            if let Err(e) = result {
+                                warn!("MQTT subscribe failed: {}", e);
+                            }
+                            if worker_settings.discovery {
+                                for (topic, payload) in discovery_messages(&worker_settings) {
+                                    if let Err(e) = worker_client.try_publish(topic, QoS::AtLeastOnce, true, payload) {
+                                        warn!("MQTT discovery failed: {}", e);
+                                    }
+                                }
+                            }
+                            worker_reconnected.store(true, Ordering::Release);
+                        }
+                        Ok(Event::Incoming(Packet::Publish(publish))) => {
+                            let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                            debug!("MQTT {} <- {}", publish.topic, payload);
+                            if let Some(subtopic) = publish.topic.strip_prefix(topic.as_str()).and_then(|t| t.strip_prefix('/')) {
+                                if message_tx.send((subtopic.to_string(), payload)).is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                        Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                        Ok(_) => {}
+                        Err(_) if stopping => break,
+                        Err(ConnectionError::RequestsDone) => break,
+                        Err(e) => {
+                            if connected {
+                                warn!("Lost connection to MQTT broker: {}", e);
+                            } else {
+                                debug!("MQTT broker {}:{} not reachable: {}", worker_settings.host, worker_settings.port, e);
+                            }
+                            connected = false;
+                            thread::sleep(RECONNECT_INTERVAL);
+                        }
+                    }
+                }
+                debug!("MQTT worker exiting");
+            });
+
+            info!("Connecting to MQTT broker {}:{} (topic {})", settings.host, settings.port, settings.topic);
+            Ok(Self { settings, client, message_rx, reconnected, stopped, tracker: StateTracker::default() })
+        }
+
+        pub fn settings(&self) -> &MqttSettings {
+            &self.settings
+        }
+
+        /// Publishes the parts of `state` that changed. Never blocks.
+        pub fn publish(&mut self, state: &MqttState) {
+            if self.reconnected.swap(false, Ordering::AcqRel) {
+                self.tracker.reset();
+            }
+            for (topic, payload) in self.tracker.update(&self.settings.topic, state, Instant::now()) {
+                if let Err(e) = self.client.try_publish(topic, QoS::AtMostOnce, true, payload) {
+                    debug!("MQTT publish failed: {}", e);
+                    // Try again with the next state
+                    self.tracker.reset();
+                    return;
+                }
+            }
+        }
+
+        /// Returns the commands received since the last call. Invalid messages are logged.
+        pub fn poll(&mut self) -> Vec<ControlCommand> {
+            self.message_rx
+                .try_iter()
+                .filter_map(|(subtopic, payload)| match parse_command(&subtopic, &payload, self.settings.privacy_rule.as_deref()) {
+                    Ok(command) => Some(command),
+                    Err(e) => {
+                        warn!("Ignoring MQTT message on {}/{}: {}", self.settings.topic, subtopic, e);
+                        None
+                    }
+                })
+                .collect()
+        }
+    }
+
+    impl Drop for MqttClient {
+        fn drop(&mut self) {
+            // A clean disconnect doesn't trigger the last will
+            self.stopped.store(true, Ordering::Release);
+            let _ = self.client.try_publish(format!("{}/status", self.settings.topic), QoS::AtLeastOnce, true, "offline");
+            let _ = self.client.try_disconnect();
+        }
+    }
+}
+
+#[cfg(feature = "mqtt")]
+pub use client::MqttClient;
+
+/// Placeholder used when Proteus is built without the `mqtt` feature.
+#[cfg(not(feature = "mqtt"))]
+pub struct MqttClient {
+    _private: (),
+}
+
+#[cfg(not(feature = "mqtt"))]
+impl MqttClient {
+    pub fn start(_settings: MqttSettings) -> Result<Self> {
+        bail!("Proteus was built without MQTT support (enable the `mqtt` feature)")
+    }
+
+    pub fn settings(&self) -> &MqttSettings {
+        unreachable!("MqttClient can't be constructed without the `mqtt` feature")
+    }
+
+    pub fn publish(&mut self, _state: &MqttState) {}
+
+    pub fn poll(&mut self) -> Vec<ControlCommand> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("live/set", "ON", None).unwrap(), ControlCommand::SetLive(true));
+        assert_eq!(parse_command("preset/set", "calm\n", None).unwrap(), ControlCommand::SwitchPreset("calm".to_string()));
+        assert_eq!(
+            parse_command("privacy/set", "off", Some("privacy")).unwrap(),
+            ControlCommand::Redact { rule: "privacy".to_string(), enabled: Some(false) }
+        );
+        assert!(parse_command("privacy/set", "ON", None).is_err());
+        assert_eq!(parse_command("command", "zoom toggle", None).unwrap(), ControlCommand::Zoom(None));
+        assert_eq!(parse_command("command", "snapshot", None).unwrap(), ControlCommand::Snapshot(None));
+        assert!(parse_command("command", "snapshot /etc/cron.d/job.png", None).is_err());
+        assert!(parse_command("command", "session load /tmp/other.yaml", None).is_err());
+        assert_eq!(
+            parse_command("event/donation", r#"{"amount": 5}"#, None).unwrap(),
+            ControlCommand::Event { name: "donation".to_string(), payload: json!({ "amount": 5 }) }
+        );
+        assert!(parse_command("live/set", "maybe", None).is_err());
+    }
+
+    #[test]
+    fn test_state_tracker() {
+        let now = Instant::now();
+        let mut tracker = StateTracker::default();
+        let state = MqttState { live: true, fps: 30.0, recording: false, preset: Some("calm".to_string()), privacy: None };
+        let topics = |messages: Vec<(String, String)>| messages.into_iter().map(|(topic, _)| topic).collect::<Vec<_>>();
+        assert_eq!(topics(tracker.update("cam", &state, now)), ["cam/live", "cam/recording", "cam/preset", "cam/fps"]);
+
+        // Small frame rate changes and unchanged state aren't published
+        let state = MqttState { fps: 29.8, recording: true, ..state };
+        assert_eq!(tracker.update("cam", &state, now + FPS_INTERVAL), [("cam/recording".to_string(), "ON".to_string())]);
+        let state = MqttState { fps: 25.0, ..state };
+        assert!(tracker.update("cam", &state, now + Duration::from_secs(1)).is_empty());
+        assert_eq!(tracker.update("cam", &state, now + FPS_INTERVAL), [("cam/fps".to_string(), "25.0".to_string())]);
+    }
+
+    #[test]
+    fn test_discovery_messages() {
+        let settings = MqttSettings { topic: "home/office-cam".to_string(), privacy_rule: Some("privacy".to_string()), presets: vec!["calm".to_string()], ..Default::default() };
+        let messages = discovery_messages(&settings);
+        let (topic, payload) = messages.iter().find(|(topic, _)| topic.contains("/select/")).unwrap();
+        assert_eq!(topic, "homeassistant/select/home_office-cam/preset/config");
+        let config: Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(config["command_topic"], "home/office-cam/preset/set");
+        assert_eq!(config["options"], json!(["calm"]));
+        assert!(messages.iter().any(|(topic, _)| topic == "homeassistant/switch/home_office-cam/privacy/config"));
+    }
+}
@@ -0,0 +1,342 @@
+//! OBS Studio integration over obs-websocket (protocol v5, built into OBS 28+).
+//!
+//! Proteus follows OBS's program scene, so the config can run control commands
+//! per scene (e.g. a different preset for the "Interview" scene), and sends
+//! scene switches and recording/streaming actions back to OBS with
+//! `proteus ctl obs ...`, which gestures, QR codes and the gamepad can trigger.
+//! The connection runs on a background thread and is retried while OBS is closed.
+
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use tungstenite::Message;
+
+/// How long the worker waits before reconnecting to OBS.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the worker checks for commands while waiting for OBS.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Event subscription bit of the scene events (`EventSubscription::Scenes`)
+const SCENE_EVENTS: u32 = 1 << 2;
+
+/// Connection settings (Tools → WebSocket Server Settings in OBS).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObsSettings {
+    /// Server address (`ws://host:port`)
+    pub url: String,
+    /// Server password, if authentication is enabled
+    pub password: Option<String>,
+}
+
+impl Default for ObsSettings {
+    fn default() -> Self {
+        Self { url: "ws://localhost:4455".to_string(), password: None }
+    }
+}
+
+/// Start, stop or toggle an OBS output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputAction {
+    Start,
+    Stop,
+    Toggle,
+}
+
+impl OutputAction {
+    fn prefix(self) -> &'static str {
+        match self {
+            OutputAction::Start => "Start",
+            OutputAction::Stop => "Stop",
+            OutputAction::Toggle => "Toggle",
+        }
+    }
+}
+
+/// An action sent to OBS.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObsCommand {
+    /// Switch the program scene
+    Scene(String),
+    Record(OutputAction),
+    Stream(OutputAction),
+}
+
+impl ObsCommand {
+    /// The obs-websocket request type and data.
+    fn request(&self) -> (String, Value) {
+        match self {
+            ObsCommand::Scene(name) => ("SetCurrentProgramScene".to_string(), json!({ "sceneName": name })),
+            ObsCommand::Record(action) => (format!("{}Record", action.prefix()), json!({})),
+            ObsCommand::Stream(action) => (format!("{}Stream", action.prefix()), json!({})),
+        }
+    }
+}
+
+impl FromStr for ObsCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        let action = |action: &str| match action {
+            "start" => Ok(OutputAction::Start),
+            "stop" => Ok(OutputAction::Stop),
+            "toggle" => Ok(OutputAction::Toggle),
+            _ => Err(anyhow!("Invalid OBS action '{}' (expected start, stop or toggle)", action)),
+        };
+        match args.as_slice() {
+            ["scene", name @ ..] if !name.is_empty() => Ok(ObsCommand::Scene(name.join(" "))),
+            ["record", state] => Ok(ObsCommand::Record(action(state)?)),
+            ["stream", state] => Ok(ObsCommand::Stream(action(state)?)),
+            _ => bail!("Unknown OBS command '{}' (expected scene <name>, record <action> or stream <action>)", s.trim()),
+        }
+    }
+}
+
+impl fmt::Display for ObsCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = |action: &OutputAction| match action {
+            OutputAction::Start => "start",
+            OutputAction::Stop => "stop",
+            OutputAction::Toggle => "toggle",
+        };
+        match self {
+            ObsCommand::Scene(name) => write!(f, "scene {}", name),
+            ObsCommand::Record(state) => write!(f, "record {}", action(state)),
+            ObsCommand::Stream(state) => write!(f, "stream {}", action(state)),
+        }
+    }
+}
+
+/// The `authentication` string of the Identify message.
+fn authentication(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(Sha256::digest(format!("{}{}", password, salt)));
+    BASE64.encode(Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+/// A message from OBS, as far as Proteus cares.
+#[derive(Debug, Clone, PartialEq)]
+enum Incoming {
+    /// Sent on connect, with the salt and challenge if a password is required
+    Hello { authentication: Option<(String, String)> },
+    Identified,
+    /// The program scene changed (or was queried)
+    ProgramScene(String),
+    RequestFailed { request_type: String, comment: String },
+    Other,
+}
+
+fn parse_message(text: &str) -> Result<Incoming> {
+    let message: Value = serde_json::from_str(text)?;
+    let data = &message["d"];
+    let string = |value: &Value| value.as_str().map(str::to_string);
+    let incoming = match message["op"].as_u64() {
+        Some(0) => Incoming::Hello {
+            authentication: string(&data["authentication"]["salt"]).zip(string(&data["authentication"]["challenge"])),
+        },
+        Some(2) => Incoming::Identified,
+        Some(5) if data["eventType"] == "CurrentProgramSceneChanged" => {
+            Incoming::ProgramScene(string(&data["eventData"]["sceneName"]).ok_or_else(|| anyhow!("Scene change without a scene name"))?)
+        }
+        Some(7) if data["requestStatus"]["result"] == false => Incoming::RequestFailed {
+            request_type: string(&data["requestType"]).unwrap_or_default(),
+            comment: string(&data["requestStatus"]["comment"]).unwrap_or_else(|| format!("code {}", data["requestStatus"]["code"])),
+        },
+        // Older servers only report `currentProgramSceneName`
+        Some(7) if data["requestType"] == "GetCurrentProgramScene" => {
+            let response = &data["responseData"];
+            match string(&response["sceneName"]).or_else(|| string(&response["currentProgramSceneName"])) {
+                Some(name) => Incoming::ProgramScene(name),
+                None => Incoming::Other,
+            }
+        }
+        Some(_) => Incoming::Other,
+        None => bail!("Message without an op code"),
+    };
+    Ok(incoming)
+}
+
+/// Background connection to OBS.
+pub struct ObsClient {
+    settings: ObsSettings,
+    command_tx: Sender<ObsCommand>,
+    scene_rx: Receiver<String>,
+    connected: Arc<AtomicBool>,
+}
+
+impl ObsClient {
+    /// Starts connecting to OBS in the background.
+    pub fn start(settings: ObsSettings) -> Self {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (scene_tx, scene_rx) = mpsc::channel();
+        let connected = Arc::new(AtomicBool::new(false));
+        let worker_settings = settings.clone();
+        let worker_connected = connected.clone();
+
+        thread::spawn(move || {
+            // Repeated failures while OBS is closed are only logged once
+            let mut last_error = String::new();
+            loop {
+                match run_session(&worker_settings, &command_rx, &scene_tx, &worker_connected) {
+                    // The client was dropped
+                    Ok(()) => return,
+                    Err(e) if worker_connected.swap(false, Ordering::AcqRel) => warn!("Lost connection to OBS: {}", e),
+                    Err(e) if e.to_string() != last_error => {
+                        last_error = e.to_string();
+                        warn!("Can't connect to OBS at {} (retrying): {}", worker_settings.url, e);
+                    }
+                    Err(e) => debug!("OBS not reachable at {}: {}", worker_settings.url, e),
+                }
+                match command_rx.recv_timeout(RECONNECT_INTERVAL) {
+                    Ok(command) => warn!("Not connected to OBS, dropping '{}'", command),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        });
+
+        info!("Connecting to OBS at {}", settings.url);
+        Self { settings, command_tx, scene_rx, connected }
+    }
+
+    pub fn settings(&self) -> &ObsSettings {
+        &self.settings
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    /// Sends a command to OBS. Errors are logged when OBS answers.
+    pub fn send(&self, command: ObsCommand) -> Result<()> {
+        if !self.is_connected() {
+            bail!("Not connected to OBS at {}", self.settings.url);
+        }
+        self.command_tx.send(command).map_err(|_| anyhow!("OBS connection closed"))
+    }
+
+    /// Returns the program scene OBS switched to since the last call, if any. Never blocks.
+    /// After connecting, the current scene is reported as well.
+    pub fn poll_scene(&self) -> Option<String> {
+        self.scene_rx.try_iter().last()
+    }
+}
+
+/// Runs one connection until it fails, or returns `Ok` once the client is dropped.
+fn run_session(settings: &ObsSettings, command_rx: &Receiver<ObsCommand>, scene_tx: &Sender<String>, connected: &AtomicBool) -> Result<()> {
+    let url = url::Url::parse(&settings.url)?;
+    if url.scheme() != "ws" {
+        bail!("Unsupported OBS address {} (expected ws://host:port)", settings.url);
+    }
+    let host = url.host_str().ok_or_else(|| anyhow!("OBS address {} has no host", settings.url))?;
+    let address = (host, url.port_or_known_default().unwrap_or(4455))
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Failed to resolve {}", host))?;
+    let stream = TcpStream::connect_timeout(&address, RECONNECT_INTERVAL)?;
+    let (mut socket, _) = tungstenite::client(settings.url.as_str(), stream).map_err(|e| anyhow!("Handshake failed: {}", e))?;
+    // Reads time out, so commands are sent without waiting for the next message
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let mut request_id = 0u64;
+    let mut send_request = |socket: &mut tungstenite::WebSocket<TcpStream>, request_type: &str, data: Value| -> Result<()> {
+        request_id += 1;
+        let message = json!({ "op": 6, "d": { "requestType": request_type, "requestId": request_id.to_string(), "requestData": data } });
+        Ok(socket.send(Message::Text(message.to_string().into()))?)
+    };
+
+    loop {
+        loop {
+            match command_rx.try_recv() {
+                Ok(command) => {
+                    debug!("OBS <- {}", command);
+                    let (request_type, data) = command.request();
+                    send_request(&mut socket, &request_type, data)?;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    return Ok(());
+                }
+            }
+        }
+
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(frame)) => bail!("OBS closed the connection ({})", frame.map(|frame| frame.to_string()).unwrap_or_default()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        match parse_message(text.as_str()) {
+            Ok(Incoming::Hello { authentication: auth }) => {
+                let mut identify = json!({ "rpcVersion": 1, "eventSubscriptions": SCENE_EVENTS });
+                if let Some((salt, challenge)) = auth {
+                    let password = settings.password.as_deref().ok_or_else(|| anyhow!("OBS requires a password (`obs.password`)"))?;
+                    identify["authentication"] = json!(authentication(password, &salt, &challenge));
+                }
+                socket.send(Message::Text(json!({ "op": 1, "d": identify }).to_string().into()))?;
+            }
+            Ok(Incoming::Identified) => {
+                connected.store(true, Ordering::Release);
+                info!("Connected to OBS at {}", settings.url);
+                send_request(&mut socket, "GetCurrentProgramScene", json!({}))?;
+            }
+            Ok(Incoming::ProgramScene(name)) => {
+                debug!("OBS program scene: {}", name);
+                let _ = scene_tx.send(name);
+            }
+            Ok(Incoming::RequestFailed { request_type, comment }) => warn!("OBS request {} failed: {}", request_type, comment),
+            Ok(Incoming::Other) => {}
+            Err(e) => debug!("Ignoring OBS message: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authentication() {
+        // Salt and challenge from the obs-websocket protocol documentation
+        let salt = "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=";
+        let challenge = "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY=";
+        assert_eq!(authentication("supersecretpassword", salt, challenge), "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4=");
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let hello = r#"{"op":0,"d":{"obsWebSocketVersion":"5.5.0","rpcVersion":1,"authentication":{"challenge":"c","salt":"s"}}}"#;
+        assert_eq!(parse_message(hello).unwrap(), Incoming::Hello { authentication: Some(("s".to_string(), "c".to_string())) });
+        assert_eq!(parse_message(r#"{"op":0,"d":{"rpcVersion":1}}"#).unwrap(), Incoming::Hello { authentication: None });
+        let event = r#"{"op":5,"d":{"eventType":"CurrentProgramSceneChanged","eventIntent":4,"eventData":{"sceneName":"Interview"}}}"#;
+        assert_eq!(parse_message(event).unwrap(), Incoming::ProgramScene("Interview".to_string()));
+        let response = r#"{"op":7,"d":{"requestType":"GetCurrentProgramScene","requestId":"1","requestStatus":{"result":true,"code":100},"responseData":{"currentProgramSceneName":"Main"}}}"#;
+        assert_eq!(parse_message(response).unwrap(), Incoming::ProgramScene("Main".to_string()));
+        let failed = r#"{"op":7,"d":{"requestType":"SetCurrentProgramScene","requestId":"2","requestStatus":{"result":false,"code":600,"comment":"No source was found"}}}"#;
+        assert_eq!(
+            parse_message(failed).unwrap(),
+            Incoming::RequestFailed { request_type: "SetCurrentProgramScene".to_string(), comment: "No source was found".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_command_round_trip() {
+        for line in ["scene Be Right Back", "record toggle", "stream stop"] {
+            let command: ObsCommand = line.parse().unwrap();
+            assert_eq!(command.to_string(), line);
+        }
+        assert_eq!("record start".parse::<ObsCommand>().unwrap().request().0, "StartRecord");
+        assert!("stream pause".parse::<ObsCommand>().is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Idle mode of outputs nobody is reading.
+//!
+//! A virtual camera usually runs all day while video apps only use it now and
+//! then. Where the platform can tell whether an application has the camera open
+//! (`OutputBackend::has_consumer`), the output drops to a low frame rate or stops
+//! rendering altogether until a consumer connects.
+
+use super::OutputBackend;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How often the consumers are looked up; also the longest a new consumer waits for frames.
+pub const CONSUMER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What an output does without consumers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdleMode {
+    /// Keeps running at the full frame rate
+    #[default]
+    Off,
+    /// Keeps delivering frames at this rate
+    LowFps(u32),
+    /// Renders nothing
+    Suspend,
+}
+
+/// Idle settings of an output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdleSettings {
+    pub mode: IdleMode,
+    /// Time without consumers before the output goes idle
+    pub delay: Duration,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        Self { mode: IdleMode::Off, delay: Duration::from_secs(5) }
+    }
+}
+
+/// Whether an output is idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputState {
+    Active,
+    /// Delivering frames at this rate
+    LowFps(u32),
+    Suspended,
+}
+
+/// Tracks the consumers of an output and decides when it goes idle.
+pub struct IdleDetector {
+    settings: IdleSettings,
+    state: OutputState,
+    last_check: Option<Instant>,
+    /// Last time a consumer was seen (or the output started)
+    last_consumer: Instant,
+}
+
+impl IdleDetector {
+    pub fn new(settings: IdleSettings, now: Instant) -> Self {
+        Self { settings, state: OutputState::Active, last_check: None, last_consumer: now }
+    }
+
+    pub fn state(&self) -> OutputState {
+        self.state
+    }
+
+    /// Changes the settings; the output becomes active until the next check.
+    pub fn set_settings(&mut self, settings: IdleSettings, now: Instant) -> Option<OutputState> {
+        self.settings = settings;
+        self.last_check = None;
+        self.last_consumer = now;
+        self.set_state(OutputState::Active)
+    }
+
+    /// Looks up the consumers of `output` (at most every `CONSUMER_CHECK_INTERVAL`).
+    /// Returns the new state if it changed.
+    pub fn update(&mut self, now: Instant, output: &mut dyn OutputBackend) -> Option<OutputState> {
+        if self.settings.mode == IdleMode::Off
+            || self.last_check.is_some_and(|last| now.duration_since(last) < CONSUMER_CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.last_check = Some(now);
+
+        // Outputs that can't tell are always active
+        if output.has_consumer().unwrap_or(true) {
+            self.last_consumer = now;
+            return self.set_state(OutputState::Active);
+        }
+        if now.duration_since(self.last_consumer) < self.settings.delay {
+            return None;
+        }
+        self.set_state(match self.settings.mode {
+            IdleMode::Off => OutputState::Active,
+            IdleMode::LowFps(fps) => OutputState::LowFps(fps.max(1)),
+            IdleMode::Suspend => OutputState::Suspended,
+        })
+    }
+
+    fn set_state(&mut self, state: OutputState) -> Option<OutputState> {
+        if state == self.state {
+            return None;
+        }
+        match state {
+            OutputState::Active => info!("Output has a consumer, resuming"),
+            OutputState::LowFps(fps) => info!("Output has no consumer, dropping to {} FPS", fps),
+            OutputState::Suspended => info!("Output has no consumer, suspending"),
+        }
+        self.state = state;
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::VideoFrame;
+    use anyhow::Result;
+
+    struct MockOutput(Option<bool>);
+
+    impl OutputBackend for MockOutput {
+        fn write_frame(&mut self, _frame: &VideoFrame) -> Result<()> {
+            Ok(())
+        }
+
+        fn has_consumer(&mut self) -> Option<bool> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_idle_detector() {
+        let start = Instant::now();
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+        let settings = IdleSettings { mode: IdleMode::Suspend, delay: Duration::from_secs(5) };
+        let mut detector = IdleDetector::new(settings, start);
+        let mut output = MockOutput(Some(false));
+
+        // Idle only after the delay
+        assert_eq!(detector.update(at(1), &mut output), None);
+        assert_eq!(detector.update(at(5), &mut output), Some(OutputState::Suspended));
+        assert_eq!(detector.update(at(6), &mut output), None);
+
+        // A consumer connecting resumes at the next check
+        output.0 = Some(true);
+        assert_eq!(detector.update(at(6) + Duration::from_millis(500), &mut output), None);
+        assert_eq!(detector.update(at(7), &mut output), Some(OutputState::Active));
+
+        // Outputs that can't tell stay active
+        let mut detector = IdleDetector::new(IdleSettings { mode: IdleMode::LowFps(1), ..settings }, start);
+        assert_eq!(detector.update(at(10), &mut MockOutput(None)), None);
+        assert_eq!(detector.update(at(20), &mut MockOutput(Some(false))), Some(OutputState::LowFps(1)));
+    }
+}
@@ -1,5 +1,7 @@
 //! Output backends for displaying processed video.
 
+pub mod idle;
+pub mod pacing;
 pub mod window_output;
 
 #[cfg(target_os = "macos")]
@@ -19,11 +21,69 @@ pub use window_output::WindowOutput;
 #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
 pub use virtual_camera::{VirtualCameraConfig, VirtualCameraOutput};
 
-use crate::frame::VideoFrame;
-use anyhow::Result;
+use crate::frame::{PixelFormat, VideoFrame};
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+
+/// Which kinds of frames an output backend accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputCapabilities {
+    /// Frames in system memory (`write_frame`)
+    pub cpu_frame: bool,
+    /// Textures on the pipeline's GPU device (`write_texture`), which skip the readback
+    pub gpu_texture: bool,
+}
+
+impl OutputCapabilities {
+    /// Only `write_frame` is supported.
+    pub const CPU: OutputCapabilities = OutputCapabilities { cpu_frame: true, gpu_texture: false };
+    /// Both `write_frame` and `write_texture` are supported.
+    pub const CPU_AND_GPU: OutputCapabilities = OutputCapabilities { cpu_frame: true, gpu_texture: true };
+}
 
 /// Trait for video output backends.
+///
+/// Every backend accepts CPU frames. Backends that live on the GPU (the
+/// preview window, texture sharing like Spout or Syphon) also accept the
+/// pipeline's output texture directly; see `WgpuPipeline::write_output`.
 pub trait OutputBackend {
     /// Write a frame to the output.
     fn write_frame(&mut self, frame: &VideoFrame) -> Result<()>;
+
+    /// Write a texture of the pipeline's device to the output. The texture
+    /// holds linear color (`Rgba16Float`), as rendered by the shaders.
+    fn write_texture(&mut self, _view: &wgpu::TextureView) -> Result<()> {
+        bail!("This output does not accept GPU textures")
+    }
+
+    /// The kinds of frames the output accepts.
+    fn capabilities(&self) -> OutputCapabilities {
+        OutputCapabilities::CPU
+    }
+
+    /// The pixel format the output delivers. Frames in other formats are converted
+    /// on the CPU, so pipelines should produce this format directly.
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Rgba
+    }
+
+    /// Whether an application is reading the output, or `None` if the platform
+    /// can't tell. May be slow; see `idle::IdleDetector`.
+    fn has_consumer(&mut self) -> Option<bool> {
+        None
+    }
+}
+
+/// Converts `frame` to `format` on the CPU, unless it already has that format.
+#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+fn convert(frame: &VideoFrame, format: PixelFormat) -> Cow<'_, VideoFrame> {
+    if frame.format == format {
+        return Cow::Borrowed(frame);
+    }
+    Cow::Owned(match format {
+        PixelFormat::Yuyv => frame.to_yuyv(),
+        PixelFormat::Uyvy => frame.to_uyvy(),
+        PixelFormat::Nv12 => frame.to_nv12(),
+        PixelFormat::Rgb | PixelFormat::Rgba => frame.to_rgba(),
+    })
 }
@@ -0,0 +1,102 @@
+//! Per-output frame rates.
+//!
+//! The pipeline renders at the highest rate any output needs; each output
+//! (preview window, virtual camera, recorder) takes the latest frame whenever
+//! its own pacer is due, so a 30 FPS camera and a 60 FPS preview can share one
+//! render loop.
+
+use std::time::{Duration, Instant};
+
+/// Frame rates of the outputs, in frames per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputRates {
+    /// Preview window
+    pub preview: u32,
+    /// Virtual camera
+    pub camera: u32,
+    /// Recordings (`proteus ctl record`)
+    pub recording: u32,
+}
+
+/// Schedules the frames of one output at a fixed rate.
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    interval: Duration,
+    next: Instant,
+}
+
+impl FramePacer {
+    /// A pacer for `fps` frames per second; the first frame is due at `now`.
+    pub fn new(fps: u32, now: Instant) -> Self {
+        Self { interval: Self::fps_interval(fps), next: now }
+    }
+
+    fn fps_interval(fps: u32) -> Duration {
+        Duration::from_secs_f64(1.0 / fps.max(1) as f64)
+    }
+
+    /// Time between two frames.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Changes the frame interval, keeping the next frame's time.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// When the next frame is due.
+    pub fn next_frame(&self) -> Instant {
+        self.next
+    }
+
+    /// Whether a frame is due at `now`. Frames up to a quarter interval early
+    /// count, so render loop jitter does not skip every other frame when the
+    /// render rate is a multiple of the output's rate.
+    pub fn is_due(&self, now: Instant) -> bool {
+        now + self.interval / 4 >= self.next
+    }
+
+    /// Takes the frame due at `now`, if any. Returns whether it was due.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if !self.is_due(now) {
+            return false;
+        }
+        // Keeps the phase, unless the output fell more than a frame behind
+        self.next = if now > self.next + self.interval { now + self.interval } else { self.next + self.interval };
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of frames a pacer at `fps` takes from a second of rendering at `render_fps`.
+    fn frames(fps: u32, render_fps: u32) -> u32 {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(fps, start);
+        (0..render_fps)
+            // A millisecond of jitter on every other frame
+            .map(|i| start + Duration::from_secs_f64(i as f64 / render_fps as f64) + Duration::from_millis(i as u64 % 2))
+            .filter(|now| pacer.tick(*now))
+            .count() as u32
+    }
+
+    #[test]
+    fn test_frame_pacer() {
+        assert_eq!(frames(30, 60), 30);
+        assert_eq!(frames(25, 60), 25);
+        assert_eq!(frames(60, 60), 60);
+        // Faster than the render loop takes every frame
+        assert_eq!(frames(60, 30), 30);
+
+        // Falling behind restarts the schedule instead of catching up
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(10, start);
+        assert!(pacer.tick(start));
+        assert!(pacer.tick(start + Duration::from_secs(1)));
+        assert!(!pacer.tick(start + Duration::from_millis(1010)));
+        assert!(pacer.tick(start + Duration::from_millis(1100)));
+    }
+}
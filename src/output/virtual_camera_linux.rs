@@ -4,8 +4,8 @@
 //! Requires v4l2loopback kernel module to be loaded.
 
 use super::OutputBackend;
-use crate::frame::VideoFrame;
-use anyhow::{anyhow, Result};
+use crate::frame::{PixelFormat, VideoFrame};
+use anyhow::{anyhow, bail, Result};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::os::unix::fs::OpenOptionsExt;
@@ -18,8 +18,26 @@ const DEFAULT_DEVICE: &str = "/dev/video10";
 
 // V4L2 Constants
 const VIDIOC_S_FMT: u64 = 0xC0D05605; // _IOWR('V', 5, struct v4l2_format)
+const VIDIOC_REQBUFS: u64 = iowr::<v4l2_requestbuffers>(8);
+const VIDIOC_QUERYBUF: u64 = iowr::<v4l2_buffer>(9);
+const VIDIOC_QBUF: u64 = iowr::<v4l2_buffer>(15);
+const VIDIOC_DQBUF: u64 = iowr::<v4l2_buffer>(17);
+const VIDIOC_STREAMON: u64 = 0x40045612; // _IOW('V', 18, int)
+const VIDIOC_STREAMOFF: u64 = 0x40045613; // _IOW('V', 19, int)
+const VIDIOC_S_PARM: u64 = iowr::<v4l2_streamparm>(22);
+const VIDIOC_DQEVENT: u64 = ior::<v4l2_event>(89);
+const VIDIOC_SUBSCRIBE_EVENT: u64 = iow::<v4l2_event_subscription>(90);
 const V4L2_BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+const V4L2_MEMORY_MMAP: u32 = 1;
+const V4L2_FIELD_NONE: u32 = 1;
+const V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC: u32 = 0x2000;
 const V4L2_PIX_FMT_YUYV: u32 = 0x56595559; // 'Y' 'U' 'Y' 'V'
+const V4L2_PIX_FMT_UYVY: u32 = 0x59565955; // 'U' 'Y' 'V' 'Y'
+const V4L2_PIX_FMT_NV12: u32 = 0x3231564E; // 'N' 'V' '1' '2'
+const V4L2_PIX_FMT_RGBA32: u32 = 0x34324241; // 'A' 'B' '2' '4'
+const V4L2_EVENT_SUB_FL_SEND_INITIAL: u32 = 1;
+// V4L2_EVENT_PRIVATE_START + 0x08E00000 + 1, sent by v4l2loopback when its readers change
+const V4L2_EVENT_PRI_CLIENT_USAGE: u32 = 0x0800_0000 + 0x08E0_0000 + 1;
 
 #[repr(C)]
 struct v4l2_format {
@@ -51,6 +69,146 @@ struct v4l2_pix_format {
     xfer_func: u32,
 }
 
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct v4l2_fract {
+    numerator: u32,
+    denominator: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct v4l2_outputparm {
+    capability: u32,
+    outputmode: u32,
+    timeperframe: v4l2_fract,
+    extendedmode: u32,
+    writebuffers: u32,
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+struct v4l2_streamparm {
+    type_: u32,
+    parm: v4l2_streamparm_union,
+}
+
+#[repr(C)]
+union v4l2_streamparm_union {
+    output: v4l2_outputparm,
+    raw_data: [u8; 200],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct v4l2_requestbuffers {
+    count: u32,
+    type_: u32,
+    memory: u32,
+    capabilities: u32,
+    flags: u8,
+    reserved: [u8; 3],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct v4l2_timecode {
+    type_: u32,
+    flags: u32,
+    frames: u8,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    userbits: [u8; 4],
+}
+
+#[repr(C)]
+struct v4l2_buffer {
+    index: u32,
+    type_: u32,
+    bytesused: u32,
+    flags: u32,
+    field: u32,
+    timestamp: libc::timeval,
+    timecode: v4l2_timecode,
+    sequence: u32,
+    memory: u32,
+    m: v4l2_buffer_m,
+    length: u32,
+    reserved2: u32,
+    request_fd: i32,
+}
+
+#[repr(C)]
+union v4l2_buffer_m {
+    offset: u32,
+    userptr: libc::c_ulong,
+}
+
+impl v4l2_buffer {
+    fn output(index: u32) -> Self {
+        Self {
+            index,
+            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            bytesused: 0,
+            flags: 0,
+            field: 0,
+            timestamp: libc::timeval { tv_sec: 0, tv_usec: 0 },
+            timecode: v4l2_timecode::default(),
+            sequence: 0,
+            memory: V4L2_MEMORY_MMAP,
+            m: v4l2_buffer_m { userptr: 0 },
+            length: 0,
+            reserved2: 0,
+            request_fd: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct v4l2_event_subscription {
+    type_: u32,
+    id: u32,
+    flags: u32,
+    reserved: [u32; 5],
+}
+
+#[repr(C)]
+struct v4l2_event {
+    type_: u32,
+    u: v4l2_event_union,
+    pending: u32,
+    sequence: u32,
+    timestamp: libc::timespec,
+    id: u32,
+    reserved: [u32; 8],
+}
+
+#[repr(C)]
+union v4l2_event_union {
+    data: [u8; 64], // `struct v4l2_event_client_usage` starts with the u32 reader count
+    _align: u64,
+}
+
+/// `_IOWR('V', nr, T)`
+const fn iowr<T>(nr: u64) -> u64 {
+    0xC000_0000 | ((std::mem::size_of::<T>() as u64) << 16) | ((b'V' as u64) << 8) | nr
+}
+
+/// `_IOR('V', nr, T)`
+const fn ior<T>(nr: u64) -> u64 {
+    0x8000_0000 | ((std::mem::size_of::<T>() as u64) << 16) | ((b'V' as u64) << 8) | nr
+}
+
+/// `_IOW('V', nr, T)`
+const fn iow<T>(nr: u64) -> u64 {
+    0x4000_0000 | ((std::mem::size_of::<T>() as u64) << 16) | ((b'V' as u64) << 8) | nr
+}
+
+/// Number of buffers requested for streaming output.
+const BUFFER_COUNT: u32 = 4;
+
 /// Configuration for virtual camera output.
 #[derive(Debug, Clone)]
 pub struct VirtualCameraConfig {
@@ -59,6 +217,8 @@ pub struct VirtualCameraConfig {
     pub width: u32,
     pub height: u32,
     pub fps: u32,
+    /// Pixel format delivered to applications (YUYV, UYVY, NV12, or RGBA)
+    pub format: PixelFormat,
 }
 
 impl Default for VirtualCameraConfig {
@@ -68,16 +228,53 @@ impl Default for VirtualCameraConfig {
             width: 1920,
             height: 1080,
             fps: 30,
+            format: PixelFormat::Yuyv,
         }
     }
 }
 
+/// V4L2 fourcc and bytes per line of a pixel format (the luma plane's for NV12).
+fn v4l2_format_of(format: PixelFormat, width: u32) -> Result<(u32, u32)> {
+    Ok(match format {
+        PixelFormat::Yuyv => (V4L2_PIX_FMT_YUYV, width * 2),
+        PixelFormat::Uyvy => (V4L2_PIX_FMT_UYVY, width * 2),
+        PixelFormat::Nv12 => (V4L2_PIX_FMT_NV12, width),
+        PixelFormat::Rgba => (V4L2_PIX_FMT_RGBA32, width * 4),
+        PixelFormat::Rgb => bail!("Unsupported virtual camera format {:?}", format),
+    })
+}
+
+/// Size in bytes of a frame in a pixel format.
+fn frame_size(format: PixelFormat, width: u32, height: u32) -> u32 {
+    match format {
+        PixelFormat::Nv12 => width * height * 3 / 2,
+        _ => width * height * format.bytes_per_pixel() as u32,
+    }
+}
+
+/// Device buffers mapped for streaming output, which lets frames carry their own
+/// timestamps.
+struct StreamingBuffers {
+    buffers: Vec<(*mut u8, usize)>,
+    /// Buffers handed to the driver so far; once all are, each frame reclaims one
+    queued: usize,
+    streaming: bool,
+}
+
 /// Virtual camera output using v4l2loopback.
 pub struct VirtualCameraOutput {
     config: VirtualCameraConfig,
     device: File,
+    /// None if the device doesn't support streaming output (frames are written instead)
+    buffers: Option<StreamingBuffers>,
+    /// Applications reading the device, as last reported by v4l2loopback; None if it
+    /// doesn't report them
+    readers: Option<u32>,
 }
 
+// SAFETY: The mapped buffers are only accessed through `&mut self`
+unsafe impl Send for VirtualCameraOutput {}
+
 impl VirtualCameraOutput {
     /// Creates a new virtual camera output.
     ///
@@ -85,22 +282,39 @@ impl VirtualCameraOutput {
     pub fn new(config: VirtualCameraConfig) -> Result<Self> {
         // Try to open the device and configure it
         let device = Self::open_and_configure_device(&config)?;
+        let buffers = match Self::map_buffers(&device) {
+            Ok(buffers) => Some(buffers),
+            Err(e) => {
+                warn!("Streaming output unavailable ({}), writing frames without timestamps", e);
+                None
+            }
+        };
+
+        let readers = match Self::subscribe_client_usage(&device) {
+            Ok(()) => Some(0),
+            Err(e) => {
+                info!("v4l2loopback doesn't report its consumers ({}), the virtual camera never goes idle", e);
+                None
+            }
+        };
 
         info!(
-            "Virtual camera output created on {} ({}x{} @ {} fps, YUYV)",
+            "Virtual camera output created on {} ({}x{} @ {} fps, {:?})",
             config.device.display(),
             config.width,
             config.height,
-            config.fps
+            config.fps,
+            config.format
         );
         info!("Select the v4l2loopback camera in your video application");
 
-        Ok(Self { config, device })
+        Ok(Self { config, device, buffers, readers })
     }
 
     /// Open the v4l2loopback device and configure format.
     fn open_and_configure_device(config: &VirtualCameraConfig) -> Result<File> {
         let path = &config.device;
+        let (pixelformat, bytesperline) = v4l2_format_of(config.format, config.width)?;
 
         // Check if device exists
         if !path.exists() {
@@ -133,10 +347,10 @@ impl VirtualCameraOutput {
         let pix = v4l2_pix_format {
             width: config.width,
             height: config.height,
-            pixelformat: V4L2_PIX_FMT_YUYV,
+            pixelformat,
             field: 0, // V4L2_FIELD_ANY / V4L2_FIELD_NONE
-            bytesperline: config.width * 2, // YUYV is 2 bytes per pixel
-            sizeimage: config.width * config.height * 2,
+            bytesperline,
+            sizeimage: frame_size(config.format, config.width, config.height),
             colorspace: 8, // V4L2_COLORSPACE_SRGB
             priv_: 0,
             flags: 0,
@@ -157,7 +371,27 @@ impl VirtualCameraOutput {
                 // We don't fail here because some devices might not support S_FMT but still work?
                 // But for v4l2loopback it is crucial.
             } else {
-                debug!("Successfully set v4l2 format to YUYV {}x{}", config.width, config.height);
+                debug!("Successfully set v4l2 format to {:?} {}x{}", config.format, config.width, config.height);
+            }
+        }
+
+        // Frame rate reported to applications
+        let mut parm = v4l2_streamparm {
+            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            parm: v4l2_streamparm_union {
+                output: v4l2_outputparm {
+                    capability: 0,
+                    outputmode: 0,
+                    timeperframe: v4l2_fract { numerator: 1, denominator: config.fps.max(1) },
+                    extendedmode: 0,
+                    writebuffers: 0,
+                    reserved: [0; 4],
+                },
+            },
+        };
+        unsafe {
+            if libc::ioctl(fd, VIDIOC_S_PARM, &mut parm) < 0 {
+                warn!("Failed to set v4l2 frame rate: {}", std::io::Error::last_os_error());
             }
         }
 
@@ -165,33 +399,165 @@ impl VirtualCameraOutput {
         Ok(file)
     }
 
+    /// Subscribes to v4l2loopback's reader count, which it sends right away and
+    /// then whenever an application starts or stops reading.
+    fn subscribe_client_usage(device: &File) -> std::io::Result<()> {
+        let mut subscription = v4l2_event_subscription {
+            type_: V4L2_EVENT_PRI_CLIENT_USAGE,
+            flags: V4L2_EVENT_SUB_FL_SEND_INITIAL,
+            ..Default::default()
+        };
+        if unsafe { libc::ioctl(device.as_raw_fd(), VIDIOC_SUBSCRIBE_EVENT, &mut subscription) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Takes the pending reader count events and keeps the latest count.
+    fn update_readers(&mut self) -> Option<u32> {
+        let readers = self.readers.as_mut()?;
+        loop {
+            let mut event: v4l2_event = unsafe { std::mem::zeroed() };
+            // Fails with ENOENT once no events are pending
+            if unsafe { libc::ioctl(self.device.as_raw_fd(), VIDIOC_DQEVENT, &mut event) } < 0 {
+                break;
+            }
+            if event.type_ == V4L2_EVENT_PRI_CLIENT_USAGE {
+                let data = unsafe { event.u.data };
+                *readers = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]);
+            }
+        }
+        Some(*readers)
+    }
+
+    /// Requests and maps the device's output buffers.
+    fn map_buffers(device: &File) -> Result<StreamingBuffers> {
+        let fd = device.as_raw_fd();
+        let mut request = v4l2_requestbuffers {
+            count: BUFFER_COUNT,
+            type_: V4L2_BUF_TYPE_VIDEO_OUTPUT,
+            memory: V4L2_MEMORY_MMAP,
+            ..Default::default()
+        };
+        if unsafe { libc::ioctl(fd, VIDIOC_REQBUFS, &mut request) } < 0 || request.count == 0 {
+            bail!("VIDIOC_REQBUFS failed: {}", std::io::Error::last_os_error());
+        }
+
+        let mut streaming = StreamingBuffers { buffers: Vec::new(), queued: 0, streaming: false };
+        for index in 0..request.count {
+            let mut buffer = v4l2_buffer::output(index);
+            if unsafe { libc::ioctl(fd, VIDIOC_QUERYBUF, &mut buffer) } < 0 {
+                bail!("VIDIOC_QUERYBUF failed: {}", std::io::Error::last_os_error());
+            }
+            let length = buffer.length as usize;
+            let data = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    length,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED,
+                    fd,
+                    buffer.m.offset as libc::off_t,
+                )
+            };
+            if data == libc::MAP_FAILED {
+                bail!("Failed to map buffer {}: {}", index, std::io::Error::last_os_error());
+            }
+            // Unmapped on drop, also if a later buffer fails
+            streaming.buffers.push((data as *mut u8, length));
+        }
+        debug!("Mapped {} v4l2 output buffers", streaming.buffers.len());
+        Ok(streaming)
+    }
+
+    /// Queues a frame with the current monotonic time as its timestamp.
+    fn queue_frame(fd: i32, streaming: &mut StreamingBuffers, data: &[u8]) -> Result<()> {
+        let mut buffer = v4l2_buffer::output(0);
+        if streaming.queued < streaming.buffers.len() {
+            buffer.index = streaming.queued as u32;
+        } else if unsafe { libc::ioctl(fd, VIDIOC_DQBUF, &mut buffer) } < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                warn!("v4l2loopback buffer full, frame dropped");
+                bail!("Buffer full");
+            }
+            bail!("Failed to dequeue v4l2loopback buffer: {}", err);
+        }
+
+        let (ptr, length) = streaming.buffers[buffer.index as usize];
+        let bytes = data.len().min(length);
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, bytes) };
+
+        let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+        buffer.timestamp = libc::timeval { tv_sec: now.tv_sec, tv_usec: now.tv_nsec / 1000 };
+        buffer.flags = V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC;
+        buffer.field = V4L2_FIELD_NONE;
+        buffer.bytesused = bytes as u32;
+        buffer.memory = V4L2_MEMORY_MMAP;
+        if unsafe { libc::ioctl(fd, VIDIOC_QBUF, &mut buffer) } < 0 {
+            bail!("Failed to queue v4l2loopback buffer: {}", std::io::Error::last_os_error());
+        }
+        streaming.queued = (streaming.queued + 1).min(streaming.buffers.len());
+
+        if !streaming.streaming {
+            let mut buffer_type = V4L2_BUF_TYPE_VIDEO_OUTPUT as libc::c_int;
+            if unsafe { libc::ioctl(fd, VIDIOC_STREAMON, &mut buffer_type) } < 0 {
+                bail!("Failed to start v4l2loopback streaming: {}", std::io::Error::last_os_error());
+            }
+            streaming.streaming = true;
+        }
+        Ok(())
+    }
+
     fn write_frame_internal(&mut self, frame: &VideoFrame) -> Result<()> {
-        // v4l2loopback typically accepts raw pixel data
-        // Convert to YUYV (most standard webcam format)
-        let yuyv_start = std::time::Instant::now();
-        let yuyv = frame.to_yuyv();
-        let yuyv_elapsed = yuyv_start.elapsed();
+        // v4l2loopback accepts raw pixel data in the configured format.
+        // Converts on the CPU only if the pipeline didn't already pack the frame.
+        let convert_start = std::time::Instant::now();
+        let converted = super::convert(frame, self.config.format);
+        let convert_elapsed = convert_start.elapsed();
 
-        // Write the raw YUYV data to the device
+        // Queue the raw frame data with its timestamp, or write it to the device
         let write_start = std::time::Instant::now();
-        self.device.write_all(&yuyv.data).map_err(|e| {
+        if let Some(streaming) = &mut self.buffers {
+            Self::queue_frame(self.device.as_raw_fd(), streaming, &converted.data)?;
+        } else {
+            self.write_raw(&converted.data)?;
+        }
+        let write_elapsed = write_start.elapsed();
+
+        debug!("  [Perf] VCam Write - {:?} conv: {:?}, Device write: {:?}", self.config.format, convert_elapsed, write_elapsed);
+
+        Ok(())
+    }
+
+    /// Writes a frame with `write()`; the driver timestamps it on arrival.
+    fn write_raw(&mut self, data: &[u8]) -> Result<()> {
+        self.device.write_all(data).map_err(|e| {
             // Non-blocking write might fail if buffer is full, that's OK
             if e.kind() == std::io::ErrorKind::WouldBlock {
                 warn!("v4l2loopback buffer full, frame dropped");
                 return anyhow!("Buffer full");
             }
             anyhow!("Failed to write to v4l2loopback: {}", e)
-        })?;
-        let write_elapsed = write_start.elapsed();
-
-        debug!("  [Perf] VCam Write - YUYV conv: {:?}, Device write: {:?}", yuyv_elapsed, write_elapsed);
+        })
+    }
+}
 
-        Ok(())
+impl Drop for StreamingBuffers {
+    fn drop(&mut self) {
+        for &(ptr, length) in &self.buffers {
+            unsafe { libc::munmap(ptr as *mut libc::c_void, length) };
+        }
     }
 }
 
 impl Drop for VirtualCameraOutput {
     fn drop(&mut self) {
+        if self.buffers.as_ref().is_some_and(|streaming| streaming.streaming) {
+            let mut buffer_type = V4L2_BUF_TYPE_VIDEO_OUTPUT as libc::c_int;
+            unsafe { libc::ioctl(self.device.as_raw_fd(), VIDIOC_STREAMOFF, &mut buffer_type) };
+        }
         debug!("Virtual camera output closed");
     }
 }
@@ -200,4 +566,12 @@ impl OutputBackend for VirtualCameraOutput {
     fn write_frame(&mut self, frame: &VideoFrame) -> Result<()> {
         self.write_frame_internal(frame)
     }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.config.format
+    }
+
+    fn has_consumer(&mut self) -> Option<bool> {
+        self.update_readers().map(|readers| readers > 0)
+    }
 }
@@ -4,18 +4,25 @@
 //! to the OBS Virtual Camera on macOS 13+ with OBS 30+.
 
 use super::OutputBackend;
-use crate::frame::VideoFrame;
+use crate::frame::{PixelFormat, VideoFrame};
 use anyhow::{anyhow, Result};
 use core_foundation::base::{CFRelease, TCFType};
 use core_foundation::string::{CFString, CFStringRef};
 use std::ffi::c_void;
 use std::ptr;
 use std::sync::Mutex;
-use tracing::{debug, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 /// OBS Virtual Camera device UUID (from OBS source code).
 const OBS_DEVICE_UUID: &str = "7626645E-4425-469E-9D8B-97E0FA59AC75";
 
+/// Consecutive failed enqueues after which the extension is assumed to have restarted.
+const MAX_ENQUEUE_FAILURES: u32 = 30;
+
+/// How often to look for the extension again while disconnected.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
 // CoreMediaIO type definitions
 type CMIOObjectID = u32;
 type CMIOStreamID = u32;
@@ -204,12 +211,58 @@ impl Default for VirtualCameraConfig {
 /// Global mutex to ensure only one virtual camera instance at a time.
 static INSTANCE_MUTEX: Mutex<()> = Mutex::new(());
 
-/// Virtual camera output using OBS CMIOExtension protocol.
-pub struct VirtualCameraOutput {
-    _config: VirtualCameraConfig,
+/// A started stream of the OBS Virtual Camera extension. Its IDs become invalid
+/// when the extension restarts.
+struct Connection {
     device_id: CMIOObjectID,
     stream_id: CMIOStreamID,
     queue: CMSimpleQueueRef,
+}
+
+impl Connection {
+    /// Finds the OBS Virtual Camera and starts its sink stream.
+    fn open() -> Result<Self> {
+        // Find OBS Virtual Camera device
+        let device_id = VirtualCameraOutput::find_obs_device()?;
+        debug!("Found OBS Virtual Camera device: {}", device_id);
+
+        // Get streams for the device
+        let stream_id = VirtualCameraOutput::get_stream(device_id)?;
+        debug!("Found OBS Virtual Camera stream: {}", stream_id);
+
+        // Get buffer queue
+        let queue = VirtualCameraOutput::get_queue(stream_id)?;
+        debug!("Got buffer queue");
+
+        // Start the stream
+        let result = unsafe { CMIODeviceStartStream(device_id, stream_id) };
+        if result != 0 {
+            unsafe { CFRelease(queue) };
+            return Err(anyhow!("Failed to start OBS Virtual Camera stream (error {})", result));
+        }
+
+        Ok(Self { device_id, stream_id, queue })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            CMIODeviceStopStream(self.device_id, self.stream_id);
+            CFRelease(self.queue);
+        }
+    }
+}
+
+/// Virtual camera output using OBS CMIOExtension protocol.
+pub struct VirtualCameraOutput {
+    _config: VirtualCameraConfig,
+    /// None while the extension is gone (e.g. restarting after an OBS update)
+    connection: Option<Connection>,
+    /// Consecutive failed enqueues
+    enqueue_failures: u32,
+    /// Last attempt to reconnect to the extension
+    last_reconnect: Instant,
     pixel_buffer_pool: CVPixelBufferPoolRef,
     format_description: CMFormatDescriptionRef,
     frame_size: usize,
@@ -231,17 +284,7 @@ impl VirtualCameraOutput {
             anyhow!("Failed to acquire virtual camera lock")
         })?;
 
-        // Find OBS Virtual Camera device
-        let device_id = Self::find_obs_device()?;
-        debug!("Found OBS Virtual Camera device: {}", device_id);
-
-        // Get streams for the device
-        let stream_id = Self::get_stream(device_id)?;
-        debug!("Found OBS Virtual Camera stream: {}", stream_id);
-
-        // Get buffer queue
-        let queue = Self::get_queue(stream_id)?;
-        debug!("Got buffer queue");
+        let connection = Connection::open()?;
 
         // Create pixel buffer pool
         let pixel_buffer_pool = Self::create_pixel_buffer_pool(config.width, config.height)?;
@@ -251,12 +294,6 @@ impl VirtualCameraOutput {
         let format_description = Self::create_format_description(config.width, config.height)?;
         debug!("Created format description");
 
-        // Start the stream
-        let result = unsafe { CMIODeviceStartStream(device_id, stream_id) };
-        if result != 0 {
-            return Err(anyhow!("Failed to start OBS Virtual Camera stream (error {})", result));
-        }
-
         let frame_size = (config.width as usize) * (config.height as usize) * 2; // UYVY = 2 bytes/pixel
 
         info!(
@@ -267,9 +304,9 @@ impl VirtualCameraOutput {
 
         Ok(Self {
             _config: config,
-            device_id,
-            stream_id,
-            queue,
+            connection: Some(connection),
+            enqueue_failures: 0,
+            last_reconnect: Instant::now(),
             pixel_buffer_pool,
             format_description,
             frame_size,
@@ -538,11 +575,48 @@ impl VirtualCameraOutput {
         Ok(format_desc)
     }
 
+    /// The stream's buffer queue. While disconnected, looks for the extension again
+    /// at most every `RECONNECT_INTERVAL`.
+    fn queue(&mut self) -> Option<CMSimpleQueueRef> {
+        if self.connection.is_none() && self.last_reconnect.elapsed() >= RECONNECT_INTERVAL {
+            self.last_reconnect = Instant::now();
+            match Connection::open() {
+                Ok(connection) => {
+                    info!("OBS Virtual Camera reconnected, resuming output");
+                    self.connection = Some(connection);
+                    self.enqueue_failures = 0;
+                }
+                Err(e) => debug!("OBS Virtual Camera still unavailable: {}", e),
+            }
+        }
+        self.connection.as_ref().map(|connection| connection.queue)
+    }
+
+    /// Counts a failed enqueue. Enqueues fail silently when the extension restarted,
+    /// so after a run of failures the stream is dropped and looked up again.
+    fn enqueue_failed(&mut self, result: OSStatus) {
+        debug!("Failed to enqueue sample buffer (error {})", result);
+        self.enqueue_failures += 1;
+        if self.enqueue_failures >= MAX_ENQUEUE_FAILURES {
+            warn!(
+                "OBS Virtual Camera stopped accepting frames (error {}), reconnecting...",
+                result
+            );
+            self.connection = None;
+            self.last_reconnect = Instant::now();
+        }
+    }
+
     /// Write a frame to the virtual camera.
     fn write_frame_internal(&mut self, frame: &VideoFrame) -> Result<()> {
-        // Convert frame to UYVY
+        // Frames are dropped while the extension is away
+        let Some(queue) = self.queue() else {
+            return Ok(());
+        };
+
+        // Convert frame to UYVY (unless the pipeline already packed it)
         let uyvy_start = std::time::Instant::now();
-        let uyvy = frame.to_uyvy();
+        let uyvy = super::convert(frame, PixelFormat::Uyvy);
         let uyvy_elapsed = uyvy_start.elapsed();
 
         // Create pixel buffer from pool
@@ -621,15 +695,19 @@ impl VirtualCameraOutput {
 
         // Enqueue the sample buffer
         let enqueue_start = std::time::Instant::now();
-        let result = unsafe { CMSimpleQueueEnqueue(self.queue, sample_buffer) };
+        let result = unsafe { CMSimpleQueueEnqueue(queue, sample_buffer) };
         let enqueue_elapsed = enqueue_start.elapsed();
 
         // Release pixel buffer (sample buffer retains it)
         unsafe { CVPixelBufferRelease(pixel_buffer) };
 
         if result != 0 {
-            return Err(anyhow!("Failed to enqueue sample buffer (error {})", result));
+            // The queue only takes ownership of enqueued buffers
+            unsafe { CFRelease(sample_buffer) };
+            self.enqueue_failed(result);
+            return Ok(());
         }
+        self.enqueue_failures = 0;
 
         debug!("  [Perf] VCam Write - UYVY conv: {:?}, Buffer alloc: {:?}, Copy: {:?}, Enqueue: {:?}", 
                uyvy_elapsed, buffer_elapsed, copy_elapsed, enqueue_elapsed);
@@ -641,9 +719,7 @@ impl VirtualCameraOutput {
 impl Drop for VirtualCameraOutput {
     fn drop(&mut self) {
         // Stop the stream
-        unsafe {
-            CMIODeviceStopStream(self.device_id, self.stream_id);
-        }
+        self.connection = None;
 
         // Release resources
         if !self.format_description.is_null() {
@@ -662,4 +738,9 @@ impl OutputBackend for VirtualCameraOutput {
     fn write_frame(&mut self, frame: &VideoFrame) -> Result<()> {
         self.write_frame_internal(frame)
     }
+
+    // Fixed by the OBS virtual camera protocol
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Uyvy
+    }
 }
@@ -4,11 +4,14 @@
 //! to the OBS Virtual Camera DirectShow filter on Windows.
 
 use super::OutputBackend;
-use crate::frame::VideoFrame;
+use crate::frame::{PixelFormat, VideoFrame};
 use anyhow::{anyhow, Result};
+use std::ffi::c_void;
 use std::ptr;
 use std::sync::atomic::{AtomicU32, Ordering};
-use tracing::{debug, info};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 use windows::core::PCWSTR;
 use windows::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
 use windows::Win32::System::Memory::{
@@ -22,6 +25,10 @@ const VIDEO_NAME: &str = "OBSVirtualCamVideo";
 /// Frame header size for alignment.
 const FRAME_HEADER_SIZE: u32 = 32;
 
+/// How long to wait for consumers to release a queue left by a previous writer.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Queue states matching OBS protocol.
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -78,6 +85,31 @@ fn align_size(size: u32) -> u32 {
     (size + 31) & !31
 }
 
+/// `PUBLIC_OBJECT_BASIC_INFORMATION` from winternl.h.
+#[repr(C)]
+#[derive(Default)]
+struct ObjectBasicInformation {
+    attributes: u32,
+    granted_access: u32,
+    handle_count: u32,
+    pointer_count: u32,
+    reserved: [u32; 10],
+}
+
+/// `ObjectBasicInformation` class of `NtQueryObject`.
+const OBJECT_BASIC_INFORMATION: u32 = 0;
+
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtQueryObject(
+        handle: HANDLE,
+        information_class: u32,
+        information: *mut c_void,
+        information_length: u32,
+        return_length: *mut u32,
+    ) -> i32;
+}
+
 /// Virtual camera output using OBS shared memory protocol.
 pub struct VirtualCameraOutput {
     config: VirtualCameraConfig,
@@ -85,6 +117,14 @@ pub struct VirtualCameraOutput {
     header: *mut QueueHeader,
     frames: [*mut u8; 3],
     timestamps: [*mut u64; 3],
+    /// Frame buffer offsets this output laid out
+    offsets: [u32; 3],
+    /// Last write index this output stored; anything else means another writer
+    write_idx: u32,
+    /// Whether a consumer had the queue open at the last frame
+    reader_attached: bool,
+    /// Whether another writer (e.g. OBS itself) is feeding the queue
+    foreign_writer: bool,
 }
 
 // SAFETY: The shared memory pointers are only accessed from one thread
@@ -95,16 +135,14 @@ impl VirtualCameraOutput {
     ///
     /// This creates the shared memory region that OBS Virtual Camera will read from.
     pub fn new(config: VirtualCameraConfig) -> Result<Self> {
-        // Check if OBS is already using the shared memory
-        let existing = Self::check_existing()?;
-        if existing {
-            return Err(anyhow!(
-                "OBS Virtual Camera shared memory already in use. \
-                Make sure OBS Virtual Camera is not active in OBS Studio."
-            ));
-        }
+        Self::create(config, VIDEO_NAME)
+    }
 
-        let (handle, header, frames, timestamps) = Self::create_shared_memory(&config)?;
+    fn create(config: VirtualCameraConfig, name: &str) -> Result<Self> {
+        let name: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        Self::wait_for_release(&name)?;
+
+        let (handle, header, frames, timestamps, offsets) = Self::create_shared_memory(&config, &name)?;
 
         info!(
             "Virtual camera output created ({}x{} @ {} fps)",
@@ -118,29 +156,65 @@ impl VirtualCameraOutput {
             header,
             frames,
             timestamps,
+            offsets,
+            write_idx: 0,
+            reader_attached: false,
+            foreign_writer: false,
         })
     }
 
-    /// Check if OBS is already using the shared memory.
-    fn check_existing() -> Result<bool> {
-        let name: Vec<u16> = VIDEO_NAME.encode_utf16().chain(std::iter::once(0)).collect();
-
+    /// State of an existing queue, if the shared memory exists.
+    fn existing_state(name: &[u16]) -> Result<Option<u32>> {
         unsafe {
-            let handle = OpenFileMappingW(FILE_MAP_READ.0, false, PCWSTR(name.as_ptr()));
+            let Ok(handle) = OpenFileMappingW(FILE_MAP_READ.0, false, PCWSTR(name.as_ptr())) else {
+                return Ok(None);
+            };
+            let view = MapViewOfFile(handle, FILE_MAP_READ, 0, 0, 0);
+            let state = if view.Value.is_null() {
+                QueueState::Invalid as u32
+            } else {
+                let state = (*(view.Value as *const QueueHeader)).state.load(Ordering::SeqCst);
+                let _ = UnmapViewOfFile(view);
+                state
+            };
+            CloseHandle(handle)?;
+            Ok(Some(state))
+        }
+    }
 
-            if let Ok(h) = handle {
-                CloseHandle(h)?;
-                return Ok(true);
+    /// Fails if OBS is using the shared memory. A queue left stopping by a previous
+    /// writer lingers until its readers notice and close it, so wait for that.
+    fn wait_for_release(name: &[u16]) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            match Self::existing_state(name)? {
+                None => return Ok(()),
+                Some(state) if state == QueueState::Starting as u32 || state == QueueState::Ready as u32 => {
+                    return Err(anyhow!(
+                        "OBS Virtual Camera shared memory already in use. \
+                        Make sure OBS Virtual Camera is not active in OBS Studio."
+                    ));
+                }
+                Some(_) if start.elapsed() >= RELEASE_TIMEOUT => {
+                    return Err(anyhow!(
+                        "OBS Virtual Camera shared memory is still held by an application. \
+                        Close the application using the camera and try again."
+                    ));
+                }
+                Some(_) => {
+                    debug!("Waiting for the previous virtual camera queue to be released");
+                    thread::sleep(RELEASE_POLL_INTERVAL);
+                }
             }
         }
-
-        Ok(false)
     }
 
     /// Create the shared memory region.
+    #[allow(clippy::type_complexity)]
     fn create_shared_memory(
         config: &VirtualCameraConfig,
-    ) -> Result<(HANDLE, *mut QueueHeader, [*mut u8; 3], [*mut u64; 3])> {
+        name: &[u16],
+    ) -> Result<(HANDLE, *mut QueueHeader, [*mut u8; 3], [*mut u64; 3], [u32; 3])> {
         // Calculate NV12 frame size: Y plane + UV plane (half height)
         let frame_size = config.width * config.height * 3 / 2;
 
@@ -156,8 +230,6 @@ impl VirtualCameraOutput {
         }
 
         // Create the shared memory
-        let name: Vec<u16> = VIDEO_NAME.encode_utf16().chain(std::iter::once(0)).collect();
-
         let handle = unsafe {
             CreateFileMappingW(
                 INVALID_HANDLE_VALUE,
@@ -180,20 +252,11 @@ impl VirtualCameraOutput {
         }
 
         let header = view.Value as *mut QueueHeader;
-
-        // Initialize the header
         unsafe {
             (*header).write_idx = AtomicU32::new(0);
             (*header).read_idx = AtomicU32::new(0);
-            (*header).state = AtomicU32::new(QueueState::Starting as u32);
-            (*header).offsets = offset_frame;
-            (*header).queue_type = 0; // Video
-            (*header).cx = config.width;
-            (*header).cy = config.height;
-            // Interval in 100ns units (10,000,000 / fps)
-            (*header).interval = 10_000_000 / config.fps as u64;
-            (*header).reserved = [0; 8];
         }
+        Self::init_header(header, config, offset_frame);
 
         // Get pointers to frame buffers
         let base = view.Value as *mut u8;
@@ -210,29 +273,118 @@ impl VirtualCameraOutput {
 
         debug!("Shared memory created: {} bytes", size);
 
-        Ok((handle, header, frames, timestamps))
+        Ok((handle, header, frames, timestamps, offset_frame))
+    }
+
+    /// Lays out the queue for our frames. Readers wait until the next frame; the
+    /// indices are kept, so open readers don't mistake it for one they already read.
+    fn init_header(header: *mut QueueHeader, config: &VirtualCameraConfig, offsets: [u32; 3]) {
+        unsafe {
+            (*header).state = AtomicU32::new(QueueState::Starting as u32);
+            (*header).offsets = offsets;
+            (*header).queue_type = 0; // Video
+            (*header).cx = config.width;
+            (*header).cy = config.height;
+            // Interval in 100ns units (10,000,000 / fps)
+            (*header).interval = 10_000_000 / config.fps as u64;
+            (*header).reserved = [0; 8];
+        }
+    }
+
+    /// Number of consumers that have the shared memory open. OBS's DirectShow filter
+    /// keeps it open while an application uses the camera.
+    fn reader_count(&self) -> Option<u32> {
+        let mut info = ObjectBasicInformation::default();
+        let status = unsafe {
+            NtQueryObject(
+                self.handle,
+                OBJECT_BASIC_INFORMATION,
+                &mut info as *mut _ as *mut c_void,
+                std::mem::size_of::<ObjectBasicInformation>() as u32,
+                ptr::null_mut(),
+            )
+        };
+        // Our own handle is one of them
+        (status >= 0).then(|| info.handle_count.saturating_sub(1))
+    }
+
+    /// Tracks consumers attaching and detaching. Returns whether one is attached.
+    fn update_reader(&mut self) -> bool {
+        // Assume a reader if the handle count is unavailable
+        let attached = self.reader_count().is_none_or(|readers| readers > 0);
+        if attached != self.reader_attached {
+            if attached {
+                info!("Virtual camera consumer connected");
+            } else {
+                info!("Virtual camera consumer disconnected");
+            }
+            self.reader_attached = attached;
+        }
+        attached
+    }
+
+    /// Detects another writer (OBS's own virtual camera opens the same shared memory)
+    /// and takes the queue back once it stops. Returns whether this output owns the queue.
+    fn claim_queue(&mut self) -> bool {
+        let header = unsafe { &*self.header };
+        let write_idx = header.write_idx.load(Ordering::SeqCst);
+        let state = header.state.load(Ordering::SeqCst);
+        let foreign = write_idx != self.write_idx
+            || header.offsets != self.offsets
+            || header.cx != self.config.width
+            || header.cy != self.config.height
+            || state == QueueState::Stopping as u32
+            || state == QueueState::Invalid as u32;
+        if !foreign {
+            return true;
+        }
+
+        if state == QueueState::Starting as u32 || state == QueueState::Ready as u32 {
+            if !self.foreign_writer {
+                warn!("Another application is writing to the OBS Virtual Camera, pausing output");
+                self.foreign_writer = true;
+            }
+            self.write_idx = write_idx;
+            return false;
+        }
+
+        // The other writer stopped (or reset the queue): lay it out for our frames again
+        if self.foreign_writer {
+            info!("OBS Virtual Camera released, resuming output");
+            self.foreign_writer = false;
+        }
+        Self::init_header(self.header, &self.config, self.offsets);
+        self.write_idx = write_idx;
+        true
     }
 
     /// Write a frame to the shared memory queue.
     fn write_frame_internal(&mut self, frame: &VideoFrame) -> Result<()> {
-        // Convert to NV12
+        if !self.claim_queue() || !self.update_reader() {
+            return Ok(());
+        }
+
+        // Convert to NV12 (unless the pipeline already packed it)
         let nv12_start = std::time::Instant::now();
-        let nv12 = frame.to_nv12();
+        let nv12 = super::convert(frame, PixelFormat::Nv12);
         let nv12_elapsed = nv12_start.elapsed();
 
+        // Our own layout, not the header's: another writer may have changed it
+        let y_size = self.config.width as usize * self.config.height as usize;
+        let uv_size = y_size / 2;
+        if nv12.data.len() < y_size + uv_size {
+            return Err(anyhow!(
+                "Frame is {}x{}, virtual camera is {}x{}",
+                frame.width, frame.height, self.config.width, self.config.height
+            ));
+        }
+
         // Get current write index and advance
         let header = unsafe { &*self.header };
         let inc = header.write_idx.fetch_add(1, Ordering::SeqCst) + 1;
+        self.write_idx = inc;
         let idx = (inc % 3) as usize;
 
-        // Get frame dimensions from header
-        let cx = header.cx as usize;
-        let cy = header.cy as usize;
-
-        // Calculate sizes
-        let y_size = cx * cy;
-        let uv_size = y_size / 2;
-
         // Write timestamp
         let timestamp = frame.timestamp_us.unwrap_or(0) * 10; // Convert to 100ns
         unsafe {
@@ -267,11 +419,13 @@ impl VirtualCameraOutput {
 
 impl Drop for VirtualCameraOutput {
     fn drop(&mut self) {
-        // Signal stopping
-        unsafe {
-            (*self.header)
-                .state
-                .store(QueueState::Stopping as u32, Ordering::SeqCst);
+        // Signal stopping, unless another writer owns the queue now
+        if !self.foreign_writer {
+            unsafe {
+                (*self.header)
+                    .state
+                    .store(QueueState::Stopping as u32, Ordering::SeqCst);
+            }
         }
 
         // Unmap and close
@@ -291,4 +445,159 @@ impl OutputBackend for VirtualCameraOutput {
     fn write_frame(&mut self, frame: &VideoFrame) -> Result<()> {
         self.write_frame_internal(frame)
     }
+
+    // Fixed by the OBS virtual camera protocol
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Nv12
+    }
+
+    fn has_consumer(&mut self) -> Option<bool> {
+        self.reader_count().map(|readers| readers > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 32;
+
+    fn config() -> VirtualCameraConfig {
+        VirtualCameraConfig { width: WIDTH, height: HEIGHT, fps: 30 }
+    }
+
+    /// An NV12 frame with every byte set to `value`.
+    fn frame(value: u8) -> VideoFrame {
+        VideoFrame::from_data(WIDTH, HEIGHT, PixelFormat::Nv12, vec![value; (WIDTH * HEIGHT * 3 / 2) as usize])
+    }
+
+    /// Opens the queue like the OBS Virtual Camera filter (shared-memory-queue.c).
+    struct MockReader {
+        handle: HANDLE,
+        header: *mut QueueHeader,
+        last_inc: u32,
+    }
+
+    // SAFETY: Only used from one thread at a time
+    unsafe impl Send for MockReader {}
+
+    impl MockReader {
+        fn open(name: &str) -> Self {
+            let name: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+            unsafe {
+                let handle = OpenFileMappingW(FILE_MAP_ALL_ACCESS.0, false, PCWSTR(name.as_ptr())).unwrap();
+                let view = MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, 0);
+                assert!(!view.Value.is_null());
+                Self { handle, header: view.Value as *mut QueueHeader, last_inc: 0 }
+            }
+        }
+
+        fn header(&self) -> &QueueHeader {
+            unsafe { &*self.header }
+        }
+
+        fn state(&self) -> u32 {
+            self.header().state.load(Ordering::SeqCst)
+        }
+
+        /// The first byte of a new frame, if one was written since the last read.
+        fn read(&mut self) -> Option<u8> {
+            if self.state() != QueueState::Ready as u32 {
+                return None;
+            }
+            let inc = self.header().read_idx.load(Ordering::SeqCst);
+            if inc == self.last_inc {
+                return None;
+            }
+            self.last_inc = inc;
+            let offset = self.header().offsets[(inc % 3) as usize] + FRAME_HEADER_SIZE;
+            Some(unsafe { *(self.header as *const u8).add(offset as usize) })
+        }
+
+        /// Writes to the queue like another OBS writer would.
+        fn write_foreign(&self, state: QueueState) {
+            self.header().write_idx.fetch_add(1, Ordering::SeqCst);
+            self.header().state.store(state as u32, Ordering::SeqCst);
+        }
+    }
+
+    impl Drop for MockReader {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS { Value: self.header as *mut _ });
+                let _ = CloseHandle(self.handle);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_reconnect() {
+        let name = "ProteusTestVideoReconnect";
+        let mut output = VirtualCameraOutput::create(config(), name).unwrap();
+
+        // Nothing is written without a consumer
+        output.write_frame(&frame(1)).unwrap();
+        let mut reader = MockReader::open(name);
+        assert_eq!(reader.read(), None);
+
+        output.write_frame(&frame(2)).unwrap();
+        assert_eq!(reader.read(), Some(2));
+        assert_eq!(reader.read(), None);
+        drop(reader);
+        output.write_frame(&frame(3)).unwrap();
+        assert!(!output.reader_attached);
+
+        // A consumer attaching again gets the next frame
+        let mut reader = MockReader::open(name);
+        output.write_frame(&frame(4)).unwrap();
+        assert_eq!(reader.read(), Some(4));
+
+        drop(output);
+        assert_eq!(reader.state(), QueueState::Stopping as u32);
+    }
+
+    #[test]
+    fn test_restart_with_lingering_reader() {
+        let name = "ProteusTestVideoRestart";
+        let output = VirtualCameraOutput::create(config(), name).unwrap();
+        let reader = MockReader::open(name);
+
+        // The reader closes the queue once it sees the writer stopping, like the OBS filter
+        let reader_thread = thread::spawn(move || {
+            while reader.state() != QueueState::Stopping as u32 {
+                thread::sleep(Duration::from_millis(10));
+            }
+            thread::sleep(Duration::from_millis(200));
+            drop(reader);
+        });
+        drop(output);
+
+        let mut output = VirtualCameraOutput::create(config(), name).unwrap();
+        reader_thread.join().unwrap();
+        let mut reader = MockReader::open(name);
+        output.write_frame(&frame(5)).unwrap();
+        assert_eq!(reader.read(), Some(5));
+    }
+
+    #[test]
+    fn test_foreign_writer() {
+        let name = "ProteusTestVideoForeign";
+        let mut output = VirtualCameraOutput::create(config(), name).unwrap();
+        let mut reader = MockReader::open(name);
+        output.write_frame(&frame(6)).unwrap();
+        assert_eq!(reader.read(), Some(6));
+
+        // OBS starts its own virtual camera on the same shared memory: pause
+        reader.write_foreign(QueueState::Ready);
+        let read_idx = reader.header().read_idx.load(Ordering::SeqCst);
+        output.write_frame(&frame(7)).unwrap();
+        assert_eq!(reader.header().read_idx.load(Ordering::SeqCst), read_idx);
+
+        // ...and take the queue back once it stops
+        reader.write_foreign(QueueState::Stopping);
+        output.write_frame(&frame(8)).unwrap();
+        assert_eq!(reader.header().state.load(Ordering::SeqCst), QueueState::Ready as u32);
+        assert_eq!(reader.read(), Some(8));
+    }
 }
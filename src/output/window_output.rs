@@ -1,13 +1,15 @@
 //! Window output backend using winit and wgpu.
 
-use super::OutputBackend;
+use super::{OutputBackend, OutputCapabilities};
 use crate::frame::{QuadVertex, VideoFrame};
 use anyhow::Result;
 use std::borrow::Cow;
 use std::sync::Arc;
 use crate::shader::gpu_context::GpuContext;
+use crate::shader::{Calibration, CalibrationBinding, CALIBRATION_SHADER};
 use wgpu::util::DeviceExt;
 use winit::dpi::PhysicalSize;
+use tracing::{info, warn};
 use winit::window::Window;
 
 /// Vertex shader for window rendering.
@@ -31,17 +33,95 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 }
 "#;
 
-/// Fragment shader for window rendering.
+/// Fragment shader for window rendering (draws the composition guides on top of
+/// the calibrated frame; appended to `CALIBRATION_SHADER`).
 const FRAGMENT_SHADER: &str = r#"
+struct WindowUniforms {
+    guides: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
 @group(0) @binding(0) var t_texture: texture_2d<f32>;
 @group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_window: WindowUniforms;
+
+const GUIDE_THIRDS: u32 = 1u;
+const GUIDE_CENTER: u32 = 2u;
+const GUIDE_SAFE_AREA: u32 = 4u;
+
+// 1.0 within a pixel of the line at `pos`
+fn guide_line(coord: f32, pos: f32, px: f32) -> f32 {
+    return step(abs(coord - pos), px);
+}
+
+// Outline of the rectangle inset by `inset` from the frame edges
+fn guide_rect(uv: vec2<f32>, inset: f32, px: vec2<f32>) -> f32 {
+    let inside = all(uv >= vec2<f32>(inset) - px) && all(uv <= vec2<f32>(1.0 - inset) + px);
+    let edge = max(
+        max(guide_line(uv.x, inset, px.x), guide_line(uv.x, 1.0 - inset, px.x)),
+        max(guide_line(uv.y, inset, px.y), guide_line(uv.y, 1.0 - inset, px.y)),
+    );
+    return select(0.0, edge, inside);
+}
 
 @fragment
 fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
-    return textureSample(t_texture, s_sampler, tex_coords);
+    let sampled = textureSample(t_texture, s_sampler, tex_coords);
+    let color = vec4<f32>(calibrate(sampled.rgb), sampled.a);
+    let uv = tex_coords;
+    // Size of one window pixel in texture coordinates
+    let px = fwidth(uv);
+
+    var guide = 0.0;
+    if ((u_window.guides & GUIDE_THIRDS) != 0u) {
+        guide = max(guide, max(
+            max(guide_line(uv.x, 1.0 / 3.0, px.x), guide_line(uv.x, 2.0 / 3.0, px.x)),
+            max(guide_line(uv.y, 1.0 / 3.0, px.y), guide_line(uv.y, 2.0 / 3.0, px.y)),
+        ));
+    }
+    if ((u_window.guides & GUIDE_CENTER) != 0u) {
+        // Arms of 4% of the frame height in both directions
+        let d = abs(uv - 0.5);
+        let arm = 0.04;
+        let vertical = d.x <= px.x && d.y < arm;
+        let horizontal = d.y <= px.y && d.x < arm * px.x / px.y;
+        guide = max(guide, select(0.0, 1.0, vertical || horizontal));
+    }
+    if ((u_window.guides & GUIDE_SAFE_AREA) != 0u) {
+        // Action safe (93%) and title safe (90%)
+        guide = max(guide, max(guide_rect(uv, 0.035, px), guide_rect(uv, 0.05, px)));
+    }
+
+    return vec4<f32>(mix(color.rgb, vec3<f32>(1.0), guide * 0.6), color.a);
 }
 "#;
 
+/// Composition guides drawn over the preview window (never part of the output).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Guides {
+    /// Rule-of-thirds grid
+    pub thirds: bool,
+    /// Center cross
+    pub center: bool,
+    /// Action safe (93%) and title safe (90%) areas
+    pub safe_area: bool,
+}
+
+impl Guides {
+    /// All guides enabled.
+    pub const ALL: Guides = Guides { thirds: true, center: true, safe_area: true };
+
+    pub fn is_empty(&self) -> bool {
+        !(self.thirds || self.center || self.safe_area)
+    }
+
+    fn bits(&self) -> u32 {
+        self.thirds as u32 | (self.center as u32) << 1 | (self.safe_area as u32) << 2
+    }
+}
+
 /// Configuration for window output.
 pub struct WindowConfig {
     pub title: String,
@@ -59,16 +139,39 @@ impl Default for WindowConfig {
     }
 }
 
+/// How frames are presented to the preview window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Waits for the vertical blank; never tears, but some compositors add a frame of latency
+    #[default]
+    AutoVsync,
+    /// Replaces a waiting frame with a newer one; no tearing, less latency (not available everywhere)
+    Mailbox,
+    /// Shows frames right away; lowest latency, may tear
+    Immediate,
+}
+
+/// Largest number of frames queued for the preview window.
+pub const MAX_FRAME_LATENCY: u32 = 3;
+
 /// Handles window rendering state.
 pub struct WindowRenderer {
     surface: wgpu::Surface<'static>,
     context: Arc<GpuContext>,
     config: wgpu::SurfaceConfiguration,
+    /// Present modes the surface supports
+    present_modes: Vec<wgpu::PresentMode>,
     render_pipeline: wgpu::RenderPipeline,
     vertex_buffer: wgpu::Buffer,
+    mirrored_vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     bind_group_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    guides: Guides,
+    calibration: CalibrationBinding,
+    // Show the frame horizontally mirrored, like a mirror
+    mirror: bool,
     window: Arc<Window>,
     current_frame: Option<VideoFrame>,
 }
@@ -111,7 +214,7 @@ impl WindowRenderer {
 
         let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Window Fragment Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(FRAGMENT_SHADER)),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!("{}{}", CALIBRATION_SHADER, FRAGMENT_SHADER))),
         });
 
         // Create bind group layout
@@ -134,12 +237,27 @@ impl WindowRenderer {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
+        // Without an sRGB surface format the shader has to encode
+        if !surface_format.is_srgb() {
+            info!("Preview surface format {:?} is not sRGB, encoding in the shader", surface_format);
+        }
+        let calibration = CalibrationBinding::new(device, &context.queue, surface_format.is_srgb());
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Window Render Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, calibration.bind_group_layout()],
             immediate_size: 0,
         });
 
@@ -183,6 +301,12 @@ impl WindowRenderer {
             usage: wgpu::BufferUsages::VERTEX,
         });
 
+        let mirrored_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Window Mirrored Vertex Buffer"),
+            contents: bytemuck::cast_slice(QuadVertex::MIRRORED_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Window Index Buffer"),
             contents: bytemuck::cast_slice(QuadVertex::INDICES),
@@ -199,25 +323,81 @@ impl WindowRenderer {
             ..Default::default()
         });
 
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Window Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[0u32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
         Ok(Self {
             surface,
             context,
             config,
+            present_modes: surface_caps.present_modes,
             render_pipeline,
             vertex_buffer,
+            mirrored_vertex_buffer,
             index_buffer,
             bind_group_layout,
             sampler,
+            uniform_buffer,
+            guides: Guides::default(),
+            calibration,
+            mirror: false,
             window,
             current_frame: None,
         })
     }
 
+    /// Sets the composition guides drawn over the window.
+    pub fn set_guides(&mut self, guides: Guides) {
+        self.guides = guides;
+        self.context.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[guides.bits(), 0, 0, 0]));
+    }
+
+    pub fn guides(&self) -> Guides {
+        self.guides
+    }
+
+    /// Sets (or removes) the color calibration of the window, independent of
+    /// the output calibration.
+    pub fn set_calibration(&mut self, calibration: Option<Calibration>) -> Result<()> {
+        self.calibration.set(&self.context.device, &self.context.queue, calibration)
+    }
+
+    /// Shows the frame horizontally mirrored (preview only).
+    pub fn set_mirror(&mut self, mirror: bool) {
+        self.mirror = mirror;
+    }
+
     /// Updates the current frame to display.
     pub fn set_frame(&mut self, frame: VideoFrame) {
         self.current_frame = Some(frame);
     }
 
+    /// Sets how frames are presented and how many may be queued (1 - `MAX_FRAME_LATENCY`).
+    /// Unsupported modes fall back to v-sync.
+    pub fn set_present_mode(&mut self, mode: PresentMode, max_frame_latency: u32) {
+        let present_mode = match mode {
+            PresentMode::AutoVsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        };
+        let present_mode = if present_mode == wgpu::PresentMode::AutoVsync || self.present_modes.contains(&present_mode) {
+            present_mode
+        } else {
+            warn!("Present mode {:?} is not supported by the preview window, using v-sync", mode);
+            wgpu::PresentMode::AutoVsync
+        };
+        let max_frame_latency = max_frame_latency.clamp(1, MAX_FRAME_LATENCY);
+        if present_mode != self.config.present_mode || max_frame_latency != self.config.desired_maximum_frame_latency {
+            self.config.present_mode = present_mode;
+            self.config.desired_maximum_frame_latency = max_frame_latency;
+            self.surface.configure(&self.context.device, &self.config);
+            info!("Preview presents with {:?}, up to {} queued frame(s)", present_mode, max_frame_latency);
+        }
+    }
+
     /// Resizes the surface.
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
@@ -240,7 +420,7 @@ impl WindowRenderer {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        // Create texture from frame
+        // Create texture from frame (sRGB-encoded, so sampling decodes it like the pipeline's output)
         let texture = self.context.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Frame Texture"),
             size: wgpu::Extent3d {
@@ -251,7 +431,7 @@ impl WindowRenderer {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
@@ -290,6 +470,10 @@ impl WindowRenderer {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&self.sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
             ],
         });
 
@@ -317,9 +501,11 @@ impl WindowRenderer {
                 multiview_mask: None,
             });
 
+            let vertex_buffer = if self.mirror { &self.mirrored_vertex_buffer } else { &self.vertex_buffer };
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_bind_group(1, self.calibration.bind_group(), &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..6, 0, 0..1);
         }
@@ -349,6 +535,10 @@ impl WindowRenderer {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&self.sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
             ],
         });
 
@@ -376,9 +566,11 @@ impl WindowRenderer {
                 multiview_mask: None,
             });
 
+            let vertex_buffer = if self.mirror { &self.mirrored_vertex_buffer } else { &self.vertex_buffer };
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_bind_group(1, self.calibration.bind_group(), &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..6, 0, 0..1);
         }
@@ -395,6 +587,21 @@ impl WindowRenderer {
     }
 }
 
+impl OutputBackend for WindowRenderer {
+    fn write_frame(&mut self, frame: &VideoFrame) -> Result<()> {
+        self.set_frame(frame.clone());
+        self.render()
+    }
+
+    fn write_texture(&mut self, view: &wgpu::TextureView) -> Result<()> {
+        self.render_texture(view)
+    }
+
+    fn capabilities(&self) -> OutputCapabilities {
+        OutputCapabilities::CPU_AND_GPU
+    }
+}
+
 /// Window output backend.
 pub struct WindowOutput {
     renderer: Option<WindowRenderer>,
@@ -419,10 +626,20 @@ impl WindowOutput {
 
 impl OutputBackend for WindowOutput {
     fn write_frame(&mut self, frame: &VideoFrame) -> Result<()> {
-        if let Some(renderer) = &mut self.renderer {
-            renderer.set_frame(frame.clone());
-            renderer.render()?;
+        match &mut self.renderer {
+            Some(renderer) => renderer.write_frame(frame),
+            None => Ok(()),
         }
-        Ok(())
+    }
+
+    fn write_texture(&mut self, view: &wgpu::TextureView) -> Result<()> {
+        match &mut self.renderer {
+            Some(renderer) => renderer.write_texture(view),
+            None => Ok(()),
+        }
+    }
+
+    fn capabilities(&self) -> OutputCapabilities {
+        OutputCapabilities::CPU_AND_GPU
     }
 }
@@ -0,0 +1,86 @@
+//! Platform-standard directories.
+//!
+//! Relative paths in a config (shaders, textures, models, ...) that don't exist
+//! in the working directory are looked up next to the config file, then in the
+//! user config directory: `$XDG_CONFIG_HOME/proteus` on Linux,
+//! `~/Library/Application Support/proteus` on macOS and `%APPDATA%\proteus` on
+//! Windows. Caches (e.g. TensorRT engines) go to the user cache directory.
+//! `proteus paths` prints the directories in use.
+
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "proteus";
+
+/// An absolute directory from an environment variable (relative ones are ignored, as XDG requires).
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name).map(PathBuf::from).filter(|dir| dir.is_absolute())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn home_dir() -> Option<PathBuf> {
+    env_dir("HOME")
+}
+
+/// User configuration directory.
+pub fn config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let base = env_dir("XDG_CONFIG_HOME").or_else(|| Some(home_dir()?.join(".config")));
+    #[cfg(target_os = "macos")]
+    let base = home_dir().map(|home| home.join("Library/Application Support"));
+    #[cfg(target_os = "windows")]
+    let base = env_dir("APPDATA");
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    let base: Option<PathBuf> = None;
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+/// User cache directory.
+pub fn cache_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let base = env_dir("XDG_CACHE_HOME").or_else(|| Some(home_dir()?.join(".cache")));
+    #[cfg(target_os = "macos")]
+    let base = home_dir().map(|home| home.join("Library/Caches"));
+    // Next to the other local app data, as Windows has no separate cache location
+    #[cfg(target_os = "windows")]
+    let base = env_dir("LOCALAPPDATA");
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    let base: Option<PathBuf> = None;
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+/// Directories searched for relative paths after the working directory.
+fn search_dirs(config_file: Option<&Path>) -> Vec<PathBuf> {
+    let config_file_dir = config_file
+        .and_then(Path::parent)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf);
+    config_file_dir.into_iter().chain(config_dir()).collect()
+}
+
+/// Resolves a relative path that doesn't exist in the working directory against
+/// the directory of `config_file` and the user config directory. Paths that
+/// aren't found anywhere (and URLs) are returned unchanged.
+pub fn resolve(path: &Path, config_file: Option<&Path>) -> PathBuf {
+    if path.is_absolute() || path.exists() {
+        return path.to_path_buf();
+    }
+    search_dirs(config_file)
+        .into_iter()
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Prints the directories for `proteus paths`.
+pub fn print() {
+    let describe = |dir: Option<PathBuf>| match dir {
+        Some(dir) if dir.is_dir() => dir.display().to_string(),
+        Some(dir) => format!("{} (does not exist)", dir.display()),
+        None => "unavailable".to_string(),
+    };
+    println!("Config directory: {}", describe(config_dir()));
+    println!("Cache directory:  {}", describe(cache_dir()));
+    println!();
+    println!("Relative paths are looked up in the working directory, next to the config file,");
+    println!("and then in the config directory.");
+}
@@ -0,0 +1,143 @@
+//! QR code detection.
+//!
+//! Scans frames for QR codes on a background thread at a low rate and reports
+//! each decoded payload once when it appears. Payloads are passed to Lua
+//! scripts (`on_qr`) and can be mapped to control commands in the config.
+
+use crate::frame::VideoFrame;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Frames wider than this are subsampled before scanning.
+const MAX_SCAN_WIDTH: u32 = 1280;
+
+/// QR scanner settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QrSettings {
+    /// Time between scans
+    pub interval: Duration,
+    /// How long a code must be out of view before it is reported again
+    pub cooldown: Duration,
+}
+
+impl Default for QrSettings {
+    fn default() -> Self {
+        Self { interval: Duration::from_millis(500), cooldown: Duration::from_secs(5) }
+    }
+}
+
+/// Background QR scanner.
+pub struct QrScanner {
+    settings: QrSettings,
+    frame_tx: SyncSender<VideoFrame>,
+    result_rx: Receiver<Vec<String>>,
+    last_scan: Option<Instant>,
+    /// When each payload was last seen
+    seen: HashMap<String, Instant>,
+}
+
+impl QrScanner {
+    pub fn new(settings: QrSettings) -> Self {
+        let (frame_tx, frame_rx) = mpsc::sync_channel::<VideoFrame>(1);
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            info!("QR scanner thread started");
+            while let Ok(frame) = frame_rx.recv() {
+                let start = Instant::now();
+                let payloads = scan(&frame);
+                debug!("QR scan: {} code(s) in {:?}", payloads.len(), start.elapsed());
+                if result_tx.send(payloads).is_err() {
+                    break;
+                }
+            }
+            info!("QR scanner thread exiting");
+        });
+
+        Self { settings, frame_tx, result_rx, last_scan: None, seen: HashMap::new() }
+    }
+
+    pub fn settings(&self) -> QrSettings {
+        self.settings
+    }
+
+    /// Returns true when the next scan is due, so the caller only copies
+    /// frames that are actually scanned.
+    pub fn wants_frame(&self, now: Instant) -> bool {
+        self.last_scan.is_none_or(|last| now.duration_since(last) >= self.settings.interval)
+    }
+
+    /// Hands a frame (RGBA) to the scanner thread. Dropped if a scan is still running.
+    pub fn submit(&mut self, frame: VideoFrame, now: Instant) {
+        if self.frame_tx.try_send(frame).is_ok() {
+            self.last_scan = Some(now);
+        }
+    }
+
+    /// Returns the payloads that came into view since the last call.
+    pub fn poll(&mut self, now: Instant) -> Vec<String> {
+        let payloads: Vec<String> = self.result_rx.try_iter().flatten().collect();
+        self.newly_seen(payloads, now)
+    }
+
+    /// Filters out payloads that were already in view within the cooldown.
+    fn newly_seen(&mut self, payloads: Vec<String>, now: Instant) -> Vec<String> {
+        let mut new = Vec::new();
+        for payload in payloads {
+            let reappeared = self.seen.get(&payload)
+                .is_none_or(|&last| now.duration_since(last) >= self.settings.cooldown);
+            if reappeared && !new.contains(&payload) {
+                new.push(payload.clone());
+            }
+            self.seen.insert(payload, now);
+        }
+        let cooldown = self.settings.cooldown;
+        self.seen.retain(|_, &mut last| now.duration_since(last) < cooldown);
+        new
+    }
+}
+
+/// Decodes all QR codes in an RGBA frame.
+fn scan(frame: &VideoFrame) -> Vec<String> {
+    let step = frame.width.div_ceil(MAX_SCAN_WIDTH).max(1) as usize;
+    let (width, height) = (frame.width as usize / step, frame.height as usize / step);
+    let stride = frame.width as usize * 4;
+    let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| {
+        let i = y * step * stride + x * step * 4;
+        let (r, g, b) = (frame.data[i] as u32, frame.data[i + 1] as u32, frame.data[i + 2] as u32);
+        ((r * 77 + g * 150 + b * 29) >> 8) as u8
+    });
+    image
+        .detect_grids()
+        .iter()
+        .filter_map(|grid| match grid.decode() {
+            Ok((_, payload)) => Some(payload),
+            Err(e) => {
+                debug!("QR code found but not decoded: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newly_seen() {
+        let mut scanner = QrScanner::new(QrSettings { interval: Duration::ZERO, cooldown: Duration::from_secs(5) });
+        let start = Instant::now();
+        let codes = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(scanner.newly_seen(codes(&["a", "a"]), start), codes(&["a"]));
+        // Still in view: not reported again
+        assert!(scanner.newly_seen(codes(&["a"]), start + Duration::from_secs(4)).is_empty());
+        assert_eq!(scanner.newly_seen(codes(&["a", "b"]), start + Duration::from_secs(8)), codes(&["b"]));
+        // Out of view for longer than the cooldown
+        assert_eq!(scanner.newly_seen(codes(&["a"]), start + Duration::from_secs(14)), codes(&["a"]));
+    }
+}
@@ -0,0 +1,247 @@
+//! Short animated clips of the processed output, for sharing an effect.
+//!
+//! Frames are taken at a capped frame rate, scaled down on a background
+//! thread and encoded as a looping GIF or APNG once the clip is complete.
+//! Repeated frames are merged into one longer frame, and GIF frames only
+//! store the pixels that changed, which keeps the files small.
+
+use crate::frame::VideoFrame;
+use anyhow::{anyhow, bail, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
+
+/// NeuQuant speed of the GIF palettes (1 - 30, higher is faster)
+const GIF_SPEED: i32 = 10;
+/// Channel difference up to which a GIF pixel counts as unchanged
+const GIF_TOLERANCE: u8 = 3;
+
+/// Caps of recorded clips.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipSettings {
+    /// Frames per second
+    pub fps: u32,
+    /// Frames are scaled down to this width
+    pub max_width: u32,
+    /// Longest clip in seconds
+    pub max_duration: f32,
+}
+
+impl Default for ClipSettings {
+    fn default() -> Self {
+        Self { fps: 15, max_width: 480, max_duration: 10.0 }
+    }
+}
+
+/// Animation format, chosen from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipFormat {
+    Gif,
+    Apng,
+}
+
+impl ClipFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("gif") => Ok(ClipFormat::Gif),
+            Some("png" | "apng") => Ok(ClipFormat::Apng),
+            _ => bail!("Unsupported clip format {:?} (expected .gif, .png or .apng)", path),
+        }
+    }
+}
+
+/// Size of a frame scaled down to at most `max_width`, keeping the aspect ratio.
+fn clip_size(width: u32, height: u32, max_width: u32) -> (u32, u32) {
+    if width <= max_width.max(1) {
+        return (width, height);
+    }
+    let scale = max_width.max(1) as f32 / width as f32;
+    (max_width.max(1), ((height as f32 * scale).round() as u32).max(1))
+}
+
+/// Merges runs of identical frames; returns each distinct frame with the number of frames it lasts.
+fn merge_repeats(frames: Vec<RgbaImage>) -> Vec<(RgbaImage, u32)> {
+    let mut merged: Vec<(RgbaImage, u32)> = Vec::new();
+    for frame in frames {
+        match merged.last_mut() {
+            Some((last, count)) if *last == frame => *count += 1,
+            _ => merged.push((frame, 1)),
+        }
+    }
+    merged
+}
+
+/// Makes the pixels of `frame` that still look like `shown` transparent, so
+/// the GIF keeps showing the previous frame there, and records the rest in `shown`.
+fn keep_unchanged(frame: &mut RgbaImage, shown: &mut RgbaImage) {
+    for (pixel, shown) in frame.pixels_mut().zip(shown.pixels_mut()) {
+        let unchanged = (0..3).all(|c| pixel[c].abs_diff(shown[c]) <= GIF_TOLERANCE);
+        if unchanged {
+            pixel.0 = [0, 0, 0, 0];
+        } else {
+            pixel[3] = 255;
+            *shown = *pixel;
+        }
+    }
+}
+
+fn encode_gif(path: &Path, frames: Vec<(RgbaImage, u32)>, fps: u32) -> Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let mut shown: Option<RgbaImage> = None;
+    for (mut image, count) in frames {
+        match &mut shown {
+            Some(shown) => keep_unchanged(&mut image, shown),
+            None => {
+                image.pixels_mut().for_each(|pixel| pixel[3] = 255);
+                shown = Some(image.clone());
+            }
+        }
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(1000 * count, fps)))?;
+    }
+    Ok(())
+}
+
+fn encode_apng(path: &Path, frames: Vec<(RgbaImage, u32)>, fps: u32) -> Result<()> {
+    let (width, height) = frames.first().map(|(image, _)| image.dimensions()).ok_or_else(|| anyhow!("No frames"))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::High);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let mut writer = encoder.write_header()?;
+    let fps = u16::try_from(fps).unwrap_or(u16::MAX);
+    for (image, count) in frames {
+        writer.set_frame_delay(u16::try_from(count).unwrap_or(u16::MAX), fps)?;
+        writer.write_image_data(image.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Records a clip of a fixed length.
+pub struct ClipRecorder {
+    path: PathBuf,
+    interval: Duration,
+    end: Instant,
+    next_frame: Instant,
+    /// Sender for the frames; dropped to encode the clip
+    frame_tx: Option<SyncSender<VideoFrame>>,
+}
+
+impl ClipRecorder {
+    /// Starts a clip of `seconds` (capped by `settings`) at `path`; the format is chosen from
+    /// the file extension.
+    pub fn start(path: &Path, seconds: f32, settings: &ClipSettings, now: Instant) -> Result<Self> {
+        let format = ClipFormat::from_path(path)?;
+        anyhow::ensure!(seconds > 0.0, "Clip length must be positive");
+        let seconds = seconds.min(settings.max_duration.max(0.1));
+        let fps = settings.fps.clamp(1, 50);
+        let max_width = settings.max_width;
+
+        // Frames are scaled on the worker; the clip is encoded once the sender is dropped
+        let (frame_tx, frame_rx) = mpsc::sync_channel::<VideoFrame>(4);
+        let output = path.to_path_buf();
+        thread::spawn(move || {
+            let mut frames = Vec::new();
+            for frame in frame_rx {
+                let (width, height) = clip_size(frame.width, frame.height, max_width);
+                let frame = frame.resize(width, height);
+                match RgbaImage::from_raw(frame.width, frame.height, frame.data) {
+                    Some(image) if frames.first().is_none_or(|first: &RgbaImage| first.dimensions() == image.dimensions()) => frames.push(image),
+                    _ => debug!("Skipping clip frame of a different size"),
+                }
+            }
+            if frames.is_empty() {
+                error!("Clip {:?} has no frames", output);
+                return;
+            }
+            let count = frames.len();
+            let frames = merge_repeats(frames);
+            let start = Instant::now();
+            let result = match format {
+                ClipFormat::Gif => encode_gif(&output, frames, fps),
+                ClipFormat::Apng => encode_apng(&output, frames, fps),
+            };
+            match result {
+                Ok(()) => info!("Saved {} frame clip to {:?} (encoded in {:?})", count, output, start.elapsed()),
+                Err(e) => error!("Failed to encode clip {:?}: {}", output, e),
+            }
+        });
+
+        info!("Recording {:.1} s clip @ {} fps to {:?}", seconds, fps, path);
+        Ok(Self {
+            path: path.to_path_buf(),
+            interval: Duration::from_secs_f32(1.0 / fps as f32),
+            end: now + Duration::from_secs_f32(seconds),
+            next_frame: now,
+            frame_tx: Some(frame_tx),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a frame is due at `now`; frames are only read back from the GPU when it is.
+    pub fn wants_frame(&self, now: Instant) -> bool {
+        !self.is_complete(now) && now >= self.next_frame
+    }
+
+    /// Whether the clip has its full length; dropping the recorder then encodes it.
+    pub fn is_complete(&self, now: Instant) -> bool {
+        now >= self.end
+    }
+
+    /// Adds a frame if one is due at `now`.
+    pub fn write_frame(&mut self, frame: &VideoFrame, now: Instant) {
+        if !self.wants_frame(now) {
+            return;
+        }
+        let Some(tx) = &self.frame_tx else { return };
+        match tx.try_send(frame.to_rgba()) {
+            // Keeps the frame rate when frames arrive late
+            Ok(()) => self.next_frame = (self.next_frame + self.interval).max(now),
+            Err(mpsc::TrySendError::Full(_)) => debug!("Clip scaling slow, dropping frame"),
+            Err(mpsc::TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_clip_size() {
+        assert_eq!(clip_size(1920, 1080, 480), (480, 270));
+        assert_eq!(clip_size(320, 240, 480), (320, 240));
+        assert_eq!(ClipFormat::from_path(Path::new("a.GIF")).unwrap(), ClipFormat::Gif);
+        assert_eq!(ClipFormat::from_path(Path::new("a.apng")).unwrap(), ClipFormat::Apng);
+        assert!(ClipFormat::from_path(Path::new("a.mp4")).is_err());
+    }
+
+    #[test]
+    fn test_merge_and_keep_unchanged() {
+        let black = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 255]));
+        let mut changed = black.clone();
+        changed.put_pixel(1, 0, Rgba([200, 10, 10, 255]));
+        let merged = merge_repeats(vec![black.clone(), black.clone(), changed.clone()]);
+        assert_eq!(merged.iter().map(|(_, count)| *count).collect::<Vec<_>>(), vec![2, 1]);
+
+        let mut shown = black;
+        let mut frame = changed;
+        frame.put_pixel(0, 0, Rgba([2, 1, 0, 255]));
+        keep_unchanged(&mut frame, &mut shown);
+        assert_eq!(frame.get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(frame.get_pixel(1, 0).0, [200, 10, 10, 255]);
+        assert_eq!(shown.get_pixel(1, 0).0, [200, 10, 10, 255]);
+    }
+}
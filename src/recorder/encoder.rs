@@ -0,0 +1,185 @@
+//! H.264 encoder selection for recordings.
+//!
+//! Hardware encoders take the encoding load off the CPU, which matters when
+//! the shaders and models already keep it busy. Which ones work depends on the
+//! ffmpeg build, the GPU and its driver, so they are tried with a one-frame
+//! test encode before use; x264 is the fallback that always works.
+
+use std::collections::HashSet;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use tracing::{info, warn};
+
+/// An H.264 encoder of ffmpeg.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum VideoEncoder {
+    /// The first available hardware encoder, x264 without one
+    #[default]
+    Auto,
+    /// Software (libx264)
+    X264,
+    /// NVIDIA
+    Nvenc,
+    /// Intel Quick Sync Video
+    Qsv,
+    /// Apple VideoToolbox
+    VideoToolbox,
+    /// AMD
+    Amf,
+}
+
+/// Hardware encoders tried by `VideoEncoder::Auto`, in order of preference.
+#[cfg(target_os = "macos")]
+const HARDWARE_ENCODERS: &[VideoEncoder] = &[VideoEncoder::VideoToolbox];
+#[cfg(not(target_os = "macos"))]
+const HARDWARE_ENCODERS: &[VideoEncoder] = &[VideoEncoder::Nvenc, VideoEncoder::Qsv, VideoEncoder::Amf];
+
+impl VideoEncoder {
+    /// Name of the ffmpeg encoder (`Auto` has none).
+    pub fn codec(self) -> Option<&'static str> {
+        match self {
+            VideoEncoder::Auto => None,
+            VideoEncoder::X264 => Some("libx264"),
+            VideoEncoder::Nvenc => Some("h264_nvenc"),
+            VideoEncoder::Qsv => Some("h264_qsv"),
+            VideoEncoder::VideoToolbox => Some("h264_videotoolbox"),
+            VideoEncoder::Amf => Some("h264_amf"),
+        }
+    }
+
+    /// The encoder to record with: `Auto` picks the first available hardware
+    /// encoder, and unavailable ones fall back to x264 with a warning.
+    pub fn resolve(self) -> VideoEncoder {
+        match self {
+            VideoEncoder::X264 => VideoEncoder::X264,
+            VideoEncoder::Auto => {
+                let encoder = HARDWARE_ENCODERS.iter().copied().find(|encoder| available_encoders().contains(encoder));
+                encoder.unwrap_or(VideoEncoder::X264)
+            }
+            encoder if available_encoders().contains(&encoder) => encoder,
+            encoder => {
+                warn!("Encoder {:?} is not available, recording with x264", encoder);
+                VideoEncoder::X264
+            }
+        }
+    }
+}
+
+/// How the encoder spends bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateControl {
+    /// The encoder's default
+    #[default]
+    Default,
+    /// Constant quality, 0 (lossless) - 51 (worst) like x264's CRF
+    Quality(u32),
+    /// Average bitrate in kbit/s, capped at the same peak
+    Bitrate(u32),
+}
+
+/// ffmpeg output options that encode with `encoder` (not `Auto`) at `rate_control`.
+pub fn encoder_args(encoder: VideoEncoder, rate_control: RateControl) -> Vec<String> {
+    let codec = encoder.codec().unwrap_or("libx264");
+    let mut args: Vec<String> = vec!["-c:v".into(), codec.into()];
+    // Fast presets; recording runs next to the render loop
+    let (preset, pixel_format) = match encoder {
+        VideoEncoder::Auto | VideoEncoder::X264 => (Some("veryfast"), "yuv420p"),
+        VideoEncoder::Nvenc => (Some("p4"), "yuv420p"),
+        VideoEncoder::Qsv => (Some("veryfast"), "nv12"),
+        VideoEncoder::VideoToolbox => (None, "yuv420p"),
+        VideoEncoder::Amf => (None, "yuv420p"),
+    };
+    if let Some(preset) = preset {
+        args.extend(["-preset".into(), preset.into()]);
+    }
+    if encoder == VideoEncoder::Amf {
+        args.extend(["-quality".into(), "speed".into()]);
+    }
+    args.extend(["-pix_fmt".into(), pixel_format.into()]);
+
+    match rate_control {
+        RateControl::Default => {}
+        RateControl::Quality(quality) => {
+            let quality = quality.min(51);
+            let quality_args: Vec<String> = match encoder {
+                VideoEncoder::Auto | VideoEncoder::X264 => vec!["-crf".into(), quality.to_string()],
+                VideoEncoder::Nvenc => vec!["-rc".into(), "vbr".into(), "-cq".into(), quality.to_string(), "-b:v".into(), "0".into()],
+                VideoEncoder::Qsv => vec!["-global_quality".into(), quality.to_string()],
+                // 1 (worst) - 100 (best)
+                VideoEncoder::VideoToolbox => vec!["-q:v".into(), ((51 - quality) * 100 / 51).max(1).to_string()],
+                VideoEncoder::Amf => vec![
+                    "-rc".into(), "cqp".into(),
+                    "-qp_i".into(), quality.to_string(),
+                    "-qp_p".into(), quality.to_string(),
+                ],
+            };
+            args.extend(quality_args);
+        }
+        RateControl::Bitrate(kbps) => {
+            let kbps = kbps.max(1);
+            if encoder == VideoEncoder::Amf {
+                args.extend(["-rc".into(), "vbr_peak".into()]);
+            }
+            args.extend([
+                "-b:v".into(), format!("{}k", kbps),
+                "-maxrate".into(), format!("{}k", kbps),
+                "-bufsize".into(), format!("{}k", kbps * 2),
+            ]);
+        }
+    }
+    args
+}
+
+/// The hardware encoders that work on this machine, tested once in parallel.
+fn available_encoders() -> &'static HashSet<VideoEncoder> {
+    static AVAILABLE: OnceLock<HashSet<VideoEncoder>> = OnceLock::new();
+    AVAILABLE.get_or_init(|| {
+        info!("Testing hardware encoders...");
+        let available: HashSet<VideoEncoder> = thread::scope(|scope| {
+            let probes: Vec<_> = HARDWARE_ENCODERS
+                .iter()
+                .map(|&encoder| scope.spawn(move || (encoder, test_encode(encoder))))
+                .collect();
+            probes.into_iter().filter_map(|probe| probe.join().ok()).filter(|(_, works)| *works).map(|(encoder, _)| encoder).collect()
+        });
+        info!("Available hardware encoders: {:?}", available);
+        available
+    })
+}
+
+/// Encodes a single frame to nowhere; fails if ffmpeg lacks the encoder or the
+/// GPU or driver doesn't support it.
+fn test_encode(encoder: VideoEncoder) -> bool {
+    let Some(codec) = encoder.codec() else { return false };
+    Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-f", "lavfi", "-i", "color=black:s=256x256", "-frames:v", "1", "-c:v", codec, "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has(args: &[String], pair: [&str; 2]) -> bool {
+        args.windows(2).any(|window| window == pair)
+    }
+
+    #[test]
+    fn test_encoder_args() {
+        let args = encoder_args(VideoEncoder::X264, RateControl::Quality(20));
+        assert!(has(&args, ["-c:v", "libx264"]) && has(&args, ["-crf", "20"]));
+
+        let args = encoder_args(VideoEncoder::Nvenc, RateControl::Bitrate(6000));
+        assert!(has(&args, ["-c:v", "h264_nvenc"]) && has(&args, ["-maxrate", "6000k"]) && has(&args, ["-bufsize", "12000k"]));
+
+        // Quick Sync wants NV12, VideoToolbox's quality scale is inverted
+        assert!(has(&encoder_args(VideoEncoder::Qsv, RateControl::Default), ["-pix_fmt", "nv12"]));
+        assert!(has(&encoder_args(VideoEncoder::VideoToolbox, RateControl::Quality(0)), ["-q:v", "100"]));
+        assert!(has(&encoder_args(VideoEncoder::VideoToolbox, RateControl::Quality(99)), ["-q:v", "1"]));
+    }
+}
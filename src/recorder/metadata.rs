@@ -0,0 +1,105 @@
+//! Per-frame metadata sidecar of a recording.
+//!
+//! Next to `recording.mp4`, `recording.jsonl` gets one JSON object per
+//! recorded frame, so analytics and replay tools can line the detections and
+//! parameter values up with the video: line `n` describes video frame `n`.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// State of the pipeline when a frame was rendered.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FrameMetadata {
+    /// Last preset applied with `switch-preset`
+    pub preset: Option<String>,
+    /// Shader parameter values, including modulation
+    pub params: BTreeMap<String, f32>,
+    /// Body keypoints as `[x, y, score]`, if a pose model is loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pose: Option<Vec<[f32; 3]>>,
+    /// Share of the frame covered by the person mask (0.0 - 1.0), if segmentation is active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mask_area: Option<f32>,
+    /// Number of people told apart by the instance segmentation, if active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub people: Option<u32>,
+}
+
+/// One line of the sidecar file.
+#[derive(Serialize)]
+struct MetadataLine<'a> {
+    /// Frame index in the video
+    frame: u64,
+    /// Presentation time in the video, in seconds
+    pts: f64,
+    /// Wall clock time the frame was recorded, in seconds since the Unix epoch
+    timestamp: f64,
+    #[serde(flatten)]
+    metadata: &'a FrameMetadata,
+}
+
+/// Writes the metadata of each recorded frame as JSON lines.
+pub struct MetadataWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl MetadataWriter {
+    /// Creates the sidecar file of the recording at `video_path`.
+    pub fn create(video_path: &Path) -> Result<Self> {
+        let path = video_path.with_extension("jsonl");
+        let file = BufWriter::new(File::create(&path)?);
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the metadata of video frame `frame` (at `fps`).
+    pub fn write(&mut self, frame: u64, fps: u32, metadata: &FrameMetadata) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        let line = MetadataLine { frame, pts: frame as f64 / fps.max(1) as f64, timestamp, metadata };
+        serde_json::to_writer(&mut self.file, &line)?;
+        self.file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Share of `mask` values above 127 (person pixels).
+pub fn mask_area(mask: &[u8]) -> f32 {
+    if mask.is_empty() {
+        return 0.0;
+    }
+    mask.iter().filter(|&&value| value > 127).count() as f32 / mask.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_line() {
+        let metadata = FrameMetadata {
+            preset: Some("calm".to_string()),
+            params: BTreeMap::from([("intensity".to_string(), 0.5)]),
+            mask_area: Some(mask_area(&[0, 255, 255, 100])),
+            ..Default::default()
+        };
+        let line = MetadataLine { frame: 30, pts: 1.0, timestamp: 0.0, metadata: &metadata };
+        assert_eq!(
+            serde_json::to_string(&line).unwrap(),
+            r#"{"frame":30,"pts":1.0,"timestamp":0.0,"preset":"calm","params":{"intensity":0.5},"mask_area":0.5}"#
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Recording of processed output to a video file.
+//! Uses the `ffmpeg` command-line tool via a subprocess to encode frames.
+
+pub mod clip;
+pub mod encoder;
+pub mod metadata;
+
+use crate::frame::VideoFrame;
+use crate::output::pacing::FramePacer;
+use encoder::{encoder_args, RateControl, VideoEncoder};
+use metadata::{FrameMetadata, MetadataWriter};
+use anyhow::{anyhow, Result};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
+
+/// Container of recordings. Both are written in fragments, so a crash or power
+/// loss only loses the last fragment instead of the whole file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordContainer {
+    /// Fragmented MP4
+    #[default]
+    Mp4,
+    /// Matroska
+    Mkv,
+}
+
+impl RecordContainer {
+    /// The container of a file extension; `None` for containers written as ffmpeg does by default.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("mp4" | "m4v" | "mov") => Some(RecordContainer::Mp4),
+            Some("mkv") => Some(RecordContainer::Mkv),
+            _ => None,
+        }
+    }
+
+    /// File extension of recordings without an explicit path.
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordContainer::Mp4 => "mp4",
+            RecordContainer::Mkv => "mkv",
+        }
+    }
+}
+
+/// How recordings are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordSettings {
+    /// Container of recordings without an explicit path
+    pub container: RecordContainer,
+    /// Seconds between fragments (and keyframes); at most this much is lost in a crash
+    pub fragment_interval: f32,
+    pub encoder: VideoEncoder,
+    pub rate_control: RateControl,
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        Self {
+            container: RecordContainer::Mp4,
+            fragment_interval: 2.0,
+            encoder: VideoEncoder::Auto,
+            rate_control: RateControl::Default,
+        }
+    }
+}
+
+/// ffmpeg output options that write `container` in fragments of `interval` seconds, each
+/// starting with a keyframe and flushed to disk as soon as it is complete.
+fn fragment_args(container: RecordContainer, interval: f32) -> Vec<String> {
+    let mut args = vec![
+        "-force_key_frames".to_string(),
+        format!("expr:gte(t,n_forced*{})", interval),
+        "-flush_packets".to_string(),
+        "1".to_string(),
+    ];
+    match container {
+        // An empty moov up front, then a self-contained moof per keyframe
+        RecordContainer::Mp4 => args.extend([
+            "-movflags".to_string(),
+            "+frag_keyframe+empty_moov+default_base_moof".to_string(),
+            "-frag_duration".to_string(),
+            ((interval * 1_000_000.0) as u64).to_string(),
+        ]),
+        // Matroska stays playable without its index; closing clusters often limits the loss
+        RecordContainer::Mkv => args.extend([
+            "-cluster_time_limit".to_string(),
+            ((interval * 1000.0) as u64).to_string(),
+        ]),
+    }
+    args
+}
+
+/// Encodes frames to a video file in a background ffmpeg process.
+pub struct Recorder {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    fps: u32,
+    /// Takes the rendered frames at `fps`
+    pacer: FramePacer,
+    /// Sender for RGBA frames; dropped to finish the recording
+    frame_tx: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+    child: Child,
+    frame_count: u64,
+    /// Per-frame metadata sidecar, if enabled
+    metadata: Option<MetadataWriter>,
+}
+
+impl Recorder {
+    /// Starts recording `width`x`height` frames at `fps` to `path` (H.264 with
+    /// `settings.encoder`; the container is chosen from the file extension). MP4 and MKV
+    /// files are written in fragments of `settings.fragment_interval` seconds.
+    pub fn start(path: &Path, width: u32, height: u32, fps: u32, settings: &RecordSettings) -> Result<Self> {
+        let size = format!("{}x{}", width, height);
+        let rate = fps.to_string();
+        let path_str = path.to_str().ok_or_else(|| anyhow!("Invalid recording path {:?}", path))?;
+        let fragment_args = match RecordContainer::from_path(path) {
+            Some(container) => fragment_args(container, settings.fragment_interval),
+            None => {
+                warn!("Recording {:?} is not written in fragments, a crash may lose all of it (use .mp4 or .mkv)", path);
+                Vec::new()
+            }
+        };
+        let encoder = settings.encoder.resolve();
+
+        // ffmpeg -f rawvideo -pix_fmt rgba -s WxH -r fps -i - -c:v <encoder> <file>
+        let mut child = Command::new("ffmpeg")
+            .args([
+                "-y",
+                "-loglevel", "error",
+                "-f", "rawvideo",
+                "-pix_fmt", "rgba",
+                "-s", &size,
+                "-r", &rate,
+                "-i", "-",
+            ])
+            .args(encoder_args(encoder, settings.rate_control))
+            .args(&fragment_args)
+            .arg(path_str)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to spawn ffmpeg: {}", e))?;
+
+        // Log ffmpeg errors
+        let mut stderr = child.stderr.take().unwrap();
+        thread::spawn(move || {
+            let mut msg = String::new();
+            if stderr.read_to_string(&mut msg).is_ok() {
+                for line in msg.lines() {
+                    error!("ffmpeg: {}", line);
+                }
+            }
+        });
+
+        // Write frames on a separate thread so encoding never stalls the render loop
+        let mut stdin = child.stdin.take().unwrap();
+        let (frame_tx, frame_rx) = mpsc::sync_channel::<Vec<u8>>(4);
+        let writer = thread::spawn(move || {
+            for data in frame_rx {
+                if let Err(e) = stdin.write_all(&data) {
+                    error!("Failed to write frame to ffmpeg: {}", e);
+                    break;
+                }
+            }
+            // Dropping stdin closes the pipe so ffmpeg finalizes the file
+        });
+
+        info!("Recording {}x{} @ {} fps with {} to {:?}", width, height, fps, encoder.codec().unwrap_or_default(), path);
+        Ok(Self {
+            path: path.to_path_buf(),
+            width,
+            height,
+            fps,
+            pacer: FramePacer::new(fps, Instant::now()),
+            frame_tx: Some(frame_tx),
+            writer: Some(writer),
+            child,
+            frame_count: 0,
+            metadata: None,
+        })
+    }
+
+    /// Path of the file being recorded.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the metadata of each recorded frame to a JSON lines file next to the video.
+    pub fn enable_metadata(&mut self) -> Result<()> {
+        let writer = MetadataWriter::create(&self.path)?;
+        info!("Writing frame metadata to {:?}", writer.path());
+        self.metadata = Some(writer);
+        Ok(())
+    }
+
+    /// Whether `write_frame` expects the frame metadata.
+    pub fn wants_metadata(&self) -> bool {
+        self.metadata.is_some()
+    }
+
+    /// Whether a frame is due at `now`; frames are only read back from the GPU when it is.
+    pub fn wants_frame(&self, now: Instant) -> bool {
+        self.pacer.is_due(now)
+    }
+
+    /// When the next frame is due.
+    pub fn next_frame(&self) -> Instant {
+        self.pacer.next_frame()
+    }
+
+    /// Queues a frame for encoding if one is due at `now`. Frames are dropped if the
+    /// encoder falls behind; `metadata` is only written for frames that are kept.
+    pub fn write_frame(&mut self, frame: &VideoFrame, metadata: Option<&FrameMetadata>, now: Instant) {
+        if !self.pacer.tick(now) {
+            return;
+        }
+        if frame.width != self.width || frame.height != self.height {
+            warn!("Skipping {}x{} frame, recording is {}x{}", frame.width, frame.height, self.width, self.height);
+            return;
+        }
+        let Some(tx) = &self.frame_tx else { return };
+        match tx.try_send(frame.to_rgba().data) {
+            Ok(()) => {
+                if let (Some(writer), Some(metadata)) = (&mut self.metadata, metadata) {
+                    if let Err(e) = writer.write(self.frame_count, self.fps, metadata) {
+                        error!("Failed to write frame metadata, disabling it: {}", e);
+                        self.metadata = None;
+                    }
+                }
+                self.frame_count += 1;
+            }
+            Err(mpsc::TrySendError::Full(_)) => debug!("Encoder slow, dropping recorded frame"),
+            Err(mpsc::TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    /// Finishes the recording and waits for ffmpeg to finalize the file.
+    pub fn stop(mut self) -> Result<PathBuf> {
+        self.finish()?;
+        Ok(self.path.clone())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let Some(tx) = self.frame_tx.take() else { return Ok(()) };
+        drop(tx);
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        if let Some(mut metadata) = self.metadata.take() {
+            if let Err(e) = metadata.flush() {
+                error!("Failed to write frame metadata: {}", e);
+            }
+        }
+        let status = self.child.wait()?;
+        info!("Recording stopped ({} frames written to {:?})", self.frame_count, self.path);
+        if !status.success() {
+            return Err(anyhow!("ffmpeg exited with {}", status));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Failed to finish recording: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_args() {
+        assert_eq!(RecordContainer::from_path(Path::new("out.MKV")), Some(RecordContainer::Mkv));
+        assert_eq!(RecordContainer::from_path(Path::new("out.mov")), Some(RecordContainer::Mp4));
+        assert_eq!(RecordContainer::from_path(Path::new("out.avi")), None);
+
+        let args = fragment_args(RecordContainer::Mp4, 2.0);
+        assert!(args.windows(2).any(|pair| pair == ["-frag_duration", "2000000"]));
+        assert!(args.windows(2).any(|pair| pair == ["-force_key_frames", "expr:gte(t,n_forced*2)"]));
+        let args = fragment_args(RecordContainer::Mkv, 0.5);
+        assert!(args.windows(2).any(|pair| pair == ["-cluster_time_limit", "500"]));
+    }
+}
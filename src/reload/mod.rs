@@ -0,0 +1,277 @@
+//! Hot reloading of the config file, shaders, Lua scripts and image textures.
+//!
+//! A single file watcher serves all of them. Changes are debounced, since
+//! editors often save a file in several writes, and every reload reports
+//! whether it worked. The last result per file is kept for
+//! `proteus ctl reload status`. `proteus ctl reload` (or `R` in the preview
+//! window) reloads everything at once, for changes the watcher can't see such
+//! as files on network shares.
+//!
+//! Watches are on the directories of the files rather than the files
+//! themselves, so saves that replace a file (write to a temporary file, then
+//! rename) are seen as well.
+
+use anyhow::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Time without further changes to a file before it is reloaded.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// What a reloaded file is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReloadKind {
+    Config,
+    Shader,
+    Lua,
+    Texture,
+}
+
+impl fmt::Display for ReloadKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ReloadKind::Config => "config",
+            ReloadKind::Shader => "shader",
+            ReloadKind::Lua => "lua",
+            ReloadKind::Texture => "texture",
+        })
+    }
+}
+
+/// Result of the last reload of a file.
+#[derive(Debug, Clone)]
+pub struct ReloadStatus {
+    pub kind: ReloadKind,
+    pub path: PathBuf,
+    pub time: Instant,
+    /// The error, if the reload failed
+    pub error: Option<String>,
+}
+
+impl fmt::Display for ReloadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: ", self.kind, self.path.display())?;
+        match &self.error {
+            None => write!(f, "ok")?,
+            Some(e) => write!(f, "error: {}", e)?,
+        }
+        write!(f, " ({} s ago)", self.time.elapsed().as_secs())
+    }
+}
+
+/// The shared file watcher and the number of watches per directory.
+struct WatcherState {
+    watcher: RecommendedWatcher,
+    directories: HashMap<PathBuf, usize>,
+}
+
+static WATCHER: Mutex<Option<WatcherState>> = Mutex::new(None);
+static SUBSCRIPTIONS: Mutex<Vec<Weak<Subscription>>> = Mutex::new(Vec::new());
+static STATUS: Mutex<BTreeMap<(ReloadKind, PathBuf), ReloadStatus>> = Mutex::new(BTreeMap::new());
+/// Incremented by `reload_all`
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Files of one watch: the path as seen in the watcher's events and as given.
+struct Subscription {
+    paths: Vec<(PathBuf, PathBuf)>,
+    pending: Mutex<Pending>,
+}
+
+/// Changed files waiting for the debounce time.
+#[derive(Debug, Default)]
+struct Pending {
+    paths: BTreeSet<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+impl Pending {
+    fn add(&mut self, path: PathBuf, now: Instant) {
+        self.paths.insert(path);
+        self.last_change = Some(now);
+    }
+
+    /// Takes the changed files once they have been quiet for `DEBOUNCE`.
+    fn take(&mut self, now: Instant) -> Option<Vec<PathBuf>> {
+        let last_change = self.last_change?;
+        if now.duration_since(last_change) < DEBOUNCE {
+            return None;
+        }
+        self.last_change = None;
+        Some(std::mem::take(&mut self.paths).into_iter().collect())
+    }
+}
+
+/// Watches files of one kind for changes. Stops watching when dropped.
+pub struct Watch {
+    kind: ReloadKind,
+    subscription: Arc<Subscription>,
+    /// Watched directories, once per file
+    directories: Vec<PathBuf>,
+    generation: u64,
+}
+
+/// Starts watching `paths`.
+pub fn watch(kind: ReloadKind, paths: impl IntoIterator<Item = PathBuf>) -> Watch {
+    let paths: Vec<(PathBuf, PathBuf)> = paths.into_iter().map(|path| (event_path(&path), path)).collect();
+    let subscription = Arc::new(Subscription { paths, pending: Mutex::new(Pending::default()) });
+
+    let mut directories = Vec::new();
+    if let Ok(mut state) = WATCHER.lock() {
+        if state.is_none() {
+            match RecommendedWatcher::new(handle_event, notify::Config::default()) {
+                Ok(watcher) => *state = Some(WatcherState { watcher, directories: HashMap::new() }),
+                Err(e) => warn!("Failed to create file watcher: {}", e),
+            }
+        }
+        if let Some(state) = state.as_mut() {
+            for (key, path) in &subscription.paths {
+                let Some(directory) = key.parent() else { continue };
+                let count = state.directories.entry(directory.to_path_buf()).or_insert(0);
+                if *count == 0 {
+                    if let Err(e) = state.watcher.watch(directory, RecursiveMode::NonRecursive) {
+                        warn!("Failed to watch {} file {:?}: {}", kind, path, e);
+                        continue;
+                    }
+                }
+                *count += 1;
+                directories.push(directory.to_path_buf());
+                info!("Watching {} file {:?} for changes", kind, path);
+            }
+            state.directories.retain(|_, count| *count > 0);
+        }
+    }
+    if let Ok(mut subscriptions) = SUBSCRIPTIONS.lock() {
+        subscriptions.push(Arc::downgrade(&subscription));
+    }
+    Watch { kind, subscription, directories, generation: GENERATION.load(Ordering::SeqCst) }
+}
+
+impl Watch {
+    /// The changed files, once they have settled; all files after `reload_all`.
+    pub fn poll(&mut self) -> Option<Vec<PathBuf>> {
+        let mut pending = self.subscription.pending.lock().ok()?;
+        let generation = GENERATION.load(Ordering::SeqCst);
+        if generation != self.generation {
+            self.generation = generation;
+            *pending = Pending::default();
+            if self.subscription.paths.is_empty() {
+                return None;
+            }
+            return Some(self.subscription.paths.iter().map(|(_, path)| path.clone()).collect());
+        }
+        pending.take(Instant::now())
+    }
+
+    /// Records and logs the result of reloading `path`.
+    pub fn report(&self, path: &Path, result: Result<()>) {
+        report(self.kind, path, result);
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let Ok(mut state) = WATCHER.lock() else { return };
+        let Some(state) = state.as_mut() else { return };
+        for directory in &self.directories {
+            let Some(count) = state.directories.get_mut(directory) else { continue };
+            *count -= 1;
+            if *count == 0 {
+                state.directories.remove(directory);
+                let _ = state.watcher.unwatch(directory);
+            }
+        }
+    }
+}
+
+/// Records and logs the result of reloading `path`.
+pub fn report(kind: ReloadKind, path: &Path, result: Result<()>) {
+    let error = match result {
+        Ok(()) => {
+            info!("Reloaded {} {:?}", kind, path);
+            None
+        }
+        Err(e) => {
+            error!("Failed to reload {} {:?}: {:#}", kind, path, e);
+            Some(format!("{:#}", e))
+        }
+    };
+    if let Ok(mut status) = STATUS.lock() {
+        let path = path.to_path_buf();
+        status.insert((kind, path.clone()), ReloadStatus { kind, path, time: Instant::now(), error });
+    }
+}
+
+/// Reloads all watched files at their next poll.
+pub fn reload_all() {
+    info!("Reloading all files...");
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// The last reload of each file, by kind and path.
+pub fn status() -> Vec<ReloadStatus> {
+    STATUS.lock().map(|status| status.values().cloned().collect()).unwrap_or_default()
+}
+
+/// The path of `path` in the watcher's events, which are absolute and have
+/// the symbolic links of the directory resolved.
+fn event_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (path.parent().and_then(|directory| directory.canonicalize().ok()), path.file_name()) {
+        (Some(directory), Some(name)) => directory.join(name),
+        _ => path,
+    }
+}
+
+fn handle_event(result: notify::Result<Event>) {
+    let event = match result {
+        Ok(event) => event,
+        Err(e) => {
+            warn!("Watch error: {}", e);
+            return;
+        }
+    };
+    // Editors that replace the file create it (or rename onto it)
+    if !matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
+        return;
+    }
+    let now = Instant::now();
+    let Ok(mut subscriptions) = SUBSCRIPTIONS.lock() else { return };
+    subscriptions.retain(|subscription| subscription.strong_count() > 0);
+    for subscription in subscriptions.iter().filter_map(Weak::upgrade) {
+        let Ok(mut pending) = subscription.pending.lock() else { continue };
+        for (key, path) in &subscription.paths {
+            if event.paths.contains(key) {
+                pending.add(path.clone(), now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debounce() {
+        let start = Instant::now();
+        let mut pending = Pending::default();
+        assert_eq!(pending.take(start), None);
+
+        // A save in two writes is a single change, once quiet for the debounce time
+        pending.add(PathBuf::from("a.frag"), start);
+        pending.add(PathBuf::from("a.frag"), start + Duration::from_millis(50));
+        pending.add(PathBuf::from("b.frag"), start + Duration::from_millis(60));
+        assert_eq!(pending.take(start + Duration::from_millis(120)), None);
+        assert_eq!(pending.take(start + Duration::from_millis(160)), Some(vec![PathBuf::from("a.frag"), PathBuf::from("b.frag")]));
+        assert_eq!(pending.take(start + Duration::from_millis(500)), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! Time-of-day visibility of compositor layers and overlays.
+//!
+//! Rules show a named element only at certain times, e.g. a "be right back"
+//! layer for the last five minutes of every hour or a clock overlay during
+//! office hours. Outside its rules' windows the element is hidden. Elements
+//! without rules are always shown.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Timelike;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How often the rules are evaluated.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Rule target that hides the watermark.
+pub const WATERMARK_TARGET: &str = "watermark";
+
+/// A daily time of day, in seconds since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    /// The local time of day.
+    pub fn now() -> Self {
+        Self(chrono::Local::now().num_seconds_from_midnight())
+    }
+
+    pub fn from_hms(hours: u32, minutes: u32, seconds: u32) -> Self {
+        Self((hours * 3600 + minutes * 60 + seconds) % SECONDS_PER_DAY)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = anyhow::Error;
+
+    /// Parses `HH:MM` or `HH:MM:SS`.
+    fn from_str(value: &str) -> Result<Self> {
+        let parts: Vec<u32> = value
+            .trim()
+            .split(':')
+            .map(|part| part.parse().map_err(|_| anyhow!("Invalid time '{}' (expected HH:MM)", value)))
+            .collect::<Result<_>>()?;
+        let (hours, minutes, seconds) = match parts.as_slice() {
+            [h, m] => (*h, *m, 0),
+            [h, m, s] => (*h, *m, *s),
+            _ => bail!("Invalid time '{}' (expected HH:MM)", value),
+        };
+        // 24:00 ends a window at midnight
+        if hours > 24 || minutes > 59 || seconds > 59 || (hours == 24 && minutes + seconds > 0) {
+            bail!("Invalid time '{}'", value);
+        }
+        Ok(Self(hours * 3600 + minutes * 60 + seconds))
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 3600, self.0 / 60 % 60)?;
+        if !self.0.is_multiple_of(60) {
+            write!(f, ":{:02}", self.0 % 60)?;
+        }
+        Ok(())
+    }
+}
+
+/// When a rule shows its element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleWindow {
+    /// Every day from `start` until `end` (past midnight if `end` is earlier)
+    Between { start: TimeOfDay, end: TimeOfDay },
+    /// For `duration` minutes every `period` minutes, starting at minute `at`
+    /// (minutes counted from midnight, so `period: 60, at: 55` is at :55 every hour)
+    Every { period: u32, at: u32, duration: u32 },
+}
+
+impl ScheduleWindow {
+    /// Parses a `HH:MM-HH:MM` range.
+    pub fn between(range: &str) -> Result<Self> {
+        let (start, end) = range.split_once('-').ok_or_else(|| anyhow!("Invalid time range '{}' (expected HH:MM-HH:MM)", range))?;
+        Ok(ScheduleWindow::Between { start: start.parse()?, end: end.parse()? })
+    }
+
+    /// Whether the window contains `time`.
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        match *self {
+            ScheduleWindow::Between { start, end } if start <= end => start <= time && time < end,
+            ScheduleWindow::Between { start, end } => time >= start || time < end,
+            ScheduleWindow::Every { period, at, duration } => {
+                let offset = (time.0 as i64 - at as i64 * 60).rem_euclid(period.max(1) as i64 * 60);
+                offset < duration as i64 * 60
+            }
+        }
+    }
+}
+
+impl fmt::Display for ScheduleWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleWindow::Between { start, end } => write!(f, "{}-{}", start, end),
+            ScheduleWindow::Every { period, at, duration } => write!(f, "every {} min at {} for {} min", period, at, duration),
+        }
+    }
+}
+
+/// Shows the element `target` during `window`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleRule {
+    /// A compositor layer name, or `watermark`
+    pub target: String,
+    pub window: ScheduleWindow,
+}
+
+/// Evaluates the rules and keeps the set of hidden elements.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    rules: Vec<ScheduleRule>,
+    hidden: BTreeSet<String>,
+    last_update: Option<Instant>,
+}
+
+impl Scheduler {
+    pub fn new(rules: Vec<ScheduleRule>) -> Self {
+        Self { rules, hidden: BTreeSet::new(), last_update: None }
+    }
+
+    /// Elements hidden at `time`: those with rules, none of which is active.
+    pub fn hidden_at(&self, time: TimeOfDay) -> BTreeSet<String> {
+        let targets: BTreeSet<&str> = self.rules.iter().map(|rule| rule.target.as_str()).collect();
+        targets
+            .into_iter()
+            .filter(|target| !self.rules.iter().any(|rule| rule.target == *target && rule.window.contains(time)))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Re-evaluates the rules against the local time (at most once per second)
+    /// and returns the hidden elements.
+    pub fn update(&mut self, now: Instant) -> &BTreeSet<String> {
+        if !self.rules.is_empty() && self.last_update.is_none_or(|last| now.duration_since(last) >= UPDATE_INTERVAL) {
+            self.last_update = Some(now);
+            let hidden = self.hidden_at(TimeOfDay::now());
+            if hidden != self.hidden {
+                for target in self.hidden.symmetric_difference(&hidden) {
+                    info!("Schedule: {} {}", if hidden.contains(target) { "hiding" } else { "showing" }, target);
+                }
+                self.hidden = hidden;
+            }
+        }
+        &self.hidden
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows() {
+        let at = |h, m| TimeOfDay::from_hms(h, m, 0);
+        let office = ScheduleWindow::between("09:00-17:00").unwrap();
+        assert!(office.contains(at(9, 0)) && office.contains(at(16, 59)));
+        assert!(!office.contains(at(17, 0)) && !office.contains(at(8, 59)));
+
+        let night = ScheduleWindow::between("22:00-06:30").unwrap();
+        assert!(night.contains(at(23, 0)) && night.contains(at(3, 0)) && !night.contains(at(12, 0)));
+        assert!(ScheduleWindow::between("25:00-26:00").is_err());
+        assert_eq!(ScheduleWindow::between("9:00-24:00").unwrap().to_string(), "09:00-24:00");
+
+        // Minutes 55 - 59 of every hour
+        let brb = ScheduleWindow::Every { period: 60, at: 55, duration: 5 };
+        assert!(brb.contains(at(10, 55)) && brb.contains(at(0, 59)));
+        assert!(!brb.contains(at(11, 0)) && !brb.contains(at(10, 54)));
+
+        let scheduler = Scheduler::new(vec![
+            ScheduleRule { target: "brb".into(), window: brb },
+            ScheduleRule { target: "clock".into(), window: office },
+            ScheduleRule { target: "clock".into(), window: night },
+        ]);
+        assert_eq!(scheduler.hidden_at(at(12, 0)), BTreeSet::from(["brb".to_string()]));
+        assert_eq!(scheduler.hidden_at(at(7, 55)), BTreeSet::from(["clock".to_string()]));
+    }
+}
@@ -0,0 +1,265 @@
+//! Background (daemon) mode and autostart service integration.
+//!
+//! `proteus service install --config <file>` registers Proteus to start at login in
+//! daemon mode: a systemd user unit on Linux, a launchd agent on macOS, and a `Run`
+//! registry entry on Windows.
+//!
+//! Detached from a terminal, the log goes to a file (by default `proteus.log` in
+//! the cache directory) instead of the discarded standard output.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::info;
+
+/// Log file once detached
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Log writer: standard output until `detach` switches to the log file.
+pub struct LogOutput;
+
+impl Write for LogOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match LOG_FILE.get() {
+            Some(file) => file.lock().unwrap_or_else(|e| e.into_inner()).write(buf),
+            None => std::io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match LOG_FILE.get() {
+            Some(file) => file.lock().unwrap_or_else(|e| e.into_inner()).flush(),
+            None => std::io::stdout().flush(),
+        }
+    }
+}
+
+/// Default log file in daemon mode.
+pub fn default_log_file() -> Option<PathBuf> {
+    crate::paths::cache_dir().map(|dir| dir.join("proteus.log"))
+}
+
+/// Opens the log file for appending, before detaching so errors still reach the terminal.
+fn open_log(path: Option<&Path>) -> Result<Option<File>> {
+    let Some(path) = path else { return Ok(None) };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = File::options().create(true).append(true).open(path).with_context(|| format!("Failed to open log file {:?}", path))?;
+    info!("Logging to {:?}", path);
+    Ok(Some(file))
+}
+
+/// Sends the log to `file` from now on.
+fn log_to(file: Option<File>) {
+    if let Some(file) = file {
+        let _ = LOG_FILE.set(Mutex::new(file));
+    }
+}
+
+/// Service / agent identifier.
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+const SERVICE_NAME: &str = "proteus";
+#[cfg(target_os = "macos")]
+const SERVICE_NAME: &str = "io.github.jdillenkofer.proteus";
+
+/// Command line the service runs: this executable in daemon mode with an absolute config path.
+fn service_command(config: &Path) -> Result<(PathBuf, Vec<String>)> {
+    let exe = std::env::current_exe().context("Failed to resolve the proteus executable path")?;
+    let config = std::fs::canonicalize(config)
+        .with_context(|| format!("Config file {:?} not found", config))?;
+    Ok((exe, vec!["--daemon".to_string(), "--config".to_string(), config.display().to_string()]))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .context("HOME is not set")
+}
+
+#[cfg(target_os = "linux")]
+fn unit_path() -> Result<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".config"),
+    };
+    Ok(config_dir.join("systemd/user").join(format!("{}.service", SERVICE_NAME)))
+}
+
+/// Installs a systemd user unit that starts Proteus at login.
+#[cfg(target_os = "linux")]
+pub fn install(config: &Path) -> Result<()> {
+    let (exe, args) = service_command(config)?;
+    let exec_start = std::iter::once(exe.display().to_string())
+        .chain(args)
+        .map(|arg| format!("\"{}\"", arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let unit = format!(
+        "[Unit]\n\
+         Description=Proteus shader virtual camera\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exec_start
+    );
+
+    let path = unit_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, unit).with_context(|| format!("Failed to write {:?}", path))?;
+    info!("Installed systemd user unit {:?}", path);
+    println!("Enable and start it with:\n  systemctl --user daemon-reload\n  systemctl --user enable --now {}", SERVICE_NAME);
+    Ok(())
+}
+
+/// Removes the systemd user unit.
+#[cfg(target_os = "linux")]
+pub fn uninstall() -> Result<()> {
+    let path = unit_path()?;
+    println!("Stop it first with:\n  systemctl --user disable --now {}", SERVICE_NAME);
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+    info!("Removed systemd user unit {:?}", path);
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn agent_path() -> Result<PathBuf> {
+    Ok(home_dir()?.join("Library/LaunchAgents").join(format!("{}.plist", SERVICE_NAME)))
+}
+
+/// Installs a launchd agent that starts Proteus at login.
+#[cfg(target_os = "macos")]
+pub fn install(config: &Path) -> Result<()> {
+    let (exe, args) = service_command(config)?;
+    let program_arguments: String = std::iter::once(exe.display().to_string())
+        .chain(args)
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(&arg)))
+        .collect();
+    let plist = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \x20   <key>Label</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>ProgramArguments</key>\n\
+         \x20   <array>\n\
+         {}\
+         \x20   </array>\n\
+         \x20   <key>RunAtLoad</key>\n\
+         \x20   <true/>\n\
+         </dict>\n\
+         </plist>\n",
+        SERVICE_NAME, program_arguments
+    );
+
+    let path = agent_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, plist).with_context(|| format!("Failed to write {:?}", path))?;
+    info!("Installed launchd agent {:?}", path);
+    println!("Load it now with:\n  launchctl load -w {:?}", path);
+    Ok(())
+}
+
+/// Removes the launchd agent.
+#[cfg(target_os = "macos")]
+pub fn uninstall() -> Result<()> {
+    let path = agent_path()?;
+    println!("Unload it first with:\n  launchctl unload -w {:?}", path);
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+    info!("Removed launchd agent {:?}", path);
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+
+/// Registers Proteus to start at login via the current user's `Run` registry key.
+#[cfg(target_os = "windows")]
+pub fn install(config: &Path) -> Result<()> {
+    let (exe, args) = service_command(config)?;
+    let command = std::iter::once(exe.display().to_string())
+        .chain(args)
+        .map(|arg| format!("\"{}\"", arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let status = std::process::Command::new("reg")
+        .args(["add", RUN_KEY, "/v", SERVICE_NAME, "/t", "REG_SZ", "/d", &command, "/f"])
+        .status()?;
+    anyhow::ensure!(status.success(), "reg add failed with {}", status);
+    info!("Registered autostart entry '{}'", SERVICE_NAME);
+    Ok(())
+}
+
+/// Removes the autostart registry entry.
+#[cfg(target_os = "windows")]
+pub fn uninstall() -> Result<()> {
+    let status = std::process::Command::new("reg")
+        .args(["delete", RUN_KEY, "/v", SERVICE_NAME, "/f"])
+        .status()?;
+    anyhow::ensure!(status.success(), "reg delete failed with {}", status);
+    info!("Removed autostart entry '{}'", SERVICE_NAME);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn install(_config: &Path) -> Result<()> {
+    anyhow::bail!("Service installation is not supported on this platform")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn uninstall() -> Result<()> {
+    anyhow::bail!("Service installation is not supported on this platform")
+}
+
+/// Detaches from the terminal so Proteus keeps running in the background, logging
+/// to `log_file`. When started by a service manager (no terminal attached) the
+/// process stays in the foreground and keeps logging to standard output, as
+/// systemd and launchd expect. Must be called before any threads are spawned.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn detach(log_file: Option<&Path>) -> Result<()> {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let file = open_log(log_file)?;
+    // Keeps the working directory so relative paths in the config still resolve
+    if unsafe { libc::daemon(1, 0) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    log_to(file);
+    Ok(())
+}
+
+/// Detaches from the console window so Proteus keeps running in the background,
+/// logging to `log_file`.
+#[cfg(target_os = "windows")]
+pub fn detach(log_file: Option<&Path>) -> Result<()> {
+    let file = open_log(log_file)?;
+    unsafe { windows::Win32::System::Console::FreeConsole()? };
+    log_to(file);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn detach(_log_file: Option<&Path>) -> Result<()> {
+    anyhow::bail!("Daemon mode is not supported on this platform")
+}
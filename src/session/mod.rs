@@ -0,0 +1,114 @@
+//! Saved sessions: a snapshot of the runtime state.
+//!
+//! `proteus ctl session save` writes everything that was changed at runtime
+//! (the preset, the parameter values, the compositor layout, the redaction rules
+//! and zoom that are switched on, the tally, the tempo and the shader time) to a
+//! YAML file. `proteus ctl session load` brings it back, so a look can be resumed
+//! exactly, even in a later run. Settings from the config file are not part of a
+//! session.
+
+use crate::clock::{ShaderTime, TimeCommand};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// The runtime state of an instance. Missing fields are left as they are on load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// The last applied preset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Shader parameter values
+    pub params: BTreeMap<String, f32>,
+    /// Compositor layout shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Whether each redaction rule is on
+    pub redaction: BTreeMap<String, bool>,
+    /// Whether the zoom is zoomed in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<bool>,
+    /// Mixer source shown (1-based, as with `switch-source`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<usize>,
+    /// Tally state
+    pub live: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f32>,
+    /// Shader time in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<SessionTime>,
+}
+
+/// Position, speed and pause state of the shader time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SessionTime {
+    pub seconds: f64,
+    pub speed: f64,
+    pub paused: bool,
+}
+
+impl SessionTime {
+    pub fn of(time: &ShaderTime, now: Instant) -> Self {
+        Self { seconds: time.time(now), speed: time.speed(), paused: time.is_paused() }
+    }
+
+    /// Moves `time` to this position, speed and pause state.
+    pub fn restore(&self, time: &mut ShaderTime, now: Instant) {
+        time.handle(TimeCommand::Seek(self.seconds), now);
+        time.handle(TimeCommand::Speed(self.speed.max(0.0)), now);
+        time.handle(if self.paused { TimeCommand::Pause } else { TimeCommand::Resume }, now);
+    }
+}
+
+impl Session {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let yaml = serde_yaml::to_string(self)?;
+        fs::write(path, yaml).map_err(|e| anyhow!("Failed to write session {:?}: {}", path, e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let yaml = fs::read_to_string(path).map_err(|e| anyhow!("Failed to read session {:?}: {}", path, e))?;
+        serde_yaml::from_str(&yaml).map_err(|e| anyhow!("Invalid session {:?}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_round_trip() {
+        let session = Session {
+            preset: Some("calm".to_string()),
+            params: BTreeMap::from([("intensity".to_string(), 0.25), ("hue".to_string(), 0.5)]),
+            layout: Some("interview".to_string()),
+            redaction: BTreeMap::from([("bystanders".to_string(), false)]),
+            zoom: Some(true),
+            source: Some(2),
+            live: true,
+            bpm: Some(128.0),
+            time: Some(SessionTime { seconds: 12.5, speed: 0.5, paused: true }),
+        };
+        let yaml = serde_yaml::to_string(&session).unwrap();
+        assert_eq!(serde_yaml::from_str::<Session>(&yaml).unwrap(), session);
+
+        // Everything is optional
+        assert_eq!(serde_yaml::from_str::<Session>("params:\n  hue: 1.0\n").unwrap().params["hue"], 1.0);
+    }
+
+    #[test]
+    fn test_restore_time() {
+        let start = Instant::now();
+        let mut time = ShaderTime::new(start);
+        let saved = SessionTime { seconds: 42.0, speed: 2.0, paused: false };
+        saved.restore(&mut time, start + Duration::from_secs(100));
+        assert_eq!(time.time(start + Duration::from_secs(101)), 44.0);
+        assert_eq!(SessionTime::of(&time, start + Duration::from_secs(101)), SessionTime { seconds: 44.0, speed: 2.0, paused: false });
+    }
+}
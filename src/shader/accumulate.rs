@@ -0,0 +1,276 @@
+//! Long-exposure frame accumulation (running average or max blend).
+//!
+//! Runs after all user shaders and blends the final pass output into a
+//! persistent history texture, for motion-blur and light-painting looks.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// How new frames are blended into the accumulated image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccumulationMode {
+    /// Running average over roughly `frames` frames (motion blur)
+    #[default]
+    Average,
+    /// Per-channel maximum with fading history (light trails)
+    Max,
+}
+
+/// Accumulation settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Accumulation {
+    pub mode: AccumulationMode,
+    /// Number of frames averaged (`Average` mode)
+    pub frames: u32,
+    /// Fraction of the trails that fades per frame (`Max` mode), 0.0 - 1.0
+    pub decay: f32,
+}
+
+impl Default for Accumulation {
+    fn default() -> Self {
+        Self { mode: AccumulationMode::Average, frames: 8, decay: 0.05 }
+    }
+}
+
+impl Accumulation {
+    /// Shader uniforms: weight of the new frame, history fade, mode, reset flag.
+    fn uniforms(&self, reset: bool) -> [u32; 4] {
+        let weight = 1.0 / self.frames.max(1) as f32;
+        let fade = 1.0 - self.decay.clamp(0.0, 1.0);
+        let mode = match self.mode {
+            AccumulationMode::Average => 0,
+            AccumulationMode::Max => 1,
+        };
+        [weight.to_bits(), fade.to_bits(), mode, reset as u32]
+    }
+}
+
+const ACCUMULATE_SHADER: &str = r#"
+struct AccumulateUniforms {
+    weight: f32,
+    fade: f32,
+    // 0 = average, 1 = max
+    mode: u32,
+    // 1 on the first frame after (re)creation, when the history is empty
+    reset: u32,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var t_history: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> u_accumulate: AccumulateUniforms;
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(position.xy);
+    let current = textureLoad(t_source, p, 0);
+    if (u_accumulate.reset == 1u) {
+        return current;
+    }
+    let history = textureLoad(t_history, p, 0);
+    if (u_accumulate.mode == 0u) {
+        return mix(history, current, u_accumulate.weight);
+    }
+    return max(history * u_accumulate.fade, current);
+}
+"#;
+
+/// GPU resources of the accumulation pass.
+pub(super) struct AccumulatePass {
+    settings: Accumulation,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    /// Accumulated frame, written each frame
+    texture: Option<wgpu::Texture>,
+    /// Copy of the previous accumulated frame
+    history: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+    reset: bool,
+}
+
+impl AccumulatePass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: Accumulation) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Accumulate Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Accumulate Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Accumulate Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(ACCUMULATE_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Accumulate Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Accumulate Uniform Buffer"),
+            contents: bytemuck::cast_slice(&settings.uniforms(true)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            settings,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            texture: None,
+            history: None,
+            bind_group: None,
+            reset: true,
+        }
+    }
+
+    /// Binds `source` (the final pass output). The accumulation textures are
+    /// (re)created, starting over, only when the size of `source` changes.
+    pub fn prepare(&mut self, device: &wgpu::Device, source: &wgpu::Texture) {
+        if self.texture.as_ref().is_none_or(|texture| texture.size() != source.size()) {
+            self.create_textures(device, source.size());
+        }
+        let Some(history) = &self.history else { return };
+
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+        let history_view = history.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Accumulate Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&source_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&history_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+    }
+
+    fn create_textures(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        let create = |label, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | usage,
+                view_formats: &[],
+            })
+        };
+        let texture = create(
+            "Accumulation Texture",
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let history = create("Accumulation History Texture", wgpu::TextureUsages::COPY_DST);
+        self.texture = Some(texture);
+        self.history = Some(history);
+        self.reset = true;
+    }
+
+    pub fn settings(&self) -> Accumulation {
+        self.settings
+    }
+
+    /// The accumulated frame, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Records the accumulation pass into `encoder` and keeps the result as
+    /// history for the next frame.
+    pub fn encode(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some(texture), Some(history), Some(bind_group)) = (&self.texture, &self.history, &self.bind_group) else { return };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.settings.uniforms(self.reset)));
+        self.reset = false;
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Accumulate Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+
+        encoder.copy_texture_to_texture(texture.as_image_copy(), history.as_image_copy(), texture.size());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniforms() {
+        let average = Accumulation { mode: AccumulationMode::Average, frames: 4, decay: 0.0 };
+        assert_eq!(average.uniforms(false), [0.25f32.to_bits(), 1.0f32.to_bits(), 0, 0]);
+
+        let max = Accumulation { mode: AccumulationMode::Max, frames: 0, decay: 0.25 };
+        assert_eq!(max.uniforms(true), [1.0f32.to_bits(), 0.75f32.to_bits(), 1, 1]);
+    }
+}
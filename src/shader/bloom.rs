@@ -0,0 +1,405 @@
+//! Multi-scale bloom (glow around bright areas).
+//!
+//! Runs after all user shaders. A bright pass keeps what is above the
+//! threshold at half resolution, a chain of downsamples halves it further,
+//! each level is blurred with a separable gaussian, and the levels are added
+//! back onto the frame. Fine levels give a tight glow, coarse ones a wide halo;
+//! `radius` shifts the weight between them.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// Number of downsampled levels (half to 1/64 resolution).
+const LEVELS: usize = 6;
+
+/// Bloom settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    /// Brightness (max of R, G, B) from which pixels glow
+    pub threshold: f32,
+    /// Width of the soft transition below the threshold, as a fraction of it (0.0 - 1.0)
+    pub softness: f32,
+    /// Strength of the glow added to the frame
+    pub intensity: f32,
+    /// Spread of the glow, 0.0 (tight) - 1.0 (wide)
+    pub radius: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self { threshold: 0.8, softness: 0.5, intensity: 0.6, radius: 0.6 }
+    }
+}
+
+impl Bloom {
+    /// Shader uniforms: threshold, knee, intensity, padding and the weights of
+    /// the levels, finest first, adding up to 1.
+    fn uniforms(&self) -> [f32; 12] {
+        let threshold = self.threshold.max(0.0);
+        let radius = self.radius.clamp(0.0, 1.0);
+        let weights: Vec<f32> = (0..LEVELS as i32).map(|level| radius.powi(level)).collect();
+        let total: f32 = weights.iter().sum();
+        let mut uniforms = [0.0; 12];
+        uniforms[..4].copy_from_slice(&[threshold, threshold * self.softness.clamp(0.0, 1.0), self.intensity.max(0.0), 0.0]);
+        for (uniform, weight) in uniforms[4..].iter_mut().zip(weights) {
+            *uniform = weight / total;
+        }
+        uniforms
+    }
+}
+
+const BLOOM_SHADER: &str = r#"
+struct BloomUniforms {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+    // Weights of the levels, finest first
+    weights: array<vec4<f32>, 2>,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_bloom: BloomUniforms;
+@group(0) @binding(3) var t_level0: texture_2d<f32>;
+@group(0) @binding(4) var t_level1: texture_2d<f32>;
+@group(0) @binding(5) var t_level2: texture_2d<f32>;
+@group(0) @binding(6) var t_level3: texture_2d<f32>;
+@group(0) @binding(7) var t_level4: texture_2d<f32>;
+@group(0) @binding(8) var t_level5: texture_2d<f32>;
+
+// Four bilinear taps around the pixel: a smooth 4x4 average that doesn't flicker
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+    var color = textureSample(t_source, s_sampler, uv + texel * vec2<f32>(-1.0, -1.0)).rgb;
+    color += textureSample(t_source, s_sampler, uv + texel * vec2<f32>(1.0, -1.0)).rgb;
+    color += textureSample(t_source, s_sampler, uv + texel * vec2<f32>(-1.0, 1.0)).rgb;
+    color += textureSample(t_source, s_sampler, uv + texel * vec2<f32>(1.0, 1.0)).rgb;
+    return color * 0.25;
+}
+
+@fragment
+fn fs_prefilter(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    // Clamped so single overbright pixels don't blow up into squares
+    let color = min(downsample(tex_coords), vec3<f32>(64.0));
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = max(u_bloom.knee, 0.0001);
+    var soft = clamp(brightness - u_bloom.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - u_bloom.threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(tex_coords), 1.0);
+}
+
+// 9-tap gaussian from 5 bilinear taps
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let step = direction / vec2<f32>(textureDimensions(t_source));
+    var color = textureSample(t_source, s_sampler, uv).rgb * 0.2270270270;
+    color += textureSample(t_source, s_sampler, uv + step * 1.3846153846).rgb * 0.3162162162;
+    color += textureSample(t_source, s_sampler, uv - step * 1.3846153846).rgb * 0.3162162162;
+    color += textureSample(t_source, s_sampler, uv + step * 3.2307692308).rgb * 0.0702702703;
+    color += textureSample(t_source, s_sampler, uv - step * 3.2307692308).rgb * 0.0702702703;
+    return vec4<f32>(color, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    return blur(tex_coords, vec2<f32>(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    return blur(tex_coords, vec2<f32>(0.0, 1.0));
+}
+
+@fragment
+fn fs_combine(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let source = textureSample(t_source, s_sampler, tex_coords);
+    let w0 = u_bloom.weights[0];
+    let w1 = u_bloom.weights[1];
+    var glow = textureSample(t_level0, s_sampler, tex_coords).rgb * w0.x;
+    glow += textureSample(t_level1, s_sampler, tex_coords).rgb * w0.y;
+    glow += textureSample(t_level2, s_sampler, tex_coords).rgb * w0.z;
+    glow += textureSample(t_level3, s_sampler, tex_coords).rgb * w0.w;
+    glow += textureSample(t_level4, s_sampler, tex_coords).rgb * w1.x;
+    glow += textureSample(t_level5, s_sampler, tex_coords).rgb * w1.y;
+    return vec4<f32>(source.rgb + glow * u_bloom.intensity, source.a);
+}
+"#;
+
+/// Indices into `BloomPass::pipelines`.
+const PREFILTER: usize = 0;
+const DOWNSAMPLE: usize = 1;
+const BLUR_HORIZONTAL: usize = 2;
+const BLUR_VERTICAL: usize = 3;
+const COMBINE: usize = 4;
+
+/// One draw of the bloom: a pipeline, its inputs and the texture it renders to.
+struct BloomStep {
+    pipeline: usize,
+    bind_group: wgpu::BindGroup,
+    target: wgpu::TextureView,
+}
+
+/// GPU resources of the bloom pass.
+pub(super) struct BloomPass {
+    settings: Bloom,
+    pipelines: Vec<wgpu::RenderPipeline>,
+    /// Source, sampler and uniforms (bright pass, downsampling, blurs)
+    sample_layout: wgpu::BindGroupLayout,
+    /// Source, sampler, uniforms and all levels (combine)
+    combine_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    /// Blurred level and scratch texture between the two blur directions, per level
+    levels: Vec<(wgpu::Texture, wgpu::Texture)>,
+    /// Frame with the glow added
+    texture: Option<wgpu::Texture>,
+    steps: Vec<BloomStep>,
+}
+
+impl BloomPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: Bloom) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sample_entries = [
+            texture_entry(0),
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        let sample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom Bind Group Layout"),
+            entries: &sample_entries,
+        });
+        let combine_entries: Vec<_> = sample_entries.iter().cloned().chain((0..LEVELS as u32).map(|level| texture_entry(3 + level))).collect();
+        let combine_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom Combine Bind Group Layout"),
+            entries: &combine_entries,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(BLOOM_SHADER)),
+        });
+
+        let create_pipeline = |entry_point: &str, layout: &wgpu::BindGroupLayout| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Bloom Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                immediate_size: 0,
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Bloom Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: vertex_module,
+                    entry_point: Some("vs_main"),
+                    buffers: &[QuadVertex::layout()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &fragment_module,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+        // In the order of the indices above
+        let pipelines = vec![
+            create_pipeline("fs_prefilter", &sample_layout),
+            create_pipeline("fs_downsample", &sample_layout),
+            create_pipeline("fs_blur_horizontal", &sample_layout),
+            create_pipeline("fs_blur_vertical", &sample_layout),
+            create_pipeline("fs_combine", &combine_layout),
+        ];
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bloom Uniform Buffer"),
+            contents: bytemuck::cast_slice(&settings.uniforms()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            settings,
+            pipelines,
+            sample_layout,
+            combine_layout,
+            uniform_buffer,
+            levels: Vec::new(),
+            texture: None,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> Bloom {
+        self.settings
+    }
+
+    /// Changes the settings. They only go into the uniforms, so this is cheap
+    /// enough to do every frame.
+    pub fn set_settings(&mut self, settings: Bloom) {
+        self.settings = settings;
+    }
+
+    /// Binds `source` (the final pass output). The level textures are
+    /// (re)created when the size of `source` changes.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, source: &wgpu::Texture) {
+        if self.texture.as_ref().is_none_or(|texture| texture.size() != source.size()) {
+            self.create_textures(device, source.size());
+        }
+        let Some(texture) = &self.texture else { return };
+
+        let view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = |input: &wgpu::TextureView| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Bloom Bind Group"),
+                layout: &self.sample_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(input) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                    wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+                ],
+            })
+        };
+
+        let source_view = view(source);
+        let mut steps = Vec::new();
+        for (level, (blurred, scratch)) in self.levels.iter().enumerate() {
+            // Each level starts from the blurred one above it
+            let (pipeline, input) = match level {
+                0 => (PREFILTER, source_view.clone()),
+                _ => (DOWNSAMPLE, view(&self.levels[level - 1].0)),
+            };
+            steps.push(BloomStep { pipeline, bind_group: bind_group(&input), target: view(blurred) });
+            steps.push(BloomStep { pipeline: BLUR_HORIZONTAL, bind_group: bind_group(&view(blurred)), target: view(scratch) });
+            steps.push(BloomStep { pipeline: BLUR_VERTICAL, bind_group: bind_group(&view(scratch)), target: view(blurred) });
+        }
+
+        let level_views: Vec<wgpu::TextureView> = self.levels.iter().map(|(blurred, _)| view(blurred)).collect();
+        let mut entries = vec![
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&source_view) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+            wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+        ];
+        for (level, level_view) in level_views.iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry { binding: 3 + level as u32, resource: wgpu::BindingResource::TextureView(level_view) });
+        }
+        let combine_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bloom Combine Bind Group"),
+            layout: &self.combine_layout,
+            entries: &entries,
+        });
+        steps.push(BloomStep { pipeline: COMBINE, bind_group: combine_bind_group, target: view(texture) });
+        self.steps = steps;
+    }
+
+    fn create_textures(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        let create = |label, width: u32, height: u32, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | usage,
+                view_formats: &[],
+            })
+        };
+        self.levels = (1..=LEVELS as u32)
+            .map(|level| {
+                let (width, height) = (size.width >> level, size.height >> level);
+                (create("Bloom Level Texture", width, height, wgpu::TextureUsages::empty()), create("Bloom Scratch Texture", width, height, wgpu::TextureUsages::empty()))
+            })
+            .collect();
+        self.texture = Some(create("Bloom Texture", size.width, size.height, wgpu::TextureUsages::COPY_SRC));
+    }
+
+    /// The frame with the glow added, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Records the bloom passes into `encoder`.
+    pub fn encode(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.settings.uniforms()));
+        for step in &self.steps {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bloom Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &step.target,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            render_pass.set_pipeline(&self.pipelines[step.pipeline]);
+            render_pass.set_bind_group(0, &step.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniforms() {
+        // Radius 0 only uses the finest level
+        let tight = Bloom { threshold: 1.0, softness: 0.5, intensity: 0.8, radius: 0.0 };
+        assert_eq!(tight.uniforms(), [1.0, 0.5, 0.8, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+
+        // Radius 1 weights all levels equally
+        let wide = Bloom { radius: 1.0, ..tight }.uniforms();
+        assert!(wide[4..10].iter().all(|weight| (weight - 1.0 / LEVELS as f32).abs() < 1e-6));
+        assert_eq!(&wide[10..], &[0.0, 0.0]);
+
+        let weights = Bloom::default().uniforms();
+        assert!((weights[4..].iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!(weights[4] > weights[5]);
+    }
+}
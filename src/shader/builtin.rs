@@ -0,0 +1,59 @@
+//! Builtin shader passes.
+//!
+//! Geometric effects whose coordinate math is easy to get subtly wrong
+//! (seams, stretched aspect ratios, smeared edges) and stylizations ship with
+//! Proteus and are used like shader files. Their settings are named parameters with a prefix
+//! per effect, so they can be set in `params`, switched with presets and
+//! animated by modulators.
+
+/// A shader pass that ships with Proteus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinShader {
+    /// Mirrored wedges around the center (`kaleidoscope_*` parameters)
+    Kaleidoscope,
+    /// One half or quadrant mirrored onto the rest (`mirror_*` parameters)
+    Mirror,
+    /// The frame wrapped around a tube running into the screen (`tunnel_*` parameters)
+    Tunnel,
+    /// Characters from the glyph atlas by brightness (`ascii_*` parameters)
+    Ascii,
+    /// Rotated CMYK dot screens (`halftone_*` parameters)
+    Halftone,
+    /// Old film and VHS artifacts, each switched on separately (`retro_*` parameters)
+    Retro,
+    /// The camera keyed with the segmentation mask over a background, with overlays (`studio_*` parameters)
+    Studio,
+    /// As `Studio`, keyed by a chroma key color instead of the mask
+    StudioChroma,
+}
+
+impl BuiltinShader {
+    /// Name used in the config and log messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinShader::Kaleidoscope => "kaleidoscope",
+            BuiltinShader::Mirror => "mirror",
+            BuiltinShader::Tunnel => "tunnel",
+            BuiltinShader::Ascii => "ascii",
+            BuiltinShader::Halftone => "halftone",
+            BuiltinShader::Retro => "retro",
+            BuiltinShader::Studio => "studio",
+            BuiltinShader::StudioChroma => "studio-chroma",
+        }
+    }
+
+    /// GLSL source of the pass.
+    pub fn code(self) -> &'static str {
+        match self {
+            BuiltinShader::Kaleidoscope => include_str!("builtin/kaleidoscope.frag"),
+            BuiltinShader::Mirror => include_str!("builtin/mirror.frag"),
+            BuiltinShader::Tunnel => include_str!("builtin/tunnel.frag"),
+            BuiltinShader::Ascii => include_str!("builtin/ascii.frag"),
+            BuiltinShader::Halftone => include_str!("builtin/halftone.frag"),
+            BuiltinShader::Retro => include_str!("builtin/retro.frag"),
+            // One source for both keys, selected by the preprocessor
+            BuiltinShader::Studio => concat!("#version 450\n", include_str!("builtin/studio.frag")),
+            BuiltinShader::StudioChroma => concat!("#version 450\n#define CHROMA_KEY\n", include_str!("builtin/studio.frag")),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+#version 450
+
+// Builtin: ascii
+// Draws the frame as characters from the glyph atlas (`glyphs` in the config),
+// picking a denser character for brighter cells.
+// Parameters:
+//   ascii_cell        Character height in pixels (default 12)
+//   ascii_palette     0: original colors, 1: white, 2: green, 3: amber
+//   ascii_background  Brightness of the original frame behind the characters (0 - 1)
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float ascii_cell;
+    float ascii_palette;
+    float ascii_background;
+};
+
+layout(set = 0, binding = 12) uniform texture2D t_glyphs;
+
+layout(location = 0) out vec4 frag_color;
+
+// Width to height of an atlas cell
+const float GLYPH_ASPECT = 0.625;
+
+void main() {
+    float cell_height = ascii_cell > 0.0 ? max(ascii_cell, 4.0) : 12.0;
+    vec2 cell_size = vec2(cell_height * GLYPH_ASPECT, cell_height);
+    vec2 size = vec2(width, height);
+    vec2 cell = floor(pixel_coords / cell_size);
+    vec2 local = fract(pixel_coords / cell_size);
+
+    // The average color of the cell, from a coarser mip level if the input has one
+    vec2 cell_uv = (cell + 0.5) * cell_size / size;
+    float lod = log2(cell_height);
+    vec4 color = textureLod(sampler2D(t_texture, s_sampler), cell_uv, lod);
+    float luma = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+
+    // Pick the character by brightness
+    vec2 atlas_size = vec2(textureSize(sampler2D(t_glyphs, s_sampler), 0));
+    float count = max(round(atlas_size.x / (atlas_size.y * GLYPH_ASPECT)), 1.0);
+    float index = min(floor(clamp(luma, 0.0, 1.0) * count), count - 1.0);
+    vec2 glyph_uv = vec2((index + local.x) / count, local.y);
+    float ink = textureLod(sampler2D(t_glyphs, s_sampler), glyph_uv, 0.0).r;
+
+    float palette = round(ascii_palette);
+    vec3 ink_color = color.rgb / max(max(color.r, max(color.g, color.b)), 0.001);
+    if (palette == 1.0) {
+        ink_color = vec3(1.0);
+    } else if (palette == 2.0) {
+        ink_color = vec3(0.2, 1.0, 0.3);
+    } else if (palette == 3.0) {
+        ink_color = vec3(1.0, 0.7, 0.2);
+    }
+
+    vec3 background = textureLod(sampler2D(t_texture, s_sampler), tex_coords, 0.0).rgb * clamp(ascii_background, 0.0, 1.0);
+    frag_color = vec4(mix(background, ink_color, ink), 1.0);
+}
@@ -0,0 +1,89 @@
+#version 450
+
+// Builtin: halftone
+// Prints the frame as rotated screens of dots, like a magazine.
+// Parameters:
+//   halftone_cell     Dot spacing in pixels (default 8)
+//   halftone_palette  0: CMYK dots on paper, 1: black dots on paper, 2: colored dots on black
+//   halftone_angle    Rotation of all screens in degrees
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float halftone_cell;
+    float halftone_palette;
+    float halftone_angle;
+};
+
+layout(location = 0) out vec4 frag_color;
+
+const int INK_CYAN = 0;
+const int INK_MAGENTA = 1;
+const int INK_YELLOW = 2;
+const int INK_BLACK = 3;
+const int INK_GRAY = 4;
+const int LIGHT = 5;
+
+// Amount of an ink (or light) needed to print `color`
+float ink_amount(vec3 color, int ink) {
+    float k = 1.0 - max(color.r, max(color.g, color.b));
+    if (ink == INK_BLACK) {
+        return k;
+    } else if (ink == INK_GRAY) {
+        return 1.0 - dot(color, vec3(0.2126, 0.7152, 0.0722));
+    } else if (ink == LIGHT) {
+        return dot(color, vec3(0.2126, 0.7152, 0.0722));
+    }
+    return (1.0 - color[ink] - k) / max(1.0 - k, 0.001);
+}
+
+// Samples the frame at the center of the nearest dot of the screen at `angle`
+vec4 dot_color(float angle, float cell, out float distance) {
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    vec2 point = transpose(rotation) * pixel_coords / cell;
+    vec2 center = floor(point) + 0.5;
+    distance = length(point - center);
+    vec2 uv = rotation * center * cell / vec2(width, height);
+    return textureLod(sampler2D(t_texture, s_sampler), clamp(uv, 0.0, 1.0), log2(cell));
+}
+
+// Coverage (0 - 1) of the screen of one ink at this pixel
+float screen(int ink, float angle, float cell) {
+    float distance;
+    vec4 color = dot_color(angle, cell, distance);
+    // Dots touch at full coverage
+    float radius = sqrt(clamp(ink_amount(color.rgb, ink), 0.0, 1.0)) * 0.7071;
+    float edge = fwidth(distance);
+    return 1.0 - smoothstep(radius - edge, radius + edge, distance);
+}
+
+void main() {
+    float cell = halftone_cell > 0.0 ? max(halftone_cell, 2.0) : 8.0;
+    float angle = radians(halftone_angle);
+    float palette = round(halftone_palette);
+
+    if (palette == 1.0) {
+        frag_color = vec4(vec3(1.0 - screen(INK_GRAY, angle + radians(45.0), cell)), 1.0);
+        return;
+    }
+
+    if (palette == 2.0) {
+        // Dots of the saturated frame color, sized by brightness
+        float distance;
+        vec4 color = dot_color(angle + radians(45.0), cell, distance);
+        float coverage = screen(LIGHT, angle + radians(45.0), cell);
+        vec3 hue = color.rgb / max(max(color.r, max(color.g, color.b)), 0.001);
+        frag_color = vec4(hue * coverage, 1.0);
+        return;
+    }
+
+    // Classic screen angles: cyan 15, magenta 75, yellow 0, black 45 degrees
+    float c = screen(INK_CYAN, angle + radians(15.0), cell);
+    float m = screen(INK_MAGENTA, angle + radians(75.0), cell);
+    float y = screen(INK_YELLOW, angle, cell);
+    float k = screen(INK_BLACK, angle + radians(45.0), cell);
+
+    // Subtractive inks on white paper
+    vec3 paper = vec3(1.0 - c, 1.0 - m, 1.0 - y) * (1.0 - k);
+    frag_color = vec4(paper, 1.0);
+}
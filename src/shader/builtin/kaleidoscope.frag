@@ -0,0 +1,46 @@
+#version 450
+
+// Builtin: kaleidoscope
+// Folds the frame into mirrored wedges around the center.
+// Parameters (0 picks the default):
+//   kaleidoscope_segments  Number of wedges (default 6)
+//   kaleidoscope_angle     Rotation of the pattern in degrees
+//   kaleidoscope_offset_x  Part of the frame shown in the wedges, moved from
+//   kaleidoscope_offset_y  the center in frame heights
+//   kaleidoscope_zoom      Magnification of the wedges (default 1)
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float kaleidoscope_segments;
+    float kaleidoscope_angle;
+    float kaleidoscope_offset_x;
+    float kaleidoscope_offset_y;
+    float kaleidoscope_zoom;
+};
+
+layout(location = 0) out vec4 frag_color;
+
+const float TAU = 6.28318530718;
+
+// Mirrored repeat, so coordinates outside the frame never hit the clamped edge
+vec2 mirror_repeat(vec2 uv) {
+    return 1.0 - abs(1.0 - mod(uv, 2.0));
+}
+
+void main() {
+    float segments = kaleidoscope_segments > 0.0 ? max(round(kaleidoscope_segments), 1.0) : 6.0;
+    float zoom = kaleidoscope_zoom > 0.0 ? kaleidoscope_zoom : 1.0;
+    float wedge = TAU / segments;
+
+    float radius = length(aspect_coords);
+    float angle = atan(aspect_coords.y, aspect_coords.x) - radians(kaleidoscope_angle);
+    // Every wedge shows the same slice, mirrored at its middle so neighbors meet seamlessly
+    angle = mod(angle, wedge);
+    angle = min(angle, wedge - angle);
+
+    vec2 point = radius * vec2(cos(angle), sin(angle)) / zoom + vec2(kaleidoscope_offset_x, kaleidoscope_offset_y);
+    vec2 uv = point / vec2(width / height, 1.0) + 0.5;
+    // No mipmap jumps at the wedge edges
+    frag_color = textureLod(sampler2D(t_texture, s_sampler), mirror_repeat(uv), 0.0);
+}
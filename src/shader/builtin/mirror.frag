@@ -0,0 +1,44 @@
+#version 450
+
+// Builtin: mirror
+// Mirrors one half of the frame onto the other, or one quadrant onto all four.
+// Parameters:
+//   mirror_axes      0: both axes (quadrants), 1: left onto right, 2: top onto bottom
+//   mirror_angle     Rotation of the mirror axes in degrees
+//   mirror_offset_x  Position of the mirror center, moved from the frame
+//   mirror_offset_y  center in frame heights
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float mirror_axes;
+    float mirror_angle;
+    float mirror_offset_x;
+    float mirror_offset_y;
+};
+
+layout(location = 0) out vec4 frag_color;
+
+vec2 mirror_repeat(vec2 uv) {
+    return 1.0 - abs(1.0 - mod(uv, 2.0));
+}
+
+void main() {
+    float axes = round(mirror_axes);
+    vec2 center = vec2(mirror_offset_x, mirror_offset_y);
+    float angle = radians(mirror_angle);
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+
+    // Into the frame of the mirror axes, fold, and back
+    vec2 point = transpose(rotation) * (aspect_coords - center);
+    if (axes != 2.0) {
+        point.x = -abs(point.x);
+    }
+    if (axes != 1.0) {
+        point.y = -abs(point.y);
+    }
+    point = rotation * point + center;
+
+    vec2 uv = point / vec2(width / height, 1.0) + 0.5;
+    frag_color = textureLod(sampler2D(t_texture, s_sampler), mirror_repeat(uv), 0.0);
+}
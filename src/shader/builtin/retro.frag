@@ -0,0 +1,132 @@
+#version 450
+
+// Builtin: retro
+// Old film and VHS artifacts. Each one is off at 0 and can be switched on
+// separately; the defaults in parentheses apply when the strength is set.
+// Parameters:
+//   retro_scanlines        Darkness of the gaps between scanlines (0 - 1)
+//   retro_scanline_size    Scanline spacing in pixels (default 3)
+//   retro_chroma_shift     Red and blue moved apart horizontally, in pixels
+//   retro_tape_noise       Rolling bands of tape noise with line jitter (0 - 1)
+//   retro_dropouts         White streaks where the tape lost its coating (0 - 1)
+//   retro_gate_weave       Film frame wandering in the projector gate, in pixels
+//   retro_grain            Film grain, strongest in the midtones (0 - 1)
+//   retro_grain_size       Grain size in pixels (default 1.5)
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float retro_scanlines;
+    float retro_scanline_size;
+    float retro_chroma_shift;
+    float retro_tape_noise;
+    float retro_dropouts;
+    float retro_gate_weave;
+    float retro_grain;
+    float retro_grain_size;
+};
+
+layout(location = 0) out vec4 frag_color;
+
+float hash(vec2 p) {
+    vec3 p3 = fract(vec3(p.xyx) * 0.1031);
+    p3 += dot(p3, p3.yzx + 33.33);
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+// Smooth 1D noise in -1 - 1
+float noise(float x, float salt) {
+    float i = floor(x);
+    float f = smoothstep(0.0, 1.0, fract(x));
+    return mix(hash(vec2(i, salt)), hash(vec2(i + 1.0, salt)), f) * 2.0 - 1.0;
+}
+
+// Smooth 2D value noise in 0 - 1
+float value_noise(vec2 p, float salt) {
+    vec2 i = floor(p);
+    vec2 f = smoothstep(0.0, 1.0, fract(p));
+    float a = hash(i + salt);
+    float b = hash(i + vec2(1.0, 0.0) + salt);
+    float c = hash(i + vec2(0.0, 1.0) + salt);
+    float d = hash(i + vec2(1.0, 1.0) + salt);
+    return mix(mix(a, b, f.x), mix(c, d, f.x), f.y);
+}
+
+vec3 sample_frame(vec2 uv) {
+    return textureLod(sampler2D(t_texture, s_sampler), clamp(uv, 0.0, 1.0), 0.0).rgb;
+}
+
+void main() {
+    vec2 size = vec2(width, height);
+    vec2 pixel = pixel_coords;
+    // A new random salt for every frame
+    float frame_salt = floor(seed * 4096.0);
+
+    // Gate weave: the whole frame wanders slowly, mostly sideways
+    if (retro_gate_weave > 0.0) {
+        pixel -= vec2(noise(time * 6.0, 1.0), noise(time * 4.0, 2.0) * 0.5) * retro_gate_weave;
+    }
+
+    // Tape noise: bands rolling down the frame in which lines are jittered and noisy
+    float band = 0.0;
+    if (retro_tape_noise > 0.0) {
+        float y = tex_coords.y - time * 0.15;
+        band = smoothstep(0.6, 1.0, noise(y * 4.0, 3.0) * 0.5 + 0.5) * clamp(retro_tape_noise, 0.0, 1.0);
+        float jitter = hash(vec2(floor(pixel.y), frame_salt)) * 2.0 - 1.0;
+        pixel.x += jitter * band * 12.0 + noise(time * 2.0 + tex_coords.y * 3.0, 4.0) * retro_tape_noise * 2.0;
+    }
+
+    // Chroma shift: red and blue sampled apart, as from a misaligned tape head
+    vec2 uv = pixel / size;
+    vec3 color;
+    if (retro_chroma_shift != 0.0) {
+        vec2 shift = vec2(retro_chroma_shift / width, 0.0);
+        color = vec3(sample_frame(uv + shift).r, sample_frame(uv).g, sample_frame(uv - shift).b);
+    } else {
+        color = sample_frame(uv);
+    }
+
+    if (band > 0.0) {
+        float static_noise = hash(floor(pixel) + frame_salt);
+        color = mix(color, vec3(static_noise), band * 0.5);
+    }
+
+    // Dropouts: short white streaks on a few random lines
+    if (retro_dropouts > 0.0) {
+        float line = floor(pixel.y / 2.0);
+        float streak_start = hash(vec2(line, frame_salt + 5.0));
+        float streak_length = hash(vec2(line, frame_salt + 6.0)) * 0.15;
+        bool on_line = hash(vec2(line, frame_salt + 7.0)) < retro_dropouts * 0.02;
+        if (on_line && tex_coords.x > streak_start && tex_coords.x < streak_start + streak_length) {
+            float fade = smoothstep(0.0, 0.01, tex_coords.x - streak_start) * smoothstep(0.0, 0.01, streak_start + streak_length - tex_coords.x);
+            color = mix(color, vec3(1.0), fade * 0.9);
+        }
+    }
+
+    // Scanlines: darker gaps between the lines of the picture
+    if (retro_scanlines > 0.0) {
+        float spacing = retro_scanline_size > 0.0 ? max(retro_scanline_size, 1.0) : 3.0;
+        float phase = fract(pixel_coords.y / spacing);
+        float gap = smoothstep(0.5, 1.0, abs(phase * 2.0 - 1.0));
+        color *= 1.0 - gap * clamp(retro_scanlines, 0.0, 1.0);
+    }
+
+    // Film grain: scaled by a response that peaks in the midtones and fades
+    // out in deep shadows and clipped highlights, as on developed film
+    if (retro_grain > 0.0) {
+        float grain_size = retro_grain_size > 0.0 ? max(retro_grain_size, 0.5) : 1.5;
+        vec2 grain_pixel = pixel_coords / grain_size;
+        vec3 grain = vec3(
+            value_noise(grain_pixel, frame_salt),
+            value_noise(grain_pixel, frame_salt + 17.0),
+            value_noise(grain_pixel, frame_salt + 31.0)
+        ) * 2.0 - 1.0;
+        // Mostly luminance grain with a little color
+        grain = mix(vec3(grain.r), grain, 0.3);
+        vec3 luma = vec3(dot(color, vec3(0.2126, 0.7152, 0.0722)));
+        vec3 response = 4.0 * clamp(luma, 0.0, 1.0) * (1.0 - clamp(luma, 0.0, 1.0));
+        color = clamp(color + grain * response * clamp(retro_grain, 0.0, 1.0) * 0.25, 0.0, 1.0);
+    }
+
+    frag_color = vec4(color, 1.0);
+}
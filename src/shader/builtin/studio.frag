@@ -0,0 +1,126 @@
+// Builtin: studio, studio-chroma
+// A virtual studio: the keyed camera placed over a background (slot 0), with
+// up to three overlays (slots 1 - 3) blended on top in order. `#version` and,
+// for studio-chroma, `#define CHROMA_KEY` are prepended by Proteus; without it
+// the camera is keyed with the segmentation mask.
+// Parameters:
+//   studio_scale       Size of the camera in the scene (default 1)
+//   studio_offset_x    Position of the camera, moved from the frame center
+//   studio_offset_y    in frame heights
+//   studio_key_r       Key color (sRGB, 0 - 1) for studio-chroma (default green)
+//   studio_key_g
+//   studio_key_b
+//   studio_tolerance   Chroma distance from the key color that is fully removed (default 0.15)
+//   studio_softness    Width of the transition from removed to kept (default 0.1)
+//   studio_spill       Removal of key color reflected onto the subject (0 - 1)
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float studio_scale;
+    float studio_offset_x;
+    float studio_offset_y;
+    float studio_key_r;
+    float studio_key_g;
+    float studio_key_b;
+    float studio_tolerance;
+    float studio_softness;
+    float studio_spill;
+};
+
+#ifndef CHROMA_KEY
+layout(set = 0, binding = 3) uniform texture2D t_mask;
+#endif
+layout(set = 0, binding = 4) uniform texture2D t_image0;
+layout(set = 0, binding = 5) uniform texture2D t_image1;
+layout(set = 0, binding = 6) uniform texture2D t_image2;
+layout(set = 0, binding = 7) uniform texture2D t_image3;
+
+layout(location = 0) out vec4 frag_color;
+
+// Empty slots hold a 1x1 placeholder
+bool slot_used(ivec2 size) {
+    return size.x > 1 || size.y > 1;
+}
+
+// The background scaled to cover the frame
+vec3 background() {
+    ivec2 size = textureSize(sampler2D(t_image0, s_sampler), 0);
+    if (!slot_used(size)) {
+        return vec3(0.0);
+    }
+    float frame_aspect = width / height;
+    float image_aspect = float(size.x) / float(size.y);
+    vec2 uv = tex_coords - 0.5;
+    if (image_aspect > frame_aspect) {
+        uv.x *= frame_aspect / image_aspect;
+    } else {
+        uv.y *= image_aspect / frame_aspect;
+    }
+    return textureLod(sampler2D(t_image0, s_sampler), uv + 0.5, 0.0).rgb;
+}
+
+#ifdef CHROMA_KEY
+vec2 chroma(vec3 color) {
+    return vec2(dot(color, vec3(-0.1146, -0.3854, 0.5)), dot(color, vec3(0.5, -0.4542, -0.0458)));
+}
+
+// Keys out the key color; returns the despilled color and its alpha
+vec4 key(vec3 color) {
+    vec3 key_color = vec3(studio_key_r, studio_key_g, studio_key_b);
+    if (key_color == vec3(0.0)) {
+        key_color = vec3(0.0, 1.0, 0.0);
+    }
+    vec2 key_chroma = chroma(key_color);
+    vec2 pixel_chroma = chroma(color);
+    float tolerance = studio_tolerance > 0.0 ? studio_tolerance : 0.15;
+    float softness = studio_softness > 0.0 ? studio_softness : 0.1;
+    float alpha = smoothstep(tolerance, tolerance + softness, distance(pixel_chroma, key_chroma));
+
+    // Take the part of the pixel's chroma that points towards the key color out
+    if (length(key_chroma) > 0.0) {
+        vec2 direction = normalize(key_chroma);
+        float along = max(dot(pixel_chroma, direction), 0.0);
+        vec2 despilled = pixel_chroma - direction * along * clamp(studio_spill, 0.0, 1.0);
+        float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+        color = clamp(vec3(
+            luma + 1.5748 * despilled.y,
+            luma - 0.1873 * despilled.x - 0.4681 * despilled.y,
+            luma + 1.8556 * despilled.x
+        ), 0.0, 1.0);
+    }
+    return vec4(color, alpha);
+}
+#endif
+
+vec3 blend_overlay(vec3 color, texture2D overlay) {
+    if (!slot_used(textureSize(sampler2D(overlay, s_sampler), 0))) {
+        return color;
+    }
+    vec4 layer = textureLod(sampler2D(overlay, s_sampler), tex_coords, 0.0);
+    return mix(color, layer.rgb, layer.a);
+}
+
+void main() {
+    // Into the camera frame, placed and scaled in the scene
+    float scale = studio_scale > 0.0 ? studio_scale : 1.0;
+    vec2 offset = vec2(studio_offset_x, studio_offset_y);
+    vec2 uv = (aspect_coords - offset) / scale / vec2(width / height, 1.0) + 0.5;
+    bool inside = all(greaterThanEqual(uv, vec2(0.0))) && all(lessThanEqual(uv, vec2(1.0)));
+
+    vec3 color = background();
+    if (inside) {
+        vec3 camera = textureLod(sampler2D(t_texture, s_sampler), uv, 0.0).rgb;
+#ifdef CHROMA_KEY
+        vec4 keyed = key(camera);
+#else
+        vec4 keyed = vec4(camera, textureLod(sampler2D(t_mask, s_sampler), uv, 0.0).r);
+#endif
+        color = mix(color, keyed.rgb, keyed.a);
+    }
+
+    color = blend_overlay(color, t_image1);
+    color = blend_overlay(color, t_image2);
+    color = blend_overlay(color, t_image3);
+    frag_color = vec4(color, 1.0);
+}
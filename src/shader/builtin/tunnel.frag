@@ -0,0 +1,46 @@
+#version 450
+
+// Builtin: tunnel
+// Wraps the frame around the inside of a tube that runs into the screen.
+// Parameters (0 picks the default):
+//   tunnel_segments  Copies of the frame around the tube (default 1)
+//   tunnel_angle     Rotation around the tube in degrees
+//   tunnel_depth     Position along the tube in frame lengths (animate with a saw LFO)
+//   tunnel_speed     Frame lengths per second the tube moves by itself
+//   tunnel_offset_x  Position of the vanishing point, moved from the frame
+//   tunnel_offset_y  center in frame heights
+
+#include <proteus>
+
+layout(set = 0, binding = 8) uniform Params {
+    float tunnel_segments;
+    float tunnel_angle;
+    float tunnel_depth;
+    float tunnel_speed;
+    float tunnel_offset_x;
+    float tunnel_offset_y;
+};
+
+layout(location = 0) out vec4 frag_color;
+
+const float TAU = 6.28318530718;
+// Radius (in frame heights) at which the frame is shown at its real size
+const float NEAR_RADIUS = 0.5;
+
+vec2 mirror_repeat(vec2 uv) {
+    return 1.0 - abs(1.0 - mod(uv, 2.0));
+}
+
+void main() {
+    float segments = tunnel_segments > 0.0 ? max(round(tunnel_segments), 1.0) : 1.0;
+    vec2 point = aspect_coords - vec2(tunnel_offset_x, tunnel_offset_y);
+    float radius = max(length(point), 0.0001);
+
+    // Around the tube: each copy of the frame is mirrored so the seam at the back matches
+    float around = (atan(point.y, point.x) - radians(tunnel_angle)) / TAU * segments * 2.0;
+    float along = NEAR_RADIUS / radius + tunnel_depth + time * tunnel_speed;
+
+    vec4 color = textureLod(sampler2D(t_texture, s_sampler), mirror_repeat(vec2(around, along)), 0.0);
+    // Darker toward the vanishing point
+    frag_color = vec4(color.rgb * smoothstep(0.0, 0.35, radius), color.a);
+}
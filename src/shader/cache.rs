@@ -0,0 +1,181 @@
+//! On-disk caches that shorten shader compilation on startup and reload.
+//!
+//! GLSL shaders are stored as the WGSL they convert to, keyed by a hash of
+//! the source (and the Proteus version, as the conversion changes with naga).
+//! Only the most recently used `MAX_WGSL_ENTRIES` conversions are kept, so
+//! editing a shader for an evening doesn't leave thousands of stale entries.
+//! Where the backend supports it (Vulkan), the driver's compiled pipelines are
+//! kept as well, in one file per adapter; drivers discard data from other
+//! driver versions themselves.
+
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use tracing::{debug, info, warn};
+
+/// Converted shaders kept in the cache
+const MAX_WGSL_ENTRIES: usize = 200;
+
+/// Shader caches in a directory (normally `<cache directory>/shaders`).
+#[derive(Debug, Clone)]
+pub struct ShaderCache {
+    dir: PathBuf,
+}
+
+/// Temporary files written so far by this process
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// Writes via a temporary file, so a crash never leaves a truncated cache entry.
+/// The temporary file is unique to the process and the call, as other instances
+/// and chains may write the same entry at the same time.
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.{}.tmp", std::process::id(), TEMP_FILES.fetch_add(1, Ordering::Relaxed)));
+    let temp = PathBuf::from(temp);
+    let result = std::fs::write(&temp, data).and_then(|()| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+impl ShaderCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn wgsl_path(&self, glsl: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update([0]);
+        hasher.update(glsl);
+        let hash: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join("wgsl").join(format!("{}.wgsl", hash))
+    }
+
+    /// The WGSL a GLSL shader was converted to before, if cached.
+    pub fn load_wgsl(&self, glsl: &str) -> Option<String> {
+        let path = self.wgsl_path(glsl);
+        let wgsl = std::fs::read_to_string(&path).ok()?;
+        debug!("Using cached WGSL {:?}", path);
+        // The modification time marks the last use for eviction
+        if let Ok(file) = std::fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(wgsl)
+    }
+
+    /// Caches a conversion and evicts the least recently used ones beyond `MAX_WGSL_ENTRIES`.
+    pub fn store_wgsl(&self, glsl: &str, wgsl: &str) {
+        let path = self.wgsl_path(glsl);
+        if let Err(e) = write_atomic(&path, wgsl.as_bytes()) {
+            warn!("Failed to cache converted shader at {:?}: {}", path, e);
+        }
+        evict(&self.dir.join("wgsl"), MAX_WGSL_ENTRIES);
+    }
+
+    fn pipelines_path(&self, adapter: &wgpu::AdapterInfo) -> Option<PathBuf> {
+        wgpu::util::pipeline_cache_key(adapter).map(|key| self.dir.join(key))
+    }
+
+    /// Creates the device's pipeline cache, seeded with the data saved for this adapter.
+    /// Returns `None` if the backend has no pipeline caches.
+    pub fn load_pipelines(&self, device: &wgpu::Device, adapter: &wgpu::AdapterInfo) -> Option<wgpu::PipelineCache> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return None;
+        }
+        let path = self.pipelines_path(adapter)?;
+        let data = std::fs::read(&path).ok();
+        if data.is_some() {
+            info!("Loading pipeline cache {:?}", path);
+        }
+        // SAFETY: The data was written by `store_pipelines` for this adapter; with
+        // `fallback`, wgpu validates it and starts an empty cache if it doesn't match.
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline Cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Some(cache)
+    }
+
+    /// Saves the compiled pipelines for the next start.
+    pub fn store_pipelines(&self, cache: &wgpu::PipelineCache, adapter: &wgpu::AdapterInfo) {
+        let (Some(path), Some(data)) = (self.pipelines_path(adapter), cache.get_data()) else { return };
+        match write_atomic(&path, &data) {
+            Ok(()) => debug!("Saved {} byte pipeline cache to {:?}", data.len(), path),
+            Err(e) => warn!("Failed to save pipeline cache to {:?}: {}", path, e),
+        }
+    }
+}
+
+/// Removes all but the `keep` most recently modified `.wgsl` files in `dir`.
+fn evict(dir: &Path, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|extension| extension == "wgsl"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    if files.len() <= keep {
+        return;
+    }
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in &files[keep..] {
+        debug!("Evicting cached WGSL {:?}", path);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wgsl_cache() {
+        let dir = std::env::temp_dir().join(format!("proteus-shader-cache-{}", std::process::id()));
+        let cache = ShaderCache::new(dir.clone());
+        assert_eq!(cache.load_wgsl("void main() {}"), None);
+        cache.store_wgsl("void main() {}", "@fragment fn main() {}");
+        assert_eq!(cache.load_wgsl("void main() {}").as_deref(), Some("@fragment fn main() {}"));
+        assert_eq!(cache.load_wgsl("void main() { }"), None);
+
+        // The least recently used conversions go first
+        cache.store_wgsl("void a() {}", "a");
+        cache.store_wgsl("void b() {}", "b");
+        let old = SystemTime::now() - std::time::Duration::from_secs(60);
+        for glsl in ["void main() {}", "void a() {}"] {
+            std::fs::File::options().append(true).open(cache.wgsl_path(glsl)).unwrap().set_modified(old).unwrap();
+        }
+        cache.load_wgsl("void main() {}");
+        evict(&dir.join("wgsl"), 2);
+        assert!(cache.load_wgsl("void main() {}").is_some());
+        assert!(cache.load_wgsl("void b() {}").is_some());
+        assert_eq!(cache.load_wgsl("void a() {}"), None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join(format!("proteus-write-atomic-{}", std::process::id()));
+        let path = dir.join("entry.wgsl");
+        // Concurrent writers of the same entry don't share a temporary file
+        std::thread::scope(|scope| {
+            for i in 0..8u8 {
+                let path = &path;
+                scope.spawn(move || write_atomic(path, &[i; 4096]).unwrap());
+            }
+        });
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.len() == 4096 && data.iter().all(|byte| *byte == data[0]));
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,407 @@
+//! Per-output color calibration.
+//!
+//! Projectors, monitors and the apps consuming the virtual camera rarely agree
+//! on color, so the preview window and the outputs (virtual camera, recordings,
+//! snapshots) each get their own gamma, brightness, contrast and saturation
+//! adjustments plus an optional 1D or 3D LUT in the `.cube` format. They are
+//! applied in the final blit of each output, after all shaders, so what the
+//! shaders see (and the other output) is unaffected.
+
+use anyhow::{anyhow, bail, Result};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use wgpu::util::DeviceExt;
+
+/// Largest supported 3D LUT (65 is the largest common size).
+const MAX_LUT_3D_SIZE: u32 = 256;
+
+/// Largest supported 1D LUT.
+const MAX_LUT_1D_SIZE: u32 = 8192;
+
+/// Transfer characteristics of an output's signal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transfer {
+    /// sRGB-encoded, what screens, cameras and video apps expect
+    #[default]
+    Srgb,
+    /// Linear light, for consumers that apply the sRGB curve themselves
+    Linear,
+}
+
+/// Calibration settings of one output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// Values above 1.0 brighten the midtones, below 1.0 darken them
+    pub gamma: f32,
+    /// Added to all channels (-1.0 - 1.0)
+    pub brightness: f32,
+    /// Scales the distance from mid grey (1.0 = unchanged)
+    pub contrast: f32,
+    /// 0.0 (greyscale) - 1.0 (unchanged) - 2.0 (doubled)
+    pub saturation: f32,
+    /// `.cube` LUT applied after the adjustments
+    pub lut: Option<PathBuf>,
+    /// Signal written to the output
+    pub transfer: Transfer,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { gamma: 1.0, brightness: 0.0, contrast: 1.0, saturation: 1.0, lut: None, transfer: Transfer::Srgb }
+    }
+}
+
+/// Fragment shader helpers for the calibration (bind group 1). The including
+/// shader calls `calibrate` on the linear color it writes to its target;
+/// adjustments and LUTs work on sRGB-encoded values, like image editors do.
+/// The result is decoded as often as the target and the transfer require:
+/// once for sRGB targets (which encode on write), once more for linear outputs.
+pub(crate) const CALIBRATION_SHADER: &str = r#"
+struct CalibrationUniforms {
+    // x = gamma, y = brightness, z = contrast, w = saturation
+    adjust: vec4<f32>,
+    // x = LUT kind (0 = none, 1 = 1D, 2 = 3D), y = LUT size, z = decodes of the result
+    lut: vec4<f32>,
+    domain_min: vec4<f32>,
+    domain_max: vec4<f32>,
+}
+
+@group(1) @binding(0) var<uniform> u_calibration: CalibrationUniforms;
+@group(1) @binding(1) var t_lut_1d: texture_2d<f32>;
+@group(1) @binding(2) var t_lut_3d: texture_3d<f32>;
+@group(1) @binding(3) var s_lut: sampler;
+
+fn calibration_encode(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+fn calibration_decode(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+fn calibrate(linear: vec3<f32>) -> vec3<f32> {
+    let adjust = u_calibration.adjust;
+    var c = calibration_encode(clamp(linear, vec3<f32>(0.0), vec3<f32>(1.0)));
+    c = (c - 0.5) * adjust.z + 0.5 + adjust.y;
+    let luma = dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+    c = clamp(mix(vec3<f32>(luma), c, adjust.w), vec3<f32>(0.0), vec3<f32>(1.0));
+    c = pow(c, vec3<f32>(1.0 / max(adjust.x, 0.01)));
+
+    let kind = u32(u_calibration.lut.x);
+    if (kind != 0u) {
+        let size = u_calibration.lut.y;
+        let domain = max(u_calibration.domain_max.rgb - u_calibration.domain_min.rgb, vec3<f32>(1e-6));
+        let t = clamp((c - u_calibration.domain_min.rgb) / domain, vec3<f32>(0.0), vec3<f32>(1.0));
+        // Sample at texel centers, so 0.0 and 1.0 hit the first and last entries
+        let coord = (t * (size - 1.0) + 0.5) / size;
+        if (kind == 1u) {
+            c = vec3<f32>(
+                textureSampleLevel(t_lut_1d, s_lut, vec2<f32>(coord.r, 0.5), 0.0).r,
+                textureSampleLevel(t_lut_1d, s_lut, vec2<f32>(coord.g, 0.5), 0.0).g,
+                textureSampleLevel(t_lut_1d, s_lut, vec2<f32>(coord.b, 0.5), 0.0).b,
+            );
+        } else {
+            c = textureSampleLevel(t_lut_3d, s_lut, coord, 0.0).rgb;
+        }
+    }
+    c = clamp(c, vec3<f32>(0.0), vec3<f32>(1.0));
+    for (var i = 0u; i < u32(u_calibration.lut.z); i++) {
+        c = calibration_decode(c);
+    }
+    return c;
+}
+"#;
+
+/// A LUT in the `.cube` format (Adobe / DaVinci Resolve).
+#[derive(Debug, Clone, PartialEq)]
+struct CubeLut {
+    /// 3D (`LUT_3D_SIZE`) or 1D (`LUT_1D_SIZE`)
+    three_d: bool,
+    size: u32,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Red changes fastest, then green, then blue
+    entries: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    fn parse(text: &str) -> Result<Self> {
+        let mut lut = CubeLut { three_d: true, size: 0, domain_min: [0.0; 3], domain_max: [1.0; 3], entries: Vec::new() };
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let numbers = || -> Result<Vec<f32>> {
+                line.split_whitespace()
+                    .skip(usize::from(keyword.starts_with(|c: char| c.is_ascii_alphabetic())))
+                    .map(|word| word.parse().map_err(|_| anyhow!("Line {}: invalid number '{}'", i + 1, word)))
+                    .collect()
+            };
+            let triple = |values: Vec<f32>| -> Result<[f32; 3]> {
+                values.try_into().map_err(|_| anyhow!("Line {}: expected three values", i + 1))
+            };
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    lut.three_d = keyword == "LUT_3D_SIZE";
+                    lut.size = words.next().and_then(|size| size.parse().ok()).ok_or_else(|| anyhow!("Line {}: invalid LUT size", i + 1))?;
+                }
+                "DOMAIN_MIN" => lut.domain_min = triple(numbers()?)?,
+                "DOMAIN_MAX" => lut.domain_max = triple(numbers()?)?,
+                // Resolve's variant of the domain, the same for all channels
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => match numbers()?.as_slice() {
+                    [min, max] => {
+                        lut.domain_min = [*min; 3];
+                        lut.domain_max = [*max; 3];
+                    }
+                    _ => bail!("Line {}: expected two values", i + 1),
+                },
+                // Other keywords (e.g. LUT_IN_VIDEO_RANGE) don't affect the mapping
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => lut.entries.push(triple(numbers()?)?),
+            }
+        }
+
+        let (max_size, expected) = if lut.three_d {
+            (MAX_LUT_3D_SIZE, lut.size.pow(3) as usize)
+        } else {
+            (MAX_LUT_1D_SIZE, lut.size as usize)
+        };
+        if !(2..=max_size).contains(&lut.size) {
+            bail!("Unsupported LUT size {} (expected 2 - {})", lut.size, max_size);
+        }
+        if lut.entries.len() != expected {
+            bail!("Expected {} LUT entries, found {}", expected, lut.entries.len());
+        }
+        Ok(lut)
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read LUT {:?}: {}", path, e))?;
+        let lut = Self::parse(&text).map_err(|e| anyhow!("Invalid LUT {:?}: {}", path, e))?;
+        info!("Loaded {} LUT from {:?} (size {})", if lut.three_d { "3D" } else { "1D" }, path, lut.size);
+        Ok(lut)
+    }
+
+    /// Entries as RGBA half floats (filterable on all GPUs, unlike 32-bit floats).
+    fn texels(&self) -> Vec<u16> {
+        self.entries
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 1.0])
+            .map(|value| half::f16::from_f32(value).to_bits())
+            .collect()
+    }
+}
+
+/// How often `calibrate` decodes its sRGB-encoded result for a target that
+/// is (or isn't) sRGB, so the target ends up holding the `transfer` signal.
+fn decodes(transfer: Transfer, target_srgb: bool) -> u32 {
+    u32::from(target_srgb) + u32::from(transfer == Transfer::Linear)
+}
+
+/// Uniforms of `CALIBRATION_SHADER`.
+fn uniforms(settings: &Calibration, lut: Option<&CubeLut>, target_srgb: bool) -> [f32; 16] {
+    let (kind, size, min, max) = match lut {
+        Some(lut) => (if lut.three_d { 2.0 } else { 1.0 }, lut.size as f32, lut.domain_min, lut.domain_max),
+        None => (0.0, 1.0, [0.0; 3], [1.0; 3]),
+    };
+    [
+        settings.gamma, settings.brightness, settings.contrast, settings.saturation,
+        kind, size, decodes(settings.transfer, target_srgb) as f32, 0.0,
+        min[0], min[1], min[2], 0.0,
+        max[0], max[1], max[2], 0.0,
+    ]
+}
+
+/// GPU resources of one output's calibration: bind group 1 of its final blit.
+pub(crate) struct CalibrationBinding {
+    settings: Option<Calibration>,
+    // Whether the blit target encodes to sRGB on write
+    target_srgb: bool,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group: wgpu::BindGroup,
+}
+
+impl CalibrationBinding {
+    /// Creates the binding without calibration (the blit passes colors through,
+    /// sRGB-encoded) for a blit to an sRGB or a linear target.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, target_srgb: bool) -> Self {
+        let texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Calibration Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1, wgpu::TextureViewDimension::D2),
+                texture_entry(2, wgpu::TextureViewDimension::D3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Calibration Uniform Buffer"),
+            contents: bytemuck::cast_slice(&uniforms(&Calibration::default(), None, target_srgb)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Calibration LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = Self::create_bind_group(device, queue, &bind_group_layout, &uniform_buffer, &sampler, None);
+        Self { settings: None, target_srgb, bind_group_layout, uniform_buffer, sampler, bind_group }
+    }
+
+    /// Creates the bind group with the LUT textures (1x1 placeholders for the
+    /// kinds not in use).
+    fn create_bind_group(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+        lut: Option<&CubeLut>,
+    ) -> wgpu::BindGroup {
+        let texture = |dimension, size: wgpu::Extent3d, texels: &[u16]| {
+            device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("Calibration LUT Texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                bytemuck::cast_slice(texels),
+            )
+        };
+        let placeholder = [0u16; 4];
+        let one = wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 };
+        let texels = lut.map(CubeLut::texels).unwrap_or_default();
+        let lut_1d = match lut {
+            Some(lut) if !lut.three_d => texture(wgpu::TextureDimension::D2, wgpu::Extent3d { width: lut.size, ..one }, &texels),
+            _ => texture(wgpu::TextureDimension::D2, one, &placeholder),
+        };
+        let lut_3d = match lut {
+            Some(lut) if lut.three_d => texture(
+                wgpu::TextureDimension::D3,
+                wgpu::Extent3d { width: lut.size, height: lut.size, depth_or_array_layers: lut.size },
+                &texels,
+            ),
+            _ => texture(wgpu::TextureDimension::D3, one, &placeholder),
+        };
+
+        let lut_1d_view = lut_1d.create_view(&wgpu::TextureViewDescriptor::default());
+        let lut_3d_view = lut_3d.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Calibration Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&lut_1d_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&lut_3d_view) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::Sampler(sampler) },
+            ],
+        })
+    }
+
+    /// Sets (or removes) the calibration, loading its LUT. If the LUT can't be
+    /// loaded the calibration is removed.
+    pub fn set(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, settings: Option<Calibration>) -> Result<()> {
+        let lut = match settings.as_ref().and_then(|settings| settings.lut.as_ref()).map(|path| CubeLut::load(path)).transpose() {
+            Ok(lut) => lut,
+            Err(e) => {
+                self.set(device, queue, None)?;
+                return Err(e);
+            }
+        };
+        let uniforms = uniforms(settings.as_ref().unwrap_or(&Calibration::default()), lut.as_ref(), self.target_srgb);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+        self.bind_group = Self::create_bind_group(device, queue, &self.bind_group_layout, &self.uniform_buffer, &self.sampler, lut.as_ref());
+        self.settings = settings;
+        Ok(())
+    }
+
+    pub fn settings(&self) -> Option<&Calibration> {
+        self.settings.as_ref()
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cube() {
+        let lut = CubeLut::parse(
+            "# Created by hand\nTITLE \"Warm\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 1 1\n\
+             0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n",
+        )
+        .unwrap();
+        assert!(lut.three_d);
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.entries[1], [1.0, 0.0, 0.0]);
+        assert_eq!(lut.texels().len(), 8 * 4);
+
+        let lut = CubeLut::parse("LUT_1D_SIZE 3\nLUT_1D_INPUT_RANGE 0.0 2.0\n0 0 0\n0.6 0.5 0.4\n1 1 1\n").unwrap();
+        assert!(!lut.three_d);
+        assert_eq!(lut.domain_max, [2.0; 3]);
+
+        assert!(CubeLut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(CubeLut::parse("LUT_1D_SIZE 2\n0 0\n1 1 1\n").is_err());
+        assert!(CubeLut::parse("0 0 0\n").is_err());
+
+        assert_eq!(decodes(Transfer::Srgb, true), 1);
+        assert_eq!(decodes(Transfer::Srgb, false), 0);
+        assert_eq!(decodes(Transfer::Linear, true), 2);
+    }
+}
@@ -0,0 +1,376 @@
+//! Caption burn-in.
+//!
+//! Blends the current caption (an SRT cue or live text, see
+//! [`crate::captions`]) onto the final frame after the layout. The caption is
+//! rendered at the output resolution whenever its text changes and drawn
+//! pixel for pixel.
+
+use crate::captions::{load_srt, render_caption, CaptionStyle, CaptionTrack};
+use crate::frame::QuadVertex;
+use crate::lua_canvas::FontDatabase;
+use anyhow::Result;
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+use wgpu::util::DeviceExt;
+
+/// Frame edge the captions are placed at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptionPosition {
+    Top,
+    #[default]
+    Bottom,
+}
+
+/// Caption settings. Colors are sRGB RGBA (0 - 1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Captions {
+    /// Subtitle file timed by the shaders' `time`
+    pub srt: Option<PathBuf>,
+    /// Font family (first system font if not found)
+    pub font: Option<String>,
+    /// Text height as a fraction of the frame height
+    pub size: f32,
+    pub color: [f32; 4],
+    /// Box behind the text
+    pub background: [f32; 4],
+    pub position: CaptionPosition,
+    /// Distance from the top or bottom edge as a fraction of the frame height
+    pub margin: f32,
+    /// Widest line as a fraction of the frame width
+    pub max_width: f32,
+    /// Seconds a live caption is shown after it was sent (0 = until cleared)
+    pub live_duration: f32,
+}
+
+impl Default for Captions {
+    fn default() -> Self {
+        Self {
+            srt: None,
+            font: None,
+            size: 0.05,
+            color: [1.0, 1.0, 1.0, 1.0],
+            background: [0.0, 0.0, 0.0, 0.6],
+            position: CaptionPosition::Bottom,
+            margin: 0.06,
+            max_width: 0.8,
+            live_duration: 5.0,
+        }
+    }
+}
+
+impl Captions {
+    /// Text style for a frame of `frame_size` output pixels.
+    fn style(&self, frame_size: (u32, u32)) -> CaptionStyle {
+        let to_u8 = |color: [f32; 4]| color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        CaptionStyle {
+            font: self.font.clone(),
+            size: self.size * frame_size.1 as f32,
+            color: to_u8(self.color),
+            background: to_u8(self.background),
+            max_width: self.max_width * frame_size.0 as f32,
+        }
+    }
+
+    /// Returns the caption rectangle (left, top, right, bottom) in 0.0 - 1.0 frame
+    /// coordinates for an image of `image_size` on a frame of `frame_size`.
+    fn rect(&self, image_size: (u32, u32), frame_size: (u32, u32)) -> [f32; 4] {
+        let w = image_size.0 as f32 / frame_size.0.max(1) as f32;
+        let h = image_size.1 as f32 / frame_size.1.max(1) as f32;
+        let left = (1.0 - w) / 2.0;
+        let top = match self.position {
+            CaptionPosition::Top => self.margin,
+            CaptionPosition::Bottom => 1.0 - self.margin - h,
+        };
+        [left, top, left + w, top + h]
+    }
+}
+
+const CAPTION_SHADER: &str = r#"
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct CaptionUniforms {
+    // left, top, right, bottom in 0..1 frame coordinates
+    rect: vec4<f32>,
+}
+
+@group(0) @binding(0) var t_caption: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_caption: CaptionUniforms;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = mix(u_caption.rect.xy, u_caption.rect.zw, in.tex_coords);
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(t_caption, s_sampler, tex_coords);
+}
+"#;
+
+/// A rendered caption on the GPU.
+struct CaptionImage {
+    text: String,
+    frame_size: (u32, u32),
+    image_size: (u32, u32),
+    bind_group: wgpu::BindGroup,
+}
+
+/// GPU resources and cues of the caption pass.
+pub(super) struct CaptionPass {
+    settings: Captions,
+    fonts: Arc<FontDatabase>,
+    track: CaptionTrack,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    /// Caption shown, if any
+    image: Option<CaptionImage>,
+}
+
+impl CaptionPass {
+    /// Loads the subtitle file (if any) and creates the overlay pipeline.
+    pub fn new(device: &wgpu::Device, settings: Captions, fonts: Arc<FontDatabase>) -> Result<Self> {
+        let cues = match &settings.srt {
+            Some(path) => {
+                let cues = load_srt(path)?;
+                info!("Loaded {} captions from {:?}", cues.len(), path);
+                cues
+            }
+            None => Vec::new(),
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Caption Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Caption Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Caption Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Caption Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Caption Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(CAPTION_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Caption Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Ok(Self {
+            settings,
+            fonts,
+            track: CaptionTrack::new(cues),
+            pipeline,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            image: None,
+        })
+    }
+
+    pub fn settings(&self) -> &Captions {
+        &self.settings
+    }
+
+    /// The system fonts, shared with the pass that replaces this one.
+    pub fn fonts(&self) -> Arc<FontDatabase> {
+        self.fonts.clone()
+    }
+
+    /// Takes over the live caption of `other`, e.g. after a settings change.
+    pub fn continue_from(&mut self, other: &CaptionPass) {
+        self.track.continue_from(&other.track);
+    }
+
+    /// Shows `text` live, or removes the live caption.
+    pub fn set_live(&mut self, text: Option<String>) {
+        self.track.set_live(text, Instant::now());
+    }
+
+    /// Renders the caption for shader time `time` on a frame of `frame_size`
+    /// if it changed since the last frame.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, time: f32, frame_size: (u32, u32)) {
+        let text = self.track.text(time, Instant::now(), self.settings.live_duration);
+        if self.image.as_ref().map(|image| (image.text.as_str(), image.frame_size)) == text.map(|text| (text, frame_size)) {
+            return;
+        }
+        let Some(text) = text else {
+            self.image = None;
+            return;
+        };
+        let Some(image) = render_caption(&self.fonts, text, &self.settings.style(frame_size)) else {
+            // Blank text or no fonts
+            self.image = None;
+            return;
+        };
+        let (w, h) = image.dimensions();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Caption Texture"),
+            size: wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            &image,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(w * 4), rows_per_image: Some(h) },
+            wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Caption Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        });
+        self.image = Some(CaptionImage { text: text.to_string(), frame_size, image_size: (w, h), bind_group });
+    }
+
+    /// Records the caption onto `target` (the final frame) into `encoder`.
+    pub fn encode(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Texture,
+        vertex_buffer: &wgpu::Buffer,
+        index_buffer: &wgpu::Buffer,
+    ) {
+        let Some(image) = &self.image else { return };
+        let rect = self.settings.rect(image.image_size, (target.width(), target.height()));
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&rect));
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Caption Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &image.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_placement() {
+        let mut captions = Captions::default();
+        // A 500x100 caption on a 1000x500 frame
+        let [left, top, right, bottom] = captions.rect((500, 100), (1000, 500));
+        assert_eq!((left, right), (0.25, 0.75));
+        assert!((bottom - 0.94).abs() < 1e-6 && (bottom - top - 0.2).abs() < 1e-6);
+
+        captions.position = CaptionPosition::Top;
+        let [_, top, _, _] = captions.rect((500, 100), (1000, 500));
+        assert!((top - 0.06).abs() < 1e-6);
+
+        let style = captions.style((1920, 1080));
+        assert!((style.size - 54.0).abs() < 1e-3 && (style.max_width - 1536.0).abs() < 1e-3);
+        assert_eq!(style.background, [0, 0, 0, 153]);
+    }
+}
@@ -0,0 +1,238 @@
+//! sRGB / linear audit of the color path.
+//!
+//! Renders a test pattern of known sRGB values through the pipeline with the
+//! passthrough shader: from each camera pixel format (with CPU and GPU YUV
+//! conversion) to each output format. Each result is read back and compared
+//! with the values it should have. A missing sRGB encoding makes the result too
+//! dark, and a double one makes it too bright, so the stage where either happens
+//! can be named. `proteus color-audit` prints the report.
+
+use super::calibration::{Calibration, Transfer};
+use super::gpu_context::GpuContext;
+use super::{Sink, WgpuPipeline};
+use crate::frame::{PixelFormat, VideoFrame};
+use anyhow::Result;
+use std::fmt;
+use std::sync::Arc;
+
+/// Size of the test pattern (even, for the YUV formats).
+const PATTERN_WIDTH: u32 = 640;
+const PATTERN_HEIGHT: u32 = 360;
+
+/// Patches of the test pattern as sRGB values, one vertical bar each: a grey
+/// ramp, which shows transfer errors, and the primaries, which show swapped
+/// channels and wrong YUV matrices.
+const PATCHES: [[u8; 3]; 8] = [
+    [16, 16, 16],
+    [64, 64, 64],
+    [128, 128, 128],
+    [192, 192, 192],
+    [240, 240, 240],
+    [192, 0, 0],
+    [0, 192, 0],
+    [0, 0, 192],
+];
+
+/// Largest acceptable difference for RGB paths, and for paths through YUV
+/// (8-bit quantization of both conversions and chroma subsampling).
+const RGB_TOLERANCE: u8 = 2;
+const YUV_TOLERANCE: u8 = 6;
+
+/// Outcome of one stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ok,
+    /// The greys are too bright: sRGB values were encoded twice
+    DoubleEncoded,
+    /// The greys are too dark: linear values were output without encoding
+    MissingEncoding,
+    /// Off in some other way (channel order, YUV matrix or range)
+    Wrong,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Verdict::Ok => "ok",
+            Verdict::DoubleEncoded => "double sRGB encoding (too bright)",
+            Verdict::MissingEncoding => "missing sRGB encoding (too dark)",
+            Verdict::Wrong => "wrong colors",
+        })
+    }
+}
+
+/// Result of one path through the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageReport {
+    /// e.g. "NV12 input (GPU) -> RGBA output"
+    pub name: String,
+    /// Largest difference of a channel from the expected value
+    pub max_error: u8,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for StageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<36} max error {:>3}  {}", self.name, self.max_error, self.verdict)
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn to_byte(c: f32) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// `patch` as it should arrive at an output with the given transfer.
+fn expected(patch: [u8; 3], transfer: Transfer) -> [u8; 3] {
+    match transfer {
+        Transfer::Srgb => patch,
+        Transfer::Linear => patch.map(|value| to_byte(srgb_to_linear(value))),
+    }
+}
+
+fn max_error(expected: &[[u8; 3]], actual: &[[u8; 3]]) -> u8 {
+    expected
+        .iter()
+        .zip(actual)
+        .flat_map(|(expected, actual)| expected.iter().zip(actual).map(|(e, a)| e.abs_diff(*a)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Compares the measured patches with the expected ones. Errors beyond the
+/// tolerance are checked against the results of one sRGB encoding too many or
+/// too few.
+pub fn classify(expected: &[[u8; 3]], actual: &[[u8; 3]], tolerance: u8) -> (u8, Verdict) {
+    let error = max_error(expected, actual);
+    if error <= tolerance {
+        return (error, Verdict::Ok);
+    }
+    let encoded: Vec<[u8; 3]> = expected.iter().map(|patch| patch.map(|value| to_byte(linear_to_srgb(value as f32 / 255.0)))).collect();
+    let decoded: Vec<[u8; 3]> = expected.iter().map(|patch| patch.map(|value| to_byte(srgb_to_linear(value)))).collect();
+    let verdict = if max_error(&encoded, actual) <= tolerance * 2 {
+        Verdict::DoubleEncoded
+    } else if max_error(&decoded, actual) <= tolerance * 2 {
+        Verdict::MissingEncoding
+    } else {
+        Verdict::Wrong
+    };
+    (error, verdict)
+}
+
+/// The test pattern as an RGBA frame.
+pub fn test_pattern() -> VideoFrame {
+    let mut frame = VideoFrame::new(PATTERN_WIDTH, PATTERN_HEIGHT, PixelFormat::Rgba);
+    let bar_width = PATTERN_WIDTH / PATCHES.len() as u32;
+    for (i, pixel) in frame.data.chunks_exact_mut(4).enumerate() {
+        let x = i as u32 % PATTERN_WIDTH;
+        let [r, g, b] = PATCHES[(x / bar_width) as usize];
+        pixel.copy_from_slice(&[r, g, b, 255]);
+    }
+    frame
+}
+
+/// Colors at the centers of the patches of an RGBA frame of the pattern.
+fn measure(frame: &VideoFrame) -> Vec<[u8; 3]> {
+    let bar_width = frame.width / PATCHES.len() as u32;
+    (0..PATCHES.len() as u32)
+        .map(|i| {
+            let offset = ((frame.height / 2 * frame.width + i * bar_width + bar_width / 2) * 4) as usize;
+            [frame.data[offset], frame.data[offset + 1], frame.data[offset + 2]]
+        })
+        .collect()
+}
+
+fn format_name(format: PixelFormat) -> &'static str {
+    match format {
+        PixelFormat::Nv12 => "NV12",
+        PixelFormat::Yuyv => "YUYV",
+        PixelFormat::Uyvy => "UYVY",
+        PixelFormat::Rgb => "RGB",
+        PixelFormat::Rgba => "RGBA",
+    }
+}
+
+fn convert(frame: &VideoFrame, format: PixelFormat) -> VideoFrame {
+    match format {
+        PixelFormat::Nv12 => frame.to_nv12(),
+        PixelFormat::Yuyv => frame.to_yuyv(),
+        PixelFormat::Uyvy => frame.to_uyvy(),
+        PixelFormat::Rgb | PixelFormat::Rgba => frame.clone(),
+    }
+}
+
+/// Runs the pattern through all camera formats (converted on the CPU and the
+/// GPU) to RGBA, and from RGBA through the packed output formats. The frames
+/// are read back for `sink` with the given transfer, without any other calibration.
+pub fn run(context: Arc<GpuContext>, sink: Sink, transfer: Transfer) -> Result<Vec<StageReport>> {
+    let mut pipeline = WgpuPipeline::new(context, PATTERN_WIDTH, PATTERN_HEIGHT, Vec::new(), Vec::new(), Vec::new())?;
+    pipeline.set_sink_calibration(sink, Some(Calibration { transfer, ..Default::default() }))?;
+    let pattern = test_pattern();
+    let expected: Vec<[u8; 3]> = PATCHES.iter().map(|&patch| expected(patch, transfer)).collect();
+
+    let inputs = [
+        (PixelFormat::Rgba, false),
+        (PixelFormat::Yuyv, false),
+        (PixelFormat::Yuyv, true),
+        (PixelFormat::Nv12, false),
+        (PixelFormat::Nv12, true),
+    ];
+    let outputs = [PixelFormat::Nv12, PixelFormat::Yuyv, PixelFormat::Uyvy];
+    let paths = inputs
+        .into_iter()
+        .map(|(input, gpu)| (input, gpu, PixelFormat::Rgba))
+        .chain(outputs.into_iter().map(|output| (PixelFormat::Rgba, false, output)));
+
+    let mut reports = Vec::new();
+    for (input, gpu, output) in paths {
+        pipeline.set_yuv_passthrough(gpu);
+        pipeline.set_output_format(output);
+        pipeline.process_frame_gpu(&convert(&pattern, input), 0.0)?;
+        let frame = pipeline.read_output(sink)?.to_rgba();
+
+        let through_yuv = input != PixelFormat::Rgba || output != PixelFormat::Rgba;
+        let tolerance = if through_yuv { YUV_TOLERANCE } else { RGB_TOLERANCE };
+        let (max_error, verdict) = classify(&expected, &measure(&frame), tolerance);
+        let conversion = match (input, gpu) {
+            (PixelFormat::Rgba, _) => "",
+            (_, true) => " (GPU)",
+            (_, false) => " (CPU)",
+        };
+        reports.push(StageReport {
+            name: format!("{} input{} -> {} output", format_name(input), conversion, format_name(output)),
+            max_error,
+            verdict,
+        });
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let expected: Vec<[u8; 3]> = PATCHES.to_vec();
+        assert_eq!(classify(&expected, &expected, RGB_TOLERANCE), (0, Verdict::Ok));
+
+        let too_bright: Vec<[u8; 3]> = expected.iter().map(|p| p.map(|v| to_byte(linear_to_srgb(v as f32 / 255.0)))).collect();
+        assert_eq!(classify(&expected, &too_bright, RGB_TOLERANCE).1, Verdict::DoubleEncoded);
+        let too_dark: Vec<[u8; 3]> = expected.iter().map(|p| p.map(srgb_to_linear).map(to_byte)).collect();
+        assert_eq!(classify(&expected, &too_dark, RGB_TOLERANCE).1, Verdict::MissingEncoding);
+        let swapped: Vec<[u8; 3]> = expected.iter().map(|&[r, g, b]| [b, g, r]).collect();
+        assert_eq!(classify(&expected, &swapped, RGB_TOLERANCE).1, Verdict::Wrong);
+
+        let pattern = test_pattern();
+        assert_eq!(measure(&pattern), expected);
+        assert_eq!(super::expected([128, 128, 128], Transfer::Linear), [55, 55, 55]);
+    }
+}
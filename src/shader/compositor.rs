@@ -0,0 +1,580 @@
+//! Config-driven compositing of several sources into one frame.
+//!
+//! Places the camera, the shader output and the texture slots (images, videos,
+//! Lua canvases) in rectangles with rounded corners and opacity, drawn back to
+//! front. Runs either before the first pass (the shaders see the composed frame)
+//! or after the last one, so talk-show style layouts need no custom shader.
+//!
+//! Several named layouts can be configured and switched at runtime. Layers that
+//! show the same source in both layouts move to their new place; the others
+//! enter and leave with the configured transition style.
+
+use crate::frame::QuadVertex;
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+/// Name of the layout given by `Compositor::layers`.
+pub const DEFAULT_LAYOUT: &str = "default";
+
+/// Layers drawn at most (during a transition, both layouts count).
+const MAX_LAYERS: usize = 64;
+
+/// Dynamic uniform offsets must be 256-byte aligned.
+const UNIFORM_STRIDE: usize = 256;
+
+/// Where in the pipeline the sources are composed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompositorStage {
+    /// Before the first pass, whose input is the composed frame
+    Before,
+    /// After the last pass (and the accumulation), before the output layout
+    #[default]
+    After,
+}
+
+/// Image shown in a compositor layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositorSource {
+    /// The camera input (after text blur and mask refinement)
+    Camera,
+    /// The shader output (the camera in the `Before` stage)
+    Output,
+    /// A texture slot (0 - 3)
+    Texture(usize),
+}
+
+impl CompositorSource {
+    /// Index of the source's bind group.
+    fn index(self) -> usize {
+        match self {
+            CompositorSource::Camera => 0,
+            CompositorSource::Output => 1,
+            CompositorSource::Texture(slot) => 2 + slot.min(3),
+        }
+    }
+}
+
+/// A source placed in the frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositorLayer {
+    /// Name to hide the layer by (see `crate::schedule`)
+    pub name: Option<String>,
+    pub source: CompositorSource,
+    /// x, y, width, height in 0.0 - 1.0 frame coordinates
+    pub rect: [f32; 4],
+    /// x, y, width, height of the part of the source shown, in 0.0 - 1.0 source coordinates
+    pub crop: [f32; 4],
+    /// Layers with a higher `z` are drawn on top
+    pub z: i32,
+    /// 0.0 (invisible) - 1.0 (opaque)
+    pub opacity: f32,
+    /// Corner radius in output pixels
+    pub radius: f32,
+}
+
+/// How layers that only exist in one of two layouts enter and leave.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransitionStyle {
+    #[default]
+    Fade,
+    /// Move in from (and out to) the nearest frame edge
+    Slide,
+    /// Grow from (and shrink to) the layer's center
+    Scale,
+}
+
+/// Animation of layout switches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompositorTransition {
+    pub style: TransitionStyle,
+    /// Seconds (0 = cut)
+    pub duration: f32,
+}
+
+impl Default for CompositorTransition {
+    fn default() -> Self {
+        Self { style: TransitionStyle::Fade, duration: 0.5 }
+    }
+}
+
+/// Compositor settings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Compositor {
+    pub stage: CompositorStage,
+    /// The `default` layout
+    pub layers: Vec<CompositorLayer>,
+    /// Further layouts to switch to, by name
+    pub layouts: BTreeMap<String, Vec<CompositorLayer>>,
+    pub transition: CompositorTransition,
+    /// Layout shown (None = `default`)
+    pub layout: Option<String>,
+}
+
+impl Compositor {
+    /// Names of all layouts, `default` first.
+    pub fn layout_names(&self) -> Vec<&str> {
+        std::iter::once(DEFAULT_LAYOUT).chain(self.layouts.keys().map(String::as_str)).collect()
+    }
+
+    /// Returns the layers of the named layout (None = `default`).
+    fn layout_layers(&self, name: Option<&str>) -> &[CompositorLayer] {
+        name.and_then(|name| self.layouts.get(name)).unwrap_or(&self.layers)
+    }
+}
+
+impl CompositorLayer {
+    /// Returns the uniforms of the layer on a frame of `frame_size`.
+    fn uniforms(&self, frame_size: (u32, u32)) -> [f32; 12] {
+        let [x, y, w, h] = self.rect;
+        let [cx, cy, cw, ch] = self.crop;
+        let (width, height) = (w * frame_size.0 as f32, h * frame_size.1 as f32);
+        [
+            x, y, x + w, y + h,
+            cx, cy, cx + cw, cy + ch,
+            self.opacity.clamp(0.0, 1.0), self.radius.max(0.0), width, height,
+        ]
+    }
+
+    /// The layer `t` of the way (0.0 - 1.0) to `to`.
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        let mix4 = |a: [f32; 4], b: [f32; 4]| std::array::from_fn(|i| mix(a[i], b[i]));
+        Self {
+            name: to.name.clone(),
+            source: to.source,
+            rect: mix4(self.rect, to.rect),
+            crop: mix4(self.crop, to.crop),
+            z: to.z,
+            opacity: mix(self.opacity, to.opacity),
+            radius: mix(self.radius, to.radius),
+        }
+    }
+
+    /// The layer `t` of the way (0.0 - 1.0) to having left the frame.
+    fn hidden(&self, style: TransitionStyle, t: f32) -> Self {
+        let [x, y, w, h] = self.rect;
+        let mut layer = self.clone();
+        match style {
+            TransitionStyle::Fade => layer.opacity *= 1.0 - t,
+            TransitionStyle::Slide => {
+                // Distance to move out past the nearest edge
+                let (left, right, top, bottom) = (x + w, 1.0 - x, y + h, 1.0 - y);
+                let nearest = left.min(right).min(top).min(bottom);
+                let (dx, dy) = if nearest == left {
+                    (-left, 0.0)
+                } else if nearest == right {
+                    (right, 0.0)
+                } else if nearest == top {
+                    (0.0, -top)
+                } else {
+                    (0.0, bottom)
+                };
+                layer.rect = [x + dx * t, y + dy * t, w, h];
+            }
+            TransitionStyle::Scale => {
+                let s = 1.0 - t;
+                layer.rect = [x + w * (1.0 - s) / 2.0, y + h * (1.0 - s) / 2.0, w * s, h * s];
+                layer.radius *= s;
+            }
+        }
+        layer
+    }
+}
+
+/// Layers at `t` (0.0 - 1.0) of a transition from `from` to `to`. Layers are
+/// matched by source, in order.
+fn interpolate(from: &[CompositorLayer], to: &[CompositorLayer], style: TransitionStyle, t: f32) -> Vec<CompositorLayer> {
+    let t = t * t * (3.0 - 2.0 * t);
+    let mut leaving: Vec<Option<&CompositorLayer>> = from.iter().map(Some).collect();
+    let mut layers: Vec<CompositorLayer> = to
+        .iter()
+        .map(|layer| {
+            let matched = leaving.iter_mut().find(|old| old.is_some_and(|old| old.source == layer.source)).and_then(Option::take);
+            match matched {
+                Some(old) => old.lerp(layer, t),
+                None => layer.hidden(style, 1.0 - t),
+            }
+        })
+        .collect();
+    layers.extend(leaving.into_iter().flatten().map(|layer| layer.hidden(style, t)));
+    layers
+}
+
+/// Returns the layers back to front. Layers with the same `z` keep their order.
+fn draw_order(layers: &mut [CompositorLayer]) {
+    layers.sort_by_key(|layer| layer.z);
+}
+
+/// A layout switch in progress.
+struct Transition {
+    /// Layers at the time of the switch
+    from: Vec<CompositorLayer>,
+    start: Instant,
+}
+
+/// Whether `layer` is one of the `hidden` ones.
+fn is_hidden(layer: &CompositorLayer, hidden: &BTreeSet<String>) -> bool {
+    layer.name.as_ref().is_some_and(|name| hidden.contains(name))
+}
+
+const COMPOSITOR_SHADER: &str = r#"
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct LayerUniforms {
+    // left, top, right, bottom in 0..1 frame coordinates
+    rect: vec4<f32>,
+    // left, top, right, bottom in 0..1 source coordinates
+    crop: vec4<f32>,
+    // x = opacity, y = corner radius, zw = layer size in pixels
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(1) @binding(0) var<uniform> u_layer: LayerUniforms;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = mix(u_layer.rect.xy, u_layer.rect.zw, in.tex_coords);
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_sampler, mix(u_layer.crop.xy, u_layer.crop.zw, tex_coords));
+
+    // Signed distance to the rounded rectangle, in pixels
+    let size = u_layer.params.zw;
+    let radius = min(u_layer.params.y, min(size.x, size.y) * 0.5);
+    let q = abs((tex_coords - 0.5) * size) - size * 0.5 + radius;
+    let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+    let coverage = clamp(0.5 - distance, 0.0, 1.0);
+
+    return vec4<f32>(color.rgb, color.a * u_layer.params.x * coverage);
+}
+"#;
+
+/// The views of the sources a compositor can show.
+pub(super) struct CompositorSources<'a> {
+    pub camera: &'a wgpu::TextureView,
+    pub output: &'a wgpu::TextureView,
+    pub slots: [&'a wgpu::TextureView; 4],
+}
+
+/// GPU resources of the compositor.
+pub(super) struct CompositorPass {
+    settings: Compositor,
+    transition: Option<Transition>,
+    /// Names of the layers not shown
+    hidden: BTreeSet<String>,
+    pipeline: wgpu::RenderPipeline,
+    source_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    texture: Option<wgpu::Texture>,
+    /// One per source (see `CompositorSource::index`)
+    source_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl CompositorPass {
+    pub fn new(device: &wgpu::Device, settings: Compositor, hidden: BTreeSet<String>) -> Self {
+        let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compositor Source Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compositor Uniform Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<[f32; 12]>() as u64),
+                },
+                count: None,
+            }],
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compositor Uniform Buffer"),
+            size: (MAX_LAYERS * UNIFORM_STRIDE) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compositor Uniform Bind Group"),
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &uniform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<[f32; 12]>() as u64),
+                }),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compositor Pipeline Layout"),
+            bind_group_layouts: &[&source_layout, &uniform_layout],
+            immediate_size: 0,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compositor Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(COMPOSITOR_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Compositor Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            settings,
+            transition: None,
+            hidden,
+            pipeline,
+            source_layout,
+            uniform_buffer,
+            uniform_bind_group,
+            texture: None,
+            source_bind_groups: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &Compositor {
+        &self.settings
+    }
+
+    pub fn stage(&self) -> CompositorStage {
+        self.settings.stage
+    }
+
+    /// Switches to the named layout, animated with the configured transition.
+    pub fn switch_layout(&mut self, name: &str, now: Instant) -> Result<()> {
+        let layout = match name {
+            DEFAULT_LAYOUT => None,
+            name if self.settings.layouts.contains_key(name) => Some(name.to_string()),
+            _ => bail!("Unknown compositor layout '{}' (available: {})", name, self.settings.layout_names().join(", ")),
+        };
+        // A switch during a transition starts from where it is
+        let from = self.layers(now);
+        self.settings.layout = layout;
+        self.transition = (self.settings.transition.duration > 0.0).then_some(Transition { from, start: now });
+        Ok(())
+    }
+
+    /// Hides the named layers (and shows the others), animated like a layout switch.
+    pub fn set_hidden(&mut self, hidden: BTreeSet<String>, now: Instant) {
+        let from = self.layers(now);
+        self.hidden = hidden;
+        self.transition = (self.settings.transition.duration > 0.0).then_some(Transition { from, start: now });
+    }
+
+    /// Returns the layers shown at `now`, back to front.
+    fn layers(&self, now: Instant) -> Vec<CompositorLayer> {
+        let to: Vec<CompositorLayer> = self.settings
+            .layout_layers(self.settings.layout.as_deref())
+            .iter()
+            .filter(|layer| !is_hidden(layer, &self.hidden))
+            .cloned()
+            .collect();
+        let mut layers = match &self.transition {
+            Some(transition) => {
+                let t = now.saturating_duration_since(transition.start).as_secs_f32() / self.settings.transition.duration;
+                interpolate(&transition.from, &to, self.settings.transition.style, t.min(1.0))
+            }
+            None => to,
+        };
+        draw_order(&mut layers);
+        layers
+    }
+
+    /// (Re)creates the composed texture and binds the sources.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, sources: &CompositorSources, width: u32, height: u32) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Compositor Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        self.source_bind_groups = [sources.camera, sources.output]
+            .into_iter()
+            .chain(sources.slots)
+            .enumerate()
+            .map(|(i, view)| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(&format!("Compositor Source Bind Group {}", i)),
+                    layout: &self.source_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                        wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                    ],
+                })
+            })
+            .collect();
+        self.texture = Some(texture);
+    }
+
+    /// The composed frame, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Records the layers at `now`, back to front on black, into `encoder`.
+    pub fn encode(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        vertex_buffer: &wgpu::Buffer,
+        index_buffer: &wgpu::Buffer,
+        now: Instant,
+    ) {
+        let Some(texture) = &self.texture else { return };
+        let mut layers = self.layers(now);
+        layers.truncate(MAX_LAYERS);
+        if self.transition.as_ref().is_some_and(|transition| now.saturating_duration_since(transition.start).as_secs_f32() >= self.settings.transition.duration) {
+            self.transition = None;
+        }
+
+        let frame_size = (texture.width(), texture.height());
+        let mut uniforms = vec![0u8; layers.len() * UNIFORM_STRIDE];
+        for (i, layer) in layers.iter().enumerate() {
+            let offset = i * UNIFORM_STRIDE;
+            uniforms[offset..offset + 48].copy_from_slice(bytemuck::cast_slice(&layer.uniforms(frame_size)));
+        }
+        if !uniforms.is_empty() {
+            queue.write_buffer(&self.uniform_buffer, 0, &uniforms);
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Compositor Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        for (i, layer) in layers.iter().enumerate() {
+            let Some(source_bind_group) = self.source_bind_groups.get(layer.source.index()) else { continue };
+            render_pass.set_bind_group(0, source_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.uniform_bind_group, &[(i * UNIFORM_STRIDE) as u32]);
+            render_pass.draw_indexed(0..6, 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(source: CompositorSource, rect: [f32; 4], z: i32) -> CompositorLayer {
+        CompositorLayer { name: None, source, rect, crop: [0.0, 0.0, 1.0, 1.0], z, opacity: 1.0, radius: 16.0 }
+    }
+
+    #[test]
+    fn test_draw_order_and_uniforms() {
+        let mut layers = vec![
+            layer(CompositorSource::Camera, [0.5, 0.25, 0.5, 0.5], 1),
+            layer(CompositorSource::Texture(0), [0.0, 0.0, 1.0, 1.0], -1),
+            layer(CompositorSource::Output, [0.0, 0.0, 0.5, 0.5], 1),
+        ];
+        let uniforms = layers[0].uniforms((1280, 720));
+        assert_eq!(uniforms[..4], [0.5, 0.25, 1.0, 0.75]);
+        assert_eq!(uniforms[8..], [1.0, 16.0, 640.0, 360.0]);
+
+        draw_order(&mut layers);
+        let sources: Vec<CompositorSource> = layers.iter().map(|layer| layer.source).collect();
+        assert_eq!(sources, [CompositorSource::Texture(0), CompositorSource::Camera, CompositorSource::Output]);
+    }
+
+    #[test]
+    fn test_transition() {
+        let from = [layer(CompositorSource::Camera, [0.0, 0.0, 1.0, 1.0], 0), layer(CompositorSource::Texture(1), [0.7, 0.1, 0.2, 0.2], 1)];
+        let to = [layer(CompositorSource::Camera, [0.0, 0.0, 0.5, 1.0], 0), layer(CompositorSource::Output, [0.5, 0.0, 0.5, 1.0], 0)];
+
+        // The camera moves, the output fades in, the texture fades out
+        let half = interpolate(&from, &to, TransitionStyle::Fade, 0.5);
+        assert_eq!(half.len(), 3);
+        assert_eq!(half[0].rect, [0.0, 0.0, 0.75, 1.0]);
+        assert_eq!((half[1].source, half[1].opacity), (CompositorSource::Output, 0.5));
+        assert_eq!((half[2].source, half[2].opacity), (CompositorSource::Texture(1), 0.5));
+        assert_eq!(interpolate(&from, &to, TransitionStyle::Fade, 1.0)[..2], to);
+
+        // The texture leaves through the right edge, the nearest one
+        let gone = interpolate(&from, &to, TransitionStyle::Slide, 1.0);
+        assert!((gone[2].rect[0] - 1.0).abs() < 1e-6 && gone[2].rect[1] == 0.1);
+        let shrunk = interpolate(&from, &to, TransitionStyle::Scale, 1.0);
+        assert_eq!(shrunk[2].rect[2..], [0.0, 0.0]);
+    }
+}
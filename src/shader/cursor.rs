@@ -0,0 +1,347 @@
+//! Cursor and click highlighting.
+//!
+//! Screen captures often lose the mouse cursor, and even when it is there it is
+//! hard to follow on a small video. This draws a dot with a highlight ring at
+//! the cursor position and an expanding ripple for every click. It runs on the
+//! composed frame, before the zoom, so a zoomed view magnifies the highlight too.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+
+/// Ripples drawn at the same time; older clicks are dropped.
+const MAX_RIPPLES: usize = 8;
+
+/// Cursor highlight settings. Sizes are in output pixels, colors are sRGB RGBA
+/// in 0.0 - 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CursorOverlay {
+    /// Radius of the dot drawn at the cursor (0.0 = none)
+    pub dot_radius: f32,
+    pub dot_color: [f32; 4],
+    /// Radius of the highlight ring around the cursor (0.0 = none)
+    pub ring_radius: f32,
+    /// Line width of the ring (0.0 fills the circle)
+    pub ring_width: f32,
+    pub ring_color: [f32; 4],
+    /// Radius a click ripple grows to
+    pub click_radius: f32,
+    /// Seconds a click ripple takes to grow and fade out
+    pub click_duration: f32,
+    pub click_color: [f32; 4],
+}
+
+impl Default for CursorOverlay {
+    fn default() -> Self {
+        Self {
+            dot_radius: 6.0,
+            dot_color: [1.0, 1.0, 1.0, 1.0],
+            ring_radius: 28.0,
+            ring_width: 0.0,
+            ring_color: [1.0, 0.85, 0.0, 0.35],
+            click_radius: 60.0,
+            click_duration: 0.5,
+            click_color: [1.0, 0.3, 0.2, 0.9],
+        }
+    }
+}
+
+/// Recent clicks and their ripples.
+#[derive(Debug, Clone, Default)]
+struct Clicks {
+    clicks: VecDeque<([f32; 2], Instant)>,
+}
+
+impl Clicks {
+    fn push(&mut self, position: [f32; 2], now: Instant) {
+        if self.clicks.len() == MAX_RIPPLES {
+            self.clicks.pop_front();
+        }
+        self.clicks.push_back((position, now));
+    }
+
+    /// Drops finished ripples and returns the others as (x, y, progress 0.0 - 1.0, 0.0).
+    fn ripples(&mut self, now: Instant, duration: f32) -> Vec<[f32; 4]> {
+        let progress = |at: Instant| now.saturating_duration_since(at).as_secs_f32() / duration.max(f32::EPSILON);
+        self.clicks.retain(|&(_, at)| progress(at) < 1.0);
+        self.clicks.iter().map(|&([x, y], at)| [x, y, progress(at), 0.0]).collect()
+    }
+}
+
+const CURSOR_SHADER: &str = r#"
+struct CursorUniforms {
+    // x, y = frame size in pixels, z = cursor visible, w = number of ripples
+    frame: vec4<f32>,
+    // xy = cursor in 0..1 frame coordinates, z = dot radius, w = ring radius
+    cursor: vec4<f32>,
+    // x = ring width, y = click radius
+    sizes: vec4<f32>,
+    dot_color: vec4<f32>,
+    ring_color: vec4<f32>,
+    click_color: vec4<f32>,
+    // xy = position, z = progress 0..1
+    ripples: array<vec4<f32>, 8>,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_cursor: CursorUniforms;
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+// Anti-aliased coverage of a circle (width 0) or a ring around `radius`
+fn coverage(distance: f32, radius: f32, width: f32) -> f32 {
+    if width <= 0.0 {
+        return clamp(radius - distance + 0.5, 0.0, 1.0);
+    }
+    return clamp(width * 0.5 - abs(distance - radius) + 0.5, 0.0, 1.0);
+}
+
+fn blend(base: vec3<f32>, color: vec4<f32>, amount: f32) -> vec3<f32> {
+    return mix(base, srgb_to_linear(color.rgb), color.a * amount);
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let source = textureSample(t_source, s_sampler, tex_coords);
+    let pixel = tex_coords * u_cursor.frame.xy;
+    var color = source.rgb;
+
+    for (var i = 0u; i < u32(u_cursor.frame.w); i++) {
+        let ripple = u_cursor.ripples[i];
+        let distance = length(pixel - ripple.xy * u_cursor.frame.xy);
+        let radius = u_cursor.sizes.y * ripple.z;
+        color = blend(color, u_cursor.click_color, coverage(distance, radius, 3.0) * (1.0 - ripple.z));
+    }
+
+    if u_cursor.frame.z > 0.0 {
+        let distance = length(pixel - u_cursor.cursor.xy * u_cursor.frame.xy);
+        color = blend(color, u_cursor.ring_color, coverage(distance, u_cursor.cursor.w, u_cursor.sizes.x));
+        color = blend(color, u_cursor.dot_color, coverage(distance, u_cursor.cursor.z, 0.0));
+    }
+
+    return vec4<f32>(color, source.a);
+}
+"#;
+
+/// GPU resources and cursor state of the highlight pass.
+pub(super) struct CursorPass {
+    settings: CursorOverlay,
+    position: Option<[f32; 2]>,
+    clicks: Clicks,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    texture: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl CursorPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: CursorOverlay) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cursor Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cursor Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cursor Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(CURSOR_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cursor Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cursor Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 4 * (6 + MAX_RIPPLES)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            settings,
+            position: None,
+            clicks: Clicks::default(),
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            texture: None,
+            bind_group: None,
+        }
+    }
+
+    pub fn settings(&self) -> CursorOverlay {
+        self.settings
+    }
+
+    /// Cursor position in 0.0 - 1.0 frame coordinates, if it is over the frame.
+    pub fn position(&self) -> Option<[f32; 2]> {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Option<[f32; 2]>) {
+        self.position = position;
+    }
+
+    /// Starts a ripple at the cursor (or the last position it had).
+    pub fn click(&mut self, now: Instant) {
+        if let Some(position) = self.position {
+            self.clicks.push(position, now);
+        }
+    }
+
+    /// Binds `source` (the composed frame) and (re)creates the highlighted
+    /// texture at the render size.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, source: &wgpu::TextureView, width: u32, height: u32) {
+        if self.texture.as_ref().is_none_or(|texture| texture.width() != width || texture.height() != height) {
+            self.texture = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Cursor Texture"),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            }));
+        }
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cursor Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(source) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+    }
+
+    /// The highlighted frame, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Records the highlight pass into `encoder`.
+    pub fn encode(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer, now: Instant) {
+        let (Some(texture), Some(bind_group)) = (&self.texture, &self.bind_group) else { return };
+
+        let settings = &self.settings;
+        let ripples = self.clicks.ripples(now, settings.click_duration);
+        let [x, y] = self.position.unwrap_or_default();
+        let mut uniforms = vec![
+            texture.width() as f32, texture.height() as f32, self.position.is_some() as u32 as f32, ripples.len() as f32,
+            x, y, settings.dot_radius, settings.ring_radius,
+            settings.ring_width, settings.click_radius, 0.0, 0.0,
+        ];
+        uniforms.extend(settings.dot_color);
+        uniforms.extend(settings.ring_color);
+        uniforms.extend(settings.click_color);
+        uniforms.extend(ripples.iter().flatten());
+        uniforms.resize(4 * (6 + MAX_RIPPLES), 0.0);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Cursor Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ripples() {
+        let start = Instant::now();
+        let mut clicks = Clicks::default();
+        clicks.push([0.25, 0.5], start);
+        clicks.push([0.75, 0.5], start + Duration::from_millis(250));
+        assert_eq!(clicks.ripples(start + Duration::from_millis(250), 0.5), vec![[0.25, 0.5, 0.5, 0.0], [0.75, 0.5, 0.0, 0.0]]);
+        // The first ripple is done after 0.5 s
+        assert_eq!(clicks.ripples(start + Duration::from_millis(500), 0.5), vec![[0.75, 0.5, 0.5, 0.0]]);
+
+        for i in 0..MAX_RIPPLES + 2 {
+            clicks.push([i as f32 / 10.0, 0.0], start + Duration::from_millis(500));
+        }
+        let ripples = clicks.ripples(start + Duration::from_millis(500), 0.5);
+        assert_eq!(ripples.len(), MAX_RIPPLES);
+        assert_eq!(ripples[0][0], 0.2);
+    }
+}
@@ -0,0 +1,72 @@
+//! Storage buffers with structured shader data.
+//!
+//! Up to `DATA_SLOTS` float arrays (bindings 13 - 16, `Data0` - `Data3`)
+//! that shaders read as `vec4` items, e.g. particle state or points to draw.
+//! Their contents come from the config, Lua (`proteus.set_buffer`), the
+//! control socket (`set-buffer`) or the pose model.
+
+/// Number of data buffer slots.
+pub const DATA_SLOTS: usize = 4;
+
+/// Binding of the first data buffer; the others follow.
+pub const DATA_BINDING: u32 = 13;
+
+/// Largest capacity of a data buffer in items.
+pub const MAX_DATA_ITEMS: u32 = 65536;
+
+/// Where the contents of a data buffer come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataSource {
+    /// Values from the config, Lua or the control socket
+    #[default]
+    Values,
+    /// The body pose keypoints, `(x, y, confidence, 0)` each
+    Pose,
+}
+
+/// A data buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataBuffer {
+    /// Name used to set the values
+    pub name: String,
+    pub source: DataSource,
+    /// Capacity in items (4 floats each)
+    pub items: u32,
+    /// Current values (`DataSource::Values` only)
+    pub values: Vec<f32>,
+}
+
+/// Size of a data buffer with room for `items` items, in bytes.
+pub fn data_size(items: u32) -> u64 {
+    16 + items.max(1) as u64 * 16
+}
+
+/// Buffer contents for `values`: the item count and three padding words,
+/// then the values padded to whole items. Items beyond `items` are dropped.
+pub fn pack_data(values: &[f32], items: u32) -> Vec<u8> {
+    let count = values.len().div_ceil(4).min(items as usize);
+    let mut floats = values[..values.len().min(count * 4)].to_vec();
+    floats.resize(count * 4, 0.0);
+    let mut data = bytemuck::cast_slice(&[count as u32, 0, 0, 0]).to_vec();
+    data.extend_from_slice(bytemuck::cast_slice(&floats));
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_data() {
+        let data = pack_data(&[1.0, 2.0, 3.0, 4.0, 5.0], 4);
+        assert_eq!(data.len(), 16 + 32);
+        assert_eq!(bytemuck::pod_collect_to_vec::<u8, u32>(&data[..16]), &[2, 0, 0, 0]);
+        assert_eq!(bytemuck::pod_collect_to_vec::<u8, f32>(&data[16..]), &[1.0, 2.0, 3.0, 4.0, 5.0, 0.0, 0.0, 0.0]);
+
+        // Truncated to the capacity
+        let data = pack_data(&[1.0; 12], 2);
+        assert_eq!(bytemuck::pod_collect_to_vec::<u8, u32>(&data[..4]), &[2]);
+        assert_eq!(data.len(), 16 + 32);
+        assert_eq!(pack_data(&[], 8).len(), 16);
+    }
+}
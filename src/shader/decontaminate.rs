@@ -0,0 +1,266 @@
+//! Edge decontamination for segmentation composites.
+//!
+//! Runs before the first user shader when segmentation is active. Erodes the
+//! person mask slightly and, where the mask is fractional, replaces the camera
+//! color with nearby foreground color so the old background doesn't bleed into
+//! the person's edges (halos on bright replacement backgrounds).
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// Edge decontamination settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decontamination {
+    /// Distance in pixels searched for clean foreground color
+    pub radius: f32,
+    /// Distance in pixels the mask is shrunk by
+    pub erode: f32,
+}
+
+impl Default for Decontamination {
+    fn default() -> Self {
+        Self { radius: 6.0, erode: 1.0 }
+    }
+}
+
+const DECONTAMINATE_SHADER: &str = r#"
+struct DecontaminateUniforms {
+    radius: f32,
+    erode: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) mask: vec4<f32>,
+}
+
+@group(0) @binding(0) var t_input: texture_2d<f32>;
+@group(0) @binding(1) var t_mask: texture_2d<f32>;
+@group(0) @binding(2) var s_sampler: sampler;
+@group(0) @binding(3) var<uniform> u_decontaminate: DecontaminateUniforms;
+
+const DIRECTIONS: u32 = 12u;
+const RINGS: u32 = 3u;
+const TAU: f32 = 6.2831853;
+
+fn mask_at(uv: vec2<f32>) -> f32 {
+    return textureSampleLevel(t_mask, s_sampler, uv, 0.0).r;
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> FragmentOutput {
+    let px = 1.0 / vec2<f32>(textureDimensions(t_input));
+    let color = textureSampleLevel(t_input, s_sampler, tex_coords, 0.0);
+
+    // Erode: minimum of the mask over two rings of the erode radius
+    var alpha = mask_at(tex_coords);
+    if (u_decontaminate.erode > 0.0) {
+        for (var i = 0u; i < 8u; i++) {
+            let dir = vec2<f32>(cos(f32(i) * TAU / 8.0), sin(f32(i) * TAU / 8.0));
+            alpha = min(alpha, mask_at(tex_coords + dir * px * u_decontaminate.erode * 0.5));
+            alpha = min(alpha, mask_at(tex_coords + dir * px * u_decontaminate.erode));
+        }
+    }
+
+    var out: FragmentOutput;
+    out.color = color;
+    out.mask = vec4<f32>(alpha, 0.0, 0.0, 1.0);
+
+    // Only fractional edges carry background color
+    if (alpha <= 0.004 || alpha >= 0.996 || u_decontaminate.radius <= 0.0) {
+        return out;
+    }
+
+    // Average the nearby confident foreground
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var ring = 1u; ring <= RINGS; ring++) {
+        let dist = u_decontaminate.radius * f32(ring) / f32(RINGS);
+        for (var i = 0u; i < DIRECTIONS; i++) {
+            let angle = (f32(i) + 0.5 * f32(ring & 1u)) * TAU / f32(DIRECTIONS);
+            let uv = tex_coords + vec2<f32>(cos(angle), sin(angle)) * px * dist;
+            let w = smoothstep(0.9, 1.0, mask_at(uv));
+            sum += textureSampleLevel(t_input, s_sampler, uv, 0.0).rgb * w;
+            weight += w;
+        }
+    }
+    if (weight > 0.0) {
+        out.color = vec4<f32>(mix(sum / weight, color.rgb, alpha), color.a);
+    }
+    return out;
+}
+"#;
+
+/// GPU resources of the decontamination pass.
+pub(super) struct DecontaminatePass {
+    settings: Decontamination,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    color: Option<wgpu::Texture>,
+    mask: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl DecontaminatePass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: Decontamination) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Decontaminate Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Decontaminate Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Decontaminate Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(DECONTAMINATE_SHADER)),
+        });
+
+        let target = |format| Some(wgpu::ColorTargetState {
+            format,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Decontaminate Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[target(wgpu::TextureFormat::Rgba16Float), target(wgpu::TextureFormat::R8Unorm)],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Decontaminate Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[settings.radius.max(0.0), settings.erode.max(0.0), 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        Self {
+            settings,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            color: None,
+            mask: None,
+            bind_group: None,
+        }
+    }
+
+    /// (Re)creates the refined color and mask textures at the size of `input`
+    /// and binds the camera input and the segmentation mask.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, input: &wgpu::Texture, mask: &wgpu::Texture) {
+        let create = |label, format| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: input.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        let mask_view = mask.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Decontaminate Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&mask_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 3, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+        self.color = Some(create("Decontaminated Input Texture", wgpu::TextureFormat::Rgba16Float));
+        self.mask = Some(create("Decontaminated Mask Texture", wgpu::TextureFormat::R8Unorm));
+    }
+
+    pub fn settings(&self) -> Decontamination {
+        self.settings
+    }
+
+    /// The refined camera input and mask, once prepared.
+    pub fn textures(&self) -> Option<(&wgpu::Texture, &wgpu::Texture)> {
+        self.color.as_ref().zip(self.mask.as_ref())
+    }
+
+    /// Records the decontamination pass into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some((color, mask)), Some(bind_group)) = (self.textures(), &self.bind_group) else { return };
+        let color_view = color.create_view(&wgpu::TextureViewDescriptor::default());
+        let mask_view = mask.create_view(&wgpu::TextureViewDescriptor::default());
+        let attachment = |view| Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            depth_slice: None,
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Decontaminate Pass"),
+            color_attachments: &[attachment(&color_view), attachment(&mask_view)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
@@ -0,0 +1,108 @@
+//! Glyph atlas for character-based effects.
+//!
+//! The configured characters are rasterized with a system font into a strip
+//! of equally sized cells (binding 12, `t_glyphs`), ordered from the least to
+//! the most ink, so a shader can pick a character by brightness. Each texel
+//! holds the glyph coverage (0.0 - 1.0).
+
+use crate::lua_canvas::FontDatabase;
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+
+/// Size of one atlas cell in pixels (width, height). Shaders get the number of
+/// characters as `atlas width / (atlas height * 0.625)`.
+pub const GLYPH_CELL: (u32, u32) = (20, 32);
+
+/// Glyph atlas settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyphs {
+    /// Font family (the first monospaced system font if not found)
+    pub font: Option<String>,
+    /// Characters to use; their order doesn't matter
+    pub characters: String,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self { font: None, characters: " .:-=+*#%@".to_string() }
+    }
+}
+
+/// Sorts characters by their ink coverage, least first. Characters with the
+/// same coverage keep their order; duplicates are removed.
+fn order_by_coverage(mut glyphs: Vec<(char, Vec<u8>)>) -> Vec<(char, Vec<u8>)> {
+    let mut seen = std::collections::HashSet::new();
+    glyphs.retain(|(c, _)| seen.insert(*c));
+    glyphs.sort_by_key(|(_, coverage)| coverage.iter().map(|&value| value as u64).sum::<u64>());
+    glyphs
+}
+
+/// Renders the atlas: one `GLYPH_CELL` per character in a row, one byte per
+/// pixel. Returns the data, width and height, or `None` without a usable font.
+pub fn render_glyph_atlas(fonts: &FontDatabase, settings: &Glyphs) -> Option<(Vec<u8>, u32, u32)> {
+    let id = settings
+        .font
+        .as_deref()
+        .and_then(|family| fonts.find_font(family))
+        .or_else(|| fonts.monospace_font())
+        .or_else(|| fonts.default_font())?;
+    let data = fonts.get_font_data(id)?;
+    let font = FontRef::try_from_slice(&data).ok()?;
+    let (cell_width, cell_height) = GLYPH_CELL;
+
+    // Fit the tallest glyphs and the widest advance into a cell
+    let unscaled = font.as_scaled(PxScale::from(cell_height as f32));
+    let widest = settings.characters.chars().map(|c| unscaled.h_advance(font.glyph_id(c))).fold(0.0, f32::max);
+    let size = (cell_height as f32 * 0.9).min(cell_height as f32 * cell_width as f32 / widest.max(1.0));
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let baseline = (cell_height as f32 - scaled.height()) / 2.0 + scaled.ascent();
+
+    let glyphs: Vec<(char, Vec<u8>)> = settings
+        .characters
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| {
+            let mut cell = vec![0u8; (cell_width * cell_height) as usize];
+            let glyph_id = font.glyph_id(c);
+            let x = (cell_width as f32 - scaled.h_advance(glyph_id)) / 2.0;
+            if let Some(outlined) = font.outline_glyph(glyph_id.with_scale_and_position(scale, point(x, baseline))) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    let (px, py) = (bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32);
+                    if (0..cell_width as i32).contains(&px) && (0..cell_height as i32).contains(&py) {
+                        let texel = &mut cell[(py as u32 * cell_width + px as u32) as usize];
+                        *texel = texel.saturating_add((coverage.clamp(0.0, 1.0) * 255.0).round() as u8);
+                    }
+                });
+            }
+            (c, cell)
+        })
+        .collect();
+    let glyphs = order_by_coverage(glyphs);
+    if glyphs.is_empty() {
+        return None;
+    }
+
+    let width = cell_width * glyphs.len() as u32;
+    let mut atlas = vec![0u8; (width * cell_height) as usize];
+    for (i, (_, cell)) in glyphs.iter().enumerate() {
+        for y in 0..cell_height {
+            let row = &cell[(y * cell_width) as usize..((y + 1) * cell_width) as usize];
+            let start = (y * width + i as u32 * cell_width) as usize;
+            atlas[start..start + cell_width as usize].copy_from_slice(row);
+        }
+    }
+    Some((atlas, width, cell_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_by_coverage() {
+        let glyphs = vec![('#', vec![255, 255]), (' ', vec![0, 0]), ('.', vec![0, 40]), ('-', vec![40, 0]), ('#', vec![255, 255])];
+        let order: String = order_by_coverage(glyphs).into_iter().map(|(c, _)| c).collect();
+        assert_eq!(order, " .-#");
+    }
+}
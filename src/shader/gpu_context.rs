@@ -4,18 +4,24 @@ use std::sync::Arc;
 use anyhow::{anyhow, Result};
 use winit::window::Window;
 
+use super::ShaderCache;
+
 /// Shared GPU resources used by multiple components.
 pub struct GpuContext {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub instance: wgpu::Instance,
     pub adapter: wgpu::Adapter,
+    shader_cache: Option<ShaderCache>,
+    pipeline_cache: Option<wgpu::PipelineCache>,
 }
 
 impl GpuContext {
     /// Initialize GPU context compatible with the given window surface.
     /// If window is None, initializes for headless/offscreen use.
-    pub fn new(window: Option<&Arc<Window>>) -> Result<Self> {
+    /// `power_preference` selects between discrete and integrated GPUs on multi-GPU systems.
+    /// `shader_cache` keeps converted shaders and compiled pipelines between runs.
+    pub fn new(window: Option<&Arc<Window>>, power_preference: wgpu::PowerPreference, shader_cache: Option<ShaderCache>) -> Result<Self> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
@@ -29,7 +35,7 @@ impl GpuContext {
         };
 
         let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
+            power_preference,
             compatible_surface: surface.as_ref(),
             force_fallback_adapter: false,
         }))
@@ -38,7 +44,11 @@ impl GpuContext {
         let (device, queue) = pollster::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("Proteus Device"),
-                required_features: wgpu::Features::empty(),
+                required_features: if shader_cache.is_some() {
+                    adapter.features() & wgpu::Features::PIPELINE_CACHE
+                } else {
+                    wgpu::Features::empty()
+                },
                 required_limits: if surface.is_some() {
                     wgpu::Limits::default()
                 } else {
@@ -50,11 +60,30 @@ impl GpuContext {
 
         ))?;
 
+        let pipeline_cache = shader_cache.as_ref().and_then(|cache| cache.load_pipelines(&device, &adapter.get_info()));
         Ok(Self {
             device,
             queue,
             instance,
             adapter,
+            shader_cache,
+            pipeline_cache,
         })
     }
+
+    pub fn shader_cache(&self) -> Option<&ShaderCache> {
+        self.shader_cache.as_ref()
+    }
+
+    /// Pipeline cache for pipeline descriptors (`None` if the backend has none or caching is off).
+    pub fn pipeline_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.pipeline_cache.as_ref()
+    }
+
+    /// Saves the pipelines compiled so far, so the next start can skip compiling them.
+    pub fn save_pipeline_cache(&self) {
+        if let (Some(shader_cache), Some(pipeline_cache)) = (&self.shader_cache, &self.pipeline_cache) {
+            shader_cache.store_pipelines(pipeline_cache, &self.adapter.get_info());
+        }
+    }
 }
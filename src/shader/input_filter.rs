@@ -0,0 +1,215 @@
+//! Filtering of the camera input.
+//!
+//! The first pass reads the camera through a sampler that can be switched to
+//! nearest-neighbor, so pixel-art shaders get crisp camera pixels. An optional
+//! sharpening pass (an unsharp mask) scales the camera to the render size
+//! first and sharpens the result, restoring some of the detail scaling softens.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// How the first pass samples the camera input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputSampling {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+/// Camera input filtering settings.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct InputFilter {
+    pub sampling: InputSampling,
+    /// Unsharp mask amount after scaling (0 = off, 0.3 is mild)
+    pub sharpen: f32,
+}
+
+/// Creates the sampler the first pass reads the camera input with.
+pub(super) fn create_input_sampler(device: &wgpu::Device, sampling: InputSampling) -> wgpu::Sampler {
+    // Linear sampling is trilinear, using the camera input's mips
+    let (filter, mipmap_filter) = match sampling {
+        InputSampling::Linear => (wgpu::FilterMode::Linear, wgpu::MipmapFilterMode::Linear),
+        InputSampling::Nearest => (wgpu::FilterMode::Nearest, wgpu::MipmapFilterMode::Nearest),
+    };
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Input Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter,
+        ..Default::default()
+    })
+}
+
+const SHARPEN_SHADER: &str = r#"
+struct SharpenUniforms {
+    amount: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+}
+
+@group(0) @binding(0) var t_input: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_sharpen: SharpenUniforms;
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    // One output pixel in texture coordinates
+    let px = vec2<f32>(abs(dpdx(tex_coords.x)), abs(dpdy(tex_coords.y)));
+    let center = textureSample(t_input, s_sampler, tex_coords);
+    let blur = (textureSample(t_input, s_sampler, tex_coords + vec2<f32>(px.x, 0.0))
+        + textureSample(t_input, s_sampler, tex_coords - vec2<f32>(px.x, 0.0))
+        + textureSample(t_input, s_sampler, tex_coords + vec2<f32>(0.0, px.y))
+        + textureSample(t_input, s_sampler, tex_coords - vec2<f32>(0.0, px.y))) * 0.25;
+    let sharpened = center.rgb + (center.rgb - blur.rgb) * u_sharpen.amount;
+    return vec4<f32>(max(sharpened, vec3<f32>(0.0)), center.a);
+}
+"#;
+
+/// GPU resources of the sharpening pass.
+pub(super) struct SharpenPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    output: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl SharpenPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, amount: f32) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sharpen Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sharpen Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sharpen Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHARPEN_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sharpen Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sharpen Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[amount.max(0.0), 0.0, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        Self { pipeline, bind_group_layout, uniform_buffer, output: None, bind_group: None }
+    }
+
+    /// (Re)creates the output texture at the render size and binds the camera input.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, input: &wgpu::Texture, width: u32, height: u32) {
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sharpen Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+        self.output = Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sharpened Input Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+    }
+
+    /// The sharpened camera input, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.output.as_ref()
+    }
+
+    /// Records the sharpening pass into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some(output), Some(bind_group)) = (&self.output, &self.bind_group) else { return };
+        let view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sharpen Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
@@ -0,0 +1,260 @@
+//! Output layout compositing (side-by-side, mirrored split, 2x2 grid).
+//!
+//! Runs as a final pass after all user shaders and composes one or more pipeline
+//! stages into the output frame.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// How the output frame is composed from the pipeline stages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputLayout {
+    /// The final pass output as is
+    #[default]
+    Single,
+    /// The final output twice, squeezed side by side (e.g. for stereoscopic displays)
+    SideBySide,
+    /// The final output on the left, a horizontally mirrored copy on the right
+    Mirrored,
+    /// A 2x2 grid of pipeline stages (0 = camera input, n = output of pass n,
+    /// followed by the accumulated and the composed frame if enabled), in reading order
+    Grid([usize; 4]),
+}
+
+impl OutputLayout {
+    fn mode(&self) -> u32 {
+        match self {
+            OutputLayout::Single => 0,
+            OutputLayout::SideBySide => 1,
+            OutputLayout::Mirrored => 2,
+            OutputLayout::Grid(_) => 3,
+        }
+    }
+
+    /// Stage index shown in each of the four cells, clamped to the available stages.
+    fn cells(&self, stage_count: usize) -> [usize; 4] {
+        let last = stage_count.saturating_sub(1);
+        match self {
+            OutputLayout::Grid(cells) => cells.map(|stage| stage.min(last)),
+            _ => [last; 4],
+        }
+    }
+}
+
+const LAYOUT_SHADER: &str = r#"
+struct LayoutUniforms {
+    mode: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+@group(0) @binding(0) var t_cell0: texture_2d<f32>;
+@group(0) @binding(1) var t_cell1: texture_2d<f32>;
+@group(0) @binding(2) var t_cell2: texture_2d<f32>;
+@group(0) @binding(3) var t_cell3: texture_2d<f32>;
+@group(0) @binding(4) var s_sampler: sampler;
+@group(0) @binding(5) var<uniform> u_layout: LayoutUniforms;
+
+fn sample_cell(cell: u32, uv: vec2<f32>) -> vec4<f32> {
+    switch cell {
+        case 0u: { return textureSampleLevel(t_cell0, s_sampler, uv, 0.0); }
+        case 1u: { return textureSampleLevel(t_cell1, s_sampler, uv, 0.0); }
+        case 2u: { return textureSampleLevel(t_cell2, s_sampler, uv, 0.0); }
+        default: { return textureSampleLevel(t_cell3, s_sampler, uv, 0.0); }
+    }
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let uv = tex_coords;
+    switch u_layout.mode {
+        case 1u: {
+            return sample_cell(0u, vec2<f32>(fract(uv.x * 2.0), uv.y));
+        }
+        case 2u: {
+            if (uv.x < 0.5) {
+                return sample_cell(0u, vec2<f32>(uv.x * 2.0, uv.y));
+            }
+            return sample_cell(0u, vec2<f32>(2.0 - uv.x * 2.0, uv.y));
+        }
+        default: {
+            let cell = u32(uv.x >= 0.5) + 2u * u32(uv.y >= 0.5);
+            return sample_cell(cell, fract(uv * 2.0));
+        }
+    }
+}
+"#;
+
+/// GPU resources of the layout pass.
+pub(super) struct LayoutPass {
+    layout: OutputLayout,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    texture: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl LayoutPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, layout: OutputLayout) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Layout Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                texture_entry(2),
+                texture_entry(3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Layout Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Layout Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(LAYOUT_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Layout Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Layout Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[layout.mode(), 0, 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        Self {
+            layout,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            texture: None,
+            bind_group: None,
+        }
+    }
+
+    /// (Re)creates the composed output texture and binds the pipeline stages
+    /// (`stages[0]` is the camera input, `stages[n]` the output of pass n).
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, stages: &[wgpu::TextureView], width: u32, height: u32) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Layout Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let cells = self.layout.cells(stages.len());
+        let entries: Vec<wgpu::BindGroupEntry> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, &stage)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(&stages[stage]),
+            })
+            .chain([
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 5, resource: self.uniform_buffer.as_entire_binding() },
+            ])
+            .collect();
+
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Layout Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        }));
+        self.texture = Some(texture);
+    }
+
+    pub fn layout(&self) -> OutputLayout {
+        self.layout
+    }
+
+    /// The composed output, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Records the layout pass into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some(texture), Some(bind_group)) = (&self.texture, &self.bind_group) else { return };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Layout Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
@@ -0,0 +1,176 @@
+//! Mipmap generation for the camera input texture.
+//!
+//! Each level is a 2x2 box filter of the previous one, computed in a compute
+//! pass. With the mip chain and the trilinear sampler, shaders that sample the
+//! input at a lower resolution (blurs, pixelation, passes with a `scale`) get a
+//! properly filtered image instead of shimmering, aliased texels.
+//!
+//! sRGB textures can't be storage textures, so each level is written to a
+//! linear scratch texture first and then copied into the input texture.
+
+use std::borrow::Cow;
+
+const MIPMAP_SHADER: &str = r#"
+// sRGB view of the previous level, so values are read as linear
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var t_target: texture_storage_2d<rgba8unorm, write>;
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+@compute @workgroup_size(8, 8)
+fn downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(t_target);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    // Odd sizes repeat the last row or column
+    let last = vec2<i32>(textureDimensions(t_source)) - 1;
+    let p = vec2<i32>(id.xy) * 2;
+    let color = (
+        textureLoad(t_source, min(p, last), 0) +
+        textureLoad(t_source, min(p + vec2<i32>(1, 0), last), 0) +
+        textureLoad(t_source, min(p + vec2<i32>(0, 1), last), 0) +
+        textureLoad(t_source, min(p + vec2<i32>(1, 1), last), 0)
+    ) * 0.25;
+    // The scratch texture is not sRGB, so store encoded values
+    textureStore(t_target, vec2<i32>(id.xy), vec4<f32>(linear_to_srgb(color.rgb), color.a));
+}
+"#;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// GPU resources of the mipmap generation.
+pub(super) struct MipmapPass {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Levels 1.. of the input texture, as scratch level 0..
+    scratch: Option<wgpu::Texture>,
+    /// One per generated level
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl MipmapPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(MIPMAP_SHADER)),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some("downsample"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self { pipeline, bind_group_layout, scratch: None, bind_groups: Vec::new() }
+    }
+
+    /// (Re)creates the scratch texture for `input`, an sRGB texture with a
+    /// full mip chain.
+    pub fn prepare(&mut self, device: &wgpu::Device, input: &wgpu::Texture) {
+        let levels = input.mip_level_count();
+        self.bind_groups.clear();
+        self.scratch = None;
+        if levels < 2 {
+            return;
+        }
+
+        let scratch = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Mipmap Scratch Texture"),
+            size: wgpu::Extent3d { width: (input.width() / 2).max(1), height: (input.height() / 2).max(1), depth_or_array_layers: 1 },
+            mip_level_count: levels - 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        self.bind_groups = (1..levels)
+            .map(|level| {
+                let source = input.create_view(&wgpu::TextureViewDescriptor {
+                    base_mip_level: level - 1,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+                let target = scratch.create_view(&wgpu::TextureViewDescriptor {
+                    base_mip_level: level - 1,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(&format!("Mipmap Bind Group {}", level)),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&source) },
+                        wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&target) },
+                    ],
+                })
+            })
+            .collect();
+        self.scratch = Some(scratch);
+    }
+
+    /// Records the generation of all levels of `input` from level 0. Each level
+    /// is copied back before the next one reads it.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, input: &wgpu::Texture) {
+        let Some(scratch) = &self.scratch else { return };
+        for (i, bind_group) in self.bind_groups.iter().enumerate() {
+            let level = i as u32 + 1;
+            let size = input.size().mip_level_size(level, wgpu::TextureDimension::D2);
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Mipmap Pass"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_pipeline(&self.pipeline);
+                compute_pass.set_bind_group(0, bind_group, &[]);
+                compute_pass.dispatch_workgroups(size.width.div_ceil(WORKGROUP_SIZE), size.height.div_ceil(WORKGROUP_SIZE), 1);
+            }
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo { texture: scratch, mip_level: level - 1, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+                wgpu::TexelCopyTextureInfo { texture: input, mip_level: level, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+                size,
+            );
+        }
+    }
+}
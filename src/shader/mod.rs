@@ -1,9 +1,58 @@
 //! GPU shader pipeline.
 
+mod accumulate;
+mod bloom;
+mod builtin;
+mod cache;
+mod calibration;
+mod captions;
+mod compositor;
+mod cursor;
+mod data;
+mod decontaminate;
+mod glyphs;
+mod input_filter;
+mod layout;
+mod mipmaps;
+mod pack;
+mod particles;
+mod readback;
+mod redaction;
+mod regions;
+mod source_transform;
+mod text_blur;
+mod warning;
+mod watermark;
+mod yuv;
+mod zoom;
 mod wgpu_pipeline;
+pub mod color_audit;
 pub mod gpu_context;
+pub mod modulation;
+pub mod params;
 
-pub use wgpu_pipeline::{TextureSlot, WgpuPipeline};
+pub use accumulate::{Accumulation, AccumulationMode};
+pub use bloom::Bloom;
+pub use builtin::BuiltinShader;
+pub use cache::ShaderCache;
+pub use calibration::{Calibration, Transfer};
+pub use captions::{CaptionPosition, Captions};
+pub(crate) use calibration::{CalibrationBinding, CALIBRATION_SHADER};
+pub use compositor::{Compositor, CompositorLayer, CompositorSource, CompositorStage, CompositorTransition, TransitionStyle, DEFAULT_LAYOUT};
+pub use cursor::CursorOverlay;
+pub use data::{DataBuffer, DataSource, DATA_SLOTS, MAX_DATA_ITEMS};
+pub use decontaminate::Decontamination;
+pub use glyphs::Glyphs;
+pub use input_filter::{InputFilter, InputSampling};
+pub use layout::OutputLayout;
+pub use particles::{ParticleEmitter, Particles, MAX_PARTICLES, MAX_RAMP_COLORS};
+pub use redaction::{RedactionEffect, RedactionRule, RedactionSource};
+pub use regions::{Region, RegionShape};
+pub use source_transform::SourceTransform;
+pub use text_blur::TextBlur;
+pub use watermark::{Watermark, WatermarkPosition};
+pub use zoom::Zoom;
+pub use wgpu_pipeline::{Sink, TextureSlot, WgpuPipeline};
 pub use gpu_context::GpuContext;
 
 use crate::frame::VideoFrame;
@@ -13,14 +62,82 @@ use anyhow::Result;
 pub trait ShaderPipeline {
     /// Process a video frame through the shader.
     /// `time` is the elapsed time in seconds since the application started.
-    fn process_frame(&mut self, input: &VideoFrame, time: f32) -> Result<VideoFrame>;
+    ///
+    /// Several frames may be in flight on the GPU, so this returns an earlier
+    /// frame once it is done, or `None` while the first frames are processed.
+    fn process_frame(&mut self, input: &VideoFrame, time: f32) -> Result<Option<VideoFrame>>;
 }
 
 /// Shader source with language specification.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ShaderSource {
     /// GLSL fragment shader source code
     Glsl { code: String, path: Option<std::path::PathBuf> },
     /// WGSL shader source code  
     Wgsl { code: String, path: Option<std::path::PathBuf> },
 }
+
+/// Per-pass rendering options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassOptions {
+    /// Fraction of the render resolution this pass runs at (e.g. 0.5 for half resolution).
+    /// Inputs and outputs are resampled automatically between passes of different sizes.
+    pub scale: f32,
+    /// Modulators that only affect this pass's parameters (applied after the global ones)
+    pub modulators: Vec<modulation::Modulator>,
+    /// Vertex shader replacing the default one, for geometric warps
+    pub vertex: Option<ShaderSource>,
+    /// Columns and rows of the grid mesh the pass is drawn with (`None`: a single quad)
+    pub mesh: Option<(u32, u32)>,
+}
+
+impl Default for PassOptions {
+    fn default() -> Self {
+        Self { scale: 1.0, modulators: Vec::new(), vertex: None, mesh: None }
+    }
+}
+
+/// How the `seed` uniform is chosen each frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SeedMode {
+    /// A new random value every frame
+    #[default]
+    Random,
+    /// Derived from the frame number, so every run sees the same sequence
+    Frame,
+    /// The same value every frame
+    Fixed(f32),
+}
+
+impl SeedMode {
+    /// The seed for frame number `frame`, in [0, 1).
+    pub fn seed(self, frame: u64) -> f32 {
+        match self {
+            SeedMode::Random => rand::random::<f32>(),
+            SeedMode::Frame => {
+                // SplitMix64 finalizer, so consecutive frames get unrelated values
+                let mut x = frame.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                x ^= x >> 31;
+                // 24 bits fit the f32 mantissa exactly
+                (x >> 40) as f32 / (1u64 << 24) as f32
+            }
+            SeedMode::Fixed(value) => value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_seed() {
+        let seeds: Vec<f32> = (0..100).map(|frame| SeedMode::Frame.seed(frame)).collect();
+        assert!(seeds.iter().all(|seed| (0.0..1.0).contains(seed)));
+        assert_eq!(seeds, (0..100).map(|frame| SeedMode::Frame.seed(frame)).collect::<Vec<_>>());
+        assert_ne!(seeds[0], seeds[1]);
+        assert_eq!(SeedMode::Fixed(0.25).seed(7), 0.25);
+    }
+}
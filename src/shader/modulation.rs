@@ -0,0 +1,116 @@
+//! Parameter modulation (LFOs).
+//!
+//! A modulator drives a named shader parameter with a periodic waveform that is
+//! evaluated every frame, so shaders can animate without time-based code of their own.
+
+use std::collections::HashMap;
+
+/// Shape of a modulator's waveform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    /// Rising ramp
+    Saw,
+    Square,
+    /// Smoothly interpolated random values, one per cycle
+    Noise,
+}
+
+/// How fast a modulator cycles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    /// Cycles per second
+    Hz(f32),
+    /// Length of one cycle in beats, synced to the tempo
+    Beats(f32),
+}
+
+/// Drives a named parameter between `min` and `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modulator {
+    pub param: String,
+    pub waveform: Waveform,
+    pub rate: Rate,
+    pub min: f32,
+    pub max: f32,
+    /// Phase offset in cycles (0.0 - 1.0)
+    pub phase: f32,
+}
+
+impl Modulator {
+    /// Evaluates the modulator at `time` seconds, with the clock's `beat` position used
+    /// for beat-synced rates.
+    pub fn value(&self, time: f32, beat: f64) -> f32 {
+        let cycles = match self.rate {
+            Rate::Hz(hz) => time * hz,
+            Rate::Beats(beats) if beats > 0.0 => (beat / beats as f64) as f32,
+            Rate::Beats(_) => 0.0,
+        } + self.phase;
+
+        let t = cycles.rem_euclid(1.0);
+        let unit = match self.waveform {
+            Waveform::Sine => 0.5 - 0.5 * (t * std::f32::consts::TAU).cos(),
+            Waveform::Triangle => 1.0 - (2.0 * t - 1.0).abs(),
+            Waveform::Saw => t,
+            Waveform::Square => if t < 0.5 { 1.0 } else { 0.0 },
+            Waveform::Noise => {
+                let seed = self.param.bytes().fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+                let cycle = cycles.floor() as i32 as u32;
+                let (a, b) = (hash_unit(seed, cycle), hash_unit(seed, cycle.wrapping_add(1)));
+                let s = t * t * (3.0 - 2.0 * t);
+                a + (b - a) * s
+            }
+        };
+
+        self.min + (self.max - self.min) * unit
+    }
+}
+
+/// Overrides `values` with the current output of each modulator.
+pub fn apply_modulators(values: &mut HashMap<String, f32>, modulators: &[Modulator], time: f32, beat: f64) {
+    for modulator in modulators {
+        values.insert(modulator.param.clone(), modulator.value(time, beat));
+    }
+}
+
+/// Pseudo-random value in 0.0 - 1.0 for an integer position.
+fn hash_unit(seed: u32, n: u32) -> f32 {
+    let mut x = n.wrapping_mul(0x9E3779B1) ^ seed;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85EBCA6B);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xC2B2AE35);
+    x ^= x >> 16;
+    (x >> 8) as f32 / (1u32 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveforms() {
+        let mut lfo = Modulator {
+            param: "intensity".to_string(),
+            waveform: Waveform::Sine,
+            rate: Rate::Hz(1.0),
+            min: 0.2,
+            max: 1.0,
+            phase: 0.0,
+        };
+        assert!((lfo.value(0.0, 0.0) - 0.2).abs() < 1e-5);
+        assert!((lfo.value(0.5, 0.0) - 1.0).abs() < 1e-5);
+
+        // One cycle per 2 beats, a quarter of the way in at beat 4.5
+        lfo.waveform = Waveform::Saw;
+        lfo.rate = Rate::Beats(2.0);
+        assert!((lfo.value(0.0, 4.5) - 0.4).abs() < 1e-5);
+
+        lfo.waveform = Waveform::Noise;
+        for i in 0..100 {
+            let value = lfo.value(i as f32 * 0.37, 0.0);
+            assert!((0.2..=1.0).contains(&value));
+        }
+    }
+}
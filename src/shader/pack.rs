@@ -0,0 +1,253 @@
+//! GPU packing of the output frame into YUV layouts (YUYV, UYVY, NV12).
+//!
+//! A compute pass converts the sRGB output and writes the bytes exactly as the
+//! virtual camera device expects them, so the read back frame goes to the
+//! device without a CPU conversion and with half the readback size.
+
+use crate::frame::{PixelFormat, YuvColorSpace, YuvMatrix, YuvRange};
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+const PACK_SHADER: &str = r#"
+struct PackUniforms {
+    // Kr, Kg, Kb of the YUV matrix
+    luma: vec4<f32>,
+    // 0 = YUYV, 1 = UYVY (NV12 has its own entry point)
+    mode: u32,
+    full_range: u32,
+    width: u32,
+    height: u32,
+}
+
+// Rgba8Unorm view of the sRGB output, so values stay gamma encoded
+@group(0) @binding(0) var t_output: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> packed: array<u32>;
+@group(0) @binding(2) var<uniform> u_pack: PackUniforms;
+
+fn yuv(x: u32, y: u32) -> vec3<f32> {
+    let rgb = textureLoad(t_output, vec2<u32>(x, y), 0).rgb;
+    let k = u_pack.luma;
+    var luma = dot(rgb, k.xyz);
+    var uv = vec2<f32>((rgb.b - luma) / (2.0 - 2.0 * k.z), (rgb.r - luma) / (2.0 - 2.0 * k.x));
+    if (u_pack.full_range == 0u) {
+        luma = 16.0 / 255.0 + luma * (219.0 / 255.0);
+        uv = uv * (224.0 / 255.0);
+    }
+    return vec3<f32>(luma, uv + vec2<f32>(128.0 / 255.0));
+}
+
+// One invocation per 2-pixel macropixel
+@compute @workgroup_size(8, 8)
+fn pack_422(@builtin(global_invocation_id) id: vec3<u32>) {
+    let half_width = u_pack.width / 2u;
+    if (id.x >= half_width || id.y >= u_pack.height) {
+        return;
+    }
+    let p0 = yuv(id.x * 2u, id.y);
+    let p1 = yuv(id.x * 2u + 1u, id.y);
+    let uv = (p0.yz + p1.yz) * 0.5;
+    var bytes = vec4<f32>(p0.x, uv.x, p1.x, uv.y);
+    if (u_pack.mode == 1u) {
+        bytes = vec4<f32>(uv.x, p0.x, uv.y, p1.x);
+    }
+    packed[id.y * half_width + id.x] = pack4x8unorm(bytes);
+}
+
+// One invocation per 4x2 pixel block: a word of Y in both rows, and two UV pairs
+@compute @workgroup_size(8, 8)
+fn pack_nv12(@builtin(global_invocation_id) id: vec3<u32>) {
+    let words = u_pack.width / 4u;
+    if (id.x >= words || id.y >= u_pack.height / 2u) {
+        return;
+    }
+    var uv = vec4<f32>(0.0);
+    for (var row = 0u; row < 2u; row++) {
+        let y = id.y * 2u + row;
+        let a = yuv(id.x * 4u, y);
+        let b = yuv(id.x * 4u + 1u, y);
+        let c = yuv(id.x * 4u + 2u, y);
+        let d = yuv(id.x * 4u + 3u, y);
+        packed[y * words + id.x] = pack4x8unorm(vec4<f32>(a.x, b.x, c.x, d.x));
+        uv += vec4<f32>(a.yz + b.yz, c.yz + d.yz);
+    }
+    // The UV plane follows the Y plane
+    let uv_start = words * u_pack.height;
+    packed[uv_start + id.y * words + id.x] = pack4x8unorm(uv * 0.25);
+}
+"#;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// GPU resources of the output packing pass.
+pub(super) struct PackPass {
+    format: PixelFormat,
+    color: YuvColorSpace,
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    packed_buffer: Option<wgpu::Buffer>,
+    bind_group: Option<wgpu::BindGroup>,
+    size: (u32, u32),
+}
+
+impl PackPass {
+    /// Whether frames of `width` x `height` can be packed into `format` on the GPU.
+    pub fn supports(format: PixelFormat, width: u32, height: u32) -> bool {
+        match format {
+            PixelFormat::Yuyv | PixelFormat::Uyvy => width.is_multiple_of(2),
+            PixelFormat::Nv12 => width.is_multiple_of(4) && height.is_multiple_of(2),
+            PixelFormat::Rgb | PixelFormat::Rgba => false,
+        }
+    }
+
+    /// `format` must be a YUV format. Frames are packed in `color`.
+    pub fn new(device: &wgpu::Device, format: PixelFormat, color: YuvColorSpace) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Pack Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pack Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Pack Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(PACK_SHADER)),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Pack Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some(if format == PixelFormat::Nv12 { "pack_nv12" } else { "pack_422" }),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pack Uniform Buffer"),
+            contents: &[0u8; 32],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            format,
+            color,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            packed_buffer: None,
+            bind_group: None,
+            size: (0, 0),
+        }
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn color(&self) -> YuvColorSpace {
+        self.color
+    }
+
+    /// Size in bytes of a packed frame.
+    pub fn frame_size(&self) -> u64 {
+        let pixels = self.size.0 as u64 * self.size.1 as u64;
+        match self.format {
+            PixelFormat::Nv12 => pixels + pixels / 2,
+            _ => pixels * 2,
+        }
+    }
+
+    /// (Re)creates the packed buffer and binds `source`, the sRGB output texture,
+    /// which must allow `Rgba8Unorm` views.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, source: &wgpu::Texture) {
+        self.size = (source.width(), source.height());
+        let packed_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Packed Output Buffer"),
+            size: self.frame_size(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let view = source.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(wgpu::TextureFormat::Rgba8Unorm),
+            ..Default::default()
+        });
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Pack Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 1, resource: packed_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+        self.packed_buffer = Some(packed_buffer);
+
+        let (kr, kb) = match self.color.matrix {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        };
+        let mode: u32 = if self.format == PixelFormat::Uyvy { 1 } else { 0 };
+        let mut uniforms = [0u8; 32];
+        uniforms[..16].copy_from_slice(bytemuck::cast_slice(&[kr, 1.0 - kr - kb, kb, 0.0f32]));
+        uniforms[16..].copy_from_slice(bytemuck::cast_slice(&[mode, (self.color.range == YuvRange::Full) as u32, self.size.0, self.size.1]));
+        queue.write_buffer(&self.uniform_buffer, 0, &uniforms);
+    }
+
+    /// Records the packing pass and the copy of the packed frame into `readback`
+    /// (at least `frame_size` bytes).
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, readback: &wgpu::Buffer) {
+        let (Some(bind_group), Some(packed_buffer)) = (&self.bind_group, &self.packed_buffer) else { return };
+        // Invocations per row and column (see the entry points)
+        let (columns, rows) = match self.format {
+            PixelFormat::Nv12 => (self.size.0 / 4, self.size.1 / 2),
+            _ => (self.size.0 / 2, self.size.1),
+        };
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Pack Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(columns.div_ceil(WORKGROUP_SIZE), rows.div_ceil(WORKGROUP_SIZE), 1);
+        }
+        encoder.copy_buffer_to_buffer(packed_buffer, 0, readback, 0, self.frame_size());
+    }
+}
@@ -0,0 +1,100 @@
+//! Named shader parameters.
+//!
+//! Shaders can declare a uniform block at binding 8 whose float members are
+//! addressed by name at runtime (e.g. from `proteus ctl set-uniform`):
+//!
+//! ```glsl
+//! layout(set=0, binding=8) uniform Params {
+//!     float intensity;
+//!     float speed;
+//! };
+//! ```
+
+use std::collections::HashMap;
+
+/// Binding index of the `Params` uniform block.
+pub const PARAMS_BINDING: u32 = 8;
+
+/// A float member of a pass's `Params` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamField {
+    pub name: String,
+    /// Byte offset within the block
+    pub offset: u32,
+}
+
+/// Layout of a pass's `Params` uniform block, reflected from the shader.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamLayout {
+    /// Size of the block in bytes
+    pub size: u32,
+    pub fields: Vec<ParamField>,
+}
+
+impl ParamLayout {
+    /// Reflects the `Params` block (binding 8) from a parsed shader module, if it declares one.
+    /// Only `float` members are addressable; other members are left zeroed.
+    pub fn reflect(module: &naga::Module) -> Option<Self> {
+        let (_, var) = module.global_variables.iter().find(|(_, var)| {
+            matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: PARAMS_BINDING }))
+        })?;
+
+        let naga::TypeInner::Struct { members, span } = &module.types[var.ty].inner else {
+            tracing::warn!("Params block at binding {} is not a struct, ignoring", PARAMS_BINDING);
+            return None;
+        };
+
+        let mut fields = Vec::new();
+        for member in members {
+            let name = member.name.clone().unwrap_or_default();
+            match module.types[member.ty].inner {
+                naga::TypeInner::Scalar(naga::Scalar::F32) => fields.push(ParamField { name, offset: member.offset }),
+                _ => tracing::warn!("Param '{}' is not a float and can't be set by name", name),
+            }
+        }
+
+        Some(Self { size: (*span).max(16), fields })
+    }
+
+    /// Packs parameter values into the block's byte layout. Missing values are zero.
+    pub fn pack(&self, values: &HashMap<String, f32>) -> Vec<u8> {
+        let mut data = vec![0u8; self.size as usize];
+        for field in &self.fields {
+            let value = values.get(&field.name).copied().unwrap_or(0.0);
+            let offset = field.offset as usize;
+            data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reflect_and_pack() {
+        let glsl = r#"
+            #version 450
+            layout(set=0, binding=8) uniform Params {
+                float intensity;
+                float speed;
+            };
+            layout(location=0) out vec4 f_color;
+            void main() { f_color = vec4(intensity, speed, 0.0, 1.0); }
+        "#;
+        let mut frontend = naga::front::glsl::Frontend::default();
+        let module = frontend
+            .parse(&naga::front::glsl::Options::from(naga::ShaderStage::Fragment), glsl)
+            .unwrap();
+
+        let layout = ParamLayout::reflect(&module).unwrap();
+        let names: Vec<_> = layout.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["intensity", "speed"]);
+
+        let values = HashMap::from([("speed".to_string(), 2.0)]);
+        let data = layout.pack(&values);
+        assert_eq!(&data[4..8], &2.0f32.to_ne_bytes());
+        assert_eq!(&data[0..4], &0.0f32.to_ne_bytes());
+    }
+}
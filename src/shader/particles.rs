@@ -0,0 +1,639 @@
+//! GPU particle system.
+//!
+//! Runs after the user shaders. A compute shader moves the particles and
+//! respawns dead ones where the emitter is strong: on the edge of the
+//! segmentation mask (sparks flying off the silhouette) or where the camera
+//! image changed since the last frame. The particles are then drawn as soft
+//! additive dots over the final pass output, colored along a ramp over their
+//! lifetime.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// Largest number of particles.
+pub const MAX_PARTICLES: u32 = 65536;
+
+/// Largest number of colors in the ramp.
+pub const MAX_RAMP_COLORS: usize = 4;
+
+/// The camera luminance compared for motion is kept at this fraction of the input size.
+const MOTION_DIVISOR: u32 = 4;
+
+/// Particles updated per compute workgroup.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Where particles are spawned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParticleEmitter {
+    /// Edge of the segmentation mask, flying outwards
+    #[default]
+    MaskEdge,
+    /// Where the camera image changed since the last frame
+    Motion,
+}
+
+/// Particle system settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Particles {
+    pub emitter: ParticleEmitter,
+    /// Number of particles
+    pub count: u32,
+    /// Longest lifetime in seconds (each particle lives 50 - 100% of it)
+    pub lifetime: f32,
+    /// Initial speed in frame heights per second
+    pub speed: f32,
+    /// Downward acceleration in frame heights per second² (negative rises)
+    pub gravity: f32,
+    /// Diameter in output pixels
+    pub size: f32,
+    /// Emitter strength (0.0 - 1.0) below which nothing spawns
+    pub threshold: f32,
+    /// Colors over the lifetime, evenly spaced (RGBA, the alpha fades)
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self {
+            emitter: ParticleEmitter::MaskEdge,
+            count: 4096,
+            lifetime: 1.5,
+            speed: 0.3,
+            gravity: 0.5,
+            size: 4.0,
+            threshold: 0.1,
+            colors: vec![[1.0, 0.9, 0.5, 1.0], [1.0, 0.4, 0.1, 1.0], [0.4, 0.05, 0.0, 0.0]],
+        }
+    }
+}
+
+/// Shader uniforms, see `ParticleUniforms` in the shader.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleUniforms {
+    ramp: [[f32; 4]; MAX_RAMP_COLORS],
+    ramp_length: u32,
+    emitter: u32,
+    count: u32,
+    frame: u32,
+    delta: f32,
+    lifetime: f32,
+    speed: f32,
+    gravity: f32,
+    size: [f32; 2],
+    threshold: f32,
+    aspect: f32,
+}
+
+impl Particles {
+    /// Shader uniforms for a frame `delta` seconds after the last one.
+    fn uniforms(&self, delta: f32, frame: u32, width: u32, height: u32) -> ParticleUniforms {
+        let mut ramp = [[1.0; 4]; MAX_RAMP_COLORS];
+        for (slot, color) in ramp.iter_mut().zip(&self.colors) {
+            *slot = *color;
+        }
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        ParticleUniforms {
+            ramp,
+            ramp_length: self.colors.len().clamp(1, MAX_RAMP_COLORS) as u32,
+            emitter: match self.emitter {
+                ParticleEmitter::MaskEdge => 0,
+                ParticleEmitter::Motion => 1,
+            },
+            count: self.count.min(MAX_PARTICLES),
+            frame,
+            delta,
+            lifetime: self.lifetime.max(0.01),
+            speed: self.speed,
+            gravity: self.gravity,
+            size: [self.size.max(0.0) / width, self.size.max(0.0) / height],
+            threshold: self.threshold.clamp(0.0, 1.0),
+            aspect: width / height,
+        }
+    }
+}
+
+/// Declarations shared by `UPDATE_SHADER` and `DRAW_SHADER`.
+const PARTICLE_COMMON: &str = r#"
+struct ParticleUniforms {
+    ramp: array<vec4<f32>, 4>,
+    ramp_length: u32,
+    emitter: u32,
+    count: u32,
+    frame: u32,
+    delta: f32,
+    lifetime: f32,
+    speed: f32,
+    gravity: f32,
+    // Diameter in texture coordinates
+    size: vec2<f32>,
+    threshold: f32,
+    aspect: f32,
+}
+
+struct Particle {
+    // Texture coordinates
+    position: vec2<f32>,
+    // Frame heights per second
+    velocity: vec2<f32>,
+    age: f32,
+    life: f32,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> u_particles: ParticleUniforms;
+"#;
+
+const UPDATE_SHADER: &str = r#"
+@group(0) @binding(1) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(2) var t_mask: texture_2d<f32>;
+@group(0) @binding(3) var t_current: texture_2d<f32>;
+@group(0) @binding(4) var t_previous: texture_2d<f32>;
+@group(0) @binding(5) var s_linear: sampler;
+
+fn hash(value: u32) -> u32 {
+    var state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+// Emitter strength at `uv` (z, 0 - 1) and the direction particles leave in (xy)
+fn emission(uv: vec2<f32>) -> vec3<f32> {
+    if u_particles.emitter == 0u {
+        let texel = 2.0 / vec2<f32>(textureDimensions(t_mask));
+        let dx = textureSampleLevel(t_mask, s_linear, uv + vec2<f32>(texel.x, 0.0), 0.0).r - textureSampleLevel(t_mask, s_linear, uv - vec2<f32>(texel.x, 0.0), 0.0).r;
+        let dy = textureSampleLevel(t_mask, s_linear, uv + vec2<f32>(0.0, texel.y), 0.0).r - textureSampleLevel(t_mask, s_linear, uv - vec2<f32>(0.0, texel.y), 0.0).r;
+        let gradient = vec2<f32>(dx, dy);
+        let strength = length(gradient);
+        // The gradient points into the person; particles leave the other way
+        return vec3<f32>(-gradient / max(strength, 0.0001), clamp(strength * 2.0, 0.0, 1.0));
+    }
+    // No motion on the first frame, which has nothing to compare with
+    if u_particles.frame == 0u {
+        return vec3<f32>(0.0);
+    }
+    let difference = textureSampleLevel(t_current, s_linear, uv, 0.0).r - textureSampleLevel(t_previous, s_linear, uv, 0.0).r;
+    return vec3<f32>(0.0, -1.0, clamp(abs(difference) * 4.0, 0.0, 1.0));
+}
+
+@compute @workgroup_size(64)
+fn cs_update(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= u_particles.count {
+        return;
+    }
+    var particle = particles[index];
+    particle.age += u_particles.delta;
+    if particle.age < particle.life {
+        particle.velocity.y += u_particles.gravity * u_particles.delta;
+        particle.position += particle.velocity * u_particles.delta * vec2<f32>(1.0 / u_particles.aspect, 1.0);
+        particles[index] = particle;
+        return;
+    }
+
+    // Dead: try a few random spots, weighted by the emitter strength
+    var state = hash(index ^ hash(u_particles.frame));
+    for (var attempt = 0; attempt < 4; attempt++) {
+        let uv = vec2<f32>(random(&state), random(&state));
+        let source = emission(uv);
+        if source.z > u_particles.threshold && random(&state) < source.z {
+            let angle = (random(&state) - 0.5) * 1.5;
+            let direction = vec2<f32>(source.x * cos(angle) - source.y * sin(angle), source.x * sin(angle) + source.y * cos(angle));
+            particle.position = uv;
+            particle.velocity = direction * u_particles.speed * (0.5 + random(&state));
+            particle.age = 0.0;
+            particle.life = u_particles.lifetime * (0.5 + 0.5 * random(&state));
+            break;
+        }
+    }
+    particles[index] = particle;
+}
+"#;
+
+const DRAW_SHADER: &str = r#"
+@group(0) @binding(1) var<storage, read> particles: array<Particle>;
+@group(0) @binding(2) var t_source: texture_2d<f32>;
+@group(0) @binding(3) var s_sampler: sampler;
+
+struct ParticleOutput {
+    @builtin(position) position: vec4<f32>,
+    // -1 - 1 across the dot
+    @location(0) offset: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn ramp(t: f32) -> vec4<f32> {
+    let last = max(u_particles.ramp_length, 1u) - 1u;
+    if last == 0u {
+        return u_particles.ramp[0];
+    }
+    let x = clamp(t, 0.0, 1.0) * f32(last);
+    let i = min(u32(x), last - 1u);
+    return mix(u_particles.ramp[i], u_particles.ramp[i + 1u], x - f32(i));
+}
+
+@vertex
+fn vs_particle(@builtin(vertex_index) vertex: u32, @builtin(instance_index) instance: u32) -> ParticleOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let particle = particles[instance];
+    let corner = corners[vertex];
+    var out: ParticleOutput;
+    out.offset = corner;
+    if particle.age >= particle.life {
+        // Dead particles are moved out of view
+        out.position = vec4<f32>(2.0, 2.0, 0.0, 1.0);
+        out.color = vec4<f32>(0.0);
+        return out;
+    }
+    let uv = particle.position + corner * u_particles.size * 0.5;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.color = ramp(particle.age / particle.life);
+    return out;
+}
+
+@fragment
+fn fs_particle(in: ParticleOutput) -> @location(0) vec4<f32> {
+    let falloff = 1.0 - smoothstep(0.0, 1.0, length(in.offset));
+    return vec4<f32>(in.color.rgb * in.color.a * falloff, 0.0);
+}
+
+@fragment
+fn fs_copy(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_sampler, tex_coords);
+}
+
+// Camera luminance for the motion emitter, averaged over the downscaled texel
+@fragment
+fn fs_luminance(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let texel = 0.25 / vec2<f32>(textureDimensions(t_source));
+    var color = textureSample(t_source, s_sampler, tex_coords + vec2<f32>(-texel.x, -texel.y)).rgb;
+    color += textureSample(t_source, s_sampler, tex_coords + vec2<f32>(texel.x, -texel.y)).rgb;
+    color += textureSample(t_source, s_sampler, tex_coords + vec2<f32>(-texel.x, texel.y)).rgb;
+    color += textureSample(t_source, s_sampler, tex_coords + vec2<f32>(texel.x, texel.y)).rgb;
+    return vec4<f32>(vec3<f32>(dot(color * 0.25, vec3<f32>(0.299, 0.587, 0.114))), 1.0);
+}
+"#;
+
+/// Bind groups of a prepared particle pass.
+struct ParticleBindGroups {
+    update: wgpu::BindGroup,
+    draw: wgpu::BindGroup,
+    luminance: wgpu::BindGroup,
+}
+
+/// GPU resources of the particle pass.
+pub(super) struct ParticlePass {
+    settings: Particles,
+    update_pipeline: wgpu::ComputePipeline,
+    copy_pipeline: wgpu::RenderPipeline,
+    particle_pipeline: wgpu::RenderPipeline,
+    luminance_pipeline: wgpu::RenderPipeline,
+    /// Uniforms, particles (read-write), mask, camera luminance now and last frame, sampler
+    update_layout: wgpu::BindGroupLayout,
+    /// Uniforms, particles (read-only), source, sampler
+    draw_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    particle_buffer: wgpu::Buffer,
+    /// Camera luminance of this and the last frame (motion emitter)
+    luminance: Option<(wgpu::Texture, wgpu::Texture)>,
+    /// Frame with the particles added
+    texture: Option<wgpu::Texture>,
+    bind_groups: Option<ParticleBindGroups>,
+    frame: u32,
+    last_time: Option<f32>,
+}
+
+impl ParticlePass {
+    /// Whether the device can run the particle system (compute shaders and
+    /// storage buffers in vertex shaders).
+    pub fn supported(adapter: &wgpu::Adapter) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::VERTEX_STORAGE)
+    }
+
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: Particles) -> Self {
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT | wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage_entry = |visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let update_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Particle Update Bind Group Layout"),
+            entries: &[
+                uniform_entry,
+                storage_entry(compute, false),
+                texture_entry(2, compute),
+                texture_entry(3, compute),
+                texture_entry(4, compute),
+                sampler_entry(5, compute),
+            ],
+        });
+        let draw_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Particle Draw Bind Group Layout"),
+            entries: &[
+                uniform_entry,
+                storage_entry(wgpu::ShaderStages::VERTEX, true),
+                texture_entry(2, wgpu::ShaderStages::FRAGMENT),
+                sampler_entry(3, wgpu::ShaderStages::FRAGMENT),
+            ],
+        });
+
+        let update_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Update Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!("{}{}", PARTICLE_COMMON, UPDATE_SHADER))),
+        });
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Draw Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!("{}{}", PARTICLE_COMMON, DRAW_SHADER))),
+        });
+        let update_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Update Pipeline Layout"),
+            bind_group_layouts: &[&update_layout],
+            immediate_size: 0,
+        });
+        let update_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Update Pipeline"),
+            layout: Some(&update_pipeline_layout),
+            module: &update_module,
+            entry_point: Some("cs_update"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let draw_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Draw Pipeline Layout"),
+            bind_group_layouts: &[&draw_layout],
+            immediate_size: 0,
+        });
+        let create_pipeline = |vertex: wgpu::VertexState, entry_point: &str, format, blend| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Particle Pipeline"),
+                layout: Some(&draw_pipeline_layout),
+                vertex,
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState { format, blend, write_mask: wgpu::ColorWrites::ALL })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+        let quad_layout = [QuadVertex::layout()];
+        let quad = || wgpu::VertexState {
+            module: vertex_module,
+            entry_point: Some("vs_main"),
+            buffers: &quad_layout,
+            compilation_options: Default::default(),
+        };
+        let copy_pipeline = create_pipeline(quad(), "fs_copy", wgpu::TextureFormat::Rgba16Float, None);
+        let luminance_pipeline = create_pipeline(quad(), "fs_luminance", wgpu::TextureFormat::R16Float, None);
+        // Added onto the frame, its alpha is kept
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add },
+            alpha: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::Zero, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add },
+        };
+        let particle_vertex = wgpu::VertexState {
+            module: &module,
+            entry_point: Some("vs_particle"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        };
+        let particle_pipeline = create_pipeline(particle_vertex, "fs_particle", wgpu::TextureFormat::Rgba16Float, Some(additive));
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Uniform Buffer"),
+            contents: bytemuck::bytes_of(&settings.uniforms(0.0, 0, 1, 1)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Zeroed: all particles start dead
+        let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Buffer"),
+            size: settings.count.clamp(1, MAX_PARTICLES) as u64 * 32,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            settings,
+            update_pipeline,
+            copy_pipeline,
+            particle_pipeline,
+            luminance_pipeline,
+            update_layout,
+            draw_layout,
+            uniform_buffer,
+            particle_buffer,
+            luminance: None,
+            texture: None,
+            bind_groups: None,
+            frame: 0,
+            last_time: None,
+        }
+    }
+
+    pub fn settings(&self) -> &Particles {
+        &self.settings
+    }
+
+    /// Changes the settings. The particle count is fixed; a different one
+    /// needs a new pass.
+    pub fn set_settings(&mut self, settings: Particles) {
+        self.settings = Particles { count: self.settings.count, ..settings };
+    }
+
+    /// Binds `source` (the final pass output), the `camera` input and the
+    /// segmentation `mask`. Textures are (re)created when the sizes change.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, source: &wgpu::Texture, camera: &wgpu::Texture, mask: &wgpu::Texture) {
+        let create = |label, size: wgpu::Extent3d, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | usage,
+                view_formats: &[],
+            })
+        };
+        if self.texture.as_ref().is_none_or(|texture| texture.size() != source.size()) {
+            let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+            self.texture = Some(create("Particle Texture", source.size(), wgpu::TextureFormat::Rgba16Float, usage));
+        }
+        let luminance_size = wgpu::Extent3d {
+            width: (camera.width() / MOTION_DIVISOR).max(1),
+            height: (camera.height() / MOTION_DIVISOR).max(1),
+            depth_or_array_layers: 1,
+        };
+        if self.luminance.as_ref().is_none_or(|(current, _)| current.size() != luminance_size) {
+            let current = create("Particle Luminance", luminance_size, wgpu::TextureFormat::R16Float, wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
+            let previous = create("Particle Previous Luminance", luminance_size, wgpu::TextureFormat::R16Float, wgpu::TextureUsages::COPY_DST);
+            self.luminance = Some((current, previous));
+            self.frame = 0;
+        }
+        let Some((current, previous)) = &self.luminance else { return };
+
+        let view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let update = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Update Bind Group"),
+            layout: &self.update_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: self.particle_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&view(mask)) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&view(current)) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&view(previous)) },
+                wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(sampler) },
+            ],
+        });
+        let draw_bind_group = |label, input: &wgpu::Texture| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &self.draw_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: self.uniform_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.particle_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&view(input)) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::Sampler(sampler) },
+                ],
+            })
+        };
+        self.bind_groups = Some(ParticleBindGroups {
+            update,
+            draw: draw_bind_group("Particle Draw Bind Group", source),
+            luminance: draw_bind_group("Particle Luminance Bind Group", camera),
+        });
+    }
+
+    /// The frame with the particles added, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Records the particle update and drawing into `encoder`. `time` is the
+    /// shader time in seconds; the particles move by the time since the last frame.
+    pub fn encode(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer, time: f32) {
+        let (Some(bind_groups), Some(texture), Some((current, previous))) = (&self.bind_groups, &self.texture, &self.luminance) else { return };
+        // Paused time freezes the particles, seeking doesn't make them jump
+        let delta = self.last_time.map_or(0.0, |last_time| (time - last_time).clamp(0.0, 0.1));
+        self.last_time = Some(time);
+        let uniforms = self.settings.uniforms(delta, self.frame, texture.width(), texture.height());
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+
+        // Camera luminance for the motion emitter
+        if self.settings.emitter == ParticleEmitter::Motion {
+            let mut luminance_pass = Self::begin_render_pass(encoder, current, "Particle Luminance Pass", vertex_buffer, index_buffer);
+            luminance_pass.set_pipeline(&self.luminance_pipeline);
+            luminance_pass.set_bind_group(0, &bind_groups.luminance, &[]);
+            luminance_pass.draw_indexed(0..6, 0, 0..1);
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Particle Update Pass"), timestamp_writes: None });
+            compute_pass.set_pipeline(&self.update_pipeline);
+            compute_pass.set_bind_group(0, &bind_groups.update, &[]);
+            compute_pass.dispatch_workgroups(uniforms.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        if self.settings.emitter == ParticleEmitter::Motion {
+            encoder.copy_texture_to_texture(current.as_image_copy(), previous.as_image_copy(), current.size());
+        }
+
+        let mut draw_pass = Self::begin_render_pass(encoder, texture, "Particle Draw Pass", vertex_buffer, index_buffer);
+        draw_pass.set_pipeline(&self.copy_pipeline);
+        draw_pass.set_bind_group(0, &bind_groups.draw, &[]);
+        draw_pass.draw_indexed(0..6, 0, 0..1);
+        draw_pass.set_pipeline(&self.particle_pipeline);
+        draw_pass.draw(0..6, 0..uniforms.count);
+        drop(draw_pass);
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Starts a render pass into `target` with the fullscreen quad bound.
+    fn begin_render_pass<'a>(encoder: &'a mut wgpu::CommandEncoder, target: &wgpu::Texture, label: &str, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) -> wgpu::RenderPass<'a> {
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniforms() {
+        let particles = Particles { colors: vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 0.0]], size: 8.0, count: MAX_PARTICLES * 2, ..Particles::default() };
+        let uniforms = particles.uniforms(0.02, 3, 1600, 800);
+        assert_eq!(uniforms.ramp_length, 2);
+        assert_eq!(uniforms.ramp[1], [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(uniforms.ramp[2], [1.0; 4]);
+        assert_eq!(uniforms.size, [0.005, 0.01]);
+        assert_eq!(uniforms.aspect, 2.0);
+        assert_eq!(uniforms.count, MAX_PARTICLES);
+
+        // Without colors the particles are white
+        let plain = Particles { colors: Vec::new(), ..Particles::default() }.uniforms(0.0, 0, 1, 1);
+        assert_eq!(plain.ramp_length, 1);
+        assert_eq!(plain.ramp[0], [1.0; 4]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Readback of output frames with several frames in flight.
+//!
+//! Instead of waiting for the GPU after every frame, each frame is copied into
+//! its own buffer, which is mapped asynchronously. The frame is taken on a later
+//! call, once the map callback has fired, so the CPU prepares frame N while the
+//! GPU still renders and copies frame N-1.
+
+use crate::frame::{PixelFormat, VideoFrame, YuvColorSpace};
+use crate::recorder::metadata::FrameMetadata;
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Number of readback buffers, i.e. frames that can be in flight at once.
+pub(super) const FRAMES_IN_FLIGHT: usize = 3;
+
+type MapResult = Arc<Mutex<Option<std::result::Result<(), wgpu::BufferAsyncError>>>>;
+
+/// Size and layout of a frame in flight.
+pub(super) struct FrameInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub color: Option<YuvColorSpace>,
+    /// Bytes used in the buffer
+    pub size: u64,
+    /// State of the pipeline when the frame was rendered
+    pub metadata: FrameMetadata,
+}
+
+struct InFlight {
+    slot: usize,
+    info: FrameInfo,
+    submission: wgpu::SubmissionIndex,
+    /// Set by the map callback
+    mapped: MapResult,
+}
+
+pub(super) struct ReadbackRing {
+    buffers: Vec<wgpu::Buffer>,
+    /// Frames in flight, oldest first
+    in_flight: VecDeque<InFlight>,
+}
+
+impl ReadbackRing {
+    /// Creates the buffers, each `size` bytes.
+    pub fn new(device: &wgpu::Device, size: u64) -> Self {
+        let buffers = (0..FRAMES_IN_FLIGHT)
+            .map(|i| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Readback Buffer {}", i)),
+                    size,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        Self { buffers, in_flight: VecDeque::new() }
+    }
+
+    /// Returns a free buffer for the next frame. If all buffers are in flight,
+    /// waits for the oldest frame and returns it as well, so it isn't lost.
+    pub fn acquire(&mut self, device: &wgpu::Device) -> Result<(usize, Option<(VideoFrame, FrameMetadata)>)> {
+        let finished = if self.in_flight.len() == self.buffers.len() { self.finish(device)? } else { None };
+        let slot = (0..self.buffers.len())
+            .find(|slot| self.in_flight.iter().all(|frame| frame.slot != *slot))
+            .ok_or_else(|| anyhow!("No readback buffer available"))?;
+        Ok((slot, finished))
+    }
+
+    pub fn buffer(&self, slot: usize) -> &wgpu::Buffer {
+        &self.buffers[slot]
+    }
+
+    /// Starts mapping `slot` after the copy into it was submitted.
+    pub fn submit(&mut self, slot: usize, info: FrameInfo, submission: wgpu::SubmissionIndex) {
+        let mapped: MapResult = Arc::new(Mutex::new(None));
+        let callback_result = mapped.clone();
+        self.buffers[slot].slice(..info.size).map_async(wgpu::MapMode::Read, move |result| {
+            *callback_result.lock().unwrap() = Some(result);
+        });
+        self.in_flight.push_back(InFlight { slot, info, submission, mapped });
+    }
+
+    /// Waits for the oldest frame in flight and takes it with its metadata.
+    pub fn finish(&mut self, device: &wgpu::Device) -> Result<Option<(VideoFrame, FrameMetadata)>> {
+        let Some(oldest) = self.in_flight.front() else {
+            return Ok(None);
+        };
+        device
+            .poll(wgpu::PollType::Wait { submission_index: Some(oldest.submission.clone()), timeout: None })
+            .map_err(|e| anyhow!("GPU readback failed: {}", e))?;
+        self.take_ready()
+    }
+
+    /// Takes the oldest frame and its metadata if its buffer is mapped. Call
+    /// `device.poll` first so the map callbacks run.
+    pub fn take_ready(&mut self) -> Result<Option<(VideoFrame, FrameMetadata)>> {
+        let Some(oldest) = self.in_flight.front() else {
+            return Ok(None);
+        };
+        let Some(result) = oldest.mapped.lock().unwrap().take() else {
+            return Ok(None);
+        };
+        let frame = self.in_flight.pop_front().unwrap();
+        result?;
+
+        let buffer = &self.buffers[frame.slot];
+        let data = buffer.slice(..frame.info.size).get_mapped_range().to_vec();
+        buffer.unmap();
+
+        let info = frame.info;
+        let output = VideoFrame::from_data(info.width, info.height, info.format, data);
+        let output = match info.color {
+            Some(color) => output.with_color(color),
+            None => output,
+        };
+        Ok(Some((output, info.metadata)))
+    }
+}
@@ -0,0 +1,485 @@
+//! Rule-based redaction of the camera input.
+//!
+//! Runs after the text blur and before the first user shader. Each rule
+//! pixelates or blurs a configured rectangle, the people found by the
+//! segmentation, or the objects found by a detection model (logos, faces, ...),
+//! so the regions stay hidden whatever the shaders do. Rules can be switched on
+//! and off while running.
+
+use crate::frame::QuadVertex;
+use crate::ml::{Detection, ObjectModel};
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+use tracing::warn;
+use wgpu::util::DeviceExt;
+
+/// Maximum number of regions redacted at once; further detections are merged into one.
+pub const MAX_REDACTED_REGIONS: usize = 32;
+
+/// How a region is hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedactionEffect {
+    #[default]
+    Pixelate,
+    Blur,
+}
+
+/// What a rule redacts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedactionSource {
+    /// A fixed rectangle (0.0 - 1.0)
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+    /// The people told apart by the instance segmentation
+    People,
+    /// The objects found by a detection model
+    Model(ObjectModel),
+}
+
+/// A redaction rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedactionRule {
+    /// Name used by `proteus ctl redact`
+    pub name: String,
+    pub enabled: bool,
+    pub source: RedactionSource,
+    pub effect: RedactionEffect,
+    /// Pixel block size or blur radius in pixels
+    pub strength: f32,
+    /// Width in pixels over which the effect fades out beyond the region
+    pub padding: f32,
+    /// Detected classes to redact (all if empty)
+    pub classes: Vec<usize>,
+    /// Leave the largest detection alone, e.g. the main subject among the people or faces
+    pub except_largest: bool,
+}
+
+const REDACTION_SHADER: &str = r#"
+struct RedactionRegion {
+    // left, top, right, bottom in 0..1 frame coordinates
+    rect: vec4<f32>,
+    // effect (0 = pixelate, 1 = blur), strength, feather (pixels), unused
+    params: vec4<f32>,
+}
+
+struct RedactionUniforms {
+    count: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    regions: array<RedactionRegion, 32>,
+}
+
+@group(0) @binding(0) var t_input: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_redaction: RedactionUniforms;
+
+const TAPS: i32 = 6;
+
+fn coverage(region: RedactionRegion, pixel: vec2<f32>, size: vec2<f32>) -> f32 {
+    let outside = max(max(region.rect.xy * size - pixel, pixel - region.rect.zw * size), vec2<f32>(0.0));
+    let distance = length(outside);
+    if (region.params.z <= 0.0) {
+        return select(0.0, 1.0, distance <= 0.0);
+    }
+    return 1.0 - smoothstep(0.0, region.params.z, distance);
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_input, s_sampler, tex_coords, 0.0);
+    let size = vec2<f32>(textureDimensions(t_input));
+    let pixel = tex_coords * size;
+
+    // The most covering region decides the effect
+    var amount = 0.0;
+    var index = 0u;
+    for (var i = 0u; i < min(u_redaction.count, 32u); i++) {
+        let c = coverage(u_redaction.regions[i], pixel, size);
+        if (c > amount) {
+            amount = c;
+            index = i;
+        }
+    }
+    if (amount <= 0.0) {
+        return color;
+    }
+
+    let region = u_redaction.regions[index];
+    let strength = max(region.params.y, 1.0);
+    var hidden: vec4<f32>;
+    if (region.params.x < 0.5) {
+        // Every pixel of a block shows the block's center
+        let block = (floor(pixel / strength) + 0.5) * strength / size;
+        hidden = textureSampleLevel(t_input, s_sampler, block, 0.0);
+    } else {
+        // Sparse box blur over the radius
+        let step = strength / f32(TAPS) / size;
+        var sum = vec4<f32>(0.0);
+        for (var x = -TAPS; x <= TAPS; x++) {
+            for (var y = -TAPS; y <= TAPS; y++) {
+                sum += textureSampleLevel(t_input, s_sampler, tex_coords + vec2<f32>(f32(x), f32(y)) * step, 0.0);
+            }
+        }
+        hidden = sum / f32((2 * TAPS + 1) * (2 * TAPS + 1));
+    }
+    return mix(color, hidden, amount);
+}
+"#;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct RedactionRegion {
+    /// left, top, right, bottom (0.0 - 1.0)
+    rect: [f32; 4],
+    /// effect, strength, feather, unused
+    params: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct RedactionUniforms {
+    count: u32,
+    _padding: [u32; 3],
+    regions: [RedactionRegion; MAX_REDACTED_REGIONS],
+}
+
+/// The whole frame, for rules whose detector hasn't reported yet
+const FULL_FRAME: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+/// Regions of the enabled rules, given the latest detections of each rule. Rules
+/// without detections yet (the model is loading or failed to load) hide the whole frame.
+fn redacted_regions(rules: &[RedactionRule], detections: &[Option<Vec<Detection>>]) -> Vec<RedactionRegion> {
+    let mut regions = Vec::new();
+    for (rule, detections) in rules.iter().zip(detections).filter(|(rule, _)| rule.enabled) {
+        let params = [
+            match rule.effect {
+                RedactionEffect::Pixelate => 0.0,
+                RedactionEffect::Blur => 1.0,
+            },
+            rule.strength,
+            rule.padding.max(0.0),
+            0.0,
+        ];
+        let boxes: Vec<[f32; 4]> = match rule.source {
+            RedactionSource::Rect { x, y, width, height } => vec![[x, y, x + width, y + height]],
+            RedactionSource::People | RedactionSource::Model(_) => {
+                let Some(detections) = detections else {
+                    regions.push(RedactionRegion { rect: FULL_FRAME, params });
+                    continue;
+                };
+                let mut detections: Vec<&Detection> = detections
+                    .iter()
+                    .filter(|detection| rule.classes.is_empty() || rule.classes.contains(&detection.class))
+                    .collect();
+                if rule.except_largest {
+                    if let Some(largest) = (0..detections.len()).max_by(|&a, &b| detections[a].area().total_cmp(&detections[b].area())) {
+                        detections.remove(largest);
+                    }
+                }
+                detections.iter().map(|d| [d.x, d.y, d.x + d.width, d.y + d.height]).collect()
+            }
+        };
+        regions.extend(boxes.into_iter().map(|rect| RedactionRegion { rect, params }));
+    }
+    regions
+}
+
+/// Merges the regions beyond [`MAX_REDACTED_REGIONS`] into their bounding box,
+/// which takes the effect of the first of them. Returns whether any were merged.
+fn merge_overflow(regions: &mut Vec<RedactionRegion>) -> bool {
+    if regions.len() <= MAX_REDACTED_REGIONS {
+        return false;
+    }
+    let overflow = regions.split_off(MAX_REDACTED_REGIONS - 1);
+    let rect = overflow.iter().fold(overflow[0].rect, |bounds, region| [
+        bounds[0].min(region.rect[0]),
+        bounds[1].min(region.rect[1]),
+        bounds[2].max(region.rect[2]),
+        bounds[3].max(region.rect[3]),
+    ]);
+    regions.push(RedactionRegion { rect, params: overflow[0].params });
+    true
+}
+
+/// GPU resources of the redaction pass.
+pub(super) struct RedactionPass {
+    rules: Vec<RedactionRule>,
+    /// Latest detections of each rule, `None` until its detector reports (unused for rectangles)
+    detections: Vec<Option<Vec<Detection>>>,
+    /// The regions need to be uploaded
+    changed: bool,
+    /// Too many regions were merged already (warned once)
+    overflowed: bool,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    output: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl RedactionPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, rules: Vec<RedactionRule>) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Redaction Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Redaction Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Redaction Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(REDACTION_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Redaction Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        // Starts without regions; `update` uploads them
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Redaction Uniform Buffer"),
+            contents: bytemuck::bytes_of(&RedactionUniforms {
+                count: 0,
+                _padding: [0; 3],
+                regions: [RedactionRegion::default(); MAX_REDACTED_REGIONS],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            detections: vec![None; rules.len()],
+            rules,
+            changed: true,
+            overflowed: false,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            output: None,
+            bind_group: None,
+        }
+    }
+
+    pub fn rules(&self) -> &[RedactionRule] {
+        &self.rules
+    }
+
+    /// Switches the rule `name` on (`Some(true)`), off or toggles it (`None`).
+    /// Returns whether it is on now.
+    pub fn set_enabled(&mut self, name: &str, enabled: Option<bool>) -> Result<bool> {
+        let rule = self.rules.iter_mut().find(|rule| rule.name == name).ok_or_else(|| anyhow!("Unknown redaction rule '{}'", name))?;
+        rule.enabled = enabled.unwrap_or(!rule.enabled);
+        self.changed = true;
+        Ok(rule.enabled)
+    }
+
+    /// Takes over the latest detections of `old` for the rules that detect the same way,
+    /// so the regions don't show until the detectors report again.
+    pub fn continue_from(&mut self, old: &RedactionPass) {
+        for (rule, detections) in self.rules.iter().zip(&mut self.detections) {
+            if let Some(index) = old.rules.iter().position(|old_rule| old_rule.source == rule.source) {
+                detections.clone_from(&old.detections[index]);
+            }
+        }
+        self.changed = true;
+    }
+
+    /// Sets the latest detections of the rule at `index`.
+    pub fn set_detections(&mut self, index: usize, detections: Vec<Detection>) {
+        if let Some(entry) = self.detections.get_mut(index) {
+            *entry = Some(detections);
+            self.changed = true;
+        }
+    }
+
+    /// Sets the people found by the segmentation for the `people` rules.
+    pub fn set_people(&mut self, people: &[Detection]) {
+        for (rule, detections) in self.rules.iter().zip(&mut self.detections) {
+            if rule.source == RedactionSource::People {
+                *detections = Some(people.to_vec());
+                self.changed = true;
+            }
+        }
+    }
+
+    /// Uploads the regions if they changed.
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+        let mut regions = redacted_regions(&self.rules, &self.detections);
+        if merge_overflow(&mut regions) && !self.overflowed {
+            warn!("More than {} regions to redact, merging the rest into one", MAX_REDACTED_REGIONS);
+            self.overflowed = true;
+        }
+        let mut uniforms = RedactionUniforms {
+            count: regions.len() as u32,
+            _padding: [0; 3],
+            regions: [RedactionRegion::default(); MAX_REDACTED_REGIONS],
+        };
+        uniforms.regions[..regions.len()].copy_from_slice(&regions);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// (Re)creates the output texture at the size of `input` and binds it.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, input: &wgpu::Texture) {
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Redaction Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+        self.output = Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Redaction Output Texture"),
+            size: input.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+    }
+
+    /// The camera input with the regions redacted, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.output.as_ref()
+    }
+
+    /// Records the redaction pass into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some(output), Some(bind_group)) = (&self.output, &self.bind_group) else { return };
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Redaction Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted_regions() {
+        let rule = |name: &str, source| RedactionRule {
+            name: name.to_string(),
+            enabled: true,
+            source,
+            effect: RedactionEffect::Blur,
+            strength: 20.0,
+            padding: 4.0,
+            classes: Vec::new(),
+            except_largest: false,
+        };
+        let person = |x, width| Detection { class: 0, score: 1.0, x, y: 0.0, width, height: 0.5 };
+        let mut rules = vec![
+            rule("board", RedactionSource::Rect { x: 0.5, y: 0.5, width: 0.25, height: 0.5 }),
+            RedactionRule { except_largest: true, effect: RedactionEffect::Pixelate, ..rule("bystanders", RedactionSource::People) },
+        ];
+        let mut detections = vec![None, Some(vec![person(0.0, 0.1), person(0.3, 0.4), person(0.8, 0.2)])];
+
+        let regions = redacted_regions(&rules, &detections);
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[0], RedactionRegion { rect: [0.5, 0.5, 0.75, 1.0], params: [1.0, 20.0, 4.0, 0.0] });
+        // The main subject (largest) is left alone
+        assert_eq!(regions[1].rect, [0.0, 0.0, 0.1, 0.5]);
+        assert_eq!(regions[2].rect, [0.8, 0.0, 1.0, 0.5]);
+        assert_eq!(regions[2].params[0], 0.0);
+
+        rules[0].enabled = false;
+        assert_eq!(redacted_regions(&rules, &detections).len(), 2);
+
+        // Until the detector reports (or if its model failed to load), the whole frame is hidden
+        detections[1] = None;
+        assert_eq!(redacted_regions(&rules, &detections), vec![RedactionRegion { rect: FULL_FRAME, params: [0.0, 20.0, 4.0, 0.0] }]);
+        rules[1].enabled = false;
+        assert!(redacted_regions(&rules, &detections).is_empty());
+    }
+
+    #[test]
+    fn test_merge_overflow() {
+        let region = |x: f32| RedactionRegion { rect: [x, 0.0, x + 0.01, 0.1], params: [0.0, 20.0, 0.0, 0.0] };
+        let mut regions: Vec<_> = (0..MAX_REDACTED_REGIONS).map(|i| region(i as f32 / 100.0)).collect();
+        assert!(!merge_overflow(&mut regions));
+
+        regions.push(region(0.9));
+        assert!(merge_overflow(&mut regions));
+        assert_eq!(regions.len(), MAX_REDACTED_REGIONS);
+        assert_eq!(regions[..MAX_REDACTED_REGIONS - 1], (0..MAX_REDACTED_REGIONS - 1).map(|i| region(i as f32 / 100.0)).collect::<Vec<_>>()[..]);
+        assert_eq!(regions.last().unwrap().rect, [0.31, 0.0, region(0.9).rect[2], 0.1]);
+    }
+}
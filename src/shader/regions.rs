@@ -0,0 +1,131 @@
+//! Static mask regions.
+//!
+//! Rectangles and polygons from the config or Lua, rasterized into a
+//! single-channel mask (binding 11, `t_regions`) that shaders use to limit
+//! effects to part of the frame.
+
+/// The region mask is rendered at this fraction of the output size
+/// (edges are smoothed by the sampler and the feathering anyway).
+const MASK_DIVISOR: u32 = 4;
+
+/// Shape of a mask region, in texture coordinates (0.0 - 1.0, top-left origin).
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionShape {
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+    Polygon(Vec<[f32; 2]>),
+}
+
+/// A mask region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub shape: RegionShape,
+    /// Width in output pixels over which the region fades out beyond its edge
+    pub feather: f32,
+}
+
+impl Region {
+    /// Polygon outline in texture coordinates.
+    fn outline(&self) -> Vec<[f32; 2]> {
+        match &self.shape {
+            RegionShape::Rect { x, y, width, height } => {
+                vec![[*x, *y], [x + width, *y], [x + width, y + height], [*x, y + height]]
+            }
+            RegionShape::Polygon(points) => points.clone(),
+        }
+    }
+}
+
+/// Size of the region mask for the given output size.
+pub fn mask_size(output_width: u32, output_height: u32) -> (u32, u32) {
+    ((output_width / MASK_DIVISOR).max(1), (output_height / MASK_DIVISOR).max(1))
+}
+
+/// Rasterizes the regions into a mask of `width` x `height` (one byte per
+/// pixel): 255 inside a region, fading to 0 over its feather width.
+pub fn render_regions(regions: &[Region], width: u32, height: u32) -> Vec<u8> {
+    let mut mask = vec![0u8; (width * height) as usize];
+    for region in regions {
+        // Work in mask pixels so distances are isotropic
+        let outline: Vec<[f32; 2]> = region
+            .outline()
+            .iter()
+            .map(|&[x, y]| [x * width as f32, y * height as f32])
+            .collect();
+        if outline.len() < 3 {
+            continue;
+        }
+        // At least one mask pixel of fade to anti-alias the edge
+        let feather = (region.feather / MASK_DIVISOR as f32).max(1.0);
+
+        // Only visit pixels within the feathered bounding box
+        let min = |axis: usize| outline.iter().map(|p| p[axis]).fold(f32::INFINITY, f32::min) - feather;
+        let max = |axis: usize| outline.iter().map(|p| p[axis]).fold(f32::NEG_INFINITY, f32::max) + feather;
+        let (x0, x1) = (min(0).floor().max(0.0) as u32, (max(0).ceil() as u32).min(width));
+        let (y0, y1) = (min(1).floor().max(0.0) as u32, (max(1).ceil() as u32).min(height));
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let point = [x as f32 + 0.5, y as f32 + 0.5];
+                let coverage = if contains(&outline, point) {
+                    1.0
+                } else {
+                    1.0 - edge_distance(&outline, point) / feather
+                };
+                let value = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                let pixel = &mut mask[(y * width + x) as usize];
+                *pixel = (*pixel).max(value);
+            }
+        }
+    }
+    mask
+}
+
+/// Even-odd point-in-polygon test.
+fn contains(outline: &[[f32; 2]], [px, py]: [f32; 2]) -> bool {
+    let mut inside = false;
+    let mut previous = outline[outline.len() - 1];
+    for &current in outline {
+        let ([x0, y0], [x1, y1]) = (previous, current);
+        if (y0 > py) != (y1 > py) && px < x0 + (py - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+/// Distance from a point to the nearest edge of the outline.
+fn edge_distance(outline: &[[f32; 2]], [px, py]: [f32; 2]) -> f32 {
+    let mut distance = f32::INFINITY;
+    let mut previous = outline[outline.len() - 1];
+    for &current in outline {
+        let ([x0, y0], [x1, y1]) = (previous, current);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_squared = dx * dx + dy * dy;
+        let t = if length_squared > 0.0 { (((px - x0) * dx + (py - y0) * dy) / length_squared).clamp(0.0, 1.0) } else { 0.0 };
+        distance = distance.min((px - (x0 + t * dx)).hypot(py - (y0 + t * dy)));
+        previous = current;
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_regions() {
+        let rect = Region { shape: RegionShape::Rect { x: 0.5, y: 0.0, width: 0.5, height: 0.5 }, feather: 16.0 };
+        let triangle = Region { shape: RegionShape::Polygon(vec![[0.0, 1.0], [0.25, 0.5], [0.5, 1.0]]), feather: 0.0 };
+        let (width, height) = (40, 40);
+        let mask = render_regions(&[rect, triangle], width, height);
+        let at = |x: u32, y: u32| mask[(y * width + x) as usize];
+
+        assert_eq!(at(30, 10), 255);
+        // Feathered by 4 mask pixels
+        assert!(at(18, 10) > 0 && at(18, 10) < 255);
+        assert_eq!(at(10, 10), 0);
+        assert_eq!(at(10, 36), 255);
+        assert_eq!(at(1, 22), 0);
+    }
+}
@@ -0,0 +1,314 @@
+//! Per-source crop and transform.
+//!
+//! Texture slots and mixer sources can be cropped, scaled, moved, rotated and
+//! faded in the config instead of editing the asset. The source keeps its
+//! size: the cropped part fills the frame, is transformed around the center,
+//! and whatever it no longer covers is transparent. Texture slots are
+//! transformed on the GPU before the passes sample them; mixer sources, which
+//! are CPU frames, are transformed by [`SourceTransform::apply`].
+
+use crate::frame::{PixelFormat, QuadVertex, VideoFrame};
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+/// Crop, scale, offset, rotation and opacity of a source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceTransform {
+    /// x, y, width, height of the part of the source shown, in 0.0 - 1.0
+    pub crop: [f32; 4],
+    /// Size of the cropped part relative to the frame
+    pub scale: f32,
+    /// Movement in frame widths and heights (positive is right and down)
+    pub offset: [f32; 2],
+    /// Clockwise rotation around the center in degrees
+    pub rotation: f32,
+    /// 0.0 (invisible) - 1.0 (opaque)
+    pub opacity: f32,
+}
+
+impl Default for SourceTransform {
+    fn default() -> Self {
+        Self { crop: [0.0, 0.0, 1.0, 1.0], scale: 1.0, offset: [0.0, 0.0], rotation: 0.0, opacity: 1.0 }
+    }
+}
+
+impl SourceTransform {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Affine map from output texture coordinates to source texture coordinates,
+    /// as two rows of `[x, y, 1]` coefficients. `aspect` is the source's width / height.
+    fn uv_transform(&self, aspect: f32) -> [[f32; 3]; 2] {
+        let [crop_x, crop_y, crop_w, crop_h] = self.crop;
+        let scale = self.scale.max(0.001);
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        // Undo the rotation in pixel space, so it doesn't shear non-square frames
+        let m = [
+            [crop_w / scale * cos, crop_w / scale * sin / aspect],
+            [-crop_h / scale * sin * aspect, crop_h / scale * cos],
+        ];
+        let center = [0.5 + self.offset[0], 0.5 + self.offset[1]];
+        [
+            [m[0][0], m[0][1], crop_x + 0.5 * crop_w - m[0][0] * center[0] - m[0][1] * center[1]],
+            [m[1][0], m[1][1], crop_y + 0.5 * crop_h - m[1][0] * center[0] - m[1][1] * center[1]],
+        ]
+    }
+
+    /// Transforms a frame on the CPU (nearest-neighbor). Returns an RGBA frame of the same size.
+    pub fn apply(&self, frame: &VideoFrame) -> VideoFrame {
+        let rgba = frame.to_rgba().upright();
+        let (w, h) = (rgba.width as usize, rgba.height as usize);
+        let [[a, b, c], [d, e, f]] = self.uv_transform(rgba.width as f32 / rgba.height.max(1) as f32);
+        let [crop_x, crop_y, crop_w, crop_h] = self.crop;
+        let opacity = self.opacity.clamp(0.0, 1.0);
+
+        let mut output = VideoFrame::new(rgba.width, rgba.height, PixelFormat::Rgba);
+        output.timestamp_us = rgba.timestamp_us;
+        for (i, out) in output.data.chunks_exact_mut(4).enumerate() {
+            let x = ((i % w) as f32 + 0.5) / w as f32;
+            let y = ((i / w) as f32 + 0.5) / h as f32;
+            let (u, v) = (a * x + b * y + c, d * x + e * y + f);
+            if u < crop_x || v < crop_y || u > crop_x + crop_w || v > crop_y + crop_h {
+                continue;
+            }
+            let sx = ((u * w as f32) as usize).min(w - 1);
+            let sy = ((v * h as f32) as usize).min(h - 1);
+            let src = (sy * w + sx) * 4;
+            out.copy_from_slice(&rgba.data[src..src + 4]);
+            out[3] = (out[3] as f32 * opacity).round() as u8;
+        }
+        output
+    }
+}
+
+const SOURCE_TRANSFORM_SHADER: &str = r#"
+struct TransformUniforms {
+    // Source texture coordinates = (dot(row0.xyz, (x, y, 1)), dot(row1.xyz, (x, y, 1)))
+    row0: vec4<f32>,
+    row1: vec4<f32>,
+    crop: vec4<f32>,
+    opacity: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_transform: TransformUniforms;
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let p = vec3<f32>(tex_coords, 1.0);
+    let uv = vec2<f32>(dot(u_transform.row0.xyz, p), dot(u_transform.row1.xyz, p));
+    let crop = u_transform.crop;
+    if (any(uv < crop.xy) || any(uv > crop.xy + crop.zw)) {
+        return vec4<f32>(0.0);
+    }
+    let color = textureSampleLevel(t_source, s_sampler, uv, 0.0);
+    return vec4<f32>(color.rgb, color.a * u_transform.opacity);
+}
+"#;
+
+/// GPU resources transforming one texture slot.
+pub(super) struct SourceTransformPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    transform: SourceTransform,
+    output: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl SourceTransformPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, transform: SourceTransform) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Source Transform Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Source Transform Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Source Transform Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SOURCE_TRANSFORM_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Source Transform Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Source Transform Uniform Buffer"),
+            contents: &[0u8; 64],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self { pipeline, bind_group_layout, uniform_buffer, transform, output: None, bind_group: None }
+    }
+
+    /// Binds the source and (re)creates the output texture at its size.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sampler: &wgpu::Sampler, source: &wgpu::TextureView, width: u32, height: u32) {
+        let [[a, b, c], [d, e, f]] = self.transform.uv_transform(width as f32 / height.max(1) as f32);
+        let mut uniforms = [0.0f32; 16];
+        uniforms[..3].copy_from_slice(&[a, b, c]);
+        uniforms[4..7].copy_from_slice(&[d, e, f]);
+        uniforms[8..12].copy_from_slice(&self.transform.crop);
+        uniforms[12] = self.transform.opacity.clamp(0.0, 1.0);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Source Transform Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(source) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+        if self.output.as_ref().is_some_and(|output| output.width() == width && output.height() == height) {
+            return;
+        }
+        self.output = Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Transformed Source Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+    }
+
+    /// The transformed source, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.output.as_ref()
+    }
+
+    /// Records the transform into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some(output), Some(bind_group)) = (&self.output, &self.bind_group) else { return };
+        let view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Source Transform Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(transform: &SourceTransform, aspect: f32, x: f32, y: f32) -> (f32, f32) {
+        let [[a, b, c], [d, e, f]] = transform.uv_transform(aspect);
+        (a * x + b * y + c, d * x + e * y + f)
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!((actual.0 - expected.0).abs() < 1e-5 && (actual.1 - expected.1).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_uv_transform() {
+        let identity = SourceTransform::default();
+        assert!(identity.is_identity());
+        assert_near(map(&identity, 1.0, 0.25, 0.75), (0.25, 0.75));
+
+        // The right half fills the frame
+        let crop = SourceTransform { crop: [0.5, 0.0, 0.5, 1.0], ..Default::default() };
+        assert_near(map(&crop, 1.0, 0.0, 0.0), (0.5, 0.0));
+        assert_near(map(&crop, 1.0, 1.0, 1.0), (1.0, 1.0));
+
+        // Half size, moved right by a quarter frame
+        let moved = SourceTransform { scale: 0.5, offset: [0.25, 0.0], ..Default::default() };
+        assert_near(map(&moved, 1.0, 0.75, 0.5), (0.5, 0.5));
+        assert_near(map(&moved, 1.0, 1.0, 0.75), (1.0, 1.0));
+
+        // Rotated clockwise, the source's top-left corner ends up top-right
+        let rotated = SourceTransform { rotation: 90.0, ..Default::default() };
+        assert_near(map(&rotated, 1.0, 1.0, 0.0), (0.0, 0.0));
+        // On a 2:1 frame, a point a quarter width right of the center came from a half height above it
+        assert_near(map(&rotated, 2.0, 0.75, 0.5), (0.5, 0.0));
+    }
+
+    #[test]
+    fn test_apply() {
+        // Left pixel red, right pixel green
+        let frame = VideoFrame::from_data(2, 1, PixelFormat::Rgba, vec![255, 0, 0, 255, 0, 255, 0, 255]);
+        let flipped = SourceTransform { rotation: 180.0, opacity: 0.5, ..Default::default() }.apply(&frame);
+        assert_eq!(flipped.data, vec![0, 255, 0, 128, 255, 0, 0, 128]);
+        let shrunk = SourceTransform { scale: 0.5, offset: [-0.25, 0.0], ..Default::default() }.apply(&frame);
+        assert_eq!(&shrunk.data[4..], &[0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Text blur for screen sharing.
+//!
+//! Runs before the first user shader and blurs the text regions found by the
+//! text detection model (see [`crate::ml::TextDetector`]) in the camera input,
+//! so emails, keys and the like stay unreadable whatever the shaders do. Until
+//! the model reports its first regions, or if it fails to load, the whole input
+//! is blurred.
+
+use super::regions::{self, Region, RegionShape};
+use crate::frame::QuadVertex;
+use crate::ml::TextBox;
+use std::borrow::Cow;
+use std::path::PathBuf;
+use wgpu::util::DeviceExt;
+
+/// Text blur settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBlur {
+    /// ONNX text detection model (DB-style probability map output)
+    pub model: PathBuf,
+    /// Probability (0.0 - 1.0) from which a pixel counts as text
+    pub threshold: f32,
+    /// Extra margin around each text region in output pixels
+    pub padding: f32,
+    /// Blur radius in output pixels
+    pub radius: f32,
+}
+
+const TEXT_BLUR_SHADER: &str = r#"
+struct TextBlurUniforms {
+    radius: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+}
+
+@group(0) @binding(0) var t_input: texture_2d<f32>;
+@group(0) @binding(1) var t_text: texture_2d<f32>;
+@group(0) @binding(2) var s_sampler: sampler;
+@group(0) @binding(3) var<uniform> u_text_blur: TextBlurUniforms;
+
+const TAPS: i32 = 6;
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_input, s_sampler, tex_coords, 0.0);
+    let text = textureSampleLevel(t_text, s_sampler, tex_coords, 0.0).r;
+    if (text <= 0.0) {
+        return color;
+    }
+
+    // Sparse box blur over the radius
+    let step = u_text_blur.radius / f32(TAPS) / vec2<f32>(textureDimensions(t_input));
+    var sum = vec4<f32>(0.0);
+    for (var x = -TAPS; x <= TAPS; x++) {
+        for (var y = -TAPS; y <= TAPS; y++) {
+            sum += textureSampleLevel(t_input, s_sampler, tex_coords + vec2<f32>(f32(x), f32(y)) * step, 0.0);
+        }
+    }
+    let blurred = sum / f32((2 * TAPS + 1) * (2 * TAPS + 1));
+    return mix(color, blurred, text);
+}
+"#;
+
+/// The text mask of `boxes`, feathered by `padding` so the blur fades in.
+/// Without boxes (nothing detected yet), all of it is text.
+fn text_mask(boxes: Option<&[TextBox]>, padding: f32, width: u32, height: u32) -> Vec<u8> {
+    let Some(boxes) = boxes else {
+        return vec![255; (width * height) as usize];
+    };
+    let regions: Vec<Region> = boxes
+        .iter()
+        .map(|b| Region {
+            shape: RegionShape::Rect { x: b.x, y: b.y, width: b.width, height: b.height },
+            feather: padding.max(0.0),
+        })
+        .collect();
+    regions::render_regions(&regions, width, height)
+}
+
+/// GPU resources of the text blur pass.
+pub(super) struct TextBlurPass {
+    settings: TextBlur,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    /// Text regions (R8), rendered like the mask regions
+    text_mask: wgpu::Texture,
+    /// Latest detected text regions, `None` until the model reports
+    boxes: Option<Vec<TextBox>>,
+    /// The text mask needs to be uploaded
+    changed: bool,
+    output: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl TextBlurPass {
+    /// `output_size` is the pipeline's output size, which sets the resolution of the text mask.
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: TextBlur, output_size: (u32, u32)) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text Blur Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Blur Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Blur Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(TEXT_BLUR_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Blur Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Blur Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[settings.radius.max(1.0), 0.0, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        // `update` fills it, blurring everything until the first detection
+        let (mask_width, mask_height) = regions::mask_size(output_size.0, output_size.1);
+        let text_mask = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text Mask"),
+            size: wgpu::Extent3d { width: mask_width, height: mask_height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        Self {
+            settings,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            text_mask,
+            boxes: None,
+            changed: true,
+            output: None,
+            bind_group: None,
+        }
+    }
+
+    pub fn settings(&self) -> &TextBlur {
+        &self.settings
+    }
+
+    /// Takes over the latest text regions of `old`, which runs the same detector,
+    /// so the text doesn't show until it reports again.
+    pub fn continue_from(&mut self, old: &TextBlurPass) {
+        self.boxes.clone_from(&old.boxes);
+        self.changed = true;
+    }
+
+    /// Sets the latest detected text regions.
+    pub fn set_boxes(&mut self, boxes: Vec<TextBox>) {
+        self.boxes = Some(boxes);
+        self.changed = true;
+    }
+
+    /// Renders the text regions into the text mask if they changed.
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+        let size = self.text_mask.size();
+        let mask = text_mask(self.boxes.as_deref(), self.settings.padding, size.width, size.height);
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo { texture: &self.text_mask, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            &mask,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(size.width), rows_per_image: Some(size.height) },
+            size,
+        );
+    }
+
+    /// (Re)creates the output texture at the size of `input` and binds the camera input.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, input: &wgpu::Texture) {
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        let text_view = self.text_mask.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text Blur Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&text_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 3, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+        self.output = Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text Blur Output Texture"),
+            size: input.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }));
+    }
+
+    /// The camera input with the text blurred, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.output.as_ref()
+    }
+
+    /// Records the text blur pass into `encoder`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let (Some(output), Some(bind_group)) = (&self.output, &self.bind_group) else { return };
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Blur Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_mask() {
+        let (width, height) = (40, 20);
+        // Everything is text until the model reports
+        assert!(text_mask(None, 0.0, width, height).iter().all(|&value| value == 255));
+
+        let boxes = [TextBox { x: 0.5, y: 0.0, width: 0.5, height: 0.5 }];
+        let mask = text_mask(Some(&boxes), 0.0, width, height);
+        assert_eq!(mask[(5 * width + 30) as usize], 255);
+        assert_eq!(mask[(15 * width + 10) as usize], 0);
+        assert!(text_mask(Some(&[]), 0.0, width, height).iter().all(|&value| value == 0));
+    }
+}
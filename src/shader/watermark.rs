@@ -0,0 +1,291 @@
+//! Watermark / logo overlay.
+//!
+//! Blends an image onto the final frame after all user shaders (and the output
+//! layout), so branding never needs a custom shader or Lua script.
+
+use crate::frame::QuadVertex;
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use tracing::info;
+use wgpu::util::DeviceExt;
+
+/// Where the watermark is placed in the frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+/// Watermark settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    /// Image file (PNG with alpha recommended)
+    pub path: PathBuf,
+    pub position: WatermarkPosition,
+    /// Distance from the frame edges in output pixels
+    pub margin: f32,
+    /// Width of the watermark as a fraction of the frame width
+    pub scale: f32,
+    /// 0.0 (invisible) - 1.0 (opaque)
+    pub opacity: f32,
+}
+
+impl Watermark {
+    /// Returns the watermark rectangle (left, top, right, bottom) in 0.0 - 1.0 frame
+    /// coordinates for an image of `image_size` on a frame of `frame_size`.
+    fn rect(&self, image_size: (u32, u32), frame_size: (u32, u32)) -> [f32; 4] {
+        let (frame_w, frame_h) = (frame_size.0.max(1) as f32, frame_size.1.max(1) as f32);
+        let aspect = image_size.1 as f32 / image_size.0.max(1) as f32;
+        let w = self.scale.clamp(0.0, 1.0);
+        let h = w * aspect * frame_w / frame_h;
+        let (mx, my) = (self.margin / frame_w, self.margin / frame_h);
+
+        let (left, top) = match self.position {
+            WatermarkPosition::TopLeft => (mx, my),
+            WatermarkPosition::TopRight => (1.0 - mx - w, my),
+            WatermarkPosition::BottomLeft => (mx, 1.0 - my - h),
+            WatermarkPosition::BottomRight => (1.0 - mx - w, 1.0 - my - h),
+            WatermarkPosition::Center => ((1.0 - w) / 2.0, (1.0 - h) / 2.0),
+        };
+        [left, top, left + w, top + h]
+    }
+}
+
+const WATERMARK_SHADER: &str = r#"
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct WatermarkUniforms {
+    // left, top, right, bottom in 0..1 frame coordinates
+    rect: vec4<f32>,
+    // x = opacity
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0) var t_watermark: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_watermark: WatermarkUniforms;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = mix(u_watermark.rect.xy, u_watermark.rect.zw, in.tex_coords);
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    return out;
+}
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(t_watermark, s_sampler, tex_coords);
+    return vec4<f32>(color.rgb, color.a * u_watermark.params.x);
+}
+"#;
+
+/// GPU resources of the watermark pass.
+pub(super) struct WatermarkPass {
+    settings: Watermark,
+    image_size: (u32, u32),
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl WatermarkPass {
+    /// Loads the watermark image and creates the overlay pipeline.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, settings: Watermark) -> Result<Self> {
+        let image = image::open(&settings.path)
+            .map_err(|e| anyhow!("Failed to load watermark {:?}: {}", settings.path, e))?
+            .to_rgba8();
+        let (w, h) = image.dimensions();
+        info!("Loaded watermark from {:?} ({}x{})", settings.path, w, h);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Watermark Texture"),
+            size: wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            &image,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(w * 4), rows_per_image: Some(h) },
+            wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Watermark Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Watermark Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Watermark Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[0.0f32; 8]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Watermark Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: uniform_buffer.as_entire_binding() },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Watermark Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Watermark Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(WATERMARK_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Watermark Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Ok(Self { settings, image_size: (w, h), pipeline, uniform_buffer, bind_group })
+    }
+
+    pub fn settings(&self) -> &Watermark {
+        &self.settings
+    }
+
+    /// Records the overlay onto `target` (the final frame) into `encoder`.
+    pub fn encode(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Texture,
+        vertex_buffer: &wgpu::Buffer,
+        index_buffer: &wgpu::Buffer,
+    ) {
+        let rect = self.settings.rect(self.image_size, (target.width(), target.height()));
+        let opacity = self.settings.opacity.clamp(0.0, 1.0);
+        let uniforms = [rect[0], rect[1], rect[2], rect[3], opacity, 0.0, 0.0, 0.0];
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&uniforms));
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Watermark Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_placement() {
+        let mut watermark = Watermark {
+            path: PathBuf::new(),
+            position: WatermarkPosition::BottomRight,
+            margin: 10.0,
+            scale: 0.25,
+            opacity: 1.0,
+        };
+        // A square logo on a 1000x500 frame is twice as tall as wide in frame coordinates
+        let [left, top, right, bottom] = watermark.rect((64, 64), (1000, 500));
+        assert!((right - 0.99).abs() < 1e-6 && (bottom - 0.98).abs() < 1e-6);
+        assert!((right - left - 0.25).abs() < 1e-6 && (bottom - top - 0.5).abs() < 1e-6);
+
+        watermark.position = WatermarkPosition::TopLeft;
+        let [left, top, _, _] = watermark.rect((64, 64), (1000, 500));
+        assert!((left - 0.01).abs() < 1e-6 && (top - 0.02).abs() < 1e-6);
+    }
+}
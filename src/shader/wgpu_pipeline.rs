@@ -1,19 +1,54 @@
 //! wgpu-based GPU shader pipeline.
 
-use super::{ShaderPipeline, ShaderSource};
-use crate::frame::{PixelFormat, QuadVertex, VideoFrame};
+use super::accumulate::{Accumulation, AccumulatePass};
+use super::bloom::{Bloom, BloomPass};
+use super::calibration::{Calibration, CalibrationBinding, CALIBRATION_SHADER};
+use super::captions::{CaptionPass, Captions};
+use super::compositor::{Compositor, CompositorPass, CompositorSources, CompositorStage};
+use super::cursor::{CursorOverlay, CursorPass};
+use super::data::{data_size, pack_data, DataBuffer, DataSource, DATA_BINDING, DATA_SLOTS};
+use super::decontaminate::{Decontamination, DecontaminatePass};
+use super::glyphs::{render_glyph_atlas, Glyphs};
+use super::input_filter::{create_input_sampler, InputFilter, SharpenPass};
+use super::layout::{LayoutPass, OutputLayout};
+use super::mipmaps::MipmapPass;
+use super::pack::PackPass;
+use super::readback::{FrameInfo, ReadbackRing};
+use super::warning::WarningPass;
+use super::watermark::{Watermark, WatermarkPass};
+use super::zoom::{Zoom, ZoomPass};
+use super::yuv::YuvPass;
+use super::regions::{self, Region};
+use super::source_transform::{SourceTransform, SourceTransformPass};
+use super::text_blur::{TextBlur, TextBlurPass};
+use super::redaction::{RedactionPass, RedactionRule, RedactionSource};
+use super::modulation::{apply_modulators, Modulator};
+use super::particles::{ParticleEmitter, ParticlePass, Particles};
+use crate::capture::hotplug::DeviceEvent;
+use crate::audio::AudioLevels;
+use crate::clock::{ClockState, WallTime};
+use crate::schedule::WATERMARK_TARGET;
+use crate::stats::{PipelineStats, Stage};
+use crate::output::OutputBackend;
+use super::params::{ParamLayout, PARAMS_BINDING};
+use super::{PassOptions, SeedMode, ShaderPipeline, ShaderSource};
+use crate::frame::{PixelFormat, QuadVertex, VideoFrame, YuvColorSpace};
 use crate::video::VideoPlayer;
-use crate::lua_canvas::LuaCanvas;
+use crate::lua_canvas::{FontDatabase, LuaCanvas, ScriptGlobals};
+use crate::ml::{person_boxes, AsyncObjectDetector, AsyncPoseEngine, AsyncTextDetector, Keypoint, ModelState, MAX_KEYPOINTS};
+use crate::qr::{QrScanner, QrSettings};
+use crate::gesture::{Gesture, GestureRecognizer, GestureSettings};
+use crate::recorder::metadata::{mask_area, FrameMetadata};
 use anyhow::{anyhow, Result};
 use naga::front::glsl::{Frontend, Options};
 use naga::valid::{Capabilities, ValidationFlags, Validator};
 use naga::ShaderStage;
 use std::borrow::Cow;
-use tracing::info;
+use std::collections::{BTreeSet, HashMap};
+use tracing::{error, info, warn};
 use wgpu::util::DeviceExt;
 
-use notify::{RecommendedWatcher, RecursiveMode, Watcher, Event};
-use std::sync::mpsc::{channel, Receiver};
+use crate::reload::{self, ReloadKind, Watch};
 use std::sync::Arc;
 use crate::shader::gpu_context::GpuContext;
 
@@ -29,6 +64,20 @@ pub enum TextureSlot {
     Empty,
 }
 
+/// Where read-back frames go. Each sink has its own calibration, applied in
+/// the sRGB resolve of its readback; the preview and program windows have their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    /// The virtual camera output, also shown by the web UI preview and checked by the frame monitor
+    VirtualCamera,
+    /// Recordings, clips and snapshots
+    Recording,
+}
+
+impl Sink {
+    pub const ALL: [Sink; 2] = [Sink::VirtualCamera, Sink::Recording];
+}
+
 /// Default vertex shader in WGSL.
 const VERTEX_SHADER: &str = r#"
 struct VertexInput {
@@ -48,8 +97,103 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     out.tex_coords = in.tex_coords;
     return out;
 }
+
+// Leading fields of the shader Uniforms block
+struct OutputSize {
+    time: f32,
+    width: f32,
+    height: f32,
+}
+
+@group(0) @binding(2) var<uniform> u_output: OutputSize;
+
+struct ShaderVertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // Centered on the frame, y from -0.5 to 0.5 and x scaled by the aspect ratio
+    @location(1) aspect_coords: vec2<f32>,
+    // In output pixels
+    @location(2) pixel_coords: vec2<f32>,
+}
+
+// Vertex stage of the user shaders, with the extra coordinates
+@vertex
+fn vs_shader(in: VertexInput) -> ShaderVertexOutput {
+    var out: ShaderVertexOutput;
+    let size = vec2<f32>(u_output.width, u_output.height);
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.aspect_coords = (in.tex_coords - 0.5) * vec2<f32>(size.x / size.y, 1.0);
+    out.pixel_coords = in.tex_coords * size;
+    return out;
+}
+"#;
+
+/// Entry point of `VERTEX_SHADER` for the user shader passes.
+const SHADER_VERTEX_ENTRY: &str = "vs_shader";
+
+/// Declarations inserted into GLSL shaders in place of `#include <proteus>`.
+const GLSL_PRELUDE: &str = r#"
+layout(location = 0) in vec2 tex_coords;
+layout(location = 1) in vec2 aspect_coords;
+layout(location = 2) in vec2 pixel_coords;
+
+layout(set = 0, binding = 0) uniform texture2D t_texture;
+layout(set = 0, binding = 1) uniform sampler s_sampler;
+
+layout(set = 0, binding = 2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+    float seed;
+    float live;
+    float beat;
+    float bar;
+    float bpm;
+    vec2 audio_peak;
+    vec2 audio_rms;
+    float clock;
+};
+
+#define TEXEL_SIZE(t) (1.0 / vec2(textureSize(sampler2D(t, s_sampler), 0)))
+"#;
+
+/// Declarations inserted into GLSL vertex shaders in place of `#include <proteus>`.
+/// `place_vertex` sets the position and the outputs the fragment stage expects.
+const GLSL_VERTEX_PRELUDE: &str = r#"
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 vertex_coords;
+
+layout(location = 0) out vec2 tex_coords;
+layout(location = 1) out vec2 aspect_coords;
+layout(location = 2) out vec2 pixel_coords;
+
+layout(set = 0, binding = 2) uniform Uniforms {
+    float time;
+    float width;
+    float height;
+    float seed;
+    float live;
+    float beat;
+    float bar;
+    float bpm;
+    vec2 audio_peak;
+    vec2 audio_rms;
+    float clock;
+};
+
+// Draws the vertex at `screen` (0 - 1, y down, like tex_coords) showing the input at `uv`
+void place_vertex(vec2 screen, vec2 uv) {
+    gl_Position = vec4(screen.x * 2.0 - 1.0, 1.0 - screen.y * 2.0, 0.0, 1.0);
+    tex_coords = uv;
+    aspect_coords = (screen - 0.5) * vec2(width / height, 1.0);
+    pixel_coords = screen * vec2(width, height);
+}
 "#;
 
+/// The line replaced by `GLSL_PRELUDE`.
+const GLSL_PRELUDE_INCLUDE: &str = "#include <proteus>";
+
 /// Default passthrough fragment shader in WGSL.
 const DEFAULT_FRAGMENT_SHADER: &str = r#"
 @group(0) @binding(0) var t_texture: texture_2d<f32>;
@@ -61,6 +205,19 @@ fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
 }
 "#;
 
+/// Fragment shader of the sRGB resolve, which applies the output calibration
+/// (appended to `CALIBRATION_SHADER`).
+const SRGB_FRAGMENT_SHADER: &str = r#"
+@group(0) @binding(0) var t_texture: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(t_texture, s_sampler, tex_coords);
+    return vec4<f32>(calibrate(color.rgb), color.a);
+}
+"#;
+
 /// Uniforms passed to the shader.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -69,23 +226,114 @@ pub struct Uniforms {
     pub width: f32,
     pub height: f32,
     pub seed: f32,
+    /// Tally state: 1.0 when live / on air, 0.0 otherwise
+    pub live: f32,
+    /// Position within the current beat (0.0 - 1.0)
+    pub beat: f32,
+    /// Position within the current bar (0.0 - 1.0)
+    pub bar: f32,
+    pub bpm: f32,
+    /// Audio peak level of the left and right channel (0.0 - 1.0)
+    pub audio_peak: [f32; 2],
+    /// Audio RMS level of the left and right channel (0.0 - 1.0)
+    pub audio_rms: [f32; 2],
+    /// Seconds since local midnight by the wall clock
+    pub clock: f32,
+    pub _padding: [f32; 3],
+}
+
+
+/// Binding of the per-person instance ID texture (`t_instances`).
+const INSTANCES_BINDING: u32 = 9;
+
+/// Binding of the body pose keypoints (`Pose` block).
+const POSE_BINDING: u32 = 10;
+
+/// Binding of the static region mask (`t_regions`).
+const REGIONS_BINDING: u32 = 11;
+
+/// Binding of the glyph atlas (`t_glyphs`).
+const GLYPHS_BINDING: u32 = 12;
+
+/// Custom vertex shader of a pass (see `PassOptions::vertex`).
+struct VertexShader {
+    module: wgpu::ShaderModule,
+    entry_point: &'static str,
+    /// Its Params block, used if the fragment shader declares none
+    params: Option<ParamLayout>,
+}
+
+/// Grid mesh a pass is drawn with (see `PassOptions::mesh`).
+struct PassMesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl PassMesh {
+    fn new(device: &wgpu::Device, index: usize, columns: u32, rows: u32) -> Self {
+        let (vertices, indices) = QuadVertex::grid(columns, rows);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Pass {} Mesh Vertices", index)),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("Pass {} Mesh Indices", index)),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self { vertex_buffer, index_buffer, index_count: indices.len() as u32 }
+    }
 }
 
+/// Capabilities of a fragment shader, detected via naga reflection.
+struct ShaderInfo {
+    /// Samples the segmentation mask (binding 3)
+    uses_mask: bool,
+    /// Reads the person instance IDs (binding 9)
+    uses_instances: bool,
+    /// Samples the glyph atlas (binding 12)
+    uses_glyphs: bool,
+    /// Writes a mask to fragment output location 1
+    outputs_mask: bool,
+    /// Named parameter block (binding 8), if declared
+    params: Option<ParamLayout>,
+}
 
 /// GPU shader pipeline using wgpu.
 pub struct WgpuPipeline {
     context: Arc<GpuContext>,
     render_pipelines: Vec<wgpu::RenderPipeline>,
     vertex_buffer: wgpu::Buffer,
+    // Quad with mirrored texture coordinates for the sRGB readback blit
+    mirrored_vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     bind_group_layout: wgpu::BindGroupLayout,
     uniform_buffer: wgpu::Buffer,
     sampler: wgpu::Sampler,
+    // Sampler of the camera input in the first pass
+    input_filter: InputFilter,
+    input_sampler: wgpu::Sampler,
     output_width: u32,
     output_height: u32,
     segmentation_engine: Option<crate::ml::AsyncSegmentationEngine>,
+    // The shaders use the mask (otherwise only redaction rules may start the segmentation)
+    shader_segmentation: bool,
     mask_texture: wgpu::Texture,
+    // Person IDs at the mask resolution (R8, id / 255)
+    instances_texture: wgpu::Texture,
+    // Static mask regions from the config or Lua
+    regions: Vec<Region>,
+    regions_texture: wgpu::Texture,
+    // Characters rendered for the shaders that use t_glyphs
+    glyphs: Glyphs,
+    glyphs_texture: wgpu::Texture,
+    // Fonts, loaded once a shader uses the glyph atlas
+    glyph_fonts: Option<Arc<FontDatabase>>,
     image_textures: [wgpu::Texture; 4],
+    // Image files by slot, reloaded when they change
+    image_paths: [Option<std::path::PathBuf>; 4],
     _loaded_textures: [Option<wgpu::Texture>; 4], // Keep original loaded textures to avoid reloading images
     _current_video_texture_sizes: [Option<(u32, u32)>; 4],
     /// Video players for dynamic texture slots
@@ -98,11 +346,21 @@ pub struct WgpuPipeline {
     lua_slot_map: [Option<usize>; 4],
     /// Cached texture views for Lua canvases (to avoid expensive copies)
     lua_canvas_views: [Option<wgpu::TextureView>; 4],
+    /// Crop and transform of each texture slot (None = shown as is)
+    slot_transforms: [Option<SourceTransformPass>; 4],
 
     // Performance Cache
     input_texture: Option<wgpu::Texture>,
     output_textures: Vec<wgpu::Texture>,
     readback_buffer: Option<wgpu::Buffer>,
+    /// Readback buffers of `read_output_pipelined`
+    readback_ring: Option<ReadbackRing>,
+    /// Metadata of the frame last returned by `read_output_pipelined`
+    read_back_metadata: FrameMetadata,
+    stats: PipelineStats,
+    seed_mode: SeedMode,
+    /// When the ML models were last sent a frame without a result yet
+    ml_sent: Option<std::time::Instant>,
     bind_groups: Vec<wgpu::BindGroup>,
     cached_width: u32,
     cached_height: u32,
@@ -113,30 +371,140 @@ pub struct WgpuPipeline {
     // Shader hot-reloading
     pipeline_mask_outputs: Vec<bool>,
     mask_targets: Vec<Option<wgpu::Texture>>,
-    _watcher: Option<RecommendedWatcher>,
-    reload_rx: Option<Receiver<std::result::Result<Event, notify::Error>>>,
+    shader_watch: Watch,
+    texture_watch: Watch,
     shader_sources: Vec<ShaderSource>, // Keep sources to re-compile
+    pass_options: Vec<PassOptions>,
     vertex_shader_module: wgpu::ShaderModule,
+    // Custom vertex shaders and grid meshes, by pass
+    vertex_shaders: Vec<Option<VertexShader>>,
+    pass_meshes: Vec<Option<PassMesh>>,
     pipeline_layout: wgpu::PipelineLayout,
     
     // sRGB Conversion resources
     srgb_pipeline: wgpu::RenderPipeline,
     srgb_output_texture: Option<wgpu::Texture>,
     srgb_bind_group: Option<wgpu::BindGroup>,
+    // Calibration of each sink, applied in the sRGB resolve
+    sink_calibrations: [CalibrationBinding; 2],
+
+    // Blurs detected text in the camera input
+    text_detector: Option<AsyncTextDetector>,
+    text_blur_pass: Option<TextBlurPass>,
+
+    // Pixelates or blurs regions by rule; the detectors feed the rules at the given index
+    redaction_detectors: Vec<(usize, AsyncObjectDetector)>,
+    redaction_pass: Option<RedactionPass>,
+
+    // Edge refinement of the camera input and mask (used with segmentation only)
+    decontaminate_pass: Option<DecontaminatePass>,
+
+    // Scales the camera input to the render size and sharpens it
+    sharpen_pass: Option<SharpenPass>,
+
+    // Particles over the final pass output
+    particle_pass: Option<ParticlePass>,
+
+    // Glow around bright areas of the final pass output
+    bloom_pass: Option<BloomPass>,
+
+    // Long-exposure accumulation of the final pass output
+    accumulate_pass: Option<AccumulatePass>,
+
+    // Sources composed into one frame before the first or after the last pass
+    compositor_pass: Option<CompositorPass>,
+
+    // Cursor and click highlighting on the composed frame
+    cursor_pass: Option<CursorPass>,
+
+    // Zoom into the final frame, following the cursor
+    zoom_pass: Option<ZoomPass>,
+
+    // Compositor layers (by name) and overlays currently hidden by the schedule
+    hidden: BTreeSet<String>,
+
+    // Final layout composition (None for a single output)
+    layout_pass: Option<LayoutPass>,
+
+    // Logo overlay blended onto the final frame
+    watermark_pass: Option<WatermarkPass>,
+    // SRT or live captions blended onto the final frame, before the watermark
+    caption_pass: Option<CaptionPass>,
+    // Flashing border while the frame monitor raises an alarm
+    warning_pass: Option<WarningPass>,
+
+    // Converts YUYV / NV12 input on the GPU (None = convert on the CPU)
+    yuv_pass: Option<YuvPass>,
+
+    // Generates the mip chain of the input texture
+    mipmap_pass: MipmapPass,
+
+    // Packs the read back frame into the output device's YUV layout (None = RGBA)
+    pack_pass: Option<PackPass>,
     
     // Device texture dimension limit
     max_texture_dimension: u32,
+
+    // Tally state exposed to shaders and Lua
+    live: bool,
+
+    // Mirror the frames read back for each sink horizontally
+    sink_mirrors: [bool; 2],
+
+    // Named shader parameters (binding 8)
+    params: HashMap<String, f32>,
+    param_layouts: Vec<Option<ParamLayout>>,
+    param_buffers: Vec<Option<wgpu::Buffer>>,
+
+    // Global parameter modulators
+    modulators: Vec<Modulator>,
+
+    // Musical clock exposed to shaders, Lua, and beat-synced modulators
+    clock: ClockState,
+
+    // Wall clock exposed to shaders (`clock`) and Lua
+    wall_time: WallTime,
+
+    // Audio levels exposed to shaders and Lua
+    audio: AudioLevels,
+
+    // Body pose estimation (binding 10 and `proteus.pose`)
+    pose_engine: Option<AsyncPoseEngine>,
+    pose_buffer: wgpu::Buffer,
+    pose: [Keypoint; MAX_KEYPOINTS],
+
+    // Data buffers (bindings 13 - 16), bound only if the device supports them
+    data_supported: bool,
+    data_buffers: Vec<DataBuffer>,
+    data_storage: Vec<wgpu::Buffer>,
+
+    // QR code detection (Lua `on_qr` and config actions)
+    qr_scanner: Option<QrScanner>,
+    qr_codes: Vec<String>,
+
+    // Gesture recognition on the pose keypoints (Lua `on_gesture` and config actions)
+    gesture_recognizer: Option<GestureRecognizer>,
+    gestures: Vec<Gesture>,
+
+    // Reported in the recording metadata
+    preset: Option<String>,
+    time: f32,
+    mask_area: Option<f32>,
+    people: Option<u32>,
 }
 
 impl WgpuPipeline {
     /// Creates a new wgpu pipeline with the given shaders.
-    /// Segmentation is automatically enabled if any shader uses the mask binding (binding 3).
+    /// Segmentation is automatically enabled if any shader uses the mask binding (binding 3)
+    /// or the instance ID binding (binding 9).
     /// Texture sources (up to 4) are used for bindings 4-7 in the order specified.
+    /// `pass_options` is matched to `shaders` by index; missing entries use the defaults.
     pub fn new(
         context: Arc<GpuContext>,
         width: u32,
         height: u32,
         shaders: Vec<ShaderSource>,
+        pass_options: Vec<PassOptions>,
         texture_sources: Vec<TextureSlot>,
     ) -> Result<Self> {
         let device = &context.device;
@@ -145,33 +513,41 @@ impl WgpuPipeline {
 
         // Prepare shader sources and detect if any shader uses the mask binding or outputs a mask
         let mut needs_segmentation = false;
+        let mut needs_instances = false;
+        let mut needs_glyphs = false;
         let mut pipeline_mask_outputs = Vec::new();
+        let mut param_layouts = Vec::new();
 
         let shader_sources = if shaders.is_empty() {
+            param_layouts.push(None);
             vec![(DEFAULT_FRAGMENT_SHADER.to_string(), "fs_main")]
         } else {
             let mut sources = Vec::new();
             for shader in &shaders {
-                let (fragment_wgsl, fragment_entry_point, uses_mask, outputs_mask) = match shader {
+                let (fragment_wgsl, fragment_entry_point, shader_info) = match shader {
                     ShaderSource::Glsl { code: glsl, .. } => {
-                        let (wgsl, uses_mask, outputs_mask) = Self::glsl_to_wgsl(&glsl)?;
-                        (wgsl, "main", uses_mask, outputs_mask)
+                        let (wgsl, shader_info) = Self::cached_glsl_to_wgsl(&context, glsl)?;
+                        (wgsl, "main", shader_info)
                     }
                     ShaderSource::Wgsl { code: wgsl, .. } => {
-                        let (uses_mask, outputs_mask) = Self::inspect_wgsl(&wgsl);
-                        (wgsl.clone(), "fs_main", uses_mask, outputs_mask)
+                        (wgsl.clone(), "fs_main", Self::inspect_wgsl(wgsl))
                     }
                 };
-                if uses_mask {
+                if shader_info.uses_mask || shader_info.uses_instances {
                     needs_segmentation = true;
                 }
-                pipeline_mask_outputs.push(outputs_mask);
+                needs_instances |= shader_info.uses_instances;
+                needs_glyphs |= shader_info.uses_glyphs;
+                pipeline_mask_outputs.push(shader_info.outputs_mask);
+                param_layouts.push(shader_info.params);
                 sources.push((fragment_wgsl, fragment_entry_point));
             }
             sources
         };
         
-        if needs_segmentation {
+        if needs_instances {
+            info!("Auto-enabling segmentation: shader uses t_instances binding");
+        } else if needs_segmentation {
             info!("Auto-enabling segmentation: shader uses t_mask binding");
         }
 
@@ -182,87 +558,158 @@ impl WgpuPipeline {
         });
 
         // Create bind group layout
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Texture Bind Group Layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
+        let mut layout_entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            // Image textures (t_image0 through t_image3)
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+                count: None,
+            },
+            // Named parameters (Params block), also readable by custom vertex shaders
+            wgpu::BindGroupLayoutEntry {
+                binding: PARAMS_BINDING,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
+                count: None,
+            },
+            // Person instance IDs (t_instances)
+            wgpu::BindGroupLayoutEntry {
+                binding: INSTANCES_BINDING,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
-                // Image textures (t_image0 through t_image3)
-                wgpu::BindGroupLayoutEntry {
-                    binding: 4,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
+                count: None,
+            },
+            // Body pose keypoints (Pose block)
+            wgpu::BindGroupLayoutEntry {
+                binding: POSE_BINDING,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 5,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
+                count: None,
+            },
+            // Static region mask (t_regions)
+            wgpu::BindGroupLayoutEntry {
+                binding: REGIONS_BINDING,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 6,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
+                count: None,
+            },
+            // Glyph atlas (t_glyphs)
+            wgpu::BindGroupLayoutEntry {
+                binding: GLYPHS_BINDING,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 7,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
+                count: None,
+            },
+        ];
+        // Data buffers (Data0 - Data3), if storage buffers can be read in fragment shaders
+        let data_supported = context.adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::FRAGMENT_STORAGE)
+            && device.limits().max_storage_buffers_per_shader_stage as usize >= DATA_SLOTS;
+        if data_supported {
+            layout_entries.extend((0..DATA_SLOTS as u32).map(|slot| wgpu::BindGroupLayoutEntry {
+                binding: DATA_BINDING + slot,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-            ],
+                count: None,
+            }));
+        }
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Texture Bind Group Layout"),
+            entries: &layout_entries,
         });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -271,72 +718,49 @@ impl WgpuPipeline {
             immediate_size: 0,
         });
 
+        // Custom vertex shaders and grid meshes
+        let mut vertex_shaders = Vec::new();
+        let mut pass_meshes = Vec::new();
+        for (i, options) in pass_options.iter().enumerate() {
+            let vertex = match &options.vertex {
+                Some(source) => Some(Self::compile_vertex_shader(device, i, source)?),
+                None => None,
+            };
+            if let Some(layout) = param_layouts.get_mut(i).filter(|layout| layout.is_none()) {
+                *layout = vertex.as_ref().and_then(|vertex| vertex.params.clone());
+            }
+            vertex_shaders.push(vertex);
+            pass_meshes.push(options.mesh.map(|(columns, rows)| PassMesh::new(device, i, columns, rows)));
+        }
+
         let mut render_pipelines = Vec::new();
         for (i, (fragment_wgsl, fragment_entry_point)) in shader_sources.into_iter().enumerate() {
             let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some(&format!("Fragment Shader {}", i)),
                 source: wgpu::ShaderSource::Wgsl(Cow::Owned(fragment_wgsl.to_string())),
             });
-
-            // Use Rgba16Float for all passes to maintain precision
-            let color_format = wgpu::TextureFormat::Rgba16Float;
-
-            let mut targets = vec![Some(wgpu::ColorTargetState {
-                format: color_format,
-                blend: None,
-                write_mask: wgpu::ColorWrites::ALL,
-            })];
-
-            // If shader outputs a mask (Location 1), add a second target
-            if i < pipeline_mask_outputs.len() && pipeline_mask_outputs[i] {
-                targets.push(Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::R8Unorm,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                }));
-            }
-
-            let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some(&format!("Render Pipeline {}", i)),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &vertex_module,
-                    entry_point: Some("vs_main"),
-                    buffers: &[QuadVertex::layout()],
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &fragment_module,
-                    entry_point: Some(fragment_entry_point),
-                    targets: &targets,
-                    compilation_options: Default::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: None,
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview_mask: None,
-                cache: None,
-            });
+            let vertex = vertex_shaders.get(i).and_then(Option::as_ref).map_or((&vertex_module, SHADER_VERTEX_ENTRY), |vertex| (&vertex.module, vertex.entry_point));
+            let outputs_mask = i < pipeline_mask_outputs.len() && pipeline_mask_outputs[i];
+            let render_pipeline = Self::create_pass_pipeline(&context, &pipeline_layout, i, vertex, (&fragment_module, fragment_entry_point), outputs_mask);
             render_pipelines.push(render_pipeline);
         }
 
         // Create sRGB Blit Pipeline (for readback conversion)
         let srgb_fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
              label: Some("sRGB Blit Fragment Shader"),
-             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(DEFAULT_FRAGMENT_SHADER)),
+             source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!("{}{}", CALIBRATION_SHADER, SRGB_FRAGMENT_SHADER))),
+        });
+
+        let sink_calibrations = Sink::ALL.map(|_| CalibrationBinding::new(device, queue, true));
+        let srgb_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sRGB Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, sink_calibrations[0].bind_group_layout()],
+            immediate_size: 0,
         });
 
         let srgb_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("sRGB Blit Pipeline"),
-            layout: Some(&pipeline_layout),
+            layout: Some(&srgb_pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &vertex_module,
                 entry_point: Some("vs_main"),
@@ -365,8 +789,9 @@ impl WgpuPipeline {
             depth_stencil: None,
             multisample: wgpu::MultisampleState::default(),
             multiview_mask: None,
-            cache: None,
+            cache: context.pipeline_cache(),
         });
+        context.save_pipeline_cache();
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
@@ -374,6 +799,12 @@ impl WgpuPipeline {
             usage: wgpu::BufferUsages::VERTEX,
         });
 
+        let mirrored_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mirrored Vertex Buffer"),
+            contents: bytemuck::cast_slice(QuadVertex::MIRRORED_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
             contents: bytemuck::cast_slice(QuadVertex::INDICES),
@@ -390,12 +821,22 @@ impl WgpuPipeline {
             mipmap_filter: wgpu::MipmapFilterMode::Linear,
             ..Default::default()
         });
+        let input_filter = InputFilter::default();
+        let input_sampler = create_input_sampler(device, input_filter.sampling);
 
         let uniforms = Uniforms {
             time: 0.0,
             width: width as f32,
             height: height as f32,
             seed: 0.0,
+            live: 0.0,
+            beat: 0.0,
+            bar: 0.0,
+            bpm: 0.0,
+            audio_peak: [0.0; 2],
+            audio_rms: [0.0; 2],
+            clock: 0.0,
+            _padding: [0.0; 3],
         };
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Uniform Buffer"),
@@ -403,8 +844,16 @@ impl WgpuPipeline {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let pose = [Keypoint::default(); MAX_KEYPOINTS];
+        let pose_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pose Buffer"),
+            contents: bytemuck::cast_slice(&pose),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let data_storage = (0..DATA_SLOTS).map(|slot| Self::create_data_buffer(device, slot, 1)).collect();
+
         let segmentation_engine = if needs_segmentation {
-             crate::ml::AsyncSegmentationEngine::new()?
+             crate::ml::AsyncSegmentationEngine::new(needs_instances)?
         } else {
             None
         };
@@ -440,44 +889,46 @@ impl WgpuPipeline {
             wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
         );
 
+        // No people identified until the first result (textures start zeroed)
+        let instances_texture = Self::create_mask_texture(device, "Person Instances", 1, 1);
+        // No regions until set: the effect applies nowhere
+        let regions_texture = Self::create_mask_texture(device, "Region Mask", 1, 1);
+        // The glyph atlas is rendered with the default characters if a shader uses it
+        let glyphs = Glyphs::default();
+        let glyph_fonts = needs_glyphs.then(|| Arc::new(FontDatabase::new()));
+        let glyphs_texture = match glyph_fonts.as_ref().and_then(|fonts| render_glyph_atlas(fonts, &glyphs)) {
+            Some((atlas, atlas_width, atlas_height)) => {
+                let texture = Self::create_mask_texture(device, "Glyph Atlas", atlas_width, atlas_height);
+                Self::upload_mask_data(queue, &texture, &atlas, atlas_width, atlas_height);
+                texture
+            }
+            None => {
+                if needs_glyphs {
+                    warn!("No font found for the glyph atlas");
+                }
+                Self::create_mask_texture(device, "Glyph Atlas", 1, 1)
+            }
+        };
+
         // Process texture sources (videos, images, lua canvases, or empty)
         let mut video_players: Vec<VideoPlayer> = Vec::new();
         let mut video_slot_map: [Option<usize>; 4] = [None; 4];
         let mut lua_canvases: Vec<LuaCanvas> = Vec::new();
         let mut lua_slot_map: [Option<usize>; 4] = [None; 4];
         let mut loaded_textures: Vec<Option<wgpu::Texture>> = vec![None; 4];
+        let mut image_paths: [Option<std::path::PathBuf>; 4] = Default::default();
         
         for (i, source) in texture_sources.into_iter().enumerate() {
             if i >= 4 { break; }
             match source {
                 TextureSlot::Image(path) => {
-                    match image::open(&path) {
-                        Ok(img) => {
-                            let rgba = img.to_rgba8();
-                            let (w, h) = rgba.dimensions();
-                            info!("Loaded image {} from {:?} ({}x{})", i, path, w, h);
-                            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                                label: Some(&format!("Image Texture {}", i)),
-                                size: wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
-                                mip_level_count: 1,
-                                sample_count: 1,
-                                dimension: wgpu::TextureDimension::D2,
-                                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                                view_formats: &[],
-                            });
-                            queue.write_texture(
-                                wgpu::TexelCopyTextureInfo { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                                &rgba,
-                                wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(w * 4), rows_per_image: Some(h) },
-                                wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
-                            );
-                            loaded_textures[i] = Some(texture);
-                        }
+                    match Self::load_image_texture(device, queue, i, &path) {
+                        Ok(texture) => loaded_textures[i] = Some(texture),
                         Err(e) => {
-                            tracing::warn!("Failed to load image {:?}: {}. Using black texture.", path, e);
+                            tracing::warn!("{}. Using black texture.", e);
                         }
                     }
+                    image_paths[i] = Some(path);
                 }
                 TextureSlot::Video(player) => {
                     info!("Video slot {} ({}x{})", i, player.width, player.height);
@@ -495,48 +946,47 @@ impl WgpuPipeline {
         
         // Create textures for each slot (use loaded or black fallback)
         
-        // Setup file watcher
-        let (watcher, reload_rx) = if !shaders.is_empty() {
-             let (tx, rx) = channel();
-             match RecommendedWatcher::new(tx, notify::Config::default()) {
-                 Ok(mut w) => {
-                     for source in &shaders {
-                         if let ShaderSource::Glsl { path: Some(p), .. } | ShaderSource::Wgsl { path: Some(p), .. } = source {
-                             if let Err(e) = w.watch(p, RecursiveMode::NonRecursive) {
-                                 tracing::warn!("Failed to watch shader file {:?}: {}", p, e);
-                             } else {
-                                 info!("Watching shader file {:?} for changes", p);
-                             }
-                         }
-                     }
-                     (Some(w), Some(rx))
-                 }
-                 Err(e) => {
-                     tracing::warn!("Failed to create file watcher: {}", e);
-                     (None, None)
-                 }
-             }
-        } else {
-            (None, None)
-        };
+        // Watch the shader and image files
+        let vertex_sources = pass_options.iter().filter_map(|options| options.vertex.as_ref());
+        let shader_paths = shaders.iter().chain(vertex_sources).filter_map(|source| match source {
+            ShaderSource::Glsl { path, .. } | ShaderSource::Wgsl { path, .. } => path.clone(),
+        });
+        let shader_watch = reload::watch(ReloadKind::Shader, shader_paths.collect::<Vec<_>>());
+        let texture_watch = reload::watch(ReloadKind::Texture, image_paths.iter().flatten().cloned().collect::<Vec<_>>());
 
         let image_textures = std::array::from_fn(|i| {
             loaded_textures[i].take().unwrap_or_else(|| Self::create_black_texture(&device, &queue, i))
         });
 
+        let param_buffers = param_layouts.iter().enumerate()
+            .map(|(i, layout)| layout.as_ref().map(|l| Self::create_param_buffer(device, i, l)))
+            .collect();
+        let mipmap_pass = MipmapPass::new(device);
+
         Ok(Self {
             context,
             render_pipelines,
             vertex_buffer,
+            mirrored_vertex_buffer,
             index_buffer,
             bind_group_layout,
             uniform_buffer,
             sampler,
+            input_filter,
+            input_sampler,
             output_width: width,
             output_height: height,
             segmentation_engine,
+            shader_segmentation: needs_segmentation,
             mask_texture,
+            instances_texture,
+            regions: Vec::new(),
+            regions_texture,
+            glyphs,
+            glyphs_texture,
+            glyph_fonts,
             image_textures,
+            image_paths,
             _loaded_textures: [None, None, None, None], // Consumed above
             _current_video_texture_sizes: [None; 4],
             video_players,
@@ -544,210 +994,950 @@ impl WgpuPipeline {
             lua_canvases,
             lua_slot_map,
             lua_canvas_views: [None, None, None, None],
+            slot_transforms: [None, None, None, None],
             input_texture: None,
             output_textures: Vec::new(),
             readback_buffer: None,
+            readback_ring: None,
+            read_back_metadata: FrameMetadata::default(),
+            stats: PipelineStats::new(),
+            seed_mode: SeedMode::default(),
+            ml_sent: None,
             bind_groups: Vec::new(),
             cached_width: 0,
             cached_height: 0,
             cached_mask_width: 0,
             cached_mask_height: 0,
             frame_count: 0,
-            _watcher: watcher,
-            reload_rx,
+            shader_watch,
+            texture_watch,
             shader_sources: shaders,
+            pass_options,
             vertex_shader_module: vertex_module,
+            vertex_shaders,
+            pass_meshes,
             pipeline_layout,
             pipeline_mask_outputs,
             mask_targets: Vec::new(),
             srgb_pipeline,
             srgb_output_texture: None,
             srgb_bind_group: None,
+            sink_calibrations,
+            text_detector: None,
+            text_blur_pass: None,
+            redaction_detectors: Vec::new(),
+            redaction_pass: None,
+            decontaminate_pass: None,
+            sharpen_pass: None,
+            particle_pass: None,
+            bloom_pass: None,
+            accumulate_pass: None,
+            compositor_pass: None,
+            cursor_pass: None,
+            zoom_pass: None,
+            hidden: BTreeSet::new(),
+            layout_pass: None,
+            watermark_pass: None,
+            caption_pass: None,
+            warning_pass: None,
+            yuv_pass: None,
+            mipmap_pass,
+            pack_pass: None,
             max_texture_dimension,
+            live: false,
+            sink_mirrors: [false; 2],
+            params: HashMap::new(),
+            modulators: Vec::new(),
+            clock: ClockState::default(),
+            wall_time: WallTime::default(),
+            audio: AudioLevels::default(),
+            param_layouts,
+            param_buffers,
+            pose_engine: None,
+            pose_buffer,
+            pose,
+            data_supported,
+            data_buffers: Vec::new(),
+            data_storage,
+            qr_scanner: None,
+            qr_codes: Vec::new(),
+            gesture_recognizer: None,
+            gestures: Vec::new(),
+            preset: None,
+            time: 0.0,
+            mask_area: None,
+            people: None,
         })
     }
 
-    /// Check for shader file updates and reload if necessary.
-    fn check_reload(&mut self) {
-        let Some(rx) = &self.reload_rx else { return; };
-        
-        let mut needs_reload = false;
-        // Drain channel to clear backlog and debounce
-        while let Ok(res) = rx.try_recv() {
-            match res {
-                Ok(event) => {
-                    // Check for modify events (and also create/remove just in case editors do weird atomic saves)
-                    if matches!(event.kind, notify::EventKind::Modify(_) | notify::EventKind::Create(_)) {
-                         needs_reload = true;
-                         info!("Shader file modified: {:?}", event.paths);
-                    }
-                }
-                Err(e) => tracing::warn!("Watch error: {}", e),
-            }
-        }
+    /// Sets the tally (live / on air) state exposed to shaders as `live` and to Lua as `proteus.live`.
+    pub fn set_live(&mut self, live: bool) {
+        self.live = live;
+    }
 
-        if needs_reload {
-            // Re-read and re-compile all shaders that changed (or just all for simplicity)
-            info!("Reloading shaders...");
-            
-            // Re-create pipelines
-            for (i, source) in self.shader_sources.iter_mut().enumerate() {
-                // Clone path to release borrow on source so we can mutate it later
-                let path = match source {
-                     ShaderSource::Glsl { path: Some(p), .. } => p.clone(),
-                     ShaderSource::Wgsl { path: Some(p), .. } => p.clone(),
-                     _ => continue,
-                };
-                
-                // Read file
-                let code = match std::fs::read_to_string(&path) {
-                    Ok(c) => c,
-                    Err(e) => {
-                        tracing::warn!("Failed to read shader file {:?}: {}", path, e);
-                        continue;
-                    }
-                };
-                
-                // Update source in memory
-                match source {
-                    ShaderSource::Glsl { code: c, .. } => *c = code.clone(),
-                    ShaderSource::Wgsl { code: c, .. } => *c = code.clone(),
-                }
-                
-                // Compile
-                // Compile and detect capabilities
-                let (fragment_wgsl, fragment_entry_point, outputs_mask) = match source {
-                     ShaderSource::Glsl { code: glsl, .. } => {
-                         match Self::glsl_to_wgsl(glsl) {
-                             Ok((wgsl, _, outputs_mask)) => (wgsl, "main", outputs_mask),
-                             Err(e) => {
-                                 tracing::error!("GLSL compilation error in {:?}: {}", path, e);
-                                 continue;
-                             }
-                         }
-                     }
-                     ShaderSource::Wgsl { code: wgsl, .. } => {
-                         let (_, outputs_mask) = Self::inspect_wgsl(wgsl);
-                         (wgsl.clone(), "fs_main", outputs_mask)
-                     }
-                };
+    /// Mirrors the frames read back for `sink` horizontally. The GPU output
+    /// texture shown in the preview and program windows is not affected.
+    pub fn set_sink_mirror(&mut self, sink: Sink, mirror: bool) {
+        self.sink_mirrors[sink as usize] = mirror;
+    }
 
-                let fragment_module = self.context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                    label: Some(&format!("Fragment Shader {}", i)),
-                    source: wgpu::ShaderSource::Wgsl(Cow::Owned(fragment_wgsl)),
-                });
+    pub fn sink_mirror(&self, sink: Sink) -> bool {
+        self.sink_mirrors[sink as usize]
+    }
 
-                // Use Rgba16Float for all passes to maintain precision
-                let color_format = wgpu::TextureFormat::Rgba16Float;
+    /// Sets (or removes) the color calibration of the frames read back for `sink`.
+    pub fn set_sink_calibration(&mut self, sink: Sink, calibration: Option<Calibration>) -> Result<()> {
+        self.sink_calibrations[sink as usize].set(&self.context.device, &self.context.queue, calibration)
+    }
 
-                let mut targets = vec![Some(wgpu::ColorTargetState {
-                    format: color_format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })];
-
-                if outputs_mask {
-                    targets.push(Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::R8Unorm,
-                        blend: None,
-                        write_mask: wgpu::ColorWrites::ALL,
-                    }));
-                }
+    pub fn sink_calibration(&self, sink: Sink) -> Option<&Calibration> {
+        self.sink_calibrations[sink as usize].settings()
+    }
 
-                let render_pipeline = self.context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some(&format!("Render Pipeline {}", i)),
-                    layout: Some(&self.pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &self.vertex_shader_module,
-                        entry_point: Some("vs_main"),
-                        buffers: &[QuadVertex::layout()],
-                        compilation_options: Default::default(),
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &fragment_module,
-                        entry_point: Some(fragment_entry_point),
-                        targets: &targets,
-                        compilation_options: Default::default(),
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: None,
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        unclipped_depth: false,
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState::default(),
-                    multiview_mask: None,
-                    cache: None,
-                });
-                
-                // Replace pipeline
-                if i < self.render_pipelines.len() {
-                    self.render_pipelines[i] = render_pipeline;
-                    if i < self.pipeline_mask_outputs.len() {
-                        self.pipeline_mask_outputs[i] = outputs_mask;
-                    }
-                    info!("Successfully reloaded shader {}", i);
-                }
-            }
-            // Force resource update to align bind groups and output textures
-            self.cached_width = 0;
-        }
+    /// Whether recordings look like the virtual camera output, so its read-back
+    /// frames can be recorded as they are.
+    pub fn recording_like_output(&self) -> bool {
+        self.sink_mirror(Sink::Recording) == self.sink_mirror(Sink::VirtualCamera) && self.sink_calibration(Sink::Recording) == self.sink_calibration(Sink::VirtualCamera)
     }
 
-    /// Update or create cached textures/buffers if dimensions changed
-    fn ensure_resources(&mut self, width: u32, height: u32, mask_w: u32, mask_h: u32) -> Result<()> {
-        // Calculate render resolution: cap at output resolution, but don't exceed input resolution.
-        // This avoids processing at 4k if input is 1080p (even if output is 4k), or processing at 4k if output is 1080p (even if input is 4k).
-        let render_width = std::cmp::min(self.output_width, width);
-        let render_height = std::cmp::min(self.output_height, height);
+    /// Sets how the `seed` uniform changes from frame to frame.
+    pub fn set_seed_mode(&mut self, mode: SeedMode) {
+        self.seed_mode = mode;
+    }
 
-        if self.cached_width == width && self.cached_height == height 
-           && self.cached_mask_width == mask_w && self.cached_mask_height == mask_h 
-           && !self.output_textures.is_empty() 
-           && self.output_textures[0].width() == render_width {
-            return Ok(());
-        }
+    pub fn seed_mode(&self) -> SeedMode {
+        self.seed_mode
+    }
 
-        info!("Creating GPU resources (Input: {}x{}, Render: {}x{}, Output: {}x{}, Mask: {}x{})", 
-              width, height, render_width, render_height, self.output_width, self.output_height, mask_w, mask_h);
-        
-        // 1. Mask Texture (Create if size changed)
-        if self.cached_mask_width != mask_w || self.cached_mask_height != mask_h {
-            self.mask_texture = self.context.device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("Segmentation Mask"),
-                size: wgpu::Extent3d { width: mask_w, height: mask_h, depth_or_array_layers: 1 },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::R8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
+    /// Converts uncompressed YUV input (YUYV, NV12) on the GPU instead of the CPU.
+    /// Frames the GPU path can't handle (e.g. with segmentation or another model
+    /// enabled, or frames that need downscaling) still take the CPU path.
+    pub fn set_yuv_passthrough(&mut self, enabled: bool) {
+        if enabled != self.yuv_pass.is_some() {
+            self.yuv_pass = enabled.then(|| YuvPass::new(&self.context.device, &self.vertex_shader_module));
         }
+    }
 
-        // 2. Input Texture
-        self.input_texture = Some(self.context.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Input Texture"),
-            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        }));
+    pub fn yuv_passthrough(&self) -> bool {
+        self.yuv_pass.is_some()
+    }
 
-        // 2. Output Textures - Use Rgba16Float for all passes to maintain precision
-        self.output_textures.clear();
-        let num_pipelines = self.render_pipelines.len();
-        for i in 0..num_pipelines {
-            let tex = self.context.device.create_texture(&wgpu::TextureDescriptor {
+    /// Sets the pixel format `read_output` returns. YUV formats (YUYV, UYVY, NV12) are
+    /// packed on the GPU in the layout the output device expects; output sizes the
+    /// packing doesn't support are read back as RGBA.
+    pub fn set_output_format(&mut self, format: PixelFormat) {
+        if format == self.output_format() {
+            return;
+        }
+        self.pack_pass = None;
+        if matches!(format, PixelFormat::Rgb | PixelFormat::Rgba) {
+            return;
+        }
+        if !PackPass::supports(format, self.output_width, self.output_height) {
+            warn!("Can't pack {}x{} frames as {:?} on the GPU, converting on the CPU", self.output_width, self.output_height, format);
+            return;
+        }
+        let mut pack_pass = PackPass::new(&self.context.device, format, YuvColorSpace::default());
+        if let Some(srgb_output_texture) = &self.srgb_output_texture {
+            pack_pass.prepare(&self.context.device, &self.context.queue, srgb_output_texture);
+        }
+        self.pack_pass = Some(pack_pass);
+    }
+
+    pub fn output_format(&self) -> PixelFormat {
+        self.pack_pass.as_ref().map_or(PixelFormat::Rgba, PackPass::format)
+    }
+
+    /// Sets (or removes) the edge decontamination of segmentation composites.
+    /// Only takes effect while segmentation is active.
+    pub fn set_decontamination(&mut self, decontamination: Option<Decontamination>) {
+        self.decontaminate_pass = decontamination.map(|settings| DecontaminatePass::new(&self.context.device, &self.vertex_shader_module, settings));
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current edge decontamination settings.
+    pub fn decontamination(&self) -> Option<Decontamination> {
+        self.decontaminate_pass.as_ref().map(|decontaminate_pass| decontaminate_pass.settings())
+    }
+
+    /// Sets how the camera input is sampled and sharpened.
+    pub fn set_input_filter(&mut self, input_filter: InputFilter) {
+        self.input_sampler = create_input_sampler(&self.context.device, input_filter.sampling);
+        self.sharpen_pass = (input_filter.sharpen > 0.0)
+            .then(|| SharpenPass::new(&self.context.device, &self.vertex_shader_module, input_filter.sharpen));
+        self.input_filter = input_filter;
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    pub fn input_filter(&self) -> InputFilter {
+        self.input_filter
+    }
+
+    /// Sets the crop and transform of the texture slots, in slot order.
+    pub fn set_texture_transforms(&mut self, transforms: &[SourceTransform]) {
+        self.slot_transforms = std::array::from_fn(|i| {
+            transforms.get(i)
+                .filter(|transform| !transform.is_identity())
+                .map(|&transform| SourceTransformPass::new(&self.context.device, &self.vertex_shader_module, transform))
+        });
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Sets (or removes) the blurring of on-screen text in the camera input.
+    /// The detection model is loaded in the background; the whole input is blurred until it
+    /// reports, or for good if it fails to load. Other changes keep the detected regions.
+    pub fn set_text_blur(&mut self, text_blur: Option<TextBlur>) {
+        if self.text_blur() == text_blur.as_ref() {
+            return;
+        }
+        let same_detector = self.text_blur().zip(text_blur.as_ref())
+            .is_some_and(|(old, new)| old.model == new.model && old.threshold == new.threshold);
+        if !same_detector {
+            self.text_detector = text_blur.as_ref().map(|settings| AsyncTextDetector::new(settings.model.clone(), settings.threshold));
+        }
+        let output_size = (self.output_width, self.output_height);
+        let mut text_blur_pass = text_blur.map(|settings| TextBlurPass::new(&self.context.device, &self.vertex_shader_module, settings, output_size));
+        if let (Some(new), Some(old), true) = (&mut text_blur_pass, &self.text_blur_pass, same_detector) {
+            new.continue_from(old);
+        }
+        self.text_blur_pass = text_blur_pass;
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current text blur settings.
+    pub fn text_blur(&self) -> Option<&TextBlur> {
+        self.text_blur_pass.as_ref().map(|text_blur_pass| text_blur_pass.settings())
+    }
+
+    /// Replaces the redaction rules applied to the camera input. Detection models
+    /// are loaded in the background, and the `people` rules start the segmentation
+    /// if no shader uses it; their rules hide the whole frame until the first detections
+    /// arrive, or for good if the model fails to load.
+    pub fn set_redaction(&mut self, rules: Vec<RedactionRule>) {
+        if self.redaction_rules() == rules.as_slice() {
+            return;
+        }
+        let people = rules.iter().any(|rule| rule.source == RedactionSource::People);
+        if people && !self.segmentation_engine.as_ref().is_some_and(|engine| engine.instances()) {
+            info!("Enabling segmentation with instances for redaction");
+            self.segmentation_engine = match crate::ml::AsyncSegmentationEngine::new(true) {
+                Ok(engine) => engine,
+                Err(e) => {
+                    warn!("Failed to start segmentation for redaction: {}", e);
+                    None
+                }
+            };
+        } else if !people && !self.shader_segmentation && !self.particles_need_mask() {
+            self.segmentation_engine = None;
+        }
+
+        // Detectors of unchanged models keep running
+        let mut old_detectors = std::mem::take(&mut self.redaction_detectors);
+        for (index, rule) in rules.iter().enumerate() {
+            let RedactionSource::Model(model) = &rule.source else { continue };
+            let detector = match old_detectors.iter().position(|(_, detector)| detector.model() == model) {
+                Some(position) => old_detectors.swap_remove(position).1,
+                None => AsyncObjectDetector::new(model.clone()),
+            };
+            self.redaction_detectors.push((index, detector));
+        }
+
+        let mut redaction_pass = (!rules.is_empty()).then(|| RedactionPass::new(&self.context.device, &self.vertex_shader_module, rules));
+        if let (Some(new), Some(old)) = (&mut redaction_pass, &self.redaction_pass) {
+            new.continue_from(old);
+        }
+        self.redaction_pass = redaction_pass;
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current redaction rules, as switched with [`Self::set_redaction_rule`].
+    pub fn redaction_rules(&self) -> &[RedactionRule] {
+        self.redaction_pass.as_ref().map_or(&[], |redaction_pass| redaction_pass.rules())
+    }
+
+    /// Switches the redaction rule `name` on (`Some(true)`), off or toggles it (`None`).
+    /// Returns whether it is on now.
+    pub fn set_redaction_rule(&mut self, name: &str, enabled: Option<bool>) -> Result<bool> {
+        self.redaction_pass.as_mut().ok_or_else(|| anyhow!("No redaction rules configured"))?.set_enabled(name, enabled)
+    }
+
+    /// Replaces the static mask regions shaders read from `t_regions` (binding 11).
+    pub fn set_regions(&mut self, regions: Vec<Region>) {
+        if regions == self.regions {
+            return;
+        }
+        let (width, height) = if regions.is_empty() { (1, 1) } else { regions::mask_size(self.output_width, self.output_height) };
+        let mask = regions::render_regions(&regions, width, height);
+        self.regions_texture = Self::create_mask_texture(&self.context.device, "Region Mask", width, height);
+        Self::upload_mask_data(&self.context.queue, &self.regions_texture, &mask, width, height);
+        self.regions = regions;
+        self.cached_width = 0; // Force bind group recreation
+    }
+
+    /// Returns the current mask regions.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Sets the characters of the glyph atlas shaders read from `t_glyphs`
+    /// (binding 12). The atlas is only rendered if a shader uses it.
+    pub fn set_glyphs(&mut self, glyphs: Glyphs) {
+        if glyphs == self.glyphs {
+            return;
+        }
+        self.glyphs = glyphs;
+        let Some(fonts) = &self.glyph_fonts else { return };
+        let Some((atlas, width, height)) = render_glyph_atlas(fonts, &self.glyphs) else {
+            warn!("No font found for the glyph atlas");
+            return;
+        };
+        self.glyphs_texture = Self::create_mask_texture(&self.context.device, "Glyph Atlas", width, height);
+        Self::upload_mask_data(&self.context.queue, &self.glyphs_texture, &atlas, width, height);
+        self.cached_width = 0; // Force bind group recreation
+    }
+
+    /// Returns the current glyph atlas settings.
+    pub fn glyphs(&self) -> &Glyphs {
+        &self.glyphs
+    }
+
+    /// Replaces the data buffers shaders read from `Data0` - `Data3` (bindings 13 - 16).
+    pub fn set_data_buffers(&mut self, buffers: Vec<DataBuffer>) {
+        if buffers == self.data_buffers {
+            return;
+        }
+        if !buffers.is_empty() && !self.data_supported {
+            warn!("Data buffers are not supported by this GPU (no storage buffers in fragment shaders)");
+        }
+        self.data_storage = (0..DATA_SLOTS)
+            .map(|slot| Self::create_data_buffer(&self.context.device, slot, buffers.get(slot).map_or(1, |buffer| buffer.items)))
+            .collect();
+        self.data_buffers = buffers;
+        for slot in 0..self.data_buffers.len() {
+            self.write_data(slot);
+        }
+        self.cached_width = 0; // Force bind group recreation
+    }
+
+    /// Returns the data buffers with their current values.
+    pub fn data_buffers(&self) -> &[DataBuffer] {
+        &self.data_buffers
+    }
+
+    /// Sets the values of the data buffer `name`, 4 per item.
+    pub fn set_data_values(&mut self, name: &str, values: Vec<f32>) -> Result<()> {
+        let slot = self.data_buffers.iter().position(|buffer| buffer.name == name).ok_or_else(|| anyhow!("No data buffer named '{}'", name))?;
+        if self.data_buffers[slot].source != DataSource::Values {
+            return Err(anyhow!("Data buffer '{}' is filled by the pose model", name));
+        }
+        self.data_buffers[slot].values = values;
+        self.write_data(slot);
+        Ok(())
+    }
+
+    /// Uploads the contents of data buffer `slot`.
+    fn write_data(&self, slot: usize) {
+        let Some(buffer) = self.data_buffers.get(slot) else { return };
+        let data = match buffer.source {
+            DataSource::Values => pack_data(&buffer.values, buffer.items),
+            DataSource::Pose => pack_data(bytemuck::cast_slice(&self.pose), buffer.items),
+        };
+        self.context.queue.write_buffer(&self.data_storage[slot], 0, &data);
+    }
+
+    /// Updates the pose keypoints in the Pose block and the data buffers filled with them.
+    fn write_pose(&self) {
+        self.context.queue.write_buffer(&self.pose_buffer, 0, bytemuck::cast_slice(&self.pose));
+        for (slot, buffer) in self.data_buffers.iter().enumerate() {
+            if buffer.source == DataSource::Pose {
+                self.write_data(slot);
+            }
+        }
+    }
+
+    fn create_data_buffer(device: &wgpu::Device, slot: usize, items: u32) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("Data Buffer {}", slot)),
+            size: data_size(items),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Bind group entries of the data buffers (none if the device doesn't support them).
+    fn data_entries(&self) -> Vec<wgpu::BindGroupEntry<'_>> {
+        if !self.data_supported {
+            return Vec::new();
+        }
+        self.data_storage.iter().zip(DATA_BINDING..).map(|(buffer, binding)| wgpu::BindGroupEntry { binding, resource: buffer.as_entire_binding() }).collect()
+    }
+
+    /// Sets (or removes) the pose estimation model. The model is loaded in the
+    /// background; keypoints stay at zero confidence until it is ready.
+    pub fn set_pose_model(&mut self, path: Option<std::path::PathBuf>) {
+        if self.pose_model() == path.as_deref() {
+            return;
+        }
+        self.pose_engine = path.map(AsyncPoseEngine::new);
+        self.pose = [Keypoint::default(); MAX_KEYPOINTS];
+        self.write_pose();
+    }
+
+    /// Returns the current pose estimation model file.
+    pub fn pose_model(&self) -> Option<&std::path::Path> {
+        self.pose_engine.as_ref().map(|pose_engine| pose_engine.path())
+    }
+
+    /// Enables (or disables) QR code detection.
+    pub fn set_qr(&mut self, settings: Option<QrSettings>) {
+        if self.qr() != settings {
+            self.qr_scanner = settings.map(QrScanner::new);
+        }
+    }
+
+    /// Returns the current QR code detection settings.
+    pub fn qr(&self) -> Option<QrSettings> {
+        self.qr_scanner.as_ref().map(|qr_scanner| qr_scanner.settings())
+    }
+
+    /// Passes a camera connect / disconnect to the Lua canvases (`on_device`).
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        let (kind, device) = match event {
+            DeviceEvent::Connected(device) => ("connected", device),
+            DeviceEvent::Disconnected(device) => ("disconnected", device),
+        };
+        for canvas in &mut self.lua_canvases {
+            canvas.on_device(kind, &device.name);
+        }
+    }
+
+    /// Returns the QR codes that came into view since the last call.
+    pub fn take_qr_codes(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.qr_codes)
+    }
+
+    /// Enables (or disables) gesture recognition. Needs a pose model (see `set_pose_model`).
+    pub fn set_gesture_recognition(&mut self, settings: Option<GestureSettings>) {
+        if self.gesture_recognition() != settings {
+            self.gesture_recognizer = settings.map(GestureRecognizer::new);
+        }
+    }
+
+    /// Returns the current gesture recognition settings.
+    pub fn gesture_recognition(&self) -> Option<GestureSettings> {
+        self.gesture_recognizer.as_ref().map(|gesture_recognizer| gesture_recognizer.settings())
+    }
+
+    /// Returns the gestures that triggered since the last call.
+    pub fn take_gestures(&mut self) -> Vec<Gesture> {
+        std::mem::take(&mut self.gestures)
+    }
+
+    /// Sets (or removes) the particle system over the final pass output. The
+    /// mask edge emitter starts the segmentation if no shader uses it. New
+    /// settings with the same particle count apply from the next frame on.
+    pub fn set_particles(&mut self, particles: Option<Particles>) {
+        if particles.is_some() && !ParticlePass::supported(&self.context.adapter) {
+            warn!("Particles are not supported by this GPU (no compute shaders or vertex storage buffers)");
+            return;
+        }
+        match (&mut self.particle_pass, particles) {
+            (Some(particle_pass), Some(settings)) if particle_pass.settings().count == settings.count => particle_pass.set_settings(settings),
+            (_, particles) => {
+                self.particle_pass = particles.map(|settings| ParticlePass::new(&self.context.device, &self.vertex_shader_module, settings));
+                self.cached_width = 0; // Force resource recreation
+            }
+        }
+        if self.particles_need_mask() && self.segmentation_engine.is_none() {
+            info!("Enabling segmentation for the particle emitter");
+            self.segmentation_engine = crate::ml::AsyncSegmentationEngine::new(false).unwrap_or_else(|e| {
+                warn!("Failed to start segmentation for particles: {}", e);
+                None
+            });
+        } else if !self.particles_need_mask() && !self.shader_segmentation
+            && !self.redaction_rules().iter().any(|rule| rule.source == RedactionSource::People) {
+            self.segmentation_engine = None;
+        }
+    }
+
+    /// Returns the current particle system settings.
+    pub fn particles(&self) -> Option<Particles> {
+        self.particle_pass.as_ref().map(|particle_pass| particle_pass.settings().clone())
+    }
+
+    /// Whether the particles are emitted from the segmentation mask.
+    fn particles_need_mask(&self) -> bool {
+        self.particle_pass.as_ref().is_some_and(|particle_pass| particle_pass.settings().emitter == ParticleEmitter::MaskEdge)
+    }
+
+    /// Sets (or removes) the bloom on the final pass output. New settings for
+    /// an existing bloom apply from the next frame on.
+    pub fn set_bloom(&mut self, bloom: Option<Bloom>) {
+        match (&mut self.bloom_pass, bloom) {
+            (Some(bloom_pass), Some(settings)) => bloom_pass.set_settings(settings),
+            (_, bloom) => {
+                self.bloom_pass = bloom.map(|settings| BloomPass::new(&self.context.device, &self.vertex_shader_module, settings));
+                self.cached_width = 0; // Force resource recreation
+            }
+        }
+    }
+
+    /// Returns the current bloom settings.
+    pub fn bloom(&self) -> Option<Bloom> {
+        self.bloom_pass.as_ref().map(|bloom_pass| bloom_pass.settings())
+    }
+
+    /// Sets (or removes) the long-exposure accumulation of the final pass output.
+    /// Changing it starts the accumulation over.
+    pub fn set_accumulation(&mut self, accumulation: Option<Accumulation>) {
+        self.accumulate_pass = accumulation.map(|settings| AccumulatePass::new(&self.context.device, &self.vertex_shader_module, settings));
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current accumulation settings.
+    pub fn accumulation(&self) -> Option<Accumulation> {
+        self.accumulate_pass.as_ref().map(|accumulate_pass| accumulate_pass.settings())
+    }
+
+    /// Sets (or removes) the compositor that places the camera, the output and
+    /// the texture slots in the frame.
+    /// The layout shown stays if the new settings still have it.
+    pub fn set_compositor(&mut self, compositor: Option<Compositor>) {
+        let layout = self.compositor_pass.as_ref().and_then(|compositor_pass| compositor_pass.settings().layout.clone());
+        self.compositor_pass = compositor.map(|mut settings| {
+            if settings.layout.is_none() {
+                settings.layout = layout.filter(|name| settings.layouts.contains_key(name));
+            }
+            CompositorPass::new(&self.context.device, settings, self.hidden.clone())
+        });
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Hides the named compositor layers and, for `WATERMARK_TARGET`, the watermark.
+    /// Everything else is shown.
+    pub fn set_hidden(&mut self, hidden: &BTreeSet<String>) {
+        if *hidden == self.hidden {
+            return;
+        }
+        self.hidden = hidden.clone();
+        if let Some(compositor_pass) = &mut self.compositor_pass {
+            compositor_pass.set_hidden(hidden.clone(), std::time::Instant::now());
+        }
+    }
+
+    /// Switches the compositor to a named layout, animated with its transition.
+    pub fn switch_compositor_layout(&mut self, name: &str) -> Result<()> {
+        let compositor_pass = self.compositor_pass.as_mut().ok_or_else(|| anyhow!("No compositor configured"))?;
+        compositor_pass.switch_layout(name, std::time::Instant::now())
+    }
+
+    /// Returns the current compositor settings.
+    pub fn compositor(&self) -> Option<&Compositor> {
+        self.compositor_pass.as_ref().map(|compositor_pass| compositor_pass.settings())
+    }
+
+    /// Sets (or removes) the cursor and click highlighting. The cursor position
+    /// stays across settings changes.
+    pub fn set_cursor_overlay(&mut self, cursor: Option<CursorOverlay>) {
+        let position = self.cursor_pass.as_ref().and_then(|cursor_pass| cursor_pass.position());
+        self.cursor_pass = cursor.map(|settings| {
+            let mut cursor_pass = CursorPass::new(&self.context.device, &self.vertex_shader_module, settings);
+            cursor_pass.set_position(position);
+            cursor_pass
+        });
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current cursor highlight settings.
+    pub fn cursor_overlay(&self) -> Option<CursorOverlay> {
+        self.cursor_pass.as_ref().map(|cursor_pass| cursor_pass.settings())
+    }
+
+    /// Moves the cursor to a point in 0.0 - 1.0 frame coordinates (`None` when
+    /// it left the frame). The highlight and a zoom that follows the cursor go along.
+    pub fn set_cursor(&mut self, position: Option<[f32; 2]>) {
+        let position = position.map(|[x, y]| [x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)]);
+        if let Some(cursor_pass) = &mut self.cursor_pass {
+            cursor_pass.set_position(position);
+        }
+        if let (Some(zoom_pass), Some([x, y])) = (self.zoom_pass.as_mut().filter(|zoom_pass| zoom_pass.settings().follow_cursor), position) {
+            zoom_pass.set_target(x, y);
+        }
+    }
+
+    /// Shows a click ripple at the cursor.
+    pub fn cursor_click(&mut self) {
+        if let Some(cursor_pass) = &mut self.cursor_pass {
+            cursor_pass.click(std::time::Instant::now());
+        }
+    }
+
+    /// Sets (or removes) the zoom into the final frame. Whether it is zoomed in
+    /// and where stays across settings changes.
+    pub fn set_zoom(&mut self, zoom: Option<Zoom>) {
+        let previous = self.zoom_pass.take();
+        self.zoom_pass = zoom.map(|settings| {
+            let mut zoom_pass = ZoomPass::new(&self.context.device, &self.vertex_shader_module, settings);
+            if let Some(previous) = &previous {
+                zoom_pass.continue_from(previous);
+            }
+            zoom_pass
+        });
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current zoom settings.
+    pub fn zoom(&self) -> Option<Zoom> {
+        self.zoom_pass.as_ref().map(|zoom_pass| zoom_pass.settings())
+    }
+
+    /// Whether it is zoomed in (`None` without a zoom).
+    pub fn zoom_active(&self) -> Option<bool> {
+        self.zoom_pass.as_ref().map(|zoom_pass| zoom_pass.active())
+    }
+
+    /// Zooms in (`Some(true)`), out (`Some(false)`) or toggles (`None`).
+    /// Returns whether it is zoomed in now.
+    pub fn set_zoom_active(&mut self, active: Option<bool>) -> Result<bool> {
+        let zoom_pass = self.zoom_pass.as_mut().ok_or_else(|| anyhow!("No zoom configured"))?;
+        let active = active.unwrap_or(!zoom_pass.active());
+        zoom_pass.set_active(active);
+        Ok(active)
+    }
+
+    /// Sets the point to zoom in on, in 0.0 - 1.0 frame coordinates.
+    pub fn set_zoom_target(&mut self, x: f32, y: f32) {
+        if let Some(zoom_pass) = &mut self.zoom_pass {
+            zoom_pass.set_target(x, y);
+        }
+    }
+
+    /// Sets how the output frame is composed from the pipeline stages.
+    pub fn set_layout(&mut self, layout: OutputLayout) {
+        self.layout_pass = match layout {
+            OutputLayout::Single => None,
+            layout => Some(LayoutPass::new(&self.context.device, &self.vertex_shader_module, layout)),
+        };
+        self.cached_width = 0; // Force resource recreation
+    }
+
+    /// Returns the current output layout.
+    pub fn layout(&self) -> OutputLayout {
+        self.layout_pass.as_ref().map_or(OutputLayout::Single, |layout_pass| layout_pass.layout())
+    }
+
+    /// Sets (or removes) the watermark blended onto the final frame.
+    pub fn set_watermark(&mut self, watermark: Option<Watermark>) -> Result<()> {
+        self.watermark_pass = match watermark {
+            Some(watermark) => Some(WatermarkPass::new(&self.context.device, &self.context.queue, watermark)?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Returns the current watermark settings.
+    pub fn watermark(&self) -> Option<&Watermark> {
+        self.watermark_pass.as_ref().map(|watermark_pass| watermark_pass.settings())
+    }
+
+    /// Shows or hides the flashing warning border of the frame monitor.
+    pub fn set_warning(&mut self, warning: bool) {
+        if warning != self.warning_pass.is_some() {
+            self.warning_pass = warning.then(|| WarningPass::new(&self.context.device));
+        }
+    }
+
+    /// Sets (or removes) the captions blended onto the final frame. A live
+    /// caption stays across settings changes.
+    pub fn set_captions(&mut self, captions: Option<Captions>) -> Result<()> {
+        let previous = self.caption_pass.take();
+        let Some(settings) = captions else { return Ok(()) };
+        // Loading the system fonts takes a moment, so they are kept
+        let fonts = previous.as_ref().map_or_else(|| Arc::new(FontDatabase::new()), |previous| previous.fonts());
+        let mut caption_pass = CaptionPass::new(&self.context.device, settings, fonts)?;
+        if let Some(previous) = &previous {
+            caption_pass.continue_from(previous);
+        }
+        self.caption_pass = Some(caption_pass);
+        Ok(())
+    }
+
+    /// Returns the current caption settings.
+    pub fn captions(&self) -> Option<&Captions> {
+        self.caption_pass.as_ref().map(|caption_pass| caption_pass.settings())
+    }
+
+    /// Shows `text` as a live caption, or removes it.
+    pub fn set_caption(&mut self, text: Option<String>) -> Result<()> {
+        let caption_pass = self.caption_pass.as_mut().ok_or_else(|| anyhow!("No captions configured"))?;
+        caption_pass.set_live(text);
+        Ok(())
+    }
+
+    /// Sets a named shader parameter (a `float` member of the `Params` block at binding 8).
+    /// Values persist across shader reloads; parameters no shader declares are kept but unused.
+    pub fn set_param(&mut self, name: &str, value: f32) {
+        self.params.insert(name.to_string(), value);
+    }
+
+    /// Returns the current parameter values.
+    pub fn params(&self) -> &HashMap<String, f32> {
+        &self.params
+    }
+
+    /// Names of the parameters the current shaders declare, sorted.
+    pub fn param_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.param_layouts.iter().flatten().flat_map(|layout| layout.fields.iter().map(|field| field.name.clone())).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Replaces all parameter values, e.g. to carry them over to a rebuilt pipeline.
+    pub fn set_params(&mut self, params: HashMap<String, f32>) {
+        self.params = params;
+    }
+
+    /// Replaces the global parameter modulators. Modulated parameters override their set values.
+    pub fn set_modulators(&mut self, modulators: Vec<Modulator>) {
+        self.modulators = modulators;
+    }
+
+    /// Returns the global parameter modulators.
+    pub fn modulators(&self) -> &[Modulator] {
+        &self.modulators
+    }
+
+    /// Sets the preset name reported in the recording metadata. The preset's
+    /// values are applied with `set_param`.
+    pub fn set_preset(&mut self, name: Option<String>) {
+        self.preset = name;
+    }
+
+    /// Returns the last applied preset.
+    pub fn preset(&self) -> Option<&str> {
+        self.preset.as_deref()
+    }
+
+    /// State of the pipeline when the frame last returned by `read_output_pipelined`
+    /// was rendered, which is a few frames behind [`Self::frame_metadata`].
+    pub fn read_back_metadata(&self) -> &FrameMetadata {
+        &self.read_back_metadata
+    }
+
+    /// State of the last rendered frame for the recording metadata: the preset,
+    /// the modulated parameter values, and the pose and segmentation results.
+    pub fn frame_metadata(&self) -> FrameMetadata {
+        let mut params = self.params.clone();
+        apply_modulators(&mut params, &self.modulators, self.time, self.clock.beat);
+        FrameMetadata {
+            preset: self.preset.clone(),
+            params: params.into_iter().collect(),
+            pose: self.pose_engine.as_ref().map(|_| self.pose.iter().map(|keypoint| [keypoint.x, keypoint.y, keypoint.score]).collect()),
+            mask_area: self.mask_area,
+            people: self.people,
+        }
+    }
+
+    /// Sets the clock state for the next frame (`beat`, `bar`, and `bpm` uniforms).
+    pub fn set_clock(&mut self, clock: ClockState) {
+        self.clock = clock;
+    }
+
+    /// Sets the wall clock time for the next frame (`clock` uniform).
+    pub fn set_wall_time(&mut self, wall_time: WallTime) {
+        self.wall_time = wall_time;
+    }
+
+    /// Sets the audio levels for the next frame (`audio_peak` and `audio_rms` uniforms).
+    pub fn set_audio_levels(&mut self, levels: AudioLevels) {
+        self.audio = levels;
+    }
+
+    fn create_param_buffer(device: &wgpu::Device, index: usize, layout: &ParamLayout) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("Params Buffer {}", index)),
+            size: layout.size as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Check for shader and image file updates and reload if necessary.
+    fn check_reload(&mut self) {
+        if let Some(changed) = self.texture_watch.poll() {
+            self.reload_images(&changed);
+        }
+
+        if let Some(changed) = self.shader_watch.poll() {
+            info!("Reloading shaders...");
+            
+            // Re-create the pipelines of the passes whose fragment or vertex shader changed
+            for (i, source) in self.shader_sources.iter_mut().enumerate() {
+                // Clone path to release borrow on source so we can mutate it later
+                let path = match source {
+                     ShaderSource::Glsl { path: Some(p), .. } => p.clone(),
+                     ShaderSource::Wgsl { path: Some(p), .. } => p.clone(),
+                     _ => continue,
+                };
+                let vertex_changed = self.pass_options.get(i).and_then(|options| options.vertex.as_ref()).is_some_and(|vertex| match vertex {
+                    ShaderSource::Glsl { path, .. } | ShaderSource::Wgsl { path, .. } => path.as_ref().is_some_and(|path| changed.contains(path)),
+                });
+                if !changed.contains(&path) && !vertex_changed {
+                    continue;
+                }
+                
+                // Read file
+                let code = match std::fs::read_to_string(&path) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        self.shader_watch.report(&path, Err(anyhow!("Failed to read shader file: {}", e)));
+                        continue;
+                    }
+                };
+                
+                // Update source in memory
+                match source {
+                    ShaderSource::Glsl { code: c, .. } => *c = code.clone(),
+                    ShaderSource::Wgsl { code: c, .. } => *c = code.clone(),
+                }
+                
+                // Compile
+                // Compile and detect capabilities
+                let (fragment_wgsl, fragment_entry_point, shader_info) = match source {
+                     ShaderSource::Glsl { code: glsl, .. } => {
+                         match Self::cached_glsl_to_wgsl(&self.context, glsl) {
+                             Ok((wgsl, shader_info)) => (wgsl, "main", shader_info),
+                             Err(e) => {
+                                 self.shader_watch.report(&path, Err(anyhow!("GLSL compilation error: {}", e)));
+                                 continue;
+                             }
+                         }
+                     }
+                     ShaderSource::Wgsl { code: wgsl, .. } => {
+                         (wgsl.clone(), "fs_main", Self::inspect_wgsl(wgsl))
+                     }
+                };
+                let outputs_mask = shader_info.outputs_mask;
+
+                let fragment_module = self.context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(&format!("Fragment Shader {}", i)),
+                    source: wgpu::ShaderSource::Wgsl(Cow::Owned(fragment_wgsl)),
+                });
+
+                // The pass's vertex shader may have changed as well
+                if let Some(vertex) = self.pass_options.get_mut(i).and_then(|options| options.vertex.as_mut()) {
+                    let vertex_path = match vertex {
+                        ShaderSource::Glsl { path, .. } | ShaderSource::Wgsl { path, .. } => path.clone(),
+                    };
+                    if let Some(vertex_path) = vertex_path {
+                        match std::fs::read_to_string(&vertex_path) {
+                            Ok(vertex_code) => match vertex {
+                                ShaderSource::Glsl { code, .. } | ShaderSource::Wgsl { code, .. } => *code = vertex_code,
+                            },
+                            Err(e) => tracing::warn!("Failed to read vertex shader {:?}: {}", vertex_path, e),
+                        }
+                    }
+                    match Self::compile_vertex_shader(&self.context.device, i, vertex) {
+                        Ok(vertex) => self.vertex_shaders[i] = Some(vertex),
+                        Err(e) => {
+                            self.shader_watch.report(&path, Err(anyhow!("Vertex shader error: {}", e)));
+                            continue;
+                        }
+                    }
+                }
+
+                let vertex_shader = self.vertex_shaders.get(i).and_then(Option::as_ref);
+                let vertex = vertex_shader.map_or((&self.vertex_shader_module, SHADER_VERTEX_ENTRY), |vertex| (&vertex.module, vertex.entry_point));
+                let params = shader_info.params.clone().or_else(|| vertex_shader.and_then(|vertex| vertex.params.clone()));
+                let render_pipeline = Self::create_pass_pipeline(&self.context, &self.pipeline_layout, i, vertex, (&fragment_module, fragment_entry_point), outputs_mask);
+                
+                // Replace pipeline
+                if i < self.render_pipelines.len() {
+                    self.render_pipelines[i] = render_pipeline;
+                    if i < self.pipeline_mask_outputs.len() {
+                        self.pipeline_mask_outputs[i] = outputs_mask;
+                    }
+                    if i < self.param_layouts.len() && self.param_layouts[i] != params {
+                        self.param_buffers[i] = params.as_ref()
+                            .map(|layout| Self::create_param_buffer(&self.context.device, i, layout));
+                        self.param_layouts[i] = params;
+                    }
+                    self.shader_watch.report(&path, Ok(()));
+                }
+            }
+            self.context.save_pipeline_cache();
+            // Force resource update to align bind groups and output textures
+            self.cached_width = 0;
+        }
+    }
+
+    /// Reloads the image textures of the changed files, keeping the old image on errors.
+    fn reload_images(&mut self, changed: &[std::path::PathBuf]) {
+        for (i, path) in self.image_paths.iter().enumerate() {
+            let Some(path) = path.as_ref().filter(|path| changed.contains(path)) else { continue };
+            match Self::load_image_texture(&self.context.device, &self.context.queue, i, path) {
+                Ok(texture) => {
+                    self.image_textures[i] = texture;
+                    self.texture_watch.report(path, Ok(()));
+                }
+                Err(e) => self.texture_watch.report(path, Err(e)),
+            }
+        }
+        // Recreate the bind groups with the new textures
+        self.cached_width = 0;
+    }
+
+    /// Update or create cached textures/buffers if dimensions changed
+    fn ensure_resources(&mut self, width: u32, height: u32, mask_w: u32, mask_h: u32) -> Result<()> {
+        // Calculate render resolution: cap at output resolution, but don't exceed input resolution.
+        // This avoids processing at 4k if input is 1080p (even if output is 4k), or processing at 4k if output is 1080p (even if input is 4k).
+        let render_width = std::cmp::min(self.output_width, width);
+        let render_height = std::cmp::min(self.output_height, height);
+
+        if self.cached_width == width && self.cached_height == height 
+           && self.cached_mask_width == mask_w && self.cached_mask_height == mask_h 
+           && !self.output_textures.is_empty() 
+           && self.output_textures[0].width() == self.pass_size(0, render_width, render_height).0 {
+            return Ok(());
+        }
+
+        info!("Creating GPU resources (Input: {}x{}, Render: {}x{}, Output: {}x{}, Mask: {}x{})", 
+              width, height, render_width, render_height, self.output_width, self.output_height, mask_w, mask_h);
+        
+        // 1. Mask and Instance Textures (Create if size changed)
+        if self.cached_mask_width != mask_w || self.cached_mask_height != mask_h {
+            self.mask_texture = Self::create_mask_texture(&self.context.device, "Segmentation Mask", mask_w, mask_h);
+            self.instances_texture = Self::create_mask_texture(&self.context.device, "Person Instances", mask_w, mask_h);
+        }
+
+        // 2. Input Texture, with a full mip chain for filtered downscaling
+        let input_size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        self.input_texture = Some(self.context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Input Texture"),
+            size: input_size,
+            mip_level_count: input_size.max_mips(wgpu::TextureDimension::D2),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            // Render attachment for the GPU YUV conversion
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Read gamma encoded by Lua canvases (canvas.draw_camera)
+            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+        }));
+        self.mipmap_pass.prepare(&self.context.device, self.input_texture.as_ref().unwrap());
+
+        // 2. Output Textures - Use Rgba16Float for all passes to maintain precision
+        self.output_textures.clear();
+        let num_pipelines = self.render_pipelines.len();
+        for i in 0..num_pipelines {
+            let (pass_width, pass_height) = self.pass_size(i, render_width, render_height);
+            if pass_width != render_width || pass_height != render_height {
+                info!("Pass {} renders at {}x{}", i, pass_width, pass_height);
+            }
+            let tex = self.context.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some(&format!("Intermediate Texture {}", i)),
-                size: wgpu::Extent3d { width: render_width, height: render_height, depth_or_array_layers: 1 },
+                size: wgpu::Extent3d { width: pass_width, height: pass_height, depth_or_array_layers: 1 },
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
@@ -766,6 +1956,8 @@ impl WgpuPipeline {
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         }));
+        // Frames still in flight are dropped with the old buffers
+        self.readback_ring = Some(ReadbackRing::new(&self.context.device, size));
 
         // sRGB Output Texture (For Readback)
         self.srgb_output_texture = Some(self.context.device.create_texture(&wgpu::TextureDescriptor {
@@ -776,17 +1968,23 @@ impl WgpuPipeline {
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
+            // Read gamma encoded by the packing pass
+            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
         }));
+        if let (Some(pack_pass), Some(srgb_output_texture)) = (&mut self.pack_pass, &self.srgb_output_texture) {
+            pack_pass.prepare(&self.context.device, &self.context.queue, srgb_output_texture);
+        }
 
         // 3. Mask Targets (For shaders that output mask)
         
         // Re-do mask targets logic: strict one-to-one mapping
+        // Mask targets must match the size of their pass's color target.
         self.mask_targets = (0..self.render_pipelines.len()).map(|i| {
              if self.pipeline_mask_outputs.get(i).copied().unwrap_or(false) {
+                 let (pass_width, pass_height) = self.pass_size(i, render_width, render_height);
                  Some(self.context.device.create_texture(&wgpu::TextureDescriptor {
                     label: Some(&format!("Intermediate Mask Texture {}", i)),
-                    size: wgpu::Extent3d { width: render_width, height: render_height, depth_or_array_layers: 1 },
+                    size: wgpu::Extent3d { width: pass_width, height: pass_height, depth_or_array_layers: 1 },
                     mip_level_count: 1,
                     sample_count: 1,
                     dimension: wgpu::TextureDimension::D2,
@@ -800,9 +1998,39 @@ impl WgpuPipeline {
         }).collect();
 
 
+        // Blur text, then refine the camera input and mask before the first pass
+        if let Some(text_blur_pass) = &mut self.text_blur_pass {
+            text_blur_pass.prepare(&self.context.device, &self.sampler, self.input_texture.as_ref().unwrap());
+        }
+        let camera = self.text_blur_pass.as_ref()
+            .and_then(|text_blur_pass| text_blur_pass.texture())
+            .unwrap_or(self.input_texture.as_ref().unwrap());
+        if let Some(redaction_pass) = &mut self.redaction_pass {
+            redaction_pass.prepare(&self.context.device, &self.sampler, camera);
+        }
+        let camera = self.redaction_pass.as_ref()
+            .and_then(|redaction_pass| redaction_pass.texture())
+            .unwrap_or(camera);
+        let segmentation = self.segmentation_engine.is_some();
+        if let Some(decontaminate_pass) = self.decontaminate_pass.as_mut().filter(|_| segmentation) {
+            decontaminate_pass.prepare(&self.context.device, &self.sampler, camera, &self.mask_texture);
+        }
+        let refined = self.decontaminate_pass.as_ref()
+            .filter(|_| segmentation)
+            .and_then(|decontaminate_pass| decontaminate_pass.textures());
+        let first_input = refined.map_or(camera, |(color, _)| color);
+        let (pass_width, pass_height) = self.pass_size(0, render_width, render_height);
+        if let Some(sharpen_pass) = &mut self.sharpen_pass {
+            sharpen_pass.prepare(&self.context.device, &self.input_sampler, first_input, pass_width, pass_height);
+        }
+        let first_input = self.sharpen_pass.as_ref()
+            .and_then(|sharpen_pass| sharpen_pass.texture())
+            .unwrap_or(first_input);
+        let camera_view = first_input.create_view(&wgpu::TextureViewDescriptor::default());
+
         // 4. Bind Groups
         self.bind_groups.clear();
-        let initial_mask_view = self.mask_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let initial_mask_view = refined.map_or(&self.mask_texture, |(_, mask)| mask).create_view(&wgpu::TextureViewDescriptor::default());
         
         let mask_target_views: Vec<Option<wgpu::TextureView>> = self.mask_targets.iter().map(|t| {
              t.as_ref().map(|tex| tex.create_view(&wgpu::TextureViewDescriptor::default()))
@@ -811,36 +2039,67 @@ impl WgpuPipeline {
         let image_views: [wgpu::TextureView; 4] = std::array::from_fn(|i| {
                 self.image_textures[i].create_view(&wgpu::TextureViewDescriptor::default())
         });
+        // Each texture slot shows either its Lua canvas or its image / video, transformed if configured
+        let source_views: [&wgpu::TextureView; 4] = std::array::from_fn(|i| self.lua_canvas_views[i].as_ref().unwrap_or(&image_views[i]));
+        let mut transformed_views: [Option<wgpu::TextureView>; 4] = [None, None, None, None];
+        for (i, slot_transform) in self.slot_transforms.iter_mut().enumerate() {
+            let Some(slot_transform) = slot_transform else { continue };
+            let (width, height) = match self.lua_slot_map[i].filter(|_| self.lua_canvas_views[i].is_some()) {
+                Some(canvas) => (self.lua_canvases[canvas].width, self.lua_canvases[canvas].height),
+                None => (self.image_textures[i].width(), self.image_textures[i].height()),
+            };
+            slot_transform.prepare(&self.context.device, &self.context.queue, &self.sampler, source_views[i], width, height);
+            transformed_views[i] = slot_transform.texture().map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        }
+        let slot_views: [&wgpu::TextureView; 4] = std::array::from_fn(|i| transformed_views[i].as_ref().unwrap_or(source_views[i]));
+
+        // Compose the sources into the input of the first pass
+        if let Some(compositor_pass) = self.compositor_pass.as_mut().filter(|compositor_pass| compositor_pass.stage() == CompositorStage::Before) {
+            let sources = CompositorSources { camera: &camera_view, output: &camera_view, slots: slot_views };
+            compositor_pass.prepare(&self.context.device, &self.sampler, &sources, render_width, render_height);
+        }
+        let first_input = self.compositor_pass.as_ref()
+            .filter(|compositor_pass| compositor_pass.stage() == CompositorStage::Before)
+            .and_then(|compositor_pass| compositor_pass.texture())
+            .unwrap_or(first_input);
         
+        let instances_view = self.instances_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let regions_view = self.regions_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let glyphs_view = self.glyphs_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
         // Track which mask to bind. Start with the ML mask.
         let mut current_mask_view = &initial_mask_view;
 
         for i in 0..self.render_pipelines.len() {
             let input_view = if i == 0 {
-                self.input_texture.as_ref().unwrap().create_view(&wgpu::TextureViewDescriptor::default())
+                first_input.create_view(&wgpu::TextureViewDescriptor::default())
             } else {
                 self.output_textures[i-1].create_view(&wgpu::TextureViewDescriptor::default())
             };
 
             let mut entries = vec![
                 wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
-                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(if i == 0 { &self.input_sampler } else { &self.sampler }) },
                 wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
                 wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(current_mask_view) },
+                wgpu::BindGroupEntry { binding: INSTANCES_BINDING, resource: wgpu::BindingResource::TextureView(&instances_view) },
+                wgpu::BindGroupEntry { binding: POSE_BINDING, resource: self.pose_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: REGIONS_BINDING, resource: wgpu::BindingResource::TextureView(&regions_view) },
+                wgpu::BindGroupEntry { binding: GLYPHS_BINDING, resource: wgpu::BindingResource::TextureView(&glyphs_view) },
             ];
 
-            // Add the 4 texture slots. For each, use either the Lua canvas view or the image view.
-            for j in 0..4 {
-                let view = if let Some(view) = self.lua_canvas_views[j].as_ref() {
-                    view
-                } else {
-                    &image_views[j]
-                };
+            // Passes without a Params block get the main uniform buffer as a placeholder
+            let params_buffer = self.param_buffers.get(i).and_then(|b| b.as_ref()).unwrap_or(&self.uniform_buffer);
+            entries.push(wgpu::BindGroupEntry { binding: PARAMS_BINDING, resource: params_buffer.as_entire_binding() });
+
+            // Add the 4 texture slots
+            for (j, view) in slot_views.iter().enumerate() {
                 entries.push(wgpu::BindGroupEntry { 
                     binding: (4 + j) as u32, 
                     resource: wgpu::BindingResource::TextureView(view) 
                 });
             }
+            entries.extend(self.data_entries());
 
             let bind_group = self.context.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some(&format!("Bind Group {}", i)),
@@ -853,23 +2112,106 @@ impl WgpuPipeline {
                 current_mask_view = view;
             }
         }
-        
+        
+        // Add the particles to the final pass output (or the input, without passes)
+        if let Some(particle_pass) = &mut self.particle_pass {
+            let source = self.output_textures.last().or(self.input_texture.as_ref()).unwrap();
+            let mask = refined.map_or(&self.mask_texture, |(_, mask)| mask);
+            particle_pass.prepare(&self.context.device, &self.sampler, source, first_input, mask);
+        }
+
+        // Add the glow to the final pass output
+        if let Some(bloom_pass) = &mut self.bloom_pass {
+            let source = self.particle_pass.as_ref()
+                .and_then(|particle_pass| particle_pass.texture())
+                .or(self.output_textures.last())
+                .or(self.input_texture.as_ref())
+                .unwrap();
+            bloom_pass.prepare(&self.context.device, &self.sampler, source);
+        }
+
+        // Accumulate the final pass output (or the input, without passes)
+        if let Some(accumulate_pass) = &mut self.accumulate_pass {
+            let source = self.bloom_pass.as_ref()
+                .and_then(|bloom_pass| bloom_pass.texture())
+                .or(self.particle_pass.as_ref().and_then(|particle_pass| particle_pass.texture()))
+                .or(self.output_textures.last())
+                .or(self.input_texture.as_ref())
+                .unwrap();
+            accumulate_pass.prepare(&self.context.device, source);
+        }
+
+        // Compose the sources with the final output
+        if let Some(compositor_pass) = self.compositor_pass.as_mut().filter(|compositor_pass| compositor_pass.stage() == CompositorStage::After) {
+            let output = self.accumulate_pass.as_ref()
+                .and_then(|accumulate_pass| accumulate_pass.texture())
+                .or(self.bloom_pass.as_ref().and_then(|bloom_pass| bloom_pass.texture()))
+                .or(self.particle_pass.as_ref().and_then(|particle_pass| particle_pass.texture()))
+                .or(self.output_textures.last())
+                .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+            let sources = CompositorSources { camera: &camera_view, output: output.as_ref().unwrap_or(&camera_view), slots: slot_views };
+            compositor_pass.prepare(&self.context.device, &self.sampler, &sources, render_width, render_height);
+        }
+
+        // Highlight the cursor on the composed (or accumulated, or final pass) frame
+        if self.cursor_pass.is_some() {
+            let source = self.composed_texture().or(self.input_texture.as_ref()).unwrap().create_view(&wgpu::TextureViewDescriptor::default());
+            if let Some(cursor_pass) = &mut self.cursor_pass {
+                cursor_pass.prepare(&self.context.device, &self.sampler, &source, render_width, render_height);
+            }
+        }
+
+        // Zoom into the highlighted (or composed) frame
+        if self.zoom_pass.is_some() {
+            let source = self.cursor_pass.as_ref()
+                .and_then(|cursor_pass| cursor_pass.texture())
+                .or(self.composed_texture())
+                .or(self.input_texture.as_ref())
+                .unwrap()
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            if let Some(zoom_pass) = &mut self.zoom_pass {
+                zoom_pass.prepare(&self.context.device, &self.sampler, &source, render_width, render_height);
+            }
+        }
+
+        // Compose the layout from the input, all pass outputs, the bloom, the accumulated, the composed, the highlighted and the zoomed frame
+        if let Some(layout_pass) = &mut self.layout_pass {
+            let stages: Vec<wgpu::TextureView> = self.input_texture.iter()
+                .chain(&self.output_textures)
+                .chain(self.particle_pass.as_ref().and_then(|particle_pass| particle_pass.texture()))
+                .chain(self.bloom_pass.as_ref().and_then(|bloom_pass| bloom_pass.texture()))
+                .chain(self.accumulate_pass.as_ref().and_then(|accumulate_pass| accumulate_pass.texture()))
+                .chain(self.compositor_pass.as_ref().filter(|compositor_pass| compositor_pass.stage() == CompositorStage::After).and_then(|compositor_pass| compositor_pass.texture()))
+                .chain(self.cursor_pass.as_ref().and_then(|cursor_pass| cursor_pass.texture()))
+                .chain(self.zoom_pass.as_ref().and_then(|zoom_pass| zoom_pass.texture()))
+                .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+                .collect();
+            layout_pass.prepare(&self.context.device, &self.sampler, &stages, render_width, render_height);
+        }
+
         // Create sRGB Bind Group (Reuse layout, bind final output as input)
-        if let Some(final_output) = self.output_textures.last() {
+        if let Some(final_output) = self.output_texture() {
              let input_view = final_output.create_view(&wgpu::TextureViewDescriptor::default());
+             let mut entries = vec![
+                 wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
+                 wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                 wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+                 wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&initial_mask_view) }, // Dummy
+                 wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&image_views[0]) }, // Dummy
+                 wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&image_views[1]) }, // Dummy
+                 wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&image_views[2]) }, // Dummy
+                 wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&image_views[3]) }, // Dummy
+                 wgpu::BindGroupEntry { binding: PARAMS_BINDING, resource: self.uniform_buffer.as_entire_binding() }, // Dummy
+                 wgpu::BindGroupEntry { binding: INSTANCES_BINDING, resource: wgpu::BindingResource::TextureView(&instances_view) }, // Dummy
+                 wgpu::BindGroupEntry { binding: POSE_BINDING, resource: self.pose_buffer.as_entire_binding() }, // Dummy
+                 wgpu::BindGroupEntry { binding: REGIONS_BINDING, resource: wgpu::BindingResource::TextureView(&regions_view) }, // Dummy
+                 wgpu::BindGroupEntry { binding: GLYPHS_BINDING, resource: wgpu::BindingResource::TextureView(&glyphs_view) }, // Dummy
+             ];
+             entries.extend(self.data_entries()); // Dummies
              let srgb_bind_group = self.context.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("sRGB Blit Bind Group"),
                 layout: &self.bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&input_view) },
-                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
-                    wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
-                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&initial_mask_view) }, // Dummy
-                    wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::TextureView(&image_views[0]) }, // Dummy
-                    wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&image_views[1]) }, // Dummy
-                    wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&image_views[2]) }, // Dummy
-                    wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::TextureView(&image_views[3]) }, // Dummy
-                ],
+                entries: &entries,
             });
             self.srgb_bind_group = Some(srgb_bind_group);
         }
@@ -881,6 +2223,39 @@ impl WgpuPipeline {
         Ok(())
     }
 
+    /// Returns the render target size of pass `index`, applying its resolution scale.
+    fn pass_size(&self, index: usize, render_width: u32, render_height: u32) -> (u32, u32) {
+        let scale = self.pass_options.get(index).map(|o| o.scale).unwrap_or(1.0).clamp(0.01, 1.0);
+        (
+            ((render_width as f32 * scale).round() as u32).max(1),
+            ((render_height as f32 * scale).round() as u32).max(1),
+        )
+    }
+
+    /// Loads an image file into a texture.
+    fn load_image_texture(device: &wgpu::Device, queue: &wgpu::Queue, index: usize, path: &std::path::Path) -> Result<wgpu::Texture> {
+        let rgba = image::open(path).map_err(|e| anyhow!("Failed to load image {:?}: {}", path, e))?.to_rgba8();
+        let (w, h) = rgba.dimensions();
+        info!("Loaded image {} from {:?} ({}x{})", index, path, w, h);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("Image Texture {}", index)),
+            size: wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo { texture: &texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            &rgba,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(w * 4), rows_per_image: Some(h) },
+            wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+        );
+        Ok(texture)
+    }
+
     /// Creates a 1x1 black RGBA texture as fallback for missing image inputs.
     fn create_black_texture(device: &wgpu::Device, queue: &wgpu::Queue, index: usize) -> wgpu::Texture {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -902,13 +2277,154 @@ impl WgpuPipeline {
         texture
     }
 
-    /// Inspect WGSL and return (uses_mask_binding, outputs_mask_location_1).
-    fn inspect_wgsl(wgsl: &str) -> (bool, bool) {
+    /// Creates a single-channel texture for mask-resolution data uploaded from the CPU.
+    fn create_mask_texture(device: &wgpu::Device, label: &str, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    /// Uploads tightly packed single-channel data, padding rows to wgpu's alignment.
+    fn upload_mask_data(queue: &wgpu::Queue, texture: &wgpu::Texture, data: &[u8], w: u32, h: u32) {
+        let align_mask = 255;
+        let padded_width = (w as usize + align_mask) & !align_mask;
+        let upload_data = if padded_width == w as usize {
+            std::borrow::Cow::Borrowed(data)
+        } else {
+            let mut aligned = vec![0u8; padded_width * h as usize];
+            for y in 0..h as usize {
+                    let src = y * w as usize;
+                    let dst = y * padded_width;
+                    aligned[dst..dst + w as usize].copy_from_slice(&data[src..src+w as usize]);
+            }
+            std::borrow::Cow::Owned(aligned)
+        };
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo { texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+            &upload_data,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(padded_width as u32), rows_per_image: Some(h) },
+            wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+        );
+    }
+
+    /// Creates the render pipeline of user shader pass `index`, with a second
+    /// target if the fragment shader outputs a mask (location 1).
+    fn create_pass_pipeline(
+        context: &GpuContext,
+        layout: &wgpu::PipelineLayout,
+        index: usize,
+        vertex: (&wgpu::ShaderModule, &str),
+        fragment: (&wgpu::ShaderModule, &str),
+        outputs_mask: bool,
+    ) -> wgpu::RenderPipeline {
+        // Use Rgba16Float for all passes to maintain precision
+        let mut targets = vec![Some(wgpu::ColorTargetState {
+            format: wgpu::TextureFormat::Rgba16Float,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        if outputs_mask {
+            targets.push(Some(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::R8Unorm,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }));
+        }
+
+        context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("Render Pipeline {}", index)),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: vertex.0,
+                entry_point: Some(vertex.1),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: fragment.0,
+                entry_point: Some(fragment.1),
+                targets: &targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: context.pipeline_cache(),
+        })
+    }
+
+    /// Compiles the custom vertex shader of pass `index`.
+    fn compile_vertex_shader(device: &wgpu::Device, index: usize, source: &ShaderSource) -> Result<VertexShader> {
+        let (wgsl, module) = Self::parse_vertex_shader(source)?;
+        let entry_point = match source {
+            ShaderSource::Glsl { .. } => "main",
+            ShaderSource::Wgsl { .. } => "vs_main",
+        };
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("Vertex Shader {}", index)),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(wgsl)),
+        });
+        Ok(VertexShader { module: shader_module, entry_point, params: ParamLayout::reflect(&module) })
+    }
+
+    /// Checks that a custom vertex shader compiles, without creating any GPU resources.
+    pub fn check_vertex_shader(shader: &ShaderSource) -> Result<()> {
+        Self::parse_vertex_shader(shader).map(|_| ())
+    }
+
+    /// Parses and validates a custom vertex shader. Returns it as WGSL along
+    /// with the parsed module. GLSL shaders use `main`, WGSL shaders `vs_main`.
+    fn parse_vertex_shader(shader: &ShaderSource) -> Result<(String, naga::Module)> {
+        match shader {
+            ShaderSource::Glsl { code, .. } => {
+                let glsl = Self::expand_prelude(code, GLSL_VERTEX_PRELUDE);
+                let mut frontend = Frontend::default();
+                let options = Options::from(ShaderStage::Vertex);
+                let module = frontend.parse(&options, &glsl).map_err(|e| anyhow!("GLSL parse error: {:?}", e))?;
+                Ok((Self::write_wgsl(&module)?, module))
+            }
+            ShaderSource::Wgsl { code, .. } => {
+                let module = naga::front::wgsl::parse_str(code).map_err(|e| anyhow!("WGSL parse error: {}", e))?;
+                if !module.entry_points.iter().any(|entry| entry.stage == ShaderStage::Vertex && entry.name == "vs_main") {
+                    return Err(anyhow!("WGSL vertex shaders need a `vs_main` entry point"));
+                }
+                let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+                validator.validate(&module).map_err(|e| anyhow!("Shader validation error: {:?}", e))?;
+                Ok((code.clone(), module))
+            }
+        }
+    }
+
+    /// Inspect WGSL and detect which optional bindings and outputs it uses.
+    fn inspect_wgsl(wgsl: &str) -> ShaderInfo {
         match naga::front::wgsl::parse_str(wgsl) {
             Ok(module) => {
                 let uses_mask = module.global_variables.iter().any(|(_, var)| {
                     matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: 3 }))
                 });
+                let uses_instances = module.global_variables.iter().any(|(_, var)| {
+                    matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: INSTANCES_BINDING }))
+                });
+                let uses_glyphs = module.global_variables.iter().any(|(_, var)| {
+                    matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: GLYPHS_BINDING }))
+                });
                 
                 // Check if fragment output has Location(1)
                 let mut outputs_mask = false;
@@ -922,23 +2438,81 @@ impl WgpuPipeline {
                      }
                 }
                 
-                (uses_mask, outputs_mask)
+                ShaderInfo { uses_mask, uses_instances, uses_glyphs, outputs_mask, params: ParamLayout::reflect(&module) }
+            }
+            Err(_) => ShaderInfo { uses_mask: false, uses_instances: false, uses_glyphs: false, outputs_mask: false, params: None },
+        }
+    }
+
+    /// Checks that a shader compiles, without creating any GPU resources.
+    pub fn check_shader(shader: &ShaderSource) -> Result<()> {
+        match shader {
+            ShaderSource::Glsl { code, .. } => Self::glsl_to_wgsl(code).map(|_| ()),
+            ShaderSource::Wgsl { code, .. } => {
+                let module = naga::front::wgsl::parse_str(code).map_err(|e| anyhow!("WGSL parse error: {}", e))?;
+                let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+                validator.validate(&module).map_err(|e| anyhow!("Shader validation error: {:?}", e))?;
+                Ok(())
             }
-            Err(_) => (false, false), 
         }
     }
 
+    /// Replaces an `#include <proteus>` line with the standard declarations.
+    fn expand_prelude<'a>(glsl: &'a str, prelude: &'a str) -> Cow<'a, str> {
+        if !glsl.lines().any(|line| line.trim() == GLSL_PRELUDE_INCLUDE) {
+            return Cow::Borrowed(glsl);
+        }
+        let lines: Vec<&str> = glsl
+            .lines()
+            .map(|line| if line.trim() == GLSL_PRELUDE_INCLUDE { prelude } else { line })
+            .collect();
+        Cow::Owned(lines.join("\n"))
+    }
+
+    /// Like `glsl_to_wgsl`, but reuses the conversion from the shader cache.
+    /// The GLSL is still parsed for reflection, as the WGSL writer renames some
+    /// identifiers (e.g. parameters ending in a digit).
+    fn cached_glsl_to_wgsl(context: &GpuContext, glsl: &str) -> Result<(String, ShaderInfo)> {
+        let Some(cache) = context.shader_cache() else { return Self::glsl_to_wgsl(glsl) };
+        let (module, shader_info) = Self::parse_glsl(glsl)?;
+        if let Some(wgsl) = cache.load_wgsl(glsl) {
+            return Ok((wgsl, shader_info));
+        }
+        let wgsl = Self::write_wgsl(&module)?;
+        cache.store_wgsl(glsl, &wgsl);
+        Ok((wgsl, shader_info))
+    }
+
     /// Converts GLSL fragment shader to WGSL.
-    /// Returns (wgsl_source, uses_mask_binding, outputs_mask_location_1)
-    fn glsl_to_wgsl(glsl: &str) -> Result<(String, bool, bool)> {
+    /// Returns the WGSL source and the detected shader capabilities.
+    fn glsl_to_wgsl(glsl: &str) -> Result<(String, ShaderInfo)> {
+        let (module, shader_info) = Self::parse_glsl(glsl)?;
+        Ok((Self::write_wgsl(&module)?, shader_info))
+    }
+
+    /// Validates a module and writes it as WGSL.
+    fn write_wgsl(module: &naga::Module) -> Result<String> {
+        let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+        let info = validator.validate(module).map_err(|e| anyhow!("Shader validation error: {:?}", e))?;
+        naga::back::wgsl::write_string(module, &info, naga::back::wgsl::WriterFlags::empty()).map_err(|e| anyhow!("WGSL generation error: {:?}", e))
+    }
+
+    fn parse_glsl(glsl: &str) -> Result<(naga::Module, ShaderInfo)> {
+        let glsl = Self::expand_prelude(glsl, GLSL_PRELUDE);
         let mut frontend = Frontend::default();
         let options = Options::from(ShaderStage::Fragment);
-        let module = frontend.parse(&options, glsl).map_err(|e| anyhow!("GLSL parse error: {:?}", e))?;
+        let module = frontend.parse(&options, &glsl).map_err(|e| anyhow!("GLSL parse error: {:?}", e))?;
         
         // Check if shader uses binding 3 (t_mask) via naga reflection
         let uses_mask = module.global_variables.iter().any(|(_, var)| {
             matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: 3 }))
         });
+        let uses_instances = module.global_variables.iter().any(|(_, var)| {
+            matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: INSTANCES_BINDING }))
+        });
+        let uses_glyphs = module.global_variables.iter().any(|(_, var)| {
+            matches!(var.binding, Some(naga::ResourceBinding { group: 0, binding: GLYPHS_BINDING }))
+        });
         
          // Check if fragment output has Location(1)
         let mut outputs_mask = false;
@@ -952,10 +2526,8 @@ impl WgpuPipeline {
              }
         }
         
-        let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
-        let info = validator.validate(&module).map_err(|e| anyhow!("Shader validation error: {:?}", e))?;
-        let wgsl = naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty()).map_err(|e| anyhow!("WGSL generation error: {:?}", e))?;
-        Ok((wgsl, uses_mask, outputs_mask))
+        let params = ParamLayout::reflect(&module);
+        Ok((module, ShaderInfo { uses_mask, uses_instances, uses_glyphs, outputs_mask, params }))
     }
 
     pub fn device_and_queue(&self) -> (&wgpu::Device, &wgpu::Queue) { (&self.context.device, &self.context.queue) }
@@ -964,74 +2536,307 @@ impl WgpuPipeline {
     pub fn buffers(&self) -> (&wgpu::Buffer, &wgpu::Buffer) { (&self.vertex_buffer, &self.index_buffer) }
     pub fn sampler(&self) -> &wgpu::Sampler { &self.sampler }
 
+    /// Frame statistics. The pipeline counts the ML and render stages; whoever
+    /// feeds it counts capture and output.
+    pub fn stats(&self) -> &PipelineStats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut PipelineStats {
+        &mut self.stats
+    }
+
+    /// Continues the statistics of a previous pipeline.
+    pub fn set_stats(&mut self, stats: PipelineStats) {
+        self.stats = stats;
+    }
+
+    /// Takes over the runtime state of the pipeline this one replaces (after a
+    /// shader or texture reload): parameters, preset, the effect settings, the
+    /// live caption, cursor and zoom position, data buffer values and statistics.
+    /// Effects that fail to start again are disabled with an error.
+    pub fn inherit_state(&mut self, old: &WgpuPipeline) {
+        self.set_params(old.params().clone());
+        self.set_preset(old.preset().map(String::from));
+        self.set_modulators(old.modulators().to_vec());
+        self.set_layout(old.layout());
+        if let Err(e) = self.set_watermark(old.watermark().cloned()) {
+            error!("Watermark disabled: {}", e);
+        }
+        self.caption_pass = match &old.caption_pass {
+            Some(old_pass) => match CaptionPass::new(&self.context.device, old_pass.settings().clone(), old_pass.fonts()) {
+                Ok(mut caption_pass) => {
+                    caption_pass.continue_from(old_pass);
+                    Some(caption_pass)
+                }
+                Err(e) => {
+                    error!("Captions disabled: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        self.set_particles(old.particles());
+        self.set_bloom(old.bloom());
+        self.set_glyphs(old.glyphs().clone());
+        self.set_accumulation(old.accumulation());
+        self.set_compositor(old.compositor().cloned());
+        self.set_cursor_overlay(old.cursor_overlay());
+        if let (Some(cursor_pass), Some(old_pass)) = (&mut self.cursor_pass, &old.cursor_pass) {
+            cursor_pass.set_position(old_pass.position());
+        }
+        self.set_zoom(old.zoom());
+        if let (Some(zoom_pass), Some(old_pass)) = (&mut self.zoom_pass, &old.zoom_pass) {
+            zoom_pass.continue_from(old_pass);
+        }
+        self.set_decontamination(old.decontamination());
+        self.set_input_filter(old.input_filter());
+        self.set_pose_model(old.pose_model().map(std::path::PathBuf::from));
+        self.set_text_blur(old.text_blur().cloned());
+        self.set_regions(old.regions().to_vec());
+        // The buffers carry the values set at runtime
+        self.set_data_buffers(old.data_buffers().to_vec());
+        self.set_redaction(old.redaction_rules().to_vec());
+        self.set_qr(old.qr());
+        self.set_gesture_recognition(old.gesture_recognition());
+        for sink in Sink::ALL {
+            self.set_sink_mirror(sink, old.sink_mirror(sink));
+            if let Err(e) = self.set_sink_calibration(sink, old.sink_calibration(sink).cloned()) {
+                error!("{:?} calibration disabled: {}", sink, e);
+            }
+        }
+        self.set_yuv_passthrough(old.yuv_passthrough());
+        self.set_seed_mode(old.seed_mode());
+        self.set_output_format(old.output_format());
+        self.set_stats(old.stats().clone());
+    }
+
+    /// Counts a frame sent to an ML model, or dropped because the model was busy.
+    fn ml_sent(&mut self, accepted: bool) {
+        if !accepted {
+            self.stats.dropped(Stage::Ml, 1);
+        } else if self.ml_sent.is_none() {
+            self.ml_sent = Some(std::time::Instant::now());
+        }
+    }
+
+    /// Counts a result of an ML model, timed from when the frame was sent.
+    fn ml_result(&mut self) {
+        match self.ml_sent.take() {
+            Some(sent) => self.stats.frame(Stage::Ml, sent.elapsed()),
+            None => self.stats.frames(Stage::Ml, 1),
+        }
+    }
+
+    /// Returns the final pass output with the particles and the bloom added.
+    fn final_pass_output(&self) -> Option<&wgpu::Texture> {
+        self.bloom_pass.as_ref()
+            .and_then(|bloom_pass| bloom_pass.texture())
+            .or(self.particle_pass.as_ref().and_then(|particle_pass| particle_pass.texture()))
+            .or(self.output_textures.last())
+    }
+
+    /// Returns the final pass output after bloom, accumulation and composition.
+    fn composed_texture(&self) -> Option<&wgpu::Texture> {
+        let compositor_pass = self.compositor_pass.as_ref().filter(|compositor_pass| compositor_pass.stage() == CompositorStage::After);
+        match (compositor_pass, &self.accumulate_pass) {
+            (Some(compositor_pass), _) => compositor_pass.texture(),
+            (None, Some(accumulate_pass)) => accumulate_pass.texture(),
+            (None, None) => self.final_pass_output(),
+        }
+    }
+
     /// Returns the current output texture.
     pub fn output_texture(&self) -> Option<&wgpu::Texture> {
-        self.output_textures.last()
+        match (&self.layout_pass, &self.zoom_pass, &self.cursor_pass) {
+            (Some(layout_pass), _, _) => layout_pass.texture(),
+            (None, Some(zoom_pass), _) => zoom_pass.texture(),
+            (None, None, Some(cursor_pass)) => cursor_pass.texture(),
+            (None, None, None) => self.composed_texture(),
+        }
     }
 
     /// Process a frame on the GPU and leave the result in the output texture.
     pub fn process_frame_gpu(&mut self, input: &VideoFrame, time: f32) -> Result<()> {
+        let frame_start = std::time::Instant::now();
         // Check for hot-reloads
         self.check_reload();
 
-        // Scale down input if it exceeds device texture limits
-        let scale_start = std::time::Instant::now();
-        let rgba_input = input.scale_to_fit(self.max_texture_dimension);
-        let scale_elapsed = scale_start.elapsed();
-        tracing::debug!("  [Perf] Input scale_to_fit (RGBA conv): {:?}", scale_elapsed);
+        // Uncompressed YUV frames can be converted on the GPU (the models and the QR scanner need RGBA)
+        let gpu_yuv = self.yuv_pass.is_some()
+            && self.segmentation_engine.is_none()
+            && self.pose_engine.is_none()
+            && self.text_detector.is_none()
+            && self.redaction_detectors.is_empty()
+            && self.qr_scanner.is_none()
+            && YuvPass::supports(input)
+            && input.width.max(input.height) <= self.max_texture_dimension;
+
+        // Scale down input if it exceeds device texture limits. Rotated frames are turned
+        // upright here, or by the YUV pass on the GPU, so the models see them upright too
+        let rgba_input = if gpu_yuv { Cow::Borrowed(input) } else { Cow::Owned(input.scale_to_fit(self.max_texture_dimension).upright()) };
+        let (input_width, input_height) = rgba_input.upright_size();
         self.frame_count += 1;
+        self.time = time;
 
-        // 1. Try to send frame to ML worker (Non-blocking)
-        if let Some(engine) = &mut self.segmentation_engine {
-            engine.try_predict(rgba_input.clone());
+        // 1. Try to send frame to ML worker (Non-blocking, no copy until the model is ready)
+        if let Some(engine) = self.segmentation_engine.as_ref().filter(|engine| engine.is_ready()) {
+            let accepted = engine.try_predict(rgba_input.clone().into_owned());
+            self.ml_sent(accepted);
+        }
+
+        // Same for the pose model, which is dropped if it failed to load
+        if self.pose_engine.as_ref().is_some_and(|engine| engine.state() == ModelState::Failed) {
+            warn!("No pose model, continuing without keypoints");
+            self.pose_engine = None;
+        }
+        if let Some(pose_engine) = self.pose_engine.as_ref().filter(|engine| engine.is_ready()) {
+            let accepted = pose_engine.try_predict(rgba_input.clone().into_owned());
+            self.ml_sent(accepted);
+        }
+        if let Some(pose) = self.pose_engine.as_ref().and_then(|engine| engine.poll_result()) {
+            self.ml_result();
+            self.pose = pose;
+            self.write_pose();
+
+            if let Some(gesture_recognizer) = &mut self.gesture_recognizer {
+                for gesture in gesture_recognizer.update(&pose, std::time::Instant::now()) {
+                    tracing::info!("Gesture detected: {}", gesture);
+                    for canvas in &mut self.lua_canvases {
+                        canvas.on_gesture(&gesture.to_string());
+                    }
+                    self.gestures.push(gesture);
+                }
+            }
+        }
+
+        // And the text detector
+        if let Some(text_detector) = self.text_detector.as_ref().filter(|detector| detector.is_ready()) {
+            let accepted = text_detector.try_predict(rgba_input.clone().into_owned());
+            self.ml_sent(accepted);
+        }
+        if let Some(boxes) = self.text_detector.as_ref().and_then(|detector| detector.poll_result()) {
+            self.ml_result();
+            if let Some(text_blur_pass) = &mut self.text_blur_pass {
+                text_blur_pass.set_boxes(boxes);
+            }
+        }
+        // The text blur of a failed model keeps blurring everything
+        if self.text_detector.as_ref().is_some_and(|detector| detector.state() == ModelState::Failed) {
+            warn!("No text detection model, blurring the whole camera input");
+            self.text_detector = None;
+        }
+
+        // And the redaction detectors. Rules of failed models keep hiding the whole frame
+        // while enabled, as they never report
+        let rules = self.redaction_pass.as_ref().map_or(&[][..], |redaction_pass| redaction_pass.rules());
+        self.redaction_detectors.retain(|(index, detector)| {
+            let failed = detector.state() == ModelState::Failed;
+            if failed {
+                let name = rules.get(*index).map_or("?", |rule| rule.name.as_str());
+                warn!("Redaction rule '{}' has no detection model, hiding the whole frame while it is enabled", name);
+            }
+            !failed
+        });
+        for i in 0..self.redaction_detectors.len() {
+            if self.redaction_detectors[i].1.is_ready() {
+                let accepted = self.redaction_detectors[i].1.try_predict(rgba_input.clone().into_owned());
+                self.ml_sent(accepted);
+            }
+            let (index, detector) = &self.redaction_detectors[i];
+            if let Some(detections) = detector.poll_result() {
+                let index = *index;
+                self.ml_result();
+                if let Some(redaction_pass) = &mut self.redaction_pass {
+                    redaction_pass.set_detections(index, detections);
+                }
+            }
+        }
+
+        // And the QR scanner, which only needs a frame every few hundred milliseconds
+        if let Some(qr_scanner) = &mut self.qr_scanner {
+            let now = std::time::Instant::now();
+            if qr_scanner.wants_frame(now) {
+                qr_scanner.submit(rgba_input.clone().into_owned(), now);
+            }
+            for payload in qr_scanner.poll(now) {
+                tracing::info!("QR code detected: {}", payload);
+                for canvas in &mut self.lua_canvases {
+                    canvas.on_qr(&payload);
+                }
+                self.qr_codes.push(payload);
+            }
         }
 
-        // 2. Poll for latest mask result
+        // 2. Poll for latest mask result. A model that failed to load is dropped, so the
+        // YUV input can be converted on the GPU again; the `people` redaction rules keep
+        // hiding the whole frame while enabled
+        if self.segmentation_engine.as_ref().is_some_and(|engine| engine.state() == ModelState::Failed) {
+            warn!("No segmentation model, continuing without the person mask");
+            self.segmentation_engine = None;
+        }
         let mut mask_result = None;
         if let Some(engine) = &mut self.segmentation_engine {
              mask_result = engine.poll_result();
         }
+        if mask_result.is_some() {
+            self.ml_result();
+        }
 
         // 3. Ensure resources (base size 1920x1080, mask size varies)
         // If no new mask was polled, we just reuse the old sizes so ensure_resources does nothing.
-        let (mask_w, mask_h) = if let Some((_, w, h)) = &mask_result { (*w, *h) } else { (self.cached_mask_width, self.cached_mask_height) };
+        let (mask_w, mask_h) = if let Some(result) = &mask_result { (result.width, result.height) } else { (self.cached_mask_width, self.cached_mask_height) };
         // Initial case: if everything is 0, default to 1x1
         let final_mask_w = if mask_w == 0 { 1 } else { mask_w };
         let final_mask_h = if mask_h == 0 { 1 } else { mask_h };
 
-        self.ensure_resources(rgba_input.width, rgba_input.height, final_mask_w, final_mask_h)?;
+        self.ensure_resources(input_width, input_height, final_mask_w, final_mask_h)?;
         
         // 3. Update uniform buffer
         let uniforms = Uniforms { 
             time, 
             width: self.output_width as f32, 
             height: self.output_height as f32, 
-            seed: rand::random::<f32>(),
+            seed: self.seed_mode.seed(self.frame_count),
+            live: if self.live { 1.0 } else { 0.0 },
+            beat: self.clock.beat_phase(),
+            bar: self.clock.bar_phase(),
+            bpm: self.clock.bpm,
+            audio_peak: self.audio.peak,
+            audio_rms: self.audio.rms,
+            clock: self.wall_time.seconds_of_day as f32,
+            _padding: [0.0; 3],
         };
         self.context.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        let mut values = Cow::Borrowed(&self.params);
+        if !self.modulators.is_empty() {
+            apply_modulators(values.to_mut(), &self.modulators, time, self.clock.beat);
+        }
+        for (i, (layout, buffer)) in self.param_layouts.iter().zip(&self.param_buffers).enumerate() {
+            if let (Some(layout), Some(buffer)) = (layout, buffer) {
+                let data = match self.pass_options.get(i).filter(|o| !o.modulators.is_empty()) {
+                    Some(options) => {
+                        let mut pass_values = values.clone().into_owned();
+                        apply_modulators(&mut pass_values, &options.modulators, time, self.clock.beat);
+                        layout.pack(&pass_values)
+                    }
+                    None => layout.pack(&values),
+                };
+                self.context.queue.write_buffer(buffer, 0, &data);
+            }
+        }
 
-        // 4. Upload Mask
-        if let Some((mask_data, w, h)) = mask_result {
-            let align_mask = 255;
-            let padded_width = (w as usize + align_mask) & !align_mask;
-            let upload_data = if padded_width == w as usize {
-                std::borrow::Cow::Borrowed(&mask_data)
-            } else {
-                let mut aligned = vec![0u8; padded_width * h as usize];
-                for y in 0..h as usize {
-                        let src = y * w as usize;
-                        let dst = y * padded_width;
-                        aligned[dst..dst + w as usize].copy_from_slice(&mask_data[src..src+w as usize]);
+        // 4. Upload Mask and Instance IDs
+        if let Some(result) = mask_result {
+            self.mask_area = Some(mask_area(&result.mask));
+            self.people = result.instances.as_ref().map(|instances| instances.iter().copied().max().unwrap_or(0) as u32);
+            Self::upload_mask_data(&self.context.queue, &self.mask_texture, &result.mask, result.width, result.height);
+            if let Some(instances) = &result.instances {
+                Self::upload_mask_data(&self.context.queue, &self.instances_texture, instances, result.width, result.height);
+                if let Some(redaction_pass) = &mut self.redaction_pass {
+                    redaction_pass.set_people(&person_boxes(instances, result.width as usize, result.height as usize));
                 }
-                std::borrow::Cow::Owned(aligned)
-            };
-
-            self.context.queue.write_texture(
-                wgpu::TexelCopyTextureInfo { texture: &self.mask_texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-                &upload_data,
-                wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(padded_width as u32), rows_per_image: Some(h) },
-                wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
-            );
+            }
         }
 
         // 5. Update video textures with current frames
@@ -1082,11 +2887,32 @@ impl WgpuPipeline {
             }
         }
 
+        // Upload the camera frame first, so Lua canvases can draw it
+        self.upload_input(input, &rgba_input, gpu_yuv);
+
         // 6. Update Lua canvas textures (Direct GPU access, no CPU readback)
+        let script_globals = ScriptGlobals {
+            live: self.live,
+            bpm: self.clock.bpm,
+            beat: self.clock.beat,
+            beat_phase: self.clock.beat_phase(),
+            bar_phase: self.clock.bar_phase(),
+            wall_time: self.wall_time,
+            audio: self.audio,
+            pose: self.pose_engine.is_some().then_some(self.pose),
+        };
+        // Not with text blur or redaction: the canvas would show the regions unhidden
+        let camera_view = self.input_texture.as_ref().filter(|_| self.text_blur_pass.is_none() && self.redaction_pass.is_none()).map(|texture| texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(wgpu::TextureFormat::Rgba8Unorm),
+            ..Default::default()
+        }));
         for (slot_index, canvas_index) in self.lua_slot_map.iter().enumerate() {
             if let Some(canvas_idx) = canvas_index {
+                if let Err(e) = self.lua_canvases[*canvas_idx].set_globals(&script_globals) {
+                    tracing::warn!("Failed to update Lua globals: {}", e);
+                }
                 // prepare_texture runs Lua update/draw and flushes to GPU
-                if let Some(view) = self.lua_canvases[*canvas_idx].prepare_texture(time) {
+                if let Some(view) = self.lua_canvases[*canvas_idx].prepare_texture(time, camera_view.as_ref()) {
                     self.lua_canvas_views[slot_index] = Some(view);
                     // Since the view identity might change, we must update bind groups
                     bind_groups_need_update = true;
@@ -1094,24 +2920,56 @@ impl WgpuPipeline {
             }
         }
 
+        // Regions set by scripts with proteus.set_regions()
+        let script_regions = self.lua_canvases.iter().filter_map(|canvas| canvas.take_regions()).last();
+        if let Some(regions) = script_regions {
+            self.set_regions(regions);
+        }
+
+        // And data buffer values set with proteus.set_buffer()
+        let script_values: Vec<(String, Vec<f32>)> = self.lua_canvases.iter().flat_map(|canvas| canvas.take_buffers()).collect();
+        for (name, values) in script_values {
+            if let Err(e) = self.set_data_values(&name, values) {
+                tracing::warn!("proteus.set_buffer: {}", e);
+            }
+        }
+
         // Recreate bind groups if any texture was resized
         if bind_groups_need_update {
             self.cached_width = 0; // Force update
-            self.ensure_resources(rgba_input.width, rgba_input.height, final_mask_w, final_mask_h)?;
+            self.ensure_resources(input_width, input_height, final_mask_w, final_mask_h)?;
+            // The input texture was recreated
+            self.upload_input(input, &rgba_input, gpu_yuv);
         }
 
-        let upload_start = std::time::Instant::now();
-        self.context.queue.write_texture(
-            wgpu::TexelCopyTextureInfo { texture: self.input_texture.as_ref().unwrap(), mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-            &rgba_input.data,
-            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(rgba_input.width * 4), rows_per_image: Some(rgba_input.height) },
-            wgpu::Extent3d { width: rgba_input.width, height: rgba_input.height, depth_or_array_layers: 1 },
-        );
-        tracing::debug!("  [Perf] Texture Upload: {:?}", upload_start.elapsed());
-
-        let shader_start = std::time::Instant::now();
         let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Render Encoder") });
 
+        for slot_transform in self.slot_transforms.iter().flatten() {
+            slot_transform.encode(&mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(text_blur_pass) = &mut self.text_blur_pass {
+            text_blur_pass.update(&self.context.queue);
+            text_blur_pass.encode(&mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(redaction_pass) = &mut self.redaction_pass {
+            redaction_pass.update(&self.context.queue);
+            redaction_pass.encode(&mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(decontaminate_pass) = self.decontaminate_pass.as_ref().filter(|_| self.segmentation_engine.is_some()) {
+            decontaminate_pass.encode(&mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(sharpen_pass) = &self.sharpen_pass {
+            sharpen_pass.encode(&mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(compositor_pass) = self.compositor_pass.as_mut().filter(|compositor_pass| compositor_pass.stage() == CompositorStage::Before) {
+            compositor_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer, std::time::Instant::now());
+        }
+
         for (i, pipeline) in self.render_pipelines.iter().enumerate() {
             let output_view = self.output_textures[i].create_view(&wgpu::TextureViewDescriptor::default());
             let mut color_attachments = vec![Some(wgpu::RenderPassColorAttachment {
@@ -1148,29 +3006,96 @@ impl WgpuPipeline {
 
                 render_pass.set_pipeline(pipeline);
                 render_pass.set_bind_group(0, &self.bind_groups[i], &[]);
-                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-                render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                render_pass.draw_indexed(0..6, 0, 0..1);
+                match self.pass_meshes.get(i).and_then(Option::as_ref) {
+                    Some(mesh) => {
+                        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                        render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+                    }
+                    None => {
+                        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                        render_pass.draw_indexed(0..6, 0, 0..1);
+                    }
+                }
             }
         }
 
+        if let Some(particle_pass) = &mut self.particle_pass {
+            particle_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer, time);
+        }
+
+        if let Some(bloom_pass) = &self.bloom_pass {
+            bloom_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(accumulate_pass) = &mut self.accumulate_pass {
+            accumulate_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(compositor_pass) = self.compositor_pass.as_mut().filter(|compositor_pass| compositor_pass.stage() == CompositorStage::After) {
+            compositor_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer, std::time::Instant::now());
+        }
+
+        if let Some(cursor_pass) = &mut self.cursor_pass {
+            cursor_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer, std::time::Instant::now());
+        }
+
+        if let Some(zoom_pass) = &mut self.zoom_pass {
+            zoom_pass.encode(&self.context.queue, &mut encoder, &self.vertex_buffer, &self.index_buffer, std::time::Instant::now());
+        }
+
+        if let Some(layout_pass) = &self.layout_pass {
+            layout_pass.encode(&mut encoder, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let Some(caption_pass) = &mut self.caption_pass {
+            caption_pass.update(&self.context.device, &self.context.queue, self.time, (self.output_width, self.output_height));
+        }
+        if let (Some(caption_pass), Some(target)) = (&self.caption_pass, self.output_texture()) {
+            caption_pass.encode(&self.context.queue, &mut encoder, target, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let (Some(watermark_pass), Some(target)) = (self.watermark_pass.as_ref().filter(|_| !self.hidden.contains(WATERMARK_TARGET)), self.output_texture()) {
+            watermark_pass.encode(&self.context.queue, &mut encoder, target, &self.vertex_buffer, &self.index_buffer);
+        }
+
+        if let (Some(warning_pass), Some(target)) = (&self.warning_pass, self.output_texture()) {
+            warning_pass.encode(&self.context.queue, &mut encoder, target, &self.vertex_buffer, &self.index_buffer);
+        }
+
         self.context.queue.submit(std::iter::once(encoder.finish()));
-        tracing::debug!("  [Perf] Shader Dispatch: {:?}", shader_start.elapsed());
-        
+        self.stats.frame(Stage::Render, frame_start.elapsed());
+
         Ok(())
     }
-}
 
-impl ShaderPipeline for WgpuPipeline {
-    fn process_frame(&mut self, input: &VideoFrame, time: f32) -> Result<VideoFrame> {
-        self.process_frame_gpu(input, time)?;
-        let start = std::time::Instant::now();
+    /// Uploads the camera frame into the input texture, converts it from YUV on
+    /// the GPU if `gpu_yuv`, and generates its mip levels.
+    fn upload_input(&mut self, input: &VideoFrame, rgba_input: &VideoFrame, gpu_yuv: bool) {
+        let input_texture = self.input_texture.as_ref().unwrap();
+        match &mut self.yuv_pass {
+            Some(yuv_pass) if gpu_yuv => yuv_pass.upload(&self.context.device, &self.context.queue, input),
+            _ => self.context.queue.write_texture(
+                wgpu::TexelCopyTextureInfo { texture: input_texture, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+                &rgba_input.data,
+                wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(rgba_input.width * 4), rows_per_image: Some(rgba_input.height) },
+                wgpu::Extent3d { width: rgba_input.width, height: rgba_input.height, depth_or_array_layers: 1 },
+            ),
+        }
 
-        // Perform sRGB Resolve Pass and Readback
-        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") });
+        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Input Encoder") });
+        if let Some(yuv_pass) = self.yuv_pass.as_ref().filter(|_| gpu_yuv) {
+            yuv_pass.encode(&mut encoder, input_texture, &self.vertex_buffer, &self.index_buffer);
+        }
+        self.mipmap_pass.encode(&mut encoder, input_texture);
+        self.context.queue.submit(std::iter::once(encoder.finish()));
+    }
 
+    /// Records the sRGB resolve of the last rendered output and its copy into
+    /// `buffer`, packed in the output format and calibrated for `sink`. Returns the bytes written.
+    fn encode_readback(&self, encoder: &mut wgpu::CommandEncoder, buffer: &wgpu::Buffer, sink: Sink) -> u64 {
         // 1. sRGB Resolve Pass: Linear F16 -> sRGB U8
-
         {
              let srgb_view = self.srgb_output_texture.as_ref().unwrap().create_view(&wgpu::TextureViewDescriptor::default());
              let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -1187,24 +3112,41 @@ impl ShaderPipeline for WgpuPipeline {
                 multiview_mask: None,
             });
 
+            let vertex_buffer = if self.sink_mirrors[sink as usize] { &self.mirrored_vertex_buffer } else { &self.vertex_buffer };
             render_pass.set_pipeline(&self.srgb_pipeline);
             render_pass.set_bind_group(0, self.srgb_bind_group.as_ref().unwrap(), &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_bind_group(1, self.sink_calibrations[sink as usize].bind_group(), &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..6, 0, 0..1);
         }
 
-        // 2. Copy sRGB texture to buffer
-        encoder.copy_texture_to_buffer(
-            wgpu::TexelCopyTextureInfo { texture: self.srgb_output_texture.as_ref().unwrap(), mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
-            wgpu::TexelCopyBufferInfo { buffer: self.readback_buffer.as_ref().unwrap(), layout: wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(self.output_width * 4), rows_per_image: Some(self.output_height) } },
-            wgpu::Extent3d { width: self.output_width, height: self.output_height, depth_or_array_layers: 1 },
-        );
+        // 2. Copy sRGB texture to buffer, or pack it into the output format first
+        if let Some(pack_pass) = &self.pack_pass {
+            pack_pass.encode(encoder, buffer);
+            pack_pass.frame_size()
+        } else {
+            encoder.copy_texture_to_buffer(
+                wgpu::TexelCopyTextureInfo { texture: self.srgb_output_texture.as_ref().unwrap(), mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+                wgpu::TexelCopyBufferInfo { buffer, layout: wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(self.output_width * 4), rows_per_image: Some(self.output_height) } },
+                wgpu::Extent3d { width: self.output_width, height: self.output_height, depth_or_array_layers: 1 },
+            );
+            self.output_width as u64 * self.output_height as u64 * 4
+        }
+    }
 
+    /// Reads the last rendered output back to the CPU as an sRGB frame in the
+    /// output format (see `set_output_format`), calibrated for `sink`. Waits for
+    /// the GPU; per-frame readback should use `read_output_pipelined`.
+    pub fn read_output(&mut self, sink: Sink) -> Result<VideoFrame> {
+        if self.srgb_output_texture.is_none() || self.srgb_bind_group.is_none() || self.readback_buffer.is_none() {
+            return Err(anyhow!("No frame has been rendered yet"));
+        }
+        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") });
+        let readback_size = self.encode_readback(&mut encoder, self.readback_buffer.as_ref().unwrap(), sink);
         let submission_index = self.context.queue.submit(std::iter::once(encoder.finish()));
 
-        let readback_start = std::time::Instant::now();
-        let buffer_slice = self.readback_buffer.as_ref().unwrap().slice(..);
+        let buffer_slice = self.readback_buffer.as_ref().unwrap().slice(..readback_size);
         let (sender, receiver) = std::sync::mpsc::channel();
         buffer_slice.map_async(wgpu::MapMode::Read, move |result| sender.send(result).unwrap());
         self.context.device.poll(wgpu::PollType::Wait { submission_index: Some(submission_index), timeout: None }).unwrap();
@@ -1217,12 +3159,82 @@ impl ShaderPipeline for WgpuPipeline {
         
         drop(data);
         self.readback_buffer.as_ref().unwrap().unmap();
-        
-        tracing::debug!("  [Perf] GPU Readback: {:?}", readback_start.elapsed());
-        tracing::debug!("  [Perf] TOTAL FRAME: {:?}", start.elapsed());
 
-        Ok(VideoFrame::from_data(self.output_width, self.output_height, PixelFormat::Rgba, output_data))
+        let frame = VideoFrame::from_data(self.output_width, self.output_height, self.output_format(), output_data);
+        Ok(match &self.pack_pass {
+            Some(pack_pass) => frame.with_color(pack_pass.color()),
+            None => frame,
+        })
+    }
+
+    /// Starts reading the last rendered output back for the virtual camera without waiting for the GPU,
+    /// and returns the oldest frame whose readback has finished, if any. Frames
+    /// come out in order, at most `FRAMES_IN_FLIGHT` calls later; only when
+    /// all readback buffers are busy does this wait for the oldest one.
+    pub fn read_output_pipelined(&mut self) -> Result<Option<VideoFrame>> {
+        if self.srgb_output_texture.is_none() || self.srgb_bind_group.is_none() {
+            return Err(anyhow!("No frame has been rendered yet"));
+        }
+        let Some(ring) = &mut self.readback_ring else {
+            return Err(anyhow!("No frame has been rendered yet"));
+        };
+        let (slot, finished) = ring.acquire(&self.context.device)?;
+
+        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Pipelined Readback Encoder") });
+        let size = self.encode_readback(&mut encoder, self.readback_ring.as_ref().unwrap().buffer(slot), Sink::VirtualCamera);
+        let submission_index = self.context.queue.submit(std::iter::once(encoder.finish()));
+        let info = FrameInfo {
+            width: self.output_width,
+            height: self.output_height,
+            format: self.output_format(),
+            color: self.pack_pass.as_ref().map(|pack_pass| pack_pass.color()),
+            size,
+            metadata: self.frame_metadata(),
+        };
+        let ring = self.readback_ring.as_mut().unwrap();
+        ring.submit(slot, info, submission_index);
+
+        // Runs the map callbacks of finished readbacks
+        self.context.device.poll(wgpu::PollType::Poll).map_err(|e| anyhow!("GPU readback failed: {}", e))?;
+        let finished = match finished {
+            Some(finished) => Some(finished),
+            None => ring.take_ready()?,
+        };
+        Ok(finished.map(|(frame, metadata)| {
+            self.read_back_metadata = metadata;
+            frame
+        }))
+    }
+
+    /// Waits for the oldest frame `read_output_pipelined` still has in flight and
+    /// returns it, or None if there is none. Used when no new frames are rendered
+    /// (the input stalled, shutdown), so the last frames aren't held back.
+    pub fn finish_readback(&mut self) -> Result<Option<VideoFrame>> {
+        let Some(ring) = &mut self.readback_ring else {
+            return Ok(None);
+        };
+        Ok(ring.finish(&self.context.device)?.map(|(frame, metadata)| {
+            self.read_back_metadata = metadata;
+            frame
+        }))
     }
 
+    /// Writes the last rendered output to `output`: as a texture if the output
+    /// accepts GPU textures, otherwise read back for the virtual camera.
+    pub fn write_output(&mut self, output: &mut dyn OutputBackend) -> Result<()> {
+        if output.capabilities().gpu_texture {
+            let texture = self.output_texture().ok_or_else(|| anyhow!("No frame has been rendered yet"))?;
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            return output.write_texture(&view);
+        }
+        let frame = self.read_output(Sink::VirtualCamera)?;
+        output.write_frame(&frame)
+    }
+}
 
+impl ShaderPipeline for WgpuPipeline {
+    fn process_frame(&mut self, input: &VideoFrame, time: f32) -> Result<Option<VideoFrame>> {
+        self.process_frame_gpu(input, time)?;
+        self.read_output_pipelined()
+    }
 }
@@ -0,0 +1,301 @@
+//! GPU conversion of uncompressed camera frames (YUYV, NV12) to RGB.
+//!
+//! Uploads the raw YUV planes and converts them into the pipeline's input
+//! texture, skipping the CPU conversion. The frame's rotation is applied on
+//! the way, so the input texture is upright.
+
+use crate::frame::{PixelFormat, QuadVertex, VideoFrame, YuvColorSpace, YuvMatrix, YuvRange};
+use std::borrow::Cow;
+use wgpu::util::DeviceExt;
+
+const YUV_SHADER: &str = r#"
+struct YuvUniforms {
+    // R = Y + x * V, G = Y - y * U - z * V, B = Y + w * U
+    coeffs: vec4<f32>,
+    // 0 = YUYV, 1 = NV12
+    mode: u32,
+    full_range: u32,
+    // Clockwise quarter turns
+    rotation: u32,
+    _padding0: u32,
+}
+
+// YUYV: Rgba8 texture of (width / 2) x height, one Y0 U Y1 V macropixel per texel
+// NV12: R8 Y plane
+@group(0) @binding(0) var t_plane0: texture_2d<f32>;
+// NV12: Rg8 UV plane at half resolution (unused for YUYV)
+@group(0) @binding(1) var t_plane1: texture_2d<f32>;
+@group(0) @binding(2) var<uniform> u_yuv: YuvUniforms;
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+// Frame pixel shown at `p` of the upright output
+fn source_pixel(p: vec2<i32>) -> vec2<i32> {
+    var size = vec2<i32>(textureDimensions(t_plane0));
+    if (u_yuv.mode == 0u) {
+        size.x *= 2;
+    }
+    switch u_yuv.rotation {
+        case 1u: { return vec2<i32>(p.y, size.y - 1 - p.x); }
+        case 2u: { return size - vec2<i32>(1) - p; }
+        case 3u: { return vec2<i32>(size.x - 1 - p.y, p.x); }
+        default: { return p; }
+    }
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = source_pixel(vec2<i32>(position.xy));
+    var yuv: vec3<f32>;
+    if (u_yuv.mode == 0u) {
+        let texel = textureLoad(t_plane0, vec2<i32>(p.x / 2, p.y), 0);
+        let y = select(texel.r, texel.b, (p.x & 1) == 1);
+        yuv = vec3<f32>(y, texel.g, texel.a);
+    } else {
+        let y = textureLoad(t_plane0, p, 0).r;
+        let uv = textureLoad(t_plane1, p / 2, 0).rg;
+        yuv = vec3<f32>(y, uv);
+    }
+
+    var y = yuv.x;
+    var uv = yuv.yz - vec2<f32>(128.0 / 255.0);
+    if (u_yuv.full_range == 0u) {
+        y = (y - 16.0 / 255.0) * (255.0 / 219.0);
+        uv = uv * (255.0 / 224.0);
+    }
+
+    let c = u_yuv.coeffs;
+    let rgb = vec3<f32>(
+        y + c.x * uv.y,
+        y - c.y * uv.x - c.z * uv.y,
+        y + c.w * uv.x,
+    );
+    // The input texture is sRGB, so store linear values
+    return vec4<f32>(srgb_to_linear(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0))), 1.0);
+}
+"#;
+
+/// GPU resources of the YUV conversion pass.
+pub(super) struct YuvPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    // (format, width, height) of the current planes
+    planes_for: Option<(PixelFormat, u32, u32)>,
+    planes: Vec<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl YuvPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("YUV Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("YUV Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("YUV Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(YUV_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("YUV Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("YUV Uniform Buffer"),
+            contents: &[0u8; 32],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            planes_for: None,
+            planes: Vec::new(),
+            bind_group: None,
+        }
+    }
+
+    /// Whether `frame` can be converted on the GPU.
+    pub fn supports(frame: &VideoFrame) -> bool {
+        let pixels = (frame.width as usize) * (frame.height as usize);
+        let even = frame.width.is_multiple_of(2) && frame.height.is_multiple_of(2);
+        match frame.format {
+            PixelFormat::Yuyv => even && frame.data.len() >= pixels * 2,
+            PixelFormat::Nv12 => even && frame.data.len() >= pixels + pixels / 2,
+            _ => false,
+        }
+    }
+
+    /// Uploads the YUV planes of `frame` (which must be `supports`ed).
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: &VideoFrame) {
+        let (w, h) = (frame.width, frame.height);
+        // (format, width, height, bytes per texel) of each plane
+        let layout = match frame.format {
+            PixelFormat::Yuyv => vec![(wgpu::TextureFormat::Rgba8Unorm, w / 2, h, 4)],
+            _ => vec![(wgpu::TextureFormat::R8Unorm, w, h, 1), (wgpu::TextureFormat::Rg8Unorm, w / 2, h / 2, 2)],
+        };
+
+        if self.planes_for != Some((frame.format, w, h)) {
+            self.planes = layout
+                .iter()
+                .map(|&(format, width, height, _)| {
+                    device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("YUV Plane"),
+                        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format,
+                        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                        view_formats: &[],
+                    })
+                })
+                .collect();
+
+            let views: Vec<wgpu::TextureView> = self.planes.iter().map(|plane| plane.create_view(&wgpu::TextureViewDescriptor::default())).collect();
+            // YUYV has a single plane; bind it twice
+            let plane1 = views.get(1).unwrap_or(&views[0]);
+            self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("YUV Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&views[0]) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(plane1) },
+                    wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+                ],
+            }));
+            self.planes_for = Some((frame.format, w, h));
+        }
+
+        let mut offset = 0;
+        for (plane, &(_, width, height, bytes_per_texel)) in self.planes.iter().zip(&layout) {
+            let size = (width * height * bytes_per_texel) as usize;
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo { texture: plane, mip_level: 0, origin: wgpu::Origin3d::ZERO, aspect: wgpu::TextureAspect::All },
+                &frame.data[offset..offset + size],
+                wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(width * bytes_per_texel), rows_per_image: Some(height) },
+                wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            );
+            offset += size;
+        }
+
+        let mode: u32 = if frame.format == PixelFormat::Yuyv { 0 } else { 1 };
+        let mut uniforms = [0u8; 32];
+        uniforms[..16].copy_from_slice(bytemuck::cast_slice(&coefficients(frame.color)));
+        uniforms[16..28].copy_from_slice(bytemuck::cast_slice(&[mode, (frame.color.range == YuvRange::Full) as u32, frame.rotation.quarter_turns()]));
+        queue.write_buffer(&self.uniform_buffer, 0, &uniforms);
+    }
+
+    /// Records the conversion of the uploaded frame into `target` (the input
+    /// texture, sized to the upright frame). Only the first mip level is written.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::Texture, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer) {
+        let Some(bind_group) = &self.bind_group else { return };
+        let view = target.create_view(&wgpu::TextureViewDescriptor { mip_level_count: Some(1), ..Default::default() });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("YUV Conversion Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+/// YUV to RGB coefficients for the shader's `coeffs` uniform.
+fn coefficients(color: YuvColorSpace) -> [f32; 4] {
+    let (kr, kb) = match color.matrix {
+        YuvMatrix::Bt601 => (0.299, 0.114),
+        YuvMatrix::Bt709 => (0.2126, 0.0722),
+    };
+    let kg = 1.0 - kr - kb;
+    [
+        2.0 - 2.0 * kr,
+        2.0 * kb * (1.0 - kb) / kg,
+        2.0 * kr * (1.0 - kr) / kg,
+        2.0 - 2.0 * kb,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bt709_coefficients() {
+        let c = coefficients(YuvColorSpace { matrix: YuvMatrix::Bt709, range: YuvRange::Limited });
+        let expected = [1.5748, 0.1873, 0.4681, 1.8556];
+        for (a, b) in c.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-3, "{:?}", c);
+        }
+    }
+}
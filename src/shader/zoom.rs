@@ -0,0 +1,295 @@
+//! Zoom that follows a point, e.g. the mouse cursor in a tutorial.
+//!
+//! Runs after the user shaders, the accumulation and the compositor, and
+//! magnifies the frame around a target point. The view eases toward the target
+//! point and zoom level, so it glides after the cursor instead of jumping, and
+//! zooming in and out is animated the same way.
+
+use crate::frame::QuadVertex;
+use std::borrow::Cow;
+use std::time::Instant;
+use wgpu::util::DeviceExt;
+
+/// Zoom settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zoom {
+    /// Magnification while zoomed in (2.0 shows a quarter of the frame)
+    pub level: f32,
+    /// Time in seconds the view takes to cover most (63%) of the way to the
+    /// target; 0.0 follows instantly
+    pub smoothing: f32,
+    /// Zoomed in at startup
+    pub active: bool,
+    /// Follow the cursor (the mouse over the preview window or `proteus ctl cursor`);
+    /// otherwise only `zoom-at` moves it
+    pub follow_cursor: bool,
+}
+
+impl Default for Zoom {
+    fn default() -> Self {
+        Self { level: 2.0, smoothing: 0.3, active: false, follow_cursor: true }
+    }
+}
+
+/// The visible part of the frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ZoomView {
+    /// Point shown in the middle, in 0.0 - 1.0 frame coordinates
+    center: [f32; 2],
+    level: f32,
+}
+
+impl ZoomView {
+    const FULL: ZoomView = ZoomView { center: [0.5, 0.5], level: 1.0 };
+
+    /// Moves toward `target` by the part of the way covered in `dt` seconds.
+    fn ease(self, target: ZoomView, dt: f32, smoothing: f32) -> ZoomView {
+        let t = if smoothing > 0.0 { 1.0 - (-dt / smoothing).exp() } else { 1.0 };
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        ZoomView {
+            center: [lerp(self.center[0], target.center[0]), lerp(self.center[1], target.center[1])],
+            level: lerp(self.level, target.level),
+        }
+    }
+
+    /// Visible rectangle (left, top, right, bottom) in 0.0 - 1.0 frame
+    /// coordinates, moved inside the frame where the center is near an edge.
+    fn rect(self) -> [f32; 4] {
+        let half = 0.5 / self.level.max(1.0);
+        let x = self.center[0].clamp(half, 1.0 - half);
+        let y = self.center[1].clamp(half, 1.0 - half);
+        [x - half, y - half, x + half, y + half]
+    }
+}
+
+const ZOOM_SHADER: &str = r#"
+struct ZoomUniforms {
+    // left, top, right, bottom in 0..1 frame coordinates
+    rect: vec4<f32>,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_sampler: sampler;
+@group(0) @binding(2) var<uniform> u_zoom: ZoomUniforms;
+
+@fragment
+fn fs_main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_sampler, mix(u_zoom.rect.xy, u_zoom.rect.zw, tex_coords));
+}
+"#;
+
+/// GPU resources and view state of the zoom pass.
+pub(super) struct ZoomPass {
+    settings: Zoom,
+    active: bool,
+    target: [f32; 2],
+    view: ZoomView,
+    last_update: Option<Instant>,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+    texture: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl ZoomPass {
+    pub fn new(device: &wgpu::Device, vertex_module: &wgpu::ShaderModule, settings: Zoom) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Zoom Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Zoom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let fragment_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Zoom Fragment Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(ZOOM_SHADER)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Zoom Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: vertex_module,
+                entry_point: Some("vs_main"),
+                buffers: &[QuadVertex::layout()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment_module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Zoom Uniform Buffer"),
+            contents: bytemuck::cast_slice(&ZoomView::FULL.rect()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self {
+            settings,
+            active: settings.active,
+            target: [0.5, 0.5],
+            view: ZoomView::FULL,
+            last_update: None,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            texture: None,
+            bind_group: None,
+        }
+    }
+
+    pub fn settings(&self) -> Zoom {
+        self.settings
+    }
+
+    /// Takes over the zoom state and view of `other`, e.g. after a settings change.
+    pub fn continue_from(&mut self, other: &ZoomPass) {
+        self.active = other.active;
+        self.target = other.target;
+        self.view = other.view;
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Zooms in or out (animated).
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Sets the point to zoom in on, in 0.0 - 1.0 frame coordinates.
+    pub fn set_target(&mut self, x: f32, y: f32) {
+        self.target = [x.clamp(0.0, 1.0), y.clamp(0.0, 1.0)];
+    }
+
+    /// Binds `source` (the final frame before the layout) and (re)creates the
+    /// zoomed texture at the render size.
+    pub fn prepare(&mut self, device: &wgpu::Device, sampler: &wgpu::Sampler, source: &wgpu::TextureView, width: u32, height: u32) {
+        if self.texture.as_ref().is_none_or(|texture| texture.width() != width || texture.height() != height) {
+            self.texture = Some(device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Zoom Texture"),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            }));
+        }
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Zoom Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(source) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.uniform_buffer.as_entire_binding() },
+            ],
+        }));
+    }
+
+    /// The zoomed frame, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Moves the view toward the target and records the zoom pass into `encoder`.
+    pub fn encode(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, vertex_buffer: &wgpu::Buffer, index_buffer: &wgpu::Buffer, now: Instant) {
+        let (Some(texture), Some(bind_group)) = (&self.texture, &self.bind_group) else { return };
+
+        let dt = self.last_update.map_or(0.0, |last| now.saturating_duration_since(last).as_secs_f32());
+        self.last_update = Some(now);
+        let target = if self.active { ZoomView { center: self.target, level: self.settings.level } } else { ZoomView::FULL };
+        self.view = self.view.ease(target, dt, self.settings.smoothing);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&self.view.rect()));
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Zoom Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..6, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view() {
+        let target = ZoomView { center: [0.75, 0.5], level: 2.0 };
+        assert_eq!(ZoomView::FULL.ease(target, 0.1, 0.0), target);
+        assert_eq!(target.rect(), [0.5, 0.25, 1.0, 0.75]);
+
+        // One time constant covers 63% of the way
+        let view = ZoomView::FULL.ease(target, 0.3, 0.3);
+        assert!((view.level - (1.0 + (1.0 - (-1.0f32).exp()))).abs() < 1e-5);
+        assert_eq!(ZoomView::FULL.ease(target, 0.0, 0.3), ZoomView::FULL);
+
+        // Near the edges the view stays inside the frame
+        let corner = ZoomView { center: [1.0, 0.0], level: 4.0 };
+        assert_eq!(corner.rect(), [0.75, 0.0, 1.0, 0.25]);
+        assert_eq!(ZoomView::FULL.rect(), [0.0, 0.0, 1.0, 1.0]);
+    }
+}
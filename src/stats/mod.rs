@@ -0,0 +1,195 @@
+//! Frame statistics of the processing pipeline.
+//!
+//! Each stage (camera capture, ML models, rendering, output) counts its frames
+//! and drops, and measures its time per frame where that's meaningful. Rates and
+//! times are moving averages, so they settle within a second or two instead of
+//! jumping from frame to frame. `proteus ctl stats` prints them.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Time constant of the frame rate average.
+const FPS_SMOOTHING: Duration = Duration::from_secs(1);
+
+/// Weight of a new sample in the time per frame average.
+const MS_SMOOTHING: f32 = 0.1;
+
+/// How often `PipelineStats::update` asks for the stats to be logged.
+const LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A stage of the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Frames from the camera
+    Capture,
+    /// Results of the ML models (all models together)
+    Ml,
+    /// Frames rendered by the shaders
+    Render,
+    /// Frames written to the output (virtual camera or window)
+    Output,
+}
+
+impl Stage {
+    const ALL: [Stage; 4] = [Stage::Capture, Stage::Ml, Stage::Render, Stage::Output];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Capture => "capture",
+            Stage::Ml => "ml",
+            Stage::Render => "render",
+            Stage::Output => "output",
+        }
+    }
+}
+
+/// Statistics of one stage.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StageStats {
+    /// Frames handled in total
+    pub frames: u64,
+    /// Frames dropped in total
+    pub dropped: u64,
+    /// Average frame rate
+    pub fps: f32,
+    /// Average time per frame in milliseconds, if the stage is timed
+    pub ms: Option<f32>,
+    /// Frames since the last update
+    pending: u64,
+}
+
+impl StageStats {
+    fn add_time(&mut self, time: Duration) {
+        let ms = time.as_secs_f32() * 1000.0;
+        self.ms = Some(match self.ms {
+            Some(average) => average + (ms - average) * MS_SMOOTHING,
+            None => ms,
+        });
+    }
+}
+
+impl fmt::Display for StageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} fps", self.fps)?;
+        if let Some(ms) = self.ms {
+            write!(f, " {:.1} ms", ms)?;
+        }
+        if self.dropped > 0 {
+            write!(f, " ({} dropped)", self.dropped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of all pipeline stages, updated every frame.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineStats {
+    stages: [StageStats; 4],
+    last_update: Option<Instant>,
+    last_log: Option<Instant>,
+}
+
+impl PipelineStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(&self, stage: Stage) -> &StageStats {
+        &self.stages[stage as usize]
+    }
+
+    /// Counts a frame that took `time` in the stage.
+    pub fn frame(&mut self, stage: Stage, time: Duration) {
+        let stats = &mut self.stages[stage as usize];
+        stats.add_time(time);
+        stats.frames += 1;
+        stats.pending += 1;
+    }
+
+    /// Counts frames of a stage that isn't timed.
+    pub fn frames(&mut self, stage: Stage, count: u64) {
+        let stats = &mut self.stages[stage as usize];
+        stats.frames += count;
+        stats.pending += count;
+    }
+
+    /// Counts frames the stage dropped.
+    pub fn dropped(&mut self, stage: Stage, count: u64) {
+        self.stages[stage as usize].dropped += count;
+    }
+
+    /// Updates the frame rates with the frames counted since the last update.
+    /// Call once per loop iteration. Returns true once per second, when the
+    /// stats should be logged.
+    pub fn update(&mut self, now: Instant) -> bool {
+        if let Some(last_update) = self.last_update {
+            let elapsed = now.duration_since(last_update).as_secs_f32();
+            if elapsed > 0.0 {
+                let weight = 1.0 - (-elapsed / FPS_SMOOTHING.as_secs_f32()).exp();
+                for stats in &mut self.stages {
+                    let fps = stats.pending as f32 / elapsed;
+                    stats.fps += (fps - stats.fps) * weight;
+                    stats.pending = 0;
+                }
+            }
+        }
+        self.last_update = Some(now);
+
+        let log = self.last_log.is_none_or(|last_log| now.duration_since(last_log) >= LOG_INTERVAL);
+        if log {
+            self.last_log = Some(now);
+        }
+        log
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut stages = Stage::ALL
+            .into_iter()
+            .filter(|&stage| self.stage(stage).frames > 0 || self.stage(stage).dropped > 0)
+            .peekable();
+        if stages.peek().is_none() {
+            return f.write_str("no frames yet");
+        }
+        for (i, stage) in stages.enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {}", stage.name(), self.stage(stage))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_and_drops() {
+        let mut stats = PipelineStats::new();
+        let start = Instant::now();
+        stats.update(start);
+
+        // 30 fps render for five seconds, every other frame reaching the ML model
+        for i in 1..=150 {
+            stats.frame(Stage::Render, Duration::from_millis(4));
+            if i % 2 == 0 {
+                stats.frames(Stage::Ml, 1);
+            } else {
+                stats.dropped(Stage::Ml, 1);
+            }
+            stats.update(start + Duration::from_secs_f32(i as f32 / 30.0));
+        }
+
+        let render = stats.stage(Stage::Render);
+        assert_eq!(render.frames, 150);
+        assert!((render.fps - 30.0).abs() < 1.0, "{}", render.fps);
+        assert!((render.ms.unwrap() - 4.0).abs() < 1e-3);
+        assert_eq!(stats.stage(Stage::Ml).dropped, 75);
+        assert!((stats.stage(Stage::Ml).fps - 15.0).abs() < 3.0);
+        assert!(stats.stage(Stage::Capture).ms.is_none());
+        assert_eq!(stats.to_string().split(", ").count(), 2);
+    }
+}
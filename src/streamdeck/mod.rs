@@ -0,0 +1,267 @@
+//! WebSocket backend for Stream Deck plugins.
+//!
+//! A plugin connects to `ws://127.0.0.1:<port>` and receives the state its keys
+//! show (presets with their icons, tally, recording, redaction rules) on
+//! connect and whenever it changes. Keys send control commands, which are
+//! answered like `proteus ctl`:
+//!
+//! ```text
+//! <- {"event":"state","live":false,"recording":false,"preset":"calm","presets":[{"name":"calm","icon":"data:image/png;base64,..."}],"redaction":[{"name":"logos","enabled":true}]}
+//! -> {"id":1,"command":"record toggle"}
+//! <- {"event":"result","id":1,"ok":true,"message":"recording-1760620800.mp4"}
+//! ```
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use tungstenite::Message;
+
+use crate::control::{ControlCommand, ControlRequest};
+
+pub const DEFAULT_PORT: u16 = 47320;
+/// How often the server threads check for new clients, state changes and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a key press waits for the render loop
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamDeckSettings {
+    /// Local port of the WebSocket server
+    pub port: u16,
+    /// Preset names, in key order
+    pub presets: Vec<String>,
+    /// Key images per preset (PNG, JPEG or SVG)
+    pub icons: HashMap<String, PathBuf>,
+}
+
+impl Default for StreamDeckSettings {
+    fn default() -> Self {
+        Self { port: DEFAULT_PORT, presets: Vec::new(), icons: HashMap::new() }
+    }
+}
+
+/// What the keys show.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamDeckState {
+    pub live: bool,
+    pub recording: bool,
+    pub preset: Option<String>,
+    /// Redaction rules and whether they're enabled
+    pub redaction: Vec<(String, bool)>,
+}
+
+/// Reads a key image as a data URL, which the Stream Deck SDK's `setImage` accepts.
+fn icon_data_url(path: &Path) -> Result<String> {
+    let mime = match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => return Err(anyhow!("Unsupported key image {:?} (expected .png, .jpg or .svg)", path)),
+    };
+    let data = std::fs::read(path).map_err(|e| anyhow!("Failed to read key image {:?}: {}", path, e))?;
+    Ok(format!("data:{};base64,{}", mime, BASE64.encode(data)))
+}
+
+fn state_message(state: &StreamDeckState, presets: &[String], icons: &HashMap<String, String>) -> String {
+    let presets: Vec<Value> = presets.iter().map(|name| json!({ "name": name, "icon": icons.get(name) })).collect();
+    let redaction: Vec<Value> = state.redaction.iter().map(|(name, enabled)| json!({ "name": name, "enabled": enabled })).collect();
+    json!({
+        "event": "state",
+        "live": state.live,
+        "recording": state.recording,
+        "preset": state.preset,
+        "presets": presets,
+        "redaction": redaction,
+    })
+    .to_string()
+}
+
+/// Parses a key press into its request id and command.
+fn parse_request(text: &str) -> Result<(Value, ControlCommand)> {
+    let request: Value = serde_json::from_str(text)?;
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let command = request.get("command").and_then(Value::as_str).ok_or_else(|| anyhow!("Missing \"command\""))?;
+    Ok((id, command.parse()?))
+}
+
+/// Turns a control reply line (`ok [message]` or `error: <message>`) into a result event.
+fn result_message(id: &Value, reply: &str) -> String {
+    let (ok, message) = match reply.strip_prefix("error: ") {
+        Some(error) => (false, error),
+        None => (true, reply.strip_prefix("ok").unwrap_or(reply).trim_start()),
+    };
+    json!({ "event": "result", "id": id, "ok": ok, "message": message }).to_string()
+}
+
+/// State shared with the client threads.
+struct Shared {
+    /// Latest state message and a version that changes with it
+    state: Mutex<(u64, String)>,
+    stop: AtomicBool,
+}
+
+/// The WebSocket server. Dropping it closes the connections and frees the port.
+pub struct StreamDeckServer {
+    settings: StreamDeckSettings,
+    icons: HashMap<String, String>,
+    request_rx: Receiver<ControlRequest>,
+    shared: Arc<Shared>,
+    last_state: Option<StreamDeckState>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl StreamDeckServer {
+    /// Starts listening on localhost.
+    pub fn start(settings: StreamDeckSettings) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", settings.port))
+            .map_err(|e| anyhow!("Stream Deck port {} unavailable: {}", settings.port, e))?;
+        listener.set_nonblocking(true)?;
+
+        let mut icons = HashMap::new();
+        for (preset, path) in &settings.icons {
+            match icon_data_url(path) {
+                Ok(icon) => {
+                    icons.insert(preset.clone(), icon);
+                }
+                Err(e) => warn!("{}", e),
+            }
+        }
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new((1, state_message(&StreamDeckState::default(), &settings.presets, &icons))),
+            stop: AtomicBool::new(false),
+        });
+        let (request_tx, request_rx) = mpsc::channel();
+        let accept_shared = shared.clone();
+        let accept_thread = thread::spawn(move || accept_loop(listener, request_tx, accept_shared));
+
+        info!("Stream Deck server listening on ws://127.0.0.1:{}", settings.port);
+        Ok(Self { settings, icons, request_rx, shared, last_state: None, accept_thread: Some(accept_thread) })
+    }
+
+    pub fn settings(&self) -> &StreamDeckSettings {
+        &self.settings
+    }
+
+    /// Returns the next pending key press, if any. Never blocks.
+    pub fn try_recv(&self) -> Option<ControlRequest> {
+        self.request_rx.try_recv().ok()
+    }
+
+    /// Sends the state to the connected plugins if it changed.
+    pub fn publish(&mut self, state: &StreamDeckState) {
+        if self.last_state.as_ref() == Some(state) {
+            return;
+        }
+        let message = state_message(state, &self.settings.presets, &self.icons);
+        if let Ok(mut shared) = self.shared.state.lock() {
+            *shared = (shared.0 + 1, message);
+        }
+        self.last_state = Some(state.clone());
+    }
+}
+
+impl Drop for StreamDeckServer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, request_tx: Sender<ControlRequest>, shared: Arc<Shared>) {
+    while !shared.stop.load(Ordering::Acquire) {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                debug!("Stream Deck plugin connected from {}", address);
+                let request_tx = request_tx.clone();
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_client(stream, &request_tx, &shared) {
+                        debug!("Stream Deck connection closed: {}", e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => warn!("Stream Deck connection failed: {}", e),
+        }
+    }
+}
+
+fn handle_client(stream: TcpStream, request_tx: &Sender<ControlRequest>, shared: &Shared) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut socket = tungstenite::accept(stream).map_err(|e| anyhow!("Handshake failed: {}", e))?;
+    // Reads time out, so state changes are sent without waiting for a key press
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let mut sent_version = 0;
+    loop {
+        if shared.stop.load(Ordering::Acquire) {
+            let _ = socket.close(None);
+            return Ok(());
+        }
+        let state = shared.state.lock().ok().filter(|state| state.0 != sent_version).map(|state| state.clone());
+        if let Some((version, message)) = state {
+            socket.send(Message::Text(message.into()))?;
+            sent_version = version;
+        }
+
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        debug!("Stream Deck: {}", text.as_str());
+        let response = match parse_request(text.as_str()) {
+            Ok((id, command)) => {
+                let (request, reply_rx) = ControlRequest::new(command);
+                request_tx.send(request).map_err(|_| anyhow!("Render loop stopped"))?;
+                let reply = reply_rx
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| "error: timed out waiting for a response".to_string());
+                result_message(&id, &reply)
+            }
+            Err(e) => result_message(&Value::Null, &format!("error: {}", e)),
+        };
+        socket.send(Message::Text(response.into()))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let (id, command) = parse_request(r#"{"id":7,"command":"switch-preset calm"}"#).unwrap();
+        assert_eq!(id, json!(7));
+        assert_eq!(command, ControlCommand::SwitchPreset("calm".to_string()));
+        assert!(parse_request(r#"{"id":7}"#).is_err());
+        assert!(parse_request(r#"{"command":"dance"}"#).is_err());
+
+        assert_eq!(result_message(&id, "ok"), r#"{"event":"result","id":7,"message":"","ok":true}"#);
+        assert_eq!(result_message(&id, "ok out.mp4"), r#"{"event":"result","id":7,"message":"out.mp4","ok":true}"#);
+        assert_eq!(result_message(&id, "error: Not recording"), r#"{"event":"result","id":7,"message":"Not recording","ok":false}"#);
+
+        let state = StreamDeckState { live: true, recording: false, preset: Some("calm".to_string()), redaction: vec![("logos".to_string(), true)] };
+        let icons = HashMap::from([("calm".to_string(), "data:image/png;base64,AA==".to_string())]);
+        let message: Value = serde_json::from_str(&state_message(&state, &["calm".to_string(), "wild".to_string()], &icons)).unwrap();
+        assert_eq!(message["preset"], "calm");
+        assert_eq!(message["presets"][0]["icon"], "data:image/png;base64,AA==");
+        assert_eq!(message["presets"][1]["icon"], Value::Null);
+        assert_eq!(message["redaction"][0], json!({ "name": "logos", "enabled": true }));
+    }
+}
@@ -0,0 +1,340 @@
+//! Multi-machine sync: follower instances mirror a leader's runtime state.
+//!
+//! The leader listens on a TCP port and sends each follower the state it would
+//! save in a session (preset, parameter values, layout, redaction rules, zoom,
+//! mixer source, tally, tempo and shader time) as soon as it connects, and
+//! then every change, so e.g. the projectors of a stage all show the same look
+//! however the leader is operated. Each message is a line of JSON in the
+//! [`Session`] format that only lists what changed; empty lines keep the
+//! connection alive.
+
+use crate::session::Session;
+use anyhow::{anyhow, Result};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+pub const DEFAULT_PORT: u16 = 47322;
+/// How often the leader checks for new followers and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Longest time without a message; the leader sends an empty line when idle
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+/// Followers reconnect when the leader has been silent this long
+const LEADER_TIMEOUT: Duration = Duration::from_secs(6);
+/// How long a follower may take to accept a message
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Wait before connecting to the leader again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+/// Followers' shader time is only moved when it's off by more than this
+const TIME_TOLERANCE: f64 = 0.05;
+
+/// The role of this instance.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncSettings {
+    /// Send the state to followers connecting to `address`:`port`
+    Leader { address: String, port: u16 },
+    /// Mirror the state of the leader at `host` or `host:port`
+    Follower { leader: String },
+}
+
+/// Decides what the leader sends.
+#[derive(Debug, Default)]
+struct Tracker {
+    /// The state as the followers have it
+    sent: Option<Session>,
+    /// When the shader time was last sent
+    time_sent: Option<Instant>,
+}
+
+impl Tracker {
+    /// The parts of `state` that changed since the last update, if any. The
+    /// shader time is only included when it jumped or its speed changed.
+    fn update(&mut self, state: &Session, now: Instant) -> Option<Session> {
+        let Some(sent) = &self.sent else {
+            self.sent = Some(state.clone());
+            self.time_sent = Some(now);
+            return Some(state.clone());
+        };
+
+        let changed = |sent: &Option<String>, state: &Option<String>| state.clone().filter(|_| sent != state);
+        let time = match (sent.time, state.time, self.time_sent) {
+            (Some(sent), Some(time), Some(at)) => {
+                let expected = sent.seconds + if sent.paused { 0.0 } else { sent.speed * now.duration_since(at).as_secs_f64() };
+                (sent.speed != time.speed || sent.paused != time.paused || (expected - time.seconds).abs() > TIME_TOLERANCE).then_some(time)
+            }
+            (_, time, _) => time,
+        };
+        let update = Session {
+            preset: changed(&sent.preset, &state.preset),
+            params: state.params.iter().filter(|(name, value)| sent.params.get(*name) != Some(*value)).map(|(name, value)| (name.clone(), *value)).collect(),
+            layout: changed(&sent.layout, &state.layout),
+            redaction: state.redaction.iter().filter(|(rule, enabled)| sent.redaction.get(*rule) != Some(*enabled)).map(|(rule, enabled)| (rule.clone(), *enabled)).collect(),
+            zoom: state.zoom.filter(|_| sent.zoom != state.zoom),
+            source: state.source.filter(|_| sent.source != state.source),
+            live: state.live,
+            bpm: state.bpm.filter(|_| sent.bpm != state.bpm),
+            time,
+        };
+        let unchanged = Session { live: sent.live, ..Session::default() };
+        if update == unchanged {
+            return None;
+        }
+
+        let time_sent = update.time.is_some();
+        self.sent = Some(Session { time: if time_sent { state.time } else { sent.time }, ..state.clone() });
+        if time_sent {
+            self.time_sent = Some(now);
+        }
+        Some(update)
+    }
+}
+
+/// Sends the state to the followers. Dropping it disconnects them and frees the port.
+pub struct SyncLeader {
+    /// Connected followers, each with its writer thread
+    followers: Vec<Sender<String>>,
+    joined_rx: Receiver<Sender<String>>,
+    tracker: Tracker,
+    last_message: Instant,
+    stop: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl SyncLeader {
+    pub fn start(address: &str, port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((address, port)).map_err(|e| anyhow!("Sync port {} unavailable: {}", port, e))?;
+        listener.set_nonblocking(true)?;
+        info!("Sync leader listening on {}:{}", address, port);
+
+        let (joined_tx, joined_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let accept_stop = stop.clone();
+        let accept_thread = thread::spawn(move || accept_loop(listener, joined_tx, accept_stop));
+        Ok(Self { followers: Vec::new(), joined_rx, tracker: Tracker::default(), last_message: Instant::now(), stop, accept_thread: Some(accept_thread) })
+    }
+
+    /// Sends what changed in `state` to the followers, and all of it to
+    /// followers that just connected. Never blocks.
+    pub fn publish(&mut self, state: &Session) {
+        let now = Instant::now();
+        let update = self.tracker.update(state, now);
+        let message = match &update {
+            Some(update) => serde_json::to_string(update).ok(),
+            None if now.duration_since(self.last_message) >= HEARTBEAT_INTERVAL => Some(String::new()),
+            None => None,
+        };
+        if let Some(message) = message {
+            self.followers.retain(|follower| follower.send(message.clone()).is_ok());
+            self.last_message = now;
+        }
+
+        for follower in self.joined_rx.try_iter().collect::<Vec<_>>() {
+            let Ok(message) = serde_json::to_string(state) else { continue };
+            if follower.send(message).is_ok() {
+                self.followers.push(follower);
+            }
+        }
+    }
+}
+
+impl Drop for SyncLeader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, joined_tx: Sender<Sender<String>>, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::Acquire) {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                let (message_tx, message_rx) = mpsc::channel::<String>();
+                if joined_tx.send(message_tx).is_err() {
+                    break;
+                }
+                info!("Sync follower {} connected", address);
+                thread::spawn(move || {
+                    if let Err(e) = write_messages(stream, message_rx) {
+                        debug!("Sync follower {}: {}", address, e);
+                    }
+                    info!("Sync follower {} disconnected", address);
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => warn!("Sync connection failed: {}", e),
+        }
+    }
+}
+
+/// Writes a follower's messages until it disconnects or the leader stops.
+fn write_messages(mut stream: TcpStream, message_rx: Receiver<String>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    for message in message_rx {
+        stream.write_all(message.as_bytes())?;
+        stream.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Receives the leader's state in the background, reconnecting as needed.
+pub struct SyncFollower {
+    update_rx: Receiver<Session>,
+    stop: Arc<AtomicBool>,
+}
+
+impl SyncFollower {
+    pub fn start(leader: &str) -> Self {
+        let (update_tx, update_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = stop.clone();
+        let leader = leader.to_string();
+        info!("Following sync leader {}", leader);
+        thread::spawn(move || {
+            let mut connected = false;
+            while !worker_stop.load(Ordering::Acquire) {
+                match follow(&leader, &update_tx, &worker_stop, &mut connected) {
+                    Ok(()) => break,
+                    Err(e) if connected => warn!("Lost sync leader {}: {}", leader, e),
+                    Err(e) => debug!("Sync leader {} not reachable: {}", leader, e),
+                }
+                connected = false;
+                thread::sleep(RECONNECT_INTERVAL);
+            }
+            debug!("Sync follower exiting");
+        });
+        Self { update_rx, stop }
+    }
+
+    /// Returns the updates received since the last call, oldest first.
+    pub fn poll(&self) -> Vec<Session> {
+        self.update_rx.try_iter().collect()
+    }
+}
+
+impl Drop for SyncFollower {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Reads the leader's messages until the connection fails, or returns `Ok`
+/// when the follower stops.
+fn follow(leader: &str, update_tx: &Sender<Session>, stop: &AtomicBool, connected: &mut bool) -> Result<()> {
+    let address = match leader.to_socket_addrs() {
+        Ok(mut addresses) => addresses.next(),
+        Err(_) => (leader, DEFAULT_PORT).to_socket_addrs()?.next(),
+    };
+    let address = address.ok_or_else(|| anyhow!("{} has no address", leader))?;
+    let stream = TcpStream::connect_timeout(&address, LEADER_TIMEOUT)?;
+    stream.set_read_timeout(Some(LEADER_TIMEOUT))?;
+    info!("Connected to sync leader {}", leader);
+    *connected = true;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("Connection closed"));
+        }
+        if stop.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Session>(&line) {
+            Ok(update) => {
+                if update_tx.send(update).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(e) => warn!("Ignoring invalid sync message from {}: {}", leader, e),
+        }
+    }
+}
+
+/// A leader or a follower.
+pub enum SyncNode {
+    Leader(Box<SyncLeader>),
+    Follower(SyncFollower),
+}
+
+impl SyncNode {
+    pub fn start(settings: &SyncSettings) -> Result<Self> {
+        Ok(match settings {
+            SyncSettings::Leader { address, port } => Self::Leader(Box::new(SyncLeader::start(address, *port)?)),
+            SyncSettings::Follower { leader } => Self::Follower(SyncFollower::start(leader)),
+        })
+    }
+
+    /// Whether the runtime state comes from the leader.
+    pub fn is_follower(&self) -> bool {
+        matches!(self, Self::Follower(_))
+    }
+
+    /// Sends `state` to the followers (leader only).
+    pub fn publish(&mut self, state: &Session) {
+        if let Self::Leader(leader) = self {
+            leader.publish(state);
+        }
+    }
+
+    /// Returns the updates from the leader to apply (follower only).
+    pub fn poll(&self) -> Vec<Session> {
+        match self {
+            Self::Leader(_) => Vec::new(),
+            Self::Follower(follower) => follower.poll(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionTime;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_tracker() {
+        let start = Instant::now();
+        let mut tracker = Tracker::default();
+        let time = |seconds| Some(SessionTime { seconds, speed: 1.0, paused: false });
+        let state = Session {
+            preset: Some("calm".to_string()),
+            params: BTreeMap::from([("intensity".to_string(), 0.25), ("hue".to_string(), 0.5)]),
+            layout: Some("single".to_string()),
+            bpm: Some(120.0),
+            time: time(10.0),
+            ..Default::default()
+        };
+        assert_eq!(tracker.update(&state, start), Some(state.clone()));
+
+        // Time running on as expected is no change
+        let later = start + Duration::from_secs(2);
+        let state = Session { time: time(12.0), ..state };
+        assert_eq!(tracker.update(&state, later), None);
+
+        // Only what changed is sent
+        let state = Session { preset: Some("wild".to_string()), params: BTreeMap::from([("intensity".to_string(), 0.9), ("hue".to_string(), 0.5)]), live: true, ..state };
+        let update = tracker.update(&state, later).unwrap();
+        assert_eq!(update, Session { preset: Some("wild".to_string()), params: BTreeMap::from([("intensity".to_string(), 0.9)]), live: true, ..Default::default() });
+
+        // Seeking and pausing are sent
+        let state = Session { time: time(30.0), ..state };
+        assert_eq!(tracker.update(&state, later).and_then(|update| update.time), time(30.0));
+        let paused = Some(SessionTime { seconds: 31.0, speed: 1.0, paused: true });
+        let state = Session { time: paused, ..state };
+        assert_eq!(tracker.update(&state, later + Duration::from_secs(1)).and_then(|update| update.time), paused);
+        assert_eq!(tracker.update(&state, later + Duration::from_secs(5)), None);
+    }
+}
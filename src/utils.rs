@@ -1,4 +1,5 @@
-use std::time::{Duration, Instant};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// A utility for tracking frames per second.
 pub struct FpsCounter {
@@ -39,3 +40,38 @@ impl Default for FpsCounter {
         Self::new()
     }
 }
+
+/// Returns a file name like `<prefix>-<unix seconds>.<extension>` in the current directory.
+pub fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    PathBuf::from(format!("{}-{}.{}", prefix, secs, extension))
+}
+
+/// Raise the scheduling priority of the current process.
+/// Must be called before worker threads are spawned so they inherit the priority.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn raise_process_priority() -> anyhow::Result<()> {
+    // Negative nice values usually require CAP_SYS_NICE (Linux) or root (macOS).
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, -10) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    tracing::info!("Raised process priority");
+    Ok(())
+}
+
+/// Raise the scheduling priority of the current process.
+#[cfg(target_os = "windows")]
+pub fn raise_process_priority() -> anyhow::Result<()> {
+    use windows::Win32::System::Threading::{GetCurrentProcess, SetPriorityClass, HIGH_PRIORITY_CLASS};
+
+    unsafe { SetPriorityClass(GetCurrentProcess(), HIGH_PRIORITY_CLASS)? };
+    tracing::info!("Raised process priority");
+    Ok(())
+}
+
+/// Raise the scheduling priority of the current process.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn raise_process_priority() -> anyhow::Result<()> {
+    anyhow::bail!("Process priority is not supported on this platform")
+}
@@ -12,6 +12,14 @@ use std::time::Duration;
 use tracing::{error, info, warn};
 use url::Url;
 
+/// Wait between attempts to resolve a stream URL again
+const RESOLVE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// yt-dlp format of YouTube videos played as textures (video only)
+const YOUTUBE_VIDEO_FORMAT: &str = "bestvideo[height<=1080][vcodec^=avc1]/bestvideo[height<=1080]/best";
+/// yt-dlp format of YouTube soundtracks
+pub(crate) const YOUTUBE_AUDIO_FORMAT: &str = "bestaudio/best";
+
 /// Supported streaming platforms
 enum StreamingPlatform {
     YouTube,
@@ -33,6 +41,55 @@ fn detect_streaming_platform(input: &str) -> Option<StreamingPlatform> {
     }
 }
 
+/// Resolves a YouTube or Twitch page URL to a URL ffmpeg can play, via yt-dlp or
+/// streamlink. Returns `None` for other inputs. The resolved URLs expire after a while.
+pub(crate) fn resolve_stream(input: &str, youtube_format: &str) -> Result<Option<std::path::PathBuf>> {
+    match detect_streaming_platform(input) {
+        Some(StreamingPlatform::YouTube) => {
+            info!("Detected YouTube URL, resolving stream via yt-dlp...");
+            let output = Command::new("yt-dlp")
+                .args(["-g", "-f", youtube_format, input])
+                .output()
+                .map_err(|e| anyhow!("Failed to run yt-dlp: {}", e))?;
+
+            if !output.status.success() {
+                return Err(anyhow!("yt-dlp failed: {}", String::from_utf8_lossy(&output.stderr)));
+            }
+
+            let url = String::from_utf8(output.stdout)?
+                .lines()
+                .next()
+                .ok_or_else(|| anyhow!("yt-dlp returned no URL"))?
+                .to_string();
+
+            info!("Resolved YouTube stream");
+            Ok(Some(std::path::PathBuf::from(url)))
+        }
+        Some(StreamingPlatform::Twitch) => {
+            info!("Detected Twitch URL, resolving stream via streamlink...");
+            let output = Command::new("streamlink")
+                .args(["--stream-url", input, "best"])
+                .output()
+                .map_err(|e| anyhow!("Failed to run streamlink: {}", e))?;
+
+            if !output.status.success() {
+                return Err(anyhow!("streamlink failed: {}", String::from_utf8_lossy(&output.stderr)));
+            }
+
+            let url = String::from_utf8(output.stdout)?
+                .lines()
+                .next()
+                .ok_or_else(|| anyhow!("streamlink returned no URL"))?
+                .trim()
+                .to_string();
+
+            info!("Resolved Twitch stream");
+            Ok(Some(std::path::PathBuf::from(url)))
+        }
+        None => Ok(None),
+    }
+}
+
 /// A video player that decodes frames using a background ffmpeg process.
 pub struct VideoPlayer {
     /// Receiver for decoded RGBA frames
@@ -51,7 +108,7 @@ pub struct VideoPlayer {
     /// Decode thread handle
     _thread: JoinHandle<()>,
     /// Signal to stop the thread
-    _stop_signal: Arc<Mutex<bool>>,
+    stop_signal: Arc<Mutex<bool>>,
     /// Frame rate (fps) - needed to timestamp frames roughly if timestamps aren't piped
     _fps: f32,
 }
@@ -72,52 +129,11 @@ impl VideoPlayer {
         info!("Opening video via ffmpeg CLI: {:?}", path);
 
         // 0. Check if input is a streaming URL and resolve it
-        let path_str = path.to_string_lossy();
-        let resolved_path = match detect_streaming_platform(&path_str) {
-            Some(StreamingPlatform::YouTube) => {
-                info!("Detected YouTube URL, resolving stream via yt-dlp...");
-                let output = Command::new("yt-dlp")
-                    .args(&["-g", "-f", "bestvideo[height<=1080][vcodec^=avc1]/bestvideo[height<=1080]/best", &path_str])
-                    .output()
-                    .map_err(|e| anyhow!("Failed to run yt-dlp: {}", e))?;
-
-                if !output.status.success() {
-                    return Err(anyhow!("yt-dlp failed: {}", String::from_utf8_lossy(&output.stderr)));
-                }
-                
-                let url = String::from_utf8(output.stdout)?
-                    .lines()
-                    .next()
-                    .ok_or_else(|| anyhow!("yt-dlp returned no URL"))?
-                    .to_string();
-                    
-                info!("Resolved YouTube stream");
-                std::path::PathBuf::from(url)
-            }
-            Some(StreamingPlatform::Twitch) => {
-                info!("Detected Twitch URL, resolving stream via streamlink...");
-                let output = Command::new("streamlink")
-                    .args(&["--stream-url", &path_str, "best"])
-                    .output()
-                    .map_err(|e| anyhow!("Failed to run streamlink: {}", e))?;
-
-                if !output.status.success() {
-                    return Err(anyhow!("streamlink failed: {}", String::from_utf8_lossy(&output.stderr)));
-                }
-                
-                let url = String::from_utf8(output.stdout)?
-                    .lines()
-                    .next()
-                    .ok_or_else(|| anyhow!("streamlink returned no URL"))?
-                    .trim()
-                    .to_string();
-                    
-                info!("Resolved Twitch stream");
-                std::path::PathBuf::from(url)
-            }
-            None => path,
-        };
-        
+        let path_str = path.to_string_lossy().to_string();
+        // Page URL of a stream, resolved again when the resolved URL stops working
+        let stream_url = detect_streaming_platform(&path_str).map(|_| path_str.clone());
+        let resolved_path = resolve_stream(&path_str, YOUTUBE_VIDEO_FORMAT)?.unwrap_or(path);
+
         // 1. Get metadata via ffprobe
         // ffprobe -v error -select_streams v:0 -show_entries stream=width,height,duration,r_frame_rate -of csv=p=0 <file>
         let output = Command::new("ffprobe")
@@ -198,7 +214,7 @@ impl VideoPlayer {
         
         let path_clone = resolved_path.clone();
         let thread = thread::spawn(move || {
-            Self::decode_loop(path_clone, width, height, fps, frame_tx, stop_signal_clone);
+            Self::decode_loop(path_clone, stream_url, width, height, fps, frame_tx, stop_signal_clone);
         });
 
         Ok(Self {
@@ -210,13 +226,13 @@ impl VideoPlayer {
              duration,
              start_time: None,
              _thread: thread,
-             _stop_signal: stop_signal,
+             stop_signal,
              _fps: fps,
         })
     }
 
-    /// Background decode loop.
-    fn decode_loop(path: std::path::PathBuf, width: u32, height: u32, fps: f32, tx: mpsc::SyncSender<DecodedFrame>, stop_signal: Arc<Mutex<bool>>) {
+    /// Background decode loop. Streams from `stream_url` get a fresh URL whenever ffmpeg stops.
+    fn decode_loop(mut path: std::path::PathBuf, stream_url: Option<String>, width: u32, height: u32, fps: f32, tx: mpsc::SyncSender<DecodedFrame>, stop_signal: Arc<Mutex<bool>>) {
         let frame_size = (width * height * 4) as usize;
         let frame_duration = if fps > 0.0 { 1.0 / fps } else { 1.0 / 30.0 };
         
@@ -243,8 +259,13 @@ impl VideoPlayer {
             }
             
             // Input and output format
+            args.extend_from_slice(&["-i", path_str]);
+            // A fresh stream URL may have a different resolution; keep the frame size
+            let size = format!("{}x{}", width, height);
+            if stream_url.is_some() {
+                args.extend_from_slice(&["-s", &size]);
+            }
             args.extend_from_slice(&[
-                "-i", path_str,
                 "-f", "image2pipe",
                 "-pix_fmt", "rgba",
                 "-vcodec", "rawvideo",
@@ -331,8 +352,32 @@ impl VideoPlayer {
                 // thread::sleep(Duration::from_secs_f32(frame_duration * 0.5));
             }
 
+            let status = child.wait();
+            let failed = frame_count == 0 || status.is_ok_and(|status| !status.success());
+
+            // Resolved stream URLs expire, so get a new one before reconnecting
+            if let Some(stream_url) = &stream_url {
+                info!("Stream stopped, resolving {} again", stream_url);
+                loop {
+                    if *stop_signal.lock().unwrap() { return; }
+                    match resolve_stream(stream_url, YOUTUBE_VIDEO_FORMAT) {
+                        Ok(Some(fresh)) => {
+                            path = fresh;
+                            break;
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            warn!("Failed to resolve stream again, retrying in {:?}: {}", RESOLVE_RETRY_INTERVAL, e);
+                            thread::sleep(RESOLVE_RETRY_INTERVAL);
+                        }
+                    }
+                }
+            } else if failed {
+                // Don't restart a broken input in a tight loop
+                thread::sleep(Duration::from_secs(1));
+            }
+
             // Loop video
-            let _ = child.wait();
             info!("Video loop restarting");
         }
     }
@@ -386,6 +431,14 @@ impl VideoPlayer {
     }
 }
 
+impl Drop for VideoPlayer {
+    fn drop(&mut self) {
+        if let Ok(mut stop) = self.stop_signal.lock() {
+            *stop = true;
+        }
+    }
+}
+
 fn parse_fps(s: &str) -> f32 {
     if let Some((num, den)) = s.split_once('/') {
         let n: f32 = num.parse().unwrap_or(0.0);
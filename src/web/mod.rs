@@ -0,0 +1,523 @@
+//! Web UI for operating an instance from a browser, e.g. on a phone.
+//!
+//! An HTTP server serves a page with a low frame rate preview of the output,
+//! sliders for the shader parameters, preset buttons and the recent log.
+//! Commands are answered like `proteus ctl`:
+//!
+//! ```text
+//! GET  /               the page
+//! GET  /api/state      {"live":false,"recording":false,"preset":"calm","presets":["calm","wild"],"params":[{"name":"intensity","value":0.5,"min":0.0,"max":1.0}],"logs":["..."]}
+//! POST /api/command    body "switch-preset wild" -> {"ok":true,"message":""}
+//! POST /api/event/<name>  body: JSON payload for Lua scripts (e.g. from a webhook) -> {"ok":true,"message":""}
+//! GET  /preview.mjpg   MJPEG stream of the output
+//! ```
+//!
+//! With a `token`, every request needs `?token=<token>`. Without one the server
+//! only listens on the loopback interface. Requests whose `Host` isn't an IP
+//! address, `localhost` or one of the configured `hosts`, or whose `Origin`
+//! is another site, are refused (against DNS rebinding and cross-site
+//! requests), and commands that name a file aren't accepted.
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::control::{ControlCommand, ControlRequest};
+use crate::events;
+use crate::frame::VideoFrame;
+
+pub const DEFAULT_PORT: u16 = 47321;
+/// How often the server threads check for new clients, preview frames and shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a command waits for the render loop
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest accepted request body
+const MAX_BODY: usize = 4096;
+/// Longest accepted request or header line
+const MAX_LINE: u64 = 8192;
+/// Most header lines accepted in a request
+const MAX_HEADERS: usize = 64;
+/// Most connections served at once; more are closed right away
+const MAX_CLIENTS: usize = 32;
+/// Preview frames are scaled down to this size
+const PREVIEW_SIZE: u32 = 640;
+const PREVIEW_QUALITY: u8 = 70;
+/// Log lines kept for the page
+const LOG_LINES: usize = 200;
+
+const INDEX_HTML: &str = include_str!("index.html");
+
+static LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Log writer that keeps the recent lines for the page, for use as a
+/// `tracing_subscriber` writer: `.with_writer(|| LogWriter)`.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Ok(mut logs) = LOGS.lock() {
+            for line in String::from_utf8_lossy(buf).lines().filter(|line| !line.trim().is_empty()) {
+                if logs.len() == LOG_LINES {
+                    logs.pop_front();
+                }
+                logs.push_back(line.to_string());
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn recent_logs() -> Vec<String> {
+    LOGS.lock().map(|logs| logs.iter().cloned().collect()).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSettings {
+    /// Address to listen on (`0.0.0.0` for other devices on the network, which requires a token)
+    pub address: String,
+    pub port: u16,
+    /// Required as `?token=` on every request, if set
+    pub token: Option<String>,
+    /// Host names the page may be opened by besides IP addresses and `localhost`
+    pub hosts: Vec<String>,
+    /// Preview frames per second
+    pub preview_fps: f32,
+    /// Slider ranges per parameter (default 0 - 1)
+    pub ranges: HashMap<String, (f32, f32)>,
+}
+
+impl Default for WebSettings {
+    fn default() -> Self {
+        Self { address: "127.0.0.1".to_string(), port: DEFAULT_PORT, token: None, hosts: Vec::new(), preview_fps: 5.0, ranges: HashMap::new() }
+    }
+}
+
+/// What the page shows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebState {
+    pub live: bool,
+    pub recording: bool,
+    pub preset: Option<String>,
+    pub presets: Vec<String>,
+    /// Parameters the shaders declare, with their current values
+    pub params: Vec<(String, f32)>,
+}
+
+fn state_json(state: &WebState, ranges: &HashMap<String, (f32, f32)>, logs: Vec<String>) -> Value {
+    let params: Vec<Value> = state
+        .params
+        .iter()
+        .map(|(name, value)| {
+            // The slider always covers the current value
+            let (min, max) = ranges.get(name).copied().unwrap_or((0.0, 1.0));
+            json!({ "name": name, "value": value, "min": min.min(*value), "max": max.max(*value) })
+        })
+        .collect();
+    json!({
+        "live": state.live,
+        "recording": state.recording,
+        "preset": state.preset,
+        "presets": state.presets,
+        "params": params,
+        "logs": logs,
+    })
+}
+
+/// Turns a control reply line (`ok [message]` or `error: <message>`) into a result.
+fn result_json(reply: &str) -> Value {
+    let (ok, message) = match reply.strip_prefix("error: ") {
+        Some(error) => (false, error),
+        None => (true, reply.strip_prefix("ok").unwrap_or(reply).trim_start()),
+    };
+    json!({ "ok": ok, "message": message })
+}
+
+/// An HTTP request, as far as the server needs it.
+#[derive(Debug, PartialEq)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    /// `Host` header, if any
+    host: Option<String>,
+    /// `Origin` header, if any
+    origin: Option<String>,
+    body: String,
+}
+
+/// Reads one line of at most [`MAX_LINE`] bytes into `line` (cleared first).
+/// Returns false at the end of the stream.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<bool> {
+    line.clear();
+    let read = reader.take(MAX_LINE).read_line(line)?;
+    if read as u64 == MAX_LINE && !line.ends_with('\n') {
+        bail!("Request line too long");
+    }
+    Ok(read > 0)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<HttpRequest> {
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else { bail!("Invalid request line {:?}", line.trim()) };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut length = 0;
+    let (mut host, mut origin) = (None, None);
+    let mut headers = 0;
+    loop {
+        if !read_line(reader, &mut line)? || line.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            bail!("Too many request headers");
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().map_err(|_| anyhow!("Invalid Content-Length"))?;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_string());
+            }
+        }
+    }
+    if length > MAX_BODY {
+        bail!("Request body too large ({} bytes)", length);
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(HttpRequest { method, path, query, host, origin, body: String::from_utf8(body)? })
+}
+
+/// Compares a given token with the expected one in constant time (for tokens of the same length).
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// The host name of a `Host` header or an origin's authority, without the port.
+fn host_name(authority: &str) -> &str {
+    match authority.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or(ipv6),
+        None => authority.rsplit_once(':').map_or(authority, |(host, _)| host),
+    }
+}
+
+/// Whether a request comes from the page itself, opened by an IP address,
+/// `localhost` or an allowed host name: other sites may neither send commands
+/// (cross-site requests) nor read the state (DNS rebinding).
+fn same_origin(request: &HttpRequest, hosts: &[String]) -> bool {
+    let Some(host) = &request.host else { return false };
+    let name = host_name(host);
+    let allowed = name.eq_ignore_ascii_case("localhost") || name.parse::<std::net::IpAddr>().is_ok() || hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(name));
+    // Browsers send the origin with every cross-site POST; tools like curl don't send one
+    let origin_matches = request.origin.as_ref().is_none_or(|origin| {
+        origin.split_once("://").is_some_and(|(_, authority)| authority.eq_ignore_ascii_case(host))
+    });
+    allowed && origin_matches
+}
+
+/// Whether the listen address only accepts connections from this machine.
+fn is_loopback(address: &str) -> bool {
+    address.eq_ignore_ascii_case("localhost") || address.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+    stream.write_all(body)?;
+    Ok(())
+}
+
+/// Scales an output frame down and encodes it as JPEG.
+fn encode_preview(frame: &VideoFrame) -> Result<Vec<u8>> {
+    let rgba = frame.scale_to_fit(PREVIEW_SIZE);
+    let image = image::RgbaImage::from_raw(rgba.width, rgba.height, rgba.data).ok_or_else(|| anyhow!("Invalid preview frame"))?;
+    let rgb = image::DynamicImage::ImageRgba8(image).to_rgb8();
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, PREVIEW_QUALITY).encode_image(&rgb)?;
+    Ok(jpeg)
+}
+
+/// State shared with the client threads.
+struct Shared {
+    settings: WebSettings,
+    state: Mutex<WebState>,
+    /// Latest preview frame and a version that changes with it
+    preview: Mutex<(u64, Option<Arc<VideoFrame>>)>,
+    preview_clients: AtomicUsize,
+    /// Open connections
+    clients: AtomicUsize,
+    stop: AtomicBool,
+}
+
+/// Counts a connection or preview stream while it's open.
+struct Counted<'a>(&'a AtomicUsize);
+
+impl<'a> Counted<'a> {
+    fn new(clients: &'a AtomicUsize) -> Self {
+        clients.fetch_add(1, Ordering::AcqRel);
+        Self(clients)
+    }
+}
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The HTTP server. Dropping it closes the connections and frees the port.
+pub struct WebServer {
+    request_rx: Receiver<ControlRequest>,
+    shared: Arc<Shared>,
+    last_state: Option<WebState>,
+    next_preview: Instant,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl WebServer {
+    pub fn start(settings: WebSettings) -> Result<Self> {
+        if settings.token.is_none() && !is_loopback(&settings.address) {
+            bail!("Web UI on {} needs a `token` (or `address: 127.0.0.1` for this machine only)", settings.address);
+        }
+        let listener = TcpListener::bind((settings.address.as_str(), settings.port))
+            .map_err(|e| anyhow!("Web UI port {} unavailable: {}", settings.port, e))?;
+        listener.set_nonblocking(true)?;
+        info!("Web UI listening on http://{}:{}", settings.address, settings.port);
+
+        let shared = Arc::new(Shared {
+            settings,
+            state: Mutex::new(WebState::default()),
+            preview: Mutex::new((0, None)),
+            preview_clients: AtomicUsize::new(0),
+            clients: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+        });
+        let (request_tx, request_rx) = mpsc::channel();
+        let accept_shared = shared.clone();
+        let accept_thread = thread::spawn(move || accept_loop(listener, request_tx, accept_shared));
+        Ok(Self { request_rx, shared, last_state: None, next_preview: Instant::now(), accept_thread: Some(accept_thread) })
+    }
+
+    pub fn settings(&self) -> &WebSettings {
+        &self.shared.settings
+    }
+
+    /// Returns the next pending command from the page, if any. Never blocks.
+    pub fn try_recv(&self) -> Option<ControlRequest> {
+        self.request_rx.try_recv().ok()
+    }
+
+    /// Updates the state the page shows.
+    pub fn publish(&mut self, state: &WebState) {
+        if self.last_state.as_ref() == Some(state) {
+            return;
+        }
+        if let Ok(mut shared) = self.shared.state.lock() {
+            *shared = state.clone();
+        }
+        self.last_state = Some(state.clone());
+    }
+
+    /// Whether a preview is being watched and the next frame is due. Frames
+    /// are read back from the GPU only then.
+    pub fn wants_preview(&mut self, now: Instant) -> bool {
+        if self.shared.preview_clients.load(Ordering::Acquire) == 0 || now < self.next_preview {
+            return false;
+        }
+        self.next_preview = now + Duration::from_secs_f32(1.0 / self.shared.settings.preview_fps.max(0.1));
+        true
+    }
+
+    /// Sends an output frame to the preview streams, which scale and encode it.
+    pub fn publish_preview(&self, frame: &VideoFrame) {
+        if let Ok(mut preview) = self.shared.preview.lock() {
+            *preview = (preview.0 + 1, Some(Arc::new(frame.clone())));
+        }
+    }
+}
+
+impl Drop for WebServer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, request_tx: Sender<ControlRequest>, shared: Arc<Shared>) {
+    while !shared.stop.load(Ordering::Acquire) {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                if shared.clients.load(Ordering::Acquire) >= MAX_CLIENTS {
+                    debug!("Web UI busy, closing connection from {}", address);
+                    continue;
+                }
+                let request_tx = request_tx.clone();
+                let shared = shared.clone();
+                shared.clients.fetch_add(1, Ordering::AcqRel);
+                thread::spawn(move || {
+                    let _client = Counted(&shared.clients);
+                    if let Err(e) = handle_client(stream, &request_tx, &shared) {
+                        debug!("Web UI request from {} failed: {}", address, e);
+                    }
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => warn!("Web UI connection failed: {}", e),
+        }
+    }
+}
+
+fn handle_client(mut stream: TcpStream, request_tx: &Sender<ControlRequest>, shared: &Shared) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = read_request(&mut BufReader::new(stream.try_clone()?))?;
+    if shared.settings.token.as_ref().is_some_and(|token| !request.query.get("token").is_some_and(|given| tokens_match(given, token))) {
+        return respond(&mut stream, "403 Forbidden", "text/plain", b"Missing or wrong token");
+    }
+    if !same_origin(&request, &shared.settings.hosts) {
+        debug!("Web UI: refused request for host {:?} from origin {:?}", request.host, request.origin);
+        return respond(&mut stream, "403 Forbidden", "text/plain", b"Cross-site request refused");
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => respond(&mut stream, "200 OK", "text/html; charset=utf-8", INDEX_HTML.as_bytes()),
+        ("GET", "/api/state") => {
+            let state = shared.state.lock().map(|state| state.clone()).unwrap_or_default();
+            let body = state_json(&state, &shared.settings.ranges, recent_logs()).to_string();
+            respond(&mut stream, "200 OK", "application/json", body.as_bytes())
+        }
+        ("POST", "/api/command") => {
+            debug!("Web UI: {}", request.body.trim());
+            let reply = match request.body.parse::<ControlCommand>() {
+                // Files are only named locally (`proteus ctl`)
+                Ok(command) if command.path().is_some() => "error: Commands with a file path aren't accepted from the web UI".to_string(),
+                Ok(command) => run_command(request_tx, command)?,
+                Err(e) => format!("error: {}", e),
+            };
+            respond(&mut stream, "200 OK", "application/json", result_json(&reply).to_string().as_bytes())
+        }
+        ("POST", path) if path.len() > "/api/event/".len() && path.starts_with("/api/event/") => {
+            let name = path["/api/event/".len()..].to_string();
+            debug!("Web UI: event {} {}", name, request.body.trim());
+            let reply = run_command(request_tx, ControlCommand::Event { name, payload: events::parse_payload(&request.body) })?;
+            respond(&mut stream, "200 OK", "application/json", result_json(&reply).to_string().as_bytes())
+        }
+        ("GET", "/preview.mjpg") => stream_preview(stream, shared),
+        _ => respond(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+    }
+}
+
+/// Hands a command to the render loop and waits for the reply line.
+fn run_command(request_tx: &Sender<ControlRequest>, command: ControlCommand) -> Result<String> {
+    let (request, reply_rx) = ControlRequest::new(command);
+    request_tx.send(request).map_err(|_| anyhow!("Render loop stopped"))?;
+    Ok(reply_rx.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(|_| "error: timed out waiting for a response".to_string()))
+}
+
+fn stream_preview(mut stream: TcpStream, shared: &Shared) -> Result<()> {
+    let _client = Counted::new(&shared.preview_clients);
+    write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=frame\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n")?;
+    let mut sent_version = 0;
+    while !shared.stop.load(Ordering::Acquire) {
+        let frame = shared.preview.lock().ok().filter(|preview| preview.0 != sent_version).and_then(|preview| Some((preview.0, preview.1.clone()?)));
+        let Some((version, frame)) = frame else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+        sent_version = version;
+        let jpeg = encode_preview(&frame)?;
+        write!(stream, "--frame\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", jpeg.len())?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let raw = "POST /api/command?token=s%20cret HTTP/1.1\r\nHost: proteus\r\ncontent-length: 15\r\n\r\nset-live on\nxyz";
+        let request = read_request(&mut std::io::Cursor::new(raw)).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/command");
+        assert_eq!(request.query.get("token").map(String::as_str), Some("s cret"));
+        assert_eq!(request.body, "set-live on\nxyz");
+
+        let request = read_request(&mut std::io::Cursor::new("GET / HTTP/1.1\r\n\r\n")).unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str(), request.body.as_str()), ("GET", "/", ""));
+        assert!(read_request(&mut std::io::Cursor::new("\r\n")).is_err());
+        assert!(read_request(&mut std::io::Cursor::new("POST / HTTP/1.1\r\nContent-Length: 99999\r\n\r\n")).is_err());
+
+        // Endless lines and header floods are cut off
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE as usize));
+        assert!(read_request(&mut std::io::Cursor::new(long)).is_err());
+        let flood = format!("GET / HTTP/1.1\r\n{}\r\n", "X-Filler: 1\r\n".repeat(MAX_HEADERS + 1));
+        assert!(read_request(&mut std::io::Cursor::new(flood)).is_err());
+
+        assert!(tokens_match("s cret", "s cret"));
+        assert!(!tokens_match("s cres", "s cret") && !tokens_match("s cre", "s cret"));
+    }
+
+    #[test]
+    fn test_same_origin() {
+        let request = |host: &str, origin: Option<&str>| {
+            let origin = origin.map(|origin| format!("Origin: {}\r\n", origin)).unwrap_or_default();
+            let raw = format!("POST /api/command HTTP/1.1\r\nHost: {}\r\n{}\r\n", host, origin);
+            read_request(&mut std::io::Cursor::new(raw)).unwrap()
+        };
+        let hosts = vec!["studio.local".to_string()];
+        assert!(same_origin(&request("192.168.1.5:47321", Some("http://192.168.1.5:47321")), &hosts));
+        assert!(same_origin(&request("[::1]:47321", None), &hosts));
+        assert!(same_origin(&request("Studio.local:47321", Some("http://studio.local:47321")), &hosts));
+        // Another site posting to the page, and a rebound DNS name
+        assert!(!same_origin(&request("127.0.0.1:47321", Some("https://example.com")), &hosts));
+        assert!(!same_origin(&request("attacker.example:47321", Some("http://attacker.example:47321")), &hosts));
+
+        assert!(is_loopback("127.0.0.1") && is_loopback("::1") && !is_loopback("0.0.0.0"));
+    }
+
+    #[test]
+    fn test_state_json() {
+        let state = WebState {
+            preset: Some("calm".to_string()),
+            presets: vec!["calm".to_string()],
+            params: vec![("intensity".to_string(), 0.5), ("speed".to_string(), 3.0), ("zoom".to_string(), 1.5)],
+            ..Default::default()
+        };
+        let ranges = HashMap::from([("zoom".to_string(), (1.0, 4.0))]);
+        let json = state_json(&state, &ranges, vec!["started".to_string()]);
+        assert_eq!(json["params"][0], json!({ "name": "intensity", "value": 0.5, "min": 0.0, "max": 1.0 }));
+        assert_eq!(json["params"][1]["max"], 3.0);
+        assert_eq!(json["params"][2]["min"], 1.0);
+        assert_eq!(json["preset"], "calm");
+        assert_eq!(json["logs"][0], "started");
+
+        assert_eq!(result_json("ok"), json!({ "ok": true, "message": "" }));
+        assert_eq!(result_json("error: Unknown preset 'x'"), json!({ "ok": false, "message": "Unknown preset 'x'" }));
+    }
+}